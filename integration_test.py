@@ -1,4 +1,5 @@
 import glob
+import json
 import os
 import random
 import subprocess
@@ -95,6 +96,113 @@ def test_one(path):
     # os.system(f"rm -rf {path}/*.bin")
 
 
+def test_oracle(path):
+    """
+    Run the fuzzer on a contract vulnerable to a single class of bugs and check
+    that the oracle described in `{path}/oracle.json` reports it within the
+    given number of executions
+    """
+    global crashed_any
+    print(path)
+    with open(f"{path}/oracle.json", "r") as file:
+        spec = json.load(file)
+
+    if "skip" in spec:
+        print(f"=== Skipped: {path}, {spec['skip']}")
+        return True, path
+
+    os.system(f"rm -rf {path}/build {path}/work_dir")
+
+    # compile with solc
+    p = subprocess.run(
+        " ".join(
+            [
+                "solc",
+                f"{path}/*.sol",
+                "-o",
+                f"{path}/",
+                "--bin",
+                "--abi",
+                "--overwrite",
+                "--base-path",
+                ".",
+                "--combined-json",
+                "bin-runtime,srcmap-runtime",
+            ]
+        ),
+        shell=True,
+        stdout=subprocess.PIPE,
+        stderr=subprocess.PIPE,
+    )
+
+    if b"Error" in p.stderr or b"Error" in p.stdout:
+        print(f"Error compiling {path}")
+        crashed_any = True
+        return False, path
+
+    start_time = time.time()
+    # the contracts are deployed by the setUp() of the deployment script if
+    # any, e.g., to fund them with cheatcodes
+    if "deployment_script" in spec:
+        target = ["-m", spec["deployment_script"]]
+        build = ["--", "solc", f"{path}/*.sol"]
+    else:
+        target = ["-t", f"'{path}/*'"]
+        build = []
+    cmd = (
+        [
+            TIMEOUT_BIN,
+            "5m",
+            "./target/release/ityfuzz",
+            "evm",
+        ]
+        + target
+        + [
+            "-d",
+            spec["detectors"],
+            "--max-execs",
+            str(spec["max_execs"]),
+            "--work-dir",
+            f"{path}/work_dir",
+        ]
+        + spec.get("args", [])
+        + build
+    )
+
+    print(" ".join(cmd))
+
+    p = subprocess.run(
+        " ".join(cmd), stdout=subprocess.PIPE, stderr=subprocess.PIPE, shell=True
+    )
+
+    bug_types = []
+    if os.path.exists(f"{path}/work_dir/vuln_info.jsonl"):
+        with open(f"{path}/work_dir/vuln_info.jsonl", "r") as file:
+            bug_types = [
+                json.loads(line)["bug_type"] for line in file if line.strip()
+            ]
+
+    if spec["bug_type"] not in bug_types:
+        print("================ STDERR =================")
+        print(p.stderr.decode("utf-8"))
+        print("================ STDOUT =================")
+        print(p.stdout.decode("utf-8"))
+        print(
+            f"=== Failed: {path}, {spec['bug_type']} not found in {spec['max_execs']} executions (found {bug_types})"
+        )
+        if b"panicked" in p.stderr or b"panicked" in p.stdout:
+            crashed_any = True
+        return False, path
+
+    # the fuzzer stops at the first bug, saving the campaign on its way out
+    if not os.path.exists(f"{path}/work_dir/campaign.cbor"):
+        print(f"=== Failed: {path}, the campaign was not saved on exit")
+        return False, path
+
+    print(f"=== Success: {path}, Finished in {time.time() - start_time}s")
+    return True, path
+
+
 def test_onchain(test):
     global crashed_any
     if len(test) != 4:
@@ -217,8 +325,10 @@ if __name__ == "__main__":
             actions.append("onchain")
         elif sys.argv[1] == "offchain":
             actions.append("offchain")
+        elif sys.argv[1] == "oracles":
+            actions.append("oracles")
     else:
-        actions = ["onchain", "offchain"]
+        actions = ["onchain", "offchain", "oracles"]
 
     if "offchain" in actions:
         build_fuzzer()
@@ -231,6 +341,20 @@ if __name__ == "__main__":
                 print(f[1])
             exit(1)
 
+    if "oracles" in actions:
+        build_fuzzer()
+        with multiprocessing.Pool(3) as p:
+            results = p.map(
+                test_oracle,
+                [os.path.dirname(spec) for spec in glob.glob("./tests/oracles/*/oracle.json")],
+            )
+        failed = [result for result in results if result and not result[0]]
+        if failed:
+            print("❌ Failed oracle tests:")
+            for f in failed:
+                print(f[1])
+            exit(1)
+
     if "onchain" in actions:
         build_flash_loan_v2_fuzzer()
         tests = read_onchain_tests()
@@ -0,0 +1,53 @@
+//! ABI of the ityfuzz oracle plugins, shared by ityfuzz loading them and the
+//! plugins, so that a change of the interface breaks the build of both sides
+//! instead of the plugins at runtime.
+//!
+//! A plugin exports, with the C ABI:
+//! - `ityfuzz_oracle_abi_version() -> u32`, returning
+//!   [`ORACLE_PLUGIN_ABI_VERSION`]
+//! - `ityfuzz_oracle_name() -> *const c_char`, the NUL-terminated name of the
+//!   oracle
+//! - `ityfuzz_oracle_check(ctx: *const PluginContext)`, called after each
+//!   execution with the post-state, reporting the bugs with `ctx.report`
+
+use std::ffi::{c_char, c_void};
+
+/// Version of the plugin interface, bumped on every breaking change of
+/// [`PluginContext`] or of the exported functions
+pub const ORACLE_PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Log emitted during the execution
+#[repr(C)]
+pub struct PluginLog {
+    pub address: [u8; 20],
+    pub topics: *const [u8; 32],
+    pub topics_len: usize,
+    pub data: *const u8,
+    pub data_len: usize,
+}
+
+/// Post-state of an execution given to `ityfuzz_oracle_check`. The words are
+/// big endian
+#[repr(C)]
+pub struct PluginContext {
+    /// Passed back to `sload` and `balance`
+    pub state: *const c_void,
+    /// Contract called by the transaction
+    pub contract: [u8; 20],
+    pub logs: *const PluginLog,
+    pub logs_len: usize,
+    /// Storage slot of a contract, false if it is not loaded
+    pub sload: extern "C" fn(
+        state: *const c_void,
+        address: *const [u8; 20],
+        slot: *const [u8; 32],
+        value: *mut [u8; 32],
+    ) -> bool,
+    /// Balance of an address, false if it is not loaded
+    pub balance: extern "C" fn(state: *const c_void, address: *const [u8; 20], value: *mut [u8; 32]) -> bool,
+    /// Passed back to `report`
+    pub reports: *mut c_void,
+    /// Report a bug, `id` telling apart the bugs of the plugin and `message`
+    /// being a NUL-terminated UTF-8 string
+    pub report: extern "C" fn(reports: *mut c_void, id: u64, message: *const c_char),
+}
@@ -0,0 +1,50 @@
+//! Template of an ityfuzz oracle plugin, reporting a bug when the called
+//! contract emits `OwnershipTransferred(address,address)`. The storage and
+//! the balances of the post-state are read with `ctx.sload` and
+//! `ctx.balance`.
+
+use std::ffi::c_char;
+
+use ityfuzz_oracle_abi::{PluginContext, ORACLE_PLUGIN_ABI_VERSION};
+
+/// `keccak256("OwnershipTransferred(address,address)")`
+const OWNERSHIP_TRANSFERRED: [u8; 32] = [
+    0x8b, 0xe0, 0x07, 0x9c, 0x53, 0x16, 0x59, 0x14, 0x13, 0x44, 0xcd, 0x1f, 0xd0, 0xa4, 0xf2, 0x84, 0x19, 0x49, 0x7f,
+    0x97, 0x22, 0xa3, 0xda, 0xaf, 0xe3, 0xb4, 0x18, 0x6f, 0x6b, 0x64, 0x57, 0xe0,
+];
+
+#[no_mangle]
+pub extern "C" fn ityfuzz_oracle_abi_version() -> u32 {
+    ORACLE_PLUGIN_ABI_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn ityfuzz_oracle_name() -> *const c_char {
+    b"ownership\0".as_ptr() as *const c_char
+}
+
+/// # Safety
+///
+/// `ctx` is a valid context given by ityfuzz
+#[no_mangle]
+pub unsafe extern "C" fn ityfuzz_oracle_check(ctx: *const PluginContext) {
+    let ctx = &*ctx;
+    let logs = if ctx.logs_len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ctx.logs, ctx.logs_len)
+    };
+    for log in logs {
+        if log.address != ctx.contract || log.topics_len == 0 {
+            continue;
+        }
+        if *log.topics == OWNERSHIP_TRANSFERRED {
+            (ctx.report)(
+                ctx.reports,
+                0,
+                b"the ownership of the contract is transferred\0".as_ptr() as *const c_char,
+            );
+            break;
+        }
+    }
+}
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.0;
+
+// Mock tokens with non-standard behaviors, for testing token integrations
+// offchain (reentrant hooks, balance drift, ...) without forking mainnet.
+//
+// Import this file into the project, or reference the contracts in the
+// offchain config (`--offchain-config-file`) after building it with the
+// project, e.g.:
+//
+// {
+//   "solidity_utils/mock_tokens.sol": {
+//     "FeeOnTransferToken": {
+//       "constructor_args": "0x<abi encoded (uint256 supply, uint256 fee_bps)>",
+//       "address": "0x..."
+//     }
+//   }
+// }
+//
+// The whole supply is minted to the deployer.
+
+contract MockERC20 {
+    string public name;
+    string public symbol;
+    uint8 public constant decimals = 18;
+    uint256 internal _totalSupply;
+
+    mapping(address => uint256) internal _balances;
+    mapping(address => mapping(address => uint256)) public allowance;
+
+    event Transfer(address indexed from, address indexed to, uint256 value);
+    event Approval(address indexed owner, address indexed spender, uint256 value);
+
+    constructor(string memory _name, string memory _symbol, uint256 supply) {
+        name = _name;
+        symbol = _symbol;
+        _mint(msg.sender, supply);
+    }
+
+    function totalSupply() public view virtual returns (uint256) {
+        return _totalSupply;
+    }
+
+    function balanceOf(address account) public view virtual returns (uint256) {
+        return _balances[account];
+    }
+
+    function approve(address spender, uint256 amount) public returns (bool) {
+        allowance[msg.sender][spender] = amount;
+        emit Approval(msg.sender, spender, amount);
+        return true;
+    }
+
+    function transfer(address to, uint256 amount) public returns (bool) {
+        _transfer(msg.sender, to, amount);
+        return true;
+    }
+
+    function transferFrom(address from, address to, uint256 amount) public returns (bool) {
+        uint256 allowed = allowance[from][msg.sender];
+        if (allowed != type(uint256).max) {
+            require(allowed >= amount, "insufficient allowance");
+            allowance[from][msg.sender] = allowed - amount;
+        }
+        _transfer(from, to, amount);
+        return true;
+    }
+
+    function _mint(address to, uint256 amount) internal virtual {
+        _totalSupply += amount;
+        _balances[to] += amount;
+        emit Transfer(address(0), to, amount);
+    }
+
+    function _move(address from, address to, uint256 amount) internal virtual {
+        require(_balances[from] >= amount, "insufficient balance");
+        _balances[from] -= amount;
+        _balances[to] += amount;
+        emit Transfer(from, to, amount);
+    }
+
+    function _transfer(address from, address to, uint256 amount) internal virtual {
+        _move(from, to, amount);
+    }
+}
+
+interface IERC777Recipient {
+    function tokensReceived(
+        address operator,
+        address from,
+        address to,
+        uint256 amount,
+        bytes calldata userData,
+        bytes calldata operatorData
+    ) external;
+}
+
+interface IERC777Sender {
+    function tokensToSend(
+        address operator,
+        address from,
+        address to,
+        uint256 amount,
+        bytes calldata userData,
+        bytes calldata operatorData
+    ) external;
+}
+
+// ERC-777 style token calling the hooks of the sender before and of the
+// recipient after each transfer. Instead of the ERC-1820 registry, hooks of
+// all contracts are called and failing hooks are ignored, so that any
+// contract (including the attacker) can reenter.
+contract MockERC777 is MockERC20 {
+    constructor(uint256 supply) MockERC20("Mock ERC777", "M777", supply) {}
+
+    function send(address to, uint256 amount, bytes calldata data) public {
+        _transfer(msg.sender, to, amount);
+        data;
+    }
+
+    function _transfer(address from, address to, uint256 amount) internal override {
+        if (from.code.length > 0) {
+            try IERC777Sender(from).tokensToSend(msg.sender, from, to, amount, "", "") {} catch {}
+        }
+        _move(from, to, amount);
+        if (to.code.length > 0) {
+            try IERC777Recipient(to).tokensReceived(msg.sender, from, to, amount, "", "") {} catch {}
+        }
+    }
+}
+
+// Token burning a fee on each transfer, so the recipient gets less than the
+// amount transferred
+contract FeeOnTransferToken is MockERC20 {
+    uint256 public feeBps;
+
+    constructor(uint256 supply, uint256 _feeBps) MockERC20("Mock Fee Token", "MFEE", supply) {
+        require(_feeBps <= 10000, "fee too high");
+        feeBps = _feeBps;
+    }
+
+    function _transfer(address from, address to, uint256 amount) internal override {
+        uint256 fee = (amount * feeBps) / 10000;
+        _move(from, to, amount);
+        _balances[to] -= fee;
+        _totalSupply -= fee;
+        emit Transfer(to, address(0), fee);
+    }
+}
+
+// Token whose balances are shares of the supply, which anyone can rebase, so
+// balances change without any transfer
+contract RebasingToken is MockERC20 {
+    // shares of each account are kept in _balances
+    uint256 internal _totalShares;
+
+    constructor(uint256 supply) MockERC20("Mock Rebasing Token", "MREB", supply) {
+        _totalShares = supply;
+    }
+
+    function totalSupply() public view override returns (uint256) {
+        return _totalSupply;
+    }
+
+    function balanceOf(address account) public view override returns (uint256) {
+        if (_totalShares == 0) {
+            return 0;
+        }
+        return (_balances[account] * _totalSupply) / _totalShares;
+    }
+
+    // scales all balances by `bps` / 10000
+    function rebase(uint256 bps) public {
+        require(bps > 0 && bps <= 20000, "invalid rebase");
+        _totalSupply = (_totalSupply * bps) / 10000;
+    }
+
+    function _transfer(address from, address to, uint256 amount) internal override {
+        require(_totalSupply > 0, "no supply");
+        uint256 shares = (amount * _totalShares) / _totalSupply;
+        require(_balances[from] >= shares, "insufficient balance");
+        _balances[from] -= shares;
+        _balances[to] += shares;
+        emit Transfer(from, to, amount);
+    }
+}
+
+// Token whose owner can blacklist accounts, transfers from or to them revert
+contract BlacklistToken is MockERC20 {
+    address public owner;
+    mapping(address => bool) public isBlacklisted;
+
+    constructor(uint256 supply) MockERC20("Mock Blacklist Token", "MBLK", supply) {
+        owner = msg.sender;
+    }
+
+    function setBlacklisted(address account, bool blacklisted) public {
+        require(msg.sender == owner, "not owner");
+        isBlacklisted[account] = blacklisted;
+    }
+
+    function _transfer(address from, address to, uint256 amount) internal override {
+        require(!isBlacklisted[from] && !isBlacklisted[to], "blacklisted");
+        _move(from, to, amount);
+    }
+}
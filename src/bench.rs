@@ -0,0 +1,437 @@
+//! Benchmark of the time to find known bugs, to evaluate scheduler and
+//! mutator changes reproducibly
+//!
+//! `ityfuzz bench` fuzzes each of the bundled vulnerable contracts (under
+//! `tests/oracles`) whose `oracle.json` has a `bench` entry, with a fixed
+//! seed, in its own process, and fails when a bug is not found within the
+//! time bound of its case. The contracts are built in a temporary directory
+//! and the results are written to `<work_dir>/bench.json`.
+//!
+//! `ityfuzz bench --post-state-oracles` instead times the evaluation of the
+//! post-state oracles one after another and concurrently.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Instant,
+};
+
+use alloy_dyn_abi::DynSolValue;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    evm::{
+        host::EmittedLog,
+        oracles::{
+            erc20::FundLossOracle,
+            event::{parse_event_properties, EventOracle},
+            post_state::{PostStateOracle, PostStateOracles},
+            reentrancy::ReentrancyOracle,
+            OracleThresholds,
+        },
+        types::{EVMAddress, EVMU256, EVMU512},
+        vm::EVMState,
+    },
+    r#const::PARALLEL_ORACLE_THRESHOLD,
+    report::Report,
+};
+
+/// CLI for benchmarking the fuzzer on the bundled vulnerable contracts
+#[derive(Parser, Debug)]
+pub struct BenchArgs {
+    /// Work dir of the benchmark, holding a work dir per case
+    #[arg(long, short, default_value = "bench_work_dir")]
+    work_dir: String,
+
+    /// Dir of the oracle tests the cases are read from
+    #[arg(long, default_value = "tests/oracles")]
+    oracles_dir: String,
+
+    /// Names of the cases to run, separated by comma (Default: all)
+    #[arg(long, default_value = "")]
+    cases: String,
+
+    /// Multiplier of the time bounds, for slower machines
+    #[arg(long, default_value = "1.0")]
+    time_scale: f64,
+
+    /// Time the post-state oracles instead of fuzzing the cases
+    #[arg(long, default_value = "false")]
+    post_state_oracles: bool,
+}
+
+/// `bench` entry of an `oracle.json`
+#[derive(Debug, Clone, Deserialize)]
+struct BenchSpec {
+    seed: u64,
+    /// Maximum time to find the bug, in seconds
+    max_secs: u64,
+}
+
+/// `oracle.json` of an oracle test, see `tests/oracles/README.md`
+#[derive(Debug, Clone, Deserialize)]
+struct OracleSpec {
+    detectors: String,
+    bug_type: String,
+    #[serde(default)]
+    args: Vec<String>,
+    deployment_script: Option<String>,
+    skip: Option<String>,
+    bench: Option<BenchSpec>,
+}
+
+/// A vulnerable contract and the bug the fuzzer has to find
+#[derive(Debug, Clone)]
+pub struct BenchCase {
+    /// Name of the dir of the oracle test
+    pub name: String,
+    /// Dir of the Solidity sources
+    pub path: PathBuf,
+    pub detectors: String,
+    /// Bug type reported in `vuln_info.jsonl`
+    pub bug_type: String,
+    /// Extra arguments passed to the fuzzer
+    pub args: Vec<String>,
+    /// `file:contract` whose `setUp()` deploys the contracts, if any
+    pub deployment_script: Option<String>,
+    pub seed: u64,
+    /// Maximum time to find the bug, in seconds
+    pub max_secs: u64,
+}
+
+/// Cases of the oracle tests under `dir` having a `bench` entry and not
+/// skipped, sorted by name
+pub fn load_cases(dir: &str) -> Result<Vec<BenchCase>, String> {
+    let specs = glob::glob(&format!("{}/*/oracle.json", dir)).map_err(|e| e.to_string())?;
+    let mut cases = vec![];
+    for spec_path in specs.filter_map(|path| path.ok()) {
+        let spec = fs::read_to_string(&spec_path).map_err(|e| format!("failed to read {:?}: {}", spec_path, e))?;
+        let spec: OracleSpec =
+            serde_json::from_str(&spec).map_err(|e| format!("failed to parse {:?}: {}", spec_path, e))?;
+        let (Some(bench), None) = (spec.bench, spec.skip) else {
+            continue;
+        };
+        let path = spec_path.parent().unwrap().to_path_buf();
+        cases.push(BenchCase {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            path,
+            detectors: spec.detectors,
+            bug_type: spec.bug_type,
+            args: spec.args,
+            deployment_script: spec.deployment_script,
+            seed: bench.seed,
+            max_secs: bench.max_secs,
+        });
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BenchResult {
+    pub name: String,
+    /// Seconds to find the bug, None if not found
+    pub time_to_bug: Option<f64>,
+    pub max_secs: f64,
+}
+
+impl BenchResult {
+    pub fn passed(&self) -> bool {
+        self.time_to_bug.is_some_and(|secs| secs <= self.max_secs)
+    }
+}
+
+/// Cases whose names are listed, all of them if none is
+pub fn select_cases(cases: Vec<BenchCase>, names: &str) -> Result<Vec<BenchCase>, String> {
+    let names = names
+        .split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        return Ok(cases);
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            cases
+                .iter()
+                .find(|case| case.name == name)
+                .cloned()
+                .ok_or(format!("unknown bench case {}", name))
+        })
+        .collect()
+}
+
+/// Build the contracts of the case into `build_dir`
+fn compile(case: &BenchCase, build_dir: &Path) -> Result<(), String> {
+    let sources = glob::glob(&format!("{}/*.sol", case.path.display()))
+        .map_err(|e| e.to_string())?
+        .filter_map(|path| path.ok())
+        .collect::<Vec<_>>();
+    let status = Command::new("solc")
+        .args(&sources)
+        .arg("-o")
+        .arg(build_dir)
+        .args(["--bin", "--abi", "--overwrite", "--base-path", "."])
+        .args(["--combined-json", "bin-runtime,srcmap-runtime"])
+        .stdout(Stdio::null())
+        .status()
+        .map_err(|e| format!("failed to run solc: {}", e))?;
+    if !status.success() {
+        return Err(format!("failed to compile {}", case.path.display()));
+    }
+    Ok(())
+}
+
+/// Fuzz the contract of the case until the bug is found or the time bound
+/// is reached
+fn run_case(case: &BenchCase, work_dir: &str, time_scale: f64) -> Result<BenchResult, String> {
+    let case_work_dir = format!("{}/{}", work_dir, case.name);
+    let _ = fs::remove_dir_all(&case_work_dir);
+    let max_secs = case.max_secs as f64 * time_scale;
+
+    // the contracts deployed by a deployment script are built by the fuzzer
+    let build_dir = std::env::temp_dir().join(format!("ityfuzz_bench_{}_{}", std::process::id(), case.name));
+    let (target, build) = match &case.deployment_script {
+        Some(script) => (
+            vec!["-m".to_string(), script.clone()],
+            vec![
+                "--".to_string(),
+                "solc".to_string(),
+                format!("{}/*.sol", case.path.display()),
+            ],
+        ),
+        None => {
+            compile(case, &build_dir)?;
+            (vec!["-t".to_string(), format!("{}/*", build_dir.display())], vec![])
+        }
+    };
+
+    let exe = std::env::current_exe().map_err(|e| format!("failed to locate the ityfuzz binary: {}", e))?;
+    let start = Instant::now();
+    let status = Command::new(exe)
+        .arg("evm")
+        .args(&target)
+        .args(["-d", &case.detectors])
+        .args(["--seed", &case.seed.to_string(), "--work-dir", &case_work_dir])
+        .args(["--timeout", &(max_secs.ceil() as u64).to_string()])
+        .args(&case.args)
+        .args(&build)
+        .stdout(Stdio::null())
+        .status();
+    let elapsed = start.elapsed();
+    let _ = fs::remove_dir_all(&build_dir);
+    status.map_err(|e| format!("failed to run the fuzzer: {}", e))?;
+
+    let vuln_info = fs::read_to_string(format!("{}/vuln_info.jsonl", case_work_dir)).unwrap_or_default();
+    let found = Report::from_vuln_info(&vuln_info).bugs.contains_key(&case.bug_type);
+    Ok(BenchResult {
+        name: case.name.clone(),
+        time_to_bug: found.then(|| elapsed.as_secs_f64()),
+        max_secs,
+    })
+}
+
+/// The fund loss, reentrancy and event oracles, `count` times each
+fn mixed_oracles(count: usize) -> PostStateOracles {
+    let events = parse_event_properties("InvariantViolated(string)").unwrap();
+    PostStateOracles::new(
+        (0..count)
+            .flat_map(|_| {
+                [
+                    Box::new(FundLossOracle::new(OracleThresholds::default())) as Box<dyn PostStateOracle>,
+                    Box::new(ReentrancyOracle::new(HashMap::new())),
+                    Box::new(EventOracle::new(HashMap::new(), events.clone())),
+                ]
+            })
+            .collect(),
+    )
+}
+
+/// A post-state making every oracle of [`mixed_oracles`] report bugs
+fn buggy_post_state() -> EVMState {
+    let events = parse_event_properties("InvariantViolated(string)").unwrap();
+    let mut state = EVMState::default();
+    state.logs = (0..256)
+        .map(|idx| EmittedLog {
+            address: EVMAddress::from_slice(&[idx as u8; 20]),
+            topics: vec![events[0].selector().0.into()],
+            data: DynSolValue::Tuple(vec![DynSolValue::String(format!("x > {}", idx))])
+                .abi_encode_params()
+                .into(),
+        })
+        .collect();
+    state.flashloan_data.earned = EVMU512::from(100) * EVMU512::from(10).pow(EVMU512::from(24));
+    for idx in 0..64u8 {
+        state
+            .reentrancy_metadata
+            .found
+            .insert((EVMAddress::from_slice(&[idx; 20]), EVMU256::from(idx)));
+    }
+    state
+}
+
+/// Executions per second of the post-state oracles evaluated one after
+/// another and concurrently
+pub fn bench_post_state_oracles(execs: usize) -> Vec<(bool, f64)> {
+    let state = buggy_post_state();
+    let mut oracles = mixed_oracles(PARALLEL_ORACLE_THRESHOLD);
+    [false, true]
+        .into_iter()
+        .map(|parallel| {
+            oracles.parallel = parallel;
+            let start = Instant::now();
+            for _ in 0..execs {
+                oracles.check(EVMAddress::zero(), &state);
+            }
+            (parallel, execs as f64 / start.elapsed().as_secs_f64())
+        })
+        .collect()
+}
+
+/// Run the benchmark, returns whether every bug was found in time
+pub fn bench_main(args: BenchArgs) -> bool {
+    if args.post_state_oracles {
+        for (parallel, execs_per_sec) in bench_post_state_oracles(2000) {
+            info!(
+                "post-state oracles, parallel: {}, exec/sec: {:.0}",
+                parallel, execs_per_sec
+            );
+        }
+        return true;
+    }
+    let cases = match load_cases(&args.oracles_dir).and_then(|cases| select_cases(cases, &args.cases)) {
+        Ok(cases) => cases,
+        Err(e) => {
+            error!("{}", e);
+            return false;
+        }
+    };
+    if let Err(e) = fs::create_dir_all(&args.work_dir) {
+        error!("failed to create {}: {}", args.work_dir, e);
+        return false;
+    }
+
+    let mut results = vec![];
+    for case in cases {
+        info!("bench case `{}`: {}", case.name, case.path.display());
+        match run_case(&case, &args.work_dir, args.time_scale) {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                error!("bench case `{}` could not run: {}", case.name, e);
+                results.push(BenchResult {
+                    name: case.name.clone(),
+                    time_to_bug: None,
+                    max_secs: case.max_secs as f64 * args.time_scale,
+                });
+            }
+        }
+    }
+
+    info!("{:<24} {:>12} {:>10}  result", "case", "time to bug", "bound");
+    for result in &results {
+        let time_to_bug = result
+            .time_to_bug
+            .map_or("-".to_string(), |secs| format!("{:.1}s", secs));
+        info!(
+            "{:<24} {:>12} {:>9.0}s  {}",
+            result.name,
+            time_to_bug,
+            result.max_secs,
+            if result.passed() { "ok" } else { "FAILED" }
+        );
+    }
+    let json = serde_json::to_string_pretty(&results).expect("failed to serialize bench results");
+    if let Err(e) = fs::write(format!("{}/bench.json", args.work_dir), json) {
+        error!("failed to write the bench results: {}", e);
+    }
+    results.iter().all(|result| result.passed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_cases() {
+        let dir = std::env::temp_dir().join(format!("ityfuzz_bench_cases_{}", std::process::id()));
+        for (name, spec) in [
+            (
+                "reentrancy",
+                r#"{"detectors": "reentrancy", "bug_type": "Reentrancy", "max_execs": 1, "bench": {"seed": 1, "max_secs": 120}}"#,
+            ),
+            (
+                "erc20",
+                r#"{"detectors": "erc20", "bug_type": "Fund Loss", "max_execs": 1, "args": ["-f"], "deployment_script": "test.sol:Setup", "bench": {"seed": 2, "max_secs": 60}}"#,
+            ),
+            // not benched
+            (
+                "supply",
+                r#"{"detectors": "supply", "bug_type": "Implicit Supply Change", "max_execs": 1}"#,
+            ),
+            (
+                "math_calculate",
+                r#"{"detectors": "math_calculate", "bug_type": "Integer Overflow", "max_execs": 1, "skip": "", "bench": {"seed": 1, "max_secs": 60}}"#,
+            ),
+        ] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(dir.join(name).join("oracle.json"), spec).unwrap();
+        }
+        let cases = load_cases(dir.to_str().unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            cases.iter().map(|case| case.name.as_str()).collect::<Vec<_>>(),
+            vec!["erc20", "reentrancy"]
+        );
+        assert_eq!(cases[0].args, vec!["-f".to_string()]);
+        assert_eq!(cases[0].deployment_script.as_deref(), Some("test.sol:Setup"));
+        assert_eq!((cases[0].seed, cases[0].max_secs), (2, 60));
+
+        assert_eq!(select_cases(cases.clone(), "").unwrap().len(), 2);
+        let selected = select_cases(cases.clone(), "reentrancy").unwrap();
+        assert_eq!(selected[0].detectors, "reentrancy");
+        assert!(select_cases(cases, "unknown").is_err());
+
+        // the bundled cases
+        let cases = load_cases("tests/oracles").unwrap();
+        assert_eq!(
+            cases.iter().map(|case| case.name.as_str()).collect::<Vec<_>>(),
+            vec!["price_manipulation", "reentrancy", "takeover"]
+        );
+    }
+
+    #[test]
+    fn test_bench_result() {
+        let result = BenchResult {
+            name: "reentrancy".to_string(),
+            time_to_bug: Some(30.0),
+            max_secs: 60.0,
+        };
+        assert!(result.passed());
+        assert!(!BenchResult {
+            time_to_bug: None,
+            ..result
+        }
+        .passed());
+    }
+
+    #[test]
+    fn test_bench_post_state_oracles() {
+        let bugs = mixed_oracles(1).check(EVMAddress::zero(), &buggy_post_state());
+        assert!(bugs.iter().any(|bug| bug.bug_type == "Fund Loss"));
+        assert!(bugs.iter().any(|bug| bug.bug_type == "Reentrancy"));
+        assert_eq!(bugs.len(), 1 + 64 + 256);
+
+        let results = bench_post_state_oracles(1);
+        assert_eq!(
+            results.iter().map(|(parallel, _)| *parallel).collect::<Vec<_>>(),
+            vec![false, true]
+        );
+        assert!(results.iter().all(|(_, execs_per_sec)| *execs_per_sec > 0.0));
+    }
+}
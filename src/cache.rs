@@ -16,12 +16,8 @@ pub struct FileSystemCache {
 }
 
 impl FileSystemCache {
+    /// The directory is created on the first save
     pub fn new(file_path: &str) -> FileSystemCache {
-        let path = Path::new(file_path);
-        if !path.exists() {
-            fs::create_dir_all(path).unwrap();
-        }
-
         FileSystemCache {
             file_path: file_path.to_string(),
         }
@@ -65,3 +61,24 @@ impl Cache for FileSystemCache {
         Ok(contents)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_system_cache() {
+        let dir = std::env::temp_dir().join("ityfuzz_cache_test");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = FileSystemCache::new(dir.join("1/100").to_str().unwrap());
+        assert!(!dir.exists());
+        assert!(cache.load("123456").is_err());
+
+        cache.save("123456", "value").unwrap();
+        assert_eq!(cache.load("123456").unwrap(), "value");
+        // another block is another namespace
+        let other = FileSystemCache::new(dir.join("1/101").to_str().unwrap());
+        assert!(other.load("123456").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
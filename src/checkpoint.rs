@@ -0,0 +1,172 @@
+//! Intermediate VM state checkpoints of long transaction sequences
+//!
+//! Only interesting VMStates enter the infant state corpus, so on deep
+//! stateful targets mutating the end of a long sequence means re-executing
+//! the whole prefix from the last interesting state. With checkpoints, the
+//! VMState after every K-th transaction of a sequence is kept as well, and
+//! mutation resumes from it instead. Checkpoints are rate limited and capped
+//! as they take the place of interesting VMStates when the corpus is pruned.
+
+use std::{collections::VecDeque, fmt::Debug};
+
+use libafl::{
+    corpus::Corpus,
+    prelude::{HasMetadata, RemovableScheduler, Scheduler},
+    state::HasCorpus,
+};
+use libafl_bolts::impl_serdeany;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    generic_vm::vm_state::VMStateT,
+    input::ConciseSerde,
+    r#const::{CHECKPOINT_MAX_STATES, CHECKPOINT_MIN_EXECS},
+    state::{HasInfantStateState, HasItyState, InfantStateState},
+    state_input::StagedVMState,
+};
+
+/// Metadata stored in the fuzz state deciding when to take checkpoints
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointMetadata {
+    /// A checkpoint is taken after every `interval`-th transaction of a
+    /// sequence
+    pub interval: usize,
+    /// Number of checkpoints taken
+    pub taken: usize,
+    /// Executions since last checkpoint
+    execs_since_checkpoint: usize,
+    /// Indexes of the checkpoints in the infant state corpus, oldest first
+    kept: VecDeque<usize>,
+}
+
+impl_serdeany!(CheckpointMetadata);
+
+impl CheckpointMetadata {
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            taken: 0,
+            execs_since_checkpoint: 0,
+            kept: VecDeque::new(),
+        }
+    }
+
+    /// Record an execution at `depth` and whether the resulting VMState
+    /// should be added to the infant state corpus as a checkpoint
+    pub fn should_checkpoint(&mut self, depth: usize) -> bool {
+        self.execs_since_checkpoint += 1;
+        if depth == 0 || depth % self.interval != 0 || self.execs_since_checkpoint < CHECKPOINT_MIN_EXECS {
+            return false;
+        }
+        self.execs_since_checkpoint = 0;
+        self.taken += 1;
+        true
+    }
+
+    /// Record the checkpoint added at `idx` of the infant state corpus and
+    /// return the oldest one to evict when too many are kept
+    pub fn record(&mut self, idx: usize) -> Option<usize> {
+        self.kept.push_back(idx);
+        if self.kept.len() > CHECKPOINT_MAX_STATES {
+            return self.kept.pop_front();
+        }
+        None
+    }
+}
+
+/// Add `new_state` to the infant state corpus as a checkpoint and evict the
+/// oldest checkpoint when more than [`CHECKPOINT_MAX_STATES`] are kept
+pub fn add_checkpoint<S, SC, Loc, Addr, VS, CI>(
+    state: &mut S,
+    scheduler: &mut SC,
+    new_state: &StagedVMState<Loc, Addr, VS, CI>,
+    parent_idx: usize,
+) -> usize
+where
+    S: HasItyState<Loc, Addr, VS, CI> + HasInfantStateState<Loc, Addr, VS, CI> + HasMetadata,
+    SC: Scheduler<State = InfantStateState<Loc, Addr, VS, CI>> + RemovableScheduler,
+    VS: Default + VMStateT,
+    Addr: Serialize + DeserializeOwned + Debug + Clone,
+    Loc: Serialize + DeserializeOwned + Debug + Clone,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
+{
+    let idx = state.add_infant_state(new_state, scheduler, parent_idx);
+    let evicted = state
+        .metadata_map_mut()
+        .get_mut::<CheckpointMetadata>()
+        .and_then(|meta| meta.record(idx));
+
+    // with full_trace, the VMStates are kept for the traces of their descendants
+    if let Some(evicted) = evicted &&
+        !cfg!(feature = "full_trace")
+    {
+        let infant_state = state.get_infant_state_state();
+        // the checkpoint may have been pruned by the scheduler already
+        if infant_state.corpus().get(evicted.into()).is_ok() {
+            scheduler
+                .on_remove(infant_state, evicted.into(), &None)
+                .expect("failed to remove checkpoint");
+            infant_state
+                .corpus_mut()
+                .remove(evicted.into())
+                .expect("failed to remove checkpoint");
+        }
+    }
+    idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evm::{
+            types::{EVMAddress, EVMFuzzState, EVMU256},
+            vm::EVMState,
+        },
+        scheduler::SortedDroppingScheduler,
+        state::FuzzState,
+    };
+
+    #[test]
+    fn test_should_checkpoint() {
+        let mut meta = CheckpointMetadata::new(4);
+        for _ in 0..CHECKPOINT_MIN_EXECS {
+            assert!(!meta.should_checkpoint(3));
+        }
+        assert!(meta.should_checkpoint(8));
+        // rate limited
+        assert!(!meta.should_checkpoint(4));
+        assert_eq!(meta.taken, 1);
+    }
+
+    #[test]
+    fn test_checkpoint_eviction() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut scheduler = SortedDroppingScheduler::new();
+        state.add_metadata(CheckpointMetadata::new(1));
+        let initial = state.add_infant_state(&StagedVMState::new_with_state(EVMState::new()), &mut scheduler, 0);
+
+        let mut checkpoints = vec![];
+        for i in 0..=CHECKPOINT_MAX_STATES {
+            let mut vm_state = EVMState::new();
+            vm_state.sstore(EVMAddress::zero(), EVMU256::from(i), EVMU256::from(1));
+            checkpoints.push(add_checkpoint(
+                &mut state,
+                &mut scheduler,
+                &StagedVMState::new_with_state(vm_state),
+                initial,
+            ));
+        }
+
+        // the oldest checkpoint is evicted, the interesting VMState is kept
+        let infant_state = state.get_infant_state_state();
+        assert_eq!(infant_state.corpus().count(), CHECKPOINT_MAX_STATES + 1);
+        assert!(infant_state.corpus().get(checkpoints[0].into()).is_err());
+        assert!(infant_state.corpus().get(checkpoints[1].into()).is_ok());
+        assert!(infant_state.corpus().get(initial.into()).is_ok());
+        for _ in 0..100 {
+            let idx = scheduler.next(infant_state).unwrap();
+            assert_ne!(usize::from(idx), checkpoints[0]);
+        }
+    }
+}
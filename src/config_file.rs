@@ -0,0 +1,152 @@
+//! Config file driven CLI
+//!
+//! Instead of passing every option on the command line, a TOML or YAML file
+//! can be given with `--config <path>`. The file selects the fuzzer with the
+//! `fuzzer` key (`evm` by default) and the remaining keys are the options of
+//! that fuzzer, named after their command line flags, e.g.:
+//!
+//! ```toml
+//! fuzzer = "evm"
+//! target = "./build/*"
+//! chain_type = "bsc"
+//! detectors = ["erc20", "reentrancy"]
+//! work_dir = "work_dir"
+//! run_forever = true
+//! seed = 42
+//! ```
+//!
+//! The file is translated into the equivalent command line, so that it
+//! supports exactly the same options with the same validation.
+//!
+//! A file with a `stages` list defines a pipeline instead, see
+//! [`crate::pipeline`].
+
+use std::{fs, path::Path};
+
+use serde_json::Value;
+
+/// Key selecting the fuzzer (subcommand) to run
+const FUZZER_KEY: &str = "fuzzer";
+/// Key holding the trailing arguments (e.g., `build_command` of EVM)
+const TRAILING_KEY: &str = "build_command";
+
+/// Reads the TOML / YAML config file at `path`
+pub fn read_config(path: &str) -> Result<Value, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("failed to read config file {}: {}", path, e))?;
+    let value: Value = match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(|e| format!("invalid TOML config {}: {}", path, e))?,
+        Some("yaml") | Some("yml") => {
+            serde_yaml::from_str(&content).map_err(|e| format!("invalid YAML config {}: {}", path, e))?
+        }
+        _ => {
+            return Err(format!(
+                "unsupported config file {}, expected .toml, .yaml or .yml",
+                path
+            ))
+        }
+    };
+    Ok(value)
+}
+
+/// Converts the parsed config into command line arguments, including the
+/// binary name and the subcommand
+pub fn config_to_args(config: &Value) -> Result<Vec<String>, String> {
+    let table = config.as_object().ok_or("config must be a table of options")?;
+
+    let fuzzer = match table.get(FUZZER_KEY) {
+        Some(Value::String(fuzzer)) => fuzzer.clone(),
+        Some(v) => return Err(format!("`{}` must be a string, got {}", FUZZER_KEY, v)),
+        None => "evm".to_string(),
+    };
+
+    let mut args = vec!["ityfuzz".to_string(), fuzzer];
+    let mut trailing = vec![];
+    for (key, value) in table {
+        if key == FUZZER_KEY {
+            continue;
+        }
+        if key == TRAILING_KEY {
+            trailing = match value {
+                Value::Array(items) => items.iter().map(scalar_to_string).collect::<Result<_, _>>()?,
+                v => vec![scalar_to_string(v)?],
+            };
+            continue;
+        }
+
+        let flag = format!("--{}", key.replace('_', "-"));
+        match value {
+            Value::Bool(true) => args.push(flag),
+            Value::Bool(false) | Value::Null => {}
+            // lists are comma separated on the command line, e.g., detectors
+            Value::Array(items) => {
+                let items = items.iter().map(scalar_to_string).collect::<Result<Vec<_>, _>>()?;
+                args.push(flag);
+                args.push(items.join(","));
+            }
+            v => {
+                args.push(flag);
+                args.push(scalar_to_string(v).map_err(|e| format!("option `{}`: {}", key, e))?);
+            }
+        }
+    }
+
+    if !trailing.is_empty() {
+        args.push("--".to_string());
+        args.extend(trailing);
+    }
+    Ok(args)
+}
+
+fn scalar_to_string(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        v => Err(format!("expected a string, number or boolean, got {}", v)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_config_to_args() {
+        let config: Value = toml::from_str(
+            r#"
+            fuzzer = "evm"
+            chain_type = "bsc"
+            detectors = ["erc20", "reentrancy"]
+            run_forever = true
+            flashloan = false
+            seed = 42
+            build_command = ["forge", "build"]
+            "#,
+        )
+        .unwrap();
+        let args = config_to_args(&config).unwrap();
+        assert_eq!(&args[..2], &["ityfuzz", "evm"]);
+        let rest = args[2..].join(" ");
+        assert!(rest.contains("--chain-type bsc"));
+        assert!(rest.contains("--detectors erc20,reentrancy"));
+        assert!(rest.contains("--run-forever"));
+        assert!(!rest.contains("--flashloan"));
+        assert!(rest.contains("--seed 42"));
+        assert!(rest.ends_with("-- forge build"));
+    }
+
+    #[test]
+    fn test_yaml_config_to_args() {
+        let config: Value = serde_yaml::from_str("target: ./build/*\nwork_dir: wd\n").unwrap();
+        let args = config_to_args(&config).unwrap();
+        assert_eq!(args[1], "evm");
+        assert!(args.join(" ").contains("--work-dir wd"));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config: Value = toml::from_str("[onchain]\nurl = \"http://localhost\"\n").unwrap();
+        assert!(config_to_args(&config).is_err());
+        assert!(config_to_args(&Value::Bool(true)).is_err());
+    }
+}
@@ -19,6 +19,31 @@ pub const PRUNE_AMT: usize = 250;
 /// ignored during pruning
 pub const VISIT_IGNORE_THRESHOLD: usize = 2;
 
+// src/sequence_length.rs
+/// Initial maximum length of a transaction sequence
+pub const SEQ_LEN_INITIAL: usize = 8;
+/// Lower bound of the adaptive maximum sequence length
+pub const SEQ_LEN_MIN: usize = 2;
+/// Upper bound of the adaptive maximum sequence length
+pub const SEQ_LEN_MAX: usize = 64;
+/// Number of executions between two adaptations of the maximum sequence length
+pub const SEQ_LEN_ADAPT_INTERVAL: usize = 1000;
+/// Gain rate past the cap above which the maximum sequence length grows
+pub const SEQ_LEN_GROW_RATE: f64 = 0.01;
+/// Gain rate at the cap below which the maximum sequence length shrinks
+pub const SEQ_LEN_SHRINK_RATE: f64 = 0.001;
+
+// src/checkpoint.rs
+/// Minimum number of executions between two intermediate VMState checkpoints
+pub const CHECKPOINT_MIN_EXECS: usize = 100;
+/// Maximum number of intermediate VMState checkpoints kept in the infant state
+/// corpus, the oldest ones are evicted first
+pub const CHECKPOINT_MAX_STATES: usize = 100;
+
+// src/persistence.rs
+/// File in the work dir the campaign is saved to
+pub const CAMPAIGN_FILE: &str = "campaign.cbor";
+
 // src/state.rs
 /// Amount of accounts and contracts that can be caller during fuzzing.
 /// We will generate random addresses for these accounts and contracts.
@@ -38,6 +63,9 @@ pub const MUTATE_CHOICE_MAX: u64 = 80;
 pub const EXPAND_CHOICE_MAX: u64 = 90;
 /// Maximum value of the random address choice. Related to [SAMPLE_MAX]
 pub const RANDOM_ADDRESS_CHOICE: u64 = 90;
+/// Maximum value of the choice of an address from the dictionary of the
+/// contract. Related to [SAMPLE_MAX] and [RANDOM_ADDRESS_CHOICE]
+pub const DICTIONARY_ADDRESS_CHOICE: u64 = 30;
 
 // src/evm/corpus_initializer.rs
 /// If there are more than 1/UNKNOWN_SIGS_DIVISOR unknown sigs, we will
@@ -61,6 +89,8 @@ pub const MUTATE_CALLER_CHOICE: u64 = 20;
 pub const TURN_TO_STEP_CHOICE: u64 = 60;
 /// Related to [MUTATOR_SAMPLE_MAX]
 pub const RANDOMNESS_CHOICE: u64 = 33;
+/// Related to [MUTATOR_SAMPLE_MAX] and [RANDOMNESS_CHOICE]
+pub const INTERESTING_AMOUNT_CHOICE: u64 = 66;
 /// Related to [MUTATOR_SAMPLE_MAX]
 pub const LIQUIDATE_CHOICE: u64 = 5;
 /// Related to [MUTATOR_SAMPLE_MAX]
@@ -70,8 +100,185 @@ pub const LIQ_PERCENT: u64 = 10;
 pub const RANDOMNESS_CHOICE_2: u64 = 6;
 /// Maximum number of retries to try to find a valid mutation
 pub const MUTATION_RETRIES: usize = 20;
+/// Related to [MUTATOR_SAMPLE_MAX]
+pub const CALL_ORDER_CHOICE: u64 = 10;
+/// Probability of moving the input to a later block than the one of its VM
+/// state. Related to [MUTATOR_SAMPLE_MAX]
+pub const ADVANCE_BLOCK_CHOICE: u64 = 5;
+
+// src/evm/splice_stage.rs
+/// Probability of splicing two sequences of the infant state corpus in a
+/// fuzzing iteration. Related to [MUTATOR_SAMPLE_MAX]
+pub const SPLICE_CHOICE: u64 = 5;
+
+// src/evm/bytecode_analyzer.rs
+/// Maximum number of instructions analyzed per function for call order hints
+pub const CALL_ORDER_MAX_OPS: usize = 8192;
+
+// src/evm/input.rs
+/// Average time between two blocks
+pub const BLOCK_TIME_SECS: u64 = 12;
+/// Maximum time the block environment advances by between two transactions
+pub const MAX_TIME_DELTA: u64 = 2 * 365 * 86400;
+/// Durations time-locked logic commonly checks: a block, a minute, an hour,
+/// a day, a week, 30 days and a year
+pub const INTERESTING_TIME_DELTAS: [u64; 7] = [12, 60, 3600, 86400, 604800, 2592000, 31536000];
+/// Maximum change of the base fee per block, in permille (EIP-1559)
+pub const BASEFEE_MAX_CHANGE_PERMILLE: u64 = 125;
+
+// src/evm/vm.rs
+/// Intrinsic gas of a transaction
+pub const TX_BASE_GAS: u64 = 21000;
+/// Estimated gas of a borrow transaction (swaps through a few pairs)
+pub const BORROW_GAS_ESTIMATE: u64 = 150_000;
+/// Gas limit of a block, transactions of a sequence are packed into blocks
+/// under this limit
+pub const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+// src/evm/oracles/mod.rs
+/// Default minimum profit for the ERC20 oracle to report a fund loss, in USD
+pub const DEFAULT_MIN_PROFIT_USD: f64 = 20.0;
+/// Price of the native token in USD when it cannot be quoted in USDC, e.g.,
+/// offchain
+pub const DEFAULT_NATIVE_PRICE_USD: f64 = 2000.0;
+
+// src/evm/oracles/erc4626.rs
+/// Change (in percent) of the share price of an ERC-4626 vault from its
+/// initial price above which it is reported as manipulated
+pub const ERC4626_PRICE_CHANGE_PERCENT: u64 = 50;
+
+// src/evm/oracles/gas_griefing.rs
+/// Factors the dynamic arrays of a call are grown by to measure its gas
+pub const GAS_GRIEFING_SCALES: [usize; 4] = [1, 2, 4, 8];
+/// Minimum number of input sizes the grown calls succeed with before the gas
+/// growth is checked
+pub const GAS_GRIEFING_MIN_SIZES: usize = 3;
+/// Minimum increase of the gas of a function over its input sizes to report
+/// it
+pub const GAS_GRIEFING_MIN_GROWTH: u64 = 100_000;
+/// Ratio between the gas per unit of size over the larger sizes and over the
+/// smaller ones above which the growth is superlinear
+pub const GAS_GRIEFING_SLOPE_RATIO: f64 = 2.0;
+
+// src/evm/blaz/storage_layout.rs
+/// Maximum number of slots of a dynamic array or of a long string matched
+/// against the changed slots
+pub const STORAGE_LAYOUT_ARRAY_MAX: u64 = 1 << 16;
+/// Maximum nesting of the mappings whose slots are resolved
+pub const STORAGE_LAYOUT_MAPPING_DEPTH: usize = 2;
+/// Integers from 0 tried as keys of the mappings
+pub const STORAGE_LAYOUT_UINT_KEYS: u64 = 32;
+
+// src/evm/onchain/provider.rs
+/// Maximum number of rounds of attempts of an RPC request over all endpoints,
+/// backing off between them
+pub const RPC_MAX_ROUNDS: usize = 3;
+/// Initial backoff after all RPC endpoints failed, in milliseconds
+pub const RPC_BACKOFF_INITIAL_MS: u64 = 200;
+/// Maximum backoff between RPC attempts, in milliseconds
+pub const RPC_BACKOFF_MAX_MS: u64 = 10_000;
+/// Read timeout of WebSocket RPC connections, in seconds
+pub const RPC_WS_TIMEOUT_SECS: u64 = 20;
+/// Credits of RPC methods not priced by `rpc_method_credits`
+pub const RPC_DEFAULT_CREDITS: u64 = 20;
+
+// src/evm/onchain/fork_backend.rs
+/// Maximum time for the fork backend to start listening, in seconds
+pub const FORK_BACKEND_STARTUP_TIMEOUT_SECS: u64 = 60;
+
+// src/evm/onchain/endpoints.rs
+/// Default directory of the persistent RPC cache
+pub const DEFAULT_CACHE_DIR: &str = "./cache";
+/// Timeout of the lookups on Sourcify and 4byte.directory, in seconds
+pub const SIGNATURE_LOOKUP_TIMEOUT_SECS: u64 = 3;
+/// Maximum gas of a transaction on Arbitrum
+pub const ARBITRUM_TX_GAS_LIMIT: u64 = 32_000_000;
+/// Maximum gas of a transaction on zkSync Era, including its pubdata
+pub const ZKSYNC_TX_GAS_LIMIT: u64 = 80_000_000;
+
+// src/evm/middlewares/chainlink.rs
+/// Number of the last rounds of a Chainlink feed replayed
+pub const CHAINLINK_REPLAY_ROUNDS: u64 = 32;
+/// Seconds between two rounds of a mocked Chainlink feed
+pub const CHAINLINK_ROUND_INTERVAL: u64 = 3600;
+
+// src/mutation_utils.rs
+/// Maximum number of values kept in the interesting values pool
+pub const INTERESTING_VALUES_MAX: usize = 1024;
 
 // src/evm/scheduler.rs
 pub const POWER_MULTIPLIER: f64 = 32.0;
 pub const MAX_POWER: f64 = 3200.0;
 pub const MIN_POWER: f64 = 32.0;
+/// Maximum multiplier of the power of testcases calling rarely executed
+/// functions
+pub const RARE_SELECTOR_MAX_BOOST: f64 = 4.0;
+
+// src/evm/slither.rs
+/// Maximum multiplier of the power of testcases calling functions flagged by
+/// Slither
+pub const SLITHER_MAX_BOOST: f64 = 4.0;
+
+// src/evm/summary.rs
+/// File of the work dir the end-of-campaign summary is written to
+pub const SUMMARY_FILE: &str = "summary.txt";
+/// Maximum number of suggestions of each kind in the summary
+pub const SUMMARY_MAX_ITEMS: usize = 5;
+/// Minimum number of calls of a function always reverting to report it
+pub const SUMMARY_STUCK_MIN_CALLS: usize = 100;
+
+// src/evm/redqueen.rs
+/// Maximum number of distinct comparisons logged per execution
+pub const REDQUEEN_MAX_COMPARISONS: usize = 256;
+/// Maximum number of input-to-state replacements tried per testcase
+pub const REDQUEEN_MAX_CANDIDATES: usize = 128;
+
+// src/evm/launcher.rs
+/// Seconds between two reports of the stats aggregated over the instances
+pub const LAUNCHER_REPORT_INTERVAL_SECS: u64 = 15;
+/// Maximum number of times a crashed instance is restarted
+pub const LAUNCHER_MAX_RESTARTS: usize = 3;
+
+// src/evm/tui.rs
+/// Number of objectives shown by the dashboard
+pub const TUI_RECENT_OBJECTIVES: usize = 5;
+
+// src/evm/dictionary.rs
+/// Maximum number of entries of each kind in the dictionary of a contract
+pub const DICTIONARY_MAX_ENTRIES: usize = 256;
+
+// src/evm/erc20_mutator.rs
+/// Probability of setting an amount arg to a balance, allowance, supply or
+/// reserve of the token. Related to [SAMPLE_MAX]
+pub const ERC20_AMOUNT_CHOICE: u64 = 15;
+/// Number of storage slots of a token searched for the total supply and the
+/// balance and allowance mappings
+pub const ERC20_STORAGE_SLOTS: u64 = 16;
+
+// src/evm/privileged.rs
+/// Maximum number of members of the default admin role impersonated per
+/// contract
+pub const ROLE_MEMBERS_MAX: u64 = 8;
+
+// src/evm/middlewares/breakpoint.rs
+/// Bytes of memory dumped when a breakpoint is hit
+pub const BREAKPOINT_MEMORY_WINDOW: usize = 512;
+/// Number of the last storage accesses dumped when a breakpoint is hit
+pub const BREAKPOINT_STORAGE_ACCESSES: usize = 32;
+
+// src/evm/oracles/post_state.rs
+/// Number of oracles reading the post-state from which they are evaluated
+/// concurrently
+pub const PARALLEL_ORACLE_THRESHOLD: usize = 4;
+
+// src/evm/code_analysis.rs
+/// Maximum number of distinct codes whose analysis is shared
+pub const CODE_ANALYSIS_CACHE_SIZE: usize = 4096;
+
+// src/evm/vm.rs
+/// Maximum number of interpreters kept for reuse by the internal calls
+pub const INTERPRETER_POOL_SIZE: usize = 8;
+
+// src/move/corpus_initializer.rs
+/// Amount of each coin borrowed by the fuzzer with flashloans
+pub const MOVE_FLASHLOAN_AMOUNT: u64 = 1_000_000_000_000_000;
@@ -28,12 +28,14 @@ use crate::evm::abi::ABILossyType::{TArray, TDynamic, TEmpty, TUnknown, T256};
 use crate::{
     evm::{
         concolic::expr::Expr,
+        dictionary::sample_dictionary_address,
         types::{EVMAddress, EVMU256},
+        vm::EVMStorage,
     },
     generic_vm::vm_state::VMStateT,
     input::ConciseSerde,
     mutation_utils::{byte_mutator, byte_mutator_with_expansion},
-    r#const::{EXPAND_CHOICE_MAX, MUTATE_CHOICE_MAX, RANDOM_ADDRESS_CHOICE, SAMPLE_MAX},
+    r#const::{DICTIONARY_ADDRESS_CHOICE, EXPAND_CHOICE_MAX, MUTATE_CHOICE_MAX, RANDOM_ADDRESS_CHOICE, SAMPLE_MAX},
     state::{HasCaller, HasItyState},
 };
 
@@ -77,13 +79,19 @@ fn set_size(bytes: *mut u8, len: usize) {
     }
 }
 
+/// Get the size (or offset) encoded in the first 32 bytes of [`bytes`],
+/// saturated to [`usize::MAX`]
 fn get_size(bytes: &[u8]) -> usize {
-    let mut size: usize = 0;
-    (0..32).for_each(|i| {
-        size <<= 8;
-        size += bytes[i] as usize;
-    });
-    size
+    if bytes[..24].iter().any(|b| *b != 0) {
+        return usize::MAX;
+    }
+    bytes[24..32].iter().fold(0, |size, b| (size << 8) | *b as usize)
+}
+
+/// Get the size (or offset) encoded at [`offset`] of [`bytes`], None if the
+/// bytes are truncated
+fn read_size(bytes: &[u8], offset: usize) -> Option<usize> {
+    bytes.get(offset..offset.checked_add(32)?).map(get_size)
 }
 
 /// ABI instance map from address
@@ -352,6 +360,7 @@ where
             1 => BoxedABI::new(Box::new(AArray {
                 data: vec![sample_abi(state, 32); vec_size],
                 dynamic_size: false,
+                element: None,
             })),
             // array[]
             2 => {
@@ -359,14 +368,16 @@ where
                 BoxedABI::new(Box::new(AArray {
                     data: vec![abi; vec_size],
                     dynamic_size: false,
+                    element: None,
                 }))
             }
             // array[...]
             3 => {
                 let abi = sample_abi(state, 32);
                 BoxedABI::new(Box::new(AArray {
-                    data: vec![abi; vec_size],
+                    data: vec![abi.clone(); vec_size],
                     dynamic_size: true,
+                    element: Some(Box::new(abi)),
                 }))
             }
             _ => unreachable!(),
@@ -393,7 +404,7 @@ impl BoxedABI {
     pub fn mutate_with_vm_slots<Loc, Addr, VS, S, CI>(
         &mut self,
         state: &mut S,
-        vm_slots: Option<HashMap<EVMU256, EVMU256>>,
+        vm_slots: Option<EVMStorage>,
     ) -> MutationResult
     where
         S: State + HasRand + HasMaxSize + HasItyState<Loc, Addr, VS, CI> + HasCaller<EVMAddress> + HasMetadata,
@@ -413,7 +424,16 @@ impl BoxedABI {
                     return MutationResult::Skipped;
                 }
                 if a256.is_address {
-                    if state.rand_mut().below(SAMPLE_MAX) < RANDOM_ADDRESS_CHOICE {
+                    let choice = state.rand_mut().below(SAMPLE_MAX);
+                    // addresses known to the contract, e.g., tokens, pools and roles
+                    let dictionary_address = if choice < DICTIONARY_ADDRESS_CHOICE {
+                        sample_dictionary_address(state)
+                    } else {
+                        None
+                    };
+                    if let Some(address) = dictionary_address {
+                        a256.data = address.0.to_vec();
+                    } else if choice < RANDOM_ADDRESS_CHOICE {
                         a256.data = state.get_rand_address().0.to_vec();
                     } else {
                         a256.data = [0; 20].to_vec();
@@ -435,23 +455,30 @@ impl BoxedABI {
                 let aarray = self.b.deref_mut().as_any().downcast_mut::<AArray>().unwrap();
 
                 let data_len = aarray.data.len();
-                if data_len == 0 {
+                if data_len == 0 && !(aarray.dynamic_size && aarray.element.is_some()) {
                     return MutationResult::Skipped;
                 }
                 if aarray.dynamic_size {
                     match state.rand_mut().below(SAMPLE_MAX) {
-                        0..=MUTATE_CHOICE_MAX => {
+                        0..=MUTATE_CHOICE_MAX if data_len > 0 => {
                             let index: usize = state.rand_mut().next() as usize % data_len;
                             let result = aarray.data[index].mutate_with_vm_slots(state, vm_slots);
                             return result;
                         }
-                        MUTATE_CHOICE_MAX..=EXPAND_CHOICE_MAX => {
-                            // increase size
-                            if state.max_size() <= aarray.data.len() {
+                        0..=EXPAND_CHOICE_MAX => {
+                            // increase size, with copies of the existing elements (e.g., the
+                            // structs of a `tuple[]`) or of the element type if it is empty
+                            if state.max_size() <= data_len {
                                 return MutationResult::Skipped;
                             }
-                            for _ in 0..state.rand_mut().next() as usize % state.max_size() {
-                                aarray.data.push(aarray.data[0].clone());
+                            let count = state.rand_mut().below((state.max_size() - data_len) as u64) as usize + 1;
+                            for _ in 0..count {
+                                let element = if data_len == 0 {
+                                    aarray.new_element().unwrap()
+                                } else {
+                                    aarray.data[state.rand_mut().below(data_len as u64) as usize].clone()
+                                };
+                                aarray.data.push(element);
                             }
                         }
                         EXPAND_CHOICE_MAX..=SAMPLE_MAX => {
@@ -488,6 +515,120 @@ impl BoxedABI {
             }
         }
     }
+
+    /// Simpler variants of the args, each with one element of a dynamic array
+    /// removed, one dynamic arg emptied or one static arg zeroed. Used to make
+    /// the reported exploits minimal.
+    pub fn shrink_candidates(&self) -> Vec<BoxedABI> {
+        let mut res = vec![];
+        match self.get_type() {
+            TEmpty => {}
+            T256 => {
+                let mut candidate = self.clone();
+                let a256 = candidate.b.deref_mut().as_any().downcast_mut::<A256>().unwrap();
+                if !a256.dont_mutate && a256.data.iter().any(|b| *b != 0) {
+                    a256.data.iter_mut().for_each(|b| *b = 0);
+                    res.push(candidate);
+                }
+            }
+            TDynamic => {
+                let mut candidate = self.clone();
+                let adyn = candidate.b.deref_mut().as_any().downcast_mut::<ADynamic>().unwrap();
+                if !adyn.data.is_empty() {
+                    adyn.data.clear();
+                    res.push(candidate);
+                }
+            }
+            TArray => {
+                let array = |abi: &mut BoxedABI| -> &mut AArray {
+                    abi.b.deref_mut().as_any().downcast_mut::<AArray>().unwrap()
+                };
+                let (elements, dynamic_size, has_element) = {
+                    let mut this = self.clone();
+                    let aarray = array(&mut this);
+                    (aarray.data.clone(), aarray.dynamic_size, aarray.element.is_some())
+                };
+                // keep an element, which is the template of new ones, unless the
+                // element type is known
+                let min_len = if has_element { 0 } else { 1 };
+                if dynamic_size && elements.len() > min_len {
+                    for idx in 0..elements.len() {
+                        let mut candidate = self.clone();
+                        array(&mut candidate).data.remove(idx);
+                        res.push(candidate);
+                    }
+                }
+                for (idx, element) in elements.iter().enumerate() {
+                    for shrunk in element.shrink_candidates() {
+                        let mut candidate = self.clone();
+                        array(&mut candidate).data[idx] = shrunk;
+                        res.push(candidate);
+                    }
+                }
+            }
+            TUnknown => {
+                let concrete = {
+                    let mut this = self.clone();
+                    let a_unknown = this.b.deref_mut().as_any().downcast_mut::<AUnknown>().unwrap();
+                    a_unknown.concrete.clone()
+                };
+                for shrunk in concrete.shrink_candidates() {
+                    let mut candidate = self.clone();
+                    candidate
+                        .b
+                        .deref_mut()
+                        .as_any()
+                        .downcast_mut::<AUnknown>()
+                        .unwrap()
+                        .concrete = shrunk;
+                    res.push(candidate);
+                }
+            }
+        }
+        res
+    }
+
+    /// The args with each non-empty dynamic array and bytes repeated `factor`
+    /// times, None if there is none. Used to measure how the gas of a call
+    /// grows with the size of its input.
+    pub fn grow_arrays(&self, factor: usize) -> Option<BoxedABI> {
+        let mut grown = self.clone();
+        let changed = match self.get_type() {
+            TEmpty | T256 => false,
+            TDynamic => {
+                let adyn = grown.b.deref_mut().as_any().downcast_mut::<ADynamic>().unwrap();
+                let changed = !adyn.data.is_empty();
+                adyn.data = adyn.data.repeat(factor);
+                changed
+            }
+            TArray => {
+                let aarray = grown.b.deref_mut().as_any().downcast_mut::<AArray>().unwrap();
+                let mut changed = false;
+                for element in aarray.data.iter_mut() {
+                    if let Some(element_grown) = element.grow_arrays(factor) {
+                        *element = element_grown;
+                        changed = true;
+                    }
+                }
+                if aarray.dynamic_size && !aarray.data.is_empty() {
+                    aarray.data = aarray.data.repeat(factor);
+                    changed = true;
+                }
+                changed
+            }
+            TUnknown => {
+                let a_unknown = grown.b.deref_mut().as_any().downcast_mut::<AUnknown>().unwrap();
+                match a_unknown.concrete.grow_arrays(factor) {
+                    Some(concrete) => {
+                        a_unknown.concrete = concrete;
+                        true
+                    }
+                    None => false,
+                }
+            }
+        };
+        changed.then_some(grown)
+    }
 }
 
 impl Clone for Box<dyn ABI> {
@@ -644,7 +785,7 @@ impl ABI for A256 {
                 prettify_value(value)
             }
             A256InnerType::Bool => {
-                if self.data == [0] {
+                if self.data.iter().all(|b| *b == 0) {
                     "false".to_string()
                 } else {
                     "true".to_string()
@@ -754,7 +895,10 @@ impl ABI for ADynamic {
             self.data = Vec::new();
             return true;
         }
-        self.data = bytes[32..32 + get_size(&bytes)].to_vec();
+        let Some(data) = read_size(&bytes, 0).and_then(|len| bytes.get(32..32usize.checked_add(len)?)) else {
+            return false;
+        };
+        self.data = data.to_vec();
         true
     }
 
@@ -793,6 +937,17 @@ pub struct AArray {
     /// whether the size of the array is dynamic (i.e., is it dynamic size
     /// array)
     pub(crate) dynamic_size: bool,
+    /// type of the elements of a dynamic size array, to create new elements
+    /// when it is empty (e.g., a struct of an empty `tuple[]`)
+    #[serde(default)]
+    pub(crate) element: Option<Box<BoxedABI>>,
+}
+
+impl AArray {
+    /// New element of a dynamic size array, None if its type is unknown
+    fn new_element(&self) -> Option<BoxedABI> {
+        self.data.first().cloned().or_else(|| self.element.as_deref().cloned())
+    }
 }
 
 impl Input for AArray {
@@ -902,13 +1057,18 @@ impl ABI for AArray {
     // Set the bytes in self.data accordingly
     fn set_bytes(&mut self, bytes: Vec<u8>) -> bool {
         let base_offset = if self.dynamic_size {
-            let array_size = get_size(&bytes);
-            while self.data.len() < array_size {
-                if self.data.is_empty() {
-                    self.data.push(BoxedABI::default());
-                } else {
-                    self.data.push(self.data[0].clone());
-                }
+            let Some(array_size) = read_size(&bytes, 0) else {
+                return false;
+            };
+            // each element takes at least a word in the head
+            if array_size > (bytes.len() - 32) / 32 {
+                return false;
+            }
+            if array_size > 0 {
+                let Some(element) = self.new_element() else {
+                    return false;
+                };
+                self.data.resize(array_size, element);
             }
             self.data.truncate(array_size);
             32
@@ -922,18 +1082,22 @@ impl ABI for AArray {
             let (item_offset, size) = match item.get_type() {
                 T256 => (offset, 32),
                 TArray if item.is_static() => (offset, item.b.get_size()),
-                TArray | TDynamic => (get_size(&bytes[offset + base_offset..]), 32),
+                TArray | TDynamic => match read_size(&bytes, offset + base_offset) {
+                    Some(item_offset) => (item_offset, 32),
+                    None => return false,
+                },
                 TEmpty => (0, 0),
                 TUnknown => {
                     unreachable!()
                 }
             };
 
-            let start = item_offset + base_offset;
-            if start + size > bytes.len() {
+            let Some(start) = item_offset.checked_add(base_offset) else {
+                return false;
+            };
+            if start.saturating_add(size) > bytes.len() || !item.b.set_bytes(bytes[start..].to_vec()) {
                 return false;
             }
-            item.b.set_bytes(bytes[start..].to_vec());
             offset += size;
         }
         true
@@ -1157,18 +1321,18 @@ pub fn get_abi_type(abi_name: &str, with_address: &Option<Vec<u8>>) -> Box<dyn A
                 })
                 .collect(),
             dynamic_size: false,
+            element: None,
         });
     }
     if abi_name_str.ends_with("[]") {
+        let element = BoxedABI {
+            b: get_abi_type(&abi_name[..abi_name_str.len() - 2], with_address),
+            function: [0; 4],
+        };
         return Box::new(AArray {
-            data: vec![
-                BoxedABI {
-                    b: get_abi_type(&abi_name[..abi_name_str.len() - 2], with_address),
-                    function: [0; 4]
-                };
-                1
-            ],
+            data: vec![element.clone()],
             dynamic_size: true,
+            element: Some(Box::new(element)),
         });
     } else if abi_name_str.ends_with(']') && abi_name_str.contains('[') {
         let split = abi_name_str.rsplit_once('[').unwrap();
@@ -1189,6 +1353,7 @@ pub fn get_abi_type(abi_name: &str, with_address: &Option<Vec<u8>>) -> Box<dyn A
                 len
             ],
             dynamic_size: false,
+            element: None,
         });
     }
     get_abi_type_basic(abi_name, 32, with_address)
@@ -1412,6 +1577,81 @@ mod tests {
         debug!("result: {:?} abi: {:?}", mutation_result, hex::encode(abibytes));
     }
 
+    #[test]
+    fn test_shrink_candidates() {
+        let word = |value: u8| {
+            BoxedABI::new(Box::new(A256 {
+                data: [vec![0; 31], vec![value]].concat(),
+                is_address: false,
+                dont_mutate: false,
+                inner_type: A256InnerType::Uint,
+            }))
+        };
+        let abi = BoxedABI::new(Box::new(AArray {
+            data: vec![word(5), word(0)],
+            dynamic_size: true,
+            element: None,
+        }));
+        // remove either element or zero the first one
+        let candidates = abi.shrink_candidates();
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(
+            candidates[0].get_bytes_vec(),
+            [vec![0; 31], vec![1], vec![0; 32]].concat()
+        );
+        assert_eq!(
+            candidates[2].get_bytes_vec(),
+            [vec![0; 31], vec![2], vec![0; 64]].concat()
+        );
+        assert!(word(0).shrink_candidates().is_empty());
+    }
+
+    #[test]
+    fn test_grow_arrays() {
+        let word = |value: u8| [vec![0; 31], vec![value]].concat();
+        let mut abi = get_abi_type_boxed("(uint256[],uint256)");
+        assert!(abi
+            .b
+            .set_bytes([word(0x40), word(7), word(2), word(1), word(2)].concat()));
+        let grown = abi.grow_arrays(2).unwrap();
+        assert_eq!(
+            grown.get_bytes_vec(),
+            [word(0x40), word(7), word(4), word(1), word(2), word(1), word(2)].concat()
+        );
+        assert!(get_abi_type_boxed("(uint256,address)").grow_arrays(2).is_none());
+    }
+
+    #[test]
+    fn test_nested_decoding() {
+        let word = |value: u8| [vec![0; 31], vec![value]].concat();
+        // an array of structs with a bytes[] member, and a uint256
+        let mut abi = get_abi_type_boxed("((uint256,bytes[])[],uint256)");
+        let empty = [word(0x40), word(7), word(0)].concat();
+        assert!(abi.b.set_bytes(empty.clone()));
+        assert_eq!(abi.get_bytes_vec(), empty);
+
+        // the structs are decoded from the element type of the empty array
+        let encoded = [
+            vec![word(0x40), word(7), word(2), word(0x40), word(0xa0)],
+            vec![word(1), word(0x40), word(0)],
+            vec![
+                word(2),
+                word(0x40),
+                word(1),
+                word(0x20),
+                word(1),
+                [vec![0xab], vec![0; 31]].concat(),
+            ],
+        ]
+        .concat()
+        .concat();
+        assert!(abi.b.set_bytes(encoded.clone()));
+        assert_eq!(abi.get_bytes_vec(), encoded);
+
+        assert!(!abi.b.set_bytes(encoded[..encoded.len() - 32].to_vec()));
+        assert!(!abi.b.set_bytes([word(0x40), word(7), vec![0xff; 32]].concat()));
+    }
+
     #[test]
     fn test_100_times() {
         for _ in 0..100 {
@@ -19,7 +19,11 @@ use tracing::{debug, error};
 
 use crate::{
     cache::{Cache, FileSystemCache},
-    evm::{blaz::get_client, srcmap::SOURCE_MAP_PROVIDER, types::EVMAddress},
+    evm::{
+        blaz::{get_client, storage_layout::StorageLayout},
+        srcmap::SOURCE_MAP_PROVIDER,
+        types::EVMAddress,
+    },
 };
 
 #[derive(Clone, Debug)]
@@ -157,6 +161,8 @@ pub struct BuildJobResult {
     pub source_maps_replacements: Vec<(String, String)>,
     /// (file name, AST object)
     pub asts: Vec<(String, Value)>,
+    #[serde(default)]
+    pub storage_layout: Option<StorageLayout>,
 }
 
 impl BuildJobResult {
@@ -167,6 +173,7 @@ impl BuildJobResult {
         abi: String,
         replacements: Vec<(String, String)>,
         asts: Vec<(String, Value)>,
+        storage_layout: Option<StorageLayout>,
     ) -> Self {
         Self {
             sources,
@@ -175,6 +182,7 @@ impl BuildJobResult {
             abi,
             source_maps_replacements: replacements,
             asts,
+            storage_layout,
         }
     }
 
@@ -231,6 +239,7 @@ impl BuildJobResult {
             abi: abi.to_string(),
             source_maps_replacements: sourcemap_replacements,
             asts,
+            storage_layout: StorageLayout::from_json(&json["storage_layout"]),
         })
     }
 
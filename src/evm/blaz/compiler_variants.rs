@@ -0,0 +1,141 @@
+//! Builds of the targets with other compiler configurations, e.g., another
+//! solc version (`forge build --use 0.8.19`) or the IR pipeline (`--via-ir`),
+//! each contract being matched to its counterpart in the main build, so that
+//! the differential oracle catches compiler-induced semantic changes.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use bytes::Bytes;
+use tracing::warn;
+
+use crate::evm::{
+    blaz::offchain_artifacts::{ContractArtifact, OffChainArtifact},
+    contract_utils::{ContractInfo, ContractLoader},
+    types::EVMAddress,
+};
+
+#[derive(Clone, Debug)]
+pub struct CompilerVariant {
+    /// Flags appended to the build command
+    pub flags: String,
+    /// Init code (with the constructor args) of the contracts in this build,
+    /// by deployed address
+    pub init_codes: HashMap<EVMAddress, Bytes>,
+}
+
+/// Parse the compiler configurations separated by comma, each being the
+/// flags appended to the build command
+pub fn parse_compiler_variants(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|flags| !flags.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Init code of the contract, None if it still has library placeholders
+fn init_code(artifact: &ContractArtifact) -> Option<Vec<u8>> {
+    if !artifact.deploy_bytecode.is_empty() {
+        return Some(artifact.deploy_bytecode.to_vec());
+    }
+    hex::decode(artifact.deploy_bytecode_str.trim_start_matches("0x")).ok()
+}
+
+/// Build command of the variant, writing the artifacts and the cache of forge
+/// to `dir` so that the build of the project is left untouched
+fn variant_command(command: &str, flags: &str, dir: &Path) -> String {
+    let command = format!("{} {}", command, flags);
+    if !command.trim_start().starts_with("forge ") {
+        return command;
+    }
+    format!(
+        "{} --out {} --cache-path {}",
+        command,
+        dir.join("out").display(),
+        dir.join("cache").display()
+    )
+}
+
+impl CompilerVariant {
+    /// Build the targets with `flags` appended to `command` in a temporary
+    /// directory, the contracts being located in the main build
+    /// `main_artifacts`
+    pub fn build(
+        command: &str,
+        flags: &str,
+        main_artifacts: &[OffChainArtifact],
+        contracts: &[ContractInfo],
+    ) -> Result<Self, String> {
+        let mut hasher = DefaultHasher::new();
+        flags.hash(&mut hasher);
+        let dir = std::env::temp_dir().join(format!("ityfuzz_variant_{}_{:x}", std::process::id(), hasher.finish()));
+        let artifacts = OffChainArtifact::from_command(variant_command(command, flags, &dir));
+        let _ = std::fs::remove_dir_all(&dir);
+        let artifacts = artifacts.map_err(|e| format!("failed to build with {}: {}", flags, e))?;
+
+        let mut init_codes = HashMap::new();
+        // the contracts deployed by a setup script would need the script to be run
+        // again with the variant build
+        for contract in contracts.iter().filter(|contract| !contract.is_code_deployed) {
+            let (_, slug) = ContractLoader::find_contract_artifact(contract.code.clone(), main_artifacts);
+            let Some(code) = artifacts
+                .iter()
+                .find_map(|artifact| artifact.contracts.get(&slug))
+                .and_then(init_code)
+            else {
+                warn!("{}:{} is not built with {}, skipped", slug.0, slug.1, flags);
+                continue;
+            };
+            init_codes.insert(
+                contract.deployed_address,
+                Bytes::from([code, contract.constructor_args.clone()].concat()),
+            );
+        }
+        Ok(Self {
+            flags: flags.to_string(),
+            init_codes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compiler_variants() {
+        assert_eq!(
+            parse_compiler_variants("--via-ir, --use 0.8.19 --optimize,"),
+            vec!["--via-ir".to_string(), "--use 0.8.19 --optimize".to_string()]
+        );
+
+        let mut artifact = ContractArtifact {
+            deploy_bytecode_str: "0x6080".to_string(),
+            deploy_bytecode: Bytes::new(),
+            lib_address: Default::default(),
+            abi: "[]".to_string(),
+            source_map: String::new(),
+            link_references: Default::default(),
+            source_map_replacements: vec![],
+            storage_layout: None,
+        };
+        assert_eq!(init_code(&artifact), Some(vec![0x60, 0x80]));
+        artifact.deploy_bytecode_str = "6080__$a1b2$__".to_string();
+        assert_eq!(init_code(&artifact), None);
+    }
+
+    #[test]
+    fn test_variant_command() {
+        let dir = Path::new("/tmp/variant");
+        assert_eq!(
+            variant_command("forge build", "--via-ir", dir),
+            "forge build --via-ir --out /tmp/variant/out --cache-path /tmp/variant/cache"
+        );
+        // solc already writes its output to a temporary directory
+        assert_eq!(variant_command("solc *.sol", "--via-ir", dir), "solc *.sol --via-ir");
+    }
+}
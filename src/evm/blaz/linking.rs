@@ -14,7 +14,10 @@ use serde_json::{Map, Value};
 use tracing::debug;
 
 use crate::evm::{
-    blaz::offchain_artifacts::{ContractArtifact, OffChainArtifact},
+    blaz::{
+        offchain_artifacts::{ContractArtifact, OffChainArtifact},
+        storage_layout::StorageLayout,
+    },
     contract_utils::compute_address,
 };
 
@@ -343,6 +346,7 @@ impl Linker {
                         source_map,
                         link_references: Default::default(),
                         source_map_replacements: vec![],
+                        storage_layout: contract.get("storage-layout").and_then(StorageLayout::from_json),
                     },
                 );
             } else {
@@ -370,6 +374,7 @@ impl Linker {
                             source_map,
                             link_references,
                             source_map_replacements: vec![],
+                            storage_layout: contract.get("storageLayout").and_then(StorageLayout::from_json),
                         },
                     );
                 }
@@ -11,9 +11,11 @@ use crate::{
 };
 
 pub mod builder;
+pub mod compiler_variants;
 pub(crate) mod linking;
 pub mod offchain_artifacts;
 pub mod offchain_config;
+pub mod storage_layout;
 
 fn get_client() -> reqwest::blocking::Client {
     reqwest::blocking::Client::builder()
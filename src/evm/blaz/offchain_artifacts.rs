@@ -10,7 +10,7 @@ use itertools::Itertools;
 use serde::Deserialize;
 use serde_json::{Map, Value};
 
-use crate::evm::blaz::{builder::BuildJobResult, get_client};
+use crate::evm::blaz::{builder::BuildJobResult, get_client, storage_layout::StorageLayout};
 
 // #[derive(Clone, Debug)]
 // pub struct ContractArtifact {
@@ -35,6 +35,7 @@ pub struct ContractArtifact {
     pub source_map: String,
     pub link_references: BTreeMap<String, BTreeMap<String, Vec<LinkReference>>>,
     pub source_map_replacements: Vec<(String, String)>,
+    pub storage_layout: Option<StorageLayout>,
 }
 
 #[derive(Clone, Debug)]
@@ -148,6 +149,7 @@ impl OffChainArtifact {
                         link_references: Default::default(),
                         source_map_replacements,
                         lib_address: Default::default(),
+                        storage_layout: None,
                     },
                 );
             }
@@ -435,6 +437,7 @@ impl OffChainArtifact {
                         link_references: Default::default(),
                         source_map_replacements: vec![],
                         lib_address: Default::default(),
+                        storage_layout: contract.get("storage-layout").and_then(StorageLayout::from_json),
                     },
                 );
             } else {
@@ -463,6 +466,7 @@ impl OffChainArtifact {
                             link_references,
                             source_map_replacements: vec![],
                             lib_address: BTreeMap::default(),
+                            storage_layout: contract.get("storageLayout").and_then(StorageLayout::from_json),
                         },
                     );
                 }
@@ -0,0 +1,360 @@
+//! Storage layouts from the solc output (`storageLayout`), to show the
+//! storage changed by an exploit as variables instead of raw slots
+//!
+//! Slots of mappings and dynamic arrays are hashes, so they are resolved by
+//! trying the addresses of the state and small integers as keys.
+
+use std::collections::{BTreeSet, HashMap};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    evm::{
+        onchain::keccak256,
+        types::{checksum, EVMAddress, EVMU256},
+        vm::{EVMState, EVMStorage},
+    },
+    r#const::{STORAGE_LAYOUT_ARRAY_MAX, STORAGE_LAYOUT_MAPPING_DEPTH, STORAGE_LAYOUT_UINT_KEYS},
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StorageVariable {
+    pub label: String,
+    /// Offset in bytes within the slot
+    pub offset: usize,
+    pub slot: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StorageType {
+    /// `inplace`, `mapping`, `dynamic_array` or `bytes`
+    pub encoding: String,
+    pub label: String,
+    #[serde(rename = "numberOfBytes")]
+    pub number_of_bytes: String,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Type of the elements of an array
+    #[serde(default)]
+    pub base: Option<String>,
+}
+
+impl StorageType {
+    fn size(&self) -> usize {
+        self.number_of_bytes.parse().unwrap_or(32)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StorageLayout {
+    pub storage: Vec<StorageVariable>,
+    #[serde(default)]
+    pub types: Option<HashMap<String, StorageType>>,
+}
+
+/// Part of a slot holding (a part of) a variable
+struct Location<'a> {
+    label: String,
+    /// Type of the value, None if the slot only holds a part of it
+    ty: Option<&'a StorageType>,
+    offset: usize,
+}
+
+fn format_value(ty: &StorageType, value: EVMU256) -> String {
+    let label = ty.label.as_str();
+    if label == "bool" {
+        return (value != EVMU256::ZERO).to_string();
+    }
+    if label.starts_with("address") || label.starts_with("contract ") {
+        return checksum(&EVMAddress::from_slice(&value.to_be_bytes::<32>()[12..]));
+    }
+    if label.starts_with("uint") || label.starts_with("enum ") {
+        return value.to_string();
+    }
+    if label.starts_with("int") {
+        let bits = ty.size() * 8;
+        if bits < 256 && value.bit(bits - 1) {
+            let negative = (EVMU256::MAX << bits | value).wrapping_neg();
+            return format!("-{}", negative);
+        }
+        if bits == 256 && value.bit(255) {
+            return format!("-{}", value.wrapping_neg());
+        }
+        return value.to_string();
+    }
+    format!("0x{:x}", value)
+}
+
+impl StorageLayout {
+    /// Parse the `storageLayout` of a contract of the solc output, given as an
+    /// object or as its JSON string by older versions of solc
+    pub fn from_json(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => serde_json::from_str(s).ok(),
+            Value::Object(_) => serde_json::from_value(value.clone()).ok(),
+            _ => None,
+        }
+    }
+
+    fn get_type(&self, id: &str) -> Option<&StorageType> {
+        self.types.as_ref()?.get(id)
+    }
+
+    /// Locations of the variables (of type `ty`, at `base`) held by the slot
+    #[allow(clippy::too_many_arguments)]
+    fn locate_in<'a>(
+        &'a self,
+        label: String,
+        ty: &'a StorageType,
+        base: EVMU256,
+        offset: usize,
+        slot: EVMU256,
+        keys: &[EVMU256],
+        depth: usize,
+        res: &mut Vec<Location<'a>>,
+    ) {
+        match ty.encoding.as_str() {
+            "inplace" => {
+                let words = ty.size().div_ceil(32);
+                if slot < base || slot - base >= EVMU256::from(words) {
+                    return;
+                }
+                if words == 1 {
+                    res.push(Location {
+                        label,
+                        ty: Some(ty),
+                        offset,
+                    });
+                } else {
+                    res.push(Location {
+                        label: format!("{} (word {})", label, slot - base),
+                        ty: None,
+                        offset: 0,
+                    });
+                }
+            }
+            "mapping" => {
+                let (Some(key_ty), Some(value_ty)) = (
+                    ty.key.as_deref().and_then(|id| self.get_type(id)),
+                    ty.value.as_deref().and_then(|id| self.get_type(id)),
+                ) else {
+                    return;
+                };
+                if depth >= STORAGE_LAYOUT_MAPPING_DEPTH || key_ty.encoding != "inplace" {
+                    return;
+                }
+                for key in keys {
+                    let key_slot = keccak256(&[key.to_be_bytes::<32>(), base.to_be_bytes::<32>()].concat());
+                    let label = format!("{}[{}]", label, format_value(key_ty, *key));
+                    self.locate_in(label, value_ty, key_slot, 0, slot, keys, depth + 1, res);
+                }
+            }
+            "dynamic_array" => {
+                if slot == base {
+                    res.push(Location {
+                        label: format!("{}.length", label),
+                        ty: None,
+                        offset: 0,
+                    });
+                    return;
+                }
+                let Some(elem_ty) = ty.base.as_deref().and_then(|id| self.get_type(id)) else {
+                    return;
+                };
+                let data = keccak256(&base.to_be_bytes::<32>());
+                if slot < data || slot - data >= EVMU256::from(STORAGE_LAYOUT_ARRAY_MAX) {
+                    return;
+                }
+                let word = (slot - data).as_limbs()[0] as usize;
+                let elem_size = elem_ty.size();
+                if elem_ty.encoding == "inplace" && elem_size < 32 {
+                    // packed elements
+                    let per_word = 32 / elem_size;
+                    for idx in 0..per_word {
+                        res.push(Location {
+                            label: format!("{}[{}]", label, word * per_word + idx),
+                            ty: Some(elem_ty),
+                            offset: idx * elem_size,
+                        });
+                    }
+                    return;
+                }
+                let words = elem_size.div_ceil(32);
+                let idx = word / words;
+                let elem_base = data + EVMU256::from(idx * words);
+                let label = format!("{}[{}]", label, idx);
+                self.locate_in(label, elem_ty, elem_base, 0, slot, keys, depth + 1, res);
+            }
+            "bytes" => {
+                // short values are stored with their length, long ones from the hash
+                let data = keccak256(&base.to_be_bytes::<32>());
+                if slot == base || (slot >= data && slot - data < EVMU256::from(STORAGE_LAYOUT_ARRAY_MAX)) {
+                    res.push(Location {
+                        label,
+                        ty: None,
+                        offset: 0,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn locate(&self, slot: EVMU256, keys: &[EVMU256]) -> Vec<Location<'_>> {
+        let mut res = vec![];
+        for var in &self.storage {
+            let (Some(ty), Ok(base)) = (self.get_type(&var.ty), EVMU256::from_str_radix(&var.slot, 10)) else {
+                continue;
+            };
+            self.locate_in(var.label.clone(), ty, base, var.offset, slot, keys, 0, &mut res);
+        }
+        res
+    }
+
+    /// Describe the change of the slot from `old` to `new`, one line per
+    /// variable changed
+    pub fn describe_change(&self, slot: EVMU256, old: EVMU256, new: EVMU256, keys: &[EVMU256]) -> Vec<String> {
+        let locations = self.locate(slot, keys);
+        if locations.is_empty() {
+            return vec![format!("slot 0x{:x}: 0x{:x} -> 0x{:x}", slot, old, new)];
+        }
+        locations
+            .into_iter()
+            .filter_map(|location| {
+                let Some(ty) = location.ty else {
+                    return Some(format!("{}: 0x{:x} -> 0x{:x}", location.label, old, new));
+                };
+                let bits = ty.size() * 8;
+                let extract = |value: EVMU256| {
+                    let value = value >> (location.offset * 8);
+                    if bits >= 256 {
+                        value
+                    } else {
+                        value & ((EVMU256::from(1) << bits) - EVMU256::from(1))
+                    }
+                };
+                let (old, new) = (extract(old), extract(new));
+                (old != new).then(|| {
+                    format!(
+                        "{}: {} -> {}",
+                        location.label,
+                        format_value(ty, old),
+                        format_value(ty, new)
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Candidate keys of the mappings: the addresses of the states and small
+/// integers
+fn candidate_keys(pre: &EVMState, post: &EVMState) -> Vec<EVMU256> {
+    let addresses = [pre, post]
+        .iter()
+        .flat_map(|state| state.state.keys().chain(state.balance.keys()))
+        .map(|address| EVMU256::from_be_slice(&address.0))
+        .collect::<BTreeSet<_>>();
+    (0..STORAGE_LAYOUT_UINT_KEYS)
+        .map(EVMU256::from)
+        .chain(addresses)
+        .unique()
+        .collect()
+}
+
+/// Storage changed from `pre` to `post`, decoded with the layouts of the
+/// contracts, one line per variable changed
+pub fn storage_diff(pre: &EVMState, post: &EVMState, layouts: &HashMap<EVMAddress, StorageLayout>) -> Vec<String> {
+    let keys = candidate_keys(pre, post);
+    let empty = EVMStorage::new();
+    let mut res = vec![];
+    for address in pre.state.keys().chain(post.state.keys()).unique().sorted() {
+        let (before, after) = (
+            pre.state.get(address).unwrap_or(&empty),
+            post.state.get(address).unwrap_or(&empty),
+        );
+        for slot in before.keys().chain(after.keys()).unique().sorted() {
+            let old = before.get(slot).cloned().unwrap_or_default();
+            let new = after.get(slot).cloned().unwrap_or_default();
+            if old == new {
+                continue;
+            }
+            let changes = match layouts.get(address) {
+                Some(layout) => layout.describe_change(*slot, old, new, &keys),
+                None => vec![format!("slot 0x{:x}: 0x{:x} -> 0x{:x}", slot, old, new)],
+            };
+            res.extend(
+                changes
+                    .into_iter()
+                    .map(|change| format!("{}.{}", checksum(address), change)),
+            );
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_diff() {
+        let layout = StorageLayout::from_json(&serde_json::json!({
+            "storage": [
+                {"label": "owner", "offset": 0, "slot": "0", "type": "t_address"},
+                {"label": "paused", "offset": 20, "slot": "0", "type": "t_bool"},
+                {"label": "balances", "offset": 0, "slot": "1", "type": "t_mapping(t_address,t_uint256)"},
+            ],
+            "types": {
+                "t_address": {"encoding": "inplace", "label": "address", "numberOfBytes": "20"},
+                "t_bool": {"encoding": "inplace", "label": "bool", "numberOfBytes": "1"},
+                "t_uint256": {"encoding": "inplace", "label": "uint256", "numberOfBytes": "32"},
+                "t_mapping(t_address,t_uint256)": {
+                    "encoding": "mapping", "key": "t_address", "label": "mapping(address => uint256)",
+                    "numberOfBytes": "32", "value": "t_uint256"
+                }
+            }
+        }))
+        .unwrap();
+
+        let contract = EVMAddress::from_slice(&[0x10; 20]);
+        let holder = EVMAddress::from_slice(&[0x20; 20]);
+        let holder_key = EVMU256::from_be_slice(&holder.0);
+        let balance_slot = keccak256(&[holder_key.to_be_bytes::<32>(), EVMU256::from(1).to_be_bytes::<32>()].concat());
+
+        let mut pre = EVMState::default();
+        pre.balance.insert(holder, EVMU256::ZERO);
+        let mut post = EVMState::default();
+        post.insert(
+            contract,
+            HashMap::from([
+                (EVMU256::ZERO, EVMU256::from(1) << 160 | holder_key),
+                (balance_slot, EVMU256::from(7)),
+                (EVMU256::from(5), EVMU256::from(1)),
+            ]),
+        );
+
+        let diff = storage_diff(&pre, &post, &HashMap::from([(contract, layout)]));
+        let prefix = checksum(&contract);
+        assert_eq!(
+            diff,
+            vec![
+                format!(
+                    "{}.owner: {} -> {}",
+                    prefix,
+                    checksum(&EVMAddress::zero()),
+                    checksum(&holder)
+                ),
+                format!("{}.paused: false -> true", prefix),
+                format!("{}.slot 0x5: 0x0 -> 0x1", prefix),
+                format!("{}.balances[{}]: 0 -> 7", prefix, checksum(&holder)),
+            ]
+        );
+    }
+}
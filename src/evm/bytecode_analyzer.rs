@@ -1,12 +1,14 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use libafl::state::{HasMetadata, State};
-use revm_interpreter::opcode::JUMPI;
+use libafl_bolts::impl_serdeany;
+use revm_interpreter::opcode::{JUMP, JUMPDEST, JUMPI, SLOAD, SSTORE};
 use revm_primitives::Bytecode;
+use serde::{Deserialize, Serialize};
 
-use crate::evm::bytecode_iterator::all_bytecode;
+use crate::evm::{bytecode_iterator::all_bytecode, types::EVMU256};
 /// Analysis passes for EVM bytecode
-use crate::mutation_utils::ConstantPoolMetadata;
+use crate::{mutation_utils::ConstantPoolMetadata, r#const::CALL_ORDER_MAX_OPS};
 
 /// Find all constants in the bytecode by observing PUSH instructions.
 ///
@@ -46,6 +48,17 @@ pub fn add_analysis_result_to_state<S>(bytecode: &Bytecode, state: &mut S)
 where
     S: HasMetadata + State,
 {
+    let hints = find_call_order_hints(bytecode);
+    if !hints.is_empty() {
+        if !state.has_metadata::<CallOrderMetadata>() {
+            state.metadata_map_mut().insert(CallOrderMetadata::default());
+        }
+        let meta = state.metadata_map_mut().get_mut::<CallOrderMetadata>().unwrap();
+        for (before, after) in hints {
+            meta.add(before, after);
+        }
+    }
+
     let constants = find_constants(bytecode);
     match state.metadata_map_mut().get_mut::<ConstantPoolMetadata>() {
         Some(meta) => {
@@ -63,6 +76,359 @@ where
     }
 }
 
+/// Call order hints found by static analysis, i.e., functions that a
+/// function likely enables when called before them
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CallOrderMetadata {
+    /// Function selector -> selectors of functions likely to follow it
+    pub successors: HashMap<[u8; 4], Vec<[u8; 4]>>,
+}
+
+impl_serdeany!(CallOrderMetadata);
+
+impl CallOrderMetadata {
+    pub fn add(&mut self, before: [u8; 4], after: [u8; 4]) {
+        let successors = self.successors.entry(before).or_default();
+        if !successors.contains(&after) {
+            successors.push(after);
+        }
+    }
+}
+
+/// Abstract value on the stack during [`find_call_order_hints`]
+#[derive(Clone, Copy, PartialEq)]
+enum AbstractValue {
+    Unknown,
+    Const(EVMU256),
+    /// Derived from the value of a constant storage slot
+    Storage(EVMU256),
+}
+
+/// Number of stack items (popped, pushed) by an opcode
+fn stack_io(op: u8) -> (usize, usize) {
+    match op {
+        0x01..=0x07 | 0x0a..=0x0b | 0x10..=0x14 | 0x16..=0x18 | 0x1a..=0x1d | 0x20 => (2, 1),
+        0x08 | 0x09 => (3, 1),
+        0x15 | 0x19 | 0x31 | 0x35 | 0x3b | 0x3f | 0x40 | 0x49 | 0x51 | 0x54 | 0x5c => (1, 1),
+        0x30 | 0x32..=0x34 | 0x36 | 0x38 | 0x3a | 0x3d | 0x41..=0x48 | 0x4a | 0x58..=0x5a => (0, 1),
+        0x37 | 0x39 | 0x3e | 0x5e => (3, 0),
+        0x3c => (4, 0),
+        0x50 | 0x56 | 0xff => (1, 0),
+        0x52 | 0x53 | 0x55 | 0x57 | 0x5d | 0xf3 | 0xfd => (2, 0),
+        0xa0..=0xa4 => (op as usize - 0x9e, 0),
+        0xf0 => (3, 1),
+        0xf1 | 0xf2 => (7, 1),
+        0xf4 | 0xfa => (6, 1),
+        0xf5 => (4, 1),
+        _ => (0, 0),
+    }
+}
+
+/// Value pushed by the PUSH instruction at `pc`
+fn push_value(bytes: &[u8], pc: usize, op: u8) -> EVMU256 {
+    let end = (pc + 1 + op as usize - 0x5f).min(bytes.len());
+    EVMU256::try_from_be_slice(&bytes[(pc + 1).min(end)..end]).unwrap_or_default()
+}
+
+/// Storage slots (guarded, written) by the code reachable from `entry`
+fn analyze_function(
+    bytes: &[u8],
+    ops: &[(usize, u8)],
+    pc_to_idx: &HashMap<usize, usize>,
+    entry: usize,
+) -> (HashSet<EVMU256>, HashSet<EVMU256>) {
+    let (mut guards, mut writes) = (HashSet::new(), HashSet::new());
+    let is_jumpdest = |pc: EVMU256| -> Option<usize> {
+        if pc >= EVMU256::from(bytes.len()) {
+            return None;
+        }
+        let idx = *pc_to_idx.get(&(pc.as_limbs()[0] as usize))?;
+        (ops[idx].1 == JUMPDEST).then_some(idx)
+    };
+    let pop = |stack: &mut Vec<AbstractValue>| stack.pop().unwrap_or(AbstractValue::Unknown);
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from([entry]);
+    let mut analyzed_ops = 0;
+    while let Some(start) = queue.pop_front() {
+        if !visited.insert(start) {
+            continue;
+        }
+        // the stack at the start of a block is unknown
+        let mut stack: Vec<AbstractValue> = vec![];
+
+        for &(pc, op) in &ops[start..] {
+            analyzed_ops += 1;
+            if analyzed_ops > CALL_ORDER_MAX_OPS {
+                return (guards, writes);
+            }
+            if op == JUMPDEST && pc != ops[start].0 {
+                // fall through to the next block
+                queue.push_back(pc_to_idx[&pc]);
+                break;
+            }
+            match op {
+                0x5f..=0x7f => {
+                    let value = push_value(bytes, pc, op);
+                    // internal functions return to pushed jump destinations
+                    if let Some(idx) = is_jumpdest(value) {
+                        queue.push_back(idx);
+                    }
+                    stack.push(AbstractValue::Const(value));
+                }
+                0x80..=0x8f => {
+                    let n = (op - 0x7f) as usize;
+                    let value = if stack.len() >= n {
+                        stack[stack.len() - n]
+                    } else {
+                        AbstractValue::Unknown
+                    };
+                    stack.push(value);
+                }
+                0x90..=0x9f => {
+                    let n = (op - 0x8f) as usize;
+                    if stack.len() > n {
+                        let len = stack.len();
+                        stack.swap(len - 1, len - 1 - n);
+                    } else {
+                        stack.clear();
+                    }
+                }
+                SLOAD => {
+                    let value = match pop(&mut stack) {
+                        AbstractValue::Const(slot) => AbstractValue::Storage(slot),
+                        _ => AbstractValue::Unknown,
+                    };
+                    stack.push(value);
+                }
+                SSTORE => {
+                    if let AbstractValue::Const(slot) = pop(&mut stack) {
+                        writes.insert(slot);
+                    }
+                    pop(&mut stack);
+                }
+                JUMP => {
+                    if let AbstractValue::Const(dest) = pop(&mut stack) {
+                        if let Some(idx) = is_jumpdest(dest) {
+                            queue.push_back(idx);
+                        }
+                    }
+                    break;
+                }
+                JUMPI => {
+                    let dest = pop(&mut stack);
+                    if let AbstractValue::Storage(slot) = pop(&mut stack) {
+                        guards.insert(slot);
+                    }
+                    if let AbstractValue::Const(dest) = dest {
+                        if let Some(idx) = is_jumpdest(dest) {
+                            queue.push_back(idx);
+                        }
+                    }
+                }
+                // STOP, RETURN, REVERT, INVALID, SELFDESTRUCT
+                0x00 | 0xf3 | 0xfd | 0xfe | 0xff => break,
+                _ => {
+                    let (pops, pushes) = stack_io(op);
+                    // comparisons and arithmetic on a storage value keep it tracked
+                    let mut derived = AbstractValue::Unknown;
+                    for _ in 0..pops {
+                        if let AbstractValue::Storage(slot) = pop(&mut stack) {
+                            derived = AbstractValue::Storage(slot);
+                        }
+                    }
+                    for _ in 0..pushes {
+                        stack.push(if op <= 0x1d { derived } else { AbstractValue::Unknown });
+                    }
+                }
+            }
+        }
+    }
+    (guards, writes)
+}
+
+/// Find the storage slots the bytecode loads with constant indices (`PUSH
+/// slot SLOAD`), e.g., state variables that are not mappings or arrays.
+pub fn find_constant_slots(bytecode: &Bytecode) -> HashSet<EVMU256> {
+    let bytes = bytecode.bytes().to_vec();
+    let ops = all_bytecode(&bytes);
+    ops.windows(2)
+        .filter_map(|window| match window {
+            [(pc, op @ 0x60..=0x7f), (_, SLOAD)] => Some(push_value(&bytes, *pc, *op)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Find call order hints in the bytecode, i.e., (f, g) such that f writes a
+/// storage slot that g checks before proceeding (e.g., `require(stage ==
+/// Stage.Open)`), so g is likely only valid after f is called.
+pub fn find_call_order_hints(bytecode: &Bytecode) -> Vec<([u8; 4], [u8; 4])> {
+    let bytes = bytecode.bytes().to_vec();
+    if bytes.is_empty() {
+        return vec![];
+    }
+    let ops = all_bytecode(&bytes);
+    let pc_to_idx: HashMap<usize, usize> = ops.iter().enumerate().map(|(idx, (pc, _))| (*pc, idx)).collect();
+
+    // function dispatcher: PUSH4 selector EQ PUSH dest JUMPI
+    let mut functions = vec![];
+    for window in ops.windows(4) {
+        if let [(pc, 0x63), (_, 0x14), (dest_pc, dest_op @ (0x60 | 0x61)), (_, JUMPI)] = window {
+            if *pc + 5 > bytes.len() {
+                continue;
+            }
+            let mut selector = [0u8; 4];
+            selector.copy_from_slice(&bytes[pc + 1..pc + 5]);
+            let dest = push_value(&bytes, *dest_pc, *dest_op).as_limbs()[0] as usize;
+            if let Some(&idx) = pc_to_idx.get(&dest) {
+                if ops[idx].1 == JUMPDEST {
+                    functions.push((selector, idx));
+                }
+            }
+        }
+    }
+
+    let analyzed = functions
+        .iter()
+        .map(|(selector, entry)| (*selector, analyze_function(&bytes, &ops, &pc_to_idx, *entry)))
+        .collect::<Vec<_>>();
+
+    let mut hints = vec![];
+    for (before, (_, writes)) in &analyzed {
+        for (after, (guards, _)) in &analyzed {
+            if before != after && !writes.is_disjoint(guards) {
+                hints.push((*before, *after));
+            }
+        }
+    }
+    hints
+}
+
+/// Account a branch compares the caller to, e.g., in `onlyOwner` modifiers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallerGuard {
+    /// `msg.sender == address`
+    Address(EVMU256),
+    /// `msg.sender == owner`, with the owner read from a constant storage slot
+    Storage(EVMU256),
+}
+
+/// Abstract value on the stack during [`find_caller_guards`]
+#[derive(Clone, Copy, PartialEq)]
+enum CallerValue {
+    Unknown,
+    Const(EVMU256),
+    Storage(EVMU256),
+    Caller,
+    /// Whether the caller is an account
+    CallerIs(CallerGuard),
+}
+
+/// Branches (pc of the JUMPI) taken depending on whether the caller is a given
+/// account. Only comparisons made in the block of the branch are found.
+pub fn find_caller_guards(bytecode: &Bytecode) -> HashMap<usize, CallerGuard> {
+    let bytes = bytecode.bytes().to_vec();
+    let mut guards = HashMap::new();
+    let mut stack: Vec<CallerValue> = vec![];
+    let pop = |stack: &mut Vec<CallerValue>| stack.pop().unwrap_or(CallerValue::Unknown);
+    for (pc, op) in all_bytecode(&bytes) {
+        match op {
+            JUMPDEST => stack.clear(),
+            0x5f..=0x7f => stack.push(CallerValue::Const(push_value(&bytes, pc, op))),
+            0x80..=0x8f => {
+                let n = (op - 0x7f) as usize;
+                let value = if stack.len() >= n {
+                    stack[stack.len() - n]
+                } else {
+                    CallerValue::Unknown
+                };
+                stack.push(value);
+            }
+            0x90..=0x9f => {
+                let n = (op - 0x8f) as usize;
+                if stack.len() > n {
+                    let len = stack.len();
+                    stack.swap(len - 1, len - 1 - n);
+                } else {
+                    stack.clear();
+                }
+            }
+            // CALLER
+            0x33 => stack.push(CallerValue::Caller),
+            SLOAD => {
+                let value = match pop(&mut stack) {
+                    CallerValue::Const(slot) => CallerValue::Storage(slot),
+                    _ => CallerValue::Unknown,
+                };
+                stack.push(value);
+            }
+            // SUB, SHL: address masks are computed as (1 << 160) - 1
+            0x03 | 0x1b => {
+                let (a, b) = (pop(&mut stack), pop(&mut stack));
+                stack.push(match (op, a, b) {
+                    (0x03, CallerValue::Const(a), CallerValue::Const(b)) => CallerValue::Const(a.wrapping_sub(b)),
+                    (0x1b, CallerValue::Const(shift), CallerValue::Const(value)) if shift < EVMU256::from(256) => {
+                        CallerValue::Const(value << shift.as_limbs()[0] as usize)
+                    }
+                    _ => CallerValue::Unknown,
+                });
+            }
+            // AND: masking keeps the masked value
+            0x16 => {
+                let (a, b) = (pop(&mut stack), pop(&mut stack));
+                stack.push(match (a, b) {
+                    (CallerValue::Const(a), CallerValue::Const(b)) => CallerValue::Const(a & b),
+                    (CallerValue::Const(_), value) | (value, CallerValue::Const(_)) => value,
+                    _ => CallerValue::Unknown,
+                });
+            }
+            // EQ
+            0x14 => {
+                let (a, b) = (pop(&mut stack), pop(&mut stack));
+                stack.push(match (a, b) {
+                    (CallerValue::Caller, CallerValue::Const(account)) |
+                    (CallerValue::Const(account), CallerValue::Caller) => {
+                        CallerValue::CallerIs(CallerGuard::Address(account))
+                    }
+                    (CallerValue::Caller, CallerValue::Storage(slot)) |
+                    (CallerValue::Storage(slot), CallerValue::Caller) => {
+                        CallerValue::CallerIs(CallerGuard::Storage(slot))
+                    }
+                    _ => CallerValue::Unknown,
+                });
+            }
+            // ISZERO
+            0x15 => {
+                let value = match pop(&mut stack) {
+                    value @ CallerValue::CallerIs(_) => value,
+                    _ => CallerValue::Unknown,
+                };
+                stack.push(value);
+            }
+            JUMPI => {
+                pop(&mut stack);
+                if let CallerValue::CallerIs(guard) = pop(&mut stack) {
+                    guards.insert(pc, guard);
+                }
+                stack.clear();
+            }
+            // JUMP, STOP, RETURN, REVERT, INVALID, SELFDESTRUCT
+            JUMP | 0x00 | 0xf3 | 0xfd | 0xfe | 0xff => stack.clear(),
+            _ => {
+                let (pops, pushes) = stack_io(op);
+                for _ in 0..pops {
+                    pop(&mut stack);
+                }
+                for _ in 0..pushes {
+                    stack.push(CallerValue::Unknown);
+                }
+            }
+        }
+    }
+    guards
+}
+
 #[cfg(test)]
 mod tests {
     use bytes::Bytes;
@@ -79,4 +445,50 @@ mod tests {
         let constants = find_constants(&bytecode);
         debug!("{:?}", constants.iter().map(hex::encode).collect::<Vec<String>>());
     }
+
+    #[test]
+    fn test_find_call_order_hints() {
+        // 0xaaaaaaaa: stage = 1
+        // 0xbbbbbbbb: require(stage == 1)
+        let bytecode = Bytecode::new_raw(Bytes::from(
+            hex::decode(concat!(
+                "60003560e01c",
+                "8063aaaaaaaa14601b57",
+                "8063bbbbbbbb14602257",
+                "00",
+                "5b600160005500",
+                "5b600054600114603057600080fd",
+                "5b00"
+            ))
+            .unwrap(),
+        ));
+        let hints = find_call_order_hints(&bytecode);
+        assert_eq!(hints, vec![([0xaa; 4], [0xbb; 4])]);
+    }
+
+    #[test]
+    fn test_find_constant_slots() {
+        // SLOAD(0), SLOAD(3), SLOAD(CALLDATALOAD(0))
+        let bytecode = Bytecode::new_raw(Bytes::from(hex::decode("60005460035460003554").unwrap()));
+        let slots = find_constant_slots(&bytecode);
+        assert_eq!(slots, HashSet::from([EVMU256::from(0), EVMU256::from(3)]));
+    }
+
+    #[test]
+    fn test_find_caller_guards() {
+        // require(msg.sender == SLOAD(0) & ((1 << 160) - 1))
+        // require(msg.sender == 0xaa..aa)
+        let bytecode = Bytecode::new_raw(Bytes::from(
+            hex::decode(concat!(
+                "6000546001600160a01b031633146100175760006000fd",
+                "5b73aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                "3314610033575b00"
+            ))
+            .unwrap(),
+        ));
+        let guards = find_caller_guards(&bytecode);
+        assert_eq!(guards.len(), 2);
+        assert_eq!(guards[&0x11], CallerGuard::Storage(EVMU256::from(0)));
+        assert_eq!(guards[&0x32], CallerGuard::Address(EVMU256::from_be_slice(&[0xaa; 20])));
+    }
 }
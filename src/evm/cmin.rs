@@ -0,0 +1,101 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tracing::info;
+
+use crate::evm::{
+    host::{BRANCH_STATUS, BRANCH_STATUS_IDX},
+    types::EVMAddress,
+};
+
+/// A side of a branch: contract, pc of the JUMPI and whether it jumped
+pub type BranchSide = (EVMAddress, usize, bool);
+
+/// Branch sides covered by the last execution
+pub fn branch_footprint() -> HashSet<BranchSide> {
+    unsafe {
+        BRANCH_STATUS
+            .iter()
+            .take(BRANCH_STATUS_IDX)
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Indexes of a subset of the testcases covering all the branch sides covered
+/// by the testcases, picked greedily: the testcase covering the most sides
+/// not covered yet first, the one with the fewest transactions on ties
+pub fn select_testcases(footprints: &[(HashSet<BranchSide>, usize)]) -> Vec<usize> {
+    let mut uncovered = footprints
+        .iter()
+        .flat_map(|(footprint, _)| footprint.iter().cloned())
+        .collect::<HashSet<_>>();
+    let mut res = vec![];
+    while !uncovered.is_empty() {
+        let (idx, gain) = footprints
+            .iter()
+            .enumerate()
+            .map(|(idx, (footprint, txns))| (idx, footprint.intersection(&uncovered).count(), *txns))
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)).then(b.0.cmp(&a.0)))
+            .map(|(idx, gain, _)| (idx, gain))
+            .unwrap();
+        if gain == 0 {
+            break;
+        }
+        for side in &footprints[idx].0 {
+            uncovered.remove(side);
+        }
+        res.push(idx);
+    }
+    res.sort();
+    res
+}
+
+/// Copy the selected replayable files, along with their human readable
+/// counterparts, to `output_dir`
+pub fn write_minimized_corpus(files: &[PathBuf], selected: &[usize], output_dir: &str) -> std::io::Result<()> {
+    let output_dir = Path::new(output_dir);
+    fs::create_dir_all(output_dir)?;
+    for idx in selected {
+        let file = &files[*idx];
+        let name = file.file_name().unwrap().to_string_lossy().to_string();
+        fs::copy(file, output_dir.join(&name))?;
+        if let Some(readable) = name.strip_suffix("_replayable") {
+            let readable_file = file.with_file_name(readable);
+            if readable_file.exists() {
+                fs::copy(&readable_file, output_dir.join(readable))?;
+            }
+        }
+    }
+    info!(
+        "Kept {} of {} testcases in {}",
+        selected.len(),
+        files.len(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_testcases() {
+        let addr = EVMAddress::zero();
+        let footprint = |sides: &[(usize, bool)]| sides.iter().map(|(pc, br)| (addr, *pc, *br)).collect();
+        let footprints = vec![
+            (footprint(&[(1, true)]), 1),
+            (footprint(&[(1, true), (2, false)]), 3),
+            (footprint(&[(1, true), (2, false)]), 2),
+            (footprint(&[(3, true)]), 1),
+            (footprint(&[]), 1),
+        ];
+        assert_eq!(select_testcases(&footprints), vec![2, 3]);
+        assert!(select_testcases(&[]).is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+//! Analyses of the code (jump tables) shared across executions: the code is
+//! analyzed once and the analysis is reused whenever the same code is deployed
+//! again, e.g., by a factory on each execution, or is the init code of a
+//! contract creation.
+
+use std::{cell::RefCell, collections::HashMap, sync::Arc};
+
+use bytes::Bytes;
+use revm_interpreter::{analysis::to_analysed, BytecodeLocked};
+use revm_primitives::Bytecode;
+
+use crate::r#const::CODE_ANALYSIS_CACHE_SIZE;
+
+thread_local! {
+    /// Analyses by the bytes and the length of the code, as the bytes of an
+    /// analyzed code are padded
+    static ANALYSES: RefCell<HashMap<(Bytes, usize), Arc<BytecodeLocked>>> = RefCell::new(HashMap::new());
+}
+
+/// Analyzed code, shared with the previous analyses of the same code
+pub fn analyze(code: Bytecode) -> Arc<BytecodeLocked> {
+    let key = (code.bytes().clone(), code.len());
+    ANALYSES.with(|analyses| {
+        if let Some(analyzed) = analyses.borrow().get(&key) {
+            return analyzed.clone();
+        }
+        let analyzed = Arc::new(BytecodeLocked::try_from(to_analysed(code)).unwrap());
+        let mut analyses = analyses.borrow_mut();
+        // the code deployed with distinct immutables can not be shared
+        if analyses.len() < CODE_ANALYSIS_CACHE_SIZE {
+            analyses.insert(key, analyzed.clone());
+        }
+        analyzed
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        let code = Bytecode::new_raw(Bytes::from(vec![0x60, 0x04, 0x56, 0x00, 0x5b, 0x00]));
+        let analyzed = analyze(code.clone());
+        assert!(Arc::ptr_eq(&analyzed, &analyze(code)));
+        assert!(!Arc::ptr_eq(
+            &analyzed,
+            &analyze(Bytecode::new_raw(Bytes::from(vec![0x60, 0x04, 0x56, 0x00, 0x5b])))
+        ));
+    }
+}
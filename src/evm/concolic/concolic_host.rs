@@ -589,11 +589,13 @@ pub struct ConcolicHost {
     // For current PC, the number of times it has been visited
     pub num_threads: usize,
     pub call_depth: usize,
+    /// Branches covered on both sides, not solved for
+    pub covered_branches: HashSet<(EVMAddress, usize)>,
 }
 
 #[allow(clippy::vec_box)]
 impl ConcolicHost {
-    pub fn new(testcase_ref: Rc<EVMInput>, num_threads: usize) -> Self {
+    pub fn new(testcase_ref: Rc<EVMInput>, num_threads: usize, covered_branches: HashSet<(EVMAddress, usize)>) -> Self {
         Self {
             symbolic_stack: Vec::new(),
             symbolic_memory: SymbolicMemory::new(),
@@ -604,6 +606,7 @@ impl ConcolicHost {
             ctxs: vec![],
             num_threads,
             call_depth: 0,
+            covered_branches,
         }
     }
 
@@ -1206,17 +1209,23 @@ where
 
                 // Get the source map of current pc
                 let mut need_solve = true;
+                let pc = interp.program_counter();
+                let address = &interp.contract.address;
                 if self.call_depth > MAX_CALL_DEPTH {
                     debug!("[concolic] skip solving due to call depth: {}", self.call_depth);
                     need_solve = false;
+                } else if self.covered_branches.contains(&(*address, pc)) {
+                    debug!("[concolic] skip solving due to both sides covered");
+                    need_solve = false;
                 } else {
-                    let pc = interp.program_counter();
-                    let address = &interp.contract.address;
-
                     match SOURCE_MAP_PROVIDER.lock().unwrap().get_source_code(address, pc) {
                         SourceCodeResult::SourceCode(_) => {
                             // Solve normal pc with source code
                         }
+                        SourceCodeResult::NoSourceMap => {
+                            // Solve all pcs of contracts without source map
+                            // (e.g., onchain ones), nothing tells what to skip
+                        }
                         _ => {
                             // Don't solve if no source code
                             debug!("[concolic] skip solving due to no source map match");
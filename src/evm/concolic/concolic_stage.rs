@@ -21,6 +21,7 @@ use crate::{
         concolic::concolic_host::{ConcolicHost, Field, Solution, ALL_SOLUTIONS, ALL_WORKER_THREADS},
         input::{EVMInput, EVMInputT},
         middlewares::middleware::MiddlewareType,
+        scheduler::UncoveredBranchesMetadata,
         types::{EVMFuzzExecutor, EVMFuzzState, EVMQueueExecutor},
     },
     generic_vm::{vm_executor::GenericVM, vm_state::VMStateT},
@@ -116,12 +117,18 @@ where
             }
 
             let testcase_ref = Rc::new(testcase.clone());
+            let covered_branches = state
+                .metadata_map()
+                .get::<UncoveredBranchesMetadata>()
+                .map(|meta| meta.fully_covered().into_iter().collect())
+                .unwrap_or_default();
 
             {
                 let mut vm = self.vm_executor.deref().borrow_mut();
                 vm.host.add_middlewares(Rc::new(RefCell::new(ConcolicHost::new(
                     testcase_ref.clone(),
                     self.num_threads,
+                    covered_branches,
                 ))));
                 vm.execute(&testcase_ref, state);
 
@@ -1,21 +1,31 @@
 use std::{
     cell::RefCell,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fmt::{self, Debug},
     rc::Rc,
     str::FromStr,
 };
 
+use alloy_json_abi::Event;
+use revm_primitives::Bytecode;
+
 /// Configuration for the EVM fuzzer
 use crate::evm::contract_utils::ContractLoader;
 use crate::{
     evm::{
-        blaz::builder::BuildJob,
+        blaz::{builder::BuildJob, compiler_variants::CompilerVariant},
+        feedbacks::CustomFeedback,
+        mempool::VictimTx,
+        middlewares::{breakpoint::Breakpoint, chainlink::FeedMode},
         onchain::endpoints::OnChainConfig,
-        oracles::erc20::IERC20OracleFlashloan,
-        types::EVMAddress,
+        oracles::{echidna::EchidnaConfig, erc20::IERC20OracleFlashloan, plugin::OraclePlugin},
+        scheduler::PowerABIScheduler,
+        senders::{CallValueRange, Sender},
+        tokens::numeraire::Numeraire,
+        types::{EVMAddress, EVMFuzzState, EVMU256},
     },
     oracle::{Oracle, Producer},
+    stop_conditions::StopConditions,
 };
 
 pub enum FuzzerTypes {
@@ -61,27 +71,103 @@ pub struct Config<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E> {
     pub onchain_storage_fetching: Option<StorageFetchingMode>,
     pub etherscan_api_key: String,
     pub flashloan: bool,
+    /// Floor prices (in wei) of the NFT collections counted as profit
+    pub nft_floor_prices: HashMap<EVMAddress, EVMU256>,
+    /// Chainlink aggregators mocked
+    pub chainlink_feeds: HashMap<EVMAddress, FeedMode>,
+    /// Breakpoints dumping the execution state when hit
+    pub breakpoints: Vec<Breakpoint>,
+    /// Scores of the selectors of the functions to fuzz more, from Slither
+    pub sig_to_score: HashMap<[u8; 4], f64>,
+    /// `--sig-to-score` file, reloaded on SIGHUP
+    pub sig_to_score_file: Option<String>,
     pub concolic: bool,
     pub concolic_caller: bool,
     pub concolic_timeout: u32,
     pub concolic_num_threads: usize,
+    pub redqueen: bool,
     pub contract_loader: ContractLoader,
     pub oracle: Vec<Rc<RefCell<dyn Oracle<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E>>>>,
     pub producers: Vec<Rc<RefCell<dyn Producer<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E>>>>,
     pub replay_file: Option<String>,
+    /// Write a minimized corpus of the replayed files to this directory
+    /// instead of printing their traces
+    pub cmin_output: Option<String>,
     pub flashloan_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
+    /// Whether the fund losses are reported, the liquidation of the
+    /// `flashloan_oracle` running before
+    pub erc20_oracle: bool,
     pub selfdestruct_oracle: bool,
+    pub takeover_oracle: bool,
     pub reentrancy_oracle: bool,
+    pub erc4626_oracle: bool,
+    pub tainted_call_oracle: bool,
+    pub supply_oracle: bool,
+    pub price_manipulation_oracle: bool,
+    pub contract_size_oracle: bool,
+    pub assertion_oracle: bool,
+    /// Selectors of the custom errors reported by the assertion oracle
+    pub assertion_errors: Vec<[u8; 4]>,
+    pub gas_griefing_oracle: bool,
+    /// Upgraded bytecode of the contracts each transaction is executed again
+    /// with, for differential fuzzing
+    pub upgrades: HashMap<EVMAddress, Bytecode>,
+    /// Builds of the targets with other compiler configurations, compared
+    /// with the main build by the differential oracle
+    pub compiler_variants: Vec<CompilerVariant>,
+    pub supply_whitelist: Vec<String>,
     // pub state_comp_oracle: Option<String>,
     // pub state_comp_matching: Option<String>,
     pub work_dir: String,
     pub write_relationship: bool,
     pub run_forever: bool,
+    pub max_execs: Option<usize>,
+    /// Coverage target, objectives and timeout stopping the campaign
+    pub stop_conditions: StopConditions,
+    pub checkpoint_interval: Option<usize>,
+    /// Seconds between two saves of the campaign, 0 if disabled
+    pub save_interval: u64,
+    pub resume: bool,
+    /// Unit of the profits and capitals, priced after the executor is set up
+    pub numeraire: Numeraire,
+    pub fuzz_constructor_args: bool,
+    /// Whether to run `setUp()` of the deployed contracts defining it
+    pub harness_setup: bool,
+    /// Whether to infer the args of the contracts without ABI by probing
+    pub infer_interface: bool,
+    /// Pending transaction of a user the fuzzer front-runs and back-runs
+    pub victim_tx: Option<VictimTx>,
+    /// Feedback maps of plugins deciding which inputs are kept in the corpus
+    /// alongside branch coverage
+    pub custom_feedbacks: Vec<CustomFeedback<PowerABIScheduler<EVMFuzzState>>>,
     pub sha3_bypass: bool,
+    /// Show the live dashboard instead of the logs
+    pub tui: bool,
+    /// File or unix socket the events of the campaign are emitted to
+    pub telemetry: Option<String>,
+    /// Address the Prometheus metrics are served on
+    pub metrics_addr: Option<String>,
     pub base_path: String,
     pub echidna_oracle: bool,
+    /// Echidna config, replacing the callers and bounding the delays
+    pub echidna_config: Option<EchidnaConfig>,
+    /// Senders replacing the default callers
+    pub senders: Vec<Sender>,
+    /// Ranges of the value of the calls
+    pub call_value_ranges: Vec<CallValueRange>,
+    /// Whether to add the holders of privileged roles to the senders
+    pub impersonate_owners: bool,
     pub invariant_oracle: bool,
+    /// Names of the contracts only holding invariants
+    pub invariant_harness: Vec<String>,
+    /// Events whose emission violates a property
+    pub event_properties: Vec<Event>,
+    /// Oracles loaded from shared libraries
+    pub oracle_plugins: Vec<OraclePlugin>,
+    /// Path of the python script registering oracles and calldata processors
+    pub python_script: String,
     pub panic_on_bug: bool,
+    pub determinism_check: bool,
     pub spec_id: String,
     pub only_fuzz: HashSet<EVMAddress>,
     pub typed_bug: bool,
@@ -90,6 +176,12 @@ pub struct Config<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E> {
     pub builder: Option<BuildJob>,
     pub local_files_basedir_pattern: Option<String>,
     pub load_corpus: String,
+    pub corpus_sync: Option<String>,
+    pub corpus_sync_interval: u64,
+    pub corpus_sync_node: String,
+    /// Seconds between two checks of the upgrades of the proxies fuzzed, 0 if
+    /// they are not watched
+    pub watch_upgrades: u64,
     #[cfg(feature = "use_presets")]
     pub preset_file_path: String,
 }
@@ -102,23 +194,57 @@ impl<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E> Debug
             .field("onchain", &self.onchain)
             // .field("onchain_storage_fetching", &self.onchain_storage_fetching)
             .field("flashloan", &self.flashloan)
+            .field("nft_floor_prices", &self.nft_floor_prices)
+            .field("chainlink_feeds", &self.chainlink_feeds)
+            .field("breakpoints", &self.breakpoints)
             .field("concolic", &self.concolic)
             .field("concolic_caller", &self.concolic_caller)
             .field("contract_loader", &self.contract_loader)
             // .field("oracle", &self.oracle)
             // .field("producers", &self.producers)
             .field("replay_file", &self.replay_file)
+            .field("cmin_output", &self.cmin_output)
             // .field("flashloan_oracle", &self.flashloan_oracle)
             .field("selfdestruct_oracle", &self.selfdestruct_oracle)
+            .field("takeover_oracle", &self.takeover_oracle)
             // .field("state_comp_oracle", &self.state_comp_oracle)
             // .field("state_comp_matching", &self.state_comp_matching)
             .field("work_dir", &self.work_dir)
             .field("write_relationship", &self.write_relationship)
             .field("run_forever", &self.run_forever)
+            .field("max_execs", &self.max_execs)
+            .field("stop_conditions", &self.stop_conditions)
+            .field("checkpoint_interval", &self.checkpoint_interval)
+            .field("save_interval", &self.save_interval)
+            .field("resume", &self.resume)
+            .field("numeraire", &self.numeraire)
+            .field("fuzz_constructor_args", &self.fuzz_constructor_args)
+            .field("harness_setup", &self.harness_setup)
+            .field("infer_interface", &self.infer_interface)
+            .field("victim_tx", &self.victim_tx)
             .field("sha3_bypass", &self.sha3_bypass)
+            .field("tui", &self.tui)
+            .field("telemetry", &self.telemetry)
+            .field("metrics_addr", &self.metrics_addr)
             .field("base_path", &self.base_path)
             .field("echidna_oracle", &self.echidna_oracle)
+            .field("echidna_config", &self.echidna_config)
+            .field("senders", &self.senders)
+            .field("call_value_ranges", &self.call_value_ranges)
+            .field("impersonate_owners", &self.impersonate_owners)
+            .field("invariant_harness", &self.invariant_harness)
+            .field("event_properties", &self.event_properties)
+            .field(
+                "oracle_plugins",
+                &self
+                    .oracle_plugins
+                    .iter()
+                    .map(|plugin| plugin.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field("python_script", &self.python_script)
             .field("panic_on_bug", &self.panic_on_bug)
+            .field("determinism_check", &self.determinism_check)
             .field("spec_id", &self.spec_id)
             .field("only_fuzz", &self.only_fuzz)
             .field("typed_bug", &self.typed_bug)
@@ -18,7 +18,10 @@ use colored::Colorize;
 /// Load contract from file system or remote
 use glob::glob;
 use itertools::Itertools;
-use libafl::{schedulers::StdScheduler, state::HasMetadata};
+use libafl::{
+    schedulers::{Scheduler, StdScheduler},
+    state::HasMetadata,
+};
 use libafl_bolts::AsSlice;
 use regex::Regex;
 use revm_primitives::{bitvec::vec, Bytecode, Env};
@@ -28,9 +31,10 @@ use crate::{
     evm::{
         middlewares::middleware::MiddlewareType,
         onchain::abi_decompiler::fetch_abi_evmole,
+        parse_constructor_args_string,
         tokens::constant_pair::ConstantPairMetadata,
         types::{fixed_address, generate_random_address, EVMAddress, EVMFuzzState},
-        vm::{IN_DEPLOY, SETCODE_ONLY},
+        vm::{is_reverted_or_control_leak, IN_DEPLOY, SETCODE_ONLY},
         PRESET_WETH,
     },
     generic_vm::vm_executor::GenericVM,
@@ -62,7 +66,7 @@ use crate::evm::{
         offchain_config::OffchainConfig,
     },
     bytecode_iterator::all_bytecode,
-    onchain::{endpoints::OnChainConfig, OnChain},
+    onchain::{endpoints::OnChainConfig, proxy, signatures::decompile_abi, OnChain},
 };
 
 // to use this address, call rand_utils::fixed_address(FIX_DEPLOYER)
@@ -312,6 +316,14 @@ impl ContractLoader {
             .collect()
     }
 
+    /// Default args of the constructor in `abi`, if any
+    fn default_constructor_args(abi: &[ABIConfig]) -> Option<Vec<u8>> {
+        let abi = abi.iter().find(|abi| abi.is_constructor)?;
+        let mut abi_instance = get_abi_type_boxed_with_address(&abi.abi, fixed_address(FIX_DEPLOYER).0.to_vec());
+        abi_instance.set_func_with_signature(abi.function, &abi.function_name, &abi.abi);
+        Some(abi_instance.get().get_bytes())
+    }
+
     pub fn from_prefix(
         prefix: &str,
         state: &mut EVMFuzzState,
@@ -372,12 +384,10 @@ impl ContractLoader {
             }
         }
 
-        if let Some(abi) = abi_result.abi.iter().find(|abi| abi.is_constructor) {
-            let mut abi_instance = get_abi_type_boxed_with_address(&abi.abi, fixed_address(FIX_DEPLOYER).0.to_vec());
-            abi_instance.set_func_with_signature(abi.function, &abi.function_name, &abi.abi);
+        if let Some(default_args) = Self::default_constructor_args(&abi_result.abi) {
             if contract_result.constructor_args.is_empty() {
                 debug!("No constructor args found, using default constructor args");
-                contract_result.constructor_args = abi_instance.get().get_bytes();
+                contract_result.constructor_args = default_args;
             }
             // debug!("Constructor args: {:?}", result.constructor_args);
             contract_result.code.extend(contract_result.constructor_args.clone());
@@ -520,6 +530,400 @@ impl ContractLoader {
         }
     }
 
+    /// Build the Foundry project in `dir` with `forge build` and load its
+    /// contracts from the artifacts of the `out` directory. Tests, scripts and
+    /// dependencies are not deployed.
+    ///
+    /// Constructor args (hex encoded) are taken from `constructor_args_map`,
+    /// then the `ITYFUZZ_CONSTRUCTOR_ARGS` env variable (same format as
+    /// `--constructor-args`), then the `[ityfuzz.constructor_args]` table of
+    /// `foundry.toml`, e.g., `Token = ["0x1000"]`.
+    pub fn from_foundry(
+        dir: &str,
+        state: &mut EVMFuzzState,
+        constructor_args_map: &HashMap<String, Vec<String>>,
+    ) -> Self {
+        info!("Building Foundry project in {}", dir);
+        let status = std::process::Command::new("forge")
+            .arg("build")
+            .current_dir(dir)
+            .status()
+            .expect("failed to execute forge build");
+        assert!(status.success(), "forge build failed");
+
+        let config = std::fs::read_to_string(format!("{}/foundry.toml", dir))
+            .ok()
+            .and_then(|content| toml::from_str::<Value>(&content).ok())
+            .unwrap_or_default();
+        let out = config["profile"]["default"]["out"].as_str().unwrap_or("out");
+
+        let mut args_map: HashMap<String, Vec<String>> = config["ityfuzz"]["constructor_args"]
+            .as_object()
+            .map(|table| {
+                table
+                    .iter()
+                    .map(|(name, args)| {
+                        let args = match args {
+                            Value::Array(args) => args
+                                .iter()
+                                .map(|arg| arg.as_str().map(|s| s.to_string()).unwrap_or(arg.to_string()))
+                                .collect(),
+                            arg => vec![arg.as_str().map(|s| s.to_string()).unwrap_or(arg.to_string())],
+                        };
+                        (name.clone(), args)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self::extend_constructor_args(&mut args_map, constructor_args_map);
+
+        let artifacts = glob(&format!("{}/{}/**/*.json", dir, out))
+            .expect("invalid foundry out directory")
+            .flatten()
+            .filter(|path| !path.components().any(|c| c.as_os_str() == "build-info"))
+            .filter_map(|path| {
+                let json = std::fs::read_to_string(&path).ok()?;
+                parse_foundry_artifact(&serde_json::from_str(&json).ok()?)
+            })
+            .sorted_by(|a, b| (&a.source, &a.name).cmp(&(&b.source, &b.name)))
+            .collect_vec();
+
+        // sources indexed by their id, as referred to by the source maps
+        let mut files = vec![];
+        for artifact in &artifacts {
+            if let Some(id) = artifact.source_id {
+                if files.len() <= id {
+                    files.resize(id + 1, (String::new(), String::new()));
+                }
+                if files[id].0.is_empty() {
+                    let content = std::fs::read_to_string(format!("{}/{}", dir, artifact.source)).unwrap_or_default();
+                    files[id] = (artifact.source.clone(), content);
+                }
+            }
+        }
+
+        let mut contracts = vec![];
+        let mut abis = vec![];
+        for artifact in artifacts {
+            if artifact.code.is_empty() || is_foundry_dependency(&artifact.source) {
+                continue;
+            }
+            let (contract, abi) = Self::built_contract(
+                format!("{}:{}", artifact.source, artifact.name),
+                artifact.code,
+                &artifact.abi,
+                args_map.get(&artifact.name),
+                generate_random_address(state),
+                files.clone(),
+                Some(artifact.source_map),
+            );
+            contracts.push(contract);
+            abis.push(abi);
+        }
+
+        ContractLoader {
+            contracts,
+            abis,
+            setup_data: None,
+        }
+    }
+
+    /// Build the Hardhat project in `dir` with `npx hardhat compile` and load
+    /// its contracts from the artifacts of the `artifacts` directory. Libraries
+    /// are deployed at the addresses their placeholders are linked to, and
+    /// dependencies (`@...`, `hardhat/`) are only deployed when they are
+    /// linked libraries.
+    ///
+    /// Constructor args (hex encoded) are taken from `constructor_args_map`,
+    /// then the `ITYFUZZ_CONSTRUCTOR_ARGS` env variable (same format as
+    /// `--constructor-args`).
+    pub fn from_hardhat(
+        dir: &str,
+        state: &mut EVMFuzzState,
+        constructor_args_map: &HashMap<String, Vec<String>>,
+    ) -> Self {
+        info!("Building Hardhat project in {}", dir);
+        let status = std::process::Command::new("npx")
+            .args(["hardhat", "compile"])
+            .current_dir(dir)
+            .status()
+            .expect("failed to execute npx hardhat compile");
+        assert!(status.success(), "hardhat compile failed");
+
+        let mut args_map = HashMap::new();
+        Self::extend_constructor_args(&mut args_map, constructor_args_map);
+
+        let artifacts: BTreeMap<(String, String), ContractArtifact> = glob(&format!("{}/artifacts/**/*.json", dir))
+            .expect("invalid hardhat artifacts directory")
+            .flatten()
+            .filter(|path| {
+                !path.components().any(|c| c.as_os_str() == "build-info") &&
+                    !path.to_string_lossy().ends_with(".dbg.json")
+            })
+            .filter_map(|path| {
+                let json = std::fs::read_to_string(&path).ok()?;
+                parse_hardhat_artifact(&serde_json::from_str(&json).ok()?)
+            })
+            .collect();
+
+        // libraries linked by any contract
+        let libs: BTreeMap<(String, String), ContractArtifact> = artifacts
+            .values()
+            .flat_map(|artifact| {
+                artifact
+                    .link_references
+                    .iter()
+                    .flat_map(|(file, refs)| refs.keys().map(move |name| (file.clone(), name.clone())))
+            })
+            .filter_map(|key| Some((key.clone(), artifacts.get(&key)?.clone())))
+            .collect();
+
+        let mut contracts = vec![];
+        let mut abis = vec![];
+        // libraries are deployed first
+        for (key, artifact) in artifacts.iter().sorted_by_key(|(key, _)| !libs.contains_key(key)) {
+            let is_lib = libs.contains_key(key);
+            if artifact.deploy_bytecode_str.is_empty() || (!is_lib && is_hardhat_dependency(&key.0)) {
+                continue;
+            }
+            let linked = match Linker::link_setup_target_with_compute_address(&libs, artifact.clone()) {
+                Ok(linked) => linked.deploy_bytecode_str,
+                Err(e) => {
+                    error!("Failed to link {}:{}: {:?}", key.0, key.1, e);
+                    continue;
+                }
+            };
+            let code = match hex::decode(&linked) {
+                Ok(code) => code,
+                Err(_) => {
+                    error!("Skipping {}:{}, it links to missing libraries", key.0, key.1);
+                    continue;
+                }
+            };
+            let deployed_address = if is_lib {
+                EVMAddress::from_str(&compute_address(key)).expect("invalid library address")
+            } else {
+                generate_random_address(state)
+            };
+            let (contract, abi) = Self::built_contract(
+                format!("{}:{}", key.0, key.1),
+                code,
+                &artifact.abi,
+                args_map.get(&key.1),
+                deployed_address,
+                vec![],
+                None,
+            );
+            contracts.push(contract);
+            abis.push(abi);
+        }
+
+        ContractLoader {
+            contracts,
+            abis,
+            setup_data: None,
+        }
+    }
+
+    /// Add the constructor args of the `ITYFUZZ_CONSTRUCTOR_ARGS` env variable,
+    /// then of `constructor_args_map`, to `args_map`
+    fn extend_constructor_args(
+        args_map: &mut HashMap<String, Vec<String>>,
+        constructor_args_map: &HashMap<String, Vec<String>>,
+    ) {
+        if let Ok(env_args) = std::env::var("ITYFUZZ_CONSTRUCTOR_ARGS") {
+            args_map.extend(parse_constructor_args_string(env_args));
+        }
+        args_map.extend(constructor_args_map.clone());
+    }
+
+    /// Contract of a locally built project, deployed with `args` or the
+    /// default constructor args
+    fn built_contract(
+        name: String,
+        code: Vec<u8>,
+        abi: &str,
+        args: Option<&Vec<String>>,
+        deployed_address: EVMAddress,
+        files: Vec<(String, String)>,
+        raw_source_map: Option<String>,
+    ) -> (ContractInfo, ABIInfo) {
+        let abi = Self::parse_abi_str(abi);
+        let constructor_args = match args {
+            Some(args) => Self::constructor_args_encode(args),
+            None => Self::default_constructor_args(&abi).unwrap_or_default(),
+        };
+        debug!("Loading contract {}", name);
+        (
+            ContractInfo {
+                name: name.clone(),
+                code: [code, constructor_args.clone()].concat(),
+                abi: abi.clone(),
+                is_code_deployed: false,
+                constructor_args,
+                deployed_address,
+                build_artifact: None,
+                files,
+                source_map_replacements: None,
+                raw_source_map,
+            },
+            ABIInfo { source: name, abi },
+        )
+    }
+
+    /// Replay the transactions recorded by `forge script --broadcast` in
+    /// `path` (`broadcast/<Script>.s.sol/<chain id>/run-latest.json`) to set up
+    /// the deployed system before fuzzing. Contracts, including the CREATE2
+    /// ones and the ones created by other contracts, are created at their
+    /// recorded addresses so that the addresses they are wired with stay
+    /// valid. ABIs are taken from the artifacts of the Foundry project the
+    /// broadcast belongs to, or decompiled when the contract is not named.
+    pub fn from_broadcast(path: &str, work_dir: String, etherscan_api_key: &str) -> Self {
+        let json = std::fs::read_to_string(path).expect("failed to read broadcast file");
+        let txs = parse_broadcast(&serde_json::from_str(&json).expect("invalid broadcast file"));
+        assert!(!txs.is_empty(), "no transaction in broadcast file {}", path);
+
+        // contracts of the project by name, dependencies only when not shadowed
+        let project = Path::new(path).ancestors().nth(4).unwrap_or(Path::new("."));
+        let out = std::fs::read_to_string(project.join("foundry.toml"))
+            .ok()
+            .and_then(|content| toml::from_str::<Value>(&content).ok())
+            .and_then(|config| config["profile"]["default"]["out"].as_str().map(|s| s.to_string()))
+            .unwrap_or("out".to_string());
+        let mut artifacts: HashMap<String, FoundryArtifact> = HashMap::new();
+        for artifact in glob(&format!("{}/{}/**/*.json", project.display(), out))
+            .expect("invalid foundry out directory")
+            .flatten()
+            .filter(|path| !path.components().any(|c| c.as_os_str() == "build-info"))
+            .filter_map(|path| {
+                let json = std::fs::read_to_string(&path).ok()?;
+                parse_foundry_artifact(&serde_json::from_str(&json).ok()?)
+            })
+            .sorted_by_key(|artifact| is_foundry_dependency(&artifact.source))
+        {
+            artifacts.entry(artifact.name.clone()).or_insert(artifact);
+        }
+
+        let deployer = EVMAddress::from_str(FOUNDRY_DEPLOYER).unwrap();
+        let (mut evm_executor, mut state) = Self::get_vm_with_cheatcode(deployer, work_dir, etherscan_api_key);
+        let mut names = HashMap::new();
+        unsafe {
+            SETCODE_ONLY = true;
+        }
+        for tx in txs {
+            if evm_executor.host.evmstate.get_balance(&tx.from).is_none() {
+                evm_executor
+                    .host
+                    .evmstate
+                    .set_balance(tx.from, EVMU256::from(INITIAL_BALANCE));
+            }
+            evm_executor.host.create_addresses = tx.additional_contracts.iter().cloned().collect();
+            let succeeded = match tx.kind {
+                BroadcastTxKind::Create | BroadcastTxKind::Create2 => {
+                    evm_executor.deployer = tx.from;
+                    evm_executor
+                        .deploy(
+                            Bytecode::new_raw(Bytes::from(tx.input.clone())),
+                            None,
+                            tx.contract_address,
+                            &mut state,
+                        )
+                        .is_some()
+                }
+                BroadcastTxKind::Call => {
+                    let mut vm_state = evm_executor.host.evmstate.clone();
+                    if tx.value > EVMU256::ZERO {
+                        let from = vm_state.get_balance(&tx.from).cloned().unwrap_or_default();
+                        let to = vm_state.get_balance(&tx.contract_address).cloned().unwrap_or_default();
+                        vm_state.set_balance(tx.from, from.saturating_sub(tx.value));
+                        vm_state.set_balance(tx.contract_address, to + tx.value);
+                    }
+                    unsafe {
+                        IN_DEPLOY = true;
+                    }
+                    let (_, ret) = evm_executor.fast_call_(
+                        tx.contract_address,
+                        Bytes::from(tx.input.clone()),
+                        &mut vm_state,
+                        &mut state,
+                        tx.value,
+                        tx.from,
+                    );
+                    unsafe {
+                        IN_DEPLOY = false;
+                    }
+                    let succeeded = !is_reverted_or_control_leak(&ret);
+                    if succeeded {
+                        evm_executor.host.evmstate = vm_state;
+                    }
+                    succeeded
+                }
+            };
+            if !succeeded {
+                error!(
+                    "Failed to replay {:?} of {} to {:?}",
+                    tx.kind,
+                    tx.contract_name.clone().unwrap_or_default(),
+                    tx.contract_address
+                );
+            }
+            if let (Some(name), true) = (tx.contract_name, tx.kind != BroadcastTxKind::Call) {
+                names.insert(tx.contract_address, name);
+            }
+        }
+        evm_executor.deployer = deployer;
+        evm_executor.host.create_addresses.clear();
+        unsafe {
+            SETCODE_ONLY = false;
+        }
+
+        let code: HashMap<EVMAddress, Bytes> = evm_executor
+            .host
+            .code
+            .iter()
+            .filter(|(addr, _)| **addr != CHEATCODE_ADDRESS)
+            .map(|(addr, code)| (*addr, Bytes::from_iter(code.bytecode().iter().cloned())))
+            .collect();
+
+        let mut contracts = vec![];
+        let mut abis = vec![];
+        for (addr, code) in code.iter().sorted_by_key(|(addr, _)| **addr) {
+            let artifact = names.get(addr).and_then(|name| artifacts.get(name));
+            let (name, abi) = match artifact {
+                Some(artifact) => (
+                    format!("{}:{}", artifact.source, artifact.name),
+                    Self::parse_abi_str(&artifact.abi),
+                ),
+                None => (format!("{}", addr), fetch_abi_evmole(hex::encode(code))),
+            };
+            debug!("Contract at address {:?} is {}", addr, name);
+            contracts.push(ContractInfo {
+                name: name.clone(),
+                code: code.to_vec(),
+                abi: abi.clone(),
+                is_code_deployed: true,
+                constructor_args: vec![],
+                deployed_address: *addr,
+                build_artifact: None,
+                files: vec![],
+                source_map_replacements: None,
+                raw_source_map: None,
+            });
+            abis.push(ABIInfo { source: name, abi });
+        }
+
+        ContractLoader {
+            contracts,
+            abis,
+            setup_data: Some(SetupData {
+                evmstate: evm_executor.host.evmstate.clone(),
+                env: evm_executor.host.env.clone(),
+                code,
+                ..Default::default()
+            }),
+        }
+    }
+
     pub fn from_address(onchain: &mut OnChainConfig, address: HashSet<EVMAddress>, builder: Option<BuildJob>) -> Self {
         let mut contracts: Vec<ContractInfo> = vec![];
         let mut abis: Vec<ABIInfo> = vec![];
@@ -556,12 +960,27 @@ impl ContractLoader {
 
             let contract_code = bytecode.expect("Failed to get bytecode");
 
-            let abi_parsed = if let Some(abi) = abi {
+            let mut abi_parsed = if let Some(abi) = abi {
                 Self::parse_abi_str(&abi)
             } else {
-                debug!("ABI not found for {}, we'll decompile", addr);
-                vec![]
+                // the functions not named on 4byte.directory keep their
+                // decompiled args, refined when the corpus is initialized
+                let decompiled = decompile_abi(onchain, contract_code.clone());
+                if decompiled.is_empty() {
+                    debug!("ABI not found for {}, we'll decompile", addr);
+                }
+                decompiled
             };
+            // calls go through proxies and diamonds, with the functions of the
+            // implementation or facets
+            let delegated_abi = proxy::delegated_abi(onchain, addr, &hex::decode(&contract_code).unwrap_or_default());
+            if !delegated_abi.is_empty() {
+                if abi_parsed.is_empty() {
+                    // the functions of the proxy itself are decompiled
+                    abi_parsed = decompile_abi(onchain, contract_code.clone());
+                }
+                abi_parsed = proxy::merge_abis(abi_parsed, delegated_abi);
+            }
 
             let (files, source_map_replacements, raw_source_map) = if let Some(job_result) = build_artifact.clone() {
                 (
@@ -638,6 +1057,7 @@ impl ContractLoader {
                     more_info.source_map_replacements.clone(),
                     // TODO: offchain ast
                     Vec::new(),
+                    more_info.storage_layout.clone(),
                 )),
                 files: sources,
                 source_map_replacements: Some(more_info.source_map_replacements),
@@ -655,7 +1075,10 @@ impl ContractLoader {
     /// This function is used to find the contract artifact from offchain
     /// artifacts by comparing the bytecode It will return the index of the
     /// artifact and the location of the contract
-    fn find_contract_artifact(to_find: Vec<u8>, offchain_artifacts: &[OffChainArtifact]) -> (usize, (String, String)) {
+    pub(crate) fn find_contract_artifact(
+        to_find: Vec<u8>,
+        offchain_artifacts: &[OffChainArtifact],
+    ) -> (usize, (String, String)) {
         let mut candidates = vec![];
         let mut all_candidates = vec![];
         for (idx, artifact) in offchain_artifacts.iter().enumerate() {
@@ -748,6 +1171,7 @@ impl ContractLoader {
                     more_info.source_map_replacements.clone(),
                     // TODO: offchain ast
                     Vec::new(),
+                    more_info.storage_layout.clone(),
                 )),
                 files: artifact.sources.clone(),
                 source_map_replacements: Some(more_info.source_map_replacements.clone()),
@@ -874,7 +1298,7 @@ impl ContractLoader {
                 }
                 let abi = match onchain_config.fetch_abi(addr) {
                     Some(abi_str) => Self::parse_abi_str(&abi_str),
-                    None => fetch_abi_evmole(code.clone()),
+                    None => decompile_abi(&onchain_config, code.clone()),
                 };
 
                 contracts.push(ContractInfo {
@@ -945,6 +1369,7 @@ impl ContractLoader {
                     more_info.source_map_replacements.clone(),
                     // TODO: offchain ast
                     Vec::new(),
+                    more_info.storage_layout.clone(),
                 )),
                 files: artifact.sources.clone(),
                 source_map_replacements: Some(more_info.source_map_replacements.clone()),
@@ -1002,6 +1427,33 @@ impl ContractLoader {
         (executor, state)
     }
 
+    /// Invoke "setUp()" of the contract at `target` on `vm_state`, returns the
+    /// VM state it establishes, None if it reverts
+    pub fn invoke_setup<SC>(
+        executor: &mut EVMExecutor<EVMState, ConciseEVMInput, SC>,
+        state: &mut EVMFuzzState,
+        caller: EVMAddress,
+        target: EVMAddress,
+        vm_state: &EVMState,
+    ) -> Option<EVMState>
+    where
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        let mut calldata = [0; 4];
+        set_hash("setUp()", &mut calldata);
+        let (res, new_vm_state) = executor.fast_call(
+            &[(caller, target, Bytes::from_iter(calldata.iter().cloned()))],
+            vm_state,
+            state,
+        );
+        if !res[0].1 {
+            error!("setUp() of {:?} failed: {:?}", target, res[0].0);
+            return None;
+        }
+        debug!("setUp() of {:?} successful!", target);
+        Some(new_vm_state)
+    }
+
     /// Deploy the contract and invoke "setUp()", returns the code, state, and
     /// environment after deployment. Foundry VM Cheatcodes and Hardhat
     /// consoles are enabled here.
@@ -1072,20 +1524,14 @@ impl ContractLoader {
         let state_after_deployment = evm_executor.host.evmstate.clone();
 
         // invoke setUp() and fails imeediately if setUp() reverts
-        let mut calldata = [0; 4];
-        set_hash("setUp()", &mut calldata);
-
-        let (res, new_vm_state) = evm_executor.fast_call(
-            &[(deployer, deployed_addr, Bytes::from_iter(calldata.iter().cloned()))],
-            &state_after_deployment,
+        let new_vm_state = Self::invoke_setup(
+            &mut evm_executor,
             &mut state,
-        );
-
-        if !res[0].1 {
-            error!("setUp() failed: {:?}", res[0].0);
-        } else {
-            debug!("setUp() successful!");
-        }
+            deployer,
+            deployed_addr,
+            &state_after_deployment,
+        )
+        .unwrap_or(state_after_deployment);
 
         // now get Foundry invariant test config by calling
         // * excludeContracts() => array of addresses
@@ -1275,6 +1721,155 @@ impl ContractLoader {
     }
 }
 
+/// Contract of a Foundry `out/<File>.sol/<Contract>.json` artifact
+#[derive(Debug, Clone, Default)]
+pub struct FoundryArtifact {
+    pub name: String,
+    /// Path of the source file, relative to the project
+    pub source: String,
+    pub source_id: Option<usize>,
+    /// Deployment bytecode, empty for interfaces and abstract contracts
+    pub code: Vec<u8>,
+    pub abi: String,
+    /// Source map of the runtime bytecode
+    pub source_map: String,
+}
+
+/// Parses a Foundry artifact, `None` if it has unlinked libraries
+pub fn parse_foundry_artifact(json: &Value) -> Option<FoundryArtifact> {
+    let (source, name) = json["metadata"]["settings"]["compilationTarget"]
+        .as_object()
+        .and_then(|target| target.iter().next())
+        .and_then(|(source, name)| Some((source.clone(), name.as_str()?.to_string())))?;
+    let code = json["bytecode"]["object"].as_str().unwrap_or_default();
+    let code = match hex::decode(code.trim_start_matches("0x")) {
+        Ok(code) => code,
+        Err(_) => {
+            debug!("Skipping {}:{} with unlinked libraries", source, name);
+            return None;
+        }
+    };
+    Some(FoundryArtifact {
+        name,
+        source,
+        source_id: json["id"].as_u64().or(json["ast"]["id"].as_u64()).map(|id| id as usize),
+        code,
+        abi: json["abi"].to_string(),
+        source_map: json["deployedBytecode"]["sourceMap"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+/// Kind of a transaction recorded by `forge script --broadcast`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastTxKind {
+    Create,
+    /// Creation through the deterministic deployment proxy
+    Create2,
+    Call,
+}
+
+/// Transaction of a Foundry broadcast file
+#[derive(Debug, Clone)]
+pub struct BroadcastTransaction {
+    pub kind: BroadcastTxKind,
+    pub contract_name: Option<String>,
+    /// Created contract, or called one for calls
+    pub contract_address: EVMAddress,
+    pub from: EVMAddress,
+    pub value: EVMU256,
+    /// Init code (without the salt for CREATE2) or calldata
+    pub input: Vec<u8>,
+    /// Contracts created by the transaction, in order
+    pub additional_contracts: Vec<EVMAddress>,
+}
+
+/// Parses the transactions of a Foundry broadcast file, skipping the ones
+/// that are not understood
+pub fn parse_broadcast(json: &Value) -> Vec<BroadcastTransaction> {
+    let parse_address = |value: &Value| EVMAddress::from_str(value.as_str()?).ok();
+    let parse_bytes = |value: &Value| hex::decode(value.as_str()?.trim_start_matches("0x")).ok();
+    json["transactions"]
+        .as_array()
+        .map(|txs| txs.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|tx| {
+            let kind = match tx["transactionType"].as_str()? {
+                "CREATE" => BroadcastTxKind::Create,
+                "CREATE2" => BroadcastTxKind::Create2,
+                "CALL" => BroadcastTxKind::Call,
+                other => {
+                    debug!("Skipping broadcast transaction of type {}", other);
+                    return None;
+                }
+            };
+            let inner = &tx["transaction"];
+            // older versions of forge name the calldata `data`
+            let mut input = parse_bytes(&inner["input"]).or_else(|| parse_bytes(&inner["data"]))?;
+            if kind == BroadcastTxKind::Create2 {
+                // the deterministic deployment proxy takes the salt then the init code
+                input = input.get(32..)?.to_vec();
+            }
+            let contract_address = match kind {
+                BroadcastTxKind::Call => {
+                    parse_address(&inner["to"]).or_else(|| parse_address(&tx["contractAddress"]))?
+                }
+                _ => parse_address(&tx["contractAddress"])?,
+            };
+            let value = inner["value"]
+                .as_str()
+                .and_then(|value| EVMU256::from_str_radix(value.trim_start_matches("0x"), 16).ok())
+                .unwrap_or_default();
+            Some(BroadcastTransaction {
+                kind,
+                contract_name: tx["contractName"].as_str().map(|s| s.to_string()),
+                contract_address,
+                from: parse_address(&inner["from"])?,
+                value,
+                input,
+                additional_contracts: tx["additionalContracts"]
+                    .as_array()
+                    .map(|contracts| contracts.iter().filter_map(|c| parse_address(&c["address"])).collect())
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Parses a Hardhat artifact into its (source, contract name) and its
+/// bytecode, which may have library placeholders, `None` if it is not one
+pub fn parse_hardhat_artifact(json: &Value) -> Option<((String, String), ContractArtifact)> {
+    if !json["_format"].as_str()?.starts_with("hh-sol-artifact") {
+        return None;
+    }
+    let key = (
+        json["sourceName"].as_str()?.to_string(),
+        json["contractName"].as_str()?.to_string(),
+    );
+    let artifact = ContractArtifact {
+        deploy_bytecode_str: json["bytecode"].as_str()?.trim_start_matches("0x").to_string(),
+        abi: json["abi"].to_string(),
+        link_references: serde_json::from_value(json["linkReferences"].clone()).unwrap_or_default(),
+        ..Default::default()
+    };
+    Some((key, artifact))
+}
+
+/// Whether the source is a dependency of a Hardhat project
+pub fn is_hardhat_dependency(source: &str) -> bool {
+    source.starts_with('@') || source.starts_with("hardhat/")
+}
+
+/// Whether the source is a test, a script or a dependency of a Foundry project
+pub fn is_foundry_dependency(source: &str) -> bool {
+    ["test/", "script/", "lib/"].iter().any(|dir| source.starts_with(dir)) ||
+        source.ends_with(".t.sol") ||
+        source.ends_with(".s.sol")
+}
+
 type CombinedJsonOutput = (
     Vec<(String, String)>,
     Option<Vec<(String, String)>>,
@@ -1395,6 +1990,35 @@ mod tests {
     use super::*;
     use crate::{skip_cbor, state::FuzzState};
 
+    #[test]
+    fn test_invoke_setup() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> = EVMExecutor::new(
+            FuzzHost::new(StdScheduler::new(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+        let caller = executor.deployer;
+        // PUSH1 1 PUSH1 0 SSTORE STOP
+        let harness = generate_random_address(&mut state);
+        executor.host.set_code(
+            harness,
+            Bytecode::new_raw(Bytes::from(vec![0x60, 0x01, 0x60, 0x00, 0x55, 0x00])),
+            &mut state,
+        );
+        // PUSH1 0 PUSH1 0 REVERT
+        let failing = generate_random_address(&mut state);
+        executor.host.set_code(
+            failing,
+            Bytecode::new_raw(Bytes::from(vec![0x60, 0x00, 0x60, 0x00, 0xfd])),
+            &mut state,
+        );
+
+        let vm_state = EVMState::new();
+        let new_state = ContractLoader::invoke_setup(&mut executor, &mut state, caller, harness, &vm_state).unwrap();
+        assert_eq!(new_state.sload(harness, EVMU256::ZERO), Some(EVMU256::from(1)));
+        assert!(ContractLoader::invoke_setup(&mut executor, &mut state, caller, failing, &vm_state).is_none());
+    }
+
     #[test]
     fn test_load() {
         let codes: Vec<String> = vec![];
@@ -1455,4 +2079,109 @@ mod tests {
         assert_eq!(abi_cfg.function_name, "constructor");
         assert_eq!(abi_cfg.abi, "(address)");
     }
+
+    #[test]
+    fn test_parse_foundry_artifact() {
+        let json = serde_json::json!({
+            "abi": [{"type": "function", "name": "foo", "inputs": [], "outputs": [], "stateMutability": "nonpayable"}],
+            "bytecode": {"object": "0x6080604052"},
+            "deployedBytecode": {"object": "0x6080", "sourceMap": "0:10:0:-:0"},
+            "metadata": {"settings": {"compilationTarget": {"src/Token.sol": "Token"}}},
+            "id": 3
+        });
+        let artifact = parse_foundry_artifact(&json).unwrap();
+        assert_eq!(artifact.name, "Token");
+        assert_eq!(artifact.source, "src/Token.sol");
+        assert_eq!(artifact.source_id, Some(3));
+        assert_eq!(artifact.code, vec![0x60, 0x80, 0x60, 0x40, 0x52]);
+        assert_eq!(artifact.source_map, "0:10:0:-:0");
+        assert_eq!(ContractLoader::parse_abi_str(&artifact.abi).len(), 1);
+
+        let mut unlinked = json.clone();
+        unlinked["bytecode"]["object"] = serde_json::json!("0x73__$b5ba0e2c$__63");
+        assert!(parse_foundry_artifact(&unlinked).is_none());
+
+        assert!(is_foundry_dependency("test/Token.t.sol"));
+        assert!(is_foundry_dependency("lib/forge-std/src/Test.sol"));
+        assert!(!is_foundry_dependency("src/Token.sol"));
+    }
+
+    #[test]
+    fn test_parse_broadcast() {
+        let json = serde_json::json!({
+            "transactions": [
+                {
+                    "transactionType": "CREATE",
+                    "contractName": "Token",
+                    "contractAddress": "0x5fbdb2315678afecb367f032d93f642f64180aa3",
+                    "transaction": {"from": "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266", "value": "0x0", "input": "0x6080"},
+                    "additionalContracts": [{"transactionType": "CREATE", "address": "0xa16e02e87b7454126e5e10d957a927a7f5b5d2be", "initCode": "0x"}]
+                },
+                {
+                    "transactionType": "CREATE2",
+                    "contractName": "Vault",
+                    "contractAddress": "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512",
+                    "transaction": {
+                        "from": "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+                        "to": "0x4e59b44847b379578588920ca78fbf26c0b4956c",
+                        "data": format!("0x{}6080", "00".repeat(32))
+                    }
+                },
+                {
+                    "transactionType": "CALL",
+                    "contractName": "Vault",
+                    "contractAddress": "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512",
+                    "transaction": {
+                        "from": "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+                        "to": "0xe7f1725e7734ce288f8367e1bb143e90bb3f0512",
+                        "value": "0x10",
+                        "input": "0x12345678"
+                    }
+                },
+                {"transactionType": "UNKNOWN"}
+            ]
+        });
+        let txs = parse_broadcast(&json);
+        assert_eq!(txs.len(), 3);
+        assert_eq!(txs[0].kind, BroadcastTxKind::Create);
+        assert_eq!(txs[0].contract_name, Some("Token".to_string()));
+        assert_eq!(txs[0].input, vec![0x60, 0x80]);
+        assert_eq!(txs[0].additional_contracts.len(), 1);
+        assert_eq!(txs[1].kind, BroadcastTxKind::Create2);
+        assert_eq!(txs[1].input, vec![0x60, 0x80]);
+        assert_eq!(txs[2].kind, BroadcastTxKind::Call);
+        assert_eq!(txs[2].contract_address, txs[1].contract_address);
+        assert_eq!(txs[2].value, EVMU256::from(16));
+        assert_eq!(txs[2].input, vec![0x12, 0x34, 0x56, 0x78]);
+    }
+
+    #[test]
+    fn test_parse_hardhat_artifact() {
+        let placeholder = "__$b5ba0e2ccb0ebb2f9c7f2b1b04a9f0e4d6$__";
+        let json = serde_json::json!({
+            "_format": "hh-sol-artifact-1",
+            "contractName": "Vault",
+            "sourceName": "contracts/Vault.sol",
+            "abi": [],
+            "bytecode": format!("0x73{}63", placeholder),
+            "linkReferences": {"contracts/Math.sol": {"Math": [{"start": 1, "length": 20}]}}
+        });
+        let (key, artifact) = parse_hardhat_artifact(&json).unwrap();
+        assert_eq!(key, ("contracts/Vault.sol".to_string(), "Vault".to_string()));
+        assert_eq!(artifact.link_references["contracts/Math.sol"]["Math"][0].start, 1);
+
+        let libs = BTreeMap::from([(
+            ("contracts/Math.sol".to_string(), "Math".to_string()),
+            ContractArtifact::default(),
+        )]);
+        let linked = Linker::link_setup_target_with_compute_address(&libs, artifact).unwrap();
+        let address = compute_address(&("contracts/Math.sol".to_string(), "Math".to_string()));
+        assert_eq!(linked.deploy_bytecode_str, format!("73{}63", address));
+
+        let mut foundry = json.clone();
+        foundry["_format"] = serde_json::json!("ethers-rs-sol-cache-1");
+        assert!(parse_hardhat_artifact(&foundry).is_none());
+        assert!(is_hardhat_dependency("@openzeppelin/contracts/token/ERC20/ERC20.sol"));
+        assert!(!is_hardhat_dependency("contracts/Vault.sol"));
+    }
 }
@@ -21,7 +21,7 @@ use libafl::{
 use libafl_bolts::impl_serdeany;
 use revm_primitives::{Bytecode, Env};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use super::{scheduler::ABIScheduler, srcmap::SOURCE_MAP_PROVIDER};
 /// Utilities to initialize the corpus
@@ -34,11 +34,20 @@ use crate::{
     evm::{
         blaz::builder::BuildJobResult,
         bytecode_analyzer,
-        contract_utils::{extract_sig_from_contract, to_hex_string, ABIConfig, ContractLoader},
+        contract_utils::{extract_sig_from_contract, set_hash, to_hex_string, ABIConfig, ContractInfo, ContractLoader},
+        dictionary::add_bytecode_to_dictionary,
         input::{ConciseEVMInput, EVMInput, EVMInputTy},
+        mempool::VictimTx,
         middlewares::cheatcode::CHEATCODE_ADDRESS,
         mutator::AccessPattern,
-        onchain::{abi_decompiler::fetch_abi_heimdall, flashloan::register_borrow_txn, BLACKLIST_ADDR},
+        onchain::{
+            abi_decompiler::fetch_abi_heimdall,
+            abi_inference::infer_abi,
+            flashloan::register_borrow_txn,
+            signatures::is_unnamed,
+            BLACKLIST_ADDR,
+        },
+        oracles::contract_size::ContractSize,
         presets::Preset,
         types::{
             fixed_address,
@@ -49,7 +58,7 @@ use crate::{
             EVMStagedVMState,
             EVMU256,
         },
-        vm::{EVMExecutor, EVMState},
+        vm::{EVMExecutor, EVMState, IN_DEPLOY, SETCODE_ONLY},
     },
     fuzzer::REPLAY,
     generic_vm::vm_executor::GenericVM,
@@ -73,6 +82,24 @@ where
     #[cfg(feature = "use_presets")]
     presets: Vec<&'a dyn Preset<EVMInput, EVMState, SC>>,
     work_dir: String,
+    /// Callers replacing the default ones, if not empty
+    callers: Vec<EVMAddress>,
+    /// Initial balances of the callers, [`INITIAL_BALANCE`] if missing
+    caller_balances: HashMap<EVMAddress, EVMU256>,
+    /// Holders of privileged roles added to the callers, keeping their onchain
+    /// balances
+    privileged_callers: Vec<EVMAddress>,
+    /// Whether to fuzz the constructor args of the deployed contracts
+    fuzz_constructor_args: bool,
+    /// Whether to run `setUp()` of the deployed contracts defining it
+    harness_setup: bool,
+    /// Environment after the `setUp()` of a harness, kept for fuzzing
+    harness_env: Option<Env>,
+    /// Pending transaction of a user the front-runs and back-runs are built
+    /// around
+    victim_tx: Option<VictimTx>,
+    /// Whether to infer the args of the contracts without ABI by probing
+    infer_interface: bool,
 }
 
 #[derive(Default)]
@@ -177,16 +204,97 @@ where
             #[cfg(feature = "use_presets")]
             presets: vec![],
             work_dir,
+            callers: vec![],
+            caller_balances: HashMap::new(),
+            privileged_callers: vec![],
+            harness_env: None,
+            fuzz_constructor_args: false,
+            harness_setup: false,
+            victim_tx: None,
+            infer_interface: false,
         }
     }
 
+    /// Use `callers` instead of the default callers
+    pub fn set_callers(&mut self, callers: Vec<EVMAddress>) {
+        self.callers = callers;
+    }
+
+    /// Set the initial balance of a caller
+    pub fn set_caller_balance(&mut self, caller: EVMAddress, balance: EVMU256) {
+        self.caller_balances.insert(caller, balance);
+    }
+
+    /// Add holders of privileged roles to the callers
+    pub fn set_privileged_callers(&mut self, callers: Vec<EVMAddress>) {
+        self.privileged_callers = callers;
+    }
+
+    fn caller_balance(&self, caller: &EVMAddress) -> EVMU256 {
+        self.caller_balances
+            .get(caller)
+            .cloned()
+            .unwrap_or(EVMU256::from(INITIAL_BALANCE))
+    }
+
+    /// Add transactions redeploying the contracts with fuzzed constructor args
+    /// to the corpus
+    pub fn set_fuzz_constructor_args(&mut self, fuzz_constructor_args: bool) {
+        self.fuzz_constructor_args = fuzz_constructor_args;
+    }
+
+    /// Run `setUp()` of the deployed contracts defining it once before
+    /// fuzzing, as Foundry does for test harnesses
+    pub fn set_harness_setup(&mut self, harness_setup: bool) {
+        self.harness_setup = harness_setup;
+    }
+
+    /// Add the pending transaction of the victim to the corpus
+    pub fn set_victim_tx(&mut self, victim_tx: Option<VictimTx>) {
+        self.victim_tx = victim_tx;
+    }
+
+    /// Infer the args of the contracts without ABI by probing them instead of
+    /// only decompiling them
+    pub fn set_infer_interface(&mut self, infer_interface: bool) {
+        self.infer_interface = infer_interface;
+    }
+
+    /// ABI of the contract at `address` without ABI, its args inferred by
+    /// calling it on the current state, which is left unchanged
+    fn probe_interface(&mut self, address: EVMAddress, code: String) -> Vec<ABIConfig> {
+        let vm_state = self.executor.host.evmstate.clone();
+        let caller = self.executor.deployer;
+        // no middleware, e.g., for coverage
+        unsafe {
+            IN_DEPLOY = true;
+        }
+        let abis = infer_abi(address, code, |calldata| {
+            let call = (caller, address, Bytes::from(calldata));
+            let (mut res, _) = self.executor.fast_call(&[call], &vm_state, self.state);
+            res.remove(0)
+        });
+        unsafe {
+            IN_DEPLOY = false;
+        }
+        self.executor.host.evmstate = vm_state;
+        abis
+    }
+
     #[cfg(feature = "use_presets")]
     pub fn register_preset(&mut self, preset: &'a dyn Preset<EVMInput, EVMState, SC>) {
         self.presets.push(preset);
     }
 
     pub fn initialize(&mut self, loader: &mut ContractLoader) -> EVMInitializationArtifacts {
-        self.state.metadata_map_mut().insert(ABIMap::new());
+        // known signatures, used to recover the ABI of contracts created by others
+        let mut abi_map = ABIMap::new();
+        for abi in loader.abis.iter().flat_map(|abis| abis.abi.iter()) {
+            if !abi.is_constructor {
+                abi_map.insert(abi.clone());
+            }
+        }
+        self.state.metadata_map_mut().insert(abi_map);
         self.setup_default_callers(loader);
         self.setup_contract_callers(loader);
         self.init_cheatcode_contract();
@@ -196,20 +304,38 @@ where
     }
 
     pub fn initialize_contract(&mut self, loader: &mut ContractLoader) {
-        self.executor
-            .host
-            .evmstate
-            .set_balance(self.executor.deployer, EVMU256::from(INITIAL_BALANCE));
+        let balance = self.caller_balance(&self.executor.deployer);
+        self.executor.host.evmstate.set_balance(self.executor.deployer, balance);
+        // contracts created by the constructors, with the contract creating them
+        let mut created = vec![];
         // deploy
         for contract in &mut loader.contracts {
             info!("Deploying contract: {}", contract.name);
             let deployed_address = if !contract.is_code_deployed {
-                match self.executor.deploy(
+                let existing = self.executor.host.code.keys().cloned().collect::<HashSet<_>>();
+                // the ABIs of the created contracts are recovered once all are deployed
+                unsafe {
+                    SETCODE_ONLY = true;
+                }
+                let deployed = self.executor.deploy(
                     Bytecode::new_raw(Bytes::from(contract.code.clone())),
                     Some(Bytes::from(contract.constructor_args.clone())),
                     contract.deployed_address,
                     self.state,
-                ) {
+                );
+                unsafe {
+                    SETCODE_ONLY = false;
+                }
+                created.extend(
+                    self.executor
+                        .host
+                        .code
+                        .keys()
+                        .filter(|addr| !existing.contains(addr) && **addr != contract.deployed_address)
+                        .sorted()
+                        .map(|addr| (*addr, contract.name.clone())),
+                );
+                match deployed {
                     Some(addr) => addr,
                     None => {
                         error!("Failed to deploy contract: {}", contract.name);
@@ -228,15 +354,127 @@ where
                 contract.deployed_address
             };
             contract.deployed_address = deployed_address;
+            if let Some(code) = self.executor.host.code.get(&deployed_address) {
+                let code = Bytecode::new_raw(code.bytecode().clone());
+                add_bytecode_to_dictionary(self.state, deployed_address, &code);
+            }
             info!("Contract {} deployed to: {deployed_address:?}", contract.name);
+            if !contract.is_code_deployed {
+                let runtime_size = self
+                    .executor
+                    .host
+                    .code
+                    .get(&deployed_address)
+                    .map(|code| code.len())
+                    .unwrap_or_default();
+                let size = ContractSize::of_contract(contract, runtime_size);
+                info!("Contract {}: {}", contract.name, size);
+                for violation in size.violations() {
+                    warn!("Contract {} can not be deployed on chain: {}", contract.name, violation);
+                }
+
+                let has_args = contract.abi.iter().any(|abi| abi.is_constructor && abi.abi != "()");
+                if self.fuzz_constructor_args && has_args && contract.code.ends_with(&contract.constructor_args) {
+                    let init_code = &contract.code[..contract.code.len() - contract.constructor_args.len()];
+                    self.executor
+                        .constructors
+                        .insert(deployed_address, Bytes::from(init_code.to_vec()));
+                }
+            }
 
             if deployed_address != CHEATCODE_ADDRESS {
                 self.state.add_address(&deployed_address);
             }
+
+            let mut setup = [0; 4];
+            set_hash("setUp()", &mut setup);
+            if self.harness_setup && !contract.is_code_deployed && contract.abi.iter().any(|abi| abi.function == setup)
+            {
+                let setup_created = self.run_harness_setup(deployed_address);
+                created.extend(setup_created.into_iter().map(|addr| (addr, contract.name.clone())));
+            }
         }
+        self.add_created_contracts(loader, created);
         info!("Deployed all contracts\n");
     }
 
+    /// Run `setUp()` of the harness at `harness` once, keeping the state it
+    /// establishes (e.g., mocks deployed, accounts funded) to fuzz its other
+    /// functions from. Returns the contracts it created.
+    fn run_harness_setup(&mut self, harness: EVMAddress) -> Vec<EVMAddress> {
+        let existing = self.executor.host.code.keys().cloned().collect::<HashSet<_>>();
+        let vm_state = self.executor.host.evmstate.clone();
+        let deployer = self.executor.deployer;
+        unsafe {
+            SETCODE_ONLY = true;
+            IN_DEPLOY = true;
+        }
+        let new_state = ContractLoader::invoke_setup(self.executor, self.state, deployer, harness, &vm_state);
+        unsafe {
+            IN_DEPLOY = false;
+            SETCODE_ONLY = false;
+        }
+        let Some(new_state) = new_state else {
+            self.executor.host.evmstate = vm_state;
+            return vec![];
+        };
+        info!("setUp() of harness {:?} executed", harness);
+        self.executor.host.evmstate = new_state;
+        self.harness_env = Some(self.executor.host.env.clone());
+        self.executor
+            .host
+            .code
+            .keys()
+            .filter(|addr| !existing.contains(addr))
+            .sorted()
+            .cloned()
+            .collect()
+    }
+
+    /// Add the contracts created by the constructors (e.g., helper contracts
+    /// or clones) to the loader so that their functions are fuzzed as well. A
+    /// created contract with the same runtime code as a loaded one takes its
+    /// name and ABI, otherwise its ABI is recovered from the known signatures
+    /// or by decompiling it.
+    fn add_created_contracts(&mut self, loader: &mut ContractLoader, created: Vec<(EVMAddress, String)>) {
+        for (addr, creator) in created {
+            let code = match self.executor.host.code.get(&addr) {
+                Some(code) => code.bytecode().to_vec(),
+                None => continue,
+            };
+            let known = loader.contracts.iter().find(|contract| {
+                self.executor
+                    .host
+                    .code
+                    .get(&contract.deployed_address)
+                    .is_some_and(|other| other.bytecode()[..] == code[..])
+            });
+            let (name, abi) = match known {
+                Some(contract) => (contract.name.clone(), contract.abi.clone()),
+                None => (format!("{}:created", creator.trim_end_matches('*')), vec![]),
+            };
+            info!("Contract {} created by {} at {:?}", name, creator, addr);
+
+            let contract_code = Bytecode::new_raw(Bytes::from(code.clone()));
+            bytecode_analyzer::add_analysis_result_to_state(&contract_code, self.state);
+            add_bytecode_to_dictionary(self.state, addr, &contract_code);
+            self.executor.host.set_code(addr, contract_code, self.state);
+            self.state.add_address(&addr);
+            loader.contracts.push(ContractInfo {
+                name,
+                code,
+                abi,
+                is_code_deployed: true,
+                constructor_args: vec![],
+                deployed_address: addr,
+                build_artifact: None,
+                files: vec![],
+                source_map_replacements: None,
+                raw_source_map: None,
+            });
+        }
+    }
+
     fn initialize_source_map(&self, loader: &ContractLoader) {
         for contract in &loader.contracts {
             if SOURCE_MAP_PROVIDER
@@ -302,7 +540,7 @@ where
             build_artifacts: Default::default(),
             initial_env: match loader.setup_data {
                 Some(ref setup_data) => setup_data.env.clone(),
-                None => Default::default(),
+                None => self.harness_env.clone().unwrap_or_default(),
             },
         };
 
@@ -330,8 +568,14 @@ where
                 }
 
                 if unknown_sigs >= sigs.len() / UNKNOWN_SIGS_DIVISOR {
-                    info!("Too many unknown function signature for {:?}, we are going to decompile this contract using Heimdall", contract.name);
-                    let abis = fetch_abi_heimdall(contract_code)
+                    let abis = if self.infer_interface {
+                        info!("Too many unknown function signature for {:?}, we are going to infer its interface by probing", contract.name);
+                        self.probe_interface(contract.deployed_address, contract_code)
+                    } else {
+                        info!("Too many unknown function signature for {:?}, we are going to decompile this contract using Heimdall", contract.name);
+                        fetch_abi_heimdall(contract_code)
+                    };
+                    let abis = abis
                         .iter()
                         .map(|abi| {
                             if let Some(known_abi) =
@@ -346,6 +590,23 @@ where
                         .collect_vec();
                     contract.abi = abis;
                 }
+            } else if contract.abi.iter().any(is_unnamed) {
+                // the decompiled functions without signature on 4byte.directory
+                // take the signature known from another contract, or their
+                // probed args
+                let probed = if self.infer_interface {
+                    self.probe_interface(contract.deployed_address, hex::encode(contract.code.clone()))
+                } else {
+                    vec![]
+                };
+                let abi_map = self.state.metadata_map().get::<ABIMap>().unwrap();
+                for abi in contract.abi.iter_mut().filter(|abi| is_unnamed(abi)) {
+                    if let Some(known_abi) = abi_map.get(&abi.function) {
+                        *abi = known_abi.clone();
+                    } else if let Some(probed) = probed.iter().find(|probed| probed.function == abi.function) {
+                        abi.abi = probed.abi.clone();
+                    }
+                }
             }
 
             artifacts
@@ -436,6 +697,11 @@ where
             }
         }
 
+        if let Some(victim_tx) = &self.victim_tx {
+            let input = victim_tx.to_input(&artifacts.initial_env);
+            add_input_to_corpus!(self.state, &mut self.scheduler, input, &artifacts);
+        }
+
         let mut tc = Testcase::new(artifacts.initial_state.clone());
         tc.set_exec_time(Duration::from_secs(0));
         let idx = self
@@ -451,6 +717,10 @@ where
     }
 
     pub fn setup_default_callers(&mut self, loader: &mut ContractLoader) {
+        for caller in self.privileged_callers.iter() {
+            self.state.add_caller(caller);
+        }
+
         // We override default callers when target senders are specified
         if let Some(setup_data) = &loader.setup_data {
             if !setup_data.target_senders.is_empty() {
@@ -465,18 +735,22 @@ where
             }
         }
 
-        let default_callers = HashSet::from([
-            fixed_address("8EF508Aca04B32Ff3ba5003177cb18BfA6Cd79dd"),
-            fixed_address("35c9dfd76bf02107ff4f7128Bd69716612d31dDb"),
-            // fixed_address("5E6B78f0748ACd4Fb4868dF6eCcfE41398aE09cb"),
-        ]);
+        // in a fixed order (the pool is deduplicated), for the callers drawn with
+        // a given seed to be the same across runs
+        let default_callers = if self.callers.is_empty() {
+            vec![
+                fixed_address("8EF508Aca04B32Ff3ba5003177cb18BfA6Cd79dd"),
+                fixed_address("35c9dfd76bf02107ff4f7128Bd69716612d31dDb"),
+                // fixed_address("5E6B78f0748ACd4Fb4868dF6eCcfE41398aE09cb"),
+            ]
+        } else {
+            self.callers.clone()
+        };
 
         for caller in default_callers {
             self.state.add_caller(&caller);
-            self.executor
-                .host
-                .evmstate
-                .set_balance(caller, EVMU256::from(INITIAL_BALANCE));
+            let balance = self.caller_balance(&caller);
+            self.executor.host.evmstate.set_balance(caller, balance);
         }
     }
 
@@ -489,12 +763,16 @@ where
                 return;
             }
         }
+        // nor when the callers are given
+        if !self.callers.is_empty() {
+            return;
+        }
 
-        let contract_callers = HashSet::from([
+        let contract_callers = [
             fixed_address("e1A425f1AC34A8a441566f93c82dD730639c8510"),
             fixed_address("68Dd4F5AC792eAaa5e36f4f4e0474E0625dc9024"),
             // fixed_address("aF97EE5eef1B02E12B650B8127D8E8a6cD722bD2"),
-        ]);
+        ];
         for caller in contract_callers {
             self.state.add_caller(&caller);
             self.executor
@@ -517,6 +795,9 @@ where
 
     fn add_abi(&mut self, abi: &ABIConfig, deployed_address: EVMAddress, artifacts: &mut EVMInitializationArtifacts) {
         if abi.is_constructor {
+            if self.executor.constructors.contains_key(&deployed_address) {
+                self.add_constructor_abi(abi, deployed_address, artifacts);
+            }
             return;
         }
 
@@ -557,6 +838,7 @@ where
             env: artifacts.initial_env.clone(),
             access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
             liquidation_percent: 0,
+            liquidation_path: 0,
             input_type: EVMInputTy::ABI,
             direct_data: Default::default(),
             randomness: vec![0],
@@ -580,4 +862,187 @@ where
             }
         }
     }
+
+    /// Add a transaction redeploying the contract at `deployed_address` with
+    /// fuzzed constructor args
+    fn add_constructor_abi(
+        &mut self,
+        abi: &ABIConfig,
+        deployed_address: EVMAddress,
+        artifacts: &mut EVMInitializationArtifacts,
+    ) {
+        let mut abi_instance = get_abi_type_boxed(&abi.abi);
+        abi_instance.set_func_with_signature(abi.function, &abi.function_name, &abi.abi);
+        let input = EVMInput {
+            caller: self.executor.deployer,
+            contract: deployed_address,
+            data: Some(abi_instance),
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: artifacts.initial_env.clone(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            input_type: EVMInputTy::Deploy,
+            direct_data: Default::default(),
+            randomness: vec![0],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        };
+        add_input_to_corpus!(self.state, &mut self.scheduler, input, artifacts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evm::{host::FuzzHost, scheduler::PowerABIScheduler, types::generate_random_address},
+        scheduler::SortedDroppingScheduler,
+        state::FuzzState,
+    };
+
+    fn contract(name: &str, code: Vec<u8>, abi: Vec<ABIConfig>, deployed_address: EVMAddress) -> ContractInfo {
+        ContractInfo {
+            name: name.to_string(),
+            code,
+            abi,
+            is_code_deployed: true,
+            constructor_args: vec![],
+            deployed_address,
+            build_artifact: None,
+            files: vec![],
+            source_map_replacements: None,
+            raw_source_map: None,
+        }
+    }
+
+    #[test]
+    fn test_add_created_contracts() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut executor = EVMExecutor::<EVMState, ConciseEVMInput, PowerABIScheduler<EVMFuzzState>>::new(
+            FuzzHost::new(PowerABIScheduler::default(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+        let token = EVMAddress::from_low_u64_be(1);
+        let clone = EVMAddress::from_low_u64_be(2);
+        let helper = EVMAddress::from_low_u64_be(3);
+        let token_code = hex::decode("6001600055").unwrap();
+        for (addr, code) in [
+            (token, token_code.clone()),
+            (clone, token_code.clone()),
+            (helper, vec![0x00]),
+        ] {
+            executor
+                .host
+                .set_code(addr, Bytecode::new_raw(Bytes::from(code)), &mut state);
+        }
+        let transfer = ABIConfig {
+            abi: "(address,uint256)".to_string(),
+            function: [0xa9, 0x05, 0x9c, 0xbb],
+            function_name: "transfer".to_string(),
+            is_static: false,
+            is_payable: false,
+            is_constructor: false,
+            should_add_corpus: true,
+        };
+        let mut loader = ContractLoader {
+            contracts: vec![contract("Token", token_code, vec![transfer], token)],
+            abis: vec![],
+            setup_data: None,
+        };
+
+        let mut initializer = EVMCorpusInitializer::new(
+            &mut executor,
+            PowerABIScheduler::default(),
+            SortedDroppingScheduler::new(),
+            &mut state,
+            "work_dir".to_string(),
+        );
+        initializer.add_created_contracts(
+            &mut loader,
+            vec![
+                (clone, "Factory".to_string()),
+                (helper, "Factory*".to_string()),
+                // no code, e.g., selfdestructed in the constructor
+                (EVMAddress::from_low_u64_be(4), "Factory".to_string()),
+            ],
+        );
+
+        // the clone takes the name and ABI of the contract with the same code
+        let names = loader.contracts.iter().map(|c| c.name.as_str()).collect_vec();
+        assert_eq!(names, vec!["Token", "Token", "Factory:created"]);
+        assert_eq!(loader.contracts[1].deployed_address, clone);
+        assert_eq!(loader.contracts[1].abi[0].function_name, "transfer");
+        assert!(loader.contracts[2].abi.is_empty());
+        assert!(state.addresses_pool.contains(&clone));
+        assert!(state.addresses_pool.contains(&helper));
+    }
+
+    /// Callers pool and (caller, function) of the corpus after initializing a
+    /// token with `seed`
+    fn initialize(seed: u64) -> (Vec<EVMAddress>, Vec<(EVMAddress, [u8; 4])>) {
+        let mut state: EVMFuzzState = FuzzState::new(seed);
+        let mut executor = EVMExecutor::<EVMState, ConciseEVMInput, PowerABIScheduler<EVMFuzzState>>::new(
+            FuzzHost::new(PowerABIScheduler::default(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+        let abi = |function_name: &str, function: [u8; 4]| ABIConfig {
+            abi: "(address,uint256)".to_string(),
+            function,
+            function_name: function_name.to_string(),
+            is_static: false,
+            is_payable: false,
+            is_constructor: false,
+            should_add_corpus: true,
+        };
+        let token = EVMAddress::from_low_u64_be(1);
+        let mut loader = ContractLoader {
+            contracts: vec![contract(
+                "Token",
+                hex::decode("6001600055").unwrap(),
+                vec![
+                    abi("transfer", [0xa9, 0x05, 0x9c, 0xbb]),
+                    abi("approve", [0x09, 0x5e, 0xa7, 0xb3]),
+                    abi("mint", [0x40, 0xc1, 0x0f, 0x19]),
+                ],
+                token,
+            )],
+            abis: vec![],
+            setup_data: None,
+        };
+        let work_dir = std::env::temp_dir().join(format!("ityfuzz_initializer_{}", std::process::id()));
+        let mut initializer = EVMCorpusInitializer::new(
+            &mut executor,
+            PowerABIScheduler::default(),
+            SortedDroppingScheduler::new(),
+            &mut state,
+            work_dir.to_string_lossy().to_string(),
+        );
+        initializer.set_callers((10..20).map(EVMAddress::from_low_u64_be).collect());
+        initializer.initialize(&mut loader);
+        let _ = std::fs::remove_dir_all(work_dir);
+
+        let mut corpus = vec![];
+        let mut id = state.corpus().first();
+        while let Some(tc_id) = id {
+            let testcase = state.corpus().get(tc_id).unwrap().borrow();
+            let input = testcase.input().as_ref().unwrap();
+            corpus.push((input.caller, input.data.as_ref().unwrap().function));
+            id = state.corpus().next(tc_id);
+        }
+        (state.callers_pool.clone(), corpus)
+    }
+
+    #[test]
+    fn test_deterministic_initialization() {
+        let (callers, corpus) = initialize(42);
+        assert_eq!(callers, (10..20).map(EVMAddress::from_low_u64_be).collect::<Vec<_>>());
+        assert_eq!(corpus.len(), 3);
+        for _ in 0..3 {
+            assert_eq!(initialize(42), (callers.clone(), corpus.clone()));
+        }
+    }
 }
@@ -0,0 +1,430 @@
+/// Synchronization of the corpus and the branch coverage between fuzzer
+/// instances running on different machines, through a shared store (a
+/// directory, a rsync / S3 remote mirrored locally, or a HTTP server)
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use libafl::{
+    events::ProgressReporter,
+    prelude::{CorpusId, HasMetadata, ObserversTuple, Stage},
+    state::UsesState,
+    Error,
+    Evaluator,
+};
+use reqwest::blocking;
+use tracing::{debug, info, warn};
+
+use crate::{
+    evm::{
+        host::CALL_UNTIL,
+        input::ConciseEVMInput,
+        scheduler::{BranchCoveredStatus, UncoveredBranchesMetadata},
+        types::{EVMAddress, EVMFuzzExecutor, EVMFuzzState, EVMStagedVMState},
+    },
+    state::HasExecutionResult,
+};
+
+/// Suffix of the entries holding the branch coverage of an instance
+const BRANCHES_SUFFIX: &str = ".branches";
+
+/// A store shared by all fuzzer instances, holding flat named entries
+pub trait SyncStore {
+    /// Names of all entries in the store
+    fn list(&mut self) -> Result<Vec<String>, String>;
+    /// Content of an entry
+    fn get(&mut self, name: &str) -> Result<Vec<u8>, String>;
+    /// Create or overwrite an entry
+    fn put(&mut self, name: &str, data: &[u8]) -> Result<(), String>;
+}
+
+/// Store in a directory, e.g., on a shared file system
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: &str) -> Self {
+        fs::create_dir_all(dir).expect("failed to create corpus sync directory");
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+impl SyncStore for DirStore {
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| e.to_string())?;
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            // entries being written
+            .filter(|name| !name.starts_with('.'))
+            .collect())
+    }
+
+    fn get(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.dir.join(name)).map_err(|e| e.to_string())
+    }
+
+    fn put(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        // write then rename so that other instances never read partial entries
+        let tmp = self.dir.join(format!(".{}", name));
+        fs::write(&tmp, data).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, self.dir.join(name)).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorKind {
+    /// `rsync` remote, e.g., `user@host:/path`
+    Rsync,
+    /// S3 bucket through the `aws` CLI, e.g., `s3://bucket/prefix`
+    S3,
+}
+
+/// Remote store mirrored into a local directory with `rsync` or `aws s3`
+pub struct MirrorStore {
+    local: DirStore,
+    remote: String,
+    kind: MirrorKind,
+}
+
+impl MirrorStore {
+    pub fn new(remote: &str, local_dir: &str, kind: MirrorKind) -> Self {
+        Self {
+            local: DirStore::new(local_dir),
+            remote: remote.trim_end_matches('/').to_string(),
+            kind,
+        }
+    }
+
+    fn local_path(&self, name: &str) -> String {
+        self.local.dir.join(name).to_string_lossy().to_string()
+    }
+
+    fn pull(&self) -> Result<(), String> {
+        let local = format!("{}/", self.local.dir.to_string_lossy());
+        match self.kind {
+            MirrorKind::Rsync => {
+                run(Command::new("rsync").args(["-a", "--exclude", ".*", &format!("{}/", self.remote), &local]))
+            }
+            MirrorKind::S3 => {
+                run(Command::new("aws").args(["s3", "sync", "--quiet", "--exclude", ".*", &self.remote, &local]))
+            }
+        }
+    }
+
+    fn push(&self, name: &str) -> Result<(), String> {
+        let local = self.local_path(name);
+        let remote = format!("{}/{}", self.remote, name);
+        match self.kind {
+            MirrorKind::Rsync => run(Command::new("rsync").args(["-a", &local, &remote])),
+            MirrorKind::S3 => run(Command::new("aws").args(["s3", "cp", "--quiet", &local, &remote])),
+        }
+    }
+}
+
+impl SyncStore for MirrorStore {
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        self.pull()?;
+        self.local.list()
+    }
+
+    fn get(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        self.local.get(name)
+    }
+
+    fn put(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        self.local.put(name, data)?;
+        self.push(name)
+    }
+}
+
+/// Store on a HTTP server: `GET <url>/` lists the entries (one per line),
+/// `GET <url>/<name>` and `PUT <url>/<name>` read and write them
+pub struct HttpStore {
+    url: String,
+    client: blocking::Client,
+}
+
+impl HttpStore {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            client: blocking::Client::new(),
+        }
+    }
+}
+
+impl SyncStore for HttpStore {
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        let resp = self
+            .client
+            .get(format!("{}/", self.url))
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.text())
+            .map_err(|e| e.to_string())?;
+        Ok(resp
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect())
+    }
+
+    fn get(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        self.client
+            .get(format!("{}/{}", self.url, name))
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.bytes())
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| e.to_string())
+    }
+
+    fn put(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        self.client
+            .put(format!("{}/{}", self.url, name))
+            .body(data.to_vec())
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn run(cmd: &mut Command) -> Result<(), String> {
+    let output = cmd.output().map_err(|e| format!("{:?}: {}", cmd, e))?;
+    if !output.status.success() {
+        return Err(format!("{:?}: {}", cmd, String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+/// Open the store at `location`: a HTTP(S) URL, a `s3://` URL, a rsync
+/// remote (`host:path` or `rsync://`) or a local directory. Remote mirrors
+/// are kept in `work_dir/sync`.
+pub fn open_store(location: &str, work_dir: &str) -> Box<dyn SyncStore> {
+    let mirror_dir = format!("{}/sync", work_dir);
+    if location.starts_with("http://") || location.starts_with("https://") {
+        Box::new(HttpStore::new(location))
+    } else if location.starts_with("s3://") {
+        Box::new(MirrorStore::new(location, &mirror_dir, MirrorKind::S3))
+    } else if location.starts_with("rsync://") || is_rsync_remote(location) {
+        Box::new(MirrorStore::new(location, &mirror_dir, MirrorKind::Rsync))
+    } else {
+        Box::new(DirStore::new(location))
+    }
+}
+
+/// Whether `location` is a `[user@]host:path` rsync remote
+fn is_rsync_remote(location: &str) -> bool {
+    match location.split_once(':') {
+        Some((host, _)) => !host.is_empty() && !host.contains('/'),
+        None => false,
+    }
+}
+
+/// Name identifying this instance in the store, unique across machines
+pub fn default_node_name() -> String {
+    let host = fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or("node".to_string());
+    format!("{}_{}", host, std::process::id())
+}
+
+/// Periodically pushes the testcases dumped in `work_dir/corpus` and the
+/// branch coverage of this instance to the store, and evaluates the
+/// testcases / merges the branch coverage of other instances
+pub struct CorpusSyncStage<OT> {
+    store: Option<Box<dyn SyncStore>>,
+    node: String,
+    interval: Duration,
+    last_sync: Instant,
+    corpus_dir: String,
+    /// Local corpus files already pushed
+    pushed: HashSet<String>,
+    /// Entries of other instances already evaluated
+    pulled: HashSet<String>,
+    initial_state: EVMStagedVMState,
+    pub phantom: std::marker::PhantomData<OT>,
+}
+
+impl<OT> UsesState for CorpusSyncStage<OT> {
+    type State = EVMFuzzState;
+}
+
+impl<OT> CorpusSyncStage<OT> {
+    /// A stage doing nothing when `store` is `None`
+    pub fn new(
+        store: Option<Box<dyn SyncStore>>,
+        node: String,
+        interval: Duration,
+        work_dir: &str,
+        initial_state: EVMStagedVMState,
+    ) -> Self {
+        Self {
+            store,
+            node,
+            interval,
+            last_sync: Instant::now(),
+            corpus_dir: format!("{}/corpus", work_dir),
+            pushed: HashSet::new(),
+            pulled: HashSet::new(),
+            initial_state,
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    fn is_own(&self, name: &str) -> bool {
+        name == format!("{}{}", self.node, BRANCHES_SUFFIX) || name.starts_with(&format!("{}-", self.node))
+    }
+
+    fn push(&mut self, state: &EVMFuzzState) -> Result<(), String> {
+        let store = self.store.as_mut().unwrap();
+        if Path::new(&self.corpus_dir).exists() {
+            let files = fs::read_dir(&self.corpus_dir).map_err(|e| e.to_string())?;
+            for file in files.filter_map(|f| f.ok()) {
+                let file_name = file.file_name().to_string_lossy().to_string();
+                if !file_name.ends_with("_replayable") || self.pushed.contains(&file_name) {
+                    continue;
+                }
+                let data = fs::read(file.path()).map_err(|e| e.to_string())?;
+                store.put(&format!("{}-{}", self.node, file_name), &data)?;
+                self.pushed.insert(file_name);
+            }
+        }
+
+        if let Some(meta) = state.metadata_map().get::<UncoveredBranchesMetadata>() {
+            let branches = serde_json::to_vec(&meta.branch_status()).map_err(|e| e.to_string())?;
+            store.put(&format!("{}{}", self.node, BRANCHES_SUFFIX), &branches)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a testcase in the replayable format, one [`ConciseEVMInput`] per line
+pub fn parse_testcase(data: &[u8]) -> Option<Vec<ConciseEVMInput>> {
+    String::from_utf8_lossy(data)
+        .split('\n')
+        .filter(|txn| txn.len() >= 4)
+        .map(|txn| serde_json::from_str::<ConciseEVMInput>(txn).ok())
+        .collect()
+}
+
+impl<EM, Z, OT> Stage<EVMFuzzExecutor<OT>, EM, Z> for CorpusSyncStage<OT>
+where
+    Z: Evaluator<EVMFuzzExecutor<OT>, EM, State = Self::State>,
+    EM: ProgressReporter + UsesState<State = Self::State>,
+    OT: ObserversTuple<Self::State>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut EVMFuzzExecutor<OT>,
+        state: &mut Self::State,
+        manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        if self.store.is_none() || self.last_sync.elapsed() < self.interval {
+            return Ok(());
+        }
+        self.last_sync = Instant::now();
+
+        if let Err(e) = self.push(state) {
+            warn!("Failed to push corpus to the sync store: {}", e);
+        }
+
+        let names = match self.store.as_mut().unwrap().list() {
+            Ok(names) => names,
+            Err(e) => {
+                warn!("Failed to list the sync store: {}", e);
+                return Ok(());
+            }
+        };
+
+        let mut new_testcases = 0;
+        for name in names {
+            if self.is_own(&name) || self.pulled.contains(&name) {
+                continue;
+            }
+            let data = match self.store.as_mut().unwrap().get(&name) {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Failed to fetch {} from the sync store: {}", name, e);
+                    continue;
+                }
+            };
+
+            // coverage of other instances changes over time, merge it at every sync
+            if name.ends_with(BRANCHES_SUFFIX) {
+                match serde_json::from_slice::<Vec<((EVMAddress, usize), BranchCoveredStatus)>>(&data) {
+                    Ok(branches) => {
+                        if let Some(meta) = state.metadata_map_mut().get_mut::<UncoveredBranchesMetadata>() {
+                            meta.merge_branch_status(&branches);
+                        }
+                    }
+                    Err(e) => warn!("Invalid branch coverage {}: {}", name, e),
+                }
+                continue;
+            }
+
+            self.pulled.insert(name.clone());
+            let testcase = match parse_testcase(&data) {
+                Some(testcase) => testcase,
+                None => {
+                    debug!("Skipping invalid testcase {} from the sync store", name);
+                    continue;
+                }
+            };
+            let mut vm_state = self.initial_state.clone();
+            for txn in testcase {
+                let (inp, call_until) = txn.to_input(vm_state.clone());
+                unsafe {
+                    CALL_UNTIL = call_until;
+                }
+                fuzzer.evaluate_input(state, executor, manager, inp)?;
+                vm_state = state.get_execution_result().new_state.clone();
+            }
+            unsafe {
+                CALL_UNTIL = u32::MAX;
+            }
+            new_testcases += 1;
+        }
+
+        if new_testcases > 0 {
+            info!("Evaluated {} testcases from other fuzzer instances", new_testcases);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_store() {
+        let dir = std::env::temp_dir().join(format!("ityfuzz_sync_test_{}", std::process::id()));
+        let mut store = DirStore::new(dir.to_str().unwrap());
+        store.put("a-1_replayable", b"data").unwrap();
+        assert_eq!(store.list().unwrap(), vec!["a-1_replayable".to_string()]);
+        assert_eq!(store.get("a-1_replayable").unwrap(), b"data".to_vec());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_rsync_remote() {
+        assert!(is_rsync_remote("user@host:/corpus"));
+        assert!(is_rsync_remote("host:corpus"));
+        assert!(!is_rsync_remote("/tmp/corpus"));
+        assert!(!is_rsync_remote("./a:b/corpus"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, fs, ops::Deref, rc::Rc};
+use std::{cell::RefCell, fs, ops::Deref, rc::Rc, sync::atomic::Ordering};
 
 use itertools::Itertools;
 use libafl::{
@@ -16,9 +16,10 @@ use crate::{
         input::EVMInput,
         middlewares::{
             call_printer::CallPrinter,
-            coverage::{Coverage, EVAL_COVERAGE},
+            coverage::{Coverage, COVERAGE_REPORT_REQUESTED, EVAL_COVERAGE},
             middleware::MiddlewareType,
         },
+        summary::CampaignSummary,
         types::{EVMFuzzExecutor, EVMFuzzState, EVMQueueExecutor, EVMStagedVMState},
     },
     generic_vm::vm_executor::GenericVM,
@@ -32,6 +33,8 @@ pub struct CoverageStage<OT> {
     coverage: Rc<RefCell<Coverage>>,
     call_printer: Rc<RefCell<CallPrinter>>,
     trace_dir: String,
+    /// Refreshed along with the coverage
+    summary: CampaignSummary,
     pub phantom: std::marker::PhantomData<OT>,
 }
 
@@ -45,6 +48,7 @@ impl<OT> CoverageStage<OT> {
         coverage: Rc<RefCell<Coverage>>,
         call_printer: Rc<RefCell<CallPrinter>>,
         work_dir: String,
+        summary: CampaignSummary,
     ) -> Self {
         let trace_dir = format!("{}/traces", work_dir);
         if !std::path::Path::new(&trace_dir).exists() {
@@ -56,6 +60,7 @@ impl<OT> CoverageStage<OT> {
             coverage,
             call_printer,
             trace_dir,
+            summary,
             phantom: std::marker::PhantomData,
         }
     }
@@ -117,7 +122,9 @@ where
             return Ok(());
         }
         let last_idx = last_idx.unwrap().into();
-        if self.last_corpus_idx == last_idx {
+        // reports requested with SIGUSR1 are written even without new testcases
+        let requested = COVERAGE_REPORT_REQUESTED.swap(false, Ordering::Relaxed);
+        if self.last_corpus_idx == last_idx && !requested {
             return Ok(());
         }
 
@@ -185,11 +192,12 @@ where
 
         exec.host.remove_middlewares_by_ty(&MiddlewareType::CallPrinter);
 
-        if self.last_corpus_idx == last_idx {
+        if self.last_corpus_idx == last_idx && !requested {
             return Ok(());
         }
 
         self.coverage.deref().borrow_mut().record_instruction_coverage();
+        self.summary.dump(&self.coverage.deref().borrow(), state);
         self.last_corpus_idx = last_idx;
         Ok(())
     }
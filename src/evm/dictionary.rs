@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use libafl::state::{HasMetadata, HasRand};
+use libafl_bolts::{impl_serdeany, prelude::Rand};
+use revm_primitives::Bytecode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    evm::{
+        bytecode_analyzer::find_constants,
+        types::{EVMAddress, EVMU256},
+    },
+    r#const::DICTIONARY_MAX_ENTRIES,
+};
+
+/// Values likely to be meaningful as arguments of the functions of a
+/// contract: the addresses, selectors and words pushed by its bytecode and
+/// the values read from its storage on chain
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct ContractDictionary {
+    /// PUSH20 constants and address-like words
+    pub addresses: Vec<EVMAddress>,
+    /// PUSH4 constants
+    pub selectors: Vec<[u8; 4]>,
+    /// PUSH32 constants and storage values, e.g., thresholds and hashes
+    pub words: Vec<EVMU256>,
+}
+
+/// Whether the word has the shape of an address, i.e., 20 non-zero bytes
+/// left-padded with zeros
+pub(crate) fn is_address_like(word: &EVMU256) -> bool {
+    let bytes = word.to_be_bytes::<32>();
+    bytes[..12].iter().all(|b| *b == 0) && bytes[12..16].iter().any(|b| *b != 0)
+}
+
+fn push_unique<T: PartialEq>(entries: &mut Vec<T>, entry: T) {
+    if entries.len() < DICTIONARY_MAX_ENTRIES && !entries.contains(&entry) {
+        entries.push(entry);
+    }
+}
+
+impl ContractDictionary {
+    /// Add a word, as an address if it has the shape of one
+    pub fn add_word(&mut self, word: EVMU256) {
+        if word <= EVMU256::from(1) || word == EVMU256::MAX {
+            return;
+        }
+        if is_address_like(&word) {
+            push_unique(
+                &mut self.addresses,
+                EVMAddress::from_slice(&word.to_be_bytes::<32>()[12..]),
+            );
+        } else {
+            push_unique(&mut self.words, word);
+        }
+    }
+
+    /// Add the PUSH4, PUSH20 and PUSH32 constants of the bytecode
+    pub fn add_bytecode(&mut self, bytecode: &Bytecode) {
+        for constant in find_constants(bytecode) {
+            match constant.len() {
+                4 => push_unique(&mut self.selectors, constant.try_into().unwrap()),
+                20 => push_unique(&mut self.addresses, EVMAddress::from_slice(&constant)),
+                32 => self.add_word(EVMU256::from_be_slice(&constant)),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.selectors.is_empty() && self.words.is_empty()
+    }
+}
+
+/// Dictionaries of the contracts, along with the contract targeted by the
+/// input being mutated, whose dictionary is used by the mutators
+///
+/// This is metadata attached to the global fuzz state
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct DictionaryMetadata {
+    pub dictionaries: HashMap<EVMAddress, ContractDictionary>,
+    /// Contract of the input being mutated
+    pub target: Option<EVMAddress>,
+}
+
+impl_serdeany!(DictionaryMetadata);
+
+impl DictionaryMetadata {
+    /// Dictionary of the contract targeted by the input being mutated
+    pub fn target_dictionary(&self) -> Option<&ContractDictionary> {
+        self.dictionaries
+            .get(self.target.as_ref()?)
+            .filter(|dictionary| !dictionary.is_empty())
+    }
+}
+
+fn dictionary_mut<S: HasMetadata>(state: &mut S, address: EVMAddress) -> &mut ContractDictionary {
+    if !state.has_metadata::<DictionaryMetadata>() {
+        state.metadata_map_mut().insert(DictionaryMetadata::default());
+    }
+    state
+        .metadata_map_mut()
+        .get_mut::<DictionaryMetadata>()
+        .unwrap()
+        .dictionaries
+        .entry(address)
+        .or_default()
+}
+
+/// Add the constants of the bytecode deployed at `address` to its dictionary
+pub fn add_bytecode_to_dictionary<S: HasMetadata>(state: &mut S, address: EVMAddress, bytecode: &Bytecode) {
+    dictionary_mut(state, address).add_bytecode(bytecode);
+}
+
+/// Add a value read from the storage of `address` to its dictionary
+pub fn add_storage_to_dictionary<S: HasMetadata>(state: &mut S, address: EVMAddress, value: EVMU256) {
+    dictionary_mut(state, address).add_word(value);
+}
+
+/// Set the contract whose dictionary is used when mutating the next input
+pub fn set_dictionary_target<S: HasMetadata>(state: &mut S, target: EVMAddress) {
+    if let Some(meta) = state.metadata_map_mut().get_mut::<DictionaryMetadata>() {
+        meta.target = Some(target);
+    }
+}
+
+/// Sample an address from the dictionary of the targeted contract
+pub fn sample_dictionary_address<S: HasRand + HasMetadata>(state: &mut S) -> Option<EVMAddress> {
+    let len = state
+        .metadata_map()
+        .get::<DictionaryMetadata>()?
+        .target_dictionary()?
+        .addresses
+        .len();
+    if len == 0 {
+        return None;
+    }
+    let idx = state.rand_mut().below(len as u64) as usize;
+    let meta = state.metadata_map().get::<DictionaryMetadata>().unwrap();
+    Some(meta.target_dictionary().unwrap().addresses[idx])
+}
+
+/// Sample a value of `len` bytes from the dictionary of the targeted contract:
+/// a selector for 4 bytes, otherwise any entry fitting in `len` bytes
+pub fn sample_dictionary_value<S: HasRand + HasMetadata>(state: &mut S, len: usize) -> Option<Vec<u8>> {
+    let mut candidates = {
+        let dictionary = state.metadata_map().get::<DictionaryMetadata>()?.target_dictionary()?;
+        if len == 4 && !dictionary.selectors.is_empty() {
+            dictionary
+                .selectors
+                .iter()
+                .map(|selector| selector.to_vec())
+                .collect::<Vec<_>>()
+        } else {
+            dictionary
+                .addresses
+                .iter()
+                .map(|addr| EVMU256::from_be_slice(&addr.0))
+                .chain(dictionary.words.iter().cloned())
+                .filter(|word| word.byte_len() <= len)
+                .map(|word| word.to_be_bytes::<32>()[32 - len..].to_vec())
+                .collect::<Vec<_>>()
+        }
+    };
+    if candidates.is_empty() {
+        return None;
+    }
+    let idx = state.rand_mut().below(candidates.len() as u64) as usize;
+    Some(candidates.swap_remove(idx))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn test_contract_dictionary() {
+        // PUSH4 selector, PUSH20 address, PUSH32 threshold, PUSH32 padded address
+        let code = [
+            vec![0x63, 0xa9, 0x05, 0x9c, 0xbb],
+            [vec![0x73], vec![0xbe; 20]].concat(),
+            [vec![0x7f], vec![0x11; 32]].concat(),
+            [vec![0x7f], vec![0; 12], vec![0xca; 20]].concat(),
+            vec![0x00],
+        ]
+        .concat();
+        let mut dictionary = ContractDictionary::default();
+        dictionary.add_bytecode(&Bytecode::new_raw(Bytes::from(code)));
+        dictionary.add_word(EVMU256::from(1));
+        dictionary.add_word(EVMU256::from(1000));
+
+        assert_eq!(dictionary.selectors, vec![[0xa9, 0x05, 0x9c, 0xbb]]);
+        let mut addresses = dictionary.addresses.clone();
+        addresses.sort();
+        assert_eq!(
+            addresses,
+            vec![EVMAddress::from_slice(&[0xbe; 20]), EVMAddress::from_slice(&[0xca; 20])]
+        );
+        let mut words = dictionary.words.clone();
+        words.sort();
+        assert_eq!(words, vec![EVMU256::from(1000), EVMU256::from_be_slice(&[0x11; 32])]);
+    }
+}
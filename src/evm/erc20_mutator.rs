@@ -0,0 +1,186 @@
+//! Mutation of the amounts passed to ERC-20 style functions, i.e., a uint256
+//! following an address as in `transfer(address to, uint256 amount)` or
+//! `approve(address spender, uint256 amount)`
+//!
+//! Random u256s rarely hit the values token logic branches on, so the amount
+//! is set to one read from the [`EVMState`] instead: the exact balances and
+//! allowances of the caller and of the addresses passed along, the total
+//! supply, the max allowance and the reserves of the pairs of the token.
+
+use std::ops::DerefMut;
+
+use libafl::{mutators::MutationResult, state::HasRand};
+use libafl_bolts::prelude::Rand;
+
+use crate::{
+    evm::{
+        abi::{A256InnerType, AArray, BoxedABI, A256},
+        dictionary::is_address_like,
+        onchain::keccak256,
+        tokens::v2_transformer::reserve_parser,
+        types::{EVMAddress, EVMU256},
+        vm::EVMState,
+    },
+    r#const::ERC20_STORAGE_SLOTS,
+};
+
+/// Storage slots of `token0`, `token1` and the reserves of a Uniswap V2 pair
+const PAIR_TOKEN0_SLOT: u64 = 6;
+const PAIR_TOKEN1_SLOT: u64 = 7;
+const PAIR_RESERVES_SLOT: u64 = 8;
+
+fn address_word(address: &EVMAddress) -> EVMU256 {
+    EVMU256::from_be_slice(&address.0)
+}
+
+/// Slot of `key` in the mapping at `slot`, i.e., `keccak256(key . slot)`
+fn mapping_slot(key: &EVMAddress, slot: EVMU256) -> EVMU256 {
+    keccak256(&[address_word(key).to_be_bytes::<32>(), slot.to_be_bytes::<32>()].concat())
+}
+
+/// Amounts of the token at `token` likely to be meaningful: the max
+/// allowance, the amounts stored in its first slots (e.g., total supply),
+/// the balances and allowances of the holders for any mapping in these slots
+/// and the reserves of the Uniswap V2 pairs of the token
+pub fn interesting_amounts(evm_state: &EVMState, token: &EVMAddress, holders: &[EVMAddress]) -> Vec<EVMU256> {
+    let mut candidates = vec![];
+    if let Some(storage) = evm_state.get(token) {
+        for slot in 0..ERC20_STORAGE_SLOTS {
+            let slot = EVMU256::from(slot);
+            candidates.extend(storage.get(&slot).cloned());
+            for owner in holders {
+                let balance_slot = mapping_slot(owner, slot);
+                candidates.extend(storage.get(&balance_slot).cloned());
+                for spender in holders {
+                    candidates.extend(storage.get(&mapping_slot(spender, balance_slot)).cloned());
+                }
+            }
+        }
+    }
+
+    let token = address_word(token);
+    for storage in evm_state.state.values() {
+        let is_pair = [PAIR_TOKEN0_SLOT, PAIR_TOKEN1_SLOT]
+            .iter()
+            .any(|slot| storage.get(&EVMU256::from(*slot)) == Some(&token));
+        if let (true, Some(reserves)) = (is_pair, storage.get(&EVMU256::from(PAIR_RESERVES_SLOT))) {
+            let (reserve0, reserve1) = reserve_parser(reserves);
+            candidates.extend([reserve0, reserve1]);
+        }
+    }
+
+    let mut res = candidates
+        .into_iter()
+        .filter(|value| *value > EVMU256::from(1) && !is_address_like(value))
+        .collect::<Vec<_>>();
+    res.push(EVMU256::MAX);
+    res.sort();
+    res.dedup();
+    res
+}
+
+/// Set one of the amount args of `args`, a call to the token at `token` by
+/// `caller`, to an interesting amount. Skipped if no arg has the shape of an
+/// amount.
+pub fn mutate_amount<S: HasRand>(
+    state: &mut S,
+    args: &mut BoxedABI,
+    evm_state: &EVMState,
+    token: EVMAddress,
+    caller: EVMAddress,
+) -> MutationResult {
+    let Some(args) = args.b.deref_mut().as_any().downcast_mut::<AArray>() else {
+        return MutationResult::Skipped;
+    };
+
+    let mut holders = vec![caller];
+    let mut amount_args = vec![];
+    let mut follows_address = false;
+    for (idx, arg) in args.data.iter_mut().enumerate() {
+        let Some(a256) = arg.b.deref_mut().as_any().downcast_mut::<A256>() else {
+            follows_address = false;
+            continue;
+        };
+        if a256.is_address {
+            if a256.data.len() == 20 {
+                holders.push(EVMAddress::from_slice(&a256.data));
+            }
+            follows_address = true;
+            continue;
+        }
+        if follows_address &&
+            !a256.dont_mutate &&
+            a256.data.len() == 32 &&
+            matches!(a256.inner_type, A256InnerType::Uint)
+        {
+            amount_args.push(idx);
+        }
+        follows_address = false;
+    }
+    if amount_args.is_empty() {
+        return MutationResult::Skipped;
+    }
+
+    let amounts = interesting_amounts(evm_state, &token, &holders);
+    let idx = amount_args[state.rand_mut().below(amount_args.len() as u64) as usize];
+    let amount = amounts[state.rand_mut().below(amounts.len() as u64) as usize];
+    let a256 = args.data[idx].b.deref_mut().as_any().downcast_mut::<A256>().unwrap();
+    a256.data = amount.to_be_bytes::<32>().to_vec();
+    MutationResult::Mutated
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::evm::{abi::get_abi_type_boxed, types::EVMFuzzState};
+
+    #[test]
+    fn test_mutate_amount() {
+        let token = EVMAddress::from_slice(&[0x10; 20]);
+        let pair = EVMAddress::from_slice(&[0x20; 20]);
+        let caller = EVMAddress::from_slice(&[0x30; 20]);
+        let balance = EVMU256::from(1234);
+        let total_supply = EVMU256::from(10000);
+
+        let mut evm_state = EVMState::default();
+        evm_state.insert(
+            token,
+            HashMap::from([
+                (EVMU256::from(2), total_supply),
+                (mapping_slot(&caller, EVMU256::from(0)), balance),
+            ]),
+        );
+        let reserves = EVMU256::from(500) << 112 | EVMU256::from(700);
+        evm_state.insert(
+            pair,
+            HashMap::from([
+                (EVMU256::from(PAIR_TOKEN0_SLOT), address_word(&token)),
+                (EVMU256::from(PAIR_RESERVES_SLOT), reserves),
+            ]),
+        );
+        assert_eq!(
+            interesting_amounts(&evm_state, &token, &[caller]),
+            vec![
+                EVMU256::from(500),
+                EVMU256::from(700),
+                balance,
+                total_supply,
+                EVMU256::MAX
+            ]
+        );
+
+        let mut state = EVMFuzzState::new(0);
+        let mut args = get_abi_type_boxed(&String::from("(address,uint256)"));
+        assert_eq!(
+            mutate_amount(&mut state, &mut args, &evm_state, token, caller),
+            MutationResult::Mutated
+        );
+        let mut args = get_abi_type_boxed(&String::from("(uint256,uint256)"));
+        assert_eq!(
+            mutate_amount(&mut state, &mut args, &evm_state, token, caller),
+            MutationResult::Skipped
+        );
+    }
+}
@@ -18,7 +18,11 @@ use libafl_bolts::Named;
 
 use super::{input::EVMInput, types::EVMFuzzState};
 use crate::{
-    evm::{input::ConciseEVMInput, middlewares::sha3_bypass::Sha3TaintAnalysis, vm::EVMExecutor},
+    evm::{
+        input::ConciseEVMInput,
+        middlewares::{middleware::Middleware, sha3_bypass::Sha3TaintAnalysis},
+        vm::EVMExecutor,
+    },
     generic_vm::vm_state::VMStateT,
     input::VMInputT,
 };
@@ -142,3 +146,147 @@ where
         todo!()
     }
 }
+
+/// A feedback map contributed by a plugin, e.g., a bitmap of the protocol
+/// specific states reached. It is updated by a middleware during execution and
+/// cleared by it in `before_execute`.
+pub trait CustomFeedbackMap {
+    fn name(&self) -> &str;
+    /// Map of the last execution
+    fn map(&self) -> &[u8];
+}
+
+/// A custom feedback map and the middleware updating it, registered through
+/// `Config::custom_feedbacks`. Both can be the same object.
+pub struct CustomFeedback<SC>
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    pub map: Rc<RefCell<dyn CustomFeedbackMap>>,
+    pub middleware: Option<Rc<RefCell<dyn Middleware<SC>>>>,
+}
+
+/// A wrapper around a feedback that also keeps the inputs reaching a new
+/// maximum in any entry of the custom feedback maps, alongside the ones
+/// deemed interesting by the inner feedback (branch coverage).
+pub struct CustomMapsFeedback<F>
+where
+    F: Feedback<EVMFuzzState>,
+{
+    pub inner_feedback: Box<F>,
+    pub maps: Vec<Rc<RefCell<dyn CustomFeedbackMap>>>,
+    /// Maximum value of each entry of each map seen so far
+    pub history: Vec<Vec<u8>>,
+}
+
+impl<F> CustomMapsFeedback<F>
+where
+    F: Feedback<EVMFuzzState>,
+{
+    pub fn new(inner_feedback: F, maps: Vec<Rc<RefCell<dyn CustomFeedbackMap>>>) -> Self {
+        Self {
+            inner_feedback: Box::new(inner_feedback),
+            history: vec![vec![]; maps.len()],
+            maps,
+        }
+    }
+}
+
+/// Update `history` with the maximums of `map`, returns whether any entry
+/// reached a new maximum
+fn update_max_map(history: &mut Vec<u8>, map: &[u8]) -> bool {
+    if history.len() < map.len() {
+        history.resize(map.len(), 0);
+    }
+    let mut is_new = false;
+    for (seen, value) in history.iter_mut().zip(map.iter()) {
+        if value > seen {
+            *seen = *value;
+            is_new = true;
+        }
+    }
+    is_new
+}
+
+impl<F> Feedback<EVMFuzzState> for CustomMapsFeedback<F>
+where
+    F: Feedback<EVMFuzzState>,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut EVMFuzzState,
+        manager: &mut EM,
+        input: &EVMInput,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = EVMFuzzState>,
+        OT: ObserversTuple<EVMFuzzState>,
+    {
+        let mut interesting = self
+            .inner_feedback
+            .is_interesting(state, manager, input, observers, exit_kind)?;
+        // all maps are checked so that their history is kept up to date
+        for (map, history) in self.maps.iter().zip(self.history.iter_mut()) {
+            interesting |= update_max_map(history, map.deref().borrow().map());
+        }
+        Ok(interesting)
+    }
+
+    #[inline]
+    fn append_metadata<OT>(
+        &mut self,
+        state: &mut EVMFuzzState,
+        observers: &OT,
+        testcase: &mut Testcase<EVMInput>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<EVMFuzzState>,
+    {
+        self.inner_feedback.as_mut().append_metadata(state, observers, testcase)
+    }
+}
+
+impl<F> Named for CustomMapsFeedback<F>
+where
+    F: Feedback<EVMFuzzState>,
+{
+    fn name(&self) -> &str {
+        "CustomMapsFeedback"
+    }
+}
+
+impl<F> Debug for CustomMapsFeedback<F>
+where
+    F: Feedback<EVMFuzzState>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("CustomMapsFeedback")
+            .field(
+                "maps",
+                &self
+                    .maps
+                    .iter()
+                    .map(|map| map.deref().borrow().name().to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_max_map() {
+        let mut history = vec![];
+        assert!(update_max_map(&mut history, &[0, 1, 0]));
+        assert_eq!(history, vec![0, 1, 0]);
+        assert!(!update_max_map(&mut history, &[0, 1, 0]));
+        assert!(!update_max_map(&mut history, &[]));
+        assert!(update_max_map(&mut history, &[0, 0, 0, 2]));
+        assert_eq!(history, vec![0, 1, 0, 2]);
+    }
+}
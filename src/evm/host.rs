@@ -19,7 +19,6 @@ use itertools::Itertools;
 use libafl::prelude::{HasMetadata, Scheduler};
 use revm::precompile::{Precompile, Precompiles};
 use revm_interpreter::{
-    analysis::to_analysed,
     return_ok,
     BytecodeLocked,
     CallContext,
@@ -72,6 +71,7 @@ use super::{
 use crate::{
     evm::{
         abi::{get_abi_type_boxed, register_abi_instance},
+        code_analysis::analyze,
         contract_utils::extract_sig_from_contract,
         corpus_initializer::ABIMap,
         input::{EVMInput, EVMInputTy},
@@ -79,10 +79,18 @@ use crate::{
         mutator::AccessPattern,
         onchain::{
             abi_decompiler::fetch_abi_heimdall,
+            endpoints::ChainProfile,
             flashloan::{register_borrow_txn, Flashloan},
         },
         types::{as_u64, generate_random_address, is_zero, EVMAddress, EVMU256},
-        vm::{is_reverted_or_control_leak, EVMState, SinglePostExecution, IN_DEPLOY, IS_FAST_CALL_STATIC},
+        vm::{
+            is_frame_reverted,
+            is_reverted_or_control_leak,
+            EVMState,
+            SinglePostExecution,
+            IN_DEPLOY,
+            IS_FAST_CALL_STATIC,
+        },
     },
     generic_vm::vm_executor::MAP_SIZE,
     handle_contract_insertion,
@@ -112,6 +120,9 @@ pub static mut RET_SIZE: usize = 0;
 pub static mut RET_OFFSET: usize = 0;
 
 pub static mut PANIC_ON_BUG: bool = false;
+/// Shall we record the events emitted during the executions, for the oracles
+/// inspecting them
+pub static mut RECORD_LOGS: bool = false;
 // for debugging purpose, return ControlLeak when the calls amount exceeds this
 // value
 pub static mut CALL_UNTIL: u32 = u32::MAX;
@@ -159,6 +170,14 @@ pub fn is_precompile(address: EVMAddress, num_of_precompiles: usize) -> bool {
     num.wrapping_sub(1) < num_of_precompiles as u16
 }
 
+/// Event emitted by a contract
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EmittedLog {
+    pub address: EVMAddress,
+    pub topics: Vec<B256>,
+    pub data: Bytes,
+}
+
 #[allow(clippy::type_complexity)]
 pub struct FuzzHost<SC>
 where
@@ -208,6 +227,11 @@ where
     pub current_arbitrary_calls: Vec<(EVMAddress, EVMAddress, usize)>,
     // integer_overflow
     pub current_integer_overflow: HashSet<(EVMAddress, usize, &'static str)>,
+    // events emitted, only recorded with RECORD_LOGS
+    pub current_logs: Vec<EmittedLog>,
+    /// Values read or compared against in the current execution, added to the
+    /// interesting values pool once it is done
+    pub interesting_values: Vec<EVMU256>,
     // relations file handle
     relations_file: std::fs::File,
     // Filter duplicate relations
@@ -220,6 +244,8 @@ where
     pub spec_id: SpecId,
     /// Precompiles
     pub precompiles: Precompiles,
+    /// Extra precompiles and semantics of the chain forked
+    pub chain_profile: ChainProfile,
 
     /// All SSTORE PCs that are for mapping (i.e., writing to multiple storage
     /// slots)
@@ -231,6 +257,9 @@ where
 
     pub jumpi_trace: usize,
 
+    /// Estimated gas used by the current transaction, as gas is not measured
+    pub gas_estimate: u64,
+
     /// Depth of call stack
     pub call_depth: u64,
     /// Prank information
@@ -243,8 +272,17 @@ where
     pub expected_calls: ExpectedCallTracker,
     /// Assert failed message for the cheatcode
     pub assert_msg: Option<String>,
+    /// Addresses of the next contracts created during deployment, used instead
+    /// of random ones when replaying recorded deployments
+    pub create_addresses: VecDeque<EVMAddress>,
+    /// Snapshots of `evmstate`, indexed by their [`SnapshotId`]
+    snapshots: Vec<EVMState>,
 }
 
+/// Id of a snapshot of the state of a [`FuzzHost`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SnapshotId(usize);
+
 impl<SC> Debug for FuzzHost<SC>
 where
     SC: Scheduler<State = EVMFuzzState> + Clone,
@@ -298,6 +336,8 @@ where
             current_self_destructs: self.current_self_destructs.clone(),
             current_arbitrary_calls: self.current_arbitrary_calls.clone(),
             current_integer_overflow: self.current_integer_overflow.clone(),
+            current_logs: self.current_logs.clone(),
+            interesting_values: self.interesting_values.clone(),
             relations_file: self.relations_file.try_clone().unwrap(),
             relations_hash: self.relations_hash.clone(),
             current_typed_bug: self.current_typed_bug.clone(),
@@ -305,16 +345,20 @@ where
             work_dir: self.work_dir.clone(),
             spec_id: self.spec_id,
             precompiles: Precompiles::default(),
+            chain_profile: self.chain_profile.clone(),
             leak_ctx: self.leak_ctx.clone(),
             mapping_sstore_pcs: self.mapping_sstore_pcs.clone(),
             mapping_sstore_pcs_to_slot: self.mapping_sstore_pcs_to_slot.clone(),
             jumpi_trace: self.jumpi_trace,
+            gas_estimate: self.gas_estimate,
             call_depth: self.call_depth,
             prank: self.prank.clone(),
             expected_emits: self.expected_emits.clone(),
             expected_revert: self.expected_revert.clone(),
             expected_calls: self.expected_calls.clone(),
             assert_msg: self.assert_msg.clone(),
+            create_addresses: self.create_addresses.clone(),
+            snapshots: self.snapshots.clone(),
         }
     }
 }
@@ -360,6 +404,8 @@ where
             current_self_destructs: Default::default(),
             current_arbitrary_calls: Default::default(),
             current_integer_overflow: Default::default(),
+            current_logs: Default::default(),
+            interesting_values: vec![],
             relations_file: std::fs::File::create(format!("{}/relations.log", workdir)).unwrap(),
             relations_hash: HashSet::new(),
             current_typed_bug: Default::default(),
@@ -367,16 +413,20 @@ where
             work_dir: workdir,
             spec_id: SpecId::LATEST,
             precompiles: Default::default(),
+            chain_profile: Default::default(),
             leak_ctx: vec![],
             mapping_sstore_pcs: Default::default(),
             mapping_sstore_pcs_to_slot: Default::default(),
             jumpi_trace: 37,
+            gas_estimate: 0,
             call_depth: 0,
             prank: None,
             expected_revert: None,
             expected_emits: VecDeque::new(),
             expected_calls: ExpectedCallTracker::new(),
             assert_msg: None,
+            create_addresses: VecDeque::new(),
+            snapshots: vec![],
         }
     }
 
@@ -384,6 +434,10 @@ where
         self.spec_id = SpecId::from(spec_id.as_str());
     }
 
+    pub fn set_chain_profile(&mut self, chain_profile: ChainProfile) {
+        self.chain_profile = chain_profile;
+    }
+
     /// custom spec id run_inspect
     pub fn run_inspect(&mut self, interp: &mut Interpreter, state: &mut EVMFuzzState) -> InstructionResult {
         match self.spec_id {
@@ -410,6 +464,29 @@ where
         }
     }
 
+    /// Snapshot the state, cheap as the storage is shared with the snapshot
+    /// until written
+    pub fn snapshot(&mut self) -> SnapshotId {
+        self.snapshots.push(self.evmstate.clone());
+        SnapshotId(self.snapshots.len() - 1)
+    }
+
+    /// Restore the state of the snapshot, discarding the snapshots taken after
+    /// it. Returns false if the snapshot was discarded
+    pub fn revert_to(&mut self, id: SnapshotId) -> bool {
+        if id.0 >= self.snapshots.len() {
+            return false;
+        }
+        self.snapshots.truncate(id.0 + 1);
+        self.evmstate = self.snapshots[id.0].clone();
+        true
+    }
+
+    /// Discard the snapshot and the ones taken after it
+    pub fn discard_snapshot(&mut self, id: SnapshotId) {
+        self.snapshots.truncate(id.0);
+    }
+
     pub fn remove_all_middlewares(&mut self) {
         self.middlewares_enabled = false;
         self.middlewares = RwLock::new(Default::default());
@@ -505,8 +582,7 @@ where
         unsafe {
             invoke_middlewares!(self, None, state, on_insert, &mut code, address);
         }
-        self.code
-            .insert(address, Arc::new(BytecodeLocked::try_from(to_analysed(code)).unwrap()));
+        self.code.insert(address, analyze(code));
     }
 
     pub fn find_static_call_read_slot(
@@ -788,6 +864,22 @@ where
         }
     }
 
+    fn call_mocked_precompile(&self, input: &CallInputs) -> (InstructionResult, Gas, Bytes) {
+        let precompile = &self.chain_profile.precompiles[&input.contract];
+        let balance = |address| self.evmstate.get_balance(&address).cloned().unwrap_or_default();
+        match precompile.call(&input.input, &self.env, balance) {
+            Some(output) => (InstructionResult::Return, Gas::new(0), Bytes::from(output)),
+            None => {
+                debug!(
+                    "function of {} not mocked: {}",
+                    precompile.name,
+                    hex::encode(&input.input)
+                );
+                (Revert, Gas::new(0), Bytes::new())
+            }
+        }
+    }
+
     /// Apply the prank
     pub fn apply_prank(&mut self, contract_caller: &EVMAddress, input: &mut CallInputs) {
         if let Some(prank) = &self.prank {
@@ -1007,6 +1099,33 @@ macro_rules! invoke_middlewares {
     };
 }
 
+/// Rough gas cost of an opcode, ignoring memory expansion, refunds and
+/// warm / cold accesses, used to model the block gas limit since gas is not
+/// measured during fuzzing
+pub fn estimate_opcode_gas(opcode: u8) -> u64 {
+    match opcode {
+        // STOP, RETURN, REVERT, INVALID
+        0x00 | 0xf3 | 0xfd | 0xfe => 0,
+        // SHA3
+        0x20 => 36,
+        // BALANCE, EXTCODESIZE, EXTCODECOPY, EXTCODEHASH
+        0x31 | 0x3b | 0x3c | 0x3f => 2600,
+        // SLOAD
+        0x54 => 2100,
+        // SSTORE
+        0x55 => 5000,
+        // LOG0 - LOG4
+        0xa0..=0xa4 => 375 * (opcode as u64 - 0x9f),
+        // CREATE, CREATE2
+        0xf0 | 0xf5 => 32000,
+        // CALL, CALLCODE, DELEGATECALL, STATICCALL
+        0xf1 | 0xf2 | 0xf4 | 0xfa => 2600,
+        // SELFDESTRUCT
+        0xff => 5000,
+        _ => 3,
+    }
+}
+
 impl<SC> Host<EVMFuzzState> for FuzzHost<SC>
 where
     SC: Scheduler<State = EVMFuzzState> + Clone,
@@ -1025,6 +1144,7 @@ where
                     interp.stack.data()[interp.stack.len() - 1 - $idx]
                 };
             }
+            self.gas_estimate += estimate_opcode_gas(*interp.instruction_pointer);
             match *interp.instruction_pointer {
                 // 0xfd => {
                 //     println!("fd {} @ {:?}", interp.program_counter(), interp.contract.address);
@@ -1097,8 +1217,19 @@ where
                 #[cfg(feature = "dataflow")]
                 0x54 => {
                     // SLOAD
-                    let mut key = fast_peek!(0);
-                    READ_MAP[process_rw_key!(key)] = true;
+                    let slot = fast_peek!(0);
+                    let mut key = slot;
+                    let idx = process_rw_key!(key);
+                    // harvest the value on first read of the slot in this execution
+                    if !READ_MAP[idx] &&
+                        let Some(value) = self
+                            .evmstate
+                            .get(&interp.contract.address)
+                            .and_then(|storage| storage.get(&slot))
+                    {
+                        self.interesting_values.push(*value);
+                    }
+                    READ_MAP[idx] = true;
                 }
 
                 // todo(shou): support signed checking
@@ -1119,6 +1250,7 @@ where
                     let idx = interp.program_counter() % MAP_SIZE;
                     if abs_diff < CMP_MAP[idx] {
                         CMP_MAP[idx] = abs_diff;
+                        self.interesting_values.extend([v1, v2]);
                     }
                 }
 
@@ -1139,6 +1271,7 @@ where
                     let idx = interp.program_counter() % MAP_SIZE;
                     if abs_diff < CMP_MAP[idx] {
                         CMP_MAP[idx] = abs_diff;
+                        self.interesting_values.extend([v1, v2]);
                     }
                 }
 
@@ -1155,6 +1288,7 @@ where
                     let idx = interp.program_counter() % MAP_SIZE;
                     if abs_diff < CMP_MAP[idx] {
                         CMP_MAP[idx] = abs_diff;
+                        self.interesting_values.extend([v1, v2]);
                     }
                 }
 
@@ -1287,6 +1421,14 @@ where
     }
 
     fn log(&mut self, _address: EVMAddress, _topics: Vec<B256>, _data: Bytes) {
+        if unsafe { RECORD_LOGS } {
+            self.current_logs.push(EmittedLog {
+                address: _address,
+                topics: _topics.clone(),
+                data: _data.clone(),
+            });
+        }
+
         // flag check
         if _topics.len() == 1 {
             let current_flag = _topics.last().unwrap().0;
@@ -1341,7 +1483,10 @@ where
     ) -> (InstructionResult, Option<EVMAddress>, Gas, Bytes) {
         if unsafe { IN_DEPLOY } {
             // todo: use nonce + hash instead
-            let r_addr = generate_random_address(state);
+            let r_addr = self
+                .create_addresses
+                .pop_front()
+                .unwrap_or_else(|| generate_random_address(state));
 
             // update local balance
             // for create we don't check sender balance
@@ -1357,9 +1502,9 @@ where
             }
 
             let mut interp = Interpreter::new_with_memory_limit(
-                Contract::new_with_context(
+                Contract::new_with_context_analyzed(
                     Bytes::new(),
-                    Bytecode::new_raw(inputs.init_code.clone()),
+                    analyze(Bytecode::new_raw(inputs.init_code.clone())),
                     &CallContext {
                         address: r_addr,
                         caller: inputs.caller,
@@ -1372,8 +1517,12 @@ where
                 false,
                 MEM_LIMIT,
             );
+            let logs = self.current_logs.len();
             let ret = self.run_inspect(&mut interp, state);
             debug!("create: {:?} -> {:?} = {:?}", inputs.caller, r_addr, ret);
+            if is_frame_reverted(&ret) {
+                self.current_logs.truncate(logs);
+            }
             if !is_reverted_or_control_leak(&ret) {
                 let runtime_code: Bytes = interp.return_value();
                 self.set_code(r_addr, Bytecode::new_raw(runtime_code.clone()), state);
@@ -1432,6 +1581,7 @@ where
                             env: Default::default(),
                             access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
                             liquidation_percent: 0,
+                            liquidation_path: 0,
                             input_type: EVMInputTy::ABI,
                             direct_data: Default::default(),
                             randomness: vec![0],
@@ -1484,14 +1634,20 @@ where
             };
         }
 
+        let logs = self.current_logs.len();
         let mut res = if is_precompile(input.contract, self.precompiles.len()) {
             self.call_precompile(input, state)
+        } else if self.chain_profile.precompiles.contains_key(&input.contract) {
+            self.call_mocked_precompile(input)
         } else if unsafe { IS_FAST_CALL_STATIC || IS_FAST_CALL } {
             self.call_forbid_control_leak(input, state)
         } else {
             self.call_allow_control_leak(input, interp, output_info, state)
         };
 
+        if is_frame_reverted(&res.0) {
+            self.current_logs.truncate(logs);
+        }
         let ret_buffer = res.2.clone();
 
         self.call_depth -= 1;
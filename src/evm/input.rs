@@ -12,15 +12,19 @@ use revm_primitives::Env;
 use serde::{Deserialize, Deserializer, Serialize};
 
 use super::{
-    onchain::flashloan::CAN_LIQUIDATE,
+    onchain::flashloan::{clamp_to_max_capital, CAN_LIQUIDATE},
     utils::{colored_address, colored_sender, prettify_value},
 };
 use crate::{
     evm::{
-        abi::{AEmpty, AUnknown, BoxedABI},
+        abi::{ABILossyType, AEmpty, AUnknown, BoxedABI},
+        dictionary::set_dictionary_target,
+        erc20_mutator::mutate_amount,
+        mempool::get_victim_tx,
         mutator::AccessPattern,
+        senders::clamp_call_value,
         types::{checksum, EVMAddress, EVMStagedVMState, EVMU256, EVMU512},
-        vm::EVMState,
+        vm::{BlockInfo, EVMState},
     },
     generic_vm::{
         vm_executor::ExecutionResult,
@@ -28,10 +32,62 @@ use crate::{
     },
     input::{ConciseSerde, SolutionTx, VMInputT},
     mutation_utils::byte_mutator,
+    r#const::{
+        BASEFEE_MAX_CHANGE_PERMILLE,
+        BLOCK_TIME_SECS,
+        ERC20_AMOUNT_CHOICE,
+        INTERESTING_TIME_DELTAS,
+        MAX_TIME_DELTA,
+        SAMPLE_MAX,
+    },
     state::{HasCaller, HasItyState},
     state_input::StagedVMState,
 };
 
+/// Maximum increase of the timestamp mutated in a transaction (Echidna
+/// `maxTimeDelay`), unbounded if `None`
+pub static mut MAX_TIME_DELAY: Option<EVMU256> = None;
+/// Maximum increase of the block number mutated in a transaction (Echidna
+/// `maxBlockDelay`), unbounded if `None`
+pub static mut MAX_BLOCK_DELAY: Option<EVMU256> = None;
+
+/// A duration time-locked logic is likely to check, give or take a second
+pub fn interesting_time_delta<S>(state: &mut S) -> u64
+where
+    S: HasRand,
+{
+    let duration = INTERESTING_TIME_DELTAS[state.rand_mut().below(INTERESTING_TIME_DELTAS.len() as u64) as usize];
+    (duration + state.rand_mut().below(3)).saturating_sub(1)
+}
+
+/// Block `secs` seconds after `base` and the matching number of blocks later,
+/// both capped by [`MAX_TIME_DELAY`] and [`MAX_BLOCK_DELAY`], with a base fee
+/// change bounded as in EIP-1559 if it is a later block
+pub fn later_block<S>(state: &mut S, base: BlockInfo, secs: u64) -> BlockInfo
+where
+    S: HasRand,
+{
+    let mut time_delta = EVMU256::from(secs);
+    let mut block_delta = EVMU256::from(secs.div_ceil(BLOCK_TIME_SECS));
+    if let Some(max) = unsafe { MAX_TIME_DELAY } {
+        time_delta = time_delta.min(max);
+    }
+    if let Some(max) = unsafe { MAX_BLOCK_DELAY } {
+        block_delta = block_delta.min(max);
+    }
+    let basefee = if block_delta == EVMU256::ZERO {
+        base.basefee
+    } else {
+        let permille = 1000 - BASEFEE_MAX_CHANGE_PERMILLE + state.rand_mut().below(2 * BASEFEE_MAX_CHANGE_PERMILLE + 1);
+        base.basefee * EVMU256::from(permille) / EVMU256::from(1000)
+    };
+    BlockInfo {
+        timestamp: base.timestamp.saturating_add(time_delta),
+        number: base.number.saturating_add(block_delta),
+        basefee,
+    }
+}
+
 /// EVM Input Types
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
 pub enum EVMInputTy {
@@ -44,6 +100,11 @@ pub enum EVMInputTy {
     ArbitraryCallBoundedAddr,
     /// [Depreciated] A liquidation transaction
     Liquidate,
+    /// A redeployment of the contract with fuzzed constructor args
+    Deploy,
+    /// The pending transaction of a user, replayed as signed between the
+    /// transactions front-running and back-running it
+    Victim,
 }
 
 const CALL_VALUE_MAX_BYTES: usize = 21; // 309M ether
@@ -92,8 +153,19 @@ pub trait EVMInputT {
     /// liquidate
     fn set_liquidation_percent(&mut self, v: u8);
 
+    /// Get the index of the swap path to liquidate through
+    fn get_liquidation_path(&self) -> u8;
+
+    /// Set the index of the swap path to liquidate through
+    fn set_liquidation_path(&mut self, v: u8);
+
     fn get_repeat(&self) -> usize;
 
+    /// Replace the ABI encoded input with `calldata`, decoded with the ABI of
+    /// the input. Returns false, leaving the input unchanged, if it does not
+    /// decode
+    fn set_calldata(&mut self, calldata: &[u8]) -> bool;
+
     fn get_swap_data(&self) -> HashMap<String, SwapInfo>;
 }
 
@@ -136,6 +208,10 @@ pub struct EVMInput {
     /// Percentage of the token amount in all callers' account to liquidate
     pub liquidation_percent: u8,
 
+    /// Which of the swap paths of a token to liquidate through
+    #[serde(default)]
+    pub liquidation_path: u8,
+
     /// If ABI is empty, use direct data, which is the raw input data
     pub direct_data: Bytes,
 
@@ -180,6 +256,10 @@ pub struct ConciseEVMInput {
     /// Percentage of the token amount in all callers' account to liquidate
     pub liquidation_percent: u8,
 
+    /// Which of the swap paths of a token to liquidate through
+    #[serde(default)]
+    pub liquidation_path: u8,
+
     /// Additional random bytes for mutator
     pub randomness: Vec<u8>,
 
@@ -198,6 +278,10 @@ pub struct ConciseEVMInput {
     /// Swap data
     #[serde(skip_deserializing)]
     pub swap_data: HashMap<String, SwapInfo>,
+
+    /// Estimated gas used by the transaction
+    #[serde(default)]
+    pub gas_used: u64,
 }
 
 /// EVM Input Minimum for Deserializing with human readable ABI
@@ -233,6 +317,10 @@ pub struct ConciseEVMInputReadable {
     /// Percentage of the token amount in all callers' account to liquidate
     pub liquidation_percent: u8,
 
+    /// Which of the swap paths of a token to liquidate through
+    #[serde(default)]
+    pub liquidation_path: u8,
+
     /// Additional random bytes for mutator
     pub randomness: Vec<u8>,
 
@@ -280,6 +368,7 @@ impl ConciseEVMInput {
             step: input.is_step(),
             env: input.get_vm_env().clone(),
             liquidation_percent: input.get_liquidation_percent(),
+            liquidation_path: input.get_liquidation_path(),
             randomness: input.get_randomness(),
             repeat: input.get_repeat(),
             layer: input.get_state().get_post_execution_len(),
@@ -289,6 +378,7 @@ impl ConciseEVMInput {
             },
             return_data,
             swap_data,
+            gas_used: execution_result.new_state.state.gas_used,
         }
     }
 
@@ -311,12 +401,14 @@ impl ConciseEVMInput {
             step: input.is_step(),
             env: input.get_vm_env().clone(),
             liquidation_percent: input.get_liquidation_percent(),
+            liquidation_path: input.get_liquidation_path(),
             randomness: input.get_randomness(),
             repeat: input.get_repeat(),
             layer: input.get_state().get_post_execution_len(),
             call_leak,
             return_data: None,
             swap_data: input.get_swap_data(),
+            gas_used: 0,
         }
     }
 
@@ -337,8 +429,12 @@ impl ConciseEVMInput {
                 env: self.env.clone(),
                 access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
                 liquidation_percent: self.liquidation_percent,
+                liquidation_path: self.liquidation_path,
                 #[cfg(not(feature = "debug"))]
-                direct_data: Bytes::new(),
+                direct_data: match self.input_type {
+                    EVMInputTy::Victim => get_victim_tx().map(|tx| tx.data.clone().into()).unwrap_or_default(),
+                    _ => Bytes::new(),
+                },
                 #[cfg(feature = "debug")]
                 direct_data: Bytes::from(hex::decode(&self.direct_data).unwrap_or_default()),
                 randomness: self.randomness.clone(),
@@ -364,6 +460,7 @@ impl ConciseEVMInput {
             step: self.step,
             env: self.env.clone(),
             liquidation_percent: self.liquidation_percent,
+            liquidation_path: self.liquidation_path,
             randomness: self.randomness.clone(),
             repeat: self.repeat,
             layer: self.layer,
@@ -380,6 +477,7 @@ impl ConciseEVMInput {
             Some(ref d) => self.as_abi_call(d.to_colored_string()),
             None => match self.input_type {
                 EVMInputTy::ABI | EVMInputTy::ArbitraryCallBoundedAddr => self.as_transfer(),
+                EVMInputTy::Victim => self.as_victim(),
                 EVMInputTy::Borrow => {
                     if self.swap_data.contains_key("deposit") {
                         self.as_deposit()
@@ -387,7 +485,7 @@ impl ConciseEVMInput {
                         self.as_borrow()
                     }
                 }
-                EVMInputTy::Liquidate => None,
+                EVMInputTy::Liquidate | EVMInputTy::Deploy => None,
             },
         }
 
@@ -453,6 +551,19 @@ impl ConciseEVMInput {
         ))
     }
 
+    #[allow(dead_code)]
+    #[inline]
+    fn as_victim(&self) -> Option<String> {
+        let data = get_victim_tx().map(|tx| hex::encode(&tx.data)).unwrap_or_default();
+        Some(format!(
+            "{}.{}{}(0x{}); // victim tx",
+            colored_address(&self.contract()),
+            self.colored_fn_name("call"),
+            self.colored_value(),
+            data
+        ))
+    }
+
     #[allow(dead_code)]
     #[inline]
     fn as_borrow(&self) -> Option<String> {
@@ -489,12 +600,17 @@ impl ConciseEVMInput {
             return call;
         }
         let liq_call = if self.swap_data.contains_key("withdraw") {
-            format!("WETH.{}(100% Balance);", self.colored_fn_name("withdraw"))
+            format!(
+                "WETH.{}({}% Balance);",
+                self.colored_fn_name("withdraw"),
+                self.liquidation_percent as u32 * 10
+            )
         } else if self.swap_data.contains_key("sell") {
             format!(
-                "{}.{}(100% Balance, 0, path:(* → WETH), address(this), block.timestamp);",
+                "{}.{}({}% Balance, 0, path:(* → WETH), address(this), block.timestamp);",
                 colored_address("Router"),
                 self.colored_fn_name("swapExactTokensForETH"),
+                self.liquidation_percent as u32 * 10
             )
         } else {
             return call;
@@ -583,6 +699,10 @@ impl SolutionTx for ConciseEVMInput {
         prettify_value(self.txn_value.unwrap_or_default())
     }
 
+    fn raw_value(&self) -> String {
+        format!("0x{:x}", self.txn_value.unwrap_or_default())
+    }
+
     fn is_borrow(&self) -> bool {
         self.input_type == EVMInputTy::Borrow
     }
@@ -595,6 +715,18 @@ impl SolutionTx for ConciseEVMInput {
         self.swap_data.clone()
     }
 
+    fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    fn block_number(&self) -> String {
+        self.env.block.number.to_string()
+    }
+
+    fn timestamp(&self) -> String {
+        self.env.block.timestamp.to_string()
+    }
+
     #[cfg(not(feature = "debug"))]
     fn calldata(&self) -> String {
         match self.data {
@@ -686,10 +818,34 @@ impl EVMInputT for EVMInput {
         self.liquidation_percent = v;
     }
 
+    fn get_liquidation_path(&self) -> u8 {
+        self.liquidation_path
+    }
+
+    fn set_liquidation_path(&mut self, v: u8) {
+        self.liquidation_path = v;
+    }
+
     fn get_repeat(&self) -> usize {
         self.repeat
     }
 
+    fn set_calldata(&mut self, calldata: &[u8]) -> bool {
+        let Some(mut abi) = self.data.clone() else {
+            return false;
+        };
+        // an empty ABI only decodes the selector
+        if calldata.len() < 4 || (matches!(abi.get_type(), ABILossyType::TEmpty) && calldata.len() > 4) {
+            return false;
+        }
+        abi.set_func(calldata[..4].try_into().unwrap());
+        if !abi.set_bytes(calldata.to_vec()) || abi.get_bytes() != calldata {
+            return false;
+        }
+        self.data = Some(abi);
+        true
+    }
+
     fn get_swap_data(&self) -> HashMap<String, SwapInfo> {
         self.swap_data.clone()
     }
@@ -698,6 +854,9 @@ impl EVMInputT for EVMInput {
 ///
 macro_rules! impl_env_mutator_u256 {
     ($item: ident, $loc: ident, $increasing_only: expr) => {
+        impl_env_mutator_u256!($item, $loc, $increasing_only, None);
+    };
+    ($item: ident, $loc: ident, $increasing_only: expr, $max_delay: expr) => {
         pub fn $item<S>(input: &mut EVMInput, state_: &mut S) -> MutationResult
         where
             S: State + HasCaller<EVMAddress> + HasRand + HasMetadata,
@@ -714,12 +873,19 @@ macro_rules! impl_env_mutator_u256 {
             if res == MutationResult::Skipped {
                 return res;
             }
-            let result_val = EVMU256::try_from_be_slice(&input_vec.as_slice()).unwrap();
+            let mut result_val = EVMU256::try_from_be_slice(&input_vec.as_slice()).unwrap();
             if $increasing_only {
                 if result_val < input.get_vm_env().$loc.$item {
                     return MutationResult::Skipped;
                 }
             }
+            let max_delay: Option<EVMU256> = $max_delay;
+            if let Some(max_delay) = max_delay {
+                let current = input.get_vm_env().$loc.$item;
+                if result_val > current.saturating_add(max_delay) {
+                    result_val = current + (result_val - current) % (max_delay + EVMU256::from(1));
+                }
+            }
 
             input.get_vm_env_mut().$loc.$item = result_val;
             res
@@ -796,10 +962,10 @@ impl<'a> HasBytesVec for MutatorInput<'a> {
 
 impl EVMInput {
     impl_env_mutator_u256!(basefee, block, false);
-    impl_env_mutator_u256!(timestamp, block, true);
+    impl_env_mutator_u256!(timestamp, block, true, unsafe { MAX_TIME_DELAY });
     impl_env_mutator_h160!(coinbase, block);
     impl_env_mutator_u256!(gas_limit, block, false);
-    impl_env_mutator_u256!(number, block, true);
+    impl_env_mutator_u256!(number, block, true, unsafe { MAX_BLOCK_DELAY });
     // impl_env_mutator_u256!(chain_id, cfg, false);
 
     pub fn prevrandao<S>(_input: &mut EVMInput, _state_: &mut S) -> MutationResult
@@ -811,6 +977,39 @@ impl EVMInput {
         MutationResult::Skipped
     }
 
+    /// Advance the block environment from the one of the VM state of the
+    /// input by a realistic delta: a duration time-locked logic is likely to
+    /// check (give or take a second) or a random one, the matching number of
+    /// blocks and a base fee change bounded as in EIP-1559
+    pub fn block_delta<S>(input: &mut EVMInput, state_: &mut S) -> MutationResult
+    where
+        S: State + HasCaller<EVMAddress> + HasRand + HasMetadata,
+    {
+        let block = &input.get_vm_env().block;
+        let base = input.sstate.state.last_block.unwrap_or(BlockInfo {
+            timestamp: block.timestamp,
+            number: block.number,
+            basefee: block.basefee,
+        });
+        let secs = if state_.rand_mut().below(2) == 0 {
+            interesting_time_delta(state_)
+        } else {
+            state_.rand_mut().below(MAX_TIME_DELTA + 1)
+        };
+        let later = later_block(state_, base, secs);
+
+        let block = &mut input.get_vm_env_mut().block;
+        let prev = (block.timestamp, block.number, block.basefee);
+        block.timestamp = later.timestamp;
+        block.number = later.number;
+        block.basefee = later.basefee;
+        if prev == (block.timestamp, block.number, block.basefee) {
+            MutationResult::Skipped
+        } else {
+            MutationResult::Mutated
+        }
+    }
+
     pub fn gas_price<S>(_input: &mut EVMInput, _state_: &mut S) -> MutationResult
     where
         S: State + HasCaller<EVMAddress> + HasRand + HasMetadata,
@@ -859,7 +1058,11 @@ impl EVMInput {
         (0..CALL_VALUE_MAX_BYTES).for_each(|i| {
             input_vec[i] = 0;
         });
-        input.set_txn_value(EVMU256::try_from_be_slice(input_vec.as_slice()).unwrap());
+        let function = input.data.as_ref().map(|abi| abi.get_func_name()).unwrap_or_default();
+        input.set_txn_value(clamp_call_value(
+            &function,
+            clamp_to_max_capital(EVMU256::try_from_be_slice(input_vec.as_slice()).unwrap()),
+        ));
         res
     }
 
@@ -893,6 +1096,7 @@ impl EVMInput {
         add_mutator!(coinbase);
         add_mutator!(gas_limit);
         add_mutator!(number);
+        add_mutator!(block_delta, ap.timestamp || ap.number || ap.basefee);
         // add_mutator!(chain_id);
         add_mutator!(prevrandao);
 
@@ -990,9 +1194,17 @@ impl VMInputT<EVMState, EVMAddress, EVMAddress, ConciseEVMInput> for EVMInput {
             + HasCaller<EVMAddress>
             + HasMetadata,
     {
+        set_dictionary_target(state, self.get_contract());
         if state.rand_mut().next() % 100 > 87 || self.data.is_none() {
             return self.mutate_env_with_access_pattern(state);
         }
+        if let Some(ref mut data) = self.data {
+            if state.rand_mut().below(SAMPLE_MAX) < ERC20_AMOUNT_CHOICE &&
+                mutate_amount(state, data, &self.sstate.state, self.contract, self.caller) == MutationResult::Mutated
+            {
+                return MutationResult::Mutated;
+            }
+        }
         let vm_slots = self.get_state().get(&self.get_contract()).cloned();
         match self.data {
             Some(ref mut data) => data.mutate_with_vm_slots(state, vm_slots),
@@ -1126,3 +1338,91 @@ impl Input for EVMInput {
         // todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evm::{abi::get_abi_type_boxed, types::EVMFuzzState},
+        state_input::StagedVMState,
+    };
+
+    fn abi_input(abi: &str) -> EVMInput {
+        let mut data = get_abi_type_boxed(abi);
+        data.set_func([0xaa; 4]);
+        EVMInput {
+            caller: EVMAddress::zero(),
+            contract: EVMAddress::zero(),
+            data: Some(data),
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            input_type: EVMInputTy::ABI,
+            direct_data: Bytes::new(),
+            randomness: vec![0],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_set_calldata() {
+        let mut input = abi_input("(uint256)");
+        let calldata = [vec![0xbb; 4], vec![0; 31], vec![7]].concat();
+        assert!(input.set_calldata(&calldata));
+        assert_eq!(input.to_bytes(), calldata);
+
+        // calldata not decoding with the ABI is dropped
+        assert!(!input.set_calldata(&[calldata.clone(), vec![1]].concat()));
+        assert!(!input.set_calldata(&[0xbb; 3]));
+        assert_eq!(input.to_bytes(), calldata);
+
+        let mut input = abi_input("()");
+        assert!(!input.set_calldata(&[0xbb; 5]));
+        assert!(input.set_calldata(&[0xbb; 4]));
+        assert_eq!(input.to_bytes(), vec![0xbb; 4]);
+    }
+
+    #[test]
+    fn test_block_delta() {
+        let mut state: EVMFuzzState = crate::state::FuzzState::new(0);
+        let mut input = abi_input("()");
+        let base = BlockInfo {
+            timestamp: EVMU256::from(1_000_000),
+            number: EVMU256::from(100),
+            basefee: EVMU256::from(1000),
+        };
+        input.sstate.state.last_block = Some(base);
+        for _ in 0..100 {
+            // the block is advanced from the one of the VM state, not from the
+            // previous mutation
+            if EVMInput::block_delta(&mut input, &mut state) == MutationResult::Skipped {
+                continue;
+            }
+            let block = &input.get_vm_env().block;
+            let secs = (block.timestamp - base.timestamp).as_limbs()[0];
+            assert!(secs <= MAX_TIME_DELTA);
+            assert_eq!(
+                block.number - base.number,
+                EVMU256::from(secs.div_ceil(BLOCK_TIME_SECS))
+            );
+            let permille = (block.basefee * EVMU256::from(1000) / base.basefee).as_limbs()[0];
+            if secs == 0 {
+                assert_eq!(block.basefee, base.basefee);
+            } else {
+                assert!(permille >= 1000 - BASEFEE_MAX_CHANGE_PERMILLE);
+                assert!(permille <= 1000 + BASEFEE_MAX_CHANGE_PERMILLE);
+            }
+        }
+
+        let deltas = (0..100).map(|_| interesting_time_delta(&mut state)).collect::<Vec<_>>();
+        assert!(deltas.iter().all(|delta| INTERESTING_TIME_DELTAS
+            .iter()
+            .any(|d| delta + 1 >= *d && *delta <= d + 1)));
+    }
+}
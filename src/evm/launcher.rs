@@ -0,0 +1,323 @@
+//! Multi-core fuzzing: one fuzzer instance is spawned per core, each pinned
+//! to its core and with its own work dir, exchanging testcases and branch
+//! coverage through a corpus sync store (see [`crate::evm::corpus_sync`]).
+//!
+//! The launcher supervises the instances as processes rather than through
+//! libafl's `Launcher` and LLMP, as the EVM host (code, middlewares, flashloan
+//! and onchain caches) is not part of the serialized fuzz state a restarting
+//! manager could hand over. It reports the stats aggregated over the
+//! instances, collects their solutions into the work dir, restarts (resuming
+//! the last save) the instances that crash, and stops the others once one
+//! reaches the stop condition of the campaign.
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    process::{exit, Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+use libafl_bolts::core_affinity::{CoreId, Cores};
+use tracing::{error, info, warn};
+
+use crate::{
+    evm::corpus_sync,
+    r#const::{CAMPAIGN_FILE, LAUNCHER_MAX_RESTARTS, LAUNCHER_REPORT_INTERVAL_SECS},
+};
+
+/// Options set by the launcher for each instance, stripped from the command
+/// line of the launcher
+const INSTANCE_OPTIONS: [&str; 5] = ["--cores", "--work-dir", "-w", "--corpus-sync-node", "--base-directory"];
+
+/// Arguments of the instance on `core`, from the arguments of the launcher
+pub fn instance_args(args: &[String], core: usize, work_dir: &str, sync_dir: Option<&str>) -> Vec<String> {
+    let mut res = vec![];
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if INSTANCE_OPTIONS.contains(&arg.as_str()) {
+            // skip the value
+            iter.next();
+            continue;
+        }
+        if INSTANCE_OPTIONS
+            .iter()
+            .any(|option| arg.starts_with(&format!("{}=", option)))
+        {
+            continue;
+        }
+        res.push(arg.clone());
+    }
+    res.extend([
+        "--bind-core".to_string(),
+        core.to_string(),
+        "--work-dir".to_string(),
+        work_dir.to_string(),
+        "--corpus-sync-node".to_string(),
+        format!("{}_core{}", corpus_sync::default_node_name(), core),
+    ]);
+    if let Some(sync_dir) = sync_dir {
+        res.extend(["--corpus-sync".to_string(), sync_dir.to_string()]);
+    }
+    res
+}
+
+/// Pin the current process to the core
+pub fn bind_core(core: usize) {
+    if let Err(e) = CoreId(core).set_affinity() {
+        warn!("Failed to bind to core {}: {}", core, e);
+    }
+}
+
+struct Instance {
+    core: usize,
+    work_dir: String,
+    args: Vec<String>,
+    child: Child,
+    restarts: usize,
+}
+
+impl Instance {
+    fn spawn(exe: &Path, core: usize, work_dir: String, args: Vec<String>) -> Self {
+        let child = Command::new(exe)
+            .args(&args)
+            .spawn()
+            .expect("failed to spawn fuzzer instance");
+        Self {
+            core,
+            work_dir,
+            args,
+            child,
+            restarts: 0,
+        }
+    }
+
+    /// Spawn the instance again, resuming its last save if any
+    fn restart(&mut self, exe: &Path) {
+        let mut args = self.args.clone();
+        if Path::new(&self.work_dir).join(CAMPAIGN_FILE).exists() && !args.iter().any(|arg| arg == "--resume") {
+            args.push("--resume".to_string());
+        }
+        self.child = Command::new(exe)
+            .args(&args)
+            .spawn()
+            .expect("failed to spawn fuzzer instance");
+        self.restarts += 1;
+    }
+}
+
+/// Number of files in `dir` satisfying `filter`
+fn count_files(dir: &Path, filter: impl Fn(&str) -> bool) -> usize {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name().to_str().is_some_and(&filter))
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+/// Copy the solutions found by the instances into `work_dir`: the files of
+/// their `vulnerabilities` dir prefixed with the core, and the lines of their
+/// `vuln_info.jsonl` not collected yet
+fn collect_solutions(instances: &[Instance], work_dir: &str) {
+    let vulns_dir = Path::new(work_dir).join("vulnerabilities");
+    let mut known = fs::read_to_string(Path::new(work_dir).join("vuln_info.jsonl"))
+        .unwrap_or_default()
+        .lines()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let mut new_lines = vec![];
+    for instance in instances {
+        let instance_dir = Path::new(&instance.work_dir);
+        if let Ok(entries) = fs::read_dir(instance_dir.join("vulnerabilities")) {
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let dest = vulns_dir.join(format!("core{}_{}", instance.core, entry.file_name().to_string_lossy()));
+                if !dest.exists() && fs::create_dir_all(&vulns_dir).is_ok() {
+                    if let Err(e) = fs::copy(entry.path(), &dest) {
+                        warn!("[launcher] failed to collect {:?}: {}", entry.path(), e);
+                    }
+                }
+            }
+        }
+        for line in fs::read_to_string(instance_dir.join("vuln_info.jsonl"))
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.is_empty())
+        {
+            if !known.iter().any(|known| known == line) {
+                known.push(line.to_string());
+                new_lines.push(line.to_string());
+            }
+        }
+    }
+    if new_lines.is_empty() {
+        return;
+    }
+    let written = fs::create_dir_all(work_dir).and_then(|_| {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Path::new(work_dir).join("vuln_info.jsonl"))?;
+        f.write_all(format!("{}\n", new_lines.join("\n")).as_bytes())
+    });
+    if let Err(e) = written {
+        warn!("[launcher] failed to collect the solutions: {}", e);
+    }
+}
+
+fn report(instances: &[Instance], work_dir: &str, sync_dir: &str) {
+    collect_solutions(instances, work_dir);
+    let testcases = count_files(Path::new(sync_dir), |name| {
+        !name.starts_with('.') && !name.ends_with(".branches")
+    });
+    let solutions = count_files(&Path::new(work_dir).join("vulnerabilities"), |name| {
+        !name.ends_with("_replayable")
+    });
+    info!(
+        "[launcher] instances: {}, shared testcases: {}, solutions: {}",
+        instances.len(),
+        testcases,
+        solutions
+    );
+}
+
+/// Spawn a fuzzer instance with the arguments of the current process on each
+/// of the `cores` (e.g., `0-3,8`) and supervise them
+///
+/// The instances exchange testcases through `work_dir/sync`, unless a corpus
+/// sync store is already given, e.g., to also exchange with other machines.
+/// A crashed instance is restarted up to [`LAUNCHER_MAX_RESTARTS`] times.
+pub fn launch(cores: &str, work_dir: &str, has_corpus_sync: bool) -> ! {
+    let cores = Cores::from_cmdline(cores).unwrap_or_else(|e| {
+        error!("Invalid cores {:?}: {}", cores, e);
+        exit(1);
+    });
+    let exe = env::current_exe().expect("failed to get the fuzzer executable");
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let sync_dir = format!("{}/sync", work_dir);
+
+    let mut instances = cores
+        .ids
+        .iter()
+        .map(|core| {
+            let instance_dir = format!("{}/core_{}", work_dir, core.0);
+            let sync = (!has_corpus_sync).then_some(sync_dir.as_str());
+            let args = instance_args(&args, core.0, &instance_dir, sync);
+            Instance::spawn(&exe, core.0, instance_dir, args)
+        })
+        .collect::<Vec<_>>();
+    info!("[launcher] spawned {} fuzzer instances", instances.len());
+
+    let mut last_report = Instant::now();
+    loop {
+        thread::sleep(Duration::from_millis(500));
+        let mut stopped = None;
+        for instance in instances.iter_mut() {
+            let Ok(Some(status)) = instance.child.try_wait() else {
+                continue;
+            };
+            // stopped by itself rather than crashed or killed by a signal
+            if status.success() {
+                stopped = Some((instance.core, status));
+                break;
+            }
+            if instance.restarts >= LAUNCHER_MAX_RESTARTS {
+                error!(
+                    "[launcher] instance on core {} crashed ({}) {} times, stopping",
+                    instance.core,
+                    status,
+                    instance.restarts + 1
+                );
+                stopped = Some((instance.core, status));
+                break;
+            }
+            warn!(
+                "[launcher] instance on core {} crashed ({}), restarting it",
+                instance.core, status
+            );
+            instance.restart(&exe);
+        }
+        if let Some((core, status)) = stopped {
+            info!(
+                "[launcher] instance on core {} stopped ({}), stopping the others",
+                core, status
+            );
+            for instance in instances.iter_mut() {
+                let _ = instance.child.kill();
+                let _ = instance.child.wait();
+            }
+            report(&instances, work_dir, &sync_dir);
+            exit(status.code().unwrap_or(1));
+        }
+        if last_report.elapsed() >= Duration::from_secs(LAUNCHER_REPORT_INTERVAL_SECS) {
+            report(&instances, work_dir, &sync_dir);
+            last_report = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_args() {
+        let args = [
+            "evm",
+            "-t",
+            "a.sol",
+            "--cores",
+            "0-3",
+            "-w",
+            "out",
+            "--base-directory=/tmp",
+        ]
+        .map(String::from)
+        .to_vec();
+        let res = instance_args(&args, 2, "out/core_2", Some("out/sync"));
+        assert_eq!(&res[..3], &["evm", "-t", "a.sol"]);
+        assert_eq!(&res[3..7], &["--bind-core", "2", "--work-dir", "out/core_2"]);
+        assert!(res[8].ends_with("_core2"));
+        assert_eq!(&res[9..], &["--corpus-sync", "out/sync"]);
+    }
+
+    #[test]
+    fn test_collect_solutions() {
+        let work_dir = std::env::temp_dir().join(format!("ityfuzz_launcher_{}", std::process::id()));
+        let instances = [0, 1]
+            .map(|core| {
+                let instance_dir = work_dir.join(format!("core_{}", core));
+                fs::create_dir_all(instance_dir.join("vulnerabilities")).unwrap();
+                fs::write(instance_dir.join("vulnerabilities").join("7_replayable"), "tx").unwrap();
+                fs::write(
+                    instance_dir.join("vuln_info.jsonl"),
+                    format!("{{\"bug_idx\":{}}}\n", core),
+                )
+                .unwrap();
+                Instance {
+                    core,
+                    work_dir: instance_dir.to_str().unwrap().to_string(),
+                    args: vec![],
+                    child: Command::new("true").spawn().unwrap(),
+                    restarts: 0,
+                }
+            })
+            .to_vec();
+        let work_dir = work_dir.to_str().unwrap();
+
+        // collecting twice does not duplicate the solutions
+        collect_solutions(&instances, work_dir);
+        collect_solutions(&instances, work_dir);
+        let vulns_dir = Path::new(work_dir).join("vulnerabilities");
+        assert!(vulns_dir.join("core0_7_replayable").exists());
+        assert!(vulns_dir.join("core1_7_replayable").exists());
+        assert_eq!(
+            fs::read_to_string(Path::new(work_dir).join("vuln_info.jsonl")).unwrap(),
+            "{\"bug_idx\":0}\n{\"bug_idx\":1}\n"
+        );
+        fs::remove_dir_all(work_dir).unwrap();
+    }
+}
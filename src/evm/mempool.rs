@@ -0,0 +1,175 @@
+//! Pending transaction of a user (e.g., seen in the mempool) the fuzzer
+//! builds a bundle around: the transactions it searches for run before
+//! (front-run) and after (back-run) the victim transaction, which is replayed
+//! as signed on the forked state.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::OnceLock};
+
+use ethers::types::Transaction;
+use revm_primitives::Env;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    input::{EVMInput, EVMInputTy},
+    mutator::AccessPattern,
+    types::{EVMAddress, EVMU256, EVMU512},
+};
+use crate::state_input::StagedVMState;
+
+/// Victim transaction of the campaign, so that the concise inputs (which do
+/// not keep the calldata) replay it
+static VICTIM_TX: OnceLock<VictimTx> = OnceLock::new();
+
+/// Pending transaction of the victim
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VictimTx {
+    pub from: EVMAddress,
+    pub to: EVMAddress,
+    pub value: EVMU256,
+    pub data: Vec<u8>,
+    /// Legacy gas price or max fee per gas
+    pub gas_price: EVMU256,
+    /// Max priority fee per gas of the EIP-1559 transactions
+    pub max_priority_fee_per_gas: Option<EVMU256>,
+    pub gas_limit: u64,
+}
+
+/// Outcome of the victim transaction in a sequence, recorded in its VM state
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VictimOutcome {
+    pub reverted: bool,
+    pub output: Vec<u8>,
+    /// Flashloan earnings and debt of the attacker before the victim
+    /// transaction, i.e., after the front-run
+    pub earned_before: EVMU512,
+    pub owed_before: EVMU512,
+}
+
+fn u256(value: ethers::types::U256) -> EVMU256 {
+    EVMU256::from_limbs(value.0)
+}
+
+/// Decode the signed transaction, RLP encoded as hex (as given to
+/// `eth_sendRawTransaction`), the sender being recovered from the signature
+pub fn parse_victim_tx(raw: &str) -> Result<VictimTx, String> {
+    let bytes = hex::decode(raw.trim().trim_start_matches("0x")).map_err(|e| format!("invalid hex: {}", e))?;
+    let tx: Transaction = rlp::decode(&bytes).map_err(|e| format!("invalid signed transaction: {}", e))?;
+    let to = tx
+        .to
+        .ok_or_else(|| "contract creations cannot be sandwiched".to_string())?;
+    Ok(VictimTx {
+        from: EVMAddress::from_slice(tx.from.as_bytes()),
+        to: EVMAddress::from_slice(to.as_bytes()),
+        value: u256(tx.value),
+        data: tx.input.to_vec(),
+        gas_price: u256(tx.max_fee_per_gas.or(tx.gas_price).unwrap_or_default()),
+        max_priority_fee_per_gas: tx.max_priority_fee_per_gas.map(u256),
+        gas_limit: tx.gas.as_u64(),
+    })
+}
+
+pub fn init_victim_tx(victim_tx: VictimTx) {
+    let _ = VICTIM_TX.set(victim_tx);
+}
+
+pub fn get_victim_tx() -> Option<&'static VictimTx> {
+    VICTIM_TX.get()
+}
+
+impl VictimTx {
+    /// Gas price paid in a block of base fee `basefee`, the EIP-1559
+    /// transactions paying the base fee and their tip up to their max fee
+    pub fn effective_gas_price(&self, basefee: EVMU256) -> EVMU256 {
+        match self.max_priority_fee_per_gas {
+            Some(tip) => self.gas_price.min(basefee.saturating_add(tip)),
+            None => self.gas_price,
+        }
+    }
+
+    /// Input replaying the transaction in the block environment `env`
+    pub fn to_input(&self, env: &Env) -> EVMInput {
+        let mut env = env.clone();
+        env.tx.caller = self.from;
+        env.tx.gas_price = self.effective_gas_price(env.block.basefee);
+        env.tx.gas_priority_fee = None;
+        env.tx.gas_limit = self.gas_limit;
+        EVMInput {
+            caller: self.from,
+            contract: self.to,
+            data: None,
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: Some(self.value),
+            step: false,
+            env,
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            input_type: EVMInputTy::Victim,
+            direct_data: self.data.clone().into(),
+            randomness: vec![0],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    /// Sender of the transactions below, signed with the key `0x4646..46`
+    const SENDER: &str = "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F";
+
+    #[test]
+    fn test_parse_legacy_tx() {
+        // the EIP-155 example
+        let tx = parse_victim_tx(
+            "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+        )
+        .unwrap();
+        assert_eq!(tx.from, EVMAddress::from_str(SENDER).unwrap());
+        assert_eq!(tx.to, EVMAddress::from_slice(&[0x35; 20]));
+        assert_eq!(tx.value, EVMU256::from(1_000_000_000_000_000_000_u128));
+        assert!(tx.data.is_empty());
+        assert_eq!(tx.gas_limit, 21000);
+        assert_eq!(tx.max_priority_fee_per_gas, None);
+        // 20 gwei whatever the base fee
+        assert_eq!(
+            tx.effective_gas_price(EVMU256::from(1_000_000_000)),
+            EVMU256::from(20_000_000_000_u64)
+        );
+    }
+
+    #[test]
+    fn test_parse_eip1559_tx() {
+        // deposit() of 0.5 ETH, tip of 2 gwei and max fee of 100 gwei
+        let tx = parse_victim_tx(
+            "0x02f8770103847735940085174876e80082ea609435353535353535353535353535353535353535358806f05b59d3b2000084d0e30db0c080a0132d9fe1840f7ccef8ce1ce181a4383d295197e11582d4b9623d10a73b4bd0c1a061e601a51562a03164b66ba66689ea921e7ac6665c861f58f39d9dd595c3a4fa",
+        )
+        .unwrap();
+        assert_eq!(tx.from, EVMAddress::from_str(SENDER).unwrap());
+        assert_eq!(tx.to, EVMAddress::from_slice(&[0x35; 20]));
+        assert_eq!(tx.value, EVMU256::from(500_000_000_000_000_000_u128));
+        assert_eq!(tx.data, vec![0xd0, 0xe3, 0x0d, 0xb0]);
+        assert_eq!(tx.gas_limit, 60000);
+        assert_eq!(tx.gas_price, EVMU256::from(100_000_000_000_u64));
+        assert_eq!(tx.max_priority_fee_per_gas, Some(EVMU256::from(2_000_000_000)));
+
+        // the base fee plus the tip, capped by the max fee
+        let gwei = |gwei: u64| EVMU256::from(gwei * 1_000_000_000);
+        assert_eq!(tx.effective_gas_price(gwei(30)), gwei(32));
+        assert_eq!(tx.effective_gas_price(gwei(99)), gwei(100));
+        let mut env = Env::default();
+        env.block.basefee = gwei(30);
+        assert_eq!(tx.to_input(&env).env.tx.gas_price, gwei(32));
+    }
+
+    #[test]
+    fn test_parse_invalid_tx() {
+        assert!(parse_victim_tx("0xzz").is_err());
+        assert!(parse_victim_tx("0x01").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Breakpoints for triage, given with `--break-at`: when the execution
+//! reaches a program counter of a contract (`0x…:1234`) or a function is
+//! called (`0xa9059cbb`, or `0x…:0xa9059cbb` for the function of a contract),
+//! the stack, a window of the memory and the last storage accesses are
+//! dumped. The execution pauses until Enter is pressed when stdin is a
+//! terminal.
+//!
+//! Only enabled by `ityfuzz replay`, as the breakpoints would be hit on every
+//! execution reaching them when fuzzing.
+
+use std::{
+    any,
+    collections::VecDeque,
+    io::{stdin, IsTerminal},
+    str::FromStr,
+};
+
+use libafl::schedulers::Scheduler;
+use revm_interpreter::Interpreter;
+
+use crate::{
+    evm::{
+        host::FuzzHost,
+        middlewares::middleware::{Middleware, MiddlewareType},
+        types::{EVMAddress, EVMFuzzState, EVMU256},
+        vm::IS_FAST_CALL,
+    },
+    r#const::{BREAKPOINT_MEMORY_WINDOW, BREAKPOINT_STORAGE_ACCESSES},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Program counter in the code of the contract
+    Pc { address: EVMAddress, pc: usize },
+    /// Call of the function, of any contract if None
+    Selector {
+        address: Option<EVMAddress>,
+        selector: [u8; 4],
+    },
+}
+
+fn parse_selector(s: &str) -> Option<[u8; 4]> {
+    let selector = s.strip_prefix("0x")?;
+    hex::decode(selector).ok()?.try_into().ok()
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((address, location)) = s.split_once(':') else {
+            return parse_selector(s)
+                .map(|selector| Breakpoint::Selector {
+                    address: None,
+                    selector,
+                })
+                .ok_or(format!(
+                    "invalid breakpoint {}, expected <address>:<pc> or a selector",
+                    s
+                ));
+        };
+        let address = EVMAddress::from_str(address.trim_start_matches("0x"))
+            .map_err(|e| format!("invalid breakpoint address {}: {}", address, e))?;
+        if let Some(selector) = parse_selector(location) {
+            return Ok(Breakpoint::Selector {
+                address: Some(address),
+                selector,
+            });
+        }
+        let pc = location
+            .parse::<usize>()
+            .map_err(|_| format!("invalid breakpoint {}, expected a decimal pc or a selector", s))?;
+        Ok(Breakpoint::Pc { address, pc })
+    }
+}
+
+/// Parse the breakpoints separated by comma
+pub fn parse_breakpoints(s: &str) -> Result<Vec<Breakpoint>, String> {
+    s.split(',')
+        .map(|breakpoint| breakpoint.trim())
+        .filter(|breakpoint| !breakpoint.is_empty())
+        .map(|breakpoint| breakpoint.parse())
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct StorageAccess {
+    address: EVMAddress,
+    slot: EVMU256,
+    /// Value read or written
+    value: EVMU256,
+    write: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    /// Last storage accesses, oldest first
+    accesses: VecDeque<StorageAccess>,
+}
+
+impl Debugger {
+    pub fn new(breakpoints: Vec<Breakpoint>) -> Self {
+        Self {
+            breakpoints,
+            accesses: VecDeque::new(),
+        }
+    }
+
+    fn hit(&self, interp: &Interpreter) -> Option<&Breakpoint> {
+        let contract = &interp.contract;
+        let pc = interp.program_counter();
+        self.breakpoints.iter().find(|breakpoint| match breakpoint {
+            Breakpoint::Pc { address, pc: at } => *address == contract.code_address && *at == pc,
+            Breakpoint::Selector { address, selector } => {
+                pc == 0 &&
+                    contract.input.get(..4) == Some(&selector[..]) &&
+                    address.map_or(true, |address| {
+                        address == contract.address || address == contract.code_address
+                    })
+            }
+        })
+    }
+
+    fn record_access(&mut self, access: StorageAccess) {
+        if self.accesses.len() == BREAKPOINT_STORAGE_ACCESSES {
+            self.accesses.pop_front();
+        }
+        self.accesses.push_back(access);
+    }
+
+    fn dump(&self, breakpoint: &Breakpoint, interp: &Interpreter) -> String {
+        let contract = &interp.contract;
+        let mut lines = vec![
+            format!("================ Breakpoint {:?} ================", breakpoint),
+            format!(
+                "contract: {:?} (code {:?}), pc: {}, opcode: {:#04x}",
+                contract.address,
+                contract.code_address,
+                interp.program_counter(),
+                unsafe { *interp.instruction_pointer }
+            ),
+            format!("caller: {:?}, value: {}", contract.caller, contract.value),
+            format!("calldata: 0x{}", hex::encode(&contract.input)),
+            format!("stack ({} items, top first):", interp.stack.len()),
+        ];
+        for idx in 0..interp.stack.len() {
+            lines.push(format!("  {:>4}: {:#x}", idx, interp.stack.peek(idx).unwrap()));
+        }
+        let memory = &interp.memory.data;
+        let window = memory.len().min(BREAKPOINT_MEMORY_WINDOW);
+        lines.push(format!("memory ({} bytes, first {} shown):", memory.len(), window));
+        for (idx, word) in memory[..window].chunks(32).enumerate() {
+            lines.push(format!("  {:#06x}: {}", idx * 32, hex::encode(word)));
+        }
+        lines.push(format!("storage accesses (last {}):", self.accesses.len()));
+        lines.extend(self.accesses.iter().map(|access| {
+            format!(
+                "  {} {:?}[{:#x}] = {:#x}",
+                if access.write { "SSTORE" } else { "SLOAD " },
+                access.address,
+                access.slot,
+                access.value
+            )
+        }));
+        lines.join("\n")
+    }
+}
+
+impl<SC> Middleware<SC> for Debugger
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        // calls of the oracles
+        if IS_FAST_CALL {
+            return;
+        }
+        if let Some(breakpoint) = self.hit(interp) {
+            println!("{}", self.dump(breakpoint, interp));
+            if stdin().is_terminal() {
+                println!("Press Enter to continue");
+                let _ = stdin().lines().next();
+            }
+        }
+
+        let address = interp.contract.address;
+        match *interp.instruction_pointer {
+            // SLOAD
+            0x54 => {
+                let slot = interp.stack.peek(0).unwrap();
+                let value = host.evmstate.sload(address, slot).unwrap_or_default();
+                self.record_access(StorageAccess {
+                    address,
+                    slot,
+                    value,
+                    write: false,
+                });
+            }
+            // SSTORE
+            0x55 => {
+                let (slot, value) = (interp.stack.peek(0).unwrap(), interp.stack.peek(1).unwrap());
+                self.record_access(StorageAccess {
+                    address,
+                    slot,
+                    value,
+                    write: true,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::Debugger
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_breakpoints() {
+        let address = EVMAddress::from_slice(&[0x11; 20]);
+        let hex_address = "0x1111111111111111111111111111111111111111";
+        let breakpoints =
+            parse_breakpoints(&format!("{}:1234, 0xa9059cbb,{}:0x2e1a7d4d", hex_address, hex_address)).unwrap();
+        assert_eq!(
+            breakpoints,
+            vec![
+                Breakpoint::Pc { address, pc: 1234 },
+                Breakpoint::Selector {
+                    address: None,
+                    selector: [0xa9, 0x05, 0x9c, 0xbb]
+                },
+                Breakpoint::Selector {
+                    address: Some(address),
+                    selector: [0x2e, 0x1a, 0x7d, 0x4d]
+                },
+            ]
+        );
+        assert!(parse_breakpoints(&format!("{}:pc", hex_address)).is_err());
+        assert!(parse_breakpoints("0xa9059c").is_err());
+        assert!(parse_breakpoints("0x11:1234").is_err());
+    }
+}
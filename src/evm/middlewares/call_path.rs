@@ -0,0 +1,71 @@
+use std::{any, fmt::Debug};
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use revm_interpreter::Interpreter;
+use serde::Serialize;
+
+use crate::evm::{
+    host::FuzzHost,
+    middlewares::middleware::{Middleware, MiddlewareType},
+    types::{as_u64, convert_u256_to_h160, EVMFuzzState},
+    vm::EVMState,
+};
+
+/// Records the functions (contract and selector) called by contracts during a
+/// transaction into `EVMState::call_path`
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CallPathTracer;
+
+impl CallPathTracer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<SC> Middleware<SC> for CallPathTracer
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        let (arg_offset, arg_len) = match *interp.instruction_pointer {
+            0xf1 | 0xf2 => (interp.stack.peek(3).unwrap(), interp.stack.peek(4).unwrap()),
+            0xf4 | 0xfa => (interp.stack.peek(2).unwrap(), interp.stack.peek(3).unwrap()),
+            _ => return,
+        };
+        let (arg_offset, arg_len) = (as_u64(arg_offset) as usize, as_u64(arg_len) as usize);
+        if arg_len < 4 || interp.memory.len() < arg_offset.saturating_add(4) {
+            return;
+        }
+        let mut selector = [0u8; 4];
+        selector.copy_from_slice(interp.memory.get_slice(arg_offset, 4));
+        // DELEGATECALL and CALLCODE run the code of the target on the storage
+        // of the caller
+        let address = match *interp.instruction_pointer {
+            0xf2 | 0xf4 => interp.contract.address,
+            _ => convert_u256_to_h160(interp.stack.peek(1).unwrap()),
+        };
+        host.evmstate.call_path.insert((address, selector));
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::CallPath
+    }
+
+    #[allow(unused_variables)]
+    unsafe fn before_execute(
+        &mut self,
+        interp: Option<&mut Interpreter>,
+        host: &mut FuzzHost<SC>,
+        state: &mut EVMFuzzState,
+        is_step: bool,
+        data: &mut Bytes,
+        evm_state: &mut EVMState,
+    ) {
+        evm_state.call_path.clear();
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
@@ -0,0 +1,326 @@
+use std::{any, fmt::Debug};
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use revm_interpreter::Interpreter;
+use serde::{Deserialize, Serialize};
+
+use crate::evm::{
+    host::FuzzHost,
+    middlewares::middleware::{Middleware, MiddlewareType},
+    types::{as_u64, convert_u256_to_h160, EVMAddress, EVMFuzzState},
+    vm::EVMState,
+};
+
+/// Memory beyond this size is not tracked
+const MAX_TRACKED_MEMORY: usize = 1 << 20;
+
+/// A CALL / CALLCODE / DELEGATECALL whose target or function selector is
+/// derived from the calldata provided by the fuzzer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TaintedCall {
+    pub caller: EVMAddress,
+    pub target: EVMAddress,
+    pub pc: usize,
+    pub delegate: bool,
+    /// Whether the target address is tainted, otherwise only the function
+    /// selector is
+    pub tainted_target: bool,
+}
+
+/// Taint of the values of a call frame
+#[derive(Debug, Clone, Default)]
+struct TaintFrame {
+    stack: Vec<bool>,
+    memory: Vec<bool>,
+    input: Vec<bool>,
+}
+
+impl TaintFrame {
+    fn read_input(&self, offset: usize, len: usize) -> Vec<bool> {
+        (offset..offset.saturating_add(len).min(MAX_TRACKED_MEMORY))
+            .map(|i| self.input.get(i).cloned().unwrap_or(false))
+            .collect()
+    }
+
+    fn read_memory(&self, offset: usize, len: usize) -> Vec<bool> {
+        (offset..offset.saturating_add(len).min(MAX_TRACKED_MEMORY))
+            .map(|i| self.memory.get(i).cloned().unwrap_or(false))
+            .collect()
+    }
+
+    fn write_memory(&mut self, offset: usize, taint: &[bool]) {
+        let end = offset.saturating_add(taint.len());
+        if end > MAX_TRACKED_MEMORY {
+            return;
+        }
+        if self.memory.len() < end {
+            self.memory.resize(end, false);
+        }
+        self.memory[offset..end].copy_from_slice(taint);
+    }
+
+    fn clear_memory(&mut self, offset: usize, len: usize) {
+        let end = offset.saturating_add(len).min(self.memory.len());
+        if offset < end {
+            self.memory[offset..end].fill(false);
+        }
+    }
+
+    fn pop(&mut self, n: usize) -> bool {
+        let mut res = false;
+        for _ in 0..n {
+            res |= self.stack.pop().unwrap_or(false);
+        }
+        res
+    }
+
+    /// Taint of the `n`-th element from the top of the stack
+    fn peek(&self, n: usize) -> bool {
+        self.stack.len() > n && self.stack[self.stack.len() - 1 - n]
+    }
+}
+
+/// Number of stack items popped and pushed by opcodes whose result is tainted
+/// iff one of their operands is
+fn stack_io(op: u8) -> Option<(usize, usize)> {
+    Some(match op {
+        0x00 | 0x5b | 0xfe => (0, 0),
+        0x01..=0x07 | 0x0a | 0x0b | 0x10..=0x14 | 0x16..=0x18 | 0x1a..=0x1d => (2, 1),
+        0x08 | 0x09 => (3, 1),
+        0x15 | 0x19 | 0x31 | 0x3b | 0x3f | 0x40 | 0x49 | 0x5c => (1, 1),
+        0x30 | 0x32..=0x34 | 0x36 | 0x38 | 0x3a | 0x3d | 0x41..=0x48 | 0x4a | 0x58..=0x5a | 0x5f..=0x7f => (0, 1),
+        0x50 | 0x56 | 0xff => (1, 0),
+        0x57 | 0x5d | 0xf3 | 0xfd => (2, 0),
+        0xa0..=0xa4 => ((op - 0xa0) as usize + 2, 0),
+        0xf0 => (3, 1),
+        0xf5 => (4, 1),
+        _ => return None,
+    })
+}
+
+/// Tracks the flow of the calldata provided by the fuzzer (the input of the
+/// outermost call) through stack, memory, storage and nested calls, and
+/// records the calls whose target or function selector it controls into
+/// `EVMState::tainted_calls`. The taint of the storage is kept in
+/// `EVMState::tainted_slots`, so that it flows across the transactions
+#[derive(Debug, Clone, Default)]
+pub struct CallTaintTracer {
+    frames: Vec<TaintFrame>,
+    /// Taint of the input of the call about to be made at the given depth
+    pending_input: Option<(usize, Vec<bool>)>,
+}
+
+impl CallTaintTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn enter(&mut self, depth: usize, interp: &Interpreter) {
+        self.frames.truncate(depth + 1);
+        if self.frames.len() == depth + 1 {
+            return;
+        }
+        let input = match self.pending_input.take() {
+            Some((d, input)) if d == depth => input,
+            // the outermost call is fully controlled by the fuzzer
+            _ if depth == 0 => vec![true; interp.contract.input.len()],
+            _ => vec![],
+        };
+        while self.frames.len() < depth {
+            self.frames.push(TaintFrame::default());
+        }
+        self.frames.push(TaintFrame {
+            input,
+            ..Default::default()
+        });
+    }
+}
+
+impl<SC> Middleware<SC> for CallTaintTracer
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        let depth = host.call_depth as usize;
+        self.enter(depth, interp);
+        // not consumed when the previous call did not execute any code (e.g.,
+        // precompiles)
+        self.pending_input = None;
+
+        let address = interp.contract.address;
+        let frame = self.frames.last_mut().unwrap();
+        // resumed executions (control leak) and frames entered before the
+        // tracer was enabled have untracked stack items
+        frame.stack.resize(interp.stack.len(), false);
+
+        let op = *interp.instruction_pointer;
+        let arg = |n: usize| as_u64(interp.stack.peek(n).unwrap_or_default()) as usize;
+
+        if let Some((pop, push)) = stack_io(op) {
+            let taint = frame.pop(pop);
+            frame.stack.extend(std::iter::repeat(taint).take(push));
+            return;
+        }
+
+        match op {
+            // SHA3
+            0x20 => {
+                let taint = frame.read_memory(arg(0), arg(1)).contains(&true);
+                frame.pop(2);
+                frame.stack.push(taint);
+            }
+            // CALLDATALOAD
+            0x35 => {
+                let taint = frame.read_input(arg(0), 32).contains(&true);
+                frame.pop(1);
+                frame.stack.push(taint);
+            }
+            // CALLDATACOPY
+            0x37 => {
+                let taint = frame.read_input(arg(1), arg(2));
+                frame.write_memory(arg(0), &taint);
+                frame.pop(3);
+            }
+            // CODECOPY, RETURNDATACOPY
+            0x39 | 0x3e => {
+                frame.clear_memory(arg(0), arg(2));
+                frame.pop(3);
+            }
+            // EXTCODECOPY
+            0x3c => {
+                frame.clear_memory(arg(1), arg(3));
+                frame.pop(4);
+            }
+            // MLOAD
+            0x51 => {
+                let taint = frame.read_memory(arg(0), 32).contains(&true);
+                frame.pop(1);
+                frame.stack.push(taint);
+            }
+            // MSTORE
+            0x52 => {
+                let taint = frame.peek(1);
+                frame.write_memory(arg(0), &[taint; 32]);
+                frame.pop(2);
+            }
+            // MSTORE8
+            0x53 => {
+                let taint = frame.peek(1);
+                frame.write_memory(arg(0), &[taint]);
+                frame.pop(2);
+            }
+            // SLOAD
+            0x54 => {
+                let slot = interp.stack.peek(0).unwrap();
+                frame.pop(1);
+                frame.stack.push(host.evmstate.tainted_slots.contains(&(address, slot)));
+            }
+            // SSTORE
+            0x55 => {
+                let slot = interp.stack.peek(0).unwrap();
+                let taint = frame.peek(1);
+                frame.pop(2);
+                if taint {
+                    host.evmstate.tainted_slots.insert((address, slot));
+                } else {
+                    host.evmstate.tainted_slots.remove(&(address, slot));
+                }
+            }
+            // MCOPY
+            0x5e => {
+                let taint = frame.read_memory(arg(1), arg(2));
+                frame.write_memory(arg(0), &taint);
+                frame.pop(3);
+            }
+            // DUP
+            0x80..=0x8f => {
+                let taint = frame.peek((op - 0x80) as usize);
+                frame.stack.push(taint);
+            }
+            // SWAP
+            0x90..=0x9f => {
+                let len = frame.stack.len();
+                let n = (op - 0x90) as usize + 1;
+                if len > n {
+                    frame.stack.swap(len - 1 - n, len - 1);
+                }
+            }
+            // CALL, CALLCODE, DELEGATECALL, STATICCALL
+            0xf1 | 0xf2 | 0xf4 | 0xfa => {
+                let (args_idx, pops) = match op {
+                    0xf1 | 0xf2 => (3, 7),
+                    _ => (2, 6),
+                };
+                let input = frame.read_memory(arg(args_idx), arg(args_idx + 1));
+                let tainted_target = frame.peek(1);
+                let tainted_selector = input.len() >= 4 && input[..4].contains(&true);
+                if op != 0xfa && (tainted_target || tainted_selector) {
+                    host.evmstate.tainted_calls.insert(TaintedCall {
+                        caller: address,
+                        target: convert_u256_to_h160(interp.stack.peek(1).unwrap()),
+                        pc: interp.program_counter(),
+                        delegate: op == 0xf4,
+                        tainted_target,
+                    });
+                }
+                frame.pop(pops);
+                frame.stack.push(false);
+                self.pending_input = Some((depth + 1, input));
+            }
+            _ => {}
+        }
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::CallTaint
+    }
+
+    #[allow(unused_variables)]
+    unsafe fn before_execute(
+        &mut self,
+        interp: Option<&mut Interpreter>,
+        host: &mut FuzzHost<SC>,
+        state: &mut EVMFuzzState,
+        is_step: bool,
+        data: &mut Bytes,
+        evm_state: &mut EVMState,
+    ) {
+        self.frames.clear();
+        self.pending_input = None;
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taint_frame() {
+        let mut frame = TaintFrame {
+            input: vec![false, false, false, false, true],
+            ..Default::default()
+        };
+        assert!(!frame.read_input(0, 4).contains(&true));
+        assert!(frame.read_input(4, 32).contains(&true));
+
+        frame.write_memory(32, &[true; 32]);
+        assert!(!frame.read_memory(0, 32).contains(&true));
+        assert!(frame.read_memory(60, 8).contains(&true));
+        // untracked memory is never tainted
+        frame.write_memory(MAX_TRACKED_MEMORY, &[true; 32]);
+        assert!(!frame.read_memory(MAX_TRACKED_MEMORY, 32).contains(&true));
+        frame.clear_memory(48, usize::MAX);
+        assert!(frame.read_memory(32, 16).contains(&true));
+        assert!(!frame.read_memory(48, 16).contains(&true));
+
+        frame.stack = vec![true, false];
+        assert!(frame.peek(1));
+        assert!(!frame.pop(1));
+        assert!(frame.pop(1));
+    }
+}
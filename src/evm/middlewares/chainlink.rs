@@ -0,0 +1,311 @@
+//! Mocks of Chainlink aggregators, given with `--chainlink-feeds`
+//!
+//! The calls to `latestRoundData()`, `latestAnswer()` and `getRoundData()` of
+//! a mocked feed return an answer chosen by its mode:
+//! - `<feed>=replay`: one of the last answers of the feed onchain
+//! - `<feed>=pinned:<answer>`: always the same answer
+//! - `<feed>=<min>..<max>`: an answer within the bounds, mutated by the fuzzer
+//!   (the feed is manipulable)
+//!
+//! The answer is picked with the randomness of the input, so that it is
+//! mutated and replayed along the input, each feed reading its own byte of it
+//! (after the first one, which is used by the other middlewares). The latest
+//! round is reported as updated at the current block to pass staleness checks,
+//! and each of the previous [`CHAINLINK_REPLAY_ROUNDS`] rounds
+//! [`CHAINLINK_ROUND_INTERVAL`] seconds before the next one.
+
+use std::{any, collections::HashMap, str::FromStr};
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use revm_interpreter::Interpreter;
+use tracing::{debug, info};
+
+use crate::{
+    evm::{
+        host::FuzzHost,
+        middlewares::middleware::{CallMiddlewareReturn, Middleware, MiddlewareType},
+        onchain::endpoints::OnChainConfig,
+        types::{as_u64, convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256},
+        vm::{IS_FAST_CALL, IS_FAST_CALL_STATIC},
+    },
+    r#const::{CHAINLINK_REPLAY_ROUNDS, CHAINLINK_ROUND_INTERVAL},
+};
+
+/// Number of mocked feeds, each reading its own byte of the randomness of the
+/// inputs
+pub static mut CHAINLINK_FEEDS: usize = 0;
+
+/// `latestRoundData()`
+const LATEST_ROUND_DATA: [u8; 4] = [0xfe, 0xaf, 0x96, 0x8c];
+/// `latestAnswer()`
+const LATEST_ANSWER: [u8; 4] = [0x50, 0xd2, 0x5b, 0xcd];
+/// `getRoundData(uint80)`
+const GET_ROUND_DATA: [u8; 4] = [0x9a, 0x6f, 0xc8, 0xf5];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeedMode {
+    /// Replay the last answers of the feed
+    Replay,
+    Pinned(EVMU256),
+    /// Any answer within the bounds
+    Manipulable {
+        min: EVMU256,
+        max: EVMU256,
+    },
+}
+
+impl FromStr for FeedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |answer: &str| {
+            EVMU256::from_str_radix(answer, 10).map_err(|_| format!("invalid Chainlink answer {}", answer))
+        };
+        if s == "replay" {
+            return Ok(Self::Replay);
+        }
+        if let Some(answer) = s.strip_prefix("pinned:") {
+            return Ok(Self::Pinned(parse(answer)?));
+        }
+        let (min, max) = s.split_once("..").ok_or(format!("invalid Chainlink feed mode {}", s))?;
+        let (min, max) = (parse(min)?, parse(max)?);
+        if min > max {
+            return Err(format!("invalid Chainlink answer range {}", s));
+        }
+        Ok(Self::Manipulable { min, max })
+    }
+}
+
+/// Parse the comma separated `<feed>=<mode>` feeds
+pub fn parse_chainlink_feeds(s: &str) -> Result<HashMap<EVMAddress, FeedMode>, String> {
+    s.split(',')
+        .map(|feed| feed.trim())
+        .filter(|feed| !feed.is_empty())
+        .map(|feed| {
+            let (address, mode) = feed.split_once('=').ok_or(format!("invalid Chainlink feed {}", feed))?;
+            let address = EVMAddress::from_str(address.trim_start_matches("0x"))
+                .map_err(|e| format!("invalid Chainlink feed address {}: {}", address, e))?;
+            Ok((address, mode.parse()?))
+        })
+        .collect()
+}
+
+fn word(data: &[u8], idx: usize) -> Option<EVMU256> {
+    data.get(idx * 32..idx * 32 + 32).map(EVMU256::from_be_slice)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChainlinkMock {
+    feeds: HashMap<EVMAddress, FeedMode>,
+    /// Byte of the randomness of the inputs read by each feed
+    randomness_idx: HashMap<EVMAddress, usize>,
+    /// Last answers of the replayed feeds, latest first
+    history: HashMap<EVMAddress, Vec<EVMU256>>,
+}
+
+impl ChainlinkMock {
+    pub fn new(feeds: HashMap<EVMAddress, FeedMode>) -> Self {
+        let mut addresses = feeds.keys().cloned().collect::<Vec<_>>();
+        addresses.sort();
+        Self {
+            randomness_idx: addresses
+                .into_iter()
+                .enumerate()
+                .map(|(idx, feed)| (feed, idx + 1))
+                .collect(),
+            feeds,
+            history: HashMap::new(),
+        }
+    }
+
+    /// Byte of `randomness` the answers of the feed are picked with
+    fn randomness(&self, feed: &EVMAddress, randomness: &[u8]) -> u8 {
+        self.randomness_idx
+            .get(feed)
+            .and_then(|idx| randomness.get(*idx))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// `(roundId, answer, startedAt, updatedAt, answeredInRound)` of the round
+    /// `round` of the feed, None if the feed is not mocked or the round is not
+    /// one of the last [`CHAINLINK_REPLAY_ROUNDS`]
+    fn round_data(&self, feed: &EVMAddress, randomness: u8, round: EVMU256, now: EVMU256) -> Option<Vec<u8>> {
+        let latest = EVMU256::from(CHAINLINK_REPLAY_ROUNDS);
+        if round.is_zero() || round > latest {
+            return None;
+        }
+        // the answers of the previous rounds follow the latest one
+        let back = latest - round;
+        let answer = self.answer(feed, randomness.wrapping_add(back.as_limbs()[0] as u8))?;
+        let updated = now.saturating_sub(back * EVMU256::from(CHAINLINK_ROUND_INTERVAL));
+        Some(
+            [round, answer, updated, updated, round]
+                .iter()
+                .flat_map(|word| word.to_be_bytes::<32>())
+                .collect(),
+        )
+    }
+
+    /// Fetch the last [`CHAINLINK_REPLAY_ROUNDS`] answers of the replayed
+    /// feeds
+    pub fn fetch_history(&mut self, endpoint: &mut OnChainConfig) {
+        for (feed, mode) in &self.feeds {
+            if *mode != FeedMode::Replay {
+                continue;
+            }
+            let latest = endpoint.eth_call(*feed, Bytes::from(LATEST_ROUND_DATA.to_vec()));
+            let Some(round) = word(&latest, 0) else {
+                continue;
+            };
+            let answers = (0..CHAINLINK_REPLAY_ROUNDS)
+                .map_while(|back| {
+                    let round = round.checked_sub(EVMU256::from(back))?;
+                    let data = [GET_ROUND_DATA.as_slice(), &round.to_be_bytes::<32>()].concat();
+                    word(&endpoint.eth_call(*feed, Bytes::from(data)), 1).filter(|answer| *answer > EVMU256::ZERO)
+                })
+                .collect::<Vec<_>>();
+            info!("Replaying {} answers of Chainlink feed {:?}", answers.len(), feed);
+            self.history.insert(*feed, answers);
+        }
+    }
+
+    /// Answer of the feed for its byte of the randomness of the input, None if
+    /// the feed is not mocked
+    pub fn answer(&self, feed: &EVMAddress, randomness: u8) -> Option<EVMU256> {
+        match self.feeds.get(feed)? {
+            FeedMode::Replay => {
+                let answers = self.history.get(feed).filter(|answers| !answers.is_empty())?;
+                Some(answers[randomness as usize % answers.len()])
+            }
+            FeedMode::Pinned(answer) => Some(*answer),
+            FeedMode::Manipulable { min, max } => {
+                let (range, randomness) = (*max - *min, EVMU256::from(randomness));
+                let offset = match range.checked_mul(randomness) {
+                    Some(scaled) => scaled / EVMU256::from(u8::MAX),
+                    None => range / EVMU256::from(u8::MAX) * randomness,
+                };
+                Some(*min + offset)
+            }
+        }
+    }
+}
+
+impl<SC> Middleware<SC> for ChainlinkMock
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        if IS_FAST_CALL || IS_FAST_CALL_STATIC {
+            return;
+        }
+        let (arg_offset, arg_len) = match *interp.instruction_pointer {
+            0xf1 => (interp.stack.peek(3).unwrap(), interp.stack.peek(4).unwrap()),
+            0xfa => (interp.stack.peek(2).unwrap(), interp.stack.peek(3).unwrap()),
+            _ => return,
+        };
+        let feed = convert_u256_to_h160(interp.stack.peek(1).unwrap());
+        let arg_offset = as_u64(arg_offset) as usize;
+        if !self.feeds.contains_key(&feed) || as_u64(arg_len) < 4 || interp.memory.len() < arg_offset.saturating_add(4)
+        {
+            return;
+        }
+        let selector = interp.memory.get_slice(arg_offset, 4).to_vec();
+        let randomness = self.randomness(&feed, &host.randomness);
+        let now = host.env.block.timestamp;
+        let latest = EVMU256::from(CHAINLINK_REPLAY_ROUNDS);
+        let ret = match selector.as_slice() {
+            s if s == LATEST_ROUND_DATA => self.round_data(&feed, randomness, latest, now),
+            s if s == LATEST_ANSWER => self
+                .answer(&feed, randomness)
+                .map(|answer| answer.to_be_bytes::<32>().to_vec()),
+            s if s == GET_ROUND_DATA => {
+                if as_u64(arg_len) < 36 || interp.memory.len() < arg_offset.saturating_add(36) {
+                    return;
+                }
+                let round = EVMU256::from_be_slice(interp.memory.get_slice(arg_offset + 4, 32));
+                match self.round_data(&feed, randomness, round, now) {
+                    Some(ret) => Some(ret),
+                    // unknown rounds revert, as with the real aggregators
+                    None => {
+                        host.middlewares_latent_call_actions
+                            .push(CallMiddlewareReturn::ReturnRevert);
+                        return;
+                    }
+                }
+            }
+            _ => return,
+        };
+        let Some(ret) = ret else {
+            return;
+        };
+        debug!("Chainlink feed {:?} returns 0x{}", feed, hex::encode(&ret));
+        host.middlewares_latent_call_actions
+            .push(CallMiddlewareReturn::ReturnSuccess(Bytes::from(ret)));
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::Chainlink
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chainlink_answers() {
+        let feeds = parse_chainlink_feeds(
+            "0x0101010101010101010101010101010101010101=pinned:2000, \
+             0x0202020202020202020202020202020202020202=1000..1255, \
+             0x0303030303030303030303030303030303030303=replay",
+        )
+        .unwrap();
+        assert!(parse_chainlink_feeds("0x0101010101010101010101010101010101010101=2..1").is_err());
+
+        let mut mock = ChainlinkMock::new(feeds);
+        let [pinned, manipulable, replayed] = [1u8, 2, 3].map(|b| EVMAddress::from_slice(&[b; 20]));
+        assert_eq!(mock.answer(&pinned, 7), Some(EVMU256::from(2000)));
+        assert_eq!(mock.answer(&manipulable, 0), Some(EVMU256::from(1000)));
+        assert_eq!(mock.answer(&manipulable, 255), Some(EVMU256::from(1255)));
+        assert_eq!(mock.answer(&replayed, 0), None);
+        mock.history.insert(replayed, vec![EVMU256::from(5), EVMU256::from(6)]);
+        assert_eq!(mock.answer(&replayed, 3), Some(EVMU256::from(6)));
+        assert_eq!(mock.answer(&EVMAddress::zero(), 0), None);
+    }
+
+    #[test]
+    fn test_chainlink_rounds() {
+        let feeds = parse_chainlink_feeds(
+            "0x0101010101010101010101010101010101010101=0..255, \
+             0x0202020202020202020202020202020202020202=0..255",
+        )
+        .unwrap();
+        let mock = ChainlinkMock::new(feeds);
+        let [first, second] = [1u8, 2].map(|b| EVMAddress::from_slice(&[b; 20]));
+
+        // each feed reads its own byte, after the one of the other middlewares
+        let randomness = [9, 10, 20];
+        assert_eq!(mock.randomness(&first, &randomness), 10);
+        assert_eq!(mock.randomness(&second, &randomness), 20);
+        assert_eq!(mock.randomness(&second, &randomness[..2]), 0);
+
+        let now = EVMU256::from(100_000);
+        let latest = EVMU256::from(CHAINLINK_REPLAY_ROUNDS);
+        let data = mock.round_data(&first, 10, latest, now).unwrap();
+        assert_eq!(word(&data, 0), Some(latest));
+        assert_eq!(word(&data, 1), Some(EVMU256::from(10)));
+        assert_eq!(word(&data, 3), Some(now));
+
+        let previous = mock.round_data(&first, 10, latest - EVMU256::from(1), now).unwrap();
+        assert_eq!(word(&previous, 1), Some(EVMU256::from(11)));
+        assert_eq!(word(&previous, 3), Some(now - EVMU256::from(CHAINLINK_ROUND_INTERVAL)));
+        assert_eq!(mock.round_data(&first, 10, latest + EVMU256::from(1), now), None);
+        assert_eq!(mock.round_data(&first, 10, EVMU256::ZERO, now), None);
+    }
+}
@@ -3,6 +3,7 @@ use std::{clone::Clone, collections::HashMap, fmt::Debug, sync::Arc};
 use alloy_primitives::Address;
 use alloy_sol_types::SolValue;
 use bytes::Bytes;
+use ethers::{core::k256::ecdsa::SigningKey, utils::secret_key_to_address};
 use foundry_cheatcodes::Vm::{self, CallerMode};
 use libafl::schedulers::Scheduler;
 use revm_interpreter::{analysis::to_analysed, BytecodeLocked};
@@ -32,6 +33,17 @@ pub struct Prank {
     pub depth: u64,
 }
 
+/// Call mocked with `mockCall`.
+#[derive(Clone, Debug, Default)]
+pub struct MockedCall {
+    /// Prefix of the calldata matched
+    pub calldata: Bytes,
+    /// `msg.value` matched, any if `None`
+    pub value: Option<U256>,
+    /// Data returned instead of calling the callee
+    pub return_data: Bytes,
+}
+
 /// Records storage slots reads and writes.
 #[derive(Clone, Debug, Default)]
 pub struct RecordAccess {
@@ -50,7 +62,8 @@ where
     #[inline]
     pub fn addr(&self, args: Vm::addrCall) -> Option<Vec<u8>> {
         let Vm::addrCall { privateKey } = args;
-        let address: Address = privateKey.to_be_bytes::<{ U256::BYTES }>()[..20].try_into().unwrap();
+        let key = SigningKey::from_slice(&privateKey.to_be_bytes::<{ U256::BYTES }>()).ok()?;
+        let address = Address::from(secret_key_to_address(&key).0);
         Some(address.abi_encode())
     }
 
@@ -336,6 +349,51 @@ where
         None
     }
 
+    /// Mocks the calls to `callee` whose calldata starts with `data`.
+    #[inline]
+    pub fn mock_call0(&mut self, args: Vm::mockCall_0Call) -> Option<Vec<u8>> {
+        let Vm::mockCall_0Call {
+            callee,
+            data,
+            returnData,
+        } = args;
+        self.add_mocked_call(callee, data, None, returnData);
+        None
+    }
+
+    /// Mocks the calls to `callee` with `msg.value` whose calldata starts with
+    /// `data`.
+    #[inline]
+    pub fn mock_call1(&mut self, args: Vm::mockCall_1Call) -> Option<Vec<u8>> {
+        let Vm::mockCall_1Call {
+            callee,
+            msgValue,
+            data,
+            returnData,
+        } = args;
+        self.add_mocked_call(callee, data, Some(msgValue), returnData);
+        None
+    }
+
+    /// Clears all mocked calls.
+    #[inline]
+    pub fn clear_mocked_calls(&mut self) -> Option<Vec<u8>> {
+        self.mocked_calls.clear();
+        None
+    }
+
+    fn add_mocked_call(&mut self, callee: Address, data: Vec<u8>, value: Option<U256>, return_data: Vec<u8>) {
+        let (calldata, return_data) = (Bytes::from(data), Bytes::from(return_data));
+        let mocks = self.mocked_calls.entry(callee).or_default();
+        // mocking the same call again replaces its return data
+        mocks.retain(|mock| mock.calldata != calldata || mock.value != value);
+        mocks.push(MockedCall {
+            calldata,
+            value,
+            return_data,
+        });
+    }
+
     /// Gets the label of an address in test traces.
     #[inline]
     pub fn get_label(&self, args: Vm::getLabelCall) -> Option<Vec<u8>> {
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, rc::Rc, str::FromStr};
+use std::{cell::RefCell, rc::Rc};
 
 use alloy_primitives::U256;
 use alloy_sol_types::SolValue;
@@ -49,13 +49,12 @@ where
         url_or_alias: &str,
         block: Option<U256>,
     ) -> Option<Vec<u8>> {
-        let chain = if url_or_alias.starts_with("http") {
-            Chain::new_with_rpc_url(url_or_alias).ok()?
+        let block_number = block.map(|b| b.as_limbs()[0]).unwrap_or_default();
+        let mut onchain = if url_or_alias.starts_with("http") {
+            OnChainConfig::new(Chain::new_with_rpc_url(url_or_alias).ok()?, block_number)
         } else {
-            Chain::from_str(url_or_alias).ok()?
+            OnChainConfig::from_chain_name(url_or_alias, block_number, None, false)?
         };
-        let block_number = block.map(|b| b.as_limbs()[0]).unwrap_or_default();
-        let mut onchain = OnChainConfig::new(chain, block_number);
         onchain.etherscan_api_key = self.etherscan_api_key.clone();
 
         let storage_fetching = StorageFetchingMode::OneByOne;
@@ -16,8 +16,12 @@ use revm_interpreter::{opcode, InstructionResult, Interpreter};
 use revm_primitives::{B160, U256};
 use tracing::{debug, error, warn};
 
-use super::middleware::{Middleware, MiddlewareType};
-use crate::evm::{host::FuzzHost, types::EVMFuzzState};
+use super::middleware::{CallMiddlewareReturn, Middleware, MiddlewareType};
+use crate::evm::{
+    host::FuzzHost,
+    types::EVMFuzzState,
+    vm::{IS_FAST_CALL, IS_FAST_CALL_STATIC},
+};
 
 mod assert;
 mod common;
@@ -26,7 +30,7 @@ mod expect;
 mod fork;
 mod string;
 
-pub use common::{Prank, RecordAccess};
+pub use common::{MockedCall, Prank, RecordAccess};
 pub use expect::{ExpectedCallData, ExpectedCallTracker, ExpectedCallType, ExpectedEmit, ExpectedRevert};
 
 /// 0x7109709ECfa91a80626fF3989D68f67F5b1DD12D
@@ -55,6 +59,8 @@ pub struct Cheatcode<SC> {
     etherscan_api_key: Vec<String>,
     /// Address labels
     labels: HashMap<Address, String>,
+    /// Mocked calls: callee -> [(calldata, msg.value, return data)]
+    mocked_calls: HashMap<Address, Vec<MockedCall>>,
 
     _phantom: PhantomData<SC>,
 }
@@ -105,7 +111,10 @@ where
         let op = interp.current_opcode();
         match get_opcode_type(op, interp) {
             OpcodeType::CheatCall => self.cheat_call(interp, host),
-            OpcodeType::RealCall => self.real_call(interp, &mut host.expected_calls),
+            OpcodeType::RealCall => {
+                self.real_call(interp, &mut host.expected_calls);
+                self.mocked_call(interp, host);
+            }
             OpcodeType::Storage => self.record_accesses(interp),
             OpcodeType::Log => self.log(interp, &mut host.expected_emits),
             _ => (),
@@ -131,6 +140,7 @@ where
             recorded_logs: None,
             etherscan_api_key: etherscan_api_key.split(',').map(|s| s.to_string()).collect(),
             labels: HashMap::new(),
+            mocked_calls: HashMap::new(),
             _phantom: PhantomData,
         }
     }
@@ -179,6 +189,9 @@ where
             VmCalls::stopPrank(_) => self.stop_prank(host),
             VmCalls::label(args) => self.label(args),
             VmCalls::getLabel(args) => self.get_label(args),
+            VmCalls::mockCall_0(args) => self.mock_call0(args),
+            VmCalls::mockCall_1(args) => self.mock_call1(args),
+            VmCalls::clearMockedCalls(_) => self.clear_mocked_calls(),
 
             // fork
             VmCalls::createSelectFork_0(args) => self.create_select_fork0(host, args),
@@ -411,6 +424,32 @@ where
         }
     }
 
+    /// Return the mocked data instead of calling the target if `mockCall`
+    /// has been called with a prefix of the calldata, the longest prefix
+    /// winning
+    pub fn mocked_call(&self, interp: &mut Interpreter, host: &mut FuzzHost<SC>) {
+        if self.mocked_calls.is_empty() || unsafe { IS_FAST_CALL || IS_FAST_CALL_STATIC } {
+            return;
+        }
+        let Ok(target) = peek_realcall_target(interp) else {
+            return;
+        };
+        let Some(mocks) = self.mocked_calls.get(&target) else {
+            return;
+        };
+        let op = interp.current_opcode();
+        let (input, value) = try_or_continue!(peek_realcall_input_value(interp, op));
+        let mock = mocks
+            .iter()
+            .filter(|mock| input.starts_with(&mock.calldata) && mock.value.map_or(true, |v| v == value))
+            .max_by_key(|mock| mock.calldata.len());
+        if let Some(mock) = mock {
+            debug!("[cheatcode] mocked call to {:?}", target);
+            host.middlewares_latent_call_actions
+                .push(CallMiddlewareReturn::ReturnSuccess(mock.return_data.clone()));
+        }
+    }
+
     /// Record storage writes and reads if `record` has been called
     pub fn record_accesses(&mut self, interp: &mut Interpreter) {
         if let Some(storage_accesses) = &mut self.accesses {
@@ -774,6 +813,7 @@ mod tests {
                     env: Default::default(),
                     access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
                     liquidation_percent: 0,
+                    liquidation_path: 0,
                     direct_data: Bytes::from(
                         [
                             function_hash.clone(),
@@ -835,6 +875,35 @@ mod tests {
         assert_fn_success!("b5a49624");
     }
 
+    #[test]
+    fn test_addr_and_mock_call() {
+        let mut cheatcode: Cheatcode<StdScheduler<EVMFuzzState>> = Cheatcode::new("");
+        let address = cheatcode
+            .addr(Vm::addrCall {
+                privateKey: U256::from(1),
+            })
+            .unwrap();
+        assert_eq!(
+            hex::encode(address),
+            "0000000000000000000000007e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+
+        let callee = Address::from([1; 20]);
+        let mock = |data: &str, ret: &str| Vm::mockCall_0Call {
+            callee,
+            data: hex::decode(data).unwrap(),
+            returnData: hex::decode(ret).unwrap(),
+        };
+        cheatcode.mock_call0(mock("feaf968c", "01"));
+        cheatcode.mock_call0(mock("feaf968c", "02"));
+        cheatcode.mock_call0(mock("", "03"));
+        let mocks = &cheatcode.mocked_calls[&callee];
+        assert_eq!(mocks.len(), 2);
+        assert_eq!(mocks[0].return_data, Bytes::from(vec![0x02]));
+        cheatcode.clear_mocked_calls();
+        assert!(cheatcode.mocked_calls.is_empty());
+    }
+
     fn load_bytecode(path: &str) -> Bytecode {
         let hex_code = fs::read_to_string(path).expect("bytecode not found").trim().to_string();
         let bytecode = hex::decode(hex_code).unwrap();
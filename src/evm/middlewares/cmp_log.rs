@@ -0,0 +1,51 @@
+use std::{any, collections::HashSet, fmt::Debug};
+
+use libafl::schedulers::Scheduler;
+use revm_interpreter::Interpreter;
+use serde::Serialize;
+
+use crate::{
+    evm::{
+        host::FuzzHost,
+        middlewares::middleware::{Middleware, MiddlewareType},
+        types::{EVMFuzzState, EVMU256},
+    },
+    r#const::REDQUEEN_MAX_COMPARISONS,
+};
+
+/// Logs the operands of the comparisons (LT, GT, SLT, SGT, EQ) executed, for
+/// input-to-state replacements by [`crate::evm::redqueen::RedqueenStage`]
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CmpLog {
+    pub comparisons: Vec<(EVMU256, EVMU256)>,
+    known: HashSet<(EVMU256, EVMU256)>,
+}
+
+impl CmpLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<SC> Middleware<SC> for CmpLog
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, _host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        if !(0x10..=0x14).contains(&*interp.instruction_pointer) || self.comparisons.len() >= REDQUEEN_MAX_COMPARISONS {
+            return;
+        }
+        let operands = (interp.stack.peek(0).unwrap(), interp.stack.peek(1).unwrap());
+        if operands.0 != operands.1 && self.known.insert(operands) {
+            self.comparisons.push(operands);
+        }
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::CmpLog
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
@@ -1,36 +1,60 @@
 use std::{
     any,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{Debug, Display, Formatter},
     fs,
     fs::OpenOptions,
     io::Write,
     path::Path,
+    sync::atomic::{AtomicBool, Ordering},
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use itertools::Itertools;
 use libafl::schedulers::Scheduler;
+use nix::{
+    libc::c_int,
+    sys::signal::{signal, SigHandler, Signal},
+};
 use revm_interpreter::{
     opcode::{INVALID, JUMPDEST, JUMPI, STOP},
     Interpreter,
 };
 use revm_primitives::Bytecode;
 use serde::Serialize;
-use serde_json;
-use tracing::info;
-
-use crate::evm::{
-    bytecode_iterator::all_bytecode,
-    host::FuzzHost,
-    middlewares::middleware::{Middleware, MiddlewareType},
-    srcmap::{RawSourceMapInfo, SourceCodeResult, SOURCE_MAP_PROVIDER},
-    types::{is_zero, EVMAddress, EVMFuzzState},
-    vm::IN_DEPLOY,
+use serde_json::{self, json};
+use tracing::{info, warn};
+
+use crate::{
+    evm::{
+        bytecode_iterator::all_bytecode,
+        host::FuzzHost,
+        middlewares::middleware::{Middleware, MiddlewareType},
+        srcmap::{RawSourceMapInfo, SourceCodeResult, SOURCE_MAP_PROVIDER},
+        types::{is_zero, EVMAddress, EVMFuzzState},
+        vm::IN_DEPLOY,
+    },
+    metrics::{self, ContractCoverage},
+    telemetry,
 };
 
 pub static mut EVAL_COVERAGE: bool = false;
 
+/// Set on SIGUSR1 to write the coverage reports without waiting for new
+/// testcases
+pub static COVERAGE_REPORT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_coverage_report(_: c_int) {
+    COVERAGE_REPORT_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Write the coverage reports when receiving SIGUSR1
+pub fn handle_coverage_report_signal() {
+    if let Err(e) = unsafe { signal(Signal::SIGUSR1, SigHandler::Handler(request_coverage_report)) } {
+        warn!("Failed to handle SIGUSR1: {}", e);
+    }
+}
+
 /// Finds all PCs (offsets of bytecode) that are instructions / JUMPDEST
 /// Returns a tuple of (instruction PCs, JUMPI PCs, Skip PCs)
 pub fn instructions_pc(bytecode: &Bytecode) -> (HashSet<usize>, HashSet<usize>, HashSet<usize>) {
@@ -220,6 +244,81 @@ impl Display for CoverageReport {
     }
 }
 
+/// Coverage of the lines of a source file, i.e., whether any of the
+/// instructions mapped to a line is covered
+#[derive(Clone, Debug, Default)]
+pub struct SourceFileCoverage {
+    pub content: String,
+    /// line (1-based) -> covered
+    pub lines: BTreeMap<usize, bool>,
+}
+
+impl SourceFileCoverage {
+    pub fn covered_lines(&self) -> usize {
+        self.lines.values().filter(|covered| **covered).count()
+    }
+}
+
+/// LCOV tracefile of the line coverage
+pub fn to_lcov(files: &BTreeMap<String, SourceFileCoverage>) -> String {
+    let mut s = String::new();
+    for (file, cov) in files {
+        s.push_str(&format!("TN:\nSF:{}\n", file));
+        for (line, covered) in &cov.lines {
+            s.push_str(&format!("DA:{},{}\n", line, *covered as u8));
+        }
+        s.push_str(&format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            cov.lines.len(),
+            cov.covered_lines()
+        ));
+    }
+    s
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// HTML page showing the source files with their covered lines in green and
+/// uncovered lines in red
+pub fn to_html(files: &BTreeMap<String, SourceFileCoverage>) -> String {
+    let mut s = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>ItyFuzz Coverage</title><style>\
+         body{font-family:sans-serif}pre{margin:0}td{padding:0 8px;vertical-align:top}\
+         .hit{background:#d4f7d4}.miss{background:#f7d4d4}.no{color:#888}</style></head><body>\n",
+    );
+    s.push_str("<h1>Line Coverage</h1>\n<table>\n");
+    for (file, cov) in files {
+        s.push_str(&format!(
+            "<tr><td><a href=\"#{0}\">{0}</a></td><td>{1}/{2}</td></tr>\n",
+            escape_html(file),
+            cov.covered_lines(),
+            cov.lines.len()
+        ));
+    }
+    s.push_str("</table>\n");
+    for (file, cov) in files {
+        s.push_str(&format!("<h2 id=\"{0}\">{0}</h2>\n<table>\n", escape_html(file)));
+        for (idx, line) in cov.content.lines().enumerate() {
+            let class = match cov.lines.get(&(idx + 1)) {
+                Some(true) => "hit",
+                Some(false) => "miss",
+                None => "no",
+            };
+            s.push_str(&format!(
+                "<tr class=\"{}\"><td>{}</td><td><pre>{}</pre></td></tr>\n",
+                class,
+                idx + 1,
+                escape_html(line)
+            ));
+        }
+        s.push_str("</table>\n");
+    }
+    s.push_str("</body></html>\n");
+    s
+}
+
 impl Coverage {
     pub fn new(address_to_name: HashMap<EVMAddress, String>, work_dir: String) -> Self {
         Self {
@@ -287,6 +386,77 @@ impl Coverage {
         report.coverage.retain(|_, v| v.total_instructions > 10);
         report.dump_file(self.work_dir.clone());
         report.summarize();
+        telemetry::emit("new_coverage", json!({ "contracts": report.succint() }));
+        metrics::metrics().record_coverage(
+            report
+                .coverage
+                .iter()
+                .map(|(name, cov)| ContractCoverage {
+                    name: name.clone(),
+                    instructions: cov.instruction_coverage,
+                    total_instructions: cov.total_instructions,
+                    branches: cov.branch_coverage,
+                    total_branches: cov.total_branches,
+                })
+                .collect(),
+        );
+        self.dump_line_coverage();
+    }
+
+    /// Covered and total branches of each contract, sorted by name
+    pub fn branch_coverage(&self) -> Vec<(String, usize, usize)> {
+        let mut res = self
+            .total_jumpi_set
+            .iter()
+            .filter(|(_, jumpis)| !jumpis.is_empty())
+            .map(|(addr, jumpis)| {
+                let name = self.address_to_name.get(addr).cloned().unwrap_or(format!("{:?}", addr));
+                let covered = self.jumpi_coverage.get(addr).map_or(0, |covered| covered.len());
+                (name, covered, jumpis.len() * 2)
+            })
+            .collect_vec();
+        res.sort();
+        res
+    }
+
+    /// Coverage of the source lines, mapped from the instructions with the
+    /// source maps
+    pub fn line_coverage(&self) -> BTreeMap<String, SourceFileCoverage> {
+        let provider = SOURCE_MAP_PROVIDER.lock().unwrap();
+        let sources = provider
+            .all_sources()
+            .into_values()
+            .flatten()
+            .collect::<HashMap<String, String>>();
+        let empty_set = HashSet::new();
+        let mut files: BTreeMap<String, SourceFileCoverage> = BTreeMap::new();
+        for (addr, pcs) in &self.total_instr_set {
+            let covered = self.pc_coverage.get(addr).unwrap_or(&empty_set);
+            for pc in pcs {
+                let Some((file, line)) = provider.get_source_line(addr, *pc) else {
+                    continue;
+                };
+                let file_cov = files.entry(file.clone()).or_insert_with(|| SourceFileCoverage {
+                    content: sources.get(&file).cloned().unwrap_or_default(),
+                    lines: Default::default(),
+                });
+                *file_cov.lines.entry(line).or_default() |= covered.contains(pc);
+            }
+        }
+        files
+    }
+
+    /// Write the line coverage as a LCOV tracefile and a HTML page
+    pub fn dump_line_coverage(&self) {
+        let files = self.line_coverage();
+        if files.is_empty() {
+            return;
+        }
+        for (name, content) in [("lcov.info", to_lcov(&files)), ("coverage.html", to_html(&files))] {
+            if let Err(e) = fs::write(format!("{}/{}", self.work_dir, name), content) {
+                warn!("Failed to write {}: {}", name, e);
+            }
+        }
     }
 }
 
@@ -387,4 +557,23 @@ mod tests {
 
         assert_eq!(pcs.len(), 1107);
     }
+
+    #[test]
+    fn test_line_coverage_reports() {
+        let files = BTreeMap::from([(
+            "A.sol".to_string(),
+            SourceFileCoverage {
+                content: "contract A {\n  a < b;\n  c;\n}".to_string(),
+                lines: BTreeMap::from([(2, true), (3, false)]),
+            },
+        )]);
+        assert_eq!(
+            to_lcov(&files),
+            "TN:\nSF:A.sol\nDA:2,1\nDA:3,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+        let html = to_html(&files);
+        assert!(html.contains("<tr class=\"hit\"><td>2</td><td><pre>  a &lt; b;</pre></td></tr>"));
+        assert!(html.contains("<tr class=\"miss\"><td>3</td>"));
+        assert!(html.contains("<tr class=\"no\"><td>1</td>"));
+    }
 }
@@ -29,9 +29,19 @@ pub enum MiddlewareType {
     Sha3Bypass,
     Sha3TaintAnalysis,
     CallPrinter,
+    CallTracer,
     Reentrancy,
     IntegerOverflow,
     Cheatcode,
+    CallTaint,
+    CallPath,
+    PriceSource,
+    Chainlink,
+    CmpLog,
+    Debugger,
+    Python,
+    /// Middlewares of plugins, e.g., updating custom feedback maps
+    Custom,
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize, Copy)]
@@ -1,6 +1,13 @@
+pub mod breakpoint;
+pub mod call_path;
 pub mod call_printer;
+pub mod call_taint;
+pub mod chainlink;
 pub mod cheatcode;
+pub mod cmp_log;
 pub mod coverage;
 pub mod middleware;
+pub mod price_source;
 pub mod reentrancy;
 pub mod sha3_bypass;
+pub mod trace;
@@ -0,0 +1,142 @@
+use std::{any, fmt::Debug};
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use revm_interpreter::Interpreter;
+use revm_primitives::Bytecode;
+use serde::{Deserialize, Serialize};
+
+use crate::evm::{
+    bytecode_analyzer::find_constants,
+    host::FuzzHost,
+    middlewares::middleware::{Middleware, MiddlewareType},
+    types::{as_u64, convert_u256_to_h160, EVMAddress, EVMFuzzState},
+    vm::EVMState,
+};
+
+/// Selector of `balanceOf(address)`
+pub const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Selectors of the functions commonly used as price sources
+pub const PRICE_SELECTORS: [[u8; 4]; 8] = [
+    // Chainlink latestRoundData()
+    [0xfe, 0xaf, 0x96, 0x8c],
+    // Chainlink latestAnswer()
+    [0x50, 0xd2, 0x5b, 0xcd],
+    // Uniswap V2 getReserves()
+    [0x09, 0x02, 0xf1, 0xac],
+    // Uniswap V3 slot0()
+    [0x38, 0x50, 0xc7, 0xbd],
+    // Uniswap V3 TWAP observe(uint32[])
+    [0x88, 0x3b, 0xdb, 0xfd],
+    // TWAP oracles consult(address,uint256)
+    [0x3d, 0xda, 0xc9, 0x53],
+    // Router quotes getAmountsOut(uint256,address[])
+    [0xd0, 0x6c, 0xa6, 0x1f],
+    BALANCE_OF,
+];
+
+/// Maximum length of the calldata of a price read kept
+const MAX_READ_LEN: usize = 4 + 32 * 4;
+
+/// A call made by `consumer` to a price source
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PriceRead {
+    pub consumer: EVMAddress,
+    pub source: EVMAddress,
+    pub input: Bytes,
+}
+
+impl PriceRead {
+    pub fn selector(&self) -> [u8; 4] {
+        let mut selector = [0u8; 4];
+        selector.copy_from_slice(&self.input[..4]);
+        selector
+    }
+}
+
+/// Static analysis: whether the contract may read prices, i.e., pushes the
+/// selector of a price source
+pub fn reads_prices(bytecode: &Bytecode) -> bool {
+    find_constants(bytecode)
+        .iter()
+        .any(|constant| constant.len() == 4 && PRICE_SELECTORS.iter().any(|s| s[..] == constant[..]))
+}
+
+/// Records the calls to price sources made during a transaction into
+/// `EVMState::price_reads`. `balanceOf` calls only count as price reads when
+/// the consumer reads the balance of another account (e.g., of a pool).
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PriceSourceTracer;
+
+impl PriceSourceTracer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<SC> Middleware<SC> for PriceSourceTracer
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        let (arg_offset, arg_len) = match *interp.instruction_pointer {
+            0xf1 => (interp.stack.peek(3).unwrap(), interp.stack.peek(4).unwrap()),
+            0xfa => (interp.stack.peek(2).unwrap(), interp.stack.peek(3).unwrap()),
+            _ => return,
+        };
+        let (arg_offset, arg_len) = (as_u64(arg_offset) as usize, as_u64(arg_len) as usize);
+        if arg_len < 4 || interp.memory.len() < arg_offset.saturating_add(arg_len.min(MAX_READ_LEN)) {
+            return;
+        }
+        let input = interp.memory.get_slice(arg_offset, arg_len.min(MAX_READ_LEN));
+        if !PRICE_SELECTORS.iter().any(|s| s[..] == input[..4]) {
+            return;
+        }
+        let consumer = interp.contract.address;
+        if input[..4] == BALANCE_OF && (input.len() < 36 || EVMAddress::from_slice(&input[16..36]) == consumer) {
+            return;
+        }
+        host.evmstate.price_reads.insert(PriceRead {
+            consumer,
+            source: convert_u256_to_h160(interp.stack.peek(1).unwrap()),
+            input: Bytes::copy_from_slice(input),
+        });
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::PriceSource
+    }
+
+    #[allow(unused_variables)]
+    unsafe fn before_execute(
+        &mut self,
+        interp: Option<&mut Interpreter>,
+        host: &mut FuzzHost<SC>,
+        state: &mut EVMFuzzState,
+        is_step: bool,
+        data: &mut Bytes,
+        evm_state: &mut EVMState,
+    ) {
+        evm_state.price_reads.clear();
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_prices() {
+        // PUSH4 getReserves() STOP
+        let bytecode = Bytecode::new_raw(Bytes::from(vec![0x63, 0x09, 0x02, 0xf1, 0xac, 0x00]));
+        assert!(reads_prices(&bytecode));
+        // PUSH4 transfer(address,uint256) STOP
+        let bytecode = Bytecode::new_raw(Bytes::from(vec![0x63, 0xa9, 0x05, 0x9c, 0xbb, 0x00]));
+        assert!(!reads_prices(&bytecode));
+    }
+}
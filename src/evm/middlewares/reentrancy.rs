@@ -12,24 +12,103 @@ use serde::{Deserialize, Serialize};
 use crate::evm::{
     host::FuzzHost,
     middlewares::middleware::{Middleware, MiddlewareType},
-    types::{EVMAddress, EVMFuzzState, EVMU256},
+    types::{as_u64, convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256},
     vm::EVMState,
 };
 
+/// Callbacks of the flashloan providers and swaps, a call back into the
+/// contract requesting them is not a reentrancy
+const CALLBACK_SELECTORS: [[u8; 4]; 9] = [
+    // uniswapV2Call(address,uint256,uint256,bytes)
+    [0x10, 0xd1, 0xe8, 0x5c],
+    // pancakeCall(address,uint256,uint256,bytes)
+    [0x84, 0x80, 0x08, 0x12],
+    // uniswapV3FlashCallback(uint256,uint256,bytes)
+    [0xe9, 0xcb, 0xaf, 0xb0],
+    // uniswapV3SwapCallback(int256,int256,bytes)
+    [0xfa, 0x46, 0x1e, 0x33],
+    // executeOperation(address[],uint256[],uint256[],address,bytes), Aave V2
+    [0x92, 0x0f, 0x5c, 0x84],
+    // executeOperation(address,uint256,uint256,address,bytes), Aave V3
+    [0x1b, 0x11, 0xd0, 0xff],
+    // onFlashLoan(address,address,uint256,uint256,bytes), ERC-3156
+    [0x23, 0xe3, 0x0c, 0x8b],
+    // receiveFlashLoan(address[],uint256[],uint256[],bytes), Balancer
+    [0xf0, 0x4f, 0x27, 0x07],
+    // callFunction(address,(address,uint256),bytes), dYdX
+    [0x8b, 0x41, 0x87, 0x13],
+];
+
 #[derive(Serialize, Debug, Clone, Default)]
-pub struct ReentrancyTracer;
+pub struct ReentrancyTracer {
+    /// Call stack of the current transaction, i.e., the contract executing at
+    /// each call depth and the storage slots it has written so far
+    frames: Vec<(EVMAddress, HashSet<EVMU256>)>,
+}
 
 impl ReentrancyTracer {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Keep the call stack in sync with the call depth of the host
+    fn update_frames(&mut self, depth: usize, address: EVMAddress) {
+        self.frames.truncate(depth + 1);
+        if self.frames.len() == depth + 1 {
+            if self.frames[depth].0 == address {
+                return;
+            }
+            self.frames.pop();
+        }
+        // frames skipped (e.g., precompiles) are never reentered
+        while self.frames.len() < depth {
+            self.frames.push((EVMAddress::zero(), HashSet::new()));
+        }
+        self.frames.push((address, HashSet::new()));
+    }
+
+    /// A call into `target` while an earlier frame of `target` has written to
+    /// storage and is waiting for an external call to another contract,
+    /// returns the call trace and the written slots. Self-calls and flashloan
+    /// or swap callbacks are not reentrancies.
+    fn reentrant_call(&self, target: EVMAddress, selector: Option<[u8; 4]>) -> Option<ReentrantCall> {
+        if selector.is_some_and(|selector| CALLBACK_SELECTORS.contains(&selector)) {
+            return None;
+        }
+        if self.frames.last().is_some_and(|(address, _)| *address == target) {
+            return None;
+        }
+        let (_, writes) = self.frames.windows(2).map(|frames| (&frames[0], &frames[1])).find_map(
+            |((address, writes), (callee, _))| {
+                (*address == target && !writes.is_empty() && *callee != target).then_some((address, writes))
+            },
+        )?;
+        let mut trace = self.frames.iter().map(|(address, _)| *address).collect::<Vec<_>>();
+        trace.push(target);
+        let mut slots = writes.iter().cloned().collect::<Vec<_>>();
+        slots.sort();
+        Some(ReentrantCall { trace, slots })
     }
 }
 
+/// A call reentering a contract in the middle of its execution after it wrote
+/// to storage
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReentrantCall {
+    /// Contracts on the call stack, from the transaction target to the
+    /// reentered contract
+    pub trace: Vec<EVMAddress>,
+    /// Storage slots written by the reentered contract before the call
+    pub slots: Vec<EVMU256>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ReentrancyData {
     pub reads: HashMap<(EVMAddress, EVMU256), Vec<u32>>,
     pub need_writes: HashMap<(EVMAddress, EVMU256), Vec<u32>>,
     pub found: HashSet<(EVMAddress, EVMU256)>,
+    /// Reentered contract -> first reentrant call found
+    pub reentrant_calls: HashMap<EVMAddress, ReentrantCall>,
 }
 
 fn merge_sorted_vec_dedup(dst: &mut Vec<u32>, another_one: &[u32]) {
@@ -85,6 +164,7 @@ where
     SC: Scheduler<State = EVMFuzzState> + Clone,
 {
     unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        self.update_frames(host.call_depth as usize, interp.contract.address);
         match *interp.instruction_pointer {
             0x54 => {
                 let depth = host.evmstate.post_execution.len() as u32;
@@ -134,6 +214,9 @@ where
             0x55 => {
                 let depth = host.evmstate.post_execution.len() as u32;
                 let slot_idx = interp.stack.peek(0).unwrap();
+                if let Some((_, writes)) = self.frames.last_mut() {
+                    writes.insert(slot_idx);
+                }
                 let write_entry = host
                     .evmstate
                     .reentrancy_metadata
@@ -151,6 +234,26 @@ where
                     }
                 }
             }
+
+            // CALL, CALLCODE
+            0xf1 | 0xf2 => {
+                let target = convert_u256_to_h160(interp.stack.peek(1).unwrap());
+                if host.evmstate.reentrancy_metadata.reentrant_calls.contains_key(&target) {
+                    return;
+                }
+                let (arg_offset, arg_len) = (
+                    as_u64(interp.stack.peek(3).unwrap()) as usize,
+                    as_u64(interp.stack.peek(4).unwrap()) as usize,
+                );
+                let selector = (arg_len >= 4 && interp.memory.len() >= arg_offset.saturating_add(4)).then(|| {
+                    let mut selector = [0u8; 4];
+                    selector.copy_from_slice(interp.memory.get_slice(arg_offset, 4));
+                    selector
+                });
+                if let Some(call) = self.reentrant_call(target, selector) {
+                    host.evmstate.reentrancy_metadata.reentrant_calls.insert(target, call);
+                }
+            }
             _ => {}
         }
     }
@@ -169,6 +272,7 @@ where
         data: &mut Bytes,
         evm_state: &mut EVMState,
     ) {
+        self.frames.clear();
         if !is_step {
             return;
         }
@@ -198,4 +302,54 @@ mod test {
         merge_sorted_vec_dedup(&mut vec2, &vec1);
         assert_eq!(vec2, vec![1, 2, 3, 4, 5, 6, 7, 8, 10]);
     }
+
+    #[test]
+    fn test_reentrant_call() {
+        let victim = EVMAddress::from_slice(&[1; 20]);
+        let attacker = EVMAddress::from_slice(&[2; 20]);
+        let mut tracer = ReentrancyTracer::new();
+        tracer.update_frames(0, victim);
+        tracer.update_frames(1, attacker);
+        // no write in the victim yet
+        assert_eq!(tracer.reentrant_call(victim, None), None);
+
+        tracer.update_frames(0, victim);
+        assert_eq!(tracer.frames.len(), 1);
+        tracer.frames[0].1.insert(EVMU256::from(3));
+        tracer.update_frames(1, attacker);
+        assert_eq!(
+            tracer.reentrant_call(victim, Some([0xa9, 0x05, 0x9c, 0xbb])),
+            Some(ReentrantCall {
+                trace: vec![victim, attacker, victim],
+                slots: vec![EVMU256::from(3)],
+            })
+        );
+        assert_eq!(tracer.reentrant_call(attacker, None), None);
+        // flashloan callback requested by the victim
+        assert_eq!(tracer.reentrant_call(victim, Some([0x10, 0xd1, 0xe8, 0x5c])), None);
+    }
+
+    #[test]
+    fn test_self_call() {
+        let victim = EVMAddress::from_slice(&[1; 20]);
+        let attacker = EVMAddress::from_slice(&[2; 20]);
+        let mut tracer = ReentrancyTracer::new();
+        tracer.update_frames(0, victim);
+        tracer.frames[0].1.insert(EVMU256::from(3));
+        // the victim calling itself
+        assert_eq!(tracer.reentrant_call(victim, None), None);
+        tracer.update_frames(1, victim);
+        tracer.frames[1].1.insert(EVMU256::from(4));
+        assert_eq!(tracer.reentrant_call(victim, None), None);
+
+        // the inner frame is waiting for the attacker
+        tracer.update_frames(2, attacker);
+        assert_eq!(
+            tracer.reentrant_call(victim, None),
+            Some(ReentrantCall {
+                trace: vec![victim, victim, attacker, victim],
+                slots: vec![EVMU256::from(4)],
+            })
+        );
+    }
 }
@@ -7,8 +7,10 @@ use std::{
 };
 
 use bytes::Bytes;
-use libafl::schedulers::Scheduler;
+use libafl::{prelude::HasMetadata, schedulers::Scheduler};
+use libafl_bolts::impl_serdeany;
 use revm_interpreter::{opcode::JUMPI, Interpreter};
+use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::evm::{
@@ -19,6 +21,15 @@ use crate::evm::{
 
 const MAX_CALL_DEPTH: u64 = 3;
 
+/// Tainted JUMPIs found so far, kept in the fuzz state so that they survive a
+/// save and resume of the campaign
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Sha3TaintedJumpiMetadata {
+    pub jumpis: HashSet<(EVMAddress, usize)>,
+}
+
+impl_serdeany!(Sha3TaintedJumpiMetadata);
+
 #[derive(Clone, Debug)]
 pub struct Sha3TaintAnalysisCtx {
     pub dirty_memory: Vec<bool>,
@@ -113,7 +124,7 @@ impl<SC> Middleware<SC> for Sha3TaintAnalysis
 where
     SC: Scheduler<State = EVMFuzzState> + Clone,
 {
-    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, state: &mut EVMFuzzState) {
         // skip taint analysis if call depth is too deep
         if host.call_depth > MAX_CALL_DEPTH {
             return;
@@ -303,8 +314,18 @@ where
                         interp.contract.address,
                         interp.program_counter()
                     );
-                    self.tainted_jumpi
-                        .insert((interp.contract.address, interp.program_counter()));
+                    let jumpi = (interp.contract.address, interp.program_counter());
+                    if self.tainted_jumpi.insert(jumpi) {
+                        if !state.has_metadata::<Sha3TaintedJumpiMetadata>() {
+                            state.add_metadata(Sha3TaintedJumpiMetadata::default());
+                        }
+                        state
+                            .metadata_map_mut()
+                            .get_mut::<Sha3TaintedJumpiMetadata>()
+                            .unwrap()
+                            .jumpis
+                            .insert(jumpi);
+                    }
                 }
             }
             // PC
@@ -492,6 +513,7 @@ mod tests {
             env: Default::default(),
             access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
             liquidation_percent: 0,
+            liquidation_path: 0,
             direct_data: bys,
             input_type: EVMInputTy::ABI,
             randomness: vec![],
@@ -0,0 +1,491 @@
+//! Execution traces as call trees: the calls, contract creations, logs and
+//! storage writes of a transaction, nested in the call they happen in, with
+//! the function names decoded from [`FUNCTION_SIG`] and the revert reasons
+//! decoded from the return data
+//!
+//! The returns are observed on the next step of the caller, whose stack then
+//! holds the success flag of a call or the address of a created contract.
+
+use std::{any, collections::HashMap};
+
+use itertools::Itertools;
+use libafl::schedulers::Scheduler;
+use revm_interpreter::Interpreter;
+
+use crate::{
+    evm::{
+        abi::FUNCTION_SIG,
+        host::FuzzHost,
+        middlewares::{
+            cheatcode::REVERT_PREFIX,
+            middleware::{Middleware, MiddlewareType},
+        },
+        types::{as_u64, convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256},
+        vm::IS_FAST_CALL,
+    },
+    generic_vm::trace::{ExecutionTrace, TraceEvent},
+};
+
+/// `keccak256("Panic(uint256)")[..4]`
+const PANIC_PREFIX: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+impl CallKind {
+    fn is_create(&self) -> bool {
+        matches!(self, CallKind::Create | CallKind::Create2)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceItem {
+    Call(CallFrame),
+    Log {
+        address: EVMAddress,
+        topics: Vec<EVMU256>,
+        data: Vec<u8>,
+    },
+    Sstore {
+        address: EVMAddress,
+        slot: EVMU256,
+        value: EVMU256,
+    },
+}
+
+/// Name of the function from [`FUNCTION_SIG`], or the selector
+fn function_name(selector: &[u8]) -> String {
+    let selector: [u8; 4] = selector.try_into().unwrap();
+    unsafe { FUNCTION_SIG.get(&selector).cloned() }.unwrap_or(format!("0x{}", hex::encode(selector)))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    pub caller: EVMAddress,
+    /// None for a contract creation that failed
+    pub target: Option<EVMAddress>,
+    pub value: EVMU256,
+    /// Calldata, or init code of a contract creation
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    /// None when the call did not return, e.g., the transaction stopped on a
+    /// control leak
+    pub success: Option<bool>,
+    pub items: Vec<TraceItem>,
+}
+
+impl CallFrame {
+    /// The call tree flattened into the trace format shared by the VMs
+    pub fn to_execution_trace(&self) -> ExecutionTrace<EVMAddress> {
+        let mut trace = ExecutionTrace::default();
+        self.push_events(0, &mut trace.events);
+        trace
+    }
+
+    fn push_events(&self, depth: usize, events: &mut Vec<TraceEvent<EVMAddress>>) {
+        let function = if self.kind.is_create() {
+            "new".to_string()
+        } else if self.input.len() < 4 {
+            "fallback".to_string()
+        } else {
+            function_name(&self.input[..4])
+        };
+        events.push(TraceEvent::Call {
+            depth,
+            caller: self.caller,
+            target: self.target,
+            function,
+            input: format!("0x{}", hex::encode(&self.input)),
+        });
+        for item in &self.items {
+            match item {
+                TraceItem::Call(call) => call.push_events(depth + 1, events),
+                TraceItem::Log { address, topics, data } => events.push(TraceEvent::Event {
+                    address: Some(*address),
+                    topics: topics.iter().map(|topic| format!("{:#x}", topic)).collect(),
+                    data: format!("0x{}", hex::encode(data)),
+                }),
+                TraceItem::Sstore { address, slot, value } => events.push(TraceEvent::StateWrite {
+                    address: *address,
+                    key: format!("{:#x}", slot),
+                    value: format!("{:#x}", value),
+                }),
+            }
+        }
+        events.push(TraceEvent::Return {
+            depth,
+            success: self.success,
+            output: format!("0x{}", hex::encode(&self.output)),
+        });
+    }
+}
+
+/// Reason of a revert: the message of `Error(string)`, the code of
+/// `Panic(uint256)` or the raw return data
+pub fn decode_revert_reason(output: &[u8]) -> String {
+    let word = |data: &[u8], offset: usize| {
+        data.get(offset..offset.checked_add(32)?)
+            .map(EVMU256::from_be_slice)
+            .filter(|v| *v <= EVMU256::from(u32::MAX))
+            .map(|v| as_u64(v) as usize)
+    };
+    if output.len() < 4 {
+        return if output.is_empty() {
+            "no reason".to_string()
+        } else {
+            format!("0x{}", hex::encode(output))
+        };
+    }
+    let (selector, data) = output.split_at(4);
+    if selector == REVERT_PREFIX {
+        let message = word(data, 0).and_then(|offset| {
+            let len = word(data, offset)?;
+            let start = offset + 32;
+            data.get(start..start.checked_add(len)?)
+        });
+        if let Some(message) = message {
+            return format!("\"{}\"", String::from_utf8_lossy(message));
+        }
+    } else if selector == PANIC_PREFIX && data.len() == 32 {
+        return format!("Panic({:#x})", EVMU256::from_be_slice(data));
+    }
+    format!("0x{}", hex::encode(output))
+}
+
+/// Bytes of the memory in `[offset, offset + len)`, truncated to the memory
+/// in use
+fn read_memory(interp: &Interpreter, offset: EVMU256, len: EVMU256) -> Vec<u8> {
+    let data = &interp.memory.data;
+    if offset >= EVMU256::from(data.len()) {
+        return vec![];
+    }
+    let offset = as_u64(offset) as usize;
+    let end = offset.saturating_add(as_u64(len.min(EVMU256::from(data.len()))) as usize);
+    data[offset..end.min(data.len())].to_vec()
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CallTracer {
+    pub address_to_name: HashMap<EVMAddress, String>,
+    /// Calls being executed, along with the interpreter of their caller (None
+    /// for the transaction)
+    open: Vec<(Option<usize>, CallFrame)>,
+}
+
+impl CallTracer {
+    pub fn new(address_to_name: HashMap<EVMAddress, String>) -> Self {
+        Self {
+            address_to_name,
+            open: vec![],
+        }
+    }
+
+    fn push_item(&mut self, item: TraceItem) {
+        if let Some((_, frame)) = self.open.last_mut() {
+            frame.items.push(item);
+        }
+    }
+
+    /// Call tree of the transaction executed since the last call, None if
+    /// nothing was executed
+    pub fn finish_tx(&mut self, reverted: bool, output: &[u8]) -> Option<CallFrame> {
+        let mut root = None;
+        while let Some((_, mut frame)) = self.open.pop() {
+            if let Some(child) = root.take() {
+                frame.items.push(TraceItem::Call(child));
+            }
+            root = Some(frame);
+        }
+        root.map(|mut root| {
+            root.success = Some(!reverted);
+            root.output = output.to_vec();
+            root
+        })
+    }
+
+    fn name(&self, address: &EVMAddress) -> String {
+        self.address_to_name
+            .get(address)
+            .cloned()
+            .unwrap_or(format!("{:?}", address))
+    }
+
+    fn function(frame: &CallFrame) -> String {
+        if frame.kind.is_create() {
+            return format!("new({} bytes)", frame.input.len());
+        }
+        if frame.input.len() < 4 {
+            return "fallback()".to_string();
+        }
+        let (selector, args) = frame.input.split_at(4);
+        let name = function_name(selector);
+        if args.is_empty() {
+            name
+        } else {
+            format!("{} 0x{}", name, hex::encode(args))
+        }
+    }
+
+    /// Render the call tree, each nested call being indented
+    pub fn render(&self, frame: &CallFrame) -> String {
+        let mut lines = vec![];
+        self.render_frame(frame, 0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn render_frame(&self, frame: &CallFrame, depth: usize, lines: &mut Vec<String>) {
+        let padding = "  ".repeat(depth);
+        let target = frame.target.map_or("?".to_string(), |target| self.name(&target));
+        let value = if frame.value == EVMU256::ZERO {
+            String::new()
+        } else {
+            format!(" {{value: {}}}", frame.value)
+        };
+        lines.push(format!(
+            "{}[{:?}] {} -> {}::{}{}",
+            padding,
+            frame.kind,
+            self.name(&frame.caller),
+            target,
+            Self::function(frame),
+            value
+        ));
+        for item in &frame.items {
+            match item {
+                TraceItem::Call(call) => self.render_frame(call, depth + 1, lines),
+                TraceItem::Log { address, topics, data } => lines.push(format!(
+                    "{}  [Log{}] {} topics: [{}] data: 0x{}",
+                    padding,
+                    topics.len(),
+                    self.name(address),
+                    topics.iter().map(|topic| format!("{:#x}", topic)).join(", "),
+                    hex::encode(data)
+                )),
+                TraceItem::Sstore { address, slot, value } => lines.push(format!(
+                    "{}  [Sstore] {} slot {:#x} = {:#x}",
+                    padding,
+                    self.name(address),
+                    slot,
+                    value
+                )),
+            }
+        }
+        let result = match frame.success {
+            Some(true) if frame.kind.is_create() => "created".to_string(),
+            Some(true) => format!("returned 0x{}", hex::encode(&frame.output)),
+            Some(false) => format!("reverted: {}", decode_revert_reason(&frame.output)),
+            None => "did not return".to_string(),
+        };
+        lines.push(format!("{}  <- {}", padding, result));
+    }
+}
+
+impl<SC> Middleware<SC> for CallTracer
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, _host: &mut FuzzHost<SC>, _state: &mut EVMFuzzState) {
+        // calls of the oracles
+        if IS_FAST_CALL {
+            return;
+        }
+        let interp_id = interp as *const Interpreter as usize;
+        if self.open.is_empty() {
+            self.open.push((
+                None,
+                CallFrame {
+                    kind: CallKind::Call,
+                    caller: interp.contract.caller,
+                    target: Some(interp.contract.address),
+                    value: interp.contract.value,
+                    input: interp.contract.input.to_vec(),
+                    output: vec![],
+                    success: None,
+                    items: vec![],
+                },
+            ));
+        }
+
+        // back in the caller of the innermost call
+        if matches!(self.open.last(), Some((Some(caller), _)) if *caller == interp_id) {
+            let (_, mut frame) = self.open.pop().unwrap();
+            let ret = interp.stack.peek(0).unwrap_or(EVMU256::ZERO);
+            frame.success = Some(ret != EVMU256::ZERO);
+            if frame.kind.is_create() {
+                frame.target = frame.success.unwrap().then(|| convert_u256_to_h160(ret));
+            }
+            frame.output = interp.return_data_buffer.to_vec();
+            self.push_item(TraceItem::Call(frame));
+        }
+
+        let address = interp.contract.address;
+        let peek = |idx: usize| interp.stack.peek(idx).unwrap_or(EVMU256::ZERO);
+        match *interp.instruction_pointer {
+            // SSTORE
+            0x55 => self.push_item(TraceItem::Sstore {
+                address,
+                slot: peek(0),
+                value: peek(1),
+            }),
+            // LOG0 - LOG4
+            op @ 0xa0..=0xa4 => {
+                let topics = (0..(op - 0xa0) as usize).map(|idx| peek(idx + 2)).collect();
+                self.push_item(TraceItem::Log {
+                    address,
+                    topics,
+                    data: read_memory(interp, peek(0), peek(1)),
+                })
+            }
+            // CREATE, CREATE2
+            op @ (0xf0 | 0xf5) => self.open.push((
+                Some(interp_id),
+                CallFrame {
+                    kind: if op == 0xf0 {
+                        CallKind::Create
+                    } else {
+                        CallKind::Create2
+                    },
+                    caller: address,
+                    target: None,
+                    value: peek(0),
+                    input: read_memory(interp, peek(1), peek(2)),
+                    output: vec![],
+                    success: None,
+                    items: vec![],
+                },
+            )),
+            // CALL, CALLCODE, DELEGATECALL, STATICCALL
+            op @ (0xf1 | 0xf2 | 0xf4 | 0xfa) => {
+                let (kind, value, args) = match op {
+                    0xf1 => (CallKind::Call, peek(2), 3),
+                    0xf2 => (CallKind::CallCode, peek(2), 3),
+                    0xf4 => (CallKind::DelegateCall, EVMU256::ZERO, 2),
+                    _ => (CallKind::StaticCall, EVMU256::ZERO, 2),
+                };
+                self.open.push((
+                    Some(interp_id),
+                    CallFrame {
+                        kind,
+                        caller: address,
+                        target: Some(convert_u256_to_h160(peek(1))),
+                        value,
+                        input: read_memory(interp, peek(args), peek(args + 1)),
+                        output: vec![],
+                        success: None,
+                        items: vec![],
+                    },
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::CallTracer
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_revert_reason() {
+        // Error("insufficient balance")
+        let error = hex::decode(
+            "08c379a0\
+             0000000000000000000000000000000000000000000000000000000000000020\
+             0000000000000000000000000000000000000000000000000000000000000014\
+             696e73756666696369656e742062616c616e6365000000000000000000000000",
+        )
+        .unwrap();
+        assert_eq!(decode_revert_reason(&error), "\"insufficient balance\"");
+        // Panic(0x11), arithmetic overflow
+        let panic = hex::decode("4e487b710000000000000000000000000000000000000000000000000000000000000011").unwrap();
+        assert_eq!(decode_revert_reason(&panic), "Panic(0x11)");
+        assert_eq!(decode_revert_reason(&[]), "no reason");
+        assert_eq!(
+            decode_revert_reason(&error[..40]),
+            format!("0x{}", hex::encode(&error[..40]))
+        );
+    }
+
+    #[test]
+    fn test_to_execution_trace() {
+        let (a, b) = (EVMAddress::from_slice(&[0x11; 20]), EVMAddress::from_slice(&[0x22; 20]));
+        let frame = CallFrame {
+            kind: CallKind::Call,
+            caller: a,
+            target: Some(b),
+            value: EVMU256::ZERO,
+            input: vec![0x12, 0x34, 0x56, 0x78, 0x01],
+            output: vec![],
+            success: Some(true),
+            items: vec![
+                TraceItem::Sstore {
+                    address: b,
+                    slot: EVMU256::from(1),
+                    value: EVMU256::from(2),
+                },
+                TraceItem::Call(CallFrame {
+                    kind: CallKind::Create,
+                    caller: b,
+                    target: None,
+                    value: EVMU256::ZERO,
+                    input: vec![0x60],
+                    output: vec![],
+                    success: Some(false),
+                    items: vec![],
+                }),
+            ],
+        };
+        let trace = frame.to_execution_trace();
+        assert_eq!(
+            trace.events,
+            vec![
+                TraceEvent::Call {
+                    depth: 0,
+                    caller: a,
+                    target: Some(b),
+                    function: "0x12345678".to_string(),
+                    input: "0x1234567801".to_string(),
+                },
+                TraceEvent::StateWrite {
+                    address: b,
+                    key: "0x1".to_string(),
+                    value: "0x2".to_string(),
+                },
+                TraceEvent::Call {
+                    depth: 1,
+                    caller: b,
+                    target: None,
+                    function: "new".to_string(),
+                    input: "0x60".to_string(),
+                },
+                TraceEvent::Return {
+                    depth: 1,
+                    success: Some(false),
+                    output: "0x".to_string(),
+                },
+                TraceEvent::Return {
+                    depth: 0,
+                    success: Some(true),
+                    output: "0x".to_string(),
+                },
+            ]
+        );
+        assert_eq!(trace.success(), Some(true));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, ops::Deref, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, ops::Deref, rc::Rc};
 
 use bytes::Bytes;
 use itertools::Itertools;
@@ -11,27 +11,47 @@ use revm_primitives::Bytecode;
 use super::types::EVMStagedVMState;
 use crate::{
     evm::{
+        blaz::{
+            builder::ArtifactInfoMetadata,
+            storage_layout::{storage_diff, StorageLayout},
+        },
         host::CALL_UNTIL,
-        input::{ConciseEVMInput, EVMInput},
-        types::{EVMAddress, EVMFuzzState, EVMQueueExecutor},
+        input::{ConciseEVMInput, EVMInput, EVMInputT},
+        oracles::{u512_div_float, ERC20_BUG_IDX, MULTI_BLOCK_FUND_LOSS_BUG_IDX},
+        tokens::numeraire::NumerairePrice,
+        types::{EVMAddress, EVMFuzzState, EVMQueueExecutor, EVMU256, EVMU512},
         vm::EVMState,
     },
     feedback::OracleFeedback,
+    fuzzer::ORACLE_OUTPUT,
     generic_vm::{vm_executor::GenericVM, vm_state::VMStateT},
     input::VMInputT,
     minimizer::SequentialMinimizer,
     oracle::BugMetadata,
+    scale,
     state::{FuzzState, HasExecutionResult, HasInfantStateState},
     tracer::TxnTrace,
 };
 
 pub struct EVMMinimizer {
     evm_executor_ref: Rc<RefCell<EVMQueueExecutor>>,
+    /// Unit in which the minimum capital is reported
+    numeraire: NumerairePrice,
+    /// Names of the contracts in the call trees
+    address_to_name: HashMap<EVMAddress, String>,
 }
 
 impl EVMMinimizer {
-    pub fn new(evm_executor_ref: Rc<RefCell<EVMQueueExecutor>>) -> Self {
-        Self { evm_executor_ref }
+    pub fn new(
+        evm_executor_ref: Rc<RefCell<EVMQueueExecutor>>,
+        numeraire: NumerairePrice,
+        address_to_name: HashMap<EVMAddress, String>,
+    ) -> Self {
+        Self {
+            evm_executor_ref,
+            numeraire,
+            address_to_name,
+        }
     }
 
     fn get_call_seq(vm_state: &EVMStagedVMState, state: &mut EVMFuzzState) -> Vec<(EVMInput, u32)> {
@@ -70,6 +90,266 @@ impl EVMMinimizer {
         }
         vec![]
     }
+
+    /// Execute the transactions from `initial_state` and check whether any of
+    /// them triggers the bugs
+    fn reproduces(
+        &mut self,
+        state: &mut EVMFuzzState,
+        txs: &[(EVMInput, u32)],
+        initial_state: &EVMStagedVMState,
+        objective: &mut EVMOracleFeedback<'_>,
+        bug_idx: &[u64],
+    ) -> bool {
+        let mut is_solution = false;
+        let mut current_state = initial_state.clone();
+
+        for item in txs {
+            // skip when there is no post execution but the tx is step
+            if item.0.is_step() && !current_state.state.has_post_execution() {
+                break;
+            }
+
+            let (mut tx, call_leak) = item.clone();
+            unsafe {
+                CALL_UNTIL = call_leak;
+            }
+            tx.sstate = current_state.clone();
+            let res = {
+                let mut executor = self.evm_executor_ref.deref().borrow_mut();
+                executor.execute(&tx, state)
+            };
+
+            state.set_execution_result(res.clone());
+            is_solution |= objective.reproduces(state, &tx, bug_idx);
+            current_state = state.get_execution_result().new_state.clone();
+            if state.get_execution_result().reverted {
+                break;
+            }
+        }
+        is_solution
+    }
+
+    /// Simplify the calldata of the transactions while the bugs are still
+    /// reproduced: remove elements of dynamic arrays, empty dynamic args and
+    /// zero static args (see
+    /// [`crate::evm::abi::BoxedABI::shrink_candidates`]), so that the
+    /// reported exploit only keeps the values it needs
+    fn shrink_calldata(
+        &mut self,
+        state: &mut EVMFuzzState,
+        mut txs: Vec<(EVMInput, u32)>,
+        initial_state: &EVMStagedVMState,
+        objective: &mut EVMOracleFeedback<'_>,
+        bug_idx: &[u64],
+    ) -> Vec<(EVMInput, u32)> {
+        let mut budget = SHRINK_MAX_EXECUTIONS;
+        for idx in 0..txs.len() {
+            if txs[idx].0.is_step() {
+                continue;
+            }
+            let mut shrunk = true;
+            while shrunk && budget > 0 {
+                shrunk = false;
+                let candidates = match txs[idx].0.get_data_abi() {
+                    Some(abi) => abi.shrink_candidates(),
+                    None => break,
+                };
+                for candidate in candidates.into_iter().take(budget) {
+                    budget -= 1;
+                    let mut trial = txs.clone();
+                    trial[idx].0.data = Some(candidate);
+                    if self.reproduces(state, &trial, initial_state, objective, bug_idx) {
+                        txs = trial;
+                        shrunk = true;
+                        break;
+                    }
+                }
+            }
+        }
+        txs
+    }
+
+    /// Find the minimum capital (ETH sent by the attacker, including the one
+    /// used to borrow tokens) of a fund loss by bisecting a ratio applied to
+    /// the values of all transactions, and append it to the bug descriptions
+    fn minimize_capital(
+        &mut self,
+        state: &mut EVMFuzzState,
+        txs: Vec<(EVMInput, u32)>,
+        initial_state: &EVMStagedVMState,
+        objective: &mut EVMOracleFeedback<'_>,
+        bug_idx: &[u64],
+    ) -> Vec<(EVMInput, u32)> {
+        if capital(&txs) == EVMU256::ZERO {
+            return txs;
+        }
+
+        // the bug is reproduced with ratio `hi` and not with ratio `lo`
+        let (mut lo, mut hi) = (0, CAPITAL_RATIO_DENOMINATOR);
+        if self.reproduces(state, &scale_values(&txs, 0), initial_state, objective, bug_idx) {
+            hi = 0;
+        }
+        while hi > lo + 1 {
+            let mid = (lo + hi) / 2;
+            if self.reproduces(state, &scale_values(&txs, mid), initial_state, objective, bug_idx) {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        let txs = scale_values(&txs, hi);
+
+        let (capital, scale) = (EVMU512::from(capital(&txs)), EVMU512::from(1_000_000_000_000_000_u128));
+        let min_capital = u512_div_float(capital, scale, 3);
+        unsafe {
+            for output in ORACLE_OUTPUT.iter_mut() {
+                if !output["bug_idx"]
+                    .as_u64()
+                    .is_some_and(|idx| idx == ERC20_BUG_IDX || idx == MULTI_BLOCK_FUND_LOSS_BUG_IDX)
+                {
+                    continue;
+                }
+                let bug_info = format!(
+                    "{}Minimum capital required: {}\n",
+                    output["bug_info"].as_str().unwrap_or_default(),
+                    self.numeraire.format(capital, scale)
+                );
+                output["bug_info"] = bug_info.into();
+                output["min_capital"] = min_capital.clone().into();
+            }
+        }
+        txs
+    }
+
+    /// Replay the transactions and attach the storage changes of the
+    /// contracts with a storage layout, decoded against it, to the bug
+    /// descriptions
+    fn attach_storage_diff(
+        &mut self,
+        state: &mut EVMFuzzState,
+        txs: &[(EVMInput, u32)],
+        initial_state: &EVMStagedVMState,
+        objective: &mut EVMOracleFeedback<'_>,
+        bug_idx: &[u64],
+    ) {
+        let layouts: HashMap<EVMAddress, StorageLayout> = match state.metadata_map().get::<ArtifactInfoMetadata>() {
+            Some(meta) => meta
+                .info
+                .iter()
+                .filter_map(|(addr, result)| Some((*addr, result.storage_layout.clone()?)))
+                .collect(),
+            None => return,
+        };
+        if layouts.is_empty() {
+            return;
+        }
+
+        self.reproduces(state, txs, initial_state, objective, bug_idx);
+        let diff = storage_diff(
+            &initial_state.state,
+            &state.get_execution_result().new_state.state,
+            &layouts,
+        );
+        if diff.is_empty() {
+            return;
+        }
+        unsafe {
+            for output in ORACLE_OUTPUT.iter_mut() {
+                if output["bug_idx"].as_u64().map_or(false, |idx| bug_idx.contains(&idx)) {
+                    output["storage_diff"] = diff.clone().into();
+                }
+            }
+        }
+    }
+
+    /// Replay the transactions with the tracing of the executor enabled and
+    /// attach their call trees to the bug descriptions, along with the ETH
+    /// earned by the exploit, out of which a flashloan is repaid
+    fn attach_call_trees(
+        &mut self,
+        state: &mut EVMFuzzState,
+        txs: &[(EVMInput, u32)],
+        initial_state: &EVMStagedVMState,
+        bug_idx: &[u64],
+    ) {
+        let mut executor = self.evm_executor_ref.deref().borrow_mut();
+        executor.set_tracing(true);
+        executor.take_traces();
+
+        let mut current_state = initial_state.clone();
+        for (tx, call_leak) in txs {
+            if tx.is_step() && !current_state.state.has_post_execution() {
+                break;
+            }
+            let mut tx = tx.clone();
+            unsafe {
+                CALL_UNTIL = *call_leak;
+            }
+            tx.sstate = current_state.clone();
+            let res = executor.execute(&tx, state);
+            current_state = res.new_state;
+            if res.reverted {
+                break;
+            }
+        }
+        executor.set_tracing(false);
+        let flashloan_data = &current_state.state.flashloan_data;
+        let profit = flashloan_data.earned.saturating_sub(flashloan_data.owed) / scale!();
+        let name = |address: &EVMAddress| {
+            self.address_to_name
+                .get(address)
+                .cloned()
+                .unwrap_or(format!("{:?}", address))
+        };
+        let call_trees = executor
+            .take_traces()
+            .iter()
+            .map(|trace| trace.render_with(name))
+            .collect_vec();
+
+        unsafe {
+            for output in ORACLE_OUTPUT.iter_mut() {
+                if output["bug_idx"].as_u64().map_or(false, |idx| bug_idx.contains(&idx)) {
+                    output["call_tree"] = call_trees.join("\n").into();
+                    output["profit"] = profit.to_string().into();
+                }
+            }
+        }
+    }
+}
+
+/// Maximum number of executions spent simplifying the calldata of an exploit
+const SHRINK_MAX_EXECUTIONS: usize = 1024;
+
+/// Denominator of the ratios applied to the transaction values when
+/// bisecting the capital
+const CAPITAL_RATIO_DENOMINATOR: u64 = 1 << 16;
+
+/// Total ETH sent by the transactions
+fn capital(txs: &[(EVMInput, u32)]) -> EVMU256 {
+    txs.iter().fold(EVMU256::ZERO, |acc, (tx, _)| {
+        acc.saturating_add(tx.get_txn_value().unwrap_or_default())
+    })
+}
+
+/// Multiply the values of the transactions by `ratio /
+/// CAPITAL_RATIO_DENOMINATOR`
+fn scale_values(txs: &[(EVMInput, u32)], ratio: u64) -> Vec<(EVMInput, u32)> {
+    let (ratio, denominator) = (EVMU256::from(ratio), EVMU256::from(CAPITAL_RATIO_DENOMINATOR));
+    txs.iter()
+        .map(|(tx, call_leak)| {
+            let mut tx = tx.clone();
+            if let Some(value) = tx.get_txn_value() {
+                let scaled = match value.checked_mul(ratio) {
+                    Some(v) => v / denominator,
+                    None => value / denominator * ratio,
+                };
+                tx.set_txn_value(scaled);
+            }
+            (tx, *call_leak)
+        })
+        .collect_vec()
 }
 
 type EVMOracleFeedback<'a> = OracleFeedback<
@@ -119,56 +399,32 @@ impl<E: libafl::executors::HasObservers>
         txs.extend(input.transactions.iter().map(|ci| ci.to_input(last_sstate.clone())));
         assert!(!txs.is_empty());
         let mut minimized = false;
-        let mut initial_state = txs[0].0.sstate.clone();
+        let initial_state = txs[0].0.sstate.clone();
         while !minimized {
             minimized = true;
             for try_skip in 0..(txs.len()) {
-                let mut is_solution = false;
-                let mut current_state = initial_state.clone();
-
-                for (i, item) in txs.iter().enumerate() {
-                    if i == try_skip {
-                        continue;
-                    }
-
-                    // skip when there is no post execution but the tx is step
-                    if item.0.is_step() && !current_state.state.has_post_execution() {
-                        break;
-                    }
-
-                    let (mut tx, call_leak) = item.clone();
-                    unsafe {
-                        CALL_UNTIL = call_leak;
-                    }
-                    tx.sstate = current_state.clone();
-                    let res = {
-                        let mut executor = self.evm_executor_ref.deref().borrow_mut();
-                        executor.execute(&tx, state)
-                    };
-
-                    state.set_execution_result(res.clone());
-                    let trial_is_solution = objective.reproduces(state, &tx, &bug_idx_needed);
-                    is_solution |= trial_is_solution;
-                    current_state = state.get_execution_result().new_state.clone();
-                    let reverted = state.get_execution_result().reverted;
-                    if reverted {
-                        break;
-                    }
-                }
-
-                if is_solution {
-                    txs = txs
-                        .into_iter()
-                        .enumerate()
-                        .filter(|(i, _)| *i != try_skip)
-                        .map(|(_, s)| s)
-                        .collect();
+                let trial = txs
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != try_skip)
+                    .map(|(_, s)| s.clone())
+                    .collect_vec();
+                if self.reproduces(state, &trial, &initial_state, objective, &bug_idx_needed) {
+                    txs = trial;
                     minimized = false;
                     break;
                 }
             }
         }
 
+        txs = self.shrink_calldata(state, txs, &initial_state, objective, &bug_idx_needed);
+
+        if bug_idx_needed.contains(&ERC20_BUG_IDX) || bug_idx_needed.contains(&MULTI_BLOCK_FUND_LOSS_BUG_IDX) {
+            txs = self.minimize_capital(state, txs, &initial_state, objective, &bug_idx_needed);
+        }
+        self.attach_storage_diff(state, &txs, &initial_state, objective, &bug_idx_needed);
+        self.attach_call_trees(state, &txs, &initial_state, &bug_idx_needed);
+
         txs.into_iter()
             .map(|(tx, call_leak)| ConciseEVMInput::from_input_with_call_leak(&tx, call_leak))
             .collect_vec()
@@ -2,14 +2,21 @@ pub mod abi;
 pub mod blaz;
 pub mod bytecode_analyzer;
 pub mod bytecode_iterator;
+pub mod cmin;
+pub mod code_analysis;
 pub mod concolic;
 pub mod config;
 pub mod contract_utils;
 pub mod corpus_initializer;
+pub mod corpus_sync;
 pub mod cov_stage;
+pub mod dictionary;
+pub mod erc20_mutator;
 pub mod feedbacks;
 pub mod host;
 pub mod input;
+pub mod launcher;
+pub mod mempool;
 pub mod middlewares;
 pub mod minimizer;
 pub mod mutator;
@@ -17,11 +24,21 @@ pub mod onchain;
 pub mod oracle;
 pub mod oracles;
 pub mod presets;
+pub mod privileged;
 pub mod producers;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod redqueen;
 pub mod scheduler;
+pub mod senders;
+pub mod sig_to_score;
+pub mod slither;
 pub mod solution;
+pub mod splice_stage;
 pub mod srcmap;
+pub mod summary;
 pub mod tokens;
+pub mod tui;
 pub mod types;
 pub mod utils;
 pub mod vm;
@@ -39,6 +56,7 @@ use std::{
 
 use blaz::{
     builder::{BuildJob, BuildJobResult},
+    compiler_variants::{parse_compiler_variants, CompilerVariant},
     offchain_artifacts::OffChainArtifact,
     offchain_config::OffchainConfig,
 };
@@ -48,14 +66,29 @@ use contract_utils::ContractLoader;
 use ethers::types::Transaction;
 use input::{ConciseEVMInput, EVMInput};
 use itertools::Itertools;
+use mempool::parse_victim_tx;
+use middlewares::{breakpoint::parse_breakpoints, chainlink::parse_chainlink_feeds};
 use num_cpus;
-use onchain::endpoints::{Chain, OnChainConfig};
-use oracles::{erc20::IERC20OracleFlashloan, v2_pair::PairBalanceOracle};
+use onchain::{chains::init_chain_registry, endpoints::OnChainConfig, fork_backend::ForkBackendKind};
+use oracles::{
+    assertion::parse_assertion_errors,
+    differential::parse_upgrades,
+    echidna::EchidnaConfig,
+    erc20::IERC20OracleFlashloan,
+    event::parse_event_properties,
+    plugin::load_oracle_plugins,
+    v2_pair::PairBalanceOracle,
+    OracleThresholds,
+};
 use producers::erc20::ERC20Producer;
 use revm_primitives::B160;
+use senders::{parse_call_value_ranges, parse_senders};
 // use revm_primitives::ruint::aliases::B160;
 use serde::Deserialize;
 use serde_json::json;
+use slither::slither_hints;
+use solution::flashloan::parse_flashloan_providers;
+use tokens::{nft::parse_floor_prices, numeraire::Numeraire};
 use tracing::debug;
 use types::{EVMAddress, EVMFuzzState, EVMU256};
 use vm::EVMState;
@@ -64,7 +97,9 @@ use self::types::EVMQueueExecutor;
 use crate::{
     fuzzers::evm_fuzzer::evm_fuzzer,
     oracle::{Oracle, Producer},
+    r#const::{BLOCK_GAS_LIMIT, DEFAULT_CACHE_DIR, DEFAULT_MIN_PROFIT_USD, DEFAULT_NATIVE_PRICE_USD},
     state::FuzzState,
+    stop_conditions::{parse_coverage_target, parse_timeout, StopConditions},
 };
 
 pub const PRESET_WETH: &str = "0x4200000000000000000000000000000000000006";
@@ -103,8 +138,9 @@ struct RPCCall {
 #[derive(Parser, Debug, Default)]
 #[command(author, version, about, long_about = None, trailing_var_arg = true, allow_hyphen_values = true)]
 pub struct EvmArgs {
-    /// Glob pattern / address to find contracts
-    #[arg(short, long, default_value = "none")]
+    /// Glob pattern / address to find contracts, onchain target addresses are
+    /// separated by comma
+    #[arg(short, long, visible_alias = "target-addresses", default_value = "none")]
     target: String,
 
     #[arg(long, default_value = "false")]
@@ -118,26 +154,50 @@ pub struct EvmArgs {
     #[arg(long, default_value = "")]
     constructor_args: String,
 
-    /// Target type (glob, address, anvil_fork, config, setup)
+    /// Target type (glob, address, anvil_fork, config, setup, foundry, hardhat,
+    /// broadcast)
     /// (Default: Automatically infer from target)
     #[arg(long)]
     target_type: Option<String>,
 
+    /// Foundry project to build with `forge build` and fuzz, inferred when the
+    /// target is a directory with a foundry.toml
+    #[arg(long)]
+    foundry_dir: Option<String>,
+
+    /// Hardhat project to build with `npx hardhat compile` and fuzz, inferred
+    /// when the target is a directory with a hardhat.config.js / .ts
+    #[arg(long)]
+    hardhat_dir: Option<String>,
+
+    /// Broadcast file of `forge script --broadcast`
+    /// (broadcast/<Script>.s.sol/<chain id>/run-latest.json) whose transactions
+    /// are replayed to deploy the system to fuzz
+    #[arg(long)]
+    broadcast: Option<String>,
+
     /// Onchain - Chain type
     /// (eth,goerli,sepolia,bsc,chapel,polygon,mumbai,fantom,avalanche,optimism,
-    /// arbitrum,gnosis,base,celo,zkevm,zkevm_testnet,blast,local)
-    #[arg(short, long)]
+    /// arbitrum,gnosis,base,celo,zkevm,zkevm_testnet,blast,zksync,local)
+    #[arg(short, long, visible_alias = "chain")]
     chain_type: Option<String>,
 
     /// Onchain - Block number (Default: 0 / latest)
-    #[arg(long, short = 'b')]
+    #[arg(long, short = 'b', visible_alias = "block-number")]
     onchain_block_number: Option<u64>,
 
     /// Onchain Customize - RPC endpoint URL (Default: inferred from
     /// chain-type), Example: https://rpc.ankr.com/eth
-    #[arg(long, short = 'u')]
+    /// Multiple comma separated URLs fail over between each other
+    /// WebSocket URLs (ws://, wss://) pipeline batched storage requests
+    #[arg(long, short = 'u', visible_alias = "rpc-url")]
     onchain_url: Option<String>,
 
+    /// Onchain Customize - Fail over to the public RPC of the chain type when
+    /// the endpoints of --onchain-url fail (Default: false)
+    #[arg(long, default_value = "false")]
+    public_rpc_fallback: bool,
+
     /// Onchain Customize - Chain ID (Default: inferred from chain-type)
     #[arg(long, short = 'i')]
     onchain_chain_id: Option<u32>,
@@ -152,15 +212,52 @@ pub struct EvmArgs {
     #[arg(long, short = 'n')]
     onchain_chain_name: Option<String>,
 
+    /// Onchain Customize - TOML file defining chains (chain id, RPC, WETH,
+    /// DEXes) usable as chain-type, see `src/evm/onchain/chains.rs`
+    #[arg(long, default_value = "")]
+    chain_registry: String,
+
     /// Onchain Etherscan API Key (Default: None)
     #[arg(long, short = 'k')]
     onchain_etherscan_api_key: Option<String>,
 
+    /// Onchain - Do not look up the ABIs of the unverified contracts on
+    /// Sourcify and their function signatures on 4byte.directory
+    #[arg(long, default_value = "false")]
+    no_signature_lookup: bool,
+
     /// Onchain which fetching method to use (dump, onebyone) (Default:
     /// onebyone)
-    #[arg(long, default_value = "onebyone")]
+    #[arg(
+        long,
+        visible_alias = "storage-fetching",
+        default_value = "onebyone",
+        value_parser = ["dump", "onebyone"]
+    )]
     onchain_storage_fetching: String,
 
+    /// Directory of the persistent RPC cache, responses are cached per chain
+    /// and block
+    #[arg(long, default_value = DEFAULT_CACHE_DIR)]
+    cache_dir: String,
+
+    /// Fetch the onchain state through a local fork node (anvil, hardhat)
+    /// spawned for the fuzzed block, instead of the remote RPC
+    #[arg(long, value_parser = ["anvil", "hardhat"])]
+    fork_backend: Option<String>,
+
+    /// Maximum number of RPC credits (compute units) to consume, after which
+    /// only cached responses are served
+    #[arg(long)]
+    rpc_budget: Option<u64>,
+
+    /// Monitoring mode - Seconds between two checks of the implementation of
+    /// the EIP-1967 proxies fuzzed at the latest block. When one is upgraded,
+    /// the new implementation is loaded and the corpus migrated to its
+    /// functions without restarting the campaign (0 to disable)
+    #[arg(long, default_value = "0")]
+    watch_upgrades: u64,
+
     /// Enable Concolic (Experimental)
     #[arg(long, default_value = "false")]
     concolic: bool,
@@ -177,19 +274,130 @@ pub struct EvmArgs {
     #[arg(long, default_value = "0")]
     concolic_num_threads: usize,
 
+    /// Replace the calldata words equal to an operand of a comparison with the
+    /// other operand (Redqueen input-to-state replacements)
+    #[arg(long, default_value = "false")]
+    redqueen: bool,
+
     /// Enable flashloan
     #[arg(short, long, default_value = "false")]
     flashloan: bool,
 
+    /// Floor prices of NFT collections, separated by comma, for the ERC-721
+    /// and ERC-1155 tokens received to count as flashloan profit. Each is
+    /// <collection>=<price> in wei or with the ether suffix, e.g.,
+    /// 0x…=1.5ether
+    #[arg(long, default_value = "")]
+    nft_floor_prices: String,
+
+    /// Chainlink aggregators to mock, separated by comma. Each is
+    /// <feed>=replay (last answers onchain), <feed>=pinned:<answer> or
+    /// <feed>=<min>..<max> (answers mutated within the bounds, i.e., a
+    /// manipulable feed)
+    #[arg(long, default_value = "")]
+    chainlink_feeds: String,
+
+    /// Flashloan providers the ETH borrowed in the generated exploits is
+    /// taken from, separated by comma in order of preference: aave_v3,
+    /// balancer, dydx, pair (flash swap of a WETH pair) or auto for all of
+    /// them. The ETH is dealt to the attacker if empty or offchain
+    #[arg(long, default_value = "")]
+    poc_flashloan: String,
+
+    /// Breakpoints for triage, separated by comma: <address>:<pc> (decimal),
+    /// a selector (e.g., 0xa9059cbb) or <address>:<selector>. The stack,
+    /// memory and last storage accesses are dumped when one is hit. Only used
+    /// with `ityfuzz replay`
+    #[arg(long, default_value = "")]
+    break_at: String,
+
+    /// Slither hints: its JSON output (`slither <target> --json <file>`), or
+    /// what to run it on, e.g., `.` for a Foundry or Hardhat project. The
+    /// functions flagged by its detectors are fuzzed more
+    #[arg(long, default_value = "")]
+    slither: String,
+
+    /// CSV file of `address,function,score` lines scoring the functions to
+    /// fuzz more, the address being `*` for any contract and the function a
+    /// `name@argcount` slug or a selector. Reloaded on SIGHUP
+    #[arg(long, default_value = "")]
+    sig_to_score: String,
+
     /// Panic when a typed_bug() is called (Default: false)
     #[arg(long, default_value = "false")]
     panic_on_bug: bool,
 
-    /// Detectors enabled (all, high_confidence, ...). Refer to https://docs.ityfuzz.rs/docs-evm-contract/detecting-common-vulns
+    /// Execute each input twice from the same state and quarantine (into
+    /// work_dir/quarantine) the inputs whose executions differ, to catch
+    /// nondeterminism (Default: false)
+    #[arg(long, default_value = "false")]
+    determinism_check: bool,
+
+    /// Detectors enabled, separated by comma (all, high_confidence, none or
+    /// the names given by --list-detectors). Refer to https://docs.ityfuzz.rs/docs-evm-contract/detecting-common-vulns
     /// (Default: high_confidence)
     #[arg(long, short, default_value = "high_confidence")]
     detectors: String, // <- internally this is known as oracles
 
+    /// List the detectors and exit
+    #[arg(long, default_value = "false")]
+    list_detectors: bool,
+
+    /// Minimum profit (in USD) for a fund loss to be reported, the native
+    /// token being priced in USDC on chain (Default: 20)
+    #[arg(long, default_value_t = DEFAULT_MIN_PROFIT_USD)]
+    min_profit: f64,
+
+    /// Minimum decrease of a reserve for an imbalanced Uniswap pair to be
+    /// reported (Default: 0)
+    #[arg(long, default_value = "0")]
+    min_reserve_delta: String,
+
+    /// Maximum gas of the transaction leading to a fund loss for it to be
+    /// reported (Default: block gas limit)
+    #[arg(long, default_value_t = BLOCK_GAS_LIMIT)]
+    max_exploit_gas: u64,
+
+    /// Maximum capital (in the numeraire) the attacker can send and borrow.
+    /// Fund losses needing more are not reported, and the minimum capital
+    /// needed is reported for the others (Default: unlimited)
+    #[arg(long)]
+    max_capital: Option<f64>,
+
+    /// Unit of the profits and capitals (native, weth, wbnb, usdc, usdt).
+    /// Minimum profit and maximum capital are given in it and fund losses
+    /// reported in it, converted through the token routing layer (Default:
+    /// native)
+    #[arg(long, default_value = "native")]
+    numeraire: String,
+
+    /// Functions allowed to change the total supply of tokens for the supply
+    /// detector, separated by comma, in addition to the mint* / burn* ones
+    #[arg(long, default_value = "")]
+    supply_whitelist: String,
+
+    /// Custom errors reported by the assertion detector in addition to failed
+    /// asserts, separated by comma, given by selector (0x12345678) or by
+    /// signature (InvariantBroken(uint256))
+    #[arg(long, default_value = "")]
+    assertion_errors: String,
+
+    /// Upgraded bytecode of contracts, separated by comma: <address>=0x<code>
+    /// or <address>=<path of a file with the code in hex>, the runtime code
+    /// (e.g., of the new implementation of a proxy). Each transaction is
+    /// executed again with it, and divergences of the return data, events or
+    /// storage writes are reported
+    #[arg(long, default_value = "")]
+    upgraded_code: String,
+
+    /// Other compiler configurations to build the targets with, separated by
+    /// comma, each being the flags appended to the build command (e.g.,
+    /// `--use 0.8.19,--via-ir`). Each transaction is executed again with the
+    /// code of every build, and divergences are reported as for
+    /// --upgraded-code
+    #[arg(long, default_value = "")]
+    compiler_variants: String,
+
     // /// Matching style for state comparison oracle (Select from "Exact",
     // /// "DesiredContain", "StateContain")
     // #[arg(long, default_value = "Exact")]
@@ -198,6 +406,11 @@ pub struct EvmArgs {
     #[arg(long, short)]
     replay_file: Option<String>,
 
+    /// Directory the minimized corpus of the replayed files is written to, set
+    /// by the cmin command
+    #[arg(skip)]
+    cmin_output: Option<String>,
+
     /// Path of work dir, saves corpus, logs, and other stuffs
     #[arg(long, short, default_value = "work_dir")]
     work_dir: String,
@@ -210,7 +423,67 @@ pub struct EvmArgs {
     #[arg(long, default_value = "false")]
     run_forever: bool,
 
-    /// random seed
+    /// Stop fuzzing after the given number of executions (Default: unlimited)
+    #[arg(long)]
+    max_execs: Option<usize>,
+
+    /// Stop fuzzing once every contract reaches the given instruction
+    /// coverage, e.g., 90%
+    #[arg(long, default_value = "")]
+    stop_on_coverage: String,
+
+    /// Stop fuzzing after finding the given number of objectives instead of
+    /// the first one
+    #[arg(long)]
+    stop_after_objectives: Option<usize>,
+
+    /// Stop fuzzing after the given duration, e.g., 2h, 1h30m or 3600 (in
+    /// seconds)
+    #[arg(long, default_value = "")]
+    timeout: String,
+
+    /// Keep the VM state after every K-th transaction of a sequence in the
+    /// infant state corpus, trading memory for throughput on deep stateful
+    /// targets (Default: disabled)
+    #[arg(long)]
+    checkpoint_interval: Option<usize>,
+
+    /// Save the campaign to the work dir every given number of seconds, so
+    /// that it can be continued with `--resume` (Default: disabled)
+    #[arg(long, default_value = "0")]
+    save_interval: u64,
+
+    /// Continue the campaign saved in the work dir where it left off
+    #[arg(long, default_value = "false")]
+    resume: bool,
+
+    /// Fuzz the constructor args of the deployed contracts, exploring bugs
+    /// only reachable with particular initialization parameters. Args stored
+    /// into immutables are not fuzzed
+    #[arg(long, default_value = "false")]
+    fuzz_constructor_args: bool,
+
+    /// Run setUp() of the deployed contracts defining it once before fuzzing,
+    /// as Foundry does for test harnesses, and fuzz from the state it sets up
+    #[arg(long, default_value = "false")]
+    harness_setup: bool,
+
+    /// Infer the args of the functions of the contracts without ABI (e.g.,
+    /// closed-source onchain contracts) by probing their ABI decoder with
+    /// calldata of different shapes, instead of only decompiling them
+    #[arg(long, default_value = "false")]
+    infer_interface: bool,
+
+    /// Pending transaction of a user (signed and RLP encoded, as hex) to
+    /// sandwich: the fuzzer searches for transactions before and after it
+    /// profiting from it or changing its outcome, the transaction being
+    /// replayed as signed on the forked state
+    #[arg(long, default_value = "")]
+    victim_tx: String,
+
+    /// Random seed, the campaign (mutations, scheduling and the addresses of
+    /// the deployed contracts) being deterministic for a given seed. 0 picks
+    /// a seed from the current time
     #[arg(long, default_value = "1667840158231589000")]
     seed: u64,
 
@@ -219,10 +492,71 @@ pub struct EvmArgs {
     #[arg(long, default_value = "false")]
     sha3_bypass: bool,
 
+    /// Show a live dashboard of the campaign instead of the logs, which are
+    /// written to the work dir
+    #[arg(long, default_value = "false")]
+    tui: bool,
+
+    /// Emit the events of the campaign as JSON lines to the given file or
+    /// unix socket (unix:<path>)
+    #[arg(long)]
+    telemetry: Option<String>,
+
+    /// Serve Prometheus metrics of the campaign on http://<addr>/metrics,
+    /// e.g., 0.0.0.0:9100
+    #[arg(long)]
+    metrics_addr: Option<String>,
+
     /// Only fuzz contracts with the addresses provided, separated by comma
     #[arg(long, default_value = "")]
     only_fuzz: String,
 
+    /// Names of harness contracts, separated by comma, whose `invariant_*()`
+    /// functions are checked after each transaction (enabling the invariant
+    /// oracle) while their other functions are not fuzzed
+    #[arg(long, default_value = "")]
+    invariant_harness: String,
+
+    /// Signatures of the events whose emission violates a property, separated
+    /// by comma, e.g., `InvariantViolated(string)` (enabling the event oracle)
+    #[arg(long, default_value = "")]
+    event_properties: String,
+
+    /// Paths of the oracle plugins (shared libraries exporting the oracle ABI
+    /// of `plugins/oracle_template`), separated by comma
+    #[arg(long, default_value = "")]
+    oracle_plugins: String,
+
+    /// Python script registering oracles and calldata processors (needs the
+    /// `python` feature)
+    #[arg(long, default_value = "")]
+    python_script: String,
+
+    /// Echidna config file (YAML), enabling the echidna oracle. Its `sender`,
+    /// `maxTimeDelay`, `maxBlockDelay` and `testLimit` options are supported.
+    #[arg(long)]
+    echidna_config: Option<String>,
+
+    /// Senders of the transactions, replacing the default ones, separated by
+    /// comma. Each is <address>[:<balance>][:<role>], e.g.,
+    /// 0x…:100ether:owner, the role being attacker (default) or owner, who
+    /// also deploys the contracts
+    #[arg(long, default_value = "")]
+    senders: String,
+
+    /// Ranges of the ETH value of the calls, separated by comma. Each is
+    /// [<function>=]<min>..<max> in wei or with the ether suffix, e.g.,
+    /// 0..1ether,deposit=1ether..10ether
+    #[arg(long, default_value = "")]
+    call_values: String,
+
+    /// Scan the onchain storage of the targets for the holders of their
+    /// Ownable, AccessControl and proxy admin roles and add them to the
+    /// senders. Findings needing them are tagged as requiring a privileged
+    /// sender
+    #[arg(long, default_value = "false")]
+    impersonate_owners: bool,
+
     /// Only needed when using combined.json (source map info).
     /// This is the base path when running solc compile (--base-path passed to
     /// solc). Also, please convert it to absolute path if you are not sure.
@@ -269,6 +603,31 @@ pub struct EvmArgs {
     #[arg(long, default_value = "")]
     load_corpus: String,
 
+    /// Exchange testcases and branch coverage with other instances fuzzing
+    /// the same target through a shared store: a directory, a rsync remote
+    /// (host:path), a S3 URL (s3://bucket/prefix) or a HTTP URL
+    #[arg(long)]
+    corpus_sync: Option<String>,
+
+    /// Seconds between two synchronizations with the corpus sync store
+    #[arg(long, default_value = "60")]
+    corpus_sync_interval: u64,
+
+    /// Name of this instance in the corpus sync store (Default: hostname and
+    /// process id)
+    #[arg(long)]
+    corpus_sync_node: Option<String>,
+
+    /// Run a fuzzer instance on each of the given cores (e.g., 0-3,8),
+    /// exchanging testcases through the work dir (Default: a single
+    /// instance)
+    #[arg(long)]
+    cores: Option<String>,
+
+    /// Pin this instance to the given core, set by the launcher
+    #[arg(long, hide = true)]
+    bind_core: Option<usize>,
+
     /// [DEPRECATED] Specify the setup file that deploys all the contract.
     /// Fuzzer invokes setUp() to deploy.
     #[arg(long, default_value = "")]
@@ -310,32 +669,83 @@ impl fmt::Display for EvmArgs {
         write!(f, "    proxy_address: {},\n", self.proxy_address)?;
         write!(f, "    constructor_args: {},\n", self.constructor_args)?;
         write!(f, "    target_type: {:?},\n", self.target_type)?;
+        write!(f, "    foundry_dir: {:?},\n", self.foundry_dir)?;
+        write!(f, "    hardhat_dir: {:?},\n", self.hardhat_dir)?;
+        write!(f, "    broadcast: {:?},\n", self.broadcast)?;
         write!(f, "    chain_type: {:?},\n", self.chain_type)?;
         write!(f, "    onchain_block_number: {:?},\n", self.onchain_block_number)?;
         write!(f, "    onchain_url: {:?},\n", self.onchain_url)?;
+        write!(f, "    public_rpc_fallback: {},\n", self.public_rpc_fallback)?;
         write!(f, "    onchain_chain_id: {:?},\n", self.onchain_chain_id)?;
         write!(f, "    onchain_explorer_url: {:?},\n", self.onchain_explorer_url)?;
         write!(f, "    onchain_chain_name: {:?},\n", self.onchain_chain_name)?;
+        write!(f, "    chain_registry: {},\n", self.chain_registry)?;
         write!(
             f,
             "    onchain_etherscan_api_key: {:?},\n",
             self.onchain_etherscan_api_key
         )?;
+        write!(f, "    no_signature_lookup: {},\n", self.no_signature_lookup)?;
         write!(f, "    onchain_storage_fetching: {},\n", self.onchain_storage_fetching)?;
+        write!(f, "    cache_dir: {},\n", self.cache_dir)?;
+        write!(f, "    fork_backend: {:?},\n", self.fork_backend)?;
+        write!(f, "    rpc_budget: {:?},\n", self.rpc_budget)?;
+        write!(f, "    watch_upgrades: {},\n", self.watch_upgrades)?;
         write!(f, "    concolic: {},\n", self.concolic)?;
         write!(f, "    concolic_caller: {},\n", self.concolic_caller)?;
         write!(f, "    concolic_timeout: {},\n", self.concolic_timeout)?;
         write!(f, "    concolic_num_threads: {},\n", self.concolic_num_threads)?;
+        write!(f, "    redqueen: {},\n", self.redqueen)?;
         write!(f, "    flashloan: {},\n", self.flashloan)?;
+        write!(f, "    nft_floor_prices: {},\n", self.nft_floor_prices)?;
+        write!(f, "    chainlink_feeds: {},\n", self.chainlink_feeds)?;
+        write!(f, "    poc_flashloan: {},\n", self.poc_flashloan)?;
+        write!(f, "    break_at: {},\n", self.break_at)?;
+        write!(f, "    slither: {},\n", self.slither)?;
+        write!(f, "    sig_to_score: {},\n", self.sig_to_score)?;
         write!(f, "    panic_on_bug: {},\n", self.panic_on_bug)?;
+        write!(f, "    determinism_check: {},\n", self.determinism_check)?;
         write!(f, "    detectors: {},\n", self.detectors)?;
+        write!(f, "    list_detectors: {},\n", self.list_detectors)?;
+        write!(f, "    min_profit: {},\n", self.min_profit)?;
+        write!(f, "    min_reserve_delta: {},\n", self.min_reserve_delta)?;
+        write!(f, "    max_exploit_gas: {},\n", self.max_exploit_gas)?;
+        write!(f, "    max_capital: {:?},\n", self.max_capital)?;
+        write!(f, "    numeraire: {},\n", self.numeraire)?;
+        write!(f, "    supply_whitelist: {},\n", self.supply_whitelist)?;
+        write!(f, "    assertion_errors: {},\n", self.assertion_errors)?;
+        write!(f, "    upgraded_code: {},\n", self.upgraded_code)?;
+        write!(f, "    compiler_variants: {},\n", self.compiler_variants)?;
         write!(f, "    replay_file: {:?},\n", self.replay_file)?;
+        write!(f, "    cmin_output: {:?},\n", self.cmin_output)?;
         write!(f, "    work_dir: {},\n", self.work_dir)?;
         write!(f, "    write_relationship: {},\n", self.write_relationship)?;
         write!(f, "    run_forever: {},\n", self.run_forever)?;
+        write!(f, "    max_execs: {:?},\n", self.max_execs)?;
+        write!(f, "    stop_on_coverage: {},\n", self.stop_on_coverage)?;
+        write!(f, "    stop_after_objectives: {:?},\n", self.stop_after_objectives)?;
+        write!(f, "    timeout: {},\n", self.timeout)?;
+        write!(f, "    checkpoint_interval: {:?},\n", self.checkpoint_interval)?;
+        write!(f, "    save_interval: {},\n", self.save_interval)?;
+        write!(f, "    resume: {},\n", self.resume)?;
+        write!(f, "    fuzz_constructor_args: {},\n", self.fuzz_constructor_args)?;
+        write!(f, "    harness_setup: {},\n", self.harness_setup)?;
+        write!(f, "    infer_interface: {},\n", self.infer_interface)?;
+        write!(f, "    victim_tx: {},\n", self.victim_tx)?;
         write!(f, "    seed: {},\n", self.seed)?;
         write!(f, "    sha3_bypass: {},\n", self.sha3_bypass)?;
+        write!(f, "    tui: {},\n", self.tui)?;
+        write!(f, "    telemetry: {:?},\n", self.telemetry)?;
+        write!(f, "    metrics_addr: {:?},\n", self.metrics_addr)?;
         write!(f, "    only_fuzz: {},\n", self.only_fuzz)?;
+        write!(f, "    invariant_harness: {},\n", self.invariant_harness)?;
+        write!(f, "    event_properties: {},\n", self.event_properties)?;
+        write!(f, "    oracle_plugins: {},\n", self.oracle_plugins)?;
+        write!(f, "    python_script: {},\n", self.python_script)?;
+        write!(f, "    echidna_config: {:?},\n", self.echidna_config)?;
+        write!(f, "    senders: {},\n", self.senders)?;
+        write!(f, "    call_values: {},\n", self.call_values)?;
+        write!(f, "    impersonate_owners: {},\n", self.impersonate_owners)?;
         write!(f, "    base_path: {},\n", self.base_path)?;
         write!(f, "    spec_id: {},\n", self.spec_id)?;
         write!(f, "    onchain_builder: {},\n", self.onchain_builder)?;
@@ -349,6 +759,11 @@ impl fmt::Display for EvmArgs {
         write!(f, "    offchain_config_url: {},\n", self.offchain_config_url)?;
         write!(f, "    offchain_config_file: {},\n", self.offchain_config_file)?;
         write!(f, "    load_corpus: {},\n", self.load_corpus)?;
+        write!(f, "    corpus_sync: {:?},\n", self.corpus_sync)?;
+        write!(f, "    corpus_sync_interval: {},\n", self.corpus_sync_interval)?;
+        write!(f, "    corpus_sync_node: {:?},\n", self.corpus_sync_node)?;
+        write!(f, "    cores: {:?},\n", self.cores)?;
+        write!(f, "    bind_core: {:?},\n", self.bind_core)?;
         write!(f, "    setup_file: {},\n", self.setup_file)?;
         write!(f, "    deployment_script: {},\n", self.deployment_script)?;
         write!(f, "    force_abi: {},\n", self.force_abi)?;
@@ -360,12 +775,61 @@ impl fmt::Display for EvmArgs {
     }
 }
 
+/// CLI for replaying transactions found by ItyFuzz against EVM smart contracts
+#[derive(Parser, Debug)]
+pub struct ReplayArgs {
+    /// Replayable file (e.g., work_dir/vulnerabilities/0_replayable) or glob
+    /// pattern of them, each transaction is re-executed and its full call
+    /// trace printed
+    #[arg(long)]
+    files: String,
+
+    /// Options of the campaign producing the files, the target has to be the
+    /// same
+    #[command(flatten)]
+    evm: EvmArgs,
+}
+
+pub fn replay_main(args: ReplayArgs) {
+    let mut evm = args.evm;
+    evm.replay_file = Some(args.files);
+    evm_main(evm);
+}
+
+/// CLI for minimizing a corpus of a previous EVM campaign, keeping the
+/// testcases needed to cover all the branches it covers
+#[derive(Parser, Debug)]
+pub struct CminArgs {
+    /// Corpus directory of the campaign (e.g., work_dir/corpus)
+    #[arg(long)]
+    input_dir: String,
+
+    /// Directory the minimized corpus is written to
+    #[arg(long)]
+    output_dir: String,
+
+    /// Options of the campaign producing the corpus, the target has to be the
+    /// same
+    #[command(flatten)]
+    evm: EvmArgs,
+}
+
+pub fn cmin_main(args: CminArgs) {
+    let mut evm = args.evm;
+    evm.replay_file = Some(format!("{}/*_replayable", args.input_dir.trim_end_matches('/')));
+    evm.cmin_output = Some(args.output_dir);
+    evm_main(evm);
+}
+
 enum EVMTargetType {
     Glob,
     Address,
     AnvilFork,
     Config,
     Setup,
+    Foundry,
+    Hardhat,
+    Broadcast,
 }
 
 impl EVMTargetType {
@@ -376,6 +840,9 @@ impl EVMTargetType {
             EVMTargetType::AnvilFork => "anvil_fork",
             EVMTargetType::Config => "config",
             EVMTargetType::Setup => "setup",
+            EVMTargetType::Foundry => "foundry",
+            EVMTargetType::Hardhat => "hardhat",
+            EVMTargetType::Broadcast => "broadcast",
         }
     }
 
@@ -386,6 +853,9 @@ impl EVMTargetType {
             "anvil_fork" => EVMTargetType::AnvilFork,
             "config" => EVMTargetType::Config,
             "setup" => EVMTargetType::Setup,
+            "foundry" => EVMTargetType::Foundry,
+            "hardhat" => EVMTargetType::Hardhat,
+            "broadcast" => EVMTargetType::Broadcast,
             _ => panic!("Invalid target type"),
         }
     }
@@ -402,10 +872,51 @@ enum OracleType {
     StateComparison,
     TypedBug,
     SelfDestruct,
+    Takeover,
     Invariant,
+    ERC4626,
+    TaintedCall,
+    Supply,
+    PriceManipulation,
+    ContractSize,
+    Assertion,
+    GasGriefing,
 }
 
 impl OracleType {
+    /// Every detector, in the order listed by `--list-detectors`
+    const ALL: [OracleType; 18] = [
+        OracleType::ERC20,
+        OracleType::Pair,
+        OracleType::Reentrancy,
+        OracleType::ArbitraryCall,
+        OracleType::MathCalculate,
+        OracleType::Echidna,
+        OracleType::StateComparison,
+        OracleType::TypedBug,
+        OracleType::SelfDestruct,
+        OracleType::Takeover,
+        OracleType::Invariant,
+        OracleType::ERC4626,
+        OracleType::TaintedCall,
+        OracleType::Supply,
+        OracleType::PriceManipulation,
+        OracleType::ContractSize,
+        OracleType::Assertion,
+        OracleType::GasGriefing,
+    ];
+
+    const HIGH_CONFIDENCE: [OracleType; 8] = [
+        OracleType::ERC20,
+        OracleType::Pair,
+        OracleType::ArbitraryCall,
+        OracleType::Echidna,
+        OracleType::TypedBug,
+        OracleType::SelfDestruct,
+        OracleType::Takeover,
+        OracleType::Invariant,
+    ];
+
     fn as_str(&self) -> &'static str {
         match self {
             OracleType::ERC20 => "erc20",
@@ -417,27 +928,71 @@ impl OracleType {
             OracleType::StateComparison => "state_comparison",
             OracleType::TypedBug => "typed_bug",
             OracleType::SelfDestruct => "selfdestruct",
+            OracleType::Takeover => "takeover",
             OracleType::Invariant => "invariant",
+            OracleType::ERC4626 => "erc4626",
+            OracleType::TaintedCall => "tainted_call",
+            OracleType::Supply => "supply",
+            OracleType::PriceManipulation => "price_manipulation",
+            OracleType::ContractSize => "contract_size",
+            OracleType::Assertion => "assertion",
+            OracleType::GasGriefing => "gas_griefing",
         }
     }
 
-    fn from_str(s: &str) -> Self {
-        match s {
-            "erc20" => OracleType::ERC20,
-            "pair" => OracleType::Pair,
-            "reentrancy" => OracleType::Reentrancy,
-            "arbitrary_call" => OracleType::ArbitraryCall,
-            "math_calculate" => OracleType::MathCalculate,
-            "echidna" => OracleType::Echidna,
-            "state_comparison" => OracleType::StateComparison,
-            "typed_bug" => OracleType::TypedBug,
-            "selfdestruct" => OracleType::SelfDestruct,
-            "invariant" => OracleType::Invariant,
-            _ => panic!("Invalid detector type: {}", s),
+    fn description(&self) -> &'static str {
+        match self {
+            OracleType::ERC20 => "funds drained with flashloans (alias erc20_drain)",
+            OracleType::Pair => "imbalanced Uniswap V2 pairs",
+            OracleType::Reentrancy => "storage read before and written after a reentrant call",
+            OracleType::ArbitraryCall => "external calls to an address controlled by the attacker",
+            OracleType::MathCalculate => "integer overflows and underflows",
+            OracleType::Echidna => "echidna_* property functions returning false",
+            OracleType::StateComparison => "states matching the desired state",
+            OracleType::TypedBug => "bugs reported by the contracts (bug(), typed_bug())",
+            OracleType::SelfDestruct => "contracts selfdestructed by the attacker",
+            OracleType::Takeover => "owners and EIP-1967 proxy slots set to an attacker address",
+            OracleType::Invariant => "invariant_* functions reverting, returning false or failing an assertion",
+            OracleType::ERC4626 => "ERC4626 vaults violating the share price invariants",
+            OracleType::TaintedCall => "calls whose target or data is controlled by the attacker",
+            OracleType::Supply => "totalSupply() changing without a call to a whitelisted mint or burn function",
+            OracleType::PriceManipulation => "prices read from manipulated pools",
+            OracleType::ContractSize => "contracts exceeding the size limits of EIP-170 and EIP-3860",
+            OracleType::Assertion => "failed assertions and the custom errors given with --assertion-errors",
+            OracleType::GasGriefing => {
+                "functions whose gas grows superlinearly with the length of their array arguments or of the arrays stored by the contract"
+            }
+        }
+    }
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.replace('-', "_").as_str() {
+            "erc20" | "erc20_drain" => Ok(OracleType::ERC20),
+            "pair" => Ok(OracleType::Pair),
+            "reentrancy" => Ok(OracleType::Reentrancy),
+            "arbitrary_call" => Ok(OracleType::ArbitraryCall),
+            "math_calculate" => Ok(OracleType::MathCalculate),
+            "echidna" => Ok(OracleType::Echidna),
+            "state_comparison" => Ok(OracleType::StateComparison),
+            "typed_bug" => Ok(OracleType::TypedBug),
+            "selfdestruct" => Ok(OracleType::SelfDestruct),
+            "takeover" => Ok(OracleType::Takeover),
+            "invariant" => Ok(OracleType::Invariant),
+            "erc4626" => Ok(OracleType::ERC4626),
+            "tainted_call" => Ok(OracleType::TaintedCall),
+            "supply" => Ok(OracleType::Supply),
+            "price_manipulation" => Ok(OracleType::PriceManipulation),
+            "contract_size" => Ok(OracleType::ContractSize),
+            "assertion" => Ok(OracleType::Assertion),
+            "gas_griefing" => Ok(OracleType::GasGriefing),
+            _ => Err(format!("unknown detector {}, see --list-detectors", s)),
         }
     }
 
-    fn from_strs(s: &str) -> Vec<Self> {
+    /// Parse the detectors separated by comma, `all` enabling every detector
+    /// but invariant, `high_confidence` the ones with few false positives and
+    /// `none` none of them
+    fn from_strs(s: &str) -> Result<Vec<Self>, String> {
         let mut results = Vec::new();
 
         for detector in s.split(',') {
@@ -446,39 +1001,46 @@ impl OracleType {
                 continue;
             }
 
-            if detector == "all" {
-                return vec![
-                    OracleType::ERC20,
-                    OracleType::Pair,
-                    OracleType::Reentrancy,
-                    OracleType::ArbitraryCall,
-                    OracleType::MathCalculate,
-                    OracleType::Echidna,
-                    OracleType::StateComparison,
-                    OracleType::TypedBug,
-                    OracleType::SelfDestruct,
-                ];
-            }
-            if detector == "high_confidence" {
-                return vec![
-                    OracleType::ERC20,
-                    OracleType::Pair,
-                    OracleType::ArbitraryCall,
-                    OracleType::Echidna,
-                    OracleType::TypedBug,
-                    OracleType::SelfDestruct,
-                    OracleType::Invariant,
-                ];
+            match detector {
+                "all" => return Ok(Self::ALL.into_iter().filter(|t| *t != OracleType::Invariant).collect()),
+                "high_confidence" => return Ok(Self::HIGH_CONFIDENCE.to_vec()),
+                "none" => return Ok(vec![]),
+                _ => results.push(OracleType::from_str(detector)?),
             }
+        }
+        Ok(results)
+    }
 
-            results.push(OracleType::from_str(detector));
+    /// Table of the detectors printed by `--list-detectors`
+    fn list() -> String {
+        let mut lines = vec![
+            "all                  every detector but invariant".to_string(),
+            "high_confidence      detectors with few false positives (default)".to_string(),
+            "none                 no detector".to_string(),
+        ];
+        for oracle_type in Self::ALL {
+            let default = if Self::HIGH_CONFIDENCE.contains(&oracle_type) {
+                " (default)"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "{:<20} {}{}",
+                oracle_type.as_str(),
+                oracle_type.description(),
+                default
+            ));
         }
-        results
+        lines.join("\n")
     }
 }
 
 #[allow(clippy::type_complexity)]
 pub fn evm_main(mut args: EvmArgs) {
+    if args.list_detectors {
+        println!("{}", OracleType::list());
+        return;
+    }
     args.setup_file = args.deployment_script;
     let target = args.target.clone();
     if !args.base_directory.is_empty() {
@@ -489,11 +1051,28 @@ pub fn evm_main(mut args: EvmArgs) {
     let work_path = Path::new(work_dir.as_str());
     let _ = std::fs::create_dir_all(work_path);
 
+    if let Some(cores) = &args.cores {
+        launcher::launch(cores, &work_dir, args.corpus_sync.is_some());
+    }
+    if let Some(core) = args.bind_core {
+        launcher::bind_core(core);
+    }
+
     let mut target_type: EVMTargetType = match args.target_type {
         Some(v) => EVMTargetType::from_str(v.as_str()),
         None => {
             // infer target type from args
-            if args.target.starts_with("0x") {
+            if args.broadcast.is_some() {
+                EVMTargetType::Broadcast
+            } else if args.foundry_dir.is_some() || Path::new(&args.target).join("foundry.toml").exists() {
+                EVMTargetType::Foundry
+            } else if args.hardhat_dir.is_some() ||
+                ["hardhat.config.js", "hardhat.config.ts"]
+                    .iter()
+                    .any(|config| Path::new(&args.target).join(config).exists())
+            {
+                EVMTargetType::Hardhat
+            } else if args.target.starts_with("0x") {
                 EVMTargetType::Address
             } else {
                 EVMTargetType::Glob
@@ -501,14 +1080,27 @@ pub fn evm_main(mut args: EvmArgs) {
         }
     };
 
+    if !args.chain_registry.is_empty() {
+        init_chain_registry(&args.chain_registry).expect("Invalid chain registry");
+    }
+
     let is_onchain = args.chain_type.is_some() || args.onchain_url.is_some();
 
     let mut onchain = if is_onchain {
         match args.chain_type {
             Some(chain_str) => {
-                let chain = Chain::from_str(&chain_str).expect("Invalid chain type");
                 let block_number = args.onchain_block_number.unwrap_or(0);
-                Some(OnChainConfig::new(chain, block_number))
+                // the public RPC of the chain is only used without user supplied endpoints,
+                // or as their fallback if opted in
+                Some(
+                    OnChainConfig::from_chain_name(
+                        &chain_str,
+                        block_number,
+                        args.onchain_url.as_deref(),
+                        args.public_rpc_fallback,
+                    )
+                    .expect("Invalid chain type"),
+                )
             }
             None => Some(OnChainConfig::new_raw(
                 args.onchain_url
@@ -526,7 +1118,8 @@ pub fn evm_main(mut args: EvmArgs) {
         None
     };
 
-    solution::init_cli_args(target, work_dir, &onchain);
+    let flashloan_providers = parse_flashloan_providers(&args.poc_flashloan).expect("Invalid PoC flashloan providers");
+    solution::init_cli_args(target, work_dir, &onchain, flashloan_providers);
     let _onchain_clone = onchain.clone();
 
     let etherscan_api_key = match args.onchain_etherscan_api_key {
@@ -534,12 +1127,35 @@ pub fn evm_main(mut args: EvmArgs) {
         None => std::env::var("ETHERSCAN_API_KEY").unwrap_or_default(),
     };
 
+    if let Some(onchain) = onchain.as_mut() {
+        onchain.set_cache_dir(&args.cache_dir);
+        onchain.signature_lookup = !args.no_signature_lookup;
+        if let Some(limit) = args.rpc_budget {
+            onchain.set_rpc_budget(limit);
+        }
+        if let Some(backend) = &args.fork_backend {
+            let kind = ForkBackendKind::from_str(backend).expect("Invalid fork backend");
+            onchain.use_fork_backend(kind).expect("failed to start fork backend");
+        }
+    }
+
     if onchain.is_some() && !etherscan_api_key.is_empty() {
         onchain.as_mut().unwrap().etherscan_api_key = etherscan_api_key.split(',').map(|s| s.to_string()).collect();
     }
     let erc20_producer = Rc::new(RefCell::new(ERC20Producer::new()));
 
-    let flashloan_oracle = Rc::new(RefCell::new(IERC20OracleFlashloan::new(erc20_producer.clone())));
+    let oracle_thresholds = OracleThresholds {
+        min_profit: args.min_profit,
+        native_usd: DEFAULT_NATIVE_PRICE_USD,
+        min_reserve_delta: EVMU256::from_str(&args.min_reserve_delta).expect("Invalid min reserve delta"),
+        max_gas: args.max_exploit_gas,
+        max_capital: args.max_capital,
+        numeraire: Default::default(),
+    };
+    let flashloan_oracle = Rc::new(RefCell::new(IERC20OracleFlashloan::new(
+        erc20_producer.clone(),
+        oracle_thresholds,
+    )));
 
     // let harness_code = "oracle_harness()";
     // let mut harness_hash: [u8; 4] = [0; 4];
@@ -595,10 +1211,10 @@ pub fn evm_main(mut args: EvmArgs) {
         >,
     > = vec![];
 
-    let oracle_types = OracleType::from_strs(args.detectors.as_str());
+    let oracle_types = OracleType::from_strs(args.detectors.as_str()).expect("Invalid detectors");
 
     if oracle_types.contains(&OracleType::Pair) {
-        oracles.push(Rc::new(RefCell::new(PairBalanceOracle::new())));
+        oracles.push(Rc::new(RefCell::new(PairBalanceOracle::new(oracle_thresholds))));
     }
 
     if oracle_types.contains(&OracleType::ERC20) {
@@ -714,9 +1330,26 @@ pub fn evm_main(mut args: EvmArgs) {
             Some(args.base_path.clone()),
         ),
         EVMTargetType::Config => ContractLoader::from_config(
-            &offchain_artifacts.expect("offchain artifacts is required for config target type"),
+            offchain_artifacts
+                .as_ref()
+                .expect("offchain artifacts is required for config target type"),
             &offchain_config.expect("offchain config is required for config target type"),
         ),
+        EVMTargetType::Foundry => ContractLoader::from_foundry(
+            args.foundry_dir.as_ref().unwrap_or(&args.target),
+            &mut state,
+            &constructor_args_map,
+        ),
+        EVMTargetType::Hardhat => ContractLoader::from_hardhat(
+            args.hardhat_dir.as_ref().unwrap_or(&args.target),
+            &mut state,
+            &constructor_args_map,
+        ),
+        EVMTargetType::Broadcast => ContractLoader::from_broadcast(
+            args.broadcast.as_ref().unwrap_or(&args.target),
+            args.work_dir.clone(),
+            &etherscan_api_key,
+        ),
         EVMTargetType::AnvilFork => {
             let addresses: Vec<EVMAddress> = args
                 .target
@@ -724,13 +1357,17 @@ pub fn evm_main(mut args: EvmArgs) {
                 .map(|s| EVMAddress::from_str(s).unwrap())
                 .collect();
             ContractLoader::from_fork(
-                &offchain_artifacts.expect("offchain artifacts is required for config target type"),
+                offchain_artifacts
+                    .as_ref()
+                    .expect("offchain artifacts is required for config target type"),
                 onchain.as_mut().expect("onchain is required to fork anvil"),
                 HashSet::from_iter(addresses),
             )
         }
         EVMTargetType::Setup => ContractLoader::from_setup(
-            &offchain_artifacts.expect("offchain artifacts is required for config target type"),
+            offchain_artifacts
+                .as_ref()
+                .expect("offchain artifacts is required for config target type"),
             args.setup_file,
             args.work_dir.clone(),
             &etherscan_api_key,
@@ -755,6 +1392,26 @@ pub fn evm_main(mut args: EvmArgs) {
 
     contract_loader.force_abi(force_abis);
 
+    let compiler_variants = parse_compiler_variants(&args.compiler_variants)
+        .iter()
+        .map(|flags| {
+            CompilerVariant::build(
+                &args.build_command.join(" "),
+                flags,
+                offchain_artifacts
+                    .as_ref()
+                    .expect("build command is required for compiler variants"),
+                &contract_loader.contracts,
+            )
+            .expect("Failed to build the compiler variant")
+        })
+        .collect_vec();
+
+    let echidna_config = args
+        .echidna_config
+        .as_ref()
+        .map(|path| EchidnaConfig::from_file(path).expect("failed to parse echidna config"));
+
     let config = Config {
         contract_loader,
         only_fuzz: if !args.only_fuzz.is_empty() {
@@ -776,9 +1433,23 @@ pub fn evm_main(mut args: EvmArgs) {
                 args.concolic_num_threads
             }
         },
+        redqueen: args.redqueen,
         oracle: oracles,
         producers,
         flashloan: args.flashloan,
+        nft_floor_prices: parse_floor_prices(&args.nft_floor_prices).expect("Invalid NFT floor prices"),
+        chainlink_feeds: parse_chainlink_feeds(&args.chainlink_feeds).expect("Invalid Chainlink feeds"),
+        breakpoints: parse_breakpoints(&args.break_at).expect("Invalid breakpoints"),
+        sig_to_score: if args.slither.is_empty() {
+            HashMap::new()
+        } else {
+            slither_hints(&args.slither).expect("Invalid Slither hints")
+        },
+        sig_to_score_file: if args.sig_to_score.is_empty() {
+            None
+        } else {
+            Some(args.sig_to_score.clone())
+        },
         onchain_storage_fetching: if is_onchain {
             Some(
                 StorageFetchingMode::from_str(args.onchain_storage_fetching.as_str())
@@ -788,17 +1459,70 @@ pub fn evm_main(mut args: EvmArgs) {
             None
         },
         replay_file: args.replay_file,
+        cmin_output: args.cmin_output,
         flashloan_oracle,
         selfdestruct_oracle: oracle_types.contains(&OracleType::SelfDestruct),
+        takeover_oracle: oracle_types.contains(&OracleType::Takeover),
+        erc20_oracle: oracle_types.contains(&OracleType::ERC20),
         reentrancy_oracle: oracle_types.contains(&OracleType::Reentrancy),
+        erc4626_oracle: oracle_types.contains(&OracleType::ERC4626),
+        tainted_call_oracle: oracle_types.contains(&OracleType::TaintedCall),
+        supply_oracle: oracle_types.contains(&OracleType::Supply),
+        price_manipulation_oracle: oracle_types.contains(&OracleType::PriceManipulation),
+        contract_size_oracle: oracle_types.contains(&OracleType::ContractSize),
+        assertion_oracle: oracle_types.contains(&OracleType::Assertion),
+        gas_griefing_oracle: oracle_types.contains(&OracleType::GasGriefing),
+        assertion_errors: parse_assertion_errors(&args.assertion_errors).expect("Invalid assertion errors"),
+        upgrades: parse_upgrades(&args.upgraded_code).expect("Invalid upgraded code"),
+        compiler_variants,
+        supply_whitelist: args
+            .supply_whitelist
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
         work_dir: args.work_dir.clone(),
         write_relationship: args.write_relationship,
         run_forever: args.run_forever,
+        max_execs: args
+            .max_execs
+            .or(echidna_config.as_ref().and_then(|config| config.test_limit)),
+        stop_conditions: StopConditions {
+            coverage: parse_coverage_target(&args.stop_on_coverage).expect("Invalid coverage target"),
+            objectives: args.stop_after_objectives,
+            timeout: parse_timeout(&args.timeout).expect("Invalid timeout"),
+        },
+        checkpoint_interval: args.checkpoint_interval,
+        save_interval: args.save_interval,
+        resume: args.resume,
+        numeraire: Numeraire::from_str(&args.numeraire).expect("Invalid numeraire"),
+        fuzz_constructor_args: args.fuzz_constructor_args,
+        harness_setup: args.harness_setup,
+        infer_interface: args.infer_interface,
+        victim_tx: (!args.victim_tx.is_empty()).then(|| parse_victim_tx(&args.victim_tx).expect("Invalid victim tx")),
+        custom_feedbacks: vec![],
         sha3_bypass: args.sha3_bypass,
+        tui: args.tui,
+        telemetry: args.telemetry.clone(),
+        metrics_addr: args.metrics_addr.clone(),
         base_path: args.base_path,
-        echidna_oracle: oracle_types.contains(&OracleType::Echidna),
-        invariant_oracle: oracle_types.contains(&OracleType::Invariant),
+        echidna_oracle: oracle_types.contains(&OracleType::Echidna) || echidna_config.is_some(),
+        echidna_config,
+        senders: parse_senders(&args.senders).expect("Invalid senders"),
+        call_value_ranges: parse_call_value_ranges(&args.call_values).expect("Invalid call values"),
+        impersonate_owners: args.impersonate_owners,
+        invariant_oracle: oracle_types.contains(&OracleType::Invariant) || !args.invariant_harness.is_empty(),
+        event_properties: parse_event_properties(&args.event_properties).expect("Invalid event properties"),
+        oracle_plugins: load_oracle_plugins(&args.oracle_plugins).expect("Invalid oracle plugins"),
+        python_script: args.python_script.clone(),
+        invariant_harness: args
+            .invariant_harness
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
         panic_on_bug: args.panic_on_bug,
+        determinism_check: args.determinism_check,
         spec_id: args.spec_id,
         typed_bug: oracle_types.contains(&OracleType::TypedBug),
         arbitrary_external_call: oracle_types.contains(&OracleType::ArbitraryCall),
@@ -811,6 +1535,10 @@ pub fn evm_main(mut args: EvmArgs) {
         #[cfg(feature = "use_presets")]
         preset_file_path: args.preset_file_path,
         load_corpus: args.load_corpus,
+        corpus_sync: args.corpus_sync,
+        corpus_sync_interval: args.corpus_sync_interval,
+        watch_upgrades: args.watch_upgrades,
+        corpus_sync_node: args.corpus_sync_node.unwrap_or_else(corpus_sync::default_node_name),
         etherscan_api_key,
     };
 
@@ -847,6 +1575,10 @@ fn test_evm_offchain_setup() {
         onchain_storage_fetching: String::from("onebyone"),
         concolic_timeout: 1000,
         detectors: String::from("high_confidence"),
+        min_profit: DEFAULT_MIN_PROFIT_USD,
+        min_reserve_delta: String::from("0"),
+        numeraire: String::from("native"),
+        max_exploit_gas: BLOCK_GAS_LIMIT,
         work_dir: String::from("work_dir"),
         seed: 1667840158231589000,
         spec_id: String::from("Latest"),
@@ -869,7 +1601,18 @@ fn test_evm_offchain_setup() {
 
     let erc20_producer = Rc::new(RefCell::new(ERC20Producer::new()));
 
-    let flashloan_oracle = Rc::new(RefCell::new(IERC20OracleFlashloan::new(erc20_producer.clone())));
+    let oracle_thresholds = OracleThresholds {
+        min_profit: args.min_profit,
+        native_usd: DEFAULT_NATIVE_PRICE_USD,
+        min_reserve_delta: EVMU256::from_str(&args.min_reserve_delta).expect("Invalid min reserve delta"),
+        max_gas: args.max_exploit_gas,
+        max_capital: args.max_capital,
+        numeraire: Default::default(),
+    };
+    let flashloan_oracle = Rc::new(RefCell::new(IERC20OracleFlashloan::new(
+        erc20_producer.clone(),
+        oracle_thresholds,
+    )));
 
     let mut oracles: Vec<
         Rc<
@@ -911,10 +1654,10 @@ fn test_evm_offchain_setup() {
         >,
     > = vec![];
 
-    let oracle_types = OracleType::from_strs(args.detectors.as_str());
+    let oracle_types = OracleType::from_strs(args.detectors.as_str()).expect("Invalid detectors");
 
     if oracle_types.contains(&OracleType::Pair) {
-        oracles.push(Rc::new(RefCell::new(PairBalanceOracle::new())));
+        oracles.push(Rc::new(RefCell::new(PairBalanceOracle::new(oracle_thresholds))));
     }
 
     if oracle_types.contains(&OracleType::ERC20) {
@@ -944,6 +1687,11 @@ fn test_evm_offchain_setup() {
         "",
     );
 
+    let echidna_config = args
+        .echidna_config
+        .as_ref()
+        .map(|path| EchidnaConfig::from_file(path).expect("failed to parse echidna config"));
+
     let config = Config {
         contract_loader,
         only_fuzz: HashSet::new(),
@@ -958,22 +1706,89 @@ fn test_evm_offchain_setup() {
                 args.concolic_num_threads
             }
         },
+        redqueen: args.redqueen,
         oracle: oracles,
         producers,
         flashloan: args.flashloan,
+        nft_floor_prices: parse_floor_prices(&args.nft_floor_prices).expect("Invalid NFT floor prices"),
+        chainlink_feeds: parse_chainlink_feeds(&args.chainlink_feeds).expect("Invalid Chainlink feeds"),
+        breakpoints: parse_breakpoints(&args.break_at).expect("Invalid breakpoints"),
+        sig_to_score: if args.slither.is_empty() {
+            HashMap::new()
+        } else {
+            slither_hints(&args.slither).expect("Invalid Slither hints")
+        },
+        sig_to_score_file: if args.sig_to_score.is_empty() {
+            None
+        } else {
+            Some(args.sig_to_score.clone())
+        },
         onchain_storage_fetching: None,
         replay_file: args.replay_file,
+        cmin_output: args.cmin_output,
         flashloan_oracle,
         selfdestruct_oracle: oracle_types.contains(&OracleType::SelfDestruct),
+        takeover_oracle: oracle_types.contains(&OracleType::Takeover),
+        erc20_oracle: oracle_types.contains(&OracleType::ERC20),
         reentrancy_oracle: oracle_types.contains(&OracleType::Reentrancy),
+        erc4626_oracle: oracle_types.contains(&OracleType::ERC4626),
+        tainted_call_oracle: oracle_types.contains(&OracleType::TaintedCall),
+        supply_oracle: oracle_types.contains(&OracleType::Supply),
+        price_manipulation_oracle: oracle_types.contains(&OracleType::PriceManipulation),
+        contract_size_oracle: oracle_types.contains(&OracleType::ContractSize),
+        assertion_oracle: oracle_types.contains(&OracleType::Assertion),
+        gas_griefing_oracle: oracle_types.contains(&OracleType::GasGriefing),
+        assertion_errors: parse_assertion_errors(&args.assertion_errors).expect("Invalid assertion errors"),
+        upgrades: parse_upgrades(&args.upgraded_code).expect("Invalid upgraded code"),
+        compiler_variants: vec![],
+        supply_whitelist: args
+            .supply_whitelist
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
         work_dir: args.work_dir.clone(),
         write_relationship: args.write_relationship,
         run_forever: args.run_forever,
+        max_execs: args
+            .max_execs
+            .or(echidna_config.as_ref().and_then(|config| config.test_limit)),
+        stop_conditions: StopConditions {
+            coverage: parse_coverage_target(&args.stop_on_coverage).expect("Invalid coverage target"),
+            objectives: args.stop_after_objectives,
+            timeout: parse_timeout(&args.timeout).expect("Invalid timeout"),
+        },
+        checkpoint_interval: args.checkpoint_interval,
+        save_interval: args.save_interval,
+        resume: args.resume,
+        numeraire: Numeraire::from_str(&args.numeraire).expect("Invalid numeraire"),
+        fuzz_constructor_args: args.fuzz_constructor_args,
+        harness_setup: args.harness_setup,
+        infer_interface: args.infer_interface,
+        victim_tx: (!args.victim_tx.is_empty()).then(|| parse_victim_tx(&args.victim_tx).expect("Invalid victim tx")),
+        custom_feedbacks: vec![],
         sha3_bypass: args.sha3_bypass,
+        tui: args.tui,
+        telemetry: args.telemetry.clone(),
+        metrics_addr: args.metrics_addr.clone(),
         base_path: args.base_path,
-        echidna_oracle: oracle_types.contains(&OracleType::Echidna),
-        invariant_oracle: oracle_types.contains(&OracleType::Invariant),
+        echidna_oracle: oracle_types.contains(&OracleType::Echidna) || echidna_config.is_some(),
+        echidna_config,
+        senders: parse_senders(&args.senders).expect("Invalid senders"),
+        call_value_ranges: parse_call_value_ranges(&args.call_values).expect("Invalid call values"),
+        impersonate_owners: args.impersonate_owners,
+        invariant_oracle: oracle_types.contains(&OracleType::Invariant) || !args.invariant_harness.is_empty(),
+        event_properties: parse_event_properties(&args.event_properties).expect("Invalid event properties"),
+        oracle_plugins: load_oracle_plugins(&args.oracle_plugins).expect("Invalid oracle plugins"),
+        python_script: args.python_script.clone(),
+        invariant_harness: args
+            .invariant_harness
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
         panic_on_bug: args.panic_on_bug,
+        determinism_check: args.determinism_check,
         spec_id: args.spec_id,
         typed_bug: oracle_types.contains(&OracleType::TypedBug),
         arbitrary_external_call: oracle_types.contains(&OracleType::ArbitraryCall),
@@ -986,6 +1801,10 @@ fn test_evm_offchain_setup() {
         #[cfg(feature = "use_presets")]
         preset_file_path: args.preset_file_path,
         load_corpus: args.load_corpus,
+        corpus_sync: args.corpus_sync,
+        corpus_sync_interval: args.corpus_sync_interval,
+        watch_upgrades: args.watch_upgrades,
+        corpus_sync_node: args.corpus_sync_node.unwrap_or_else(corpus_sync::default_node_name),
         etherscan_api_key: String::from(""),
     };
 
@@ -1015,3 +1834,55 @@ fn test_evm_offchain_setup() {
     utils::try_write_file(&abis_json, &json_str, true).unwrap();
     evm_fuzzer(config, &mut state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detectors() {
+        assert_eq!(
+            OracleType::from_strs("reentrancy, erc20-drain,selfdestruct"),
+            Ok(vec![
+                OracleType::Reentrancy,
+                OracleType::ERC20,
+                OracleType::SelfDestruct
+            ])
+        );
+        assert_eq!(OracleType::from_strs("none"), Ok(vec![]));
+        assert_eq!(OracleType::from_strs("all").unwrap().len(), OracleType::ALL.len() - 1);
+        assert!(OracleType::from_strs("reentrancy,unknown").is_err());
+        assert!(OracleType::list().contains("gas_griefing"));
+        // the takeovers are reported apart from the selfdestructs
+        assert!(OracleType::HIGH_CONFIDENCE.contains(&OracleType::Takeover));
+        assert_eq!(OracleType::from_strs("takeover"), Ok(vec![OracleType::Takeover]));
+    }
+
+    #[test]
+    fn test_detector_descriptions() {
+        // SupplyOracle: the supply changes without a whitelisted function
+        let supply = OracleType::Supply.description();
+        assert!(supply.contains("totalSupply()") && supply.contains("mint") && supply.contains("burn"));
+        // GasGriefingOracle: the gas grows superlinearly with the input size
+        let gas_griefing = OracleType::GasGriefing.description();
+        assert!(gas_griefing.contains("superlinearly") && gas_griefing.contains("stored"));
+        // InvariantOracle: Foundry-style violations
+        let invariant = OracleType::Invariant.description();
+        assert!(invariant.contains("reverting") && invariant.contains("false") && invariant.contains("assertion"));
+    }
+
+    #[test]
+    fn test_replay_args() {
+        let args = ReplayArgs::try_parse_from([
+            "replay",
+            "--files",
+            "work_dir/vulnerabilities/*_replayable",
+            "-t",
+            "tests/evm/reentrancy/*",
+        ])
+        .unwrap();
+        assert_eq!(args.files, "work_dir/vulnerabilities/*_replayable");
+        assert_eq!(args.evm.target, "tests/evm/reentrancy/*");
+        assert!(args.evm.build_command.is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, rc::Rc};
 
 use libafl::{
     inputs::Input,
@@ -11,24 +11,38 @@ use libafl::{
 use libafl_bolts::{prelude::Rand, Named};
 use revm_interpreter::Interpreter;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::debug;
 
-use super::onchain::flashloan::CAN_LIQUIDATE;
+use super::{
+    middlewares::chainlink::CHAINLINK_FEEDS,
+    onchain::flashloan::{clamp_to_max_capital, CAN_LIQUIDATE},
+};
 /// Mutator for EVM inputs
 use crate::evm::input::EVMInputT;
 use crate::{
     evm::{
-        abi::ABIAddressToInstanceMap,
-        input::EVMInputTy::Borrow,
+        abi::{ABIAddressToInstanceMap, BoxedABI},
+        bytecode_analyzer::CallOrderMetadata,
+        input::{
+            interesting_time_delta,
+            later_block,
+            EVMInputTy::{Borrow, Deploy, Victim},
+        },
         types::{convert_u256_to_h160, EVMAddress, EVMU256},
-        vm::{Constraint, EVMStateT},
+        vm::{BlockInfo, Constraint, EVMStateT},
     },
     generic_vm::vm_state::VMStateT,
     input::{ConciseSerde, VMInputT},
+    mutation_utils::sample_interesting_value,
     r#const::{
         ABI_MUTATE_CHOICE,
+        ADVANCE_BLOCK_CHOICE,
+        BLOCK_TIME_SECS,
+        CALL_ORDER_CHOICE,
         EXPLOIT_PRESET_CHOICE,
         HAVOC_CHOICE,
         HAVOC_MAX_ITERS,
+        INTERESTING_AMOUNT_CHOICE,
         LIQUIDATE_CHOICE,
         LIQ_PERCENT,
         LIQ_PERCENT_CHOICE,
@@ -100,6 +114,9 @@ impl AccessPattern {
     }
 }
 
+/// Rewrites the calldata of a mutated input
+pub type CalldataProcessor = Rc<dyn Fn(&[u8]) -> Vec<u8>>;
+
 /// [`FuzzMutator`] is a mutator that mutates the input based on the ABI and
 /// access pattern
 pub struct FuzzMutator<VS, Loc, Addr, SC, CI>
@@ -113,6 +130,9 @@ where
     /// Scheduler for selecting the next VM state to use if we decide to mutate
     /// the VM state of the input
     pub infant_scheduler: SC,
+    /// Rewrites the calldata of each mutated input (e.g., the calldata
+    /// processors of the python script)
+    pub calldata_processor: Option<CalldataProcessor>,
     pub phantom: std::marker::PhantomData<(VS, Loc, Addr, CI)>,
 }
 
@@ -128,10 +148,16 @@ where
     pub fn new(infant_scheduler: SC) -> Self {
         Self {
             infant_scheduler,
+            calldata_processor: None,
             phantom: Default::default(),
         }
     }
 
+    /// Process the calldata of each mutated input with `processor`
+    pub fn set_calldata_processor(&mut self, processor: CalldataProcessor) {
+        self.calldata_processor = Some(processor);
+    }
+
     fn ensures_constraint<I, S>(input: &mut I, state: &mut S, new_vm_state: &VS, constraints: Vec<Constraint>) -> bool
     where
         I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
@@ -141,12 +167,12 @@ where
         for constraint in &constraints {
             match constraint {
                 Constraint::MustStepNow => {
-                    if input.get_input_type() == Borrow {
+                    if matches!(input.get_input_type(), Borrow | Deploy) {
                         return false;
                     }
                 }
                 Constraint::Contract(_) => {
-                    if input.get_input_type() == Borrow {
+                    if matches!(input.get_input_type(), Borrow | Deploy) {
                         return false;
                     }
                 }
@@ -216,19 +242,21 @@ where
     }
 }
 
-impl<VS, Loc, Addr, I, S, SC, CI> Mutator<I, S> for FuzzMutator<VS, Loc, Addr, SC, CI>
+impl<VS, Loc, Addr, SC, CI> FuzzMutator<VS, Loc, Addr, SC, CI>
 where
-    I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
-    S: State + HasRand + HasMaxSize + HasItyState<Loc, Addr, VS, CI> + HasCaller<Addr> + HasMetadata + HasPresets,
     SC: Scheduler<State = InfantStateState<Loc, Addr, VS, CI>>,
     VS: Default + VMStateT + EVMStateT,
     Addr: PartialEq + Debug + Serialize + DeserializeOwned + Clone,
     Loc: Serialize + DeserializeOwned + Debug + Clone,
     CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
 {
-    /// Mutate the input
+    /// Mutate the input, before the calldata is processed
     #[allow(unused_assignments)]
-    fn mutate(&mut self, state: &mut S, input: &mut I, _stage_idx: i32) -> Result<MutationResult, Error> {
+    fn mutate_input<I, S>(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error>
+    where
+        I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
+        S: State + HasRand + HasMaxSize + HasItyState<Loc, Addr, VS, CI> + HasCaller<Addr> + HasMetadata + HasPresets,
+    {
         // if the VM state of the input is not initialized, swap it with a state
         // initialized
         if !input.get_staged_state().initialized {
@@ -236,10 +264,22 @@ where
             input.set_staged_state(concrete.1, concrete.0);
         }
 
+        // the pending transaction of the victim is replayed as signed, only the
+        // state it runs on (i.e., the front-run leading to it) changes
+        if input.get_input_type() == Victim {
+            let old_idx = input.get_state_idx();
+            let (idx, new_state) = state.get_infant_state(&mut self.infant_scheduler).unwrap();
+            if idx == old_idx || new_state.state.has_post_execution() {
+                return Ok(MutationResult::Skipped);
+            }
+            input.set_staged_state(new_state, idx);
+            return Ok(MutationResult::Mutated);
+        }
+
         // use exploit template
         if state.has_preset() && state.rand_mut().below(MUTATOR_SAMPLE_MAX) < EXPLOIT_PRESET_CHOICE {
             // if flashloan_v2, we don't mutate if it's a borrow
-            if input.get_input_type() != Borrow {
+            if !matches!(input.get_input_type(), Borrow | Deploy) {
                 match state.get_next_call() {
                     Some((addr, abi)) => {
                         input.set_contract_and_abi(addr, Some(abi));
@@ -252,6 +292,31 @@ where
                 }
             }
         }
+
+        // follow call order hints, i.e., call a function likely enabled by the
+        // last transaction leading to the VM state of the input
+        if !matches!(input.get_input_type(), Borrow | Deploy) &&
+            !input.is_step() &&
+            state.rand_mut().below(MUTATOR_SAMPLE_MAX) < CALL_ORDER_CHOICE
+        {
+            let last_function = input.get_staged_state().state.get_last_function();
+            if let Some((addr, abi)) = next_ordered_call(state, last_function) {
+                input.set_contract_and_abi(addr, Some(abi));
+                input.mutate(state);
+                return Ok(MutationResult::Mutated);
+            }
+        }
+        // start a new block before the input, a step resuming a control leak
+        // being in the block of the transaction it resumes
+        if input.get_input_type() != Deploy &&
+            !input.is_step() &&
+            state.rand_mut().below(MUTATOR_SAMPLE_MAX) < ADVANCE_BLOCK_CHOICE
+        {
+            let last_block = input.get_staged_state().state.get_last_block();
+            if advance_block(input, state, last_block) == MutationResult::Mutated {
+                return Ok(MutationResult::Mutated);
+            }
+        }
         // determine whether we should conduct havoc
         // (a sequence of mutations in batch vs single mutation)
         // let mut amount_of_args = input.get_data_abi().map(|abi|
@@ -288,6 +353,7 @@ where
             }
 
             if input.get_staged_state().state.has_post_execution() &&
+                input.get_input_type() != Deploy &&
                 !input.is_step() &&
                 state.rand_mut().below(MUTATOR_SAMPLE_MAX) < TURN_TO_STEP_CHOICE
             {
@@ -319,19 +385,7 @@ where
                     0..=LIQUIDATE_CHOICE => {
                         // only when there are more than one liquidation path, we attempt to liquidate
                         if unsafe { CAN_LIQUIDATE } {
-                            let prev_percent = input.get_liquidation_percent();
-                            input.set_liquidation_percent(if state.rand_mut().below(MUTATOR_SAMPLE_MAX) <
-                                LIQ_PERCENT_CHOICE
-                            {
-                                LIQ_PERCENT
-                            } else {
-                                0
-                            } as u8);
-                            if prev_percent != input.get_liquidation_percent() {
-                                MutationResult::Mutated
-                            } else {
-                                MutationResult::Skipped
-                            }
+                            mutate_liquidation(input, state)
                         } else {
                             MutationResult::Skipped
                         }
@@ -345,13 +399,9 @@ where
             // if the input is to borrow token, we should mutate the randomness
             // (use to select the paths to buy token), VM state, and bytes
             if input.get_input_type() == Borrow {
-                let rand_u8 = state.rand_mut().below(256) as u8;
                 return match state.rand_mut().below(MUTATOR_SAMPLE_MAX) {
-                    0..=RANDOMNESS_CHOICE => {
-                        // mutate the randomness
-                        input.set_randomness(vec![rand_u8; 1]);
-                        MutationResult::Mutated
-                    }
+                    0..=RANDOMNESS_CHOICE => mutate_randomness(input, state, 0),
+                    RANDOMNESS_CHOICE..=INTERESTING_AMOUNT_CHOICE => mutate_amount_in(input, state),
                     // mutate the bytes
                     _ => input.mutate(state),
                 };
@@ -360,23 +410,10 @@ where
             // mutate the bytes or VM state or liquidation percent (percentage of token to
             // liquidate) by default
             match state.rand_mut().below(MUTATOR_SAMPLE_MAX) {
-                0..=LIQUIDATE_CHOICE => {
-                    let prev_percent = input.get_liquidation_percent();
-                    input.set_liquidation_percent(if state.rand_mut().below(MUTATOR_SAMPLE_MAX) < LIQ_PERCENT_CHOICE {
-                        LIQ_PERCENT
-                    } else {
-                        0
-                    } as u8);
-                    if prev_percent != input.get_liquidation_percent() {
-                        MutationResult::Mutated
-                    } else {
-                        MutationResult::Skipped
-                    }
-                }
+                0..=LIQUIDATE_CHOICE => mutate_liquidation(input, state),
                 LIQUIDATE_CHOICE..=RANDOMNESS_CHOICE_2 => {
-                    let rand_u8 = state.rand_mut().below(256) as u8;
-                    input.set_randomness(vec![rand_u8; 1]);
-                    MutationResult::Mutated
+                    let idx = state.rand_mut().below(1 + unsafe { CHAINLINK_FEEDS } as u64) as usize;
+                    mutate_randomness(input, state, idx)
                 }
                 _ => input.mutate(state),
             }
@@ -402,3 +439,231 @@ where
         Ok(res)
     }
 }
+
+impl<VS, Loc, Addr, I, S, SC, CI> Mutator<I, S> for FuzzMutator<VS, Loc, Addr, SC, CI>
+where
+    I: VMInputT<VS, Loc, Addr, CI> + Input + EVMInputT,
+    S: State + HasRand + HasMaxSize + HasItyState<Loc, Addr, VS, CI> + HasCaller<Addr> + HasMetadata + HasPresets,
+    SC: Scheduler<State = InfantStateState<Loc, Addr, VS, CI>>,
+    VS: Default + VMStateT + EVMStateT,
+    Addr: PartialEq + Debug + Serialize + DeserializeOwned + Clone,
+    Loc: Serialize + DeserializeOwned + Debug + Clone,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
+{
+    /// Mutate the input, then process its calldata so that the input stored in
+    /// the corpus and reported is the one executed
+    fn mutate(&mut self, state: &mut S, input: &mut I, _stage_idx: i32) -> Result<MutationResult, Error> {
+        let res = self.mutate_input(state, input)?;
+        if res == MutationResult::Mutated &&
+            !input.is_step() &&
+            !matches!(input.get_input_type(), Borrow | Victim) &&
+            let Some(processor) = &self.calldata_processor
+        {
+            let calldata = input.to_bytes();
+            if !calldata.is_empty() && !input.set_calldata(&processor(&calldata)) {
+                debug!("the processed calldata does not decode with the ABI of the input, dropped");
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Mutate how much of the tokens the input liquidates (in tenths) and through
+/// which swap path
+fn mutate_liquidation<I, S>(input: &mut I, state: &mut S) -> MutationResult
+where
+    I: EVMInputT,
+    S: HasRand,
+{
+    let prev = (input.get_liquidation_percent(), input.get_liquidation_path());
+    if state.rand_mut().below(MUTATOR_SAMPLE_MAX) < LIQ_PERCENT_CHOICE {
+        // selling everything is the most common exit, so keep it likely
+        let percent = if state.rand_mut().below(2) == 0 {
+            LIQ_PERCENT
+        } else {
+            state.rand_mut().below(LIQ_PERCENT) + 1
+        };
+        input.set_liquidation_percent(percent as u8);
+        input.set_liquidation_path(state.rand_mut().below(256) as u8);
+    } else {
+        input.set_liquidation_percent(0);
+    }
+    if prev != (input.get_liquidation_percent(), input.get_liquidation_path()) {
+        MutationResult::Mutated
+    } else {
+        MutationResult::Skipped
+    }
+}
+
+/// Randomize the `idx`-th byte of the randomness of the input. The first one
+/// selects the token paths and the branches of the bypassed hashes, each of
+/// the following ones the answer of a mocked Chainlink feed.
+fn mutate_randomness<I, S>(input: &mut I, state: &mut S, idx: usize) -> MutationResult
+where
+    I: EVMInputT,
+    S: HasRand,
+{
+    let mut randomness = input.get_randomness();
+    if randomness.len() <= idx {
+        randomness.resize(idx + 1, 0);
+    }
+    randomness[idx] = state.rand_mut().below(256) as u8;
+    input.set_randomness(randomness);
+    MutationResult::Mutated
+}
+
+/// Set the ETH the input spends buying tokens to a value of the interesting
+/// values pool, e.g., the reserve of a pair or a threshold compared against
+fn mutate_amount_in<I, S>(input: &mut I, state: &mut S) -> MutationResult
+where
+    I: EVMInputT,
+    S: HasRand + HasMetadata,
+{
+    let Some(amount_in) = sample_interesting_value(state).map(clamp_to_max_capital) else {
+        return MutationResult::Skipped;
+    };
+    if input.get_txn_value() == Some(amount_in) {
+        return MutationResult::Skipped;
+    }
+    input.set_txn_value(amount_in);
+    MutationResult::Mutated
+}
+
+/// Move the input to a block after `last_block`, the one of its VM state, built
+/// by another validator: the next block (e.g., for a TWAP to observe the price
+/// of the previous transaction) or the one after a duration time-locked logic
+/// is likely to check (e.g., for a vesting to unlock)
+fn advance_block<I, S>(input: &mut I, state: &mut S, last_block: Option<BlockInfo>) -> MutationResult
+where
+    I: EVMInputT,
+    S: HasRand,
+{
+    let block = &input.get_vm_env().block;
+    let base = last_block.unwrap_or(BlockInfo {
+        timestamp: block.timestamp,
+        number: block.number,
+        basefee: block.basefee,
+    });
+    let secs = if state.rand_mut().below(2) == 0 {
+        BLOCK_TIME_SECS
+    } else {
+        interesting_time_delta(state)
+    };
+    let later = later_block(state, base, secs);
+    if later.number == base.number {
+        return MutationResult::Skipped;
+    }
+    let mut coinbase = [0u8; 20];
+    coinbase.iter_mut().for_each(|b| *b = state.rand_mut().below(256) as u8);
+
+    let block = &mut input.get_vm_env_mut().block;
+    block.timestamp = later.timestamp;
+    block.number = later.number;
+    block.basefee = later.basefee;
+    block.coinbase = EVMAddress::from_slice(&coinbase);
+    MutationResult::Mutated
+}
+
+/// Pick a function that follows the last called function according to
+/// [`CallOrderMetadata`]
+fn next_ordered_call<S>(state: &mut S, last_function: Option<(EVMAddress, [u8; 4])>) -> Option<(EVMAddress, BoxedABI)>
+where
+    S: HasMetadata + HasRand,
+{
+    let (addr, function) = last_function?;
+    let successors = state
+        .metadata_map()
+        .get::<CallOrderMetadata>()?
+        .successors
+        .get(&function)?
+        .clone();
+    let next = successors[state.rand_mut().below(successors.len() as u64) as usize];
+    state
+        .metadata_map()
+        .get::<ABIAddressToInstanceMap>()?
+        .map
+        .get(&addr)?
+        .iter()
+        .find(|abi| abi.function == next)
+        .map(|abi| (addr, abi.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap};
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{
+        evm::{
+            input::{ConciseEVMInput, EVMInput, EVMInputTy},
+            types::EVMFuzzState,
+        },
+        mutation_utils::add_interesting_values,
+        r#const::BASEFEE_MAX_CHANGE_PERMILLE,
+        state::FuzzState,
+        state_input::StagedVMState,
+    };
+
+    #[test]
+    fn test_mutate_amount_in() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut input = ConciseEVMInput::default().to_input(Default::default()).0;
+        input.input_type = EVMInputTy::Borrow;
+        input.txn_value = Some(EVMU256::from(1));
+        // nothing harvested yet
+        assert_eq!(mutate_amount_in(&mut input, &mut state), MutationResult::Skipped);
+
+        let reserve = EVMU256::from(123_456_789);
+        add_interesting_values(&mut state, [EVMU256::from(1), reserve]);
+        for _ in 0..10 {
+            mutate_amount_in(&mut input, &mut state);
+            let amount_in = input.get_txn_value().unwrap();
+            assert!(amount_in >= reserve - EVMU256::from(1) && amount_in <= reserve + EVMU256::from(1));
+        }
+    }
+
+    #[test]
+    fn test_advance_block() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut input = EVMInput {
+            caller: EVMAddress::zero(),
+            contract: EVMAddress::zero(),
+            data: None,
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            input_type: EVMInputTy::ABI,
+            direct_data: Bytes::new(),
+            randomness: vec![0],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        };
+        let last = BlockInfo {
+            timestamp: EVMU256::from(1_000_000),
+            number: EVMU256::from(100),
+            basefee: EVMU256::from(1000),
+        };
+        for _ in 0..100 {
+            if advance_block(&mut input, &mut state, Some(last)) == MutationResult::Skipped {
+                continue;
+            }
+            // a later block, as many blocks after as the time elapsed takes
+            let block = &input.get_vm_env().block;
+            let secs = (block.timestamp - last.timestamp).to::<u64>();
+            assert!(block.number > last.number);
+            assert_eq!(
+                block.number - last.number,
+                EVMU256::from(secs.div_ceil(BLOCK_TIME_SECS))
+            );
+            let max_change = last.basefee * EVMU256::from(BASEFEE_MAX_CHANGE_PERMILLE) / EVMU256::from(1000);
+            assert!(block.basefee >= last.basefee - max_change && block.basefee <= last.basefee + max_change);
+        }
+    }
+}
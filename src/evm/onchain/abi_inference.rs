@@ -0,0 +1,337 @@
+//! Interface of the contracts without ABI (e.g., closed-source onchain
+//! contracts): the selectors and arguments are decompiled by evmole, and the
+//! arguments are cross-checked by probing how the ABI decoder of each
+//! function, which reverts without data on malformed calldata, handles
+//! calldata of different lengths and words. The probes only replace the
+//! decompiled arguments when the head of the calldata they take is wrong, as
+//! they do not see tuples nor nested arrays.
+
+use tracing::debug;
+
+use crate::evm::{
+    contract_utils::ABIConfig,
+    onchain::abi_decompiler::fetch_abi_evmole,
+    types::{EVMAddress, EVMU256},
+};
+
+/// Maximum number of head words of the arguments probed
+const MAX_HEAD_WORDS: usize = 8;
+
+/// Whether the call got past the ABI decoder
+fn is_decoded((output, success): (Vec<u8>, bool)) -> bool {
+    success || !output.is_empty()
+}
+
+/// Call the function with the args `words`, returning whether they were
+/// decoded
+fn probe(call: &mut impl FnMut(Vec<u8>) -> (Vec<u8>, bool), selector: [u8; 4], words: &[EVMU256]) -> bool {
+    let calldata = words.iter().fold(selector.to_vec(), |mut data, word| {
+        data.extend(word.to_be_bytes::<32>());
+        data
+    });
+    is_decoded(call(calldata))
+}
+
+/// Values probing the static types, from the most general type accepting
+/// them, along with the type and the array of the type
+fn static_candidates() -> [(EVMU256, &'static str, &'static str); 4] {
+    [
+        (EVMU256::MAX, "uint256", "uint256[]"),
+        (EVMU256::from_be_slice(&[0xff; 20]), "address", "address[]"),
+        (EVMU256::from(0xff), "uint8", "uint8[]"),
+        (EVMU256::from(1), "bool", "bool[]"),
+    ]
+}
+
+/// Type of the static arg at `idx` of the head `base`, the most general one
+/// among those its decoder accepts
+fn static_type(
+    call: &mut impl FnMut(Vec<u8>) -> (Vec<u8>, bool),
+    selector: [u8; 4],
+    base: &[EVMU256],
+    idx: usize,
+) -> &'static str {
+    for (value, ty, _) in static_candidates() {
+        let mut words = base.to_vec();
+        words[idx] = value;
+        if probe(call, selector, &words) {
+            return ty;
+        }
+    }
+    "uint256"
+}
+
+/// Type of the dynamic arg at `idx` of the head `base` (followed by a zero
+/// word), None if it is neither bytes nor an array of static words (e.g., an
+/// array of tuples)
+fn dynamic_type(
+    call: &mut impl FnMut(Vec<u8>) -> (Vec<u8>, bool),
+    selector: [u8; 4],
+    base: &[EVMU256],
+    idx: usize,
+) -> Option<&'static str> {
+    let mut with_tail = |tail: &[EVMU256]| {
+        let mut words = base.to_vec();
+        words[idx] = EVMU256::from(32 * base.len());
+        words.extend(tail);
+        probe(call, selector, &words)
+    };
+    // 33 bytes take 2 words, while 33 elements take 33 words
+    if with_tail(&[EVMU256::from(33), EVMU256::ZERO, EVMU256::ZERO]) {
+        return Some("bytes");
+    }
+    // an element taking more than a word is not decoded
+    static_candidates()
+        .into_iter()
+        .find(|(value, _, _)| with_tail(&[EVMU256::from(1), *value]))
+        .map(|(_, _, array)| array)
+}
+
+/// Number of head words of the args of the function `selector`, the
+/// shortest zero calldata its decoder accepts, None if it does not check the
+/// calldata (e.g., it always reverts)
+fn head_words(call: &mut impl FnMut(Vec<u8>) -> (Vec<u8>, bool), selector: [u8; 4]) -> Option<usize> {
+    (0..=MAX_HEAD_WORDS).find(|words| probe(call, selector, &vec![EVMU256::ZERO; *words]))
+}
+
+/// Infer the args of the function `selector` by probing it with `call`, None
+/// if its decoder does not check the calldata or an arg is not inferred
+pub fn infer_arguments(
+    call: &mut impl FnMut(Vec<u8>) -> (Vec<u8>, bool),
+    selector: [u8; 4],
+) -> Option<Vec<&'static str>> {
+    // the decoder reverts on calldata shorter than the head of the args
+    let head_words = head_words(call, selector)?;
+    if head_words == 0 {
+        return None;
+    }
+    // a dynamic arg is an offset, accepted when pointing to the trailing zero
+    // word (an empty array) and not past the calldata
+    let tail = EVMU256::from(32 * head_words);
+    let past = tail + EVMU256::from(32);
+    let all_tail = [vec![tail; head_words], vec![EVMU256::ZERO]].concat();
+    let mut dynamic = vec![false; head_words];
+    if probe(call, selector, &all_tail) {
+        for (idx, is_dynamic) in dynamic.iter_mut().enumerate() {
+            let mut words = all_tail.clone();
+            words[idx] = past;
+            *is_dynamic = !probe(call, selector, &words);
+        }
+    } else {
+        // some static args reject the offset (e.g., a bool), so the head is
+        // zero and the dynamic args found point to the trailing word, the
+        // others reading their length from the head until they are found
+        let mut base = vec![EVMU256::ZERO; head_words + 1];
+        loop {
+            let mut changed = false;
+            for (idx, is_dynamic) in dynamic.iter_mut().enumerate() {
+                if *is_dynamic {
+                    continue;
+                }
+                let mut words = base.clone();
+                words[idx] = tail;
+                if !probe(call, selector, &words) {
+                    continue;
+                }
+                words[idx] = past;
+                if !probe(call, selector, &words) {
+                    *is_dynamic = true;
+                    base[idx] = tail;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    let base = (0..=head_words)
+        .map(|idx| {
+            if dynamic.get(idx) == Some(&true) {
+                tail
+            } else {
+                EVMU256::ZERO
+            }
+        })
+        .collect::<Vec<_>>();
+    (0..head_words)
+        .map(|idx| {
+            if dynamic[idx] {
+                dynamic_type(call, selector, &base, idx)
+            } else {
+                Some(static_type(call, selector, &base, idx))
+            }
+        })
+        .collect()
+}
+
+/// Top-level types of the args `abi` (e.g., `(uint256,(bytes,address))`)
+fn split_types(abi: &str) -> Option<Vec<&str>> {
+    let inner = abi.strip_prefix('(')?.strip_suffix(')')?;
+    if inner.is_empty() {
+        return Some(vec![]);
+    }
+    let mut types = vec![];
+    let (mut depth, mut start) = (0usize, 0);
+    for (idx, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                types.push(&inner[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    types.push(&inner[start..]);
+    Some(types)
+}
+
+/// Number of head words taken by the type `ty` and whether it is dynamic
+fn type_words(ty: &str) -> Option<(usize, bool)> {
+    if let Some(array) = ty.strip_suffix(']') {
+        let (element, len) = array.rsplit_once('[')?;
+        let (words, dynamic) = type_words(element)?;
+        if len.is_empty() || dynamic {
+            return Some((1, true));
+        }
+        return Some((words * len.parse::<usize>().ok()?, false));
+    }
+    if ty.starts_with('(') {
+        let (words, dynamic) = args_words(ty)?;
+        return Some(if dynamic { (1, true) } else { (words, false) });
+    }
+    match ty {
+        "" => None,
+        "bytes" | "string" => Some((1, true)),
+        _ => Some((1, false)),
+    }
+}
+
+/// Number of head words of the args `abi` and whether one of them is
+/// dynamic, None if they do not parse
+fn args_words(abi: &str) -> Option<(usize, bool)> {
+    split_types(abi)?
+        .into_iter()
+        .try_fold((0, false), |(words, dynamic), ty| {
+            let (ty_words, ty_dynamic) = type_words(ty)?;
+            Some((words + ty_words, dynamic || ty_dynamic))
+        })
+}
+
+/// Args of the function `selector`, the decompiled ones unless their head
+/// does not match the calldata its decoder accepts, in which case they are
+/// inferred by probing with `call`. The decompiled args are kept when the
+/// probes are inconclusive, as the probes do not see tuples
+fn cross_check(call: &mut impl FnMut(Vec<u8>) -> (Vec<u8>, bool), selector: [u8; 4], decompiled: String) -> String {
+    let Some(words) = head_words(call, selector) else {
+        return decompiled;
+    };
+    if args_words(&decompiled).is_some_and(|(decompiled_words, _)| decompiled_words == words) {
+        return decompiled;
+    }
+    match infer_arguments(call, selector) {
+        Some(args) => format!("({})", args.join(",")),
+        None => decompiled,
+    }
+}
+
+/// ABI of the contract with code `bytecode` deployed at `address`, decompiled
+/// and cross-checked by probing with `call`
+pub fn infer_abi(
+    address: EVMAddress,
+    bytecode: String,
+    mut call: impl FnMut(Vec<u8>) -> (Vec<u8>, bool),
+) -> Vec<ABIConfig> {
+    fetch_abi_evmole(bytecode)
+        .into_iter()
+        .map(|mut abi| {
+            let args = cross_check(&mut call, abi.function, abi.abi.clone());
+            if args != abi.abi {
+                debug!(
+                    "inferred args of {:?}::0x{}: {} instead of {}",
+                    address, abi.function_name, args, abi.abi
+                );
+                abi.abi = args;
+            }
+            abi
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decoder of `f(uint256,bytes,address)` as generated by solc
+    fn decoder(calldata: Vec<u8>) -> (Vec<u8>, bool) {
+        let args = &calldata[4..];
+        let word = |offset: usize| EVMU256::from_be_slice(&args[offset..offset + 32]);
+        if args.len() < 96 || word(64) > EVMU256::from_be_slice(&[0xff; 20]) {
+            return (vec![], false);
+        }
+        let offset = word(32);
+        if offset.saturating_add(EVMU256::from(32)) > EVMU256::from(args.len()) {
+            return (vec![], false);
+        }
+        let offset = offset.to::<usize>();
+        let len = word(offset);
+        (
+            vec![],
+            len.saturating_add(EVMU256::from(offset + 32)) <= EVMU256::from(args.len()),
+        )
+    }
+
+    #[test]
+    fn test_infer_arguments() {
+        let mut call = decoder;
+        assert_eq!(
+            infer_arguments(&mut call, [0x12, 0x34, 0x56, 0x78]),
+            Some(vec!["uint256", "bytes", "address"])
+        );
+        // a function always reverting is left to the decompiler
+        let mut call = |_| (vec![], false);
+        assert_eq!(infer_arguments(&mut call, [0x12, 0x34, 0x56, 0x78]), None);
+    }
+
+    /// Decoder of `g((uint256,uint256)[])` as generated by solc
+    fn tuple_array_decoder(calldata: Vec<u8>) -> (Vec<u8>, bool) {
+        let args = &calldata[4..];
+        let word = |offset: usize| EVMU256::from_be_slice(&args[offset..offset + 32]);
+        if args.len() < 32 || word(0).saturating_add(EVMU256::from(32)) > EVMU256::from(args.len()) {
+            return (vec![], false);
+        }
+        let offset = word(0).to::<usize>();
+        let len = word(offset);
+        (
+            vec![],
+            len.saturating_mul(EVMU256::from(64))
+                .saturating_add(EVMU256::from(offset + 32)) <=
+                EVMU256::from(args.len()),
+        )
+    }
+
+    #[test]
+    fn test_cross_check() {
+        assert_eq!(args_words("()"), Some((0, false)));
+        assert_eq!(args_words("(uint256,(address,uint8),bytes[2])"), Some((4, true)));
+        assert_eq!(args_words("((uint256,bytes),uint256[3])"), Some((4, true)));
+
+        let selector = [0x12, 0x34, 0x56, 0x78];
+        let mut call = decoder;
+        // the decompiled args are kept when their head matches
+        let decompiled = "(uint256,(uint256,bytes),address)".to_string();
+        assert_eq!(cross_check(&mut call, selector, decompiled.clone()), decompiled);
+        assert_eq!(
+            cross_check(&mut call, selector, "()".to_string()),
+            "(uint256,bytes,address)"
+        );
+
+        // the probes do not see the tuples
+        let mut call = tuple_array_decoder;
+        assert_eq!(infer_arguments(&mut call, selector), None);
+        assert_eq!(cross_check(&mut call, selector, "()".to_string()), "()");
+    }
+}
@@ -0,0 +1,197 @@
+//! Chains defined in a TOML file given with `--chain-registry`, so that
+//! fuzzing on a chain that is not built in [`Chain`] does not need source
+//! edits, e.g.:
+//!
+//! ```toml
+//! [[chains]]
+//! name = "mantle"
+//! chain_id = 5000
+//! rpc = "https://rpc.mantle.xyz"
+//! explorer = "https://api.mantlescan.xyz/api"
+//! weth = "0x78c1b0c915c4faa5fffa6cabf0219da63d7f4cb8"
+//! pegged_tokens = { USDT = "0x201eba5cc46d216ce6dc03f6a759e8e766e956ae" }
+//!
+//! [[chains.dexes]]
+//! name = "v2_mantle"
+//! factory = "0x..."
+//! # optional
+//! router = "0x..."
+//! init_code_hash = "0x..."
+//! fee = 30
+//! ```
+//!
+//! The DEXes are Uniswap V2 forks. Those of a built-in chain are added to the
+//! ones known for it.
+//!
+//! [`Chain`]: super::endpoints::Chain
+
+use std::{collections::HashMap, fs, str::FromStr, sync::OnceLock};
+
+use serde::Deserialize;
+
+use super::keccak256;
+use crate::evm::types::{EVMAddress, EVMU256};
+
+/// Chains loaded from the registry
+static CHAIN_REGISTRY: OnceLock<Vec<CustomChain>> = OnceLock::new();
+
+fn default_fee() -> u32 {
+    30
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct CustomDex {
+    /// Name of the DEX, used as the `src_exact` of its pairs
+    pub name: String,
+    pub factory: String,
+    #[serde(default)]
+    pub router: Option<String>,
+    /// Hash of the init code of the pairs, so that their address is computed
+    /// instead of being queried from the factory
+    #[serde(default)]
+    pub init_code_hash: Option<String>,
+    /// Swap fee, in basis points
+    #[serde(default = "default_fee")]
+    pub fee: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct CustomChain {
+    /// Name given to `--chain-type`
+    pub name: String,
+    pub chain_id: u32,
+    pub rpc: String,
+    /// Etherscan compatible API of the block explorer
+    #[serde(default)]
+    pub explorer: String,
+    /// Wrapped native token
+    pub weth: String,
+    /// Tokens the flashloans are priced with, by symbol
+    #[serde(default)]
+    pub pegged_tokens: HashMap<String, String>,
+    #[serde(default)]
+    pub dexes: Vec<CustomDex>,
+}
+
+#[derive(Deserialize)]
+struct ChainRegistry {
+    #[serde(default)]
+    chains: Vec<CustomChain>,
+}
+
+impl CustomDex {
+    /// Address of the pair of the two tokens, None without the init code hash
+    pub fn pair_address(&self, token_a: EVMAddress, token_b: EVMAddress) -> Option<EVMAddress> {
+        let init_code_hash = hex::decode(self.init_code_hash.as_ref()?.trim_start_matches("0x")).ok()?;
+        let factory = EVMAddress::from_str(&self.factory).ok()?;
+        Some(pair_address(factory, &init_code_hash, token_a, token_b))
+    }
+}
+
+/// CREATE2 address of a Uniswap V2 pair, deployed by `factory` with the tokens
+/// sorted as the salt
+pub fn pair_address(
+    factory: EVMAddress,
+    init_code_hash: &[u8],
+    token_a: EVMAddress,
+    token_b: EVMAddress,
+) -> EVMAddress {
+    let (token0, token1) = if token_a < token_b {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    };
+    let salt = keccak256(&[token0.0, token1.0].concat());
+    let preimage: [&[u8]; 4] = [&[0xff], &factory.0, &salt.to_be_bytes::<32>(), init_code_hash];
+    let hash = keccak256(&preimage.concat());
+    EVMAddress::from_slice(&hash.to_be_bytes::<32>()[12..])
+}
+
+/// Parse the content of the registry and check its addresses
+pub fn parse_chain_registry(content: &str) -> Result<Vec<CustomChain>, String> {
+    let registry: ChainRegistry = toml::from_str(content).map_err(|e| format!("invalid chain registry: {}", e))?;
+    for chain in &registry.chains {
+        let addresses = chain.pegged_tokens.values().chain([&chain.weth]).chain(
+            chain
+                .dexes
+                .iter()
+                .flat_map(|dex| [Some(&dex.factory), dex.router.as_ref()].into_iter().flatten()),
+        );
+        for address in addresses {
+            if EVMAddress::from_str(address).is_err() {
+                return Err(format!("invalid address {} of chain {}", address, chain.name));
+            }
+        }
+        for dex in &chain.dexes {
+            if let Some(hash) = &dex.init_code_hash {
+                if EVMU256::from_str_radix(hash.trim_start_matches("0x"), 16).is_err() {
+                    return Err(format!("invalid init code hash {} of {}", hash, dex.name));
+                }
+            }
+        }
+    }
+    Ok(registry.chains)
+}
+
+/// Load the registry at `path`, once for the whole process
+pub fn init_chain_registry(path: &str) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("failed to read chain registry {}: {}", path, e))?;
+    let chains = parse_chain_registry(&content)?;
+    CHAIN_REGISTRY
+        .set(chains)
+        .map_err(|_| "chain registry is already loaded".to_string())
+}
+
+pub fn get_custom_chain(name: &str) -> Option<&'static CustomChain> {
+    CHAIN_REGISTRY.get()?.iter().find(|chain| chain.name == name)
+}
+
+/// DEX whose pairs have `src_exact` as the source
+pub fn get_custom_dex(src_exact: &str) -> Option<&'static CustomDex> {
+    CHAIN_REGISTRY
+        .get()?
+        .iter()
+        .flat_map(|chain| &chain.dexes)
+        .find(|dex| dex.name == src_exact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chain_registry() {
+        let chains = parse_chain_registry(
+            r#"
+            [[chains]]
+            name = "l2"
+            chain_id = 12345
+            rpc = "https://rpc.l2.xyz"
+            weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"
+
+            [[chains.dexes]]
+            name = "uniswapv2_l2"
+            factory = "0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f"
+            init_code_hash = "0x96e8ac4277198ff8b6f785478aa9a39f403cb768dd02cbee326c3e7da348845f"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].chain_id, 12345);
+        let dex = &chains[0].dexes[0];
+        assert_eq!(dex.fee, 30);
+
+        // USDC / WETH of Uniswap V2
+        let usdc = EVMAddress::from_str("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").unwrap();
+        let weth = EVMAddress::from_str(&chains[0].weth).unwrap();
+        let pair = EVMAddress::from_str("0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc").unwrap();
+        assert_eq!(dex.pair_address(weth, usdc), Some(pair));
+        assert_eq!(dex.pair_address(usdc, weth), Some(pair));
+
+        assert!(
+            parse_chain_registry("[[chains]]\nname = \"l2\"\nchain_id = 1\nrpc = \"\"\nweth = \"0x12\"\n")
+                .unwrap_err()
+                .starts_with("invalid address 0x12")
+        );
+    }
+}
@@ -5,27 +5,43 @@ use std::{
     hash::{Hash, Hasher},
     panic,
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
 use itertools::Itertools;
-use reqwest::{blocking, header::HeaderMap};
+use reqwest::{blocking, header::HeaderMap, StatusCode};
 use retry::{delay::Fixed, retry_with_index, OperationResult};
 use revm_interpreter::analysis::to_analysed;
-use revm_primitives::{Bytecode, B160};
+use revm_primitives::{Bytecode, Env, B160};
 use serde::Deserialize;
 use serde_json::{json, Value};
 use tracing::{debug, error, info, warn};
 
-use super::ChainConfig;
+use super::{
+    chains::{get_custom_chain, get_custom_dex},
+    fork_backend::{ForkBackend, ForkBackendKind},
+    keccak256,
+    provider::{FailoverProvider, RpcBudget, RpcProvider},
+    ChainConfig,
+};
 use crate::{
     cache::{Cache, FileSystemCache},
     evm::{
         tokens::TokenContext,
-        types::{EVMAddress, EVMU256},
+        types::{convert_u256_to_h160, EVMAddress, EVMU256},
+    },
+    r#const::{
+        ARBITRUM_TX_GAS_LIMIT,
+        BLOCK_GAS_LIMIT,
+        DEFAULT_CACHE_DIR,
+        SIGNATURE_LOOKUP_TIMEOUT_SECS,
+        ZKSYNC_TX_GAS_LIMIT,
     },
 };
 
@@ -49,6 +65,7 @@ pub enum Chain {
     ZkevmTestnet,
     BLAST,
     LINEA,
+    ZKSYNC,
     LOCAL,
 }
 
@@ -81,6 +98,7 @@ impl FromStr for Chain {
             "zkevm_testnet" => Ok(Self::ZkevmTestnet),
             "blast" => Ok(Self::BLAST),
             "linea" => Ok(Self::LINEA),
+            "zksync" => Ok(Self::ZKSYNC),
             "local" => Ok(Self::LOCAL),
             _ => Err(()),
         }
@@ -107,7 +125,14 @@ impl Chain {
         // Use rpc_url instead of the default one
         env::set_var("ETH_RPC_URL", rpc_url);
 
-        Ok(match chain_id {
+        u32::try_from(chain_id)
+            .ok()
+            .and_then(Self::from_chain_id)
+            .ok_or_else(|| anyhow!("Unknown chain id: {}", chain_id))
+    }
+
+    pub fn from_chain_id(chain_id: u32) -> Option<Self> {
+        Some(match chain_id {
             1 => Self::ETH,
             5 => Self::GOERLI,
             11155111 => Self::SEPOLIA,
@@ -126,8 +151,9 @@ impl Chain {
             1442 => Self::ZkevmTestnet,
             81457 => Self::BLAST,
             59144 => Self::LINEA,
+            324 => Self::ZKSYNC,
             31337 => Self::LOCAL,
-            _ => return Err(anyhow!("Unknown chain id: {}", chain_id)),
+            _ => return None,
         })
     }
 
@@ -151,6 +177,7 @@ impl Chain {
             Chain::ZkevmTestnet => 1442,
             Chain::BLAST => 81457,
             Chain::LINEA => 59144,
+            Chain::ZKSYNC => 324,
             Chain::LOCAL => 31337,
         }
     }
@@ -175,6 +202,7 @@ impl Chain {
             Chain::ZkevmTestnet => "zkevm_testnet",
             Chain::BLAST => "blast",
             Chain::LINEA => "linea",
+            Chain::ZKSYNC => "zksync",
             Chain::LOCAL => "local",
         }
         .to_string()
@@ -203,6 +231,7 @@ impl Chain {
             Chain::ZkevmTestnet => "https://rpc.ankr.com/polygon_zkevm_testnet",
             Chain::BLAST => "https://rpc.ankr.com/blast",
             Chain::LINEA => "https://rpc.ankr.com/linea",
+            Chain::ZKSYNC => "https://mainnet.era.zksync.io",
             Chain::LOCAL => "http://localhost:8545",
         }
         .to_string()
@@ -228,10 +257,199 @@ impl Chain {
             Chain::ZkevmTestnet => "https://api-testnet-zkevm.polygonscan.com/api",
             Chain::BLAST => "https://api.routescan.io/v2/network/mainnet/evm/81457/etherscan",
             Chain::LINEA => "https://api.lineascan.build/api",
+            Chain::ZKSYNC => "https://api-era.zksync.network/api",
             Chain::LOCAL => "http://localhost:8080/abi/",
         }
         .to_string()
     }
+
+    /// Execution profile of the chain, see [`ChainProfile`]
+    pub fn get_profile(&self) -> ChainProfile {
+        use MockValue::*;
+        let precompiles = match self {
+            // https://docs.arbitrum.io/build-decentralized-apps/precompiles/reference
+            Chain::ARBITRUM => vec![
+                MockedPrecompile::new(
+                    "ArbSys",
+                    "0x0000000000000000000000000000000000000064",
+                    &[
+                        ("arbBlockNumber()", &[BlockNumber]),
+                        ("arbBlockHash(uint256)", &[CalldataHash]),
+                        ("arbChainID()", &[ChainId]),
+                        ("isTopLevelCall()", &[Const(EVMU256::from(1))]),
+                        ("wasMyCallersAddressAliased()", &[Const(EVMU256::ZERO)]),
+                        ("sendTxToL1(address,bytes)", &[Const(EVMU256::ZERO)]),
+                        ("withdrawEth(address)", &[Const(EVMU256::ZERO)]),
+                    ],
+                ),
+                MockedPrecompile::new(
+                    "ArbGasInfo",
+                    "0x000000000000000000000000000000000000006c",
+                    &[
+                        (
+                            "getPricesInWei()",
+                            &[
+                                Const(EVMU256::ZERO),
+                                Const(EVMU256::ZERO),
+                                Const(EVMU256::ZERO),
+                                GasPrice,
+                                Const(EVMU256::ZERO),
+                                GasPrice,
+                            ],
+                        ),
+                        ("getL1BaseFeeEstimate()", &[BaseFee]),
+                        ("getCurrentTxL1GasFees()", &[Const(EVMU256::ZERO)]),
+                    ],
+                ),
+            ],
+            // the system contracts reading the environment, the contracts
+            // themselves have to be compiled to the EVM to be executed
+            Chain::ZKSYNC => vec![
+                MockedPrecompile::new(
+                    "SystemContext",
+                    "0x000000000000000000000000000000000000800b",
+                    &[
+                        ("chainId()", &[ChainId]),
+                        ("origin()", &[Origin]),
+                        ("gasPrice()", &[GasPrice]),
+                        ("blockGasLimit()", &[GasLimit]),
+                        ("coinbase()", &[Coinbase]),
+                        ("getBlockNumber()", &[BlockNumber]),
+                        ("getBlockTimestamp()", &[Timestamp]),
+                        ("baseFee()", &[BaseFee]),
+                        ("difficulty()", &[Const(EVMU256::from(2500000000000000u64))]),
+                    ],
+                ),
+                MockedPrecompile::new(
+                    "L2BaseToken",
+                    "0x000000000000000000000000000000000000800a",
+                    &[("balanceOf(uint256)", &[BalanceOfArg])],
+                ),
+                MockedPrecompile::new(
+                    "NonceHolder",
+                    "0x0000000000000000000000000000000000008003",
+                    &[
+                        ("getMinNonce(address)", &[Const(EVMU256::ZERO)]),
+                        ("getRawNonce(address)", &[Const(EVMU256::ZERO)]),
+                    ],
+                ),
+                MockedPrecompile::new(
+                    "L1Messenger",
+                    "0x0000000000000000000000000000000000008008",
+                    &[("sendToL1(bytes)", &[CalldataHash])],
+                ),
+            ],
+            _ => vec![],
+        };
+        let tx_gas_limit = match self {
+            Chain::ARBITRUM => ARBITRUM_TX_GAS_LIMIT,
+            // the gas of zkSync Era pays for the pubdata as well
+            Chain::ZKSYNC => ZKSYNC_TX_GAS_LIMIT,
+            _ => BLOCK_GAS_LIMIT,
+        };
+        ChainProfile {
+            precompiles: precompiles.into_iter().map(|p| (p.address, p)).collect(),
+            tx_gas_limit,
+        }
+    }
+}
+
+/// Word returned by a mocked precompile, resolved against the environment of
+/// the call
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockValue {
+    Const(EVMU256),
+    BlockNumber,
+    Timestamp,
+    BaseFee,
+    GasLimit,
+    GasPrice,
+    ChainId,
+    Origin,
+    Coinbase,
+    /// Balance of the address given as the first argument
+    BalanceOfArg,
+    /// `keccak256` of the arguments, e.g., for the block hashes
+    CalldataHash,
+}
+
+/// Contract executed natively by the nodes of a chain, e.g., ArbSys, so that
+/// there is no bytecode to fetch and execute
+#[derive(Clone, Debug)]
+pub struct MockedPrecompile {
+    pub name: &'static str,
+    pub address: EVMAddress,
+    /// Words returned by the functions, the calls to other functions revert
+    pub functions: HashMap<[u8; 4], Vec<MockValue>>,
+}
+
+impl MockedPrecompile {
+    pub fn new(name: &'static str, address: &str, functions: &[(&str, &[MockValue])]) -> Self {
+        Self {
+            name,
+            address: EVMAddress::from_str(address).unwrap(),
+            functions: functions
+                .iter()
+                .map(|(sig, values)| {
+                    let hash = keccak256(sig.as_bytes()).to_be_bytes::<32>();
+                    ([hash[0], hash[1], hash[2], hash[3]], values.to_vec())
+                })
+                .collect(),
+        }
+    }
+
+    /// Output of the call, None if the function is not mocked
+    pub fn call(&self, input: &[u8], env: &Env, balance: impl Fn(EVMAddress) -> EVMU256) -> Option<Vec<u8>> {
+        if input.len() < 4 {
+            return None;
+        }
+        let (selector, args) = input.split_at(4);
+        let values = self.functions.get(selector)?;
+        let address_value = |address: EVMAddress| EVMU256::from_be_slice(&address.0);
+        let mut output = Vec::with_capacity(values.len() * 32);
+        for value in values {
+            let word = match value {
+                MockValue::Const(v) => *v,
+                MockValue::BlockNumber => env.block.number,
+                MockValue::Timestamp => env.block.timestamp,
+                MockValue::BaseFee => env.block.basefee,
+                MockValue::GasLimit => env.block.gas_limit,
+                MockValue::GasPrice => env.tx.gas_price,
+                MockValue::ChainId => EVMU256::from(env.cfg.chain_id),
+                MockValue::Origin => address_value(env.tx.caller),
+                MockValue::Coinbase => address_value(env.block.coinbase),
+                MockValue::BalanceOfArg => {
+                    let mut arg = [0u8; 32];
+                    let len = args.len().min(32);
+                    arg[..len].copy_from_slice(&args[..len]);
+                    balance(convert_u256_to_h160(EVMU256::from_be_bytes(arg)))
+                }
+                MockValue::CalldataHash => keccak256(args),
+            };
+            output.extend_from_slice(&word.to_be_bytes::<32>());
+        }
+        Some(output)
+    }
+}
+
+/// How a chain executes differently from Ethereum: the precompiles executed
+/// natively by its nodes (returning its L1 or gas pricing data), which have no
+/// bytecode to fetch, and the gas a transaction can use. The predeploys with a
+/// bytecode (e.g., L1Block of the OP stack) are executed as any contract
+#[derive(Clone, Debug)]
+pub struct ChainProfile {
+    pub precompiles: HashMap<EVMAddress, MockedPrecompile>,
+    /// Maximum gas of a transaction, the ones using more are infeasible
+    pub tx_gas_limit: u64,
+}
+
+impl Default for ChainProfile {
+    fn default() -> Self {
+        Self {
+            precompiles: HashMap::new(),
+            tx_gas_limit: BLOCK_GAS_LIMIT,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -279,6 +497,12 @@ pub struct GetPairResponseDataPairToken {
 pub struct OnChainConfig {
     pub endpoint_url: String,
     pub client: reqwest::blocking::Client,
+    /// Provider sending the RPC requests, over `endpoint_url` by default
+    pub rpc_provider: Option<Arc<dyn RpcProvider + Send + Sync>>,
+    /// Local fork node the requests go to, kept alive as long as the config
+    pub fork_backend: Option<Arc<ForkBackend>>,
+    /// RPC requests and credits consumed, shared by the clones of the config
+    pub rpc_budget: Arc<RpcBudget>,
     pub chain_id: u32,
     pub block_number: String,
     pub timestamp: Option<String>,
@@ -291,6 +515,12 @@ pub struct OnChainConfig {
 
     pub chain_name: String,
 
+    /// Whether the ABIs and signatures of the unverified contracts are looked
+    /// up on Sourcify and 4byte.directory
+    pub signature_lookup: bool,
+    /// Set once a lookup service is unreachable, skipping the next lookups
+    lookup_unreachable: Arc<AtomicBool>,
+
     balance_cache: HashMap<EVMAddress, EVMU256>,
     pair_cache: HashMap<EVMAddress, Vec<PairData>>,
     slot_cache: HashMap<(EVMAddress, EVMU256), EVMU256>,
@@ -300,6 +530,9 @@ pub struct OnChainConfig {
     abi_cache: HashMap<EVMAddress, Option<String>>,
     storage_dump_cache: HashMap<EVMAddress, Option<Arc<HashMap<EVMU256, EVMU256>>>>,
     uniswap_path_cache: HashMap<EVMAddress, TokenContext>,
+    /// Directory of the persistent RPC cache, which is namespaced by chain and
+    /// block so that a block change never hits stale responses
+    cache_dir: String,
     rpc_cache: FileSystemCache,
 }
 
@@ -307,6 +540,9 @@ impl Debug for OnChainConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("OnChainConfig")
             .field("endpoint_url", &self.endpoint_url)
+            .field("rpc_provider", &self.rpc_provider)
+            .field("fork_backend", &self.fork_backend)
+            .field("rpc_budget", &self.rpc_budget)
             .field("chain_id", &self.chain_id)
             .field("block_number", &self.block_number)
             .field("timestamp", &self.timestamp)
@@ -316,6 +552,7 @@ impl Debug for OnChainConfig {
             .field("etherscan_api_key", &self.etherscan_api_key)
             .field("etherscan_base", &self.etherscan_base)
             .field("chain_name", &self.chain_name)
+            .field("signature_lookup", &self.signature_lookup)
             .field("balance_cache", &self.balance_cache)
             .field("pair_cache", &self.pair_cache)
             .field("slot_cache", &self.slot_cache)
@@ -324,6 +561,7 @@ impl Debug for OnChainConfig {
             .field("abi_cache", &self.abi_cache)
             .field("storage_dump_cache", &self.storage_dump_cache)
             .field("uniswap_path_cache", &self.uniswap_path_cache)
+            .field("cache_dir", &self.cache_dir)
             .field("rpc_cache", &self.rpc_cache)
             .finish()
     }
@@ -352,6 +590,18 @@ impl ChainConfig for OnChainConfig {
         self.get_token_balance(token, address)
     }
 
+    fn get_curve_coins(&mut self, pool: EVMAddress) -> Vec<EVMAddress> {
+        self.get_curve_coins(pool)
+    }
+
+    fn get_token_decimals(&mut self, token: EVMAddress) -> u8 {
+        self.get_token_decimals(token)
+    }
+
+    fn get_factory_pairs(&mut self, token: &str, base: &str) -> Vec<PairData> {
+        self.get_factory_pairs(token, base)
+    }
+
     fn get_weth(&self) -> String {
         let pegged_token = self.get_pegged_token();
 
@@ -361,10 +611,13 @@ impl ChainConfig for OnChainConfig {
             "polygon" => return pegged_token.get("WMATIC").unwrap().to_string(),
             "local" => return pegged_token.get("ZERO").unwrap().to_string(),
             // "mumbai" => panic!("Not supported"),
-            _ => {
-                warn!("Unknown network");
-                "".to_string()
-            }
+            name => match get_custom_chain(name) {
+                Some(chain) => chain.weth.clone(),
+                None => {
+                    warn!("Unknown network");
+                    "".to_string()
+                }
+            },
         }
     }
 
@@ -409,15 +662,62 @@ impl ChainConfig for OnChainConfig {
                 .iter()
                 .map(|(k, v)| (k.to_string(), v.to_string()))
                 .collect(),
-            _ => {
-                warn!("[Flashloan] Network is not supported");
-                HashMap::new()
-            }
+            name => match get_custom_chain(name) {
+                Some(chain) => {
+                    let mut pegged_token = chain.pegged_tokens.clone();
+                    pegged_token.entry("WETH".to_string()).or_insert(chain.weth.clone());
+                    pegged_token
+                }
+                None => {
+                    warn!("[Flashloan] Network is not supported");
+                    HashMap::new()
+                }
+            },
         }
     }
 }
 
+/// Endpoints of a chain whose public RPC is `rpc`: the `urls` of the user if
+/// any, only failing over to the public RPC if `public_fallback` is set
+fn endpoint_urls(urls: Option<&str>, rpc: String, public_fallback: bool) -> String {
+    match urls {
+        Some(urls) if public_fallback => format!("{},{}", urls, rpc),
+        Some(urls) => urls.to_string(),
+        None => rpc,
+    }
+}
+
 impl OnChainConfig {
+    /// Config of a built-in chain or of one of the chain registry (see
+    /// [`super::chains`]), requests going to `urls` if given instead of the
+    /// public RPC of the chain, which they fail over to if `public_fallback`
+    pub fn from_chain_name(name: &str, block_number: u64, urls: Option<&str>, public_fallback: bool) -> Option<Self> {
+        let (rpc, chain_id, etherscan_base, chain_name) = match Chain::from_str(name) {
+            Ok(chain) => (
+                chain.get_chain_rpc(),
+                chain.get_chain_id(),
+                chain.get_chain_etherscan_base(),
+                chain.to_lowercase(),
+            ),
+            Err(_) => {
+                let chain = get_custom_chain(name)?;
+                (
+                    chain.rpc.clone(),
+                    chain.chain_id,
+                    chain.explorer.clone(),
+                    chain.name.clone(),
+                )
+            }
+        };
+        Some(Self::new_raw(
+            endpoint_urls(urls, rpc, public_fallback),
+            chain_id,
+            block_number,
+            etherscan_base,
+            chain_name,
+        ))
+    }
+
     pub fn new(chain: Chain, block_number: u64) -> Self {
         Self::new_raw(
             chain.get_chain_rpc(),
@@ -428,6 +728,8 @@ impl OnChainConfig {
         )
     }
 
+    /// `endpoint_url` can contain multiple comma separated URLs, requests fail
+    /// over between them
     pub fn new_raw(
         endpoint_url: String,
         chain_id: u32,
@@ -435,8 +737,10 @@ impl OnChainConfig {
         etherscan_base: String,
         chain_name: String,
     ) -> Self {
+        let provider = FailoverProvider::from_urls(&endpoint_url);
         let mut s = Self {
-            endpoint_url,
+            endpoint_url: provider.endpoints().first().cloned().unwrap_or(endpoint_url),
+            rpc_provider: Some(Arc::new(provider)),
             client: reqwest::blocking::Client::builder()
                 .timeout(Duration::from_secs(20))
                 .build()
@@ -450,16 +754,87 @@ impl OnChainConfig {
             etherscan_api_key: vec![],
             etherscan_base,
             chain_name,
-            rpc_cache: FileSystemCache::new("./cache"),
+            signature_lookup: true,
+            cache_dir: DEFAULT_CACHE_DIR.to_string(),
             ..Default::default()
         };
         if block_number == 0 {
             s.set_latest_block_number();
         }
+        s.reset_rpc_cache();
         s
     }
 
-    fn get(&self, url: String) -> Option<String> {
+    /// Spawn a local fork node of the current endpoint at the current block
+    /// and send all the requests to it
+    pub fn use_fork_backend(&mut self, kind: ForkBackendKind) -> Result<(), String> {
+        let block_number = u64::from_str_radix(self.block_number.trim_start_matches("0x"), 16)
+            .map_err(|e| format!("invalid block number {}: {}", self.block_number, e))?;
+        let backend = ForkBackend::spawn(kind, &self.endpoint_url, block_number)?;
+        self.endpoint_url = backend.url();
+        self.rpc_provider = Some(Arc::new(FailoverProvider::new(vec![backend.url()])));
+        self.fork_backend = Some(Arc::new(backend));
+        Ok(())
+    }
+
+    /// Set the directory of the persistent RPC cache
+    pub fn set_cache_dir(&mut self, cache_dir: &str) {
+        self.cache_dir = cache_dir.to_string();
+        self.reset_rpc_cache();
+    }
+
+    /// Limit the credits consumed by RPC requests, after which only cached
+    /// responses are served
+    pub fn set_rpc_budget(&mut self, limit: u64) {
+        self.rpc_budget = Arc::new(RpcBudget::new(Some(limit)));
+    }
+
+    fn reset_rpc_cache(&mut self) {
+        self.rpc_cache = FileSystemCache::new(&format!("{}/{}/{}", self.cache_dir, self.chain_id, self.block_number));
+    }
+
+    /// Body of the response to a GET of `url` on an optional lookup service,
+    /// None if not found or failed. A single attempt is made with a short
+    /// timeout, both the responses and the "not found" are cached, and the
+    /// lookups stop once the service is unreachable.
+    pub(crate) fn lookup(&self, url: String) -> Option<String> {
+        if !self.signature_lookup || self.lookup_unreachable.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut hasher = DefaultHasher::new();
+        format!("lookup_{}", url).hash(&mut hasher);
+        let hash = hasher.finish().to_string();
+        if let Ok(t) = self.rpc_cache.load(hash.as_str()) {
+            return (!t.is_empty()).then_some(t);
+        }
+        let resp = match self
+            .client
+            .get(url.as_str())
+            .timeout(Duration::from_secs(SIGNATURE_LOOKUP_TIMEOUT_SECS))
+            .send()
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                debug!("{} is unreachable, skipping the next lookups: {}", url, e);
+                self.lookup_unreachable.store(true, Ordering::Relaxed);
+                return None;
+            }
+        };
+        let t = match resp.status() {
+            StatusCode::NOT_FOUND => String::new(),
+            status if status.is_success() => resp.text().ok()?,
+            status => {
+                debug!("lookup of {} failed: {}", url, status);
+                return None;
+            }
+        };
+        if let Err(e) = self.rpc_cache.save(hash.as_str(), t.as_str()) {
+            debug!("failed to cache the lookup of {}: {}", url, e);
+        }
+        (!t.is_empty()).then_some(t)
+    }
+
+    pub(crate) fn get(&self, url: String) -> Option<String> {
         let mut hasher = DefaultHasher::new();
         let key = format!("get_{}", url.as_str());
         key.hash(&mut hasher);
@@ -509,62 +884,60 @@ impl OnChainConfig {
         }
     }
 
-    fn post(&self, url: String, data: String) -> Option<String> {
+    /// Key of the response to the JSON-RPC request `data` in the RPC cache
+    fn post_cache_key(data: &str) -> String {
         let mut hasher = DefaultHasher::new();
-        let key = format!("post_{}_{}", url.as_str(), data.as_str());
-        key.hash(&mut hasher);
-        let hash = hasher.finish().to_string();
+        // the cache is namespaced by chain and block, so the responses do not
+        // depend on the endpoint, e.g., the random port of the fork backend
+        format!("post_{}", data).hash(&mut hasher);
+        hasher.finish().to_string()
+    }
+
+    fn post(&self, data: String) -> Option<String> {
+        let hash = Self::post_cache_key(&data);
         if let Ok(t) = self.rpc_cache.load(hash.as_str()) {
             return Some(t);
         }
-        match retry_with_index(Fixed::from_millis(100), |current_try| {
-            if current_try > 3 {
-                return OperationResult::Err("did not succeed within 3 tries".to_string());
-            }
-            match self
-                .client
-                .post(url.to_string())
-                .header("Content-Type", "application/json")
-                .headers(get_header())
-                .body(data.to_string())
-                .send()
-            {
-                Ok(resp) => {
-                    let text = resp.text();
-                    match text {
-                        Ok(t) => OperationResult::Ok(t),
-                        Err(e) => {
-                            error!("{:?}", e);
-                            OperationResult::Retry("failed to parse response".to_string())
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Error: {}", e);
-                    OperationResult::Retry("failed to send request".to_string())
-                }
-            }
-        }) {
-            Ok(t) => {
-                if !t.contains("error") {
-                    self.rpc_cache.save(hash.as_str(), t.as_str()).unwrap();
-                }
-                Some(t)
-            }
-            Err(e) => {
-                error!("Error: {}", e);
-                None
-            }
+        let t = self.post_uncached(&data)?;
+        if !t.contains("error") {
+            self.rpc_cache.save(hash.as_str(), t.as_str()).unwrap();
+        }
+        Some(t)
+    }
+
+    fn post_uncached(&self, data: &str) -> Option<String> {
+        if !self.rpc_budget.spend_request(data) {
+            return None;
         }
+        let result = match &self.rpc_provider {
+            Some(provider) => provider.send(&self.client, data),
+            None => FailoverProvider::new(vec![self.endpoint_url.clone()]).send(&self.client, data),
+        };
+        result.map_err(|e| error!("Error: {:?}", e)).ok()
+    }
+
+    /// Number of the latest block in hex, None if it cannot be fetched
+    fn latest_block_number(&self) -> Option<String> {
+        // the latest block changes, so never serve it from the cache
+        let data = format!(
+            "{{\"jsonrpc\":\"2.0\", \"method\": \"eth_blockNumber\", \"params\": [], \"id\": {}}}",
+            self.chain_id
+        );
+        self.post_uncached(&data)
+            .and_then(|resp| serde_json::from_str::<Value>(&resp).ok())
+            .and_then(|json| {
+                json.get("result")?
+                    .as_str()
+                    .map(|block_number| block_number.to_string())
+            })
     }
 
     pub fn set_latest_block_number(&mut self) {
-        let resp = self._request("eth_blockNumber".to_string(), "[]".to_string());
-        match resp {
-            Some(resp) => {
-                let block_number = resp.as_str().unwrap();
-                self.block_number = block_number.to_string();
-                let block_number = EVMU256::from_str_radix(block_number.trim_start_matches("0x"), 16)
+        match self.latest_block_number() {
+            Some(block_number) => {
+                self.block_number = block_number;
+                self.reset_rpc_cache();
+                let block_number = EVMU256::from_str_radix(self.block_number.trim_start_matches("0x"), 16)
                     .unwrap()
                     .to_string();
                 debug!("latest block number is {}", block_number);
@@ -573,6 +946,24 @@ impl OnChainConfig {
         }
     }
 
+    /// Copy of the config at the latest block with empty caches, None if the
+    /// latest block number cannot be fetched
+    pub fn at_latest_block(&self) -> Option<Self> {
+        let mut config = self.clone();
+        config.block_number = self.latest_block_number()?;
+        config.timestamp = None;
+        config.coinbase = None;
+        config.gaslimit = None;
+        config.block_hash = None;
+        config.balance_cache.clear();
+        config.slot_cache.clear();
+        config.code_cache.clear();
+        config.code_cache_analyzed.clear();
+        config.storage_dump_cache.clear();
+        config.reset_rpc_cache();
+        Some(config)
+    }
+
     pub fn add_etherscan_api_key(&mut self, key: String) {
         self.etherscan_api_key.push(key);
     }
@@ -688,7 +1079,10 @@ impl OnChainConfig {
         if self.abi_cache.contains_key(&address) {
             return self.abi_cache.get(&address).unwrap().clone();
         }
-        let abi = self.fetch_abi_uncached(address);
+        // contracts not verified on the explorer may be on Sourcify
+        let abi = self
+            .fetch_abi_uncached(address)
+            .or_else(|| self.fetch_sourcify_abi(address));
         self.abi_cache.insert(address, abi.clone());
         abi
     }
@@ -698,7 +1092,7 @@ impl OnChainConfig {
             "{{\"jsonrpc\":\"2.0\", \"method\": \"{}\", \"params\": {}, \"id\": {}}}",
             method, params, self.chain_id
         );
-        self.post(self.endpoint_url.clone(), data)
+        self.post(data)
             .and_then(|resp| serde_json::from_str(&resp).ok())
             .and_then(|json: Value| json.get("result").cloned())
             .or_else(|| {
@@ -712,7 +1106,7 @@ impl OnChainConfig {
             "{{\"jsonrpc\":\"2.0\", \"method\": \"{}\", \"params\": {}, \"id\": {}}}",
             method, params, id
         );
-        self.post(self.endpoint_url.clone(), data)
+        self.post(data)
             .and_then(|resp| serde_json::from_str(&resp).ok())
             .and_then(|json: Value| json.get("result").cloned())
             .or_else(|| {
@@ -737,7 +1131,7 @@ impl OnChainConfig {
                     let balance = resp.as_str().unwrap();
                     balance.to_string()
                 }
-                None => "".to_string(),
+                None => "0x0".to_string(),
             }
         };
         let balance = EVMU256::from_str(&resp_string).unwrap();
@@ -768,12 +1162,58 @@ impl OnChainConfig {
         call_result
     }
 
+    /// `eth_call` expected to fail on most contracts, e.g., probing an
+    /// optional interface. None if it reverts, logged at debug level only.
+    pub fn probe_call(&mut self, address: EVMAddress, calldata: Bytes) -> Option<Bytes> {
+        let call = format!(
+            "\"from\": null,\"to\":\"{:?}\",\"data\":\"0x{}\"",
+            address,
+            hex::encode(calldata.to_vec())
+        );
+        let data = format!(
+            "{{\"jsonrpc\":\"2.0\", \"method\": \"eth_call\", \"params\": [{{{}}},\"{}\"], \"id\": {}}}",
+            call, self.block_number, self.chain_id
+        );
+        let res = self
+            .post(data)
+            .and_then(|resp| serde_json::from_str::<Value>(&resp).ok())
+            .and_then(|json| json.get("result")?.as_str().map(|res| res.to_string()))
+            .and_then(|res| hex::decode(res.trim_start_matches("0x")).ok());
+        if res.is_none() {
+            debug!("eth_call to {:?} failed", address);
+        }
+        res.map(Bytes::from)
+    }
+
     pub fn get_token_balance(&mut self, token: EVMAddress, address: EVMAddress) -> EVMU256 {
         let data = format!("70a08231000000000000000000000000{:x}", address);
         let balance = self.eth_call(token, Bytes::from(hex::decode(data).unwrap()));
         EVMU256::from_be_slice(&balance)
     }
 
+    pub fn get_curve_coins(&mut self, pool: EVMAddress) -> Vec<EVMAddress> {
+        // StableSwap pools hold at most 8 coins, coins(i) reverts past the last one
+        let mut coins = vec![];
+        for idx in 0..8u64 {
+            let data = format!("c6610657{:064x}", idx);
+            let coin = self.eth_call(pool, Bytes::from(hex::decode(data).unwrap()));
+            if coin.len() < 32 {
+                break;
+            }
+            coins.push(EVMAddress::from_slice(&coin[12..32]));
+        }
+        coins
+    }
+
+    /// Decimals of the ERC20 `token`, 18 if it does not tell
+    pub fn get_token_decimals(&mut self, token: EVMAddress) -> u8 {
+        let decimals = self.eth_call(token, Bytes::from(hex::decode("313ce567").unwrap()));
+        if decimals.len() < 32 || decimals[..31].iter().any(|b| *b != 0) {
+            return 18;
+        }
+        decimals[31]
+    }
+
     pub fn get_v3_fee(&mut self, address: EVMAddress) -> u32 {
         let data = "ddca3f43".to_string();
         let fee = self.eth_call(address, Bytes::from(hex::decode(data).unwrap()));
@@ -850,7 +1290,7 @@ impl OnChainConfig {
         if self.code_cache.contains_key(&address) {
             return self.code_cache[&address].clone();
         }
-        if force_cache {
+        if force_cache || self.rpc_budget.is_exhausted() {
             return "".to_string();
         }
 
@@ -885,7 +1325,6 @@ impl OnChainConfig {
         let contract_code = to_analysed(Bytecode::new_raw(Bytes::from(
             hex::decode(code).expect("fail to decode contract code"),
         )));
-        let contract_code = to_analysed(contract_code);
         self.code_cache_analyzed.insert(address, contract_code.clone());
         contract_code
     }
@@ -894,7 +1333,7 @@ impl OnChainConfig {
         if self.slot_cache.contains_key(&(address, slot)) {
             return self.slot_cache[&(address, slot)];
         }
-        if force_cache {
+        if force_cache || self.rpc_budget.is_exhausted() {
             return EVMU256::ZERO;
         }
 
@@ -924,6 +1363,61 @@ impl OnChainConfig {
         self.slot_cache.insert((address, slot), slot_value);
         slot_value
     }
+
+    /// Fetch the slots of `address` missing from the RPC cache in one batch,
+    /// so that they are pipelined over WebSocket endpoints, and cache them for
+    /// [`OnChainConfig::get_contract_slot`]
+    pub fn prefetch_contract_slots(&mut self, address: EVMAddress, slots: impl IntoIterator<Item = EVMU256>) {
+        let mut resps = vec![];
+        let mut missing = vec![];
+        for slot in slots {
+            if self.slot_cache.contains_key(&(address, slot)) {
+                continue;
+            }
+            // the same request as `get_contract_slot`, to share its cache
+            let data = format!(
+                "{{\"jsonrpc\":\"2.0\", \"method\": \"eth_getStorageAt\", \"params\": [\"0x{:x}\",\"0x{:x}\",\"{}\"], \"id\": {}}}",
+                address, slot, self.block_number, self.chain_id
+            );
+            match self.rpc_cache.load(&Self::post_cache_key(&data)) {
+                Ok(resp) => resps.push((slot, resp)),
+                Err(_) => missing.push((slot, data)),
+            }
+        }
+        if !missing.is_empty() && missing.iter().all(|_| self.rpc_budget.spend("eth_getStorageAt")) {
+            let provider = match &self.rpc_provider {
+                Some(provider) => provider.clone(),
+                None => Arc::new(FailoverProvider::new(vec![self.endpoint_url.clone()])),
+            };
+            let data = missing.iter().map(|(_, data)| data.clone()).collect_vec();
+            match provider.send_batch(&self.client, &data) {
+                Ok(fetched) => {
+                    for ((slot, data), resp) in missing.into_iter().zip(fetched) {
+                        if !resp.contains("error") &&
+                            let Err(e) = self.rpc_cache.save(&Self::post_cache_key(&data), &resp)
+                        {
+                            debug!("failed to cache the slot {:?} of {:?}: {}", slot, address, e);
+                        }
+                        resps.push((slot, resp));
+                    }
+                }
+                Err(e) => debug!("failed to prefetch slots of {:?}: {:?}", address, e),
+            }
+        }
+        for (slot, resp) in resps {
+            let value = serde_json::from_str::<Value>(&resp)
+                .ok()
+                .and_then(|json| json["result"].as_str().map(|v| v.trim_start_matches("0x").to_string()));
+            if let Some(value) = value {
+                let value = if value.is_empty() {
+                    EVMU256::ZERO
+                } else {
+                    EVMU256::from_str_radix(&value, 16).unwrap_or_default()
+                };
+                self.slot_cache.insert((address, slot), value);
+            }
+        }
+    }
 }
 
 impl OnChainConfig {
@@ -987,6 +1481,111 @@ impl OnChainConfig {
         pairs
     }
 
+    /// Known Uniswap V2 style factories of the chain, with the `src_exact`
+    /// label used to look up their fee in `get_uniswap_info`, including those
+    /// of the chain registry
+    pub fn get_v2_factories(&self) -> Vec<(String, EVMAddress)> {
+        let factories: &[(&'static str, &str)] = match self.chain_name.as_str() {
+            "eth" => &[
+                ("uniswapv2_eth", "0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f"),
+                ("sushiswapv2_eth", "0xc0aee478e3658e2610c5f7a4a2e1777ce9e4f2ac"),
+            ],
+            "bsc" => &[
+                ("pancakeswapv2_bsc", "0xca143ce32fe78f1f7019d7d551a6402fc5350c73"),
+                ("biswapv2_bsc", "0x858e3312ed3a876947ea49d572a7c42de08af7ee"),
+            ],
+            "polygon" => &[
+                ("quickswapv2_polygon", "0x5757371414417b8c6caad45baef941abc7d3ab32"),
+                ("sushiswapv2_polygon", "0xc35dadb65012ec5796536bd9864ed8773abc74c4"),
+            ],
+            _ => &[],
+        };
+        let custom = get_custom_chain(&self.chain_name)
+            .map(|chain| chain.dexes.as_slice())
+            .unwrap_or_default();
+        factories
+            .iter()
+            .map(|(src_exact, factory)| (src_exact.to_string(), EVMAddress::from_str(factory).unwrap()))
+            .chain(
+                custom
+                    .iter()
+                    .map(|dex| (dex.name.clone(), EVMAddress::from_str(&dex.factory).unwrap())),
+            )
+            .collect()
+    }
+
+    /// Queries `getPair(token, base)` on every known factory and returns the
+    /// pairs that exist, with `token` as the input token.
+    pub fn get_factory_pairs(&mut self, token: &str, base: &str) -> Vec<PairData> {
+        let token = token.to_lowercase();
+        let base = base.to_lowercase();
+        let token_addr = EVMAddress::from_str(&token).unwrap();
+        let base_addr = EVMAddress::from_str(&base).unwrap();
+
+        let mut pairs = vec![];
+        for (src_exact, factory) in self.get_v2_factories() {
+            // the pairs of the registry with an init code hash are not queried
+            let pair = match get_custom_dex(&src_exact).and_then(|dex| dex.pair_address(token_addr, base_addr)) {
+                Some(pair) => {
+                    if self.get_contract_code(pair, false).is_empty() {
+                        continue;
+                    }
+                    pair
+                }
+                None => {
+                    let data = format!(
+                        "e6a43905000000000000000000000000{:x}000000000000000000000000{:x}",
+                        token_addr, base_addr
+                    );
+                    let res = self.eth_call(factory, Bytes::from(hex::decode(data).unwrap()));
+                    if res.len() < 32 {
+                        continue;
+                    }
+                    EVMAddress::from_slice(&res[12..32])
+                }
+            };
+            if pair.is_zero() {
+                continue;
+            }
+
+            // token0() is the lower address
+            let (token0, token1) = if token_addr < base_addr {
+                (token.clone(), base.clone())
+            } else {
+                (base.clone(), token.clone())
+            };
+            let decimals = |chain: &mut Self, addr: &str| {
+                let res = chain.eth_call(
+                    EVMAddress::from_str(addr).unwrap(),
+                    Bytes::from(vec![0x31, 0x3c, 0xe5, 0x67]),
+                );
+                if res.len() < 32 {
+                    0
+                } else {
+                    res[31] as u32
+                }
+            };
+            let decimals_0 = decimals(self, &token0);
+            let decimals_1 = decimals(self, &token1);
+            pairs.push(PairData {
+                src: "lp".to_string(),
+                in_: if token == token0 { 0 } else { 1 },
+                pair: format!("{:?}", pair),
+                in_token: token.clone(),
+                next: base.clone(),
+                interface: "uniswapv2".to_string(),
+                src_exact,
+                initial_reserves_0: EVMU256::ZERO,
+                initial_reserves_1: EVMU256::ZERO,
+                decimals_0,
+                decimals_1,
+                token0,
+                token1,
+            });
+        }
+        pairs
+    }
+
     pub fn fetch_reserve(&self, pair: &str) -> Option<(String, String)> {
         let result = {
             let params = json!([{
@@ -1157,4 +1756,52 @@ mod tests {
 
     //     assert_eq!(slot_v, v0);
     // }
+
+    #[test]
+    fn test_endpoint_urls() {
+        let rpc = "https://eth.llamarpc.com".to_string();
+        assert_eq!(endpoint_urls(None, rpc.clone(), false), rpc);
+        assert_eq!(
+            endpoint_urls(Some("http://localhost:8545"), rpc.clone(), false),
+            "http://localhost:8545"
+        );
+        assert_eq!(
+            endpoint_urls(Some("http://localhost:8545"), rpc.clone(), true),
+            "http://localhost:8545,https://eth.llamarpc.com"
+        );
+    }
+
+    #[test]
+    fn test_chain_profile() {
+        assert_eq!(Chain::from_chain_id(324), Some(Chain::ZKSYNC));
+        assert!(Chain::ETH.get_profile().precompiles.is_empty());
+        assert_eq!(Chain::ETH.get_profile().tx_gas_limit, BLOCK_GAS_LIMIT);
+        // the predeploys of the OP stack have a bytecode
+        assert!(Chain::OPTIMISM.get_profile().precompiles.is_empty());
+
+        let profile = Chain::ARBITRUM.get_profile();
+        let arb_sys =
+            &profile.precompiles[&EVMAddress::from_str("0x0000000000000000000000000000000000000064").unwrap()];
+        let mut env = Env::default();
+        env.block.number = EVMU256::from(42);
+        // arbBlockNumber()
+        let output = arb_sys.call(&hex::decode("a3b1b31d").unwrap(), &env, |_| EVMU256::ZERO);
+        assert_eq!(output, Some(EVMU256::from(42).to_be_bytes::<32>().to_vec()));
+        assert_eq!(arb_sys.call(&[0xde, 0xad, 0xbe, 0xef], &env, |_| EVMU256::ZERO), None);
+
+        let profile = Chain::ZKSYNC.get_profile();
+        let base_token =
+            &profile.precompiles[&EVMAddress::from_str("0x000000000000000000000000000000000000800a").unwrap()];
+        let owner = EVMAddress::from_slice(&[0x11; 20]);
+        // balanceOf(uint256)
+        let input = [hex::decode("9cc7f708").unwrap(), [0u8; 12].to_vec(), owner.0.to_vec()].concat();
+        let output = base_token.call(&input, &env, |address| {
+            if address == owner {
+                EVMU256::from(7)
+            } else {
+                EVMU256::ZERO
+            }
+        });
+        assert_eq!(output, Some(EVMU256::from(7).to_be_bytes::<32>().to_vec()));
+    }
 }
@@ -35,8 +35,12 @@ use crate::{
         middlewares::middleware::{Middleware, MiddlewareType},
         mutator::AccessPattern,
         oracles::erc20::IERC20OracleFlashloan,
-        tokens::{uniswap::fetch_uniswap_path, TokenContext},
-        types::{convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256, EVMU512},
+        tokens::{
+            nft::{decode_transfer, NFTTransfer},
+            uniswap::fetch_uniswap_path,
+            TokenContext,
+        },
+        types::{as_u64, convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMU256, EVMU512},
     },
     generic_vm::vm_state::VMStateT,
     input::VMInputT,
@@ -44,6 +48,17 @@ use crate::{
 };
 
 pub static mut CAN_LIQUIDATE: bool = false;
+/// Maximum value (in wei) of a transaction sent by the attacker, unlimited if
+/// None
+pub static mut MAX_CAPITAL: Option<EVMU256> = None;
+
+/// Clamp the value of a transaction sent by the attacker to [`MAX_CAPITAL`]
+pub fn clamp_to_max_capital(value: EVMU256) -> EVMU256 {
+    match unsafe { MAX_CAPITAL } {
+        Some(max) if value > max => value % (max + EVMU256::from(1)),
+        _ => value,
+    }
+}
 
 #[macro_export]
 macro_rules! scale {
@@ -60,6 +75,8 @@ pub struct Flashloan {
     pub unbound_tracker: HashMap<usize, HashSet<EVMAddress>>, // pc -> [address called]
     pub flashloan_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
     pub token_context_cache: HashMap<EVMAddress, TokenContext>,
+    /// Floor prices (in wei) of the NFT collections received by the attacker
+    pub nft_floor_prices: HashMap<EVMAddress, EVMU256>,
 }
 
 impl Debug for Flashloan {
@@ -94,11 +111,12 @@ where
                 data: None,
                 sstate: Default::default(),
                 sstate_idx: 0,
-                txn_value: Some(EVMU256::from_str("10000000000000000000").unwrap()),
+                txn_value: Some(clamp_to_max_capital(EVMU256::from_str("10000000000000000000").unwrap())),
                 step: false,
                 env: state.metadata_map().get::<EnvMetadata>().unwrap().env.clone(),
                 access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
                 liquidation_percent: 0,
+                liquidation_path: 0,
                 direct_data: Default::default(),
                 randomness: vec![0],
                 repeat: 1,
@@ -130,9 +148,14 @@ impl Flashloan {
             unbound_tracker: Default::default(),
             token_context_cache: Default::default(),
             flashloan_oracle,
+            nft_floor_prices: Default::default(),
         }
     }
 
+    pub fn set_nft_floor_prices(&mut self, nft_floor_prices: HashMap<EVMAddress, EVMU256>) {
+        self.nft_floor_prices = nft_floor_prices;
+    }
+
     fn get_token_context(&mut self, addr: EVMAddress) -> Option<TokenContext> {
         self.chain_cfg.as_mut().map(|config| fetch_uniswap_path(config, addr))
     }
@@ -225,6 +248,34 @@ impl Flashloan {
 }
 
 impl Flashloan {
+    /// Record the NFTs transferred by an LOG4 to or from the attacker, they
+    /// are valued once the transaction succeeds
+    fn on_nft_event(&self, interp: &Interpreter, flashloan_data: &mut FlashloanData, state: &EVMFuzzState) {
+        if !self.nft_floor_prices.contains_key(&interp.contract.address) {
+            return;
+        }
+        let offset = as_u64(interp.stack.peek(0).unwrap()) as usize;
+        let len = as_u64(interp.stack.peek(1).unwrap()) as usize;
+        if interp.memory.len() < offset.saturating_add(len) {
+            return;
+        }
+        let topics = (2..6).map(|i| interp.stack.peek(i).unwrap()).collect::<Vec<_>>();
+        let transfers = decode_transfer(interp.contract.address, &topics, interp.memory.get_slice(offset, len));
+        flashloan_data.pending_nfts.extend(
+            transfers
+                .into_iter()
+                .filter(|transfer| transfer.from != transfer.to)
+                .filter(|transfer| state.has_caller(&transfer.to) || state.has_caller(&transfer.from)),
+        );
+    }
+
+    /// Value of `amount` tokens of `collection` at its floor price
+    pub fn nft_value(&self, collection: &EVMAddress, amount: EVMU256) -> EVMU512 {
+        self.nft_floor_prices.get(collection).map_or(EVMU512::ZERO, |price| {
+            EVMU512::from(*price).saturating_mul(EVMU512::from(amount)) * scale!()
+        })
+    }
+
     pub fn analyze_call(&self, input: &EVMInput, flashloan_data: &mut FlashloanData) {
         // if the txn is a transfer op, record it
         if input.get_txn_value().is_some() {
@@ -259,6 +310,10 @@ where
         match *interp.instruction_pointer {
             // detect whether it mutates token balance
             0xf1 | 0xfa => {}
+            0xa4 => {
+                self.on_nft_event(interp, &mut host.evmstate.flashloan_data, s);
+                return;
+            }
             0x55 => {
                 if self.pair_address.contains(&interp.contract.address) {
                     let key = interp.stack.peek(0).unwrap();
@@ -309,9 +364,16 @@ pub struct FlashloanData {
     pub oracle_recheck_balance: HashSet<EVMAddress>,
     pub owed: EVMU512,
     pub earned: EVMU512,
+    /// Largest debt (owed but not earned back) carried over to a later block,
+    /// which cannot be flashloaned but is capital held between the blocks
+    pub carried: EVMU512,
     pub prev_reserves: HashMap<EVMAddress, (EVMU256, EVMU256)>,
     pub unliquidated_tokens: HashMap<EVMAddress, EVMU256>,
     pub extra_info: String,
+    /// NFTs transferred to or from the attacker by the transaction, not valued
+    /// until it succeeds
+    #[serde(default)]
+    pub pending_nfts: Vec<NFTTransfer>,
 }
 
 impl FlashloanData {
@@ -321,9 +383,19 @@ impl FlashloanData {
             oracle_recheck_balance: HashSet::new(),
             owed: Default::default(),
             earned: Default::default(),
+            carried: Default::default(),
             prev_reserves: Default::default(),
             unliquidated_tokens: Default::default(),
             extra_info: Default::default(),
+            pending_nfts: Default::default(),
+        }
+    }
+
+    /// The sequence goes on in a later block: the debt not repaid yet cannot
+    /// be flashloaned, and is carried over as capital of the attacker
+    pub fn carry_debt(&mut self) {
+        if self.owed > self.earned {
+            self.carried = self.carried.max(self.owed - self.earned);
         }
     }
 }
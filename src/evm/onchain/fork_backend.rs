@@ -0,0 +1,186 @@
+//! Local fork node (anvil or hardhat) serving the onchain state
+//!
+//! Instead of sending every request to a remote RPC endpoint, the fuzzer can
+//! spawn a local node forking that endpoint at the fuzzed block. The node
+//! fetches each piece of state only once, so the fuzzer no longer hits rate
+//! limits and all of its requests see the same pinned state.
+//!
+//! The node is killed when the backend is dropped, and also when the fuzzer
+//! exits without dropping it (`exit`) or is killed.
+
+use std::{
+    net::{TcpListener, TcpStream},
+    os::unix::process::CommandExt,
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    sync::{Mutex, Once},
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::libc;
+use tracing::{debug, info};
+
+use crate::r#const::FORK_BACKEND_STARTUP_TIMEOUT_SECS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkBackendKind {
+    Anvil,
+    Hardhat,
+}
+
+impl FromStr for ForkBackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anvil" => Ok(ForkBackendKind::Anvil),
+            "hardhat" => Ok(ForkBackendKind::Hardhat),
+            _ => Err(format!("Unknown fork backend: {}", s)),
+        }
+    }
+}
+
+impl ForkBackendKind {
+    /// Command starting the node forking `fork_url` at `block_number` (latest
+    /// when 0), listening on `port`
+    fn command(&self, fork_url: &str, block_number: u64, port: u16) -> Command {
+        let mut cmd = match self {
+            ForkBackendKind::Anvil => {
+                let mut cmd = Command::new("anvil");
+                cmd.args(["--fork-url", fork_url, "--port", &port.to_string(), "--silent"]);
+                cmd
+            }
+            ForkBackendKind::Hardhat => {
+                let mut cmd = Command::new("npx");
+                cmd.args(["hardhat", "node", "--fork", fork_url, "--port", &port.to_string()]);
+                cmd
+            }
+        };
+        // both take the same flag
+        if block_number != 0 {
+            cmd.args(["--fork-block-number", &block_number.to_string()]);
+        }
+        cmd
+    }
+}
+
+/// Process ids of the running fork nodes
+static RUNNING_BACKENDS: Mutex<Vec<u32>> = Mutex::new(vec![]);
+static KILL_AT_EXIT: Once = Once::new();
+
+extern "C" fn kill_running_backends() {
+    if let Ok(pids) = RUNNING_BACKENDS.lock() {
+        for pid in pids.iter() {
+            unsafe {
+                libc::killpg(*pid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// A running fork node, killed when dropped
+#[derive(Debug)]
+pub struct ForkBackend {
+    kind: ForkBackendKind,
+    port: u16,
+    child: Child,
+}
+
+impl ForkBackend {
+    /// Spawn the node forking `fork_url` at `block_number` and wait until it
+    /// accepts connections
+    pub fn spawn(kind: ForkBackendKind, fork_url: &str, block_number: u64) -> Result<Self, String> {
+        let port = free_port().ok_or("no free port for the fork backend")?;
+        let mut cmd = kind.command(fork_url, block_number, port);
+        // in its own process group, so that the node spawned by npx is killed
+        // along with it
+        cmd.stdout(Stdio::null()).stderr(Stdio::null()).process_group(0);
+        // killed along with the fuzzer
+        #[cfg(target_os = "linux")]
+        unsafe {
+            cmd.pre_exec(|| {
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                Ok(())
+            });
+        }
+        let child = cmd
+            .spawn()
+            .map_err(|e| format!("failed to spawn {:?} fork backend: {}", kind, e))?;
+        KILL_AT_EXIT.call_once(|| unsafe {
+            libc::atexit(kill_running_backends);
+        });
+        RUNNING_BACKENDS.lock().unwrap().push(child.id());
+        let mut backend = Self { kind, port, child };
+
+        let deadline = Instant::now() + Duration::from_secs(FORK_BACKEND_STARTUP_TIMEOUT_SECS);
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            if let Ok(Some(status)) = backend.child.try_wait() {
+                return Err(format!("{:?} fork backend exited with {}", kind, status));
+            }
+            if Instant::now() > deadline {
+                return Err(format!("{:?} fork backend did not start in time", kind));
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+        info!("{:?} fork backend of {} listening on {}", kind, fork_url, backend.url());
+        Ok(backend)
+    }
+
+    /// RPC URL of the node
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn kind(&self) -> ForkBackendKind {
+        self.kind
+    }
+}
+
+impl Drop for ForkBackend {
+    fn drop(&mut self) {
+        debug!("stopping {:?} fork backend", self.kind);
+        unsafe {
+            libc::killpg(self.child.id() as libc::pid_t, libc::SIGKILL);
+        }
+        let _ = self.child.wait();
+        if let Ok(mut pids) = RUNNING_BACKENDS.lock() {
+            pids.retain(|pid| *pid != self.child.id());
+        }
+    }
+}
+
+fn free_port() -> Option<u16> {
+    TcpListener::bind(("127.0.0.1", 0))
+        .ok()
+        .and_then(|listener| listener.local_addr().ok())
+        .map(|addr| addr.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fork_backend_command() {
+        let cmd = ForkBackendKind::Anvil.command("http://rpc", 100, 8545);
+        let args = cmd.get_args().map(|a| a.to_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(cmd.get_program(), "anvil");
+        assert_eq!(
+            args,
+            vec![
+                "--fork-url",
+                "http://rpc",
+                "--port",
+                "8545",
+                "--silent",
+                "--fork-block-number",
+                "100"
+            ]
+        );
+
+        let cmd = ForkBackendKind::Hardhat.command("http://rpc", 0, 8545);
+        assert!(!cmd.get_args().any(|a| a == "--fork-block-number"));
+        assert!(ForkBackendKind::from_str("ganache").is_err());
+    }
+}
@@ -1,7 +1,14 @@
 pub mod abi_decompiler;
+pub mod abi_inference;
+pub mod chains;
 pub mod endpoints;
 pub mod flashloan;
+pub mod fork_backend;
 pub mod offchain;
+pub mod provider;
+pub mod proxy;
+pub mod signatures;
+pub mod upgrades;
 
 use std::{
     cell::RefCell,
@@ -31,6 +38,7 @@ use crate::{
         config::StorageFetchingMode,
         contract_utils::{extract_sig_from_contract, ABIConfig, ContractLoader},
         corpus_initializer::ABIMap,
+        dictionary::{add_bytecode_to_dictionary, add_storage_to_dictionary},
         host::FuzzHost,
         input::{EVMInput, EVMInputTy},
         middlewares::{
@@ -38,7 +46,7 @@ use crate::{
             middleware::{add_corpus, Middleware, MiddlewareType},
         },
         mutator::AccessPattern,
-        onchain::{abi_decompiler::fetch_abi_heimdall, endpoints::OnChainConfig, flashloan::register_borrow_txn},
+        onchain::{endpoints::OnChainConfig, flashloan::register_borrow_txn, signatures::decompile_abi},
         types::{convert_u256_to_h160, EVMAddress, EVMU256},
         vm::IS_FAST_CALL,
     },
@@ -58,6 +66,9 @@ pub trait ChainConfig {
     fn get_contract_code_analyzed(&mut self, address: EVMAddress, force_cache: bool) -> Bytecode;
     fn get_v3_fee(&mut self, address: EVMAddress) -> u32;
     fn get_token_balance(&mut self, token: EVMAddress, address: EVMAddress) -> EVMU256;
+    fn get_curve_coins(&mut self, pool: EVMAddress) -> Vec<EVMAddress>;
+    fn get_token_decimals(&mut self, token: EVMAddress) -> u8;
+    fn get_factory_pairs(&mut self, token: &str, base: &str) -> Vec<PairData>;
     fn get_weth(&self) -> String;
     fn get_pegged_token(&self) -> HashMap<String, String>;
 }
@@ -220,6 +231,10 @@ where
                             .get_contract_slot(address, slot_idx, force_cache!(self.locs, slot_idx))
                     }
                 };
+                // the value of a slot on chain does not change, add it once
+                if self.loaded_data.insert((address, slot_idx)) {
+                    add_storage_to_dictionary(state, address, host.next_slot);
+                }
                 // println!("SLOAD {:?} {:?}", slot_idx, host.next_slot);
             }
             #[cfg(feature = "real_balance")]
@@ -325,7 +340,7 @@ impl OnChain {
     {
         let contract_code = self.endpoint.get_contract_code(address_h160, force_cache);
         let code = hex::decode(contract_code).unwrap();
-        let contract_code = to_analysed(Bytecode::new_raw(Bytes::from(code)));
+        let contract_code = to_analysed(Bytecode::new_raw(Bytes::from(code.clone())));
 
         if contract_code.is_empty() || force_cache {
             self.loaded_code.insert(address_h160);
@@ -334,7 +349,13 @@ impl OnChain {
         }
         if !self.loaded_code.contains(&address_h160) && !host.code.contains_key(&address_h160) {
             bytecode_analyzer::add_analysis_result_to_state(&contract_code, state);
+            add_bytecode_to_dictionary(state, address_h160, &contract_code);
             host.set_codedata(address_h160, contract_code.clone());
+            // fetch the state variables at once instead of on each SLOAD
+            if let StorageFetchingMode::OneByOne = self.storage_fetching {
+                self.endpoint
+                    .prefetch_contract_slots(address_h160, bytecode_analyzer::find_constant_slots(&contract_code));
+            }
         }
         if unsafe { IS_FAST_CALL } || self.blacklist.contains(&address_h160) {
             return;
@@ -377,8 +398,8 @@ impl OnChain {
                 None => {
                     // 1. Extract abi from bytecode, and see do we have any function sig available
                     //    in state
-                    // 2. Use Heimdall to extract abi
-                    // 3. Reconfirm on failures of heimdall
+                    // 2. Decompile the abi, naming the functions after their signatures
+                    // 3. Reconfirm on failures of the decompiler
                     debug!("Contract {:?} has no abi", address_h160);
                     let contract_code_str = hex::encode(contract_code.bytes());
                     let sigs = extract_sig_from_contract(&contract_code_str);
@@ -392,8 +413,8 @@ impl OnChain {
                     }
 
                     if unknown_sigs >= sigs.len() / 30 {
-                        debug!("Too many unknown function signature ({:?}) for {:?}, we are going to decompile this contract using Heimdall", unknown_sigs, address_h160);
-                        let abis = fetch_abi_heimdall(contract_code_str)
+                        debug!("Too many unknown function signature ({:?}) for {:?}, we are going to decompile this contract", unknown_sigs, address_h160);
+                        let abis = decompile_abi(&self.endpoint, contract_code_str)
                             .iter()
                             .map(|abi| {
                                 if let Some(known_abi) =
@@ -411,6 +432,12 @@ impl OnChain {
                 }
             }
         }
+        // fuzz proxies and diamonds with the functions of their implementations
+        if !is_proxy_call {
+            let delegated_abi = proxy::delegated_abi(&mut self.endpoint, address_h160, &code);
+            parsed_abi = proxy::merge_abis(parsed_abi, delegated_abi);
+        }
+
         // set up host
         let mut abi_hashes_to_add = HashSet::new();
         if is_proxy_call {
@@ -486,6 +513,7 @@ impl OnChain {
                     env: state.metadata_map().get::<EnvMetadata>().unwrap().env.clone(),
                     access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
                     liquidation_percent: 0,
+                    liquidation_path: 0,
                     input_type: EVMInputTy::ABI,
                     direct_data: Default::default(),
                     randomness: vec![0],
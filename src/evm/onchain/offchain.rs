@@ -267,6 +267,26 @@ impl ChainConfig for OffChainConfig {
         self.balance_cache.get(&(address, token)).cloned().unwrap_or_default()
     }
 
+    fn get_curve_coins(&mut self, _pool: EVMAddress) -> Vec<EVMAddress> {
+        vec![]
+    }
+
+    fn get_token_decimals(&mut self, _token: EVMAddress) -> u8 {
+        18
+    }
+
+    fn get_factory_pairs(&mut self, token: &str, base: &str) -> Vec<PairData> {
+        let base = base.to_lowercase();
+        self.get_pair(token, false)
+            .into_iter()
+            .filter(|pair| pair.next.to_lowercase() == base)
+            .map(|pair| PairData {
+                src: "lp".to_string(),
+                ..pair
+            })
+            .collect()
+    }
+
     fn get_weth(&self) -> String {
         PRESET_WETH.to_string()
     }
@@ -0,0 +1,417 @@
+//! RPC providers sending the JSON-RPC requests of
+//! [`super::endpoints::OnChainConfig`]
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use reqwest::{blocking, StatusCode};
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::r#const::{
+    RPC_BACKOFF_INITIAL_MS,
+    RPC_BACKOFF_MAX_MS,
+    RPC_DEFAULT_CREDITS,
+    RPC_MAX_ROUNDS,
+    RPC_WS_TIMEOUT_SECS,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcError {
+    /// The endpoint throttles the requests
+    RateLimited,
+    /// The request failed (connection error, bad response, ...)
+    Failed(String),
+}
+
+pub trait RpcProvider: Debug {
+    /// Send the JSON-RPC request `data`, returning the response body
+    fn send(&self, client: &blocking::Client, data: &str) -> Result<String, RpcError>;
+
+    /// Send the JSON-RPC requests `data`, returning the response bodies in the
+    /// same order. Transports supporting it pipeline the requests.
+    fn send_batch(&self, client: &blocking::Client, data: &[String]) -> Result<Vec<String>, RpcError> {
+        data.iter().map(|d| self.send(client, d)).collect()
+    }
+
+    /// URL of the endpoint currently used
+    fn endpoint(&self) -> String;
+}
+
+/// Provider over multiple endpoints, requests go to the current endpoint and
+/// fail over to the next one when it errors or throttles. After each round
+/// over all endpoints, it backs off exponentially.
+///
+/// Endpoints are either HTTP or WebSocket (`ws://`, `wss://`) URLs. WebSocket
+/// connections are kept open and pipeline batched requests.
+#[derive(Clone, Default)]
+pub struct FailoverProvider {
+    endpoints: Vec<String>,
+    /// Index of the current endpoint, shared by clones so that all of them
+    /// skip a failing endpoint
+    current: Arc<AtomicUsize>,
+    /// Open WebSocket connections by URL
+    ws_sockets: Arc<Mutex<HashMap<String, WebSocket<MaybeTlsStream<TcpStream>>>>>,
+}
+
+impl Debug for FailoverProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FailoverProvider")
+            .field("endpoints", &self.endpoints)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+impl FailoverProvider {
+    pub fn new(endpoints: Vec<String>) -> Self {
+        Self {
+            endpoints,
+            current: Arc::new(AtomicUsize::new(0)),
+            ws_sockets: Default::default(),
+        }
+    }
+
+    /// Create a provider from comma separated endpoint URLs
+    pub fn from_urls(urls: &str) -> Self {
+        Self::new(
+            urls.split(',')
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+                .collect(),
+        )
+    }
+
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    /// Run `request` on the current endpoint, failing over to the next ones
+    fn with_failover<T>(&self, request: impl Fn(&str) -> Result<T, RpcError>) -> Result<T, RpcError> {
+        if self.endpoints.is_empty() {
+            return Err(RpcError::Failed("no rpc endpoint".to_string()));
+        }
+
+        let mut backoff = RPC_BACKOFF_INITIAL_MS;
+        let mut last_err = RpcError::Failed("no attempt".to_string());
+        for round in 1..=RPC_MAX_ROUNDS {
+            for _ in 0..self.endpoints.len() {
+                let idx = self.current.load(Ordering::Relaxed) % self.endpoints.len();
+                let url = &self.endpoints[idx];
+                match request(url) {
+                    Ok(t) => return Ok(t),
+                    Err(e) => {
+                        debug!("rpc request to {} failed: {:?}", url, e);
+                        last_err = e;
+                    }
+                }
+
+                // fail over to the next endpoint
+                let _ = self.current.compare_exchange(
+                    idx,
+                    (idx + 1) % self.endpoints.len(),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+            }
+            if round < RPC_MAX_ROUNDS {
+                warn!("all rpc endpoints failed ({:?}), retrying in {}ms", last_err, backoff);
+                thread::sleep(Duration::from_millis(backoff));
+                backoff = (backoff * 2).min(RPC_BACKOFF_MAX_MS);
+            }
+        }
+        Err(last_err)
+    }
+
+    fn send_to(&self, client: &blocking::Client, url: &str, data: &str) -> Result<String, RpcError> {
+        if is_ws_url(url) {
+            return self.send_ws(url, &[data.to_string()]).map(|mut resps| resps.remove(0));
+        }
+        let resp = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(data.to_string())
+            .send()
+            .map_err(|e| RpcError::Failed(e.to_string()))?;
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(RpcError::RateLimited);
+        }
+        let text = resp.text().map_err(|e| RpcError::Failed(e.to_string()))?;
+        if is_rate_limited(&text) {
+            return Err(RpcError::RateLimited);
+        }
+        Ok(text)
+    }
+
+    /// Send the requests over the WebSocket connection to `url`, all of them
+    /// are written before reading the responses
+    fn send_ws(&self, url: &str, data: &[String]) -> Result<Vec<String>, RpcError> {
+        let mut sockets = self.ws_sockets.lock().unwrap();
+        if !sockets.contains_key(url) {
+            let (socket, _) = tungstenite::connect(url).map_err(|e| RpcError::Failed(e.to_string()))?;
+            let stream = match socket.get_ref() {
+                MaybeTlsStream::Plain(stream) => stream,
+                MaybeTlsStream::Rustls(stream) => stream.get_ref(),
+                _ => return Err(RpcError::Failed(format!("unsupported websocket stream to {}", url))),
+            };
+            stream
+                .set_read_timeout(Some(Duration::from_secs(RPC_WS_TIMEOUT_SECS)))
+                .map_err(|e| RpcError::Failed(e.to_string()))?;
+            sockets.insert(url.to_string(), socket);
+        }
+        let result = pipeline(sockets.get_mut(url).unwrap(), data);
+        if matches!(result, Err(RpcError::Failed(_))) {
+            // reconnect on the next request
+            sockets.remove(url);
+        }
+        result
+    }
+}
+
+fn is_ws_url(url: &str) -> bool {
+    url.starts_with("ws://") || url.starts_with("wss://")
+}
+
+/// Write all requests to the socket, then match the responses with their
+/// requests by id, as they may arrive in any order
+fn pipeline(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, data: &[String]) -> Result<Vec<String>, RpcError> {
+    let ws_err = |e: tungstenite::Error| RpcError::Failed(e.to_string());
+    for (idx, req) in data.iter().enumerate() {
+        let mut req: Value = serde_json::from_str(req).map_err(|e| RpcError::Failed(e.to_string()))?;
+        req["id"] = json!(idx);
+        socket.write(Message::Text(req.to_string())).map_err(ws_err)?;
+    }
+    socket.flush().map_err(ws_err)?;
+
+    let mut responses: Vec<Option<String>> = vec![None; data.len()];
+    let mut remaining = data.len();
+    while remaining > 0 {
+        let text = match socket.read().map_err(ws_err)? {
+            Message::Text(text) => text,
+            Message::Binary(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+            // pings are answered by tungstenite
+            _ => continue,
+        };
+        if is_rate_limited(&text) {
+            return Err(RpcError::RateLimited);
+        }
+        let id = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|resp| resp["id"].as_u64())
+            .map(|id| id as usize);
+        match id {
+            Some(id) if id < responses.len() && responses[id].is_none() => {
+                responses[id] = Some(text);
+                remaining -= 1;
+            }
+            _ => debug!("unexpected websocket response: {}", text),
+        }
+    }
+    Ok(responses.into_iter().flatten().collect())
+}
+
+/// Whether the response body reports throttling, as some nodes reply with
+/// status 200 and a JSON-RPC error
+fn is_rate_limited(body: &str) -> bool {
+    let body = body.to_lowercase();
+    body.contains("rate limit") || body.contains("too many requests")
+}
+
+/// Credits (compute units) charged by providers for an RPC method, following
+/// the pricing of the common providers
+pub fn rpc_method_credits(method: &str) -> u64 {
+    match method {
+        "eth_chainId" | "net_version" => 0,
+        "eth_blockNumber" => 10,
+        "eth_getBlockByNumber" => 16,
+        "eth_getStorageAt" => 17,
+        "eth_getBalance" => 19,
+        "eth_getCode" | "eth_call" => 26,
+        "debug_storageRangeAt" => 40,
+        _ => RPC_DEFAULT_CREDITS,
+    }
+}
+
+/// RPC requests and credits consumed in a campaign, shared by the clones of
+/// [`super::endpoints::OnChainConfig`]. Once the budget is exhausted, no more
+/// requests are sent and only cached responses are served.
+#[derive(Debug, Default)]
+pub struct RpcBudget {
+    requests: AtomicU64,
+    credits: AtomicU64,
+    /// Maximum number of credits to consume, unlimited if `None`
+    limit: Option<u64>,
+    exhausted: AtomicBool,
+}
+
+impl RpcBudget {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    /// Account for a request of `method`, returns false if it would exceed
+    /// the budget, in which case the request must not be sent
+    pub fn spend(&self, method: &str) -> bool {
+        if self.is_exhausted() {
+            return false;
+        }
+        let credits = rpc_method_credits(method);
+        if let Some(limit) = self.limit {
+            if self.credits.load(Ordering::Relaxed) + credits > limit {
+                if !self.exhausted.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "rpc budget of {} credits exhausted, only serving cached responses from now on",
+                        limit
+                    );
+                }
+                return false;
+            }
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.credits.fetch_add(credits, Ordering::Relaxed);
+        true
+    }
+
+    /// Account for the JSON-RPC request `data`
+    pub fn spend_request(&self, data: &str) -> bool {
+        let method = serde_json::from_str::<Value>(data)
+            .ok()
+            .and_then(|req| req["method"].as_str().map(|m| m.to_string()))
+            .unwrap_or_default();
+        self.spend(&method)
+    }
+
+    /// Whether the budget is exhausted, i.e., only cached responses are served
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn credits(&self) -> u64 {
+        self.credits.load(Ordering::Relaxed)
+    }
+}
+
+impl Display for RpcBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rpc: {} reqs, {}", self.requests(), self.credits())?;
+        if let Some(limit) = self.limit {
+            write!(f, "/{}", limit)?;
+        }
+        write!(f, " credits")?;
+        if self.is_exhausted() {
+            write!(f, " (cache only)")?;
+        }
+        Ok(())
+    }
+}
+
+impl RpcProvider for FailoverProvider {
+    fn send(&self, client: &blocking::Client, data: &str) -> Result<String, RpcError> {
+        self.with_failover(|url| self.send_to(client, url, data))
+    }
+
+    fn send_batch(&self, client: &blocking::Client, data: &[String]) -> Result<Vec<String>, RpcError> {
+        self.with_failover(|url| {
+            if is_ws_url(url) {
+                self.send_ws(url, data)
+            } else {
+                data.iter().map(|d| self.send_to(client, url, d)).collect()
+            }
+        })
+    }
+
+    fn endpoint(&self) -> String {
+        self.endpoints
+            .get(self.current.load(Ordering::Relaxed) % self.endpoints.len().max(1))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_failover_provider() {
+        let provider = FailoverProvider::from_urls("http://127.0.0.1:1, http://127.0.0.1:2,");
+        assert_eq!(provider.endpoints(), &["http://127.0.0.1:1", "http://127.0.0.1:2"]);
+        assert_eq!(provider.endpoint(), "http://127.0.0.1:1");
+
+        // nothing listens on these ports, so it fails over between them
+        let client = blocking::Client::new();
+        assert!(provider.send(&client, "{}").is_err());
+        assert_eq!(provider.clone().endpoint(), provider.endpoint());
+    }
+
+    #[test]
+    fn test_single_endpoint_backoff() {
+        let provider = FailoverProvider::from_urls("http://127.0.0.1:1");
+        let attempts = AtomicUsize::new(0);
+        let start = Instant::now();
+        let result: Result<(), RpcError> = provider.with_failover(|_| {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(RpcError::Failed("refused".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::Relaxed), RPC_MAX_ROUNDS);
+        // no backoff after the last round
+        assert!(start.elapsed() < Duration::from_millis(RPC_BACKOFF_INITIAL_MS * 4));
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        assert!(is_rate_limited("Max rate limit reached"));
+        assert!(is_rate_limited(
+            r#"{"jsonrpc":"2.0","error":{"code":429,"message":"Too Many Requests"}}"#
+        ));
+        assert!(!is_rate_limited(r#"{"jsonrpc":"2.0","result":"0x1"}"#));
+    }
+
+    #[test]
+    fn test_ws_failover() {
+        let provider = FailoverProvider::from_urls("ws://127.0.0.1:1,http://127.0.0.1:2");
+        assert!(is_ws_url(&provider.endpoint()));
+        let client = blocking::Client::new();
+        let data = vec!["{\"id\":1}".to_string(), "{\"id\":1}".to_string()];
+        assert!(provider.send_batch(&client, &data).is_err());
+    }
+
+    #[test]
+    fn test_rpc_budget() {
+        let budget = RpcBudget::new(Some(50));
+        assert!(budget.spend("eth_getCode"));
+        assert!(budget.spend_request(r#"{"jsonrpc":"2.0","method":"eth_getStorageAt","params":[],"id":1}"#));
+        assert_eq!(budget.credits(), 43);
+        // exceeds the budget, which then stays exhausted
+        assert!(!budget.spend("eth_call"));
+        assert!(budget.is_exhausted());
+        assert!(!budget.spend("eth_chainId"));
+        assert_eq!(budget.requests(), 2);
+        assert_eq!(budget.to_string(), "rpc: 2 reqs, 43/50 credits (cache only)");
+
+        let unlimited = RpcBudget::new(None);
+        assert!(unlimited.spend("eth_call"));
+        assert_eq!(unlimited.to_string(), "rpc: 1 reqs, 26 credits");
+    }
+}
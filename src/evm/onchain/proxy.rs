@@ -0,0 +1,272 @@
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+use tracing::info;
+
+use crate::evm::{
+    bytecode_analyzer::find_constants,
+    bytecode_iterator::all_bytecode,
+    contract_utils::{ABIConfig, ContractLoader},
+    onchain::{endpoints::OnChainConfig, signatures::decompile_abi},
+    types::{convert_u256_to_h160, EVMAddress, EVMU256},
+};
+
+/// EIP-1167 runtime code before the address of the implementation
+const MINIMAL_PROXY_PREFIX: [u8; 10] = [0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+/// EIP-1167 runtime code after the address of the implementation
+const MINIMAL_PROXY_SUFFIX: [u8; 15] = [
+    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3,
+];
+/// EIP-1967 slot of the implementation,
+/// `keccak256("eip1967.proxy.implementation") - 1`
+pub(crate) const EIP1967_IMPLEMENTATION_SLOT: &str = "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+/// EIP-1967 slot of the beacon, `keccak256("eip1967.proxy.beacon") - 1`
+const EIP1967_BEACON_SLOT: &str = "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+/// Selector of `implementation()` of beacons
+const IMPLEMENTATION: [u8; 4] = [0x5c, 0x60, 0xda, 0x1b];
+/// Selector of the EIP-2535 loupe function `facetAddresses()`
+const FACET_ADDRESSES: [u8; 4] = [0x52, 0xef, 0x6b, 0x2c];
+/// Selector of the EIP-2535 loupe function `facetFunctionSelectors(address)`
+const FACET_FUNCTION_SELECTORS: [u8; 4] = [0xad, 0xfc, 0xa1, 0x5e];
+/// Storage slot of the selectors of the EIP-2535 reference implementation,
+/// `keccak256("diamond.standard.diamond.storage")`
+const DIAMOND_STORAGE_POSITION: &str = "c8fcad8db84d3cc18b4c41d551ea0ee66dd599cde068d998e57d5e09332c131c";
+
+/// How a proxy stores the address of its implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// EIP-1167 minimal proxy, the implementation is in the code
+    Minimal(EVMAddress),
+    /// EIP-1967 proxy, the implementation is in the implementation slot
+    Eip1967,
+    /// EIP-1967 beacon proxy, the implementation is returned by the beacon
+    Beacon,
+}
+
+pub(crate) fn slot(hex_slot: &str) -> EVMU256 {
+    EVMU256::from_str_radix(hex_slot, 16).unwrap()
+}
+
+/// Detect proxies from their runtime code
+pub fn detect_proxy(code: &[u8]) -> Option<ProxyKind> {
+    let minimal_len = MINIMAL_PROXY_PREFIX.len() + 20 + MINIMAL_PROXY_SUFFIX.len();
+    if code.len() == minimal_len && code.starts_with(&MINIMAL_PROXY_PREFIX) && code.ends_with(&MINIMAL_PROXY_SUFFIX) {
+        let start = MINIMAL_PROXY_PREFIX.len();
+        return Some(ProxyKind::Minimal(EVMAddress::from_slice(&code[start..start + 20])));
+    }
+
+    let constants = find_constants(&Bytecode::new_raw(Bytes::copy_from_slice(code)));
+    let pushes = |hex_slot: &str| constants.contains(&slot(hex_slot).to_be_bytes::<32>().to_vec());
+    if pushes(EIP1967_IMPLEMENTATION_SLOT) {
+        Some(ProxyKind::Eip1967)
+    } else if pushes(EIP1967_BEACON_SLOT) {
+        Some(ProxyKind::Beacon)
+    } else {
+        None
+    }
+}
+
+/// Whether the runtime code looks like an EIP-2535 diamond: it routes the
+/// calls through DELEGATECALL and uses the diamond storage or implements the
+/// loupe functions itself
+pub fn is_diamond(code: &[u8]) -> bool {
+    if !all_bytecode(&code.to_vec()).iter().any(|(_, op)| *op == 0xf4) {
+        return false;
+    }
+    let constants = find_constants(&Bytecode::new_raw(Bytes::copy_from_slice(code)));
+    constants.contains(&slot(DIAMOND_STORAGE_POSITION).to_be_bytes::<32>().to_vec()) ||
+        constants.contains(&FACET_ADDRESSES.to_vec())
+}
+
+/// Address of the implementation of the proxy at `proxy` on chain, None if
+/// it is not a proxy or the implementation is not set
+pub fn resolve_implementation(onchain: &mut OnChainConfig, proxy: EVMAddress, code: &[u8]) -> Option<EVMAddress> {
+    let implementation = match detect_proxy(code)? {
+        ProxyKind::Minimal(implementation) => implementation,
+        ProxyKind::Eip1967 => {
+            convert_u256_to_h160(onchain.get_contract_slot(proxy, slot(EIP1967_IMPLEMENTATION_SLOT), false))
+        }
+        ProxyKind::Beacon => {
+            let beacon = convert_u256_to_h160(onchain.get_contract_slot(proxy, slot(EIP1967_BEACON_SLOT), false));
+            if beacon == EVMAddress::zero() {
+                return None;
+            }
+            let out = onchain.eth_call(beacon, Bytes::from(IMPLEMENTATION.to_vec()));
+            if out.len() != 32 {
+                return None;
+            }
+            EVMAddress::from_slice(&out[12..])
+        }
+    };
+    (implementation != EVMAddress::zero()).then_some(implementation)
+}
+
+/// ABI of the implementation of the proxy at `proxy`, fetched from the
+/// explorer or decompiled. Empty if it is not a proxy.
+pub fn implementation_abi(onchain: &mut OnChainConfig, proxy: EVMAddress, code: &[u8]) -> Vec<ABIConfig> {
+    let implementation = match resolve_implementation(onchain, proxy, code) {
+        Some(implementation) => implementation,
+        None => return vec![],
+    };
+    info!("{:?} is a proxy of {:?}", proxy, implementation);
+    contract_abi(onchain, implementation)
+}
+
+/// ABI of the contract at `address`, fetched from the explorer or decompiled
+pub fn contract_abi(onchain: &mut OnChainConfig, address: EVMAddress) -> Vec<ABIConfig> {
+    match onchain.fetch_abi(address) {
+        Some(abi) => ContractLoader::parse_abi_str(&abi),
+        None => {
+            let code = onchain.get_contract_code(address, false);
+            decompile_abi(onchain, code)
+        }
+    }
+}
+
+/// Words of an ABI encoded dynamic array returned by a function, None if
+/// malformed
+fn decode_word_array(out: &[u8]) -> Option<Vec<&[u8]>> {
+    let word = |offset: usize| -> Option<usize> {
+        let word = out.get(offset..offset.checked_add(32)?)?;
+        if word[..24].iter().any(|b| *b != 0) {
+            return None;
+        }
+        Some(u64::from_be_bytes(word[24..].try_into().unwrap()) as usize)
+    };
+    let start = word(0)?;
+    let len = word(start)?;
+    (0..len)
+        .map(|i| {
+            let begin = i.checked_add(1)?.checked_mul(32)?.checked_add(start)?;
+            out.get(begin..begin.checked_add(32)?)
+        })
+        .collect()
+}
+
+/// Facets of the EIP-2535 diamond at `diamond` on chain with the selectors
+/// routed to each, queried through the loupe functions. Empty if it is not a
+/// diamond.
+pub fn diamond_facets(
+    onchain: &mut OnChainConfig,
+    diamond: EVMAddress,
+    code: &[u8],
+) -> Vec<(EVMAddress, Vec<[u8; 4]>)> {
+    if !is_diamond(code) {
+        return vec![];
+    }
+    let out = onchain
+        .probe_call(diamond, Bytes::from(FACET_ADDRESSES.to_vec()))
+        .unwrap_or_default();
+    let facets = match decode_word_array(&out) {
+        Some(facets) => facets
+            .iter()
+            .map(|word| EVMAddress::from_slice(&word[12..]))
+            .collect::<Vec<_>>(),
+        None => return vec![],
+    };
+
+    let mut res = vec![];
+    for facet in facets {
+        let calldata = [FACET_FUNCTION_SELECTORS.to_vec(), vec![0; 12], facet.0.to_vec()].concat();
+        let out = onchain.probe_call(diamond, Bytes::from(calldata)).unwrap_or_default();
+        let selectors = decode_word_array(&out)
+            .unwrap_or_default()
+            .iter()
+            .map(|word| word[..4].try_into().unwrap())
+            .collect::<Vec<[u8; 4]>>();
+        if !selectors.is_empty() {
+            res.push((facet, selectors));
+        }
+    }
+    res
+}
+
+/// ABI of the functions the EIP-2535 diamond at `diamond` routes to its
+/// facets, fetched from the explorer or decompiled. Empty if it is not a
+/// diamond.
+pub fn diamond_abi(onchain: &mut OnChainConfig, diamond: EVMAddress, code: &[u8]) -> Vec<ABIConfig> {
+    let mut res = vec![];
+    for (facet, selectors) in diamond_facets(onchain, diamond, code) {
+        info!("{:?} is a diamond with facet {:?}", diamond, facet);
+        let abi = contract_abi(onchain, facet);
+        res.extend(abi.into_iter().filter(|abi| selectors.contains(&abi.function)));
+    }
+    res
+}
+
+/// ABI of the functions the contract at `address` delegates to other
+/// contracts: the implementation of a proxy or the facets of a diamond
+pub fn delegated_abi(onchain: &mut OnChainConfig, address: EVMAddress, code: &[u8]) -> Vec<ABIConfig> {
+    match detect_proxy(code) {
+        Some(_) => implementation_abi(onchain, address, code),
+        None => diamond_abi(onchain, address, code),
+    }
+}
+
+/// ABI of a proxy fuzzed with the functions of its implementation, the
+/// functions of the proxy taking precedence
+pub fn merge_abis(proxy: Vec<ABIConfig>, implementation: Vec<ABIConfig>) -> Vec<ABIConfig> {
+    let mut res = proxy;
+    for abi in implementation {
+        if !abi.is_constructor && !res.iter().any(|existing| existing.function == abi.function) {
+            res.push(abi);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_proxy() {
+        let implementation = EVMAddress::from_slice(&[0xbe; 20]);
+        let minimal = [&MINIMAL_PROXY_PREFIX[..], &[0xbe; 20], &MINIMAL_PROXY_SUFFIX[..]].concat();
+        assert_eq!(detect_proxy(&minimal), Some(ProxyKind::Minimal(implementation)));
+
+        // PUSH32 implementation slot SLOAD STOP
+        let eip1967 = [
+            vec![0x7f],
+            hex::decode(EIP1967_IMPLEMENTATION_SLOT).unwrap(),
+            vec![0x54, 0x00],
+        ]
+        .concat();
+        assert_eq!(detect_proxy(&eip1967), Some(ProxyKind::Eip1967));
+        assert_eq!(detect_proxy(&[0x60, 0x00, 0x54, 0x00]), None);
+    }
+
+    #[test]
+    fn test_decode_word_array() {
+        let word = |value: u8| [vec![0; 31], vec![value]].concat();
+        let out = [word(0x20), word(2), word(1), word(2)].concat();
+        let words = decode_word_array(&out).unwrap();
+        assert_eq!(words, vec![&word(1)[..], &word(2)[..]]);
+        // length past the end of the output
+        let out = [word(0x20), word(3), word(1), word(2)].concat();
+        assert!(decode_word_array(&out).is_none());
+        assert!(decode_word_array(&[]).is_none());
+        // offset overflowing
+        assert!(decode_word_array(&[vec![0; 24], vec![0xff; 8]].concat()).is_none());
+        let out = [word(0x20), [vec![0; 24], vec![0xff; 8]].concat()].concat();
+        assert!(decode_word_array(&out).is_none());
+    }
+
+    #[test]
+    fn test_is_diamond() {
+        // PUSH32 diamond storage SLOAD DELEGATECALL
+        let diamond = [
+            vec![0x7f],
+            hex::decode(DIAMOND_STORAGE_POSITION).unwrap(),
+            vec![0x54, 0xf4],
+        ]
+        .concat();
+        assert!(is_diamond(&diamond));
+        // PUSH4 facetAddresses() EQ DELEGATECALL
+        assert!(is_diamond(
+            &[vec![0x63], FACET_ADDRESSES.to_vec(), vec![0x14, 0xf4]].concat()
+        ));
+        // any contract delegating
+        assert!(!is_diamond(&[0x60, 0x00, 0x54, 0xf4]));
+        assert!(!is_diamond(&diamond[..diamond.len() - 1]));
+    }
+}
@@ -0,0 +1,121 @@
+//! Interfaces of the contracts whose source is not verified on the explorer:
+//! the ABI is looked up on Sourcify, and the functions recovered from the
+//! bytecode are named after their signature on 4byte.directory. The lookups
+//! are best effort (see [`OnChainConfig::lookup`]) and can be turned off with
+//! `--no-signature-lookup`.
+
+use serde_json::Value;
+use tracing::debug;
+
+use crate::evm::{
+    contract_utils::{set_hash, ABIConfig},
+    onchain::{abi_decompiler::fetch_abi_evmole, endpoints::OnChainConfig},
+    types::EVMAddress,
+};
+
+const SOURCIFY_URL: &str = "https://sourcify.dev/server/v2/contract";
+const FOURBYTE_URL: &str = "https://www.4byte.directory/api/v1/signatures/";
+
+/// ABI in the response of Sourcify, None if the contract is not verified
+fn parse_sourcify_abi(resp: &str) -> Option<String> {
+    let json = serde_json::from_str::<Value>(resp).ok()?;
+    json.get("abi").filter(|abi| abi.is_array()).map(|abi| abi.to_string())
+}
+
+/// Signature of `selector` in the response of 4byte.directory, the oldest
+/// one hashing to it if they collide
+fn parse_4byte_signature(resp: &str, selector: [u8; 4]) -> Option<String> {
+    let json = serde_json::from_str::<Value>(resp).ok()?;
+    json["results"]
+        .as_array()?
+        .iter()
+        .filter_map(|result| Some((result["id"].as_u64()?, result["text_signature"].as_str()?)))
+        .filter(|(_, signature)| {
+            let mut hash = [0; 4];
+            set_hash(signature, &mut hash);
+            hash == selector
+        })
+        .min_by_key(|(id, _)| *id)
+        .map(|(_, signature)| signature.to_string())
+}
+
+/// Whether the function is still named after its selector, i.e., its
+/// signature is unknown
+pub fn is_unnamed(abi: &ABIConfig) -> bool {
+    abi.function_name == hex::encode(abi.function)
+}
+
+impl OnChainConfig {
+    /// ABI of the contract verified on Sourcify, None if it is not
+    pub fn fetch_sourcify_abi(&self, address: EVMAddress) -> Option<String> {
+        let url = format!("{}/{}/{:?}?fields=abi", SOURCIFY_URL, self.chain_id, address);
+        debug!("fetching abi from {}", url);
+        parse_sourcify_abi(&self.lookup(url)?)
+    }
+
+    /// Signature of the function `selector` on 4byte.directory, e.g.,
+    /// `transfer(address,uint256)`
+    pub fn fetch_signature(&self, selector: [u8; 4]) -> Option<String> {
+        let url = format!("{}?hex_signature=0x{}", FOURBYTE_URL, hex::encode(selector));
+        parse_4byte_signature(&self.lookup(url)?, selector)
+    }
+}
+
+/// Name the unnamed functions of `abis` and type their args after their
+/// signature on 4byte.directory
+pub fn resolve_signatures(onchain: &OnChainConfig, abis: &mut [ABIConfig]) {
+    for abi in abis.iter_mut().filter(|abi| is_unnamed(abi)) {
+        let Some(signature) = onchain.fetch_signature(abi.function) else {
+            continue;
+        };
+        let Some((name, args)) = signature.split_once('(') else {
+            continue;
+        };
+        debug!("0x{} is {}", abi.function_name, signature);
+        abi.function_name = name.to_string();
+        abi.abi = format!("({}", args);
+    }
+}
+
+/// ABI of the contract with code `bytecode` without verified source,
+/// decompiled and named after the signatures on 4byte.directory
+pub fn decompile_abi(onchain: &OnChainConfig, bytecode: String) -> Vec<ABIConfig> {
+    let mut abis = fetch_abi_evmole(bytecode);
+    resolve_signatures(onchain, &mut abis);
+    abis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_responses() {
+        let resp = r#"{"count": 2, "results": [
+            {"id": 31780, "text_signature": "many_msg_babbage(bytes1)"},
+            {"id": 145, "text_signature": "transfer(address,uint256)"},
+            {"id": 7, "text_signature": "transfer(address,uint256)"}
+        ]}"#;
+        assert_eq!(
+            parse_4byte_signature(resp, [0xa9, 0x05, 0x9c, 0xbb]).as_deref(),
+            Some("transfer(address,uint256)")
+        );
+        assert_eq!(parse_4byte_signature(resp, [0x12, 0x34, 0x56, 0x78]), None);
+
+        assert_eq!(
+            parse_sourcify_abi(r#"{"abi": [{"type": "fallback"}], "match": "exact_match"}"#).as_deref(),
+            Some(r#"[{"type":"fallback"}]"#)
+        );
+        assert_eq!(parse_sourcify_abi(r#"{"customCode": "not_found"}"#), None);
+    }
+
+    #[test]
+    fn test_lookup_disabled() {
+        let onchain = OnChainConfig {
+            signature_lookup: false,
+            ..Default::default()
+        };
+        assert_eq!(onchain.fetch_signature([0xa9, 0x05, 0x9c, 0xbb]), None);
+        assert_eq!(onchain.fetch_sourcify_abi(EVMAddress::zero()), None);
+    }
+}
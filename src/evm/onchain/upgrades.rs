@@ -0,0 +1,333 @@
+//! Hot-reload of the ABI of the proxies fuzzed when they are upgraded on
+//! chain (monitoring mode): the implementation of the EIP-1967 proxies is
+//! polled at the latest block, and when it changes, the new implementation is
+//! fetched (or built), its functions replace those of the old one in
+//! `FUNCTION_SIG` and the ABI maps, and the corpus is migrated by dropping the
+//! inputs calling removed functions, without restarting the campaign. Beacon
+//! proxies are not watched.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use libafl::{
+    corpus::Corpus,
+    events::ProgressReporter,
+    prelude::{CorpusId, HasMetadata, ObserversTuple, Stage},
+    schedulers::RemovableScheduler,
+    state::{HasCorpus, UsesState},
+    Error,
+    Evaluator,
+};
+use revm_interpreter::analysis::to_analysed;
+use revm_primitives::Bytecode;
+use tracing::{info, warn};
+
+use crate::{
+    evm::{
+        abi::{get_abi_type_boxed, register_abi_instance, ABIAddressToInstanceMap},
+        blaz::builder::{ArtifactInfoMetadata, BuildJob},
+        bytecode_analyzer,
+        contract_utils::{ABIConfig, ContractLoader},
+        corpus_initializer::EnvMetadata,
+        input::{EVMInput, EVMInputTy},
+        middlewares::middleware::add_corpus,
+        mutator::AccessPattern,
+        onchain::{
+            endpoints::OnChainConfig,
+            proxy::{self, ProxyKind, EIP1967_IMPLEMENTATION_SLOT},
+        },
+        types::{EVMAddress, EVMFuzzExecutor, EVMFuzzState, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    state::{HasCaller, HasInfantStateState},
+    state_input::StagedVMState,
+};
+
+/// Point the EIP-1967 proxy at `proxy` to `implementation` in `state`
+fn set_implementation(state: &mut EVMState, proxy: EVMAddress, implementation: EVMAddress) {
+    state.sstore(
+        proxy,
+        proxy::slot(EIP1967_IMPLEMENTATION_SLOT),
+        EVMU256::from_be_slice(implementation.as_bytes()),
+    );
+}
+
+/// Selectors of `old` not in `new`
+fn removed_functions(old: &[ABIConfig], new: &[ABIConfig]) -> HashSet<[u8; 4]> {
+    let new = new.iter().map(|abi| abi.function).collect::<HashSet<_>>();
+    old.iter()
+        .map(|abi| abi.function)
+        .filter(|function| !new.contains(function))
+        .collect()
+}
+
+/// Periodically checks the implementation of the watched proxies at the latest
+/// block and reloads the ABI of the upgraded ones
+pub struct UpgradeWatchStage<OT> {
+    /// Config of the forked chain, None if the upgrades are not watched
+    onchain: Option<OnChainConfig>,
+    builder: Option<BuildJob>,
+    interval: Duration,
+    last_check: Instant,
+    /// Current implementation of each watched proxy
+    implementations: HashMap<EVMAddress, EVMAddress>,
+    executor: Rc<RefCell<EVMQueueExecutor>>,
+    pub phantom: PhantomData<OT>,
+}
+
+impl<OT> UsesState for UpgradeWatchStage<OT> {
+    type State = EVMFuzzState;
+}
+
+impl<OT> UpgradeWatchStage<OT> {
+    /// A stage doing nothing when `onchain` is None or `interval` is zero,
+    /// watching the EIP-1967 proxies among `contracts`
+    pub fn new(
+        onchain: Option<OnChainConfig>,
+        builder: Option<BuildJob>,
+        interval: Duration,
+        contracts: &HashMap<EVMAddress, Bytecode>,
+        executor: Rc<RefCell<EVMQueueExecutor>>,
+    ) -> Self {
+        let onchain = onchain.filter(|_| !interval.is_zero());
+        let mut implementations = HashMap::new();
+        if let Some(mut onchain) = onchain.clone() {
+            for (address, code) in contracts {
+                let code = code.bytes().to_vec();
+                if proxy::detect_proxy(&code) != Some(ProxyKind::Eip1967) {
+                    continue;
+                }
+                if let Some(implementation) = proxy::resolve_implementation(&mut onchain, *address, &code) {
+                    info!("Watching the upgrades of {:?}", address);
+                    implementations.insert(*address, implementation);
+                }
+            }
+        }
+        Self {
+            onchain,
+            builder,
+            interval,
+            last_check: Instant::now(),
+            implementations,
+            executor,
+            phantom: PhantomData,
+        }
+    }
+
+    /// ABI of the new implementation, built when a builder is set
+    fn implementation_abi(
+        &self,
+        latest: &mut OnChainConfig,
+        state: &mut EVMFuzzState,
+        implementation: EVMAddress,
+    ) -> Vec<ABIConfig> {
+        let job = self
+            .builder
+            .as_ref()
+            .and_then(|builder| builder.onchain_job(latest.chain_name.clone(), implementation));
+        match job {
+            Some(job) => {
+                state
+                    .metadata_map_mut()
+                    .get_mut::<ArtifactInfoMetadata>()
+                    .expect("artifact info metadata")
+                    .add(implementation, job.clone());
+                job.save_source_map(&implementation);
+                ContractLoader::parse_abi_str(&job.abi)
+            }
+            None => proxy::contract_abi(latest, implementation),
+        }
+    }
+
+    fn upgrade(
+        &mut self,
+        latest: &mut OnChainConfig,
+        state: &mut EVMFuzzState,
+        proxy: EVMAddress,
+        old: EVMAddress,
+        new: EVMAddress,
+    ) -> Result<(), Error> {
+        info!("{:?} is upgraded from {:?} to {:?}, reloading its ABI", proxy, old, new);
+        let code = hex::decode(latest.get_contract_code(new, false)).unwrap_or_default();
+        let code = to_analysed(Bytecode::new_raw(Bytes::from(code)));
+        let old_abi = proxy::contract_abi(latest, old);
+        let new_abi = self.implementation_abi(latest, state, new);
+        let removed = removed_functions(&old_abi, &new_abi);
+
+        // the proxy delegates to the new implementation in every VM state
+        let infant_corpus = state.get_infant_state_state().corpus();
+        let mut id = infant_corpus.first();
+        while let Some(tc_id) = id {
+            if let Some(vm_state) = infant_corpus.get(tc_id)?.borrow_mut().input_mut() {
+                set_implementation(&mut vm_state.state, proxy, new);
+            }
+            id = infant_corpus.next(tc_id);
+        }
+
+        // drop the inputs calling removed functions
+        let mut dropped = vec![];
+        let mut id = state.corpus().first();
+        while let Some(tc_id) = id {
+            if let Some(input) = state.corpus().get(tc_id)?.borrow_mut().input_mut() {
+                let function = input.data.as_ref().map(|abi| abi.function);
+                if input.contract == proxy && function.is_some_and(|function| removed.contains(&function)) {
+                    dropped.push(tc_id);
+                } else {
+                    set_implementation(&mut input.sstate.state, proxy, new);
+                }
+            }
+            id = state.corpus().next(tc_id);
+        }
+        // through the scheduler, so that it forgets them
+        let mut scheduler = self.executor.borrow().host.scheduler.clone();
+        for tc_id in &dropped {
+            let testcase = state.corpus_mut().remove(*tc_id)?;
+            scheduler.on_remove(state, *tc_id, &Some(testcase))?;
+        }
+        if let Some(instances) = state
+            .metadata_map_mut()
+            .get_mut::<ABIAddressToInstanceMap>()
+            .and_then(|map| map.map.get_mut(&proxy))
+        {
+            instances.retain(|abi| !removed.contains(&abi.function));
+        }
+
+        let mut executor = self.executor.borrow_mut();
+        let host = &mut executor.host;
+        bytecode_analyzer::add_analysis_result_to_state(&code, state);
+        host.set_code(new, code, state);
+        for function in &removed {
+            if let Some(addresses) = host.hash_to_address.get_mut(function) {
+                addresses.remove(&proxy);
+            }
+        }
+        let known = host.address_to_hash.entry(proxy).or_default();
+        known.retain(|function| !removed.contains(function));
+        let known = known.iter().cloned().collect::<HashSet<_>>();
+
+        // fuzz the functions added
+        let added = new_abi
+            .iter()
+            .filter(|abi| !abi.is_constructor && !known.contains(&abi.function))
+            .collect_vec();
+        for abi in &added {
+            host.add_one_hashes(proxy, abi.function);
+            #[cfg(not(feature = "fuzz_static"))]
+            if abi.is_static {
+                continue;
+            }
+
+            let mut abi_instance = get_abi_type_boxed(&abi.abi);
+            abi_instance.set_func_with_signature(abi.function, &abi.function_name, &abi.abi);
+            register_abi_instance(proxy, abi_instance.clone(), state);
+
+            let input = EVMInput {
+                caller: state.get_rand_caller(),
+                contract: proxy,
+                data: Some(abi_instance),
+                sstate: StagedVMState::new_uninitialized(),
+                sstate_idx: 0,
+                txn_value: if abi.is_payable { Some(EVMU256::ZERO) } else { None },
+                step: false,
+                env: state.metadata_map().get::<EnvMetadata>().unwrap().env.clone(),
+                access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+                liquidation_percent: 0,
+                liquidation_path: 0,
+                input_type: EVMInputTy::ABI,
+                direct_data: Default::default(),
+                randomness: vec![0],
+                repeat: 1,
+                swap_data: HashMap::new(),
+            };
+            add_corpus(host, state, &input);
+        }
+        info!(
+            "Migrated the corpus of {:?}: {} functions added, {} removed, {} inputs dropped",
+            proxy,
+            added.len(),
+            removed.len(),
+            dropped.len()
+        );
+        Ok(())
+    }
+}
+
+impl<EM, Z, OT> Stage<EVMFuzzExecutor<OT>, EM, Z> for UpgradeWatchStage<OT>
+where
+    Z: Evaluator<EVMFuzzExecutor<OT>, EM, State = Self::State>,
+    EM: ProgressReporter + UsesState<State = Self::State>,
+    OT: ObserversTuple<Self::State>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut EVMFuzzExecutor<OT>,
+        state: &mut Self::State,
+        _manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        if self.implementations.is_empty() || self.last_check.elapsed() < self.interval {
+            return Ok(());
+        }
+        self.last_check = Instant::now();
+
+        let Some(mut latest) = self.onchain.as_ref().and_then(|onchain| onchain.at_latest_block()) else {
+            warn!("Failed to fetch the latest block, the upgrades are not checked");
+            return Ok(());
+        };
+        let upgrades = self
+            .implementations
+            .iter()
+            .filter_map(|(proxy, old)| {
+                let code = hex::decode(latest.get_contract_code(*proxy, false)).unwrap_or_default();
+                let new = proxy::resolve_implementation(&mut latest, *proxy, &code)?;
+                (new != *old).then_some((*proxy, *old, new))
+            })
+            .collect_vec();
+        for (proxy, old, new) in upgrades {
+            self.upgrade(&mut latest, state, proxy, old, new)?;
+            self.implementations.insert(proxy, new);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_migration() {
+        let proxy = EVMAddress::from_str("0x1000000000000000000000000000000000000001").unwrap();
+        let implementation = EVMAddress::from_str("0x2000000000000000000000000000000000000002").unwrap();
+        let mut state = EVMState::default();
+        set_implementation(&mut state, proxy, implementation);
+        assert_eq!(
+            state.sload(proxy, proxy::slot(EIP1967_IMPLEMENTATION_SLOT)),
+            Some(EVMU256::from_be_slice(implementation.as_bytes()))
+        );
+
+        let abi = |function: [u8; 4]| ABIConfig {
+            abi: "()".to_string(),
+            function,
+            function_name: hex::encode(function),
+            is_static: false,
+            is_payable: false,
+            is_constructor: false,
+            should_add_corpus: true,
+        };
+        assert_eq!(
+            removed_functions(&[abi([1; 4]), abi([2; 4])], &[abi([2; 4]), abi([3; 4])]),
+            HashSet::from([[1; 4]])
+        );
+    }
+}
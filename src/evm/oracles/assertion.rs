@@ -0,0 +1,223 @@
+//! Failed assertions: transactions reverting with `Panic(0x01)`, i.e., a
+//! failed `assert`, or with one of the custom errors given with
+//! `--assertion-errors`, for the targets encoding their invariants as asserts
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        onchain::keccak256,
+        oracle::EVMBugResult,
+        oracles::ASSERTION_BUG_IDX,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    input::VMInputT,
+    oracle::{Oracle, OracleCtx},
+    state::HasExecutionResult,
+};
+
+/// `Panic(uint256)`
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+/// Panic code of a failed `assert`
+const ASSERT_PANIC_CODE: u64 = 0x01;
+
+/// Split comma separated signatures, ignoring the commas between the
+/// parentheses of their parameters
+pub fn split_signatures(s: &str) -> Vec<&str> {
+    let (mut res, mut depth, mut start) = (vec![], 0usize, 0);
+    for (idx, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                res.push(&s[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    res.push(&s[start..]);
+    res
+}
+
+/// Parse the comma separated custom errors, given by selector (e.g.,
+/// `0x12345678`) or by signature (e.g., `InvariantBroken(uint256)`)
+pub fn parse_assertion_errors(s: &str) -> Result<Vec<[u8; 4]>, String> {
+    split_signatures(s)
+        .into_iter()
+        .map(|error| error.trim())
+        .filter(|error| !error.is_empty())
+        .map(|error| {
+            if error.contains('(') {
+                let hash = keccak256(error.as_bytes()).to_be_bytes::<32>();
+                return Ok([hash[0], hash[1], hash[2], hash[3]]);
+            }
+            let selector = hex::decode(error.trim_start_matches("0x"))
+                .map_err(|e| format!("invalid custom error {}: {}", error, e))?;
+            selector
+                .try_into()
+                .map_err(|_| format!("invalid custom error selector {}", error))
+        })
+        .collect()
+}
+
+/// Description of the revert data if it is a failed assertion, None
+/// otherwise
+pub fn decode_assertion_failure(output: &[u8], errors: &[[u8; 4]]) -> Option<String> {
+    let selector: [u8; 4] = output.get(..4)?.try_into().ok()?;
+    let args = &output[4..];
+    if selector == PANIC_SELECTOR {
+        let code = EVMU256::from_be_slice(args.get(..32)?);
+        return (code == EVMU256::from(ASSERT_PANIC_CODE)).then(|| "assertion failed (Panic(0x01))".to_string());
+    }
+    if !errors.contains(&selector) {
+        return None;
+    }
+    let words = args.chunks(32).map(hex::encode).join(", ");
+    Some(format!(
+        "custom error 0x{} raised with data [{}]",
+        hex::encode(selector),
+        words
+    ))
+}
+
+pub struct AssertionOracle {
+    address_to_name: HashMap<EVMAddress, String>,
+    /// Selectors of the custom errors reported as failed assertions
+    errors: Vec<[u8; 4]>,
+}
+
+impl AssertionOracle {
+    pub fn new(address_to_name: HashMap<EVMAddress, String>, errors: Vec<[u8; 4]>) -> Self {
+        Self {
+            address_to_name,
+            errors,
+        }
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for AssertionOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        let result = ctx.fuzz_state.get_execution_result();
+        if !result.reverted || ctx.input.is_step() {
+            return vec![];
+        }
+        let Some(description) = decode_assertion_failure(&result.output, &self.errors) else {
+            return vec![];
+        };
+
+        let addr = ctx.input.get_contract();
+        let function = ctx.input.get_data_abi().map(|abi| abi.function).unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        addr.hash(&mut hasher);
+        function.hash(&mut hasher);
+        result.output[..4].hash(&mut hasher);
+        let bug_idx = (hasher.finish() << 8) + ASSERTION_BUG_IDX;
+
+        let name = self
+            .address_to_name
+            .get(&addr)
+            .cloned()
+            .unwrap_or(format!("{:?}", addr));
+        EVMBugResult::new(
+            "Assertion Failure".to_string(),
+            bug_idx,
+            format!(
+                "{}::0x{} reverted with {} (revert data 0x{})\n",
+                name,
+                hex::encode(function),
+                description,
+                hex::encode(&result.output)
+            ),
+            ConciseEVMInput::from_input(ctx.input, result),
+            None,
+            Some(name),
+        )
+        .push_to_output();
+        vec![bug_idx]
+    }
+
+    fn on_revert(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_assertion_failure() {
+        let errors = parse_assertion_errors("0xdeadbeef, InvariantBroken(uint256), Broken(uint256,address)").unwrap();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0], [0xde, 0xad, 0xbe, 0xef]);
+        assert!(parse_assertion_errors("0xdead").is_err());
+
+        let panic = |code: u64| {
+            [
+                PANIC_SELECTOR.to_vec(),
+                EVMU256::from(code).to_be_bytes::<32>().to_vec(),
+            ]
+            .concat()
+        };
+        assert!(decode_assertion_failure(&panic(0x01), &errors).is_some());
+        // arithmetic overflow
+        assert_eq!(decode_assertion_failure(&panic(0x11), &errors), None);
+
+        let custom = [errors[1].to_vec(), vec![0u8; 31], vec![7]].concat();
+        assert_eq!(
+            decode_assertion_failure(&custom, &errors),
+            Some(format!(
+                "custom error 0x{} raised with data [{}07]",
+                hex::encode(errors[1]),
+                "00".repeat(31)
+            ))
+        );
+        // Error(string) of a require
+        assert_eq!(decode_assertion_failure(&[0x08, 0xc3, 0x79, 0xa0], &errors), None);
+        assert_eq!(decode_assertion_failure(&[], &errors), None);
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
+
+use crate::evm::{
+    contract_utils::ContractInfo,
+    oracles::{
+        post_state::{PostStateBug, PostStateOracle},
+        CONTRACT_SIZE_BUG_IDX,
+    },
+    types::EVMAddress,
+    vm::EVMState,
+};
+
+/// Maximum size of the runtime code (EIP-170)
+pub const MAX_CODE_SIZE: usize = 0x6000;
+/// Maximum size of the init code (EIP-3860)
+pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+/// Sizes and deployment cost of a locally built contract
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractSize {
+    pub init_size: usize,
+    pub runtime_size: usize,
+    /// Gas of the deployment transaction, excluding the execution of the
+    /// constructor
+    pub deployment_gas: u64,
+}
+
+impl ContractSize {
+    /// `init_code` includes the constructor arguments
+    pub fn new(init_code: &[u8], runtime_size: usize) -> Self {
+        let zeros = init_code.iter().filter(|b| **b == 0).count() as u64;
+        let calldata_gas = zeros * 4 + (init_code.len() as u64 - zeros) * 16;
+        // transaction + CREATE + init code words (EIP-3860) + code deposit
+        let deployment_gas =
+            21000 + 32000 + calldata_gas + 2 * ((init_code.len() as u64 + 31) / 32) + 200 * runtime_size as u64;
+        Self {
+            init_size: init_code.len(),
+            runtime_size,
+            deployment_gas,
+        }
+    }
+
+    /// Sizes of a contract built locally, its code already ends with the
+    /// constructor arguments
+    pub fn of_contract(contract: &ContractInfo, runtime_size: usize) -> Self {
+        Self::new(&contract.code, runtime_size)
+    }
+
+    /// Size limits exceeded, the contract can not be deployed on chain if any
+    pub fn violations(&self) -> Vec<String> {
+        let mut res = vec![];
+        if self.runtime_size > MAX_CODE_SIZE {
+            res.push(format!(
+                "runtime code of {} bytes exceeds the EIP-170 limit of {} bytes",
+                self.runtime_size, MAX_CODE_SIZE
+            ));
+        }
+        if self.init_size > MAX_INITCODE_SIZE {
+            res.push(format!(
+                "init code of {} bytes exceeds the EIP-3860 limit of {} bytes",
+                self.init_size, MAX_INITCODE_SIZE
+            ));
+        }
+        res
+    }
+}
+
+impl Display for ContractSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "init code {} bytes, runtime code {} bytes, deployment gas {} + constructor",
+            self.init_size, self.runtime_size, self.deployment_gas
+        )
+    }
+}
+
+/// Flags transactions executing the code of contracts exceeding the size
+/// limits. The fuzzer deploys them regardless, but they can not be deployed on
+/// chain, so the paths reaching them are unreachable in practice and the
+/// contracts need to be split.
+pub struct ContractSizeOracle {
+    pub address_to_name: HashMap<EVMAddress, String>,
+    /// Contracts exceeding the limits, with the limits exceeded
+    pub oversized: HashMap<EVMAddress, Vec<String>>,
+}
+
+impl ContractSizeOracle {
+    pub fn new(address_to_name: HashMap<EVMAddress, String>, oversized: HashMap<EVMAddress, Vec<String>>) -> Self {
+        Self {
+            address_to_name,
+            oversized,
+        }
+    }
+}
+
+impl PostStateOracle for ContractSizeOracle {
+    fn check(&self, contract: EVMAddress, post_state: &EVMState) -> Vec<PostStateBug> {
+        if self.oversized.is_empty() {
+            return vec![];
+        }
+
+        // the contract called by the transaction and the ones called by contracts
+        let mut reached = vec![contract];
+        reached.extend(post_state.call_path.iter().map(|(addr, _)| *addr));
+
+        let mut res = vec![];
+        for addr in reached {
+            let violations = match self.oversized.get(&addr) {
+                Some(violations) => violations,
+                None => continue,
+            };
+            let mut hasher = DefaultHasher::new();
+            addr.hash(&mut hasher);
+            let bug_idx = (hasher.finish() << 8) + CONTRACT_SIZE_BUG_IDX;
+            if res.iter().any(|bug: &PostStateBug| bug.bug_idx == bug_idx) {
+                continue;
+            }
+
+            let name = self
+                .address_to_name
+                .get(&addr)
+                .cloned()
+                .unwrap_or(format!("{:?}", addr));
+            res.push(PostStateBug {
+                bug_type: "Undeployable Contract".to_string(),
+                bug_idx,
+                message: format!(
+                    "{} is reached but can not be deployed on chain: {}\n",
+                    name,
+                    violations.join(", ")
+                ),
+                source: Some(name),
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contract_size() {
+        let size = ContractSize::new(&[0x60, 0x80, 0x00, 0x00], 100);
+        assert_eq!(size.deployment_gas, 21000 + 32000 + 2 * 16 + 2 * 4 + 2 + 200 * 100);
+        assert!(size.violations().is_empty());
+
+        let size = ContractSize::new(&vec![1u8; MAX_INITCODE_SIZE + 1], MAX_CODE_SIZE + 1);
+        assert_eq!(size.violations().len(), 2);
+    }
+
+    #[test]
+    fn test_constructor_args_counted_once() {
+        let args = vec![0u8; 64];
+        let contract = ContractInfo {
+            name: "C".to_string(),
+            code: [vec![0x60, 0x80], args.clone()].concat(),
+            abi: vec![],
+            is_code_deployed: false,
+            constructor_args: args,
+            deployed_address: EVMAddress::zero(),
+            build_artifact: None,
+            files: vec![],
+            source_map_replacements: None,
+            raw_source_map: None,
+        };
+        let size = ContractSize::of_contract(&contract, 10);
+        assert_eq!(size.init_size, 66);
+        assert_eq!(size, ContractSize::new(&contract.code, 10));
+    }
+}
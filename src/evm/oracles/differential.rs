@@ -0,0 +1,375 @@
+//! Differential fuzzing of an upgrade: each transaction is executed again on
+//! the same pre-state with the upgraded bytecode of the contracts (e.g., the
+//! new implementation behind a proxy), and any divergence of the return data,
+//! the emitted events or the storage written is reported, so that an upgrade
+//! can be checked to preserve the behavior of the previous version. The same
+//! oracle compares the build of the targets with another compiler
+//! configuration (see [`crate::evm::blaz::compiler_variants`]), catching
+//! compiler-induced semantic changes.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    str::FromStr,
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use revm_interpreter::BytecodeLocked;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        code_analysis::analyze,
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::DIFFERENTIAL_BUG_IDX,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    generic_vm::{vm_executor::GenericVM, vm_state::VMStateT},
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+/// Parse the upgraded bytecode of the contracts, separated by comma:
+/// `<address>=0x<runtime bytecode>` or `<address>=<path>` of a file holding
+/// the runtime bytecode in hex
+pub fn parse_upgrades(s: &str) -> Result<HashMap<EVMAddress, Bytecode>, String> {
+    let mut upgrades = HashMap::new();
+    for upgrade in s.split(',').map(str::trim).filter(|u| !u.is_empty()) {
+        let (address, code) = upgrade
+            .split_once('=')
+            .ok_or_else(|| format!("invalid upgrade {}, expected <address>=<bytecode>", upgrade))?;
+        let address = EVMAddress::from_str(address.trim()).map_err(|_| format!("invalid address {}", address))?;
+        let code = match code.trim() {
+            code if code.starts_with("0x") => code.to_string(),
+            path => fs::read_to_string(path).map_err(|e| format!("failed to read bytecode {}: {}", path, e))?,
+        };
+        let code = hex::decode(code.trim().trim_start_matches("0x"))
+            .map_err(|e| format!("invalid bytecode of {:?}: {}", address, e))?;
+        if code.is_empty() {
+            return Err(format!("empty bytecode of {:?}", address));
+        }
+        upgrades.insert(address, Bytecode::new_raw(Bytes::from(code)));
+    }
+    Ok(upgrades)
+}
+
+/// First difference between the execution of a transaction with the
+/// original bytecode and with the upgraded one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    Revert {
+        original: bool,
+    },
+    ReturnData {
+        original: Vec<u8>,
+        upgraded: Vec<u8>,
+    },
+    /// Index of the first event that differs
+    Event {
+        index: usize,
+    },
+    StorageWrite {
+        address: EVMAddress,
+        slot: EVMU256,
+        original: Option<EVMU256>,
+        upgraded: Option<EVMU256>,
+    },
+}
+
+impl Divergence {
+    fn kind(&self) -> &'static str {
+        match self {
+            Divergence::Revert { .. } => "revert",
+            Divergence::ReturnData { .. } => "return data",
+            Divergence::Event { .. } => "event",
+            Divergence::StorageWrite { .. } => "storage write",
+        }
+    }
+
+    fn describe(&self) -> String {
+        let word = |value: &Option<EVMU256>| value.map_or("unset".to_string(), |v| format!("{:#x}", v));
+        match self {
+            Divergence::Revert { original: true } => "succeeds while it reverted with the original code".to_string(),
+            Divergence::Revert { original: false } => "reverts while it succeeded with the original code".to_string(),
+            Divergence::ReturnData { original, upgraded } => format!(
+                "returns 0x{} instead of 0x{}",
+                hex::encode(upgraded),
+                hex::encode(original)
+            ),
+            Divergence::Event { index } => format!("emits different events from the event #{}", index),
+            Divergence::StorageWrite {
+                address,
+                slot,
+                original,
+                upgraded,
+            } => format!(
+                "sets slot {:#x} of {:?} to {} instead of {}",
+                slot,
+                address,
+                word(upgraded),
+                word(original)
+            ),
+        }
+    }
+}
+
+/// Outcome of a transaction compared between the builds
+pub struct Outcome<'a> {
+    pub reverted: bool,
+    pub output: &'a [u8],
+    pub state: &'a EVMState,
+}
+
+/// First storage slot whose value differs between the two states, the
+/// contracts and slots being visited in order
+fn storage_divergence(original: &EVMState, upgraded: &EVMState) -> Option<Divergence> {
+    let addresses = original.state.keys().chain(upgraded.state.keys()).unique().sorted();
+    for address in addresses {
+        let (a, b) = (original.state.get(address), upgraded.state.get(address));
+        if a == b {
+            continue;
+        }
+        let slots = a
+            .into_iter()
+            .chain(b)
+            .flat_map(|storage| storage.keys())
+            .unique()
+            .sorted();
+        for slot in slots {
+            let (x, y) = (
+                a.and_then(|s| s.get(slot)).cloned(),
+                b.and_then(|s| s.get(slot)).cloned(),
+            );
+            // an unset slot reads as zero
+            if x.unwrap_or_default() != y.unwrap_or_default() {
+                return Some(Divergence::StorageWrite {
+                    address: *address,
+                    slot: *slot,
+                    original: x,
+                    upgraded: y,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// How the upgraded execution diverges from the original one, None if they
+/// behave the same
+pub fn divergence(original: &Outcome, upgraded: &Outcome) -> Option<Divergence> {
+    if original.reverted != upgraded.reverted {
+        return Some(Divergence::Revert {
+            original: original.reverted,
+        });
+    }
+    if original.output != upgraded.output {
+        return Some(Divergence::ReturnData {
+            original: original.output.to_vec(),
+            upgraded: upgraded.output.to_vec(),
+        });
+    }
+    // a reverted transaction has no effect
+    if original.reverted {
+        return None;
+    }
+    let (a, b) = (&original.state.logs, &upgraded.state.logs);
+    if let Some(index) = (0..a.len().max(b.len())).find(|idx| a.get(*idx) != b.get(*idx)) {
+        return Some(Divergence::Event { index });
+    }
+    storage_divergence(original.state, upgraded.state)
+}
+
+pub struct DifferentialOracle {
+    /// What the code is changed by, e.g., `the upgrade`
+    change: String,
+    /// Upgraded bytecode of the contracts, analyzed once
+    upgrades: Vec<(EVMAddress, Arc<BytecodeLocked>)>,
+    address_to_name: HashMap<EVMAddress, String>,
+}
+
+impl DifferentialOracle {
+    pub fn new(
+        change: String,
+        upgrades: HashMap<EVMAddress, Bytecode>,
+        address_to_name: HashMap<EVMAddress, String>,
+    ) -> Self {
+        Self {
+            change,
+            upgrades: upgrades.into_iter().map(|(addr, code)| (addr, analyze(code))).collect(),
+            address_to_name,
+        }
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for DifferentialOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> Vec<u64> {
+        if ctx.input.is_step() || ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+
+        // execute the transaction again with the upgraded bytecode
+        let mut input = ctx.input.clone();
+        input.sstate.state = ctx.pre_state.clone();
+        let saved_result = ctx.fuzz_state.get_execution_result().clone();
+        let upgraded = {
+            let mut executor = ctx.executor.deref().borrow_mut();
+            let previous = self
+                .upgrades
+                .iter()
+                .map(|(addr, code)| (*addr, executor.host.code.insert(*addr, code.clone())))
+                .collect_vec();
+            let res = executor.execute(&input, ctx.fuzz_state);
+            for (addr, code) in previous {
+                match code {
+                    Some(code) => executor.host.code.insert(addr, code),
+                    None => executor.host.code.remove(&addr),
+                };
+            }
+            res
+        };
+        ctx.fuzz_state.set_execution_result(saved_result);
+        if upgraded.new_state.state.has_post_execution() {
+            return vec![];
+        }
+
+        let original = Outcome {
+            reverted: ctx.fuzz_state.get_execution_result().reverted,
+            output: &ctx.fuzz_state.get_execution_result().output,
+            state: &ctx.post_state,
+        };
+        let Some(divergence) = divergence(
+            &original,
+            &Outcome {
+                reverted: upgraded.reverted,
+                output: &upgraded.output,
+                state: &upgraded.new_state.state,
+            },
+        ) else {
+            return vec![];
+        };
+
+        let contract = ctx.input.get_contract();
+        let selector = ctx.input.get_data_abi().map(|abi| abi.function).unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        (&self.change, contract, selector, divergence.kind()).hash(&mut hasher);
+        let bug_idx = (hasher.finish() << 8) + DIFFERENTIAL_BUG_IDX;
+        if oracle_should_skip!(ctx, bug_idx) {
+            return vec![];
+        }
+
+        let name = self
+            .address_to_name
+            .get(&contract)
+            .cloned()
+            .unwrap_or(format!("{:?}", contract));
+        EVMBugResult::new(
+            "Differential".to_string(),
+            bug_idx,
+            format!(
+                "With {}, {}::0x{} {}\n",
+                self.change,
+                name,
+                hex::encode(selector),
+                divergence.describe()
+            ),
+            ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+            None,
+            Some(name),
+        )
+        .push_to_output();
+        vec![bug_idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::host::EmittedLog;
+
+    fn outcome(state: &EVMState) -> Outcome<'_> {
+        Outcome {
+            reverted: false,
+            output: &[0x01],
+            state,
+        }
+    }
+
+    #[test]
+    fn test_divergence() {
+        let token = EVMAddress::from_slice(&[0x11; 20]);
+        let mut state = EVMState::default();
+        state
+            .state
+            .insert(token, [(EVMU256::from(1), EVMU256::from(5))].into_iter().collect());
+        assert_eq!(divergence(&outcome(&state), &outcome(&state)), None);
+
+        let mut upgraded = state.clone();
+        upgraded
+            .state
+            .get_mut(&token)
+            .unwrap()
+            .insert(EVMU256::from(2), EVMU256::ZERO);
+        assert_eq!(divergence(&outcome(&state), &outcome(&upgraded)), None);
+        upgraded
+            .state
+            .get_mut(&token)
+            .unwrap()
+            .insert(EVMU256::from(1), EVMU256::from(6));
+        assert_eq!(
+            divergence(&outcome(&state), &outcome(&upgraded)),
+            Some(Divergence::StorageWrite {
+                address: token,
+                slot: EVMU256::from(1),
+                original: Some(EVMU256::from(5)),
+                upgraded: Some(EVMU256::from(6)),
+            })
+        );
+
+        let mut upgraded = state.clone();
+        upgraded.logs.push(EmittedLog::default());
+        assert_eq!(
+            divergence(&outcome(&state), &outcome(&upgraded)),
+            Some(Divergence::Event { index: 0 })
+        );
+        let reverted = Outcome {
+            reverted: true,
+            output: &[],
+            state: &state,
+        };
+        assert_eq!(
+            divergence(&outcome(&state), &reverted),
+            Some(Divergence::Revert { original: false })
+        );
+
+        let upgrades = parse_upgrades(&format!("{:?}=0x6000, ", token)).unwrap();
+        assert_eq!(upgrades[&token].bytecode().to_vec(), vec![0x60, 0x00]);
+        assert!(parse_upgrades("0x12=0x60").is_err());
+        assert!(parse_upgrades(&format!("{:?}", token)).is_err());
+    }
+}
@@ -1,21 +1,67 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs};
 
 use bytes::Bytes;
 use itertools::Itertools;
 use revm_primitives::Bytecode;
+use serde::Deserialize;
 
 use crate::{
     evm::{
         input::{ConciseEVMInput, EVMInput},
         oracle::EVMBugResult,
         oracles::ECHIDNA_BUG_IDX,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        types::{convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
     oracle::{Oracle, OracleCtx},
     state::HasExecutionResult,
 };
 
+/// Options of an Echidna config file (YAML) supported, so that Echidna test
+/// suites run unchanged. Other options are ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EchidnaConfig {
+    /// Addresses sending the transactions, e.g., `["0x10000", "0x20000"]`
+    #[serde(default)]
+    pub sender: Vec<String>,
+    /// Maximum increase of the timestamp in a transaction, in seconds
+    pub max_time_delay: Option<u64>,
+    /// Maximum increase of the block number in a transaction
+    pub max_block_delay: Option<u64>,
+    /// Number of executions to run
+    pub test_limit: Option<usize>,
+}
+
+impl EchidnaConfig {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("failed to read echidna config {}: {}", path, e))?;
+        Self::parse(&content).map_err(|e| format!("invalid echidna config {}: {}", path, e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        // an empty file is a valid config
+        if content.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// Sender addresses, which Echidna allows to be shorter than 20 bytes
+    pub fn senders(&self) -> Result<Vec<EVMAddress>, String> {
+        self.sender
+            .iter()
+            .map(|sender| {
+                EVMU256::from_str_radix(sender.trim_start_matches("0x"), 16)
+                    .map(convert_u256_to_h160)
+                    .map_err(|e| format!("invalid sender {}: {}", sender, e))
+            })
+            .collect()
+    }
+}
+
+/// Checks the `echidna_*()` properties after each transaction, a property
+/// fails when it returns false or reverts
 pub struct EchidnaOracle {
     pub batch_call_txs: Vec<(EVMAddress, Bytes)>,
     pub names: HashMap<Vec<u8>, String>,
@@ -98,3 +144,24 @@ impl
             .collect_vec()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echidna_config() {
+        let config = EchidnaConfig::parse(
+            "testLimit: 50000\nseqLen: 100\nsender: [\"0x10000\", \"0x20000\"]\nmaxTimeDelay: 604800\n",
+        )
+        .unwrap();
+        assert_eq!(config.test_limit, Some(50000));
+        assert_eq!(config.max_time_delay, Some(604800));
+        assert_eq!(config.max_block_delay, None);
+        let senders = config.senders().unwrap();
+        assert_eq!(senders[1], convert_u256_to_h160(EVMU256::from(0x20000)));
+
+        assert!(EchidnaConfig::parse("").unwrap().sender.is_empty());
+        assert!(EchidnaConfig::parse("sender: [\"0xzz\"]").unwrap().senders().is_err());
+    }
+}
@@ -7,8 +7,12 @@ use crate::{
     evm::{
         input::{ConciseEVMInput, EVMInput},
         onchain::flashloan::CAN_LIQUIDATE,
-        oracle::EVMBugResult,
-        oracles::{u512_div_float, ERC20_BUG_IDX},
+        oracles::{
+            post_state::{PostStateBug, PostStateOracle},
+            OracleThresholds,
+            ERC20_BUG_IDX,
+            MULTI_BLOCK_FUND_LOSS_BUG_IDX,
+        },
         producers::erc20::ERC20Producer,
         tokens::TokenContext,
         types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256, EVMU512},
@@ -24,15 +28,17 @@ pub struct IERC20OracleFlashloan {
     pub known_tokens: HashMap<EVMAddress, TokenContext>,
     pub known_pair_reserve_slot: HashMap<EVMAddress, EVMU256>,
     pub erc20_producer: Rc<RefCell<ERC20Producer>>,
+    pub thresholds: OracleThresholds,
 }
 
 impl IERC20OracleFlashloan {
-    pub fn new(erc20_producer: Rc<RefCell<ERC20Producer>>) -> Self {
+    pub fn new(erc20_producer: Rc<RefCell<ERC20Producer>>, thresholds: OracleThresholds) -> Self {
         Self {
             balance_of: hex::decode("70a08231").unwrap(),
             known_tokens: HashMap::new(),
             known_pair_reserve_slot: HashMap::new(),
             erc20_producer,
+            thresholds,
         }
     }
 
@@ -101,27 +107,19 @@ impl
                 }
             }
 
-            let _path_idx = ctx.input.get_randomness()[0] as usize;
-
             {
                 ctx.executor.deref().borrow_mut().host.evmstate = ctx.post_state.clone();
             }
             let mut failed = false;
             for (caller, _token_info, _amount) in liquidations_earned {
-                let backup = ctx.executor.deref().borrow_mut().host.evmstate.clone();
-                if _token_info
-                    .sell(
-                        _amount,
-                        caller,
-                        ctx.fuzz_state,
-                        &mut *ctx.executor.deref().borrow_mut(),
-                        ctx.input.get_randomness().as_slice(),
-                    )
-                    .is_none()
-                {
-                    ctx.executor.deref().borrow_mut().host.evmstate = backup;
-                    continue;
-                }
+                // a failed sell leaves the state untouched
+                let _ = _token_info.sell(
+                    _amount,
+                    caller,
+                    ctx.fuzz_state,
+                    &mut *ctx.executor.deref().borrow_mut(),
+                    &[ctx.input.get_liquidation_path()],
+                );
             }
             if !failed {
                 ctx.fuzz_state.get_execution_result_mut().new_state.state =
@@ -129,37 +127,70 @@ impl
             }
         }
 
-        let exec_res = ctx.fuzz_state.get_execution_result_mut();
+        // the profit is checked by the FundLossOracle, among the post-state oracles
+        vec![]
+    }
+}
 
-        if exec_res.new_state.state.has_post_execution() {
+/// Fund loss: the attacker earned more than it owes (flashloans and capital)
+/// at the end of the transaction, after the liquidation of its tokens by
+/// [`IERC20OracleFlashloan`]. A sequence carrying a debt over to a later block
+/// is reported apart, as the debt is capital held by the attacker
+pub struct FundLossOracle {
+    thresholds: OracleThresholds,
+}
+
+impl FundLossOracle {
+    pub fn new(thresholds: OracleThresholds) -> Self {
+        Self { thresholds }
+    }
+}
+
+impl PostStateOracle for FundLossOracle {
+    fn check(&self, _contract: EVMAddress, post_state: &EVMState) -> Vec<PostStateBug> {
+        if post_state.has_post_execution() {
+            return vec![];
+        }
+
+        if post_state.gas_used > self.thresholds.max_gas {
             return vec![];
         }
 
-        // println!(
-        //     "balance: {:?} - {:?}",
-        //     exec_res.new_state.state.flashloan_data.earned,
-        // exec_res.new_state.state.flashloan_data.owed );
+        let flashloan_data = &post_state.flashloan_data;
+        if self.thresholds.capital_exceeded(flashloan_data.owed) {
+            return vec![];
+        }
 
-        if exec_res.new_state.state.flashloan_data.earned > exec_res.new_state.state.flashloan_data.owed &&
-            exec_res.new_state.state.flashloan_data.earned - exec_res.new_state.state.flashloan_data.owed >
-                EVMU512::from(10_000_000_000_000_000_000_000_u128)
-        // > 0.01ETH
+        if flashloan_data.earned > flashloan_data.owed &&
+            flashloan_data.earned - flashloan_data.owed > self.thresholds.min_profit_scaled()
         {
-            let net = exec_res.new_state.state.flashloan_data.earned - exec_res.new_state.state.flashloan_data.owed;
+            let net = flashloan_data.earned - flashloan_data.owed;
             // we scaled by 1e24, so divide by 1e24 to get ETH
-            let net_eth = u512_div_float(net, EVMU512::from(1_000_000_000_000_000_000_000_u128), 3);
-
-            EVMBugResult::new_simple(
-                "Fund Loss".to_string(),
-                ERC20_BUG_IDX,
-                format!(
-                    "Anyone can earn {} ETH by interacting with the provided contracts\n",
-                    net_eth,
-                ),
-                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
-            )
-            .push_to_output();
-            vec![ERC20_BUG_IDX]
+            let scale = EVMU512::from(1_000_000_000_000_000_000_000_u128);
+            let net = self.thresholds.numeraire.format(net, scale);
+            // a debt carried over to a later block is not flashloaned but held
+            // by the attacker, the profit is not free
+            let carried = flashloan_data.carried;
+            let bug = if carried > EVMU512::ZERO {
+                PostStateBug {
+                    bug_type: "Multi-block Fund Loss".to_string(),
+                    bug_idx: MULTI_BLOCK_FUND_LOSS_BUG_IDX,
+                    message: format!(
+                        "Holding {} across blocks, one can earn {} by interacting with the provided contracts\n",
+                        self.thresholds.numeraire.format(carried, scale),
+                        net
+                    ),
+                    source: None,
+                }
+            } else {
+                PostStateBug {
+                    bug_type: "Fund Loss".to_string(),
+                    bug_idx: ERC20_BUG_IDX,
+                    message: format!("Anyone can earn {} by interacting with the provided contracts\n", net),
+                    source: None,
+                }
+            };
+            vec![bug]
         } else {
             vec![]
         }
@@ -0,0 +1,314 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use libafl::state::HasMetadata;
+use libafl_bolts::impl_serdeany;
+use revm_primitives::Bytecode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::ERC4626_BUG_IDX,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    generic_vm::vm_state::VMStateT,
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    r#const::ERC4626_PRICE_CHANGE_PERCENT,
+    state::HasExecutionResult,
+};
+
+/// Selector of `convertToAssets(uint256)`
+const CONVERT_TO_ASSETS: [u8; 4] = [0x07, 0xa2, 0xd1, 0x3a];
+/// Selector of `asset()`
+const ASSET: [u8; 4] = [0x38, 0xd5, 0x2e, 0x0f];
+/// Selector of `balanceOf(address)`
+const BALANCE_OF: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+/// Detects share price manipulation of ERC-4626 vaults: the assets of one
+/// share (`convertToAssets`) moving far from the initial price allows
+/// inflation attacks on depositors or draining the vault on withdrawals.
+/// Only the manipulations the sender of the transaction profits from, i.e.,
+/// ending up with more assets held directly or through shares, are reported.
+pub struct ERC4626Oracle {
+    /// Vault address -> name
+    pub vaults: HashMap<EVMAddress, String>,
+    /// `convertToAssets(1e18)` call
+    pub convert_call: Bytes,
+}
+
+/// Initial share price of each vault, i.e., the price before the first
+/// transaction touching it
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct ERC4626Metadata {
+    pub initial_prices: HashMap<EVMAddress, EVMU256>,
+}
+
+impl_serdeany!(ERC4626Metadata);
+
+impl ERC4626Oracle {
+    pub fn new(vaults: HashMap<EVMAddress, String>) -> Self {
+        let one_share = EVMU256::from(1_000_000_000_000_000_000_u128);
+        Self {
+            vaults,
+            convert_call: word_call(CONVERT_TO_ASSETS, one_share.to_be_bytes()),
+        }
+    }
+
+    /// Whether the ABI (`function_name(abi)` pairs) is the one of an ERC-4626
+    /// vault
+    pub fn is_vault<'a>(mut functions: impl Iterator<Item = (&'a str, &'a str)>) -> bool {
+        functions.any(|(name, abi)| name == "convertToAssets" && abi == "(uint256)")
+    }
+}
+
+fn parse_uint(out: &[u8]) -> Option<EVMU256> {
+    if out.len() != 32 {
+        return None;
+    }
+    EVMU256::try_from_be_slice(out)
+}
+
+fn parse_address(out: &[u8]) -> Option<EVMAddress> {
+    if out.len() != 32 {
+        return None;
+    }
+    Some(EVMAddress::from_slice(&out[12..]))
+}
+
+/// Call of `selector` with a single word argument
+fn word_call(selector: [u8; 4], arg: [u8; 32]) -> Bytes {
+    Bytes::from([selector.to_vec(), arg.to_vec()].concat())
+}
+
+fn balance_of_call(owner: EVMAddress) -> Bytes {
+    let mut arg = [0; 32];
+    arg[12..].copy_from_slice(owner.as_bytes());
+    word_call(BALANCE_OF, arg)
+}
+
+/// Assets `owner` holds directly and through its shares of `vault`, on the
+/// state before the execution or after it if `post`
+fn holdings(ctx: &mut EVMOracleCtx<'_>, vault: EVMAddress, owner: EVMAddress, post: bool) -> Option<EVMU256> {
+    let mut call = |calls: &[(EVMAddress, Bytes)]| {
+        if post {
+            ctx.call_post_batch(calls)
+        } else {
+            ctx.call_pre_batch(calls)
+        }
+    };
+    let out = call(&[(vault, Bytes::from(ASSET.to_vec())), (vault, balance_of_call(owner))]);
+    let asset = parse_address(&out[0])?;
+    let shares = parse_uint(&out[1])?;
+    let mut calls = vec![(asset, balance_of_call(owner))];
+    if shares != EVMU256::ZERO {
+        calls.push((vault, word_call(CONVERT_TO_ASSETS, shares.to_be_bytes())));
+    }
+    let out = call(&calls);
+    let mut assets = parse_uint(&out[0])?;
+    if let Some(out) = out.get(1) {
+        assets = assets.saturating_add(parse_uint(out)?);
+    }
+    Some(assets)
+}
+
+fn parse_price(out: &[u8]) -> Option<EVMU256> {
+    if out.len() != 32 {
+        return None;
+    }
+    let price = EVMU256::try_from_be_slice(out)?;
+    if price == EVMU256::ZERO {
+        None
+    } else {
+        Some(price)
+    }
+}
+
+/// Whether `price` moved from `initial` by more than
+/// [`ERC4626_PRICE_CHANGE_PERCENT`], returns `Some(true)` for inflation and
+/// `Some(false)` for deflation
+pub fn price_manipulated(initial: EVMU256, price: EVMU256) -> Option<bool> {
+    let hundred = EVMU256::from(100);
+    let change = EVMU256::from(ERC4626_PRICE_CHANGE_PERCENT);
+    if price.saturating_mul(hundred) > initial.saturating_mul(hundred + change) {
+        Some(true)
+    } else if price.saturating_mul(hundred) < initial.saturating_mul(hundred.saturating_sub(change)) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for ERC4626Oracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if self.vaults.is_empty() || ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+        if !ctx.fuzz_state.has_metadata::<ERC4626Metadata>() {
+            ctx.fuzz_state.metadata_map_mut().insert(ERC4626Metadata::default());
+        }
+
+        let vaults = self.vaults.keys().cloned().collect_vec();
+        let calls = vaults
+            .iter()
+            .map(|vault| (*vault, self.convert_call.clone()))
+            .collect_vec();
+
+        // initial prices are taken from the state the first transaction runs on
+        let missing = {
+            let meta = ctx.fuzz_state.metadata_map().get::<ERC4626Metadata>().unwrap();
+            vaults.iter().any(|vault| !meta.initial_prices.contains_key(vault))
+        };
+        if missing {
+            let pre_prices = ctx.call_pre_batch(&calls);
+            let meta = ctx.fuzz_state.metadata_map_mut().get_mut::<ERC4626Metadata>().unwrap();
+            for (vault, out) in vaults.iter().zip(pre_prices) {
+                if let Some(price) = parse_price(&out) {
+                    meta.initial_prices.entry(*vault).or_insert(price);
+                }
+            }
+        }
+
+        let post_prices = ctx.call_post_batch(&calls);
+        let mut res = vec![];
+        for (vault, out) in vaults.iter().zip(post_prices) {
+            let price = match parse_price(&out) {
+                Some(price) => price,
+                None => continue,
+            };
+            let initial = match ctx
+                .fuzz_state
+                .metadata_map()
+                .get::<ERC4626Metadata>()
+                .unwrap()
+                .initial_prices
+                .get(vault)
+            {
+                Some(initial) => *initial,
+                None => continue,
+            };
+            let inflated = match price_manipulated(initial, price) {
+                Some(inflated) => inflated,
+                None => continue,
+            };
+            // moving the price without gaining anything is not an exploit
+            let sender = ctx.input.get_caller();
+            let profited = match (
+                holdings(ctx, *vault, sender, false),
+                holdings(ctx, *vault, sender, true),
+            ) {
+                (Some(pre), Some(post)) => post > pre,
+                _ => false,
+            };
+            if !profited {
+                continue;
+            }
+
+            let mut hasher = DefaultHasher::new();
+            vault.hash(&mut hasher);
+            inflated.hash(&mut hasher);
+            let bug_idx = (hasher.finish() << 8) + ERC4626_BUG_IDX;
+            if oracle_should_skip!(ctx, bug_idx) {
+                continue;
+            }
+
+            let name = self.vaults[vault].clone();
+            EVMBugResult::new(
+                "ERC4626 Share Price Manipulation".to_string(),
+                bug_idx,
+                format!(
+                    "Share price of vault {} is {} from {} to {} assets per share and the sender profits, it can be manipulated to extract funds from depositors or the vault\n",
+                    name,
+                    if inflated { "inflated" } else { "deflated" },
+                    initial,
+                    price
+                ),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+                None,
+                Some(name),
+            )
+            .push_to_output();
+            res.push(bug_idx);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_manipulated() {
+        let initial = EVMU256::from(1000);
+        assert_eq!(price_manipulated(initial, EVMU256::from(1200)), None);
+        assert_eq!(price_manipulated(initial, EVMU256::from(600)), None);
+        assert_eq!(price_manipulated(initial, EVMU256::from(1600)), Some(true));
+        assert_eq!(price_manipulated(initial, EVMU256::from(400)), Some(false));
+    }
+
+    #[test]
+    fn test_is_vault() {
+        assert!(ERC4626Oracle::is_vault(
+            [("asset", "()"), ("convertToAssets", "(uint256)")].into_iter()
+        ));
+        assert!(!ERC4626Oracle::is_vault([("balanceOf", "(address)")].into_iter()));
+        assert_eq!(parse_price(&[0u8; 32]), None);
+    }
+
+    #[test]
+    fn test_holdings_calls() {
+        let owner = EVMAddress::from_low_u64_be(0xdead);
+        let call = balance_of_call(owner);
+        assert_eq!(&call[..4], &BALANCE_OF);
+        assert_eq!(parse_address(&call[4..]), Some(owner));
+        assert_eq!(parse_uint(&call[4..]), Some(EVMU256::from(0xdead)));
+        assert_eq!(parse_uint(&[]), None);
+        assert_eq!(
+            hex::encode(&ERC4626Oracle::new(HashMap::new()).convert_call),
+            "07a2d13a0000000000000000000000000000000000000000000000000de0b6b3a7640000"
+        );
+    }
+}
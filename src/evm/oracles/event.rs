@@ -0,0 +1,154 @@
+//! Properties encoded as events: transactions emitting one of the events
+//! given with `--event-properties`, e.g., `InvariantViolated(string)`, are
+//! reported with the decoded args of the event
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use alloy_dyn_abi::{DynSolValue, EventExt};
+use alloy_json_abi::Event;
+use alloy_primitives::{hex, B256};
+use itertools::Itertools;
+
+use crate::evm::{
+    host::EmittedLog,
+    oracles::{
+        assertion::split_signatures,
+        post_state::{PostStateBug, PostStateOracle},
+        EVENT_BUG_IDX,
+    },
+    types::EVMAddress,
+    vm::EVMState,
+};
+
+/// Parse the comma separated event signatures, e.g.,
+/// `InvariantViolated(string),Drained(address indexed,uint256)`
+pub fn parse_event_properties(s: &str) -> Result<Vec<Event>, String> {
+    split_signatures(s)
+        .into_iter()
+        .map(|event| event.trim())
+        .filter(|event| !event.is_empty())
+        .map(|event| Event::parse(event).map_err(|e| format!("invalid event {}: {}", event, e)))
+        .collect()
+}
+
+fn format_value(value: &DynSolValue) -> String {
+    match value {
+        DynSolValue::Bool(b) => b.to_string(),
+        DynSolValue::Int(i, _) => i.to_string(),
+        DynSolValue::Uint(u, _) => u.to_string(),
+        DynSolValue::Address(address) => address.to_checksum(None),
+        DynSolValue::String(s) => format!("{:?}", s),
+        DynSolValue::Bytes(b) => format!("0x{}", hex::encode(b)),
+        DynSolValue::FixedBytes(word, size) => format!("0x{}", hex::encode(&word[..*size])),
+        DynSolValue::Array(values) | DynSolValue::FixedArray(values) => {
+            format!("[{}]", values.iter().map(format_value).join(", "))
+        }
+        DynSolValue::Tuple(values) => format!("({})", values.iter().map(format_value).join(", ")),
+        _ => format!("{:?}", value),
+    }
+}
+
+/// The event with its args if the log is one of the events, None otherwise
+pub fn decode_event(events: &[Event], log: &EmittedLog) -> Option<(Event, String)> {
+    let topic0 = B256::from(log.topics.first()?.0);
+    let event = events
+        .iter()
+        .find(|event| !event.anonymous && event.selector() == topic0)?;
+    let topics = log.topics.iter().map(|topic| B256::from(topic.0));
+    let decoded = event.decode_log_parts(topics, &log.data, false).ok()?;
+
+    // the indexed and non indexed args are decoded apart
+    let (mut indexed, mut body) = (decoded.indexed.iter(), decoded.body.iter());
+    let args = event
+        .inputs
+        .iter()
+        .filter_map(|input| if input.indexed { indexed.next() } else { body.next() })
+        .map(format_value)
+        .join(", ");
+    Some((event.clone(), format!("{}({})", event.name, args)))
+}
+
+pub struct EventOracle {
+    address_to_name: HashMap<EVMAddress, String>,
+    events: Vec<Event>,
+}
+
+impl EventOracle {
+    pub fn new(address_to_name: HashMap<EVMAddress, String>, events: Vec<Event>) -> Self {
+        Self {
+            address_to_name,
+            events,
+        }
+    }
+}
+
+impl PostStateOracle for EventOracle {
+    fn check(&self, _contract: EVMAddress, post_state: &EVMState) -> Vec<PostStateBug> {
+        let mut res = vec![];
+        for log in &post_state.logs {
+            let Some((event, decoded)) = decode_event(&self.events, log) else {
+                continue;
+            };
+            let mut hasher = DefaultHasher::new();
+            log.address.hash(&mut hasher);
+            event.selector().hash(&mut hasher);
+            let bug_idx = (hasher.finish() << 8) + EVENT_BUG_IDX;
+            if res.iter().any(|bug: &PostStateBug| bug.bug_idx == bug_idx) {
+                continue;
+            }
+
+            let name = self
+                .address_to_name
+                .get(&log.address)
+                .cloned()
+                .unwrap_or(format!("{:?}", log.address));
+            res.push(PostStateBug {
+                bug_type: "Property Violation".to_string(),
+                bug_idx,
+                message: format!("{} emitted {}\n", name, decoded),
+                source: Some(name),
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::U256;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_event() {
+        let events = parse_event_properties("InvariantViolated(string), Drained(address indexed,uint256)").unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(parse_event_properties("Drained(address").is_err());
+
+        let violated = EmittedLog {
+            address: EVMAddress::zero(),
+            topics: vec![events[0].selector().0.into()],
+            data: DynSolValue::Tuple(vec![DynSolValue::String("x > 0".to_string())])
+                .abi_encode_params()
+                .into(),
+        };
+        assert_eq!(
+            decode_event(&events, &violated).map(|(_, decoded)| decoded),
+            Some("InvariantViolated(\"x > 0\")".to_string())
+        );
+
+        let mut drained = EmittedLog {
+            address: EVMAddress::zero(),
+            topics: vec![events[1].selector().0.into(), [0x11; 32].into()],
+            data: DynSolValue::Uint(U256::from(7), 256).abi_encode().into(),
+        };
+        let (_, decoded) = decode_event(&events, &drained).unwrap();
+        assert!(decoded.starts_with("Drained(0x") && decoded.ends_with(", 7)"));
+        // the indexed address is missing
+        drained.topics.pop();
+        assert_eq!(decode_event(&events, &drained), None);
+    }
+}
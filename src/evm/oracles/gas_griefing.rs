@@ -0,0 +1,308 @@
+//! Gas griefing: functions whose gas grows superlinearly with the length of
+//! the arrays passed to them or stored by the contract, e.g., nested loops
+//! over an array anyone can push to. Such functions can eventually not fit in
+//! a block, locking the funds depending on them.
+//!
+//! The growth with the calldata is computed from the call itself: it is
+//! replayed on the same state with its dynamic arrays grown by each of
+//! `GAS_GRIEFING_SCALES`. The storage can't be grown by a replay, so the gas of
+//! each function is recorded across the executions by the number of storage
+//! slots of the contract, the other calls (e.g., `push()`) growing it. In both
+//! cases, the growth is superlinear when the gas per unit of size over the
+//! larger sizes is much higher than over the smaller ones. Onchain, the size of
+//! the storage is only the number of slots cached so far.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use bytes::Bytes;
+use libafl::state::HasMetadata;
+use libafl_bolts::impl_serdeany;
+use revm_primitives::Bytecode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput, EVMInputT},
+        oracle::EVMBugResult,
+        oracles::GAS_GRIEFING_BUG_IDX,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    r#const::{
+        BLOCK_GAS_LIMIT,
+        GAS_GRIEFING_MIN_GROWTH,
+        GAS_GRIEFING_MIN_SIZES,
+        GAS_GRIEFING_SCALES,
+        GAS_GRIEFING_SLOPE_RATIO,
+    },
+    state::HasExecutionResult,
+};
+
+/// A function and the calldata words of its args
+pub type CallShape = (EVMAddress, [u8; 4], usize);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GasGriefingMetadata {
+    /// Calls already replayed with their arrays grown, so that each shape of
+    /// input is replayed once
+    pub measured: HashSet<CallShape>,
+    /// Gas of the calls by the number of storage slots of the contract they
+    /// were executed on
+    #[serde(default)]
+    pub storage_gas: HashMap<CallShape, BTreeMap<usize, u64>>,
+}
+
+impl_serdeany!(GasGriefingMetadata);
+
+impl GasGriefingMetadata {
+    /// Whether the gas of the call is recorded for `slots` storage slots
+    pub fn has_storage_gas(&self, shape: &CallShape, slots: usize) -> bool {
+        self.storage_gas
+            .get(shape)
+            .is_some_and(|profile| profile.contains_key(&slots))
+    }
+
+    /// Record the gas of the call executed on `slots` storage slots, returns
+    /// the samples of the profile if the gas grows superlinearly with them
+    pub fn record_storage_gas(&mut self, shape: CallShape, slots: usize, gas: u64) -> Option<[(usize, u64); 3]> {
+        let profile = self.storage_gas.entry(shape).or_default();
+        profile.entry(slots).or_insert(gas);
+        superlinear_growth(profile)
+    }
+}
+
+/// Smallest, middle and largest samples of the profile (size -> gas)
+/// if the gas grows superlinearly with the size
+pub fn superlinear_growth(profile: &BTreeMap<usize, u64>) -> Option<[(usize, u64); 3]> {
+    if profile.len() < GAS_GRIEFING_MIN_SIZES {
+        return None;
+    }
+    let (first, last) = (profile.first_key_value()?, profile.last_key_value()?);
+    if last.1.saturating_sub(*first.1) < GAS_GRIEFING_MIN_GROWTH {
+        return None;
+    }
+    let mid = (first.0 + last.0) / 2;
+    let middle = profile
+        .iter()
+        .filter(|(size, _)| *size != first.0 && *size != last.0)
+        .min_by_key(|(size, _)| size.abs_diff(mid))?;
+
+    let slope = |(s0, g0): (&usize, &u64), (s1, g1): (&usize, &u64)| (*g1 as f64 - *g0 as f64) / (s1 - s0) as f64;
+    let (low, high) = (slope(first, middle), slope(middle, last));
+    (low > 0.0 && high > GAS_GRIEFING_SLOPE_RATIO * low).then_some([
+        (*first.0, *first.1),
+        (*middle.0, *middle.1),
+        (*last.0, *last.1),
+    ])
+}
+
+pub struct GasGriefingOracle {
+    address_to_name: HashMap<EVMAddress, String>,
+}
+
+impl GasGriefingOracle {
+    pub fn new(address_to_name: HashMap<EVMAddress, String>) -> Self {
+        Self { address_to_name }
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for GasGriefingOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if ctx.input.is_step() || ctx.fuzz_state.get_execution_result().reverted {
+            return vec![];
+        }
+        let Some(abi) = ctx.input.get_data_abi() else {
+            return vec![];
+        };
+        let contract = ctx.input.get_contract();
+        let mut hasher = DefaultHasher::new();
+        (contract, abi.function).hash(&mut hasher);
+        let bug_idx = (hasher.finish() << 8) + GAS_GRIEFING_BUG_IDX;
+        if abi.is_static() || oracle_should_skip!(ctx, bug_idx) {
+            return vec![];
+        }
+
+        let words = abi.get_bytes_vec().len() / 32;
+        let shape = (contract, abi.function, words);
+        let slots = ctx
+            .pre_state
+            .state
+            .state
+            .get(&contract)
+            .map_or(0, |storage| storage.len());
+        if !ctx.fuzz_state.has_metadata::<GasGriefingMetadata>() {
+            ctx.fuzz_state.add_metadata(GasGriefingMetadata::default());
+        }
+        let meta = ctx
+            .fuzz_state
+            .metadata_map_mut()
+            .get_mut::<GasGriefingMetadata>()
+            .unwrap();
+        let grow_calldata = meta.measured.insert(shape);
+        if !grow_calldata && meta.has_storage_gas(&shape, slots) {
+            return vec![];
+        }
+
+        // replay the call, with its arrays grown if not done yet, on the state it
+        // was executed on
+        let caller = ctx.input.get_caller();
+        let value = ctx.input.get_txn_value().unwrap_or_default();
+        let scales: &[usize] = if grow_calldata { &GAS_GRIEFING_SCALES } else { &[1] };
+        let mut profile = BTreeMap::new();
+        for factor in scales {
+            let Some(grown) = (if *factor == 1 {
+                Some(abi.clone())
+            } else {
+                abi.grow_arrays(*factor)
+            }) else {
+                break;
+            };
+            let Some(gas) = ctx.executor.deref().borrow_mut().call_gas(
+                caller,
+                contract,
+                Bytes::from(grown.get_bytes()),
+                value,
+                &ctx.pre_state.state,
+                ctx.fuzz_state,
+            ) else {
+                break;
+            };
+            profile.insert(grown.get_bytes_vec().len() / 32, gas);
+        }
+
+        let (growth, unit) = match superlinear_growth(&profile) {
+            Some(growth) => (growth, "calldata words"),
+            None => {
+                let Some(gas) = profile.get(&words) else {
+                    return vec![];
+                };
+                let meta = ctx
+                    .fuzz_state
+                    .metadata_map_mut()
+                    .get_mut::<GasGriefingMetadata>()
+                    .unwrap();
+                match meta.record_storage_gas(shape, slots, *gas) {
+                    Some(growth) => (growth, "storage slots"),
+                    None => return vec![],
+                }
+            }
+        };
+        let [(s0, g0), (s1, g1), (s2, g2)] = growth;
+
+        let name = self
+            .address_to_name
+            .get(&contract)
+            .cloned()
+            .unwrap_or(format!("{:?}", contract));
+        EVMBugResult::new(
+            "Gas Griefing".to_string(),
+            bug_idx,
+            format!(
+                "Gas of {}::0x{} grows superlinearly with the length of its arrays: {} gas at {} {}, {} at {}, {} at {} (block gas limit {}), potential DoS\n",
+                name,
+                hex::encode(abi.function),
+                g0,
+                s0,
+                unit,
+                g1,
+                s1,
+                g2,
+                s2,
+                BLOCK_GAS_LIMIT
+            ),
+            ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+            None,
+            Some(name),
+        )
+        .push_to_output();
+        vec![bug_idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_superlinear_growth() {
+        // linear: 50k gas per word
+        let profile = (0..5)
+            .map(|size| (size * 10, 30_000 + size as u64 * 500_000))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(superlinear_growth(&profile), None);
+
+        // quadratic: 1k gas per word squared
+        let profile = [0u64, 10, 20, 30, 40]
+            .into_iter()
+            .map(|size| (size as usize, 30_000 + size * size * 1_000))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            superlinear_growth(&profile),
+            Some([(0, 30_000), (20, 430_000), (40, 1_630_000)])
+        );
+        // too few samples
+        assert_eq!(superlinear_growth(&profile.into_iter().take(2).collect()), None);
+    }
+
+    #[test]
+    fn test_storage_gas() {
+        let mut meta = GasGriefingMetadata::default();
+        let shape = (EVMAddress::zero(), [0xaa; 4], 0);
+        // nested loops over a storage array growing by a slot per push
+        for slots in [1, 11, 21, 31] {
+            let length = slots as u64 - 1;
+            let growth = meta.record_storage_gas(shape, slots, 30_000 + length * length * 1_000);
+            // a profile of three sizes is enough
+            assert_eq!(growth.is_some(), slots >= 21);
+        }
+        assert!(meta.has_storage_gas(&shape, 21));
+        assert!(!meta.has_storage_gas(&shape, 41));
+        // the first gas recorded for a size is kept
+        assert!(meta.record_storage_gas(shape, 31, 0).is_some());
+        // another calldata size is another profile
+        assert!(meta
+            .record_storage_gas((EVMAddress::zero(), [0xaa; 4], 1), 31, 0)
+            .is_none());
+    }
+}
@@ -23,6 +23,9 @@ use crate::{
     state::HasExecutionResult,
 };
 
+/// Checks the `invariant_*()` functions of the targets and harness contracts
+/// after each transaction, Foundry-style: an invariant is violated when it
+/// reverts, returns false or fails an assertion of the cheatcode contract.
 pub struct InvariantOracle {
     pub batch_call_txs: Vec<(EVMAddress, EVMAddress, Bytes)>,
     pub names: HashMap<Vec<u8>, (String, u64)>,
@@ -63,6 +66,19 @@ impl InvariantOracle {
             .unwrap(),
         }
     }
+
+    /// Whether the contract named `name` (`path/Contract` or
+    /// `path:Contract`) is one of the harness contracts
+    pub fn is_harness(name: &str, harness: &[String]) -> bool {
+        harness
+            .iter()
+            .any(|h| name == h || name.ends_with(&format!("/{}", h)) || name.ends_with(&format!(":{}", h)))
+    }
+}
+
+/// Whether the output of an invariant function is `false`
+fn returned_false(output: &[u8]) -> bool {
+    output.len() == 32 && output.iter().all(|b| *b == 0)
 }
 
 impl
@@ -110,6 +126,7 @@ impl
             let (call_res, new_state) = ctx.call_post_batch_dyn(&[tx.clone()]);
             let (msg, succ) = &call_res[0];
             if *succ &&
+                !returned_false(msg) &&
                 !{
                     // assertTrue in Foundry writes to slot
                     // 0x6661696c65640000000000000000000000000000000000000000000000000000
@@ -128,14 +145,18 @@ impl
                 continue;
             }
             let (name, _) = self.names.get(&tx.2.to_vec()).unwrap();
+            let reason = if *succ && returned_false(msg) {
+                "returned false".to_string()
+            } else {
+                format!(
+                    "{:?}",
+                    String::from_utf8(msg.iter().filter(|&c| *c > 0).cloned().collect::<Vec<u8>>())
+                )
+            };
             EVMBugResult::new(
                 "Invariant".to_string(),
                 bug_idx,
-                format!(
-                    "Invariant {:?} violated, {:?}",
-                    name,
-                    String::from_utf8(msg.iter().filter(|&c| *c > 0).cloned().collect::<Vec<u8>>())
-                ),
+                format!("Invariant {:?} violated, {}", name, reason),
                 ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
                 None,
                 Some(name.clone()),
@@ -146,3 +167,23 @@ impl
         res
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invariant_harness() {
+        let harness = vec!["Harness".to_string()];
+        assert!(InvariantOracle::is_harness("Harness", &harness));
+        assert!(InvariantOracle::is_harness("tests/invariant/Harness", &harness));
+        assert!(InvariantOracle::is_harness("src/Harness.sol:Harness", &harness));
+        assert!(!InvariantOracle::is_harness("tests/invariant/MyHarness", &harness));
+
+        assert!(returned_false(&[0u8; 32]));
+        let mut out = [0u8; 32];
+        out[31] = 1;
+        assert!(!returned_false(&out));
+        assert!(!returned_false(&[]));
+    }
+}
@@ -1,13 +1,30 @@
-use super::types::EVMU512;
+use super::{
+    tokens::numeraire::NumerairePrice,
+    types::{EVMU256, EVMU512},
+};
+use crate::r#const::{BLOCK_GAS_LIMIT, DEFAULT_MIN_PROFIT_USD, DEFAULT_NATIVE_PRICE_USD};
 
 pub mod arb_call;
+pub mod assertion;
+pub mod contract_size;
+pub mod differential;
 pub mod echidna;
 pub mod erc20;
+pub mod erc4626;
+pub mod event;
 pub mod function;
+pub mod gas_griefing;
 pub mod invariant;
+pub mod plugin;
+pub mod post_state;
+pub mod price_manipulation;
 pub mod reentrancy;
+pub mod sandwich;
 pub mod selfdestruct;
 pub mod state_comp;
+pub mod supply;
+pub mod tainted_call;
+pub mod takeover;
 pub mod typed_bug;
 pub mod v2_pair;
 
@@ -22,6 +39,75 @@ pub static ARB_CALL_BUG_IDX: u64 = 8;
 pub static REENTRANCY_BUG_IDX: u64 = 9;
 pub static INVARIANT_BUG_IDX: u64 = 10;
 pub static INTEGER_OVERFLOW_BUG_IDX: u64 = 11;
+pub static ERC4626_BUG_IDX: u64 = 12;
+pub static TAINTED_CALL_BUG_IDX: u64 = 13;
+pub static SUPPLY_BUG_IDX: u64 = 14;
+pub static PRICE_MANIPULATION_BUG_IDX: u64 = 15;
+pub static CONTRACT_SIZE_BUG_IDX: u64 = 16;
+pub static ASSERTION_BUG_IDX: u64 = 17;
+pub static EVENT_BUG_IDX: u64 = 18;
+pub static GAS_GRIEFING_BUG_IDX: u64 = 19;
+pub static PLUGIN_BUG_IDX: u64 = 20;
+pub static PYTHON_BUG_IDX: u64 = 21;
+pub static SANDWICH_BUG_IDX: u64 = 22;
+pub static DIFFERENTIAL_BUG_IDX: u64 = 23;
+pub static MULTI_BLOCK_FUND_LOSS_BUG_IDX: u64 = 24;
+pub static TAKEOVER_BUG_IDX: u64 = 25;
+
+/// Thresholds below which the oracles do not report a bug, to tune the noise
+/// level per target
+#[derive(Debug, Clone, Copy)]
+pub struct OracleThresholds {
+    /// Minimum profit of a fund loss, in USD
+    pub min_profit: f64,
+    /// Price of the native token in USD, converting the minimum profit
+    pub native_usd: f64,
+    /// Minimum decrease of a reserve of an imbalanced Uniswap pair
+    pub min_reserve_delta: EVMU256,
+    /// Maximum gas of the transaction leading to a fund loss
+    pub max_gas: u64,
+    /// Maximum capital (ETH sent and borrowed) the attacker can put in, in
+    /// the numeraire. Unlimited if None
+    pub max_capital: Option<f64>,
+    /// Unit of the profits and capitals, in which the thresholds are given
+    /// and the fund losses reported
+    pub numeraire: NumerairePrice,
+}
+
+impl Default for OracleThresholds {
+    fn default() -> Self {
+        Self {
+            min_profit: DEFAULT_MIN_PROFIT_USD,
+            native_usd: DEFAULT_NATIVE_PRICE_USD,
+            min_reserve_delta: EVMU256::ZERO,
+            max_gas: BLOCK_GAS_LIMIT,
+            max_capital: None,
+            numeraire: NumerairePrice::default(),
+        }
+    }
+}
+
+impl OracleThresholds {
+    /// Minimum profit scaled as the flashloan earnings (1 ETH = 1e24)
+    pub fn min_profit_scaled(&self) -> EVMU512 {
+        let min_profit = self.min_profit / self.native_usd;
+        EVMU512::from((min_profit * 1e6) as u128) * EVMU512::from(1_000_000_000_000_000_000_u128)
+    }
+
+    /// Maximum capital in wei
+    pub fn max_capital_wei(&self) -> Option<EVMU256> {
+        self.max_capital
+            .map(|capital| self.numeraire.to_native(capital))
+            .map(|eth| EVMU256::from((eth * 1e6) as u128) * EVMU256::from(1_000_000_000_000_u128))
+    }
+
+    /// Whether the capital owed by the attacker (scaled as the flashloan
+    /// earnings) exceeds the maximum capital
+    pub fn capital_exceeded(&self, owed: EVMU512) -> bool {
+        self.max_capital_wei()
+            .is_some_and(|max| owed > EVMU512::from(max) * EVMU512::from(1_000_000))
+    }
+}
 
 /// Divide a U512 by another U512 and return a string with the decimal point at
 /// the correct position For example, 1000 / 3 = 333.333, then a = 1000e6, b =
@@ -45,3 +131,54 @@ macro_rules! oracle_should_skip {
         res
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::tokens::numeraire::Numeraire;
+
+    #[test]
+    fn test_min_profit_scaled() {
+        let thresholds = OracleThresholds::default();
+        assert_eq!(
+            thresholds.min_profit_scaled(),
+            EVMU512::from(10_000_000_000_000_000_000_000_u128)
+        );
+        assert_eq!(
+            u512_div_float(thresholds.min_profit_scaled(), EVMU512::from(10_u128.pow(21)), 3),
+            "0.010"
+        );
+        assert!(!thresholds.capital_exceeded(EVMU512::MAX));
+
+        // the profit is given in USD whatever the numeraire
+        let thresholds = OracleThresholds {
+            min_profit: 300.0,
+            native_usd: 600.0,
+            numeraire: NumerairePrice {
+                numeraire: Numeraire::USDC,
+                per_native: 600.0,
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            u512_div_float(thresholds.min_profit_scaled(), EVMU512::from(10_u128.pow(21)), 3),
+            "0.500"
+        );
+    }
+
+    #[test]
+    fn test_max_capital() {
+        let thresholds = OracleThresholds {
+            max_capital: Some(1.5),
+            ..Default::default()
+        };
+        assert_eq!(
+            thresholds.max_capital_wei(),
+            Some(EVMU256::from(1_500_000_000_000_000_000_u128))
+        );
+        // 1.5 ETH scaled by 1e6
+        let max_scaled = EVMU512::from(1_500_000_000_000_000_000_000_000_u128);
+        assert!(!thresholds.capital_exceeded(max_scaled));
+        assert!(thresholds.capital_exceeded(max_scaled + EVMU512::from(1)));
+    }
+}
@@ -0,0 +1,207 @@
+//! Oracles loaded from shared libraries given with `--oracle-plugins`, so that
+//! custom detectors are built as separate crates (see
+//! `plugins/oracle_template`) instead of forks of ityfuzz.
+//!
+//! The exported functions and [`PluginContext`] are defined by the
+//! `ityfuzz-oracle-abi` crate (`plugins/oracle_abi`), which the plugins depend
+//! on too.
+//!
+//! The plugins are evaluated along the other post-state oracles, possibly from
+//! several threads at once, so `ityfuzz_oracle_check` has to be thread safe.
+//! The pointers of the context are only valid during the call.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    ffi::{c_char, c_void, CStr},
+    hash::{Hash, Hasher},
+};
+
+pub use ityfuzz_oracle_abi::{PluginContext, PluginLog, ORACLE_PLUGIN_ABI_VERSION};
+use libloading::Library;
+
+use crate::evm::{
+    oracles::{
+        post_state::{PostStateBug, PostStateOracle},
+        PLUGIN_BUG_IDX,
+    },
+    types::{EVMAddress, EVMU256},
+    vm::EVMState,
+};
+
+extern "C" fn plugin_sload(
+    state: *const c_void,
+    address: *const [u8; 20],
+    slot: *const [u8; 32],
+    value: *mut [u8; 32],
+) -> bool {
+    let state = unsafe { &*(state as *const EVMState) };
+    let (address, slot) = unsafe { (EVMAddress::from_slice(&*address), EVMU256::from_be_bytes(*slot)) };
+    match state.sload(address, slot) {
+        Some(v) => {
+            unsafe { *value = v.to_be_bytes::<32>() };
+            true
+        }
+        None => false,
+    }
+}
+
+extern "C" fn plugin_balance(state: *const c_void, address: *const [u8; 20], value: *mut [u8; 32]) -> bool {
+    let state = unsafe { &*(state as *const EVMState) };
+    let address = unsafe { EVMAddress::from_slice(&*address) };
+    match state.get_balance(&address) {
+        Some(v) => {
+            unsafe { *value = v.to_be_bytes::<32>() };
+            true
+        }
+        None => false,
+    }
+}
+
+extern "C" fn plugin_report(reports: *mut c_void, id: u64, message: *const c_char) {
+    let reports = unsafe { &mut *(reports as *mut Vec<(u64, String)>) };
+    let message = if message.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+    };
+    reports.push((id, message));
+}
+
+pub struct OraclePlugin {
+    name: String,
+    check: unsafe extern "C" fn(ctx: *const PluginContext),
+    // keeps `check` loaded
+    _library: Library,
+}
+
+impl OraclePlugin {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let err = |e: libloading::Error| format!("failed to load oracle plugin {}: {}", path, e);
+        unsafe {
+            let library = Library::new(path).map_err(err)?;
+            let version = library
+                .get::<unsafe extern "C" fn() -> u32>(b"ityfuzz_oracle_abi_version")
+                .map_err(err)?();
+            if version != ORACLE_PLUGIN_ABI_VERSION {
+                return Err(format!(
+                    "oracle plugin {} is built for the ABI version {}, expected {}",
+                    path, version, ORACLE_PLUGIN_ABI_VERSION
+                ));
+            }
+            let name = library
+                .get::<unsafe extern "C" fn() -> *const c_char>(b"ityfuzz_oracle_name")
+                .map_err(err)?();
+            let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+            let check = *library
+                .get::<unsafe extern "C" fn(*const PluginContext)>(b"ityfuzz_oracle_check")
+                .map_err(err)?;
+            Ok(Self {
+                name,
+                check,
+                _library: library,
+            })
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn bug_idx(&self, id: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.name.hash(&mut hasher);
+        id.hash(&mut hasher);
+        (hasher.finish() << 8) + PLUGIN_BUG_IDX
+    }
+}
+
+impl PostStateOracle for OraclePlugin {
+    fn check(&self, contract: EVMAddress, post_state: &EVMState) -> Vec<PostStateBug> {
+        let topics = post_state
+            .logs
+            .iter()
+            .map(|log| log.topics.iter().map(|topic| topic.0).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let logs = post_state
+            .logs
+            .iter()
+            .zip(&topics)
+            .map(|(log, topics)| PluginLog {
+                address: log.address.0,
+                topics: topics.as_ptr(),
+                topics_len: topics.len(),
+                data: log.data.as_ptr(),
+                data_len: log.data.len(),
+            })
+            .collect::<Vec<_>>();
+        let mut reports: Vec<(u64, String)> = vec![];
+        let ctx = PluginContext {
+            state: post_state as *const EVMState as *const c_void,
+            contract: contract.0,
+            logs: logs.as_ptr(),
+            logs_len: logs.len(),
+            sload: plugin_sload,
+            balance: plugin_balance,
+            reports: &mut reports as *mut Vec<(u64, String)> as *mut c_void,
+            report: plugin_report,
+        };
+        unsafe { (self.check)(&ctx) };
+
+        reports
+            .into_iter()
+            .map(|(id, message)| PostStateBug {
+                bug_type: self.name.clone(),
+                bug_idx: self.bug_idx(id),
+                message: format!("{}\n", message),
+                source: None,
+            })
+            .collect()
+    }
+}
+
+/// Parse the paths of the plugins separated by comma
+pub fn load_oracle_plugins(s: &str) -> Result<Vec<OraclePlugin>, String> {
+    s.split(',')
+        .map(|path| path.trim())
+        .filter(|path| !path.is_empty())
+        .map(OraclePlugin::load)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_oracle_plugins() {
+        assert!(load_oracle_plugins("").unwrap().is_empty());
+        assert!(load_oracle_plugins("does_not_exist.so")
+            .err()
+            .unwrap()
+            .starts_with("failed to load oracle plugin does_not_exist.so"));
+
+        // the callbacks given to the plugins
+        let address = EVMAddress::from_slice(&[0x11; 20]);
+        let mut state = EVMState::default();
+        state.sstore(address, EVMU256::from(1), EVMU256::from(7));
+        let state_ptr = &state as *const EVMState as *const c_void;
+        let mut value = [0u8; 32];
+        assert!(plugin_sload(
+            state_ptr,
+            &address.0,
+            &EVMU256::from(1).to_be_bytes::<32>(),
+            &mut value
+        ));
+        assert_eq!(EVMU256::from_be_bytes(value), EVMU256::from(7));
+        assert!(!plugin_sload(state_ptr, &address.0, &[0u8; 32], &mut value));
+        assert!(!plugin_balance(state_ptr, &address.0, &mut value));
+
+        let mut reports: Vec<(u64, String)> = vec![];
+        plugin_report(
+            &mut reports as *mut Vec<(u64, String)> as *mut c_void,
+            3,
+            b"drained\0".as_ptr() as *const c_char,
+        );
+        assert_eq!(reports, vec![(3, "drained".to_string())]);
+    }
+}
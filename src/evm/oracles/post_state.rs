@@ -0,0 +1,233 @@
+//! Oracles only reading the post-state of the execution. They do not need the
+//! executor nor the fuzz state, so [`PostStateOracles`] evaluates them
+//! concurrently when many of them are attached, and reports their bugs
+//! afterwards.
+//!
+//! The fund loss, reentrancy, contract size, event and plugin oracles are
+//! post-state oracles. The post-state is the one left by the oracles running
+//! before, i.e., after the liquidation of the attacker's tokens. The price
+//! manipulation oracle is not one, as it replays the calls reading prices on
+//! the executor.
+
+use bytes::Bytes;
+use rayon::prelude::*;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    r#const::PARALLEL_ORACLE_THRESHOLD,
+    state::HasExecutionResult,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostStateBug {
+    pub bug_type: String,
+    pub bug_idx: u64,
+    pub message: String,
+    /// Name of the contract the bug is in
+    pub source: Option<String>,
+}
+
+pub trait PostStateOracle: Send + Sync {
+    /// Bugs of the transaction calling `contract` and leading to `post_state`
+    fn check(&self, contract: EVMAddress, post_state: &EVMState) -> Vec<PostStateBug>;
+}
+
+pub struct PostStateOracles {
+    oracles: Vec<Box<dyn PostStateOracle>>,
+    /// Whether the oracles are evaluated concurrently
+    pub parallel: bool,
+}
+
+impl PostStateOracles {
+    pub fn new(oracles: Vec<Box<dyn PostStateOracle>>) -> Self {
+        let parallel = oracles.len() >= PARALLEL_ORACLE_THRESHOLD;
+        Self { oracles, parallel }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.oracles.is_empty()
+    }
+
+    /// Bugs found by the oracles, in the order of the oracles
+    pub fn check(&self, contract: EVMAddress, post_state: &EVMState) -> Vec<PostStateBug> {
+        if self.parallel {
+            self.oracles
+                .par_iter()
+                .flat_map_iter(|oracle| oracle.check(contract, post_state))
+                .collect()
+        } else {
+            self.oracles
+                .iter()
+                .flat_map(|oracle| oracle.check(contract, post_state))
+                .collect()
+        }
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for PostStateOracles
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        let mut res = vec![];
+        let bugs = self.check(
+            ctx.input.get_contract(),
+            &ctx.fuzz_state.get_execution_result().new_state.state,
+        );
+        for bug in bugs {
+            if res.contains(&bug.bug_idx) || oracle_should_skip!(ctx, bug.bug_idx) {
+                continue;
+            }
+            EVMBugResult::new(
+                bug.bug_type,
+                bug.bug_idx,
+                bug.message,
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+                None,
+                bug.source,
+            )
+            .push_to_output();
+            res.push(bug.bug_idx);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use alloy_dyn_abi::DynSolValue;
+
+    use super::*;
+    use crate::evm::{
+        host::EmittedLog,
+        middlewares::reentrancy::ReentrantCall,
+        oracles::{
+            erc20::FundLossOracle,
+            event::{parse_event_properties, EventOracle},
+            reentrancy::ReentrancyOracle,
+            OracleThresholds,
+            MULTI_BLOCK_FUND_LOSS_BUG_IDX,
+        },
+        types::EVMU512,
+    };
+
+    fn event_oracles(count: usize) -> PostStateOracles {
+        let events = parse_event_properties("InvariantViolated(string)").unwrap();
+        PostStateOracles::new(
+            (0..count)
+                .map(|_| Box::new(EventOracle::new(HashMap::new(), events.clone())) as Box<dyn PostStateOracle>)
+                .collect(),
+        )
+    }
+
+    fn post_state(logs: usize) -> EVMState {
+        let events = parse_event_properties("InvariantViolated(string)").unwrap();
+        let mut state = EVMState::default();
+        state.logs = (0..logs)
+            .map(|idx| EmittedLog {
+                address: EVMAddress::from_slice(&[idx as u8; 20]),
+                topics: vec![events[0].selector().0.into()],
+                data: DynSolValue::Tuple(vec![DynSolValue::String(format!("x > {}", idx))])
+                    .abi_encode_params()
+                    .into(),
+            })
+            .collect();
+        state
+    }
+
+    #[test]
+    fn test_post_state_oracles() {
+        let state = post_state(2);
+        let mut oracles = event_oracles(PARALLEL_ORACLE_THRESHOLD);
+        assert!(oracles.parallel);
+        let bugs = oracles.check(EVMAddress::zero(), &state);
+        assert_eq!(bugs.len(), 2 * PARALLEL_ORACLE_THRESHOLD);
+        oracles.parallel = false;
+        assert_eq!(oracles.check(EVMAddress::zero(), &state), bugs);
+        assert!(!event_oracles(1).parallel);
+    }
+
+    #[test]
+    fn test_fund_loss_and_reentrancy() {
+        let mut state = EVMState::default();
+        let oracles = PostStateOracles::new(vec![
+            Box::new(FundLossOracle::new(OracleThresholds::default())),
+            Box::new(ReentrancyOracle::new(HashMap::new())),
+        ]);
+        assert!(oracles.check(EVMAddress::zero(), &state).is_empty());
+
+        state.flashloan_data.earned = EVMU512::from(100) * EVMU512::from(10).pow(EVMU512::from(24));
+        state.reentrancy_metadata.reentrant_calls.insert(
+            EVMAddress::zero(),
+            ReentrantCall {
+                trace: vec![EVMAddress::zero()],
+                slots: vec![EVMU256::from(1)],
+            },
+        );
+        let bugs = oracles.check(EVMAddress::zero(), &state);
+        assert_eq!(
+            bugs.iter().map(|bug| bug.bug_type.as_str()).collect::<Vec<_>>(),
+            vec!["Fund Loss", "Reentrancy"]
+        );
+    }
+
+    #[test]
+    fn test_fund_loss_across_blocks() {
+        let oracles = PostStateOracles::new(vec![Box::new(FundLossOracle::new(OracleThresholds::default()))]);
+        let eth = EVMU512::from(10).pow(EVMU512::from(24));
+        let mut state = EVMState::default();
+        state.flashloan_data.owed = EVMU512::from(50) * eth;
+        state.flashloan_data.carry_debt();
+        assert_eq!(state.flashloan_data.carried, EVMU512::from(50) * eth);
+        // repaid in the later block, the largest debt is kept
+        state.flashloan_data.earned = EVMU512::from(150) * eth;
+        state.flashloan_data.carry_debt();
+        assert_eq!(state.flashloan_data.carried, EVMU512::from(50) * eth);
+
+        let bugs = oracles.check(EVMAddress::zero(), &state);
+        assert_eq!(bugs.len(), 1);
+        assert_eq!(bugs[0].bug_idx, MULTI_BLOCK_FUND_LOSS_BUG_IDX);
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use libafl::state::HasMetadata;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput, EVMInputT},
+        middlewares::price_source::{PriceRead, BALANCE_OF},
+        oracle::EVMBugResult,
+        oracles::{erc20::IERC20OracleFlashloan, PRICE_MANIPULATION_BUG_IDX},
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    generic_vm::{vm_executor::GenericVM, vm_state::VMStateT},
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+/// Detects price oracle manipulation: a contract reading a price source
+/// (Chainlink feed, Uniswap reserves / TWAP, balance of a pool, ... tracked by
+/// [`crate::evm::middlewares::price_source::PriceSourceTracer`]) that the
+/// fuzzer has moved away from its initial value, and paying out more than it
+/// would if the price source had not been touched.
+///
+/// The payout without manipulation is obtained by replaying the transaction
+/// with a fast call on a copy of the state where the storage slots of the
+/// price source the read depends on are restored to their initial value.
+pub struct PriceManipulationOracle {
+    pub address_to_name: HashMap<EVMAddress, String>,
+    /// Contracts whose bytecode refers to price sources
+    pub consumers: HashSet<EVMAddress>,
+    /// Storage of the contracts before fuzzing
+    pub initial_state: EVMState,
+    /// Provides the known tokens
+    pub erc20_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
+}
+
+impl PriceManipulationOracle {
+    pub fn new(
+        address_to_name: HashMap<EVMAddress, String>,
+        consumers: HashSet<EVMAddress>,
+        initial_state: EVMState,
+        erc20_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
+    ) -> Self {
+        Self {
+            address_to_name,
+            consumers,
+            initial_state,
+            erc20_oracle,
+        }
+    }
+
+    fn name(&self, addr: &EVMAddress) -> String {
+        self.address_to_name.get(addr).cloned().unwrap_or(format!("{:?}", addr))
+    }
+
+    /// `balanceOf` reads are only considered for contracts known to read
+    /// prices, as most contracts query balances
+    fn is_relevant(&self, read: &PriceRead) -> bool {
+        read.selector() != BALANCE_OF || self.consumers.contains(&read.consumer)
+    }
+
+    /// `state` with `slots` of `source` restored to their initial value
+    fn restore_slots(&self, state: &EVMState, source: &EVMAddress, slots: &[EVMU256]) -> EVMState {
+        let mut state = state.clone();
+        for slot in slots {
+            let initial = self.initial_state.sload(*source, *slot).unwrap_or_default();
+            state.sstore(*source, *slot, initial);
+        }
+        state
+    }
+
+    /// `state` with the slots of the price source changed since the initial
+    /// state and affecting the price read restored, along with the initial
+    /// price. When no single slot moves the price back, all the changed slots
+    /// are restored.
+    fn restore_source(
+        &self,
+        ctx: &mut EVMOracleCtx<'_>,
+        read: &PriceRead,
+        manipulated_price: &[u8],
+    ) -> (EVMState, Vec<u8>) {
+        let state = ctx.pre_state;
+        let initial = self.initial_state.state.get(&read.source);
+        let changed = state
+            .state
+            .get(&read.source)
+            .map(|storage| {
+                storage
+                    .iter()
+                    .filter(|(slot, value)| initial.and_then(|initial| initial.get(slot)) != Some(value))
+                    .map(|(slot, _)| *slot)
+                    .collect_vec()
+            })
+            .unwrap_or_default();
+        let calls = [(read.source, read.input.clone())];
+        let mut executor = ctx.executor.deref().borrow_mut();
+
+        let price_slots = changed
+            .iter()
+            .filter(|slot| {
+                let restored = self.restore_slots(state, &read.source, &[**slot]);
+                executor
+                    .fast_static_call(&calls, &restored, ctx.fuzz_state)
+                    .pop()
+                    .unwrap_or_default() !=
+                    *manipulated_price
+            })
+            .cloned()
+            .collect_vec();
+        let restored = if price_slots.is_empty() {
+            self.restore_slots(state, &read.source, &changed)
+        } else {
+            self.restore_slots(state, &read.source, &price_slots)
+        };
+        let initial_price = executor
+            .fast_static_call(&calls, &restored, ctx.fuzz_state)
+            .pop()
+            .unwrap_or_default();
+        (restored, initial_price)
+    }
+
+    /// Balances of `holder` of ETH and of each token
+    fn balances(
+        ctx: &mut EVMOracleCtx<'_>,
+        tokens: &[EVMAddress],
+        holder: &EVMAddress,
+        state: &EVMState,
+    ) -> Vec<EVMU256> {
+        let call = Bytes::from([BALANCE_OF.to_vec(), vec![0u8; 12], holder.0.to_vec()].concat());
+        let calls = tokens.iter().map(|token| (*token, call.clone())).collect_vec();
+        let mut res = vec![state.balance.get(holder).cloned().unwrap_or_default()];
+        res.extend(
+            ctx.executor
+                .deref()
+                .borrow_mut()
+                .fast_static_call(&calls, state, ctx.fuzz_state)
+                .iter()
+                .map(|out| {
+                    if out.len() == 32 {
+                        EVMU256::try_from_be_slice(out).unwrap_or_default()
+                    } else {
+                        EVMU256::ZERO
+                    }
+                }),
+        );
+        res
+    }
+
+    /// Decrease of the balances of `holder` of ETH and of each token between
+    /// `pre` and `post`
+    fn payouts(
+        ctx: &mut EVMOracleCtx<'_>,
+        tokens: &[EVMAddress],
+        holder: &EVMAddress,
+        pre: &EVMState,
+        post: &EVMState,
+    ) -> Vec<EVMU256> {
+        let before = Self::balances(ctx, tokens, holder, pre);
+        let after = Self::balances(ctx, tokens, holder, post);
+        before
+            .iter()
+            .zip(after.iter())
+            .map(|(before, after)| before.saturating_sub(*after))
+            .collect()
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for PriceManipulationOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if ctx.input.is_step() || ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+        let reads = ctx
+            .post_state
+            .price_reads
+            .iter()
+            .filter(|read| self.is_relevant(read))
+            .cloned()
+            .collect_vec();
+        if reads.is_empty() {
+            return vec![];
+        }
+
+        let mut tokens = self.erc20_oracle.borrow().known_tokens.keys().cloned().collect_vec();
+        for read in &reads {
+            if read.selector() == BALANCE_OF && !tokens.contains(&read.source) {
+                tokens.push(read.source);
+            }
+        }
+
+        let mut res = vec![];
+        for read in reads {
+            let mut hasher = DefaultHasher::new();
+            read.consumer.hash(&mut hasher);
+            read.source.hash(&mut hasher);
+            read.selector().hash(&mut hasher);
+            let bug_idx = (hasher.finish() << 8) + PRICE_MANIPULATION_BUG_IDX;
+            if oracle_should_skip!(ctx, bug_idx) {
+                continue;
+            }
+
+            // whether the fuzzer has moved the price
+            let calls = [(read.source, read.input.clone())];
+            let manipulated_price = ctx.call_pre_batch(&calls).pop().unwrap_or_default();
+            let (restored, initial_price) = self.restore_source(ctx, &read, &manipulated_price);
+            if manipulated_price.is_empty() || manipulated_price == initial_price {
+                continue;
+            }
+
+            // replay the transaction without the manipulation, leaving the
+            // host and the execution result untouched
+            let mut calldata = ctx.input.to_bytes();
+            if calldata.is_empty() {
+                calldata = ctx.input.get_direct_data();
+            }
+            let call = [(ctx.input.caller, ctx.input.contract, Bytes::from(calldata))];
+            let (outs, unmanipulated_state) =
+                ctx.executor
+                    .deref()
+                    .borrow_mut()
+                    .fast_call(&call, &restored, ctx.fuzz_state);
+            let unmanipulated_state = match outs.first() {
+                Some((_, true)) => unmanipulated_state,
+                _ => restored.clone(),
+            };
+
+            // compare the amounts paid out by the consumer
+            let (pre_state, post_state) = (ctx.pre_state.clone(), ctx.post_state.clone());
+            let paid = Self::payouts(ctx, &tokens, &read.consumer, &pre_state, &post_state);
+            let paid_unmanipulated = Self::payouts(ctx, &tokens, &read.consumer, &restored, &unmanipulated_state);
+            let overpaid = paid
+                .iter()
+                .zip(paid_unmanipulated.iter())
+                .any(|(paid, expected)| paid > expected);
+            if !overpaid {
+                continue;
+            }
+
+            let name = self.name(&read.consumer);
+            EVMBugResult::new(
+                "Price Manipulation".to_string(),
+                bug_idx,
+                format!(
+                    "{} pays out more after the price read from {} (0x{}) is manipulated from 0x{} to 0x{}\n",
+                    name,
+                    self.name(&read.source),
+                    hex::encode(read.selector()),
+                    hex::encode(&initial_price),
+                    hex::encode(&manipulated_price),
+                ),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+                None,
+                Some(name),
+            )
+            .push_to_output();
+            res.push(bug_idx);
+        }
+        res
+    }
+}
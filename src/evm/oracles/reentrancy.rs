@@ -3,21 +3,13 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-use bytes::Bytes;
 use itertools::Itertools;
-use revm_primitives::Bytecode;
 
 use super::REENTRANCY_BUG_IDX;
-use crate::{
-    evm::{
-        input::{ConciseEVMInput, EVMInput},
-        oracle::EVMBugResult,
-        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
-        vm::EVMState,
-    },
-    generic_vm::vm_state::VMStateT,
-    oracle::{Oracle, OracleCtx},
-    state::HasExecutionResult,
+use crate::evm::{
+    oracles::post_state::{PostStateBug, PostStateOracle},
+    types::EVMAddress,
+    vm::EVMState,
 };
 
 pub struct ReentrancyOracle {
@@ -28,54 +20,19 @@ impl ReentrancyOracle {
     pub fn new(address_to_name: HashMap<EVMAddress, String>) -> Self {
         Self { address_to_name }
     }
-}
 
-impl
-    Oracle<
-        EVMState,
-        EVMAddress,
-        Bytecode,
-        Bytes,
-        EVMAddress,
-        EVMU256,
-        Vec<u8>,
-        EVMInput,
-        EVMFuzzState,
-        ConciseEVMInput,
-        EVMQueueExecutor,
-    > for ReentrancyOracle
-{
-    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
-        0
+    fn name(&self, addr: &EVMAddress) -> String {
+        self.address_to_name.get(addr).cloned().unwrap_or(format!("{:?}", addr))
     }
+}
 
-    fn oracle(
-        &self,
-        ctx: &mut OracleCtx<
-            EVMState,
-            EVMAddress,
-            Bytecode,
-            Bytes,
-            EVMAddress,
-            EVMU256,
-            Vec<u8>,
-            EVMInput,
-            EVMFuzzState,
-            ConciseEVMInput,
-            EVMQueueExecutor,
-        >,
-        _stage: u64,
-    ) -> Vec<u64> {
-        let reetrancy_metadata = unsafe {
-            &ctx.post_state
-                .as_any()
-                .downcast_ref_unchecked::<EVMState>()
-                .reentrancy_metadata
-        };
-        if reetrancy_metadata.found.is_empty() {
+impl PostStateOracle for ReentrancyOracle {
+    fn check(&self, _contract: EVMAddress, post_state: &EVMState) -> Vec<PostStateBug> {
+        let reetrancy_metadata = &post_state.reentrancy_metadata;
+        if reetrancy_metadata.found.is_empty() && reetrancy_metadata.reentrant_calls.is_empty() {
             return vec![];
         }
-        reetrancy_metadata
+        let mut res = reetrancy_metadata
             .found
             .iter()
             .map(|(addr, slot)| {
@@ -83,18 +40,35 @@ impl
                 addr.hash(&mut hasher);
                 let real_bug_idx = (hasher.finish() << 8) + REENTRANCY_BUG_IDX;
 
-                let name = self.address_to_name.get(addr).unwrap_or(&format!("{:?}", addr)).clone();
-                EVMBugResult::new(
-                    "Reentrancy".to_string(),
-                    real_bug_idx,
-                    format!("Reentrancy on {:?} at slot {:?}", name, slot),
-                    ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
-                    None,
-                    Some(name.clone()),
-                )
-                .push_to_output();
-                real_bug_idx
+                let name = self.name(addr);
+                PostStateBug {
+                    bug_type: "Reentrancy".to_string(),
+                    bug_idx: real_bug_idx,
+                    message: format!("Reentrancy on {:?} at slot {:?}", name, slot),
+                    source: Some(name),
+                }
             })
-            .collect_vec()
+            .collect_vec();
+
+        for (addr, call) in reetrancy_metadata.reentrant_calls.iter() {
+            let mut hasher = DefaultHasher::new();
+            addr.hash(&mut hasher);
+            call.slots.hash(&mut hasher);
+            let real_bug_idx = (hasher.finish() << 8) + REENTRANCY_BUG_IDX;
+
+            let name = self.name(addr);
+            let trace = call.trace.iter().map(|addr| self.name(addr)).join(" -> ");
+            let slots = call.slots.iter().map(|slot| format!("{:#x}", slot)).join(", ");
+            res.push(PostStateBug {
+                bug_type: "Reentrancy".to_string(),
+                bug_idx: real_bug_idx,
+                message: format!(
+                    "{} is reentered after writing to storage\nCall trace: {}\nSlots written before reentry: {}\n",
+                    name, trace, slots
+                ),
+                source: Some(name),
+            });
+        }
+        res
     }
 }
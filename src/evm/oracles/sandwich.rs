@@ -0,0 +1,168 @@
+//! Sandwich of the pending transaction of a user: the transactions the
+//! fuzzer runs before it (front-run) and after it (back-run) either earn more
+//! than the minimum profit only because the victim transaction ran in between,
+//! or change the outcome of the victim transaction, e.g., make it revert or
+//! return less than it would on the unmodified state.
+
+use bytes::Bytes;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput, EVMInputT, EVMInputTy},
+        mempool::VictimOutcome,
+        oracle::EVMBugResult,
+        oracles::{OracleThresholds, SANDWICH_BUG_IDX},
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256, EVMU512},
+        vm::EVMState,
+    },
+    generic_vm::vm_state::VMStateT,
+    oracle::{BugMetadata, Oracle},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+pub struct SandwichOracle {
+    /// Outcome of the victim transaction on the forked state, without
+    /// front-run
+    baseline: VictimOutcome,
+    thresholds: OracleThresholds,
+}
+
+impl SandwichOracle {
+    pub fn new(baseline: VictimOutcome, thresholds: OracleThresholds) -> Self {
+        Self { baseline, thresholds }
+    }
+
+    /// How the front-run changed the outcome of the victim transaction, None
+    /// if it did not
+    fn interference(&self, outcome: &VictimOutcome) -> Option<String> {
+        if self.baseline.reverted {
+            return None;
+        }
+        if outcome.reverted {
+            Some("reverts".to_string())
+        } else if outcome.output != self.baseline.output {
+            Some(format!(
+                "returns 0x{} instead of 0x{}",
+                hex::encode(&outcome.output),
+                hex::encode(&self.baseline.output)
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Earnings of the attacker exceeding their debt, zero if the debt is larger
+fn net(earned: EVMU512, owed: EVMU512) -> EVMU512 {
+    earned.saturating_sub(owed)
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for SandwichOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> Vec<u64> {
+        let new_state = &ctx.fuzz_state.get_execution_result().new_state.state;
+        if new_state.has_post_execution() {
+            return vec![];
+        }
+        let Some(outcome) = new_state.victim.clone() else {
+            return vec![];
+        };
+
+        // the victim transaction itself, on the state left by the front-run
+        if ctx.input.get_input_type() == EVMInputTy::Victim {
+            let bug_idx = (1 << 8) + SANDWICH_BUG_IDX;
+            if oracle_should_skip!(ctx, bug_idx) {
+                return vec![];
+            }
+            let Some(change) = self.interference(&outcome) else {
+                return vec![];
+            };
+            EVMBugResult::new_simple(
+                "Sandwich".to_string(),
+                bug_idx,
+                format!(
+                    "The victim transaction {} when front-run by the preceding transactions\n",
+                    change
+                ),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+            )
+            .push_to_output();
+            return vec![bug_idx];
+        }
+
+        // a back-run, profitable only with the victim transaction in between
+        if oracle_should_skip!(ctx, SANDWICH_BUG_IDX) || new_state.gas_used > self.thresholds.max_gas {
+            return vec![];
+        }
+        let data = &new_state.flashloan_data;
+        let min_profit = self.thresholds.min_profit_scaled();
+        let profit = net(data.earned, data.owed);
+        if profit <= min_profit || net(outcome.earned_before, outcome.owed_before) > min_profit {
+            return vec![];
+        }
+        // we scaled by 1e24, so divide by 1e24 to get ETH
+        let scale = EVMU512::from(1_000_000_000_000_000_000_000_u128);
+        EVMBugResult::new_simple(
+            "Sandwich".to_string(),
+            SANDWICH_BUG_IDX,
+            format!(
+                "Anyone can earn {} by front-running and back-running the victim transaction\n",
+                self.thresholds.numeraire.format(profit, scale)
+            ),
+            ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+        )
+        .push_to_output();
+        vec![SANDWICH_BUG_IDX]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interference() {
+        let baseline = VictimOutcome {
+            output: vec![0x10],
+            ..Default::default()
+        };
+        let oracle = SandwichOracle::new(baseline.clone(), OracleThresholds::default());
+        assert_eq!(oracle.interference(&baseline), None);
+        let reverted = VictimOutcome {
+            reverted: true,
+            ..Default::default()
+        };
+        assert_eq!(oracle.interference(&reverted).as_deref(), Some("reverts"));
+        let output = VictimOutcome {
+            output: vec![0x08],
+            ..Default::default()
+        };
+        assert_eq!(
+            oracle.interference(&output).as_deref(),
+            Some("returns 0x08 instead of 0x10")
+        );
+
+        // a victim transaction already reverting on the forked state
+        let oracle = SandwichOracle::new(reverted.clone(), OracleThresholds::default());
+        assert_eq!(oracle.interference(&reverted), None);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use libafl::state::HasMetadata;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::SUPPLY_BUG_IDX,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    generic_vm::vm_state::VMStateT,
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+/// Selector of `totalSupply()`
+const TOTAL_SUPPLY: [u8; 4] = [0x18, 0x16, 0x0d, 0xdd];
+
+/// A token watched by [`SupplyOracle`]
+pub struct SupplyToken {
+    pub name: String,
+    /// Selectors of the functions allowed to change the supply
+    pub whitelist: HashSet<[u8; 4]>,
+}
+
+/// Detects tokens whose `totalSupply()` changes in a transaction without any
+/// of its whitelisted functions (mint / burn) being called, e.g., hidden mint
+/// backdoors or accounting bugs.
+pub struct SupplyOracle {
+    pub tokens: HashMap<EVMAddress, SupplyToken>,
+}
+
+impl SupplyOracle {
+    pub fn new(tokens: HashMap<EVMAddress, SupplyToken>) -> Self {
+        Self { tokens }
+    }
+
+    /// Whether the ABI (`function_name(abi)` pairs) has `totalSupply()`
+    pub fn has_supply<'a>(mut functions: impl Iterator<Item = (&'a str, &'a str)>) -> bool {
+        functions.any(|(name, abi)| name == "totalSupply" && abi == "()")
+    }
+
+    /// Whether a function is allowed to change the supply: its name starts with
+    /// `mint` / `burn` or is in `extra`
+    pub fn is_whitelisted(function_name: &str, extra: &[String]) -> bool {
+        let lower = function_name.to_lowercase();
+        lower.starts_with("mint") || lower.starts_with("burn") || extra.iter().any(|name| name == function_name)
+    }
+}
+
+fn parse_supply(out: &[u8]) -> Option<EVMU256> {
+    if out.len() != 32 {
+        return None;
+    }
+    EVMU256::try_from_be_slice(out)
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for SupplyOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if self.tokens.is_empty() || ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+
+        let tokens = self.tokens.keys().cloned().collect_vec();
+        let calls = tokens
+            .iter()
+            .map(|token| (*token, Bytes::from(TOTAL_SUPPLY.to_vec())))
+            .collect_vec();
+        let pre_supplies = ctx.call_pre_batch(&calls);
+        let post_supplies = ctx.call_post_batch(&calls);
+
+        // the function called by the transaction and the ones called by contracts
+        let mut called = ctx.post_state.call_path.clone();
+        if let Some(abi) = ctx.input.get_data_abi() {
+            called.insert((ctx.input.get_contract(), abi.function));
+        }
+
+        let mut res = vec![];
+        for ((token, pre), post) in tokens.iter().zip(pre_supplies).zip(post_supplies) {
+            let (pre, post) = match (parse_supply(&pre), parse_supply(&post)) {
+                (Some(pre), Some(post)) if pre != post => (pre, post),
+                _ => continue,
+            };
+            let info = &self.tokens[token];
+            if called
+                .iter()
+                .any(|(addr, selector)| addr == token && info.whitelist.contains(selector))
+            {
+                continue;
+            }
+
+            let mut hasher = DefaultHasher::new();
+            token.hash(&mut hasher);
+            (post > pre).hash(&mut hasher);
+            let bug_idx = (hasher.finish() << 8) + SUPPLY_BUG_IDX;
+            if oracle_should_skip!(ctx, bug_idx) {
+                continue;
+            }
+
+            EVMBugResult::new(
+                "Implicit Supply Change".to_string(),
+                bug_idx,
+                format!(
+                    "Total supply of {} changed from {} to {} without calling any of its mint / burn functions\n",
+                    info.name, pre, post
+                ),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+                None,
+                Some(info.name.clone()),
+            )
+            .push_to_output();
+            res.push(bug_idx);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_whitelisted() {
+        assert!(SupplyOracle::is_whitelisted("mint", &[]));
+        assert!(SupplyOracle::is_whitelisted("burnFrom", &[]));
+        assert!(SupplyOracle::is_whitelisted("rebase", &["rebase".to_string()]));
+        assert!(!SupplyOracle::is_whitelisted("transfer", &[]));
+        assert!(SupplyOracle::has_supply([("totalSupply", "()")].into_iter()));
+    }
+}
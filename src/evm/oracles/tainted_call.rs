@@ -0,0 +1,147 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use libafl::state::HasMetadata;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        middlewares::call_taint::TaintedCall,
+        oracle::EVMBugResult,
+        oracles::{erc20::IERC20OracleFlashloan, TAINTED_CALL_BUG_IDX},
+        srcmap::SOURCE_MAP_PROVIDER,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+/// Detects CALL / DELEGATECALL whose target or function selector is
+/// controlled by the fuzzer input (tracked by
+/// [`crate::evm::middlewares::call_taint::CallTaintTracer`]) made by a
+/// contract holding funds, i.e., ETH or any known token. Attacker controlled
+/// DELEGATECALL targets are always reported.
+pub struct TaintedCallOracle {
+    pub address_to_name: HashMap<EVMAddress, String>,
+    /// Provides the known tokens
+    pub erc20_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
+}
+
+impl TaintedCallOracle {
+    pub fn new(address_to_name: HashMap<EVMAddress, String>, erc20_oracle: Rc<RefCell<IERC20OracleFlashloan>>) -> Self {
+        Self {
+            address_to_name,
+            erc20_oracle,
+        }
+    }
+
+    fn name(&self, addr: &EVMAddress) -> String {
+        self.address_to_name.get(addr).cloned().unwrap_or(format!("{:?}", addr))
+    }
+
+    /// Whether `holder` has ETH or a positive balance of any known token
+    fn holds_funds(&self, ctx: &mut EVMOracleCtx<'_>, holder: EVMAddress) -> bool {
+        if ctx.post_state.balance.get(&holder).is_some_and(|b| *b > EVMU256::ZERO) {
+            return true;
+        }
+        let erc20_oracle = self.erc20_oracle.borrow();
+        let call = Bytes::from([erc20_oracle.balance_of.clone(), vec![0u8; 12], holder.0.to_vec()].concat());
+        let calls = erc20_oracle
+            .known_tokens
+            .keys()
+            .map(|token| (*token, call.clone()))
+            .collect_vec();
+        ctx.call_post_batch(&calls)
+            .iter()
+            .any(|out| out.len() == 32 && EVMU256::try_from_be_slice(out).is_some_and(|b| b > EVMU256::ZERO))
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for TaintedCallOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        let calls: Vec<TaintedCall> = ctx.post_state.tainted_calls.iter().cloned().collect();
+        let mut res = vec![];
+        for call in calls {
+            let mut hasher = DefaultHasher::new();
+            call.caller.hash(&mut hasher);
+            call.pc.hash(&mut hasher);
+            call.delegate.hash(&mut hasher);
+            let real_bug_idx = (hasher.finish() << 8) + TAINTED_CALL_BUG_IDX;
+            if oracle_should_skip!(ctx, real_bug_idx) {
+                continue;
+            }
+            if !(call.delegate && call.tainted_target) && !self.holds_funds(ctx, call.caller) {
+                continue;
+            }
+
+            let name = self.name(&call.caller);
+            EVMBugResult::new(
+                "Tainted External Call".to_string(),
+                real_bug_idx,
+                format!(
+                    "{} from {} to {} with attacker controlled {}",
+                    if call.delegate { "Delegatecall" } else { "Call" },
+                    name,
+                    self.name(&call.target),
+                    if call.tainted_target {
+                        "target"
+                    } else {
+                        "function selector"
+                    },
+                ),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+                SOURCE_MAP_PROVIDER
+                    .lock()
+                    .unwrap()
+                    .get_raw_source_map_info(&call.caller, call.pc),
+                Some(name),
+            )
+            .push_to_output();
+            res.push(real_bug_idx);
+        }
+        res
+    }
+}
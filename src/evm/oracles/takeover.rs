@@ -0,0 +1,195 @@
+//! Contract takeover: contracts whose owner (`owner()`) or EIP-1967
+//! implementation / admin / beacon is set to an attacker address
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use bytes::Bytes;
+use itertools::Itertools;
+use libafl::state::HasMetadata;
+use revm_primitives::Bytecode;
+
+use crate::{
+    evm::{
+        input::{ConciseEVMInput, EVMInput},
+        oracle::EVMBugResult,
+        oracles::TAKEOVER_BUG_IDX,
+        types::{convert_u256_to_h160, EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    generic_vm::vm_state::VMStateT,
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    state::{HasCaller, HasExecutionResult},
+};
+
+/// Selector of `owner()`
+const OWNER: [u8; 4] = [0x8d, 0xa5, 0xcb, 0x5b];
+
+/// EIP-1967 slots of the implementation, admin and beacon of proxies
+const PROXY_SLOTS: [(&str, &str); 3] = [
+    (
+        "implementation",
+        "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc",
+    ),
+    (
+        "admin",
+        "0xb53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103",
+    ),
+    (
+        "beacon",
+        "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50",
+    ),
+];
+
+pub struct TakeoverOracle {
+    pub address_to_name: HashMap<EVMAddress, String>,
+    /// Contracts with an `owner()` function
+    pub owned: HashSet<EVMAddress>,
+    proxy_slots: Vec<(&'static str, EVMU256)>,
+}
+
+impl TakeoverOracle {
+    pub fn new(address_to_name: HashMap<EVMAddress, String>, owned: HashSet<EVMAddress>) -> Self {
+        Self {
+            address_to_name,
+            owned,
+            proxy_slots: PROXY_SLOTS
+                .iter()
+                .map(|(role, slot)| (*role, EVMU256::from_str(slot).unwrap()))
+                .collect(),
+        }
+    }
+
+    /// Whether the ABI (`function_name(abi)` pairs) has `owner()`
+    pub fn has_owner<'a>(mut functions: impl Iterator<Item = (&'a str, &'a str)>) -> bool {
+        functions.any(|(name, abi)| name == "owner" && abi == "()")
+    }
+
+    fn name(&self, addr: &EVMAddress) -> String {
+        self.address_to_name.get(addr).cloned().unwrap_or(format!("{:?}", addr))
+    }
+
+    /// Privileged roles (contract, role, new holder) set to an attacker
+    /// address by the execution
+    fn taken_over_roles(&self, ctx: &mut EVMOracleCtx<'_>) -> Vec<(EVMAddress, &'static str, EVMAddress)> {
+        let mut res = vec![];
+        for addr in self.address_to_name.keys() {
+            for (role, slot) in &self.proxy_slots {
+                let post = ctx.post_state.sload(*addr, *slot);
+                if post.is_none() || post == ctx.pre_state.sload(*addr, *slot) {
+                    continue;
+                }
+                let holder = convert_u256_to_h160(post.unwrap());
+                if ctx.fuzz_state.has_caller(&holder) {
+                    res.push((*addr, *role, holder));
+                }
+            }
+        }
+
+        if self.owned.is_empty() {
+            return res;
+        }
+        let owned = self.owned.iter().cloned().collect_vec();
+        let calls = owned
+            .iter()
+            .map(|addr| (*addr, Bytes::from(OWNER.to_vec())))
+            .collect_vec();
+        let pre_owners = ctx.call_pre_batch(&calls);
+        let post_owners = ctx.call_post_batch(&calls);
+        for ((addr, pre), post) in owned.iter().zip(pre_owners).zip(post_owners) {
+            if post.len() != 32 || pre == post {
+                continue;
+            }
+            let holder = EVMAddress::from_slice(&post[12..32]);
+            if ctx.fuzz_state.has_caller(&holder) {
+                res.push((*addr, "owner", holder));
+            }
+        }
+        res
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for TakeoverOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        if ctx.post_state.has_post_execution() {
+            return vec![];
+        }
+        let mut res = vec![];
+        for (addr, role, holder) in self.taken_over_roles(ctx) {
+            let mut hasher = DefaultHasher::new();
+            addr.hash(&mut hasher);
+            role.hash(&mut hasher);
+            let real_bug_idx = (hasher.finish() << 8) + TAKEOVER_BUG_IDX;
+            if oracle_should_skip!(ctx, real_bug_idx) {
+                continue;
+            }
+
+            let name = self.name(&addr);
+            EVMBugResult::new(
+                "Contract Takeover".to_string(),
+                real_bug_idx,
+                format!("The {} of {} is set to attacker address {:?}", role, name, holder),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+                None,
+                Some(name),
+            )
+            .push_to_output();
+            res.push(real_bug_idx);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_owner() {
+        assert!(TakeoverOracle::has_owner([("owner", "()")].into_iter()));
+        assert!(!TakeoverOracle::has_owner([("owner", "(uint256)")].into_iter()));
+        let oracle = TakeoverOracle::new(HashMap::new(), HashSet::new());
+        assert_eq!(
+            oracle.proxy_slots[0].1,
+            EVMU256::from_be_bytes(hex::decode(&PROXY_SLOTS[0].1[2..]).unwrap().try_into().unwrap())
+        );
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
     evm::{
         input::{ConciseEVMInput, EVMInput},
         oracle::EVMBugResult,
-        oracles::V2_PAIR_BUG_IDX,
+        oracles::{OracleThresholds, V2_PAIR_BUG_IDX},
         types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
         vm::EVMState,
     },
@@ -18,17 +18,19 @@ use crate::{
     state::HasExecutionResult,
 };
 
-pub struct PairBalanceOracle {}
+pub struct PairBalanceOracle {
+    pub thresholds: OracleThresholds,
+}
 
 impl Default for PairBalanceOracle {
     fn default() -> Self {
-        Self::new()
+        Self::new(OracleThresholds::default())
     }
 }
 
 impl PairBalanceOracle {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(thresholds: OracleThresholds) -> Self {
+        Self { thresholds }
     }
 }
 
@@ -98,7 +100,10 @@ impl
                 let (pre_r0, pre_r1) = reserve_parser(prev_reserve_slot.unwrap());
                 let (r0, r1) = reserve_parser(new_reserve_slot.unwrap());
 
-                if pre_r0 == r0 && pre_r1 > r1 || pre_r1 == r1 && pre_r0 > r0 {
+                let delta = self.thresholds.min_reserve_delta;
+                if pre_r0 == r0 && pre_r1 > r1.saturating_add(delta) ||
+                    pre_r1 == r1 && pre_r0 > r0.saturating_add(delta)
+                {
                     // calculate hash in u64 of pair address (addr) using DefaultHasher
                     let mut hasher = DefaultHasher::new();
                     addr.hash(&mut hasher);
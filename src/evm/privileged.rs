@@ -0,0 +1,114 @@
+//! Holders of the privileged roles of the onchain contracts, i.e., Ownable
+//! owners, AccessControl admins and proxy admins, impersonated with
+//! `--impersonate-owners`
+//!
+//! Findings whose transactions are sent by one of them are tagged as
+//! requiring a privileged sender, so that centralization issues can be told
+//! apart from bugs exploitable by anyone.
+
+use bytes::Bytes;
+use itertools::Itertools;
+use tracing::info;
+
+use crate::{
+    evm::{
+        onchain::endpoints::OnChainConfig,
+        types::{checksum, EVMAddress, EVMU256},
+    },
+    r#const::ROLE_MEMBERS_MAX,
+};
+
+/// Role holders included in the senders
+pub static mut PRIVILEGED_SENDERS: Vec<EVMAddress> = Vec::new();
+
+/// `owner()`
+const OWNER_SELECTOR: [u8; 4] = [0x8d, 0xa5, 0xcb, 0x5b];
+/// `getRoleMemberCount(bytes32)`
+const ROLE_MEMBER_COUNT_SELECTOR: [u8; 4] = [0xca, 0x15, 0xc8, 0x73];
+/// `getRoleMember(bytes32,uint256)`
+const ROLE_MEMBER_SELECTOR: [u8; 4] = [0x90, 0x10, 0xd0, 0x7c];
+/// Slots holding an admin or owner address: the EIP-1967 proxy admin and the
+/// ERC-7201 storage of `OwnableUpgradeable`
+const ADMIN_SLOTS: [&str; 2] = [
+    "b53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103",
+    "9016d09d72d40fdae2fd8ceac6b6234c7706214fd39c1cd1e609a0528c199300",
+];
+
+/// Address returned by a call, if it returned one
+fn returned_address(ret: &[u8]) -> Option<EVMAddress> {
+    if ret.len() != 32 || ret[..12].iter().any(|b| *b != 0) || ret[12..].iter().all(|b| *b == 0) {
+        return None;
+    }
+    Some(EVMAddress::from_slice(&ret[12..]))
+}
+
+/// Owner, default admins and proxy admin of the contract
+pub fn find_role_holders(endpoint: &mut OnChainConfig, contract: EVMAddress) -> Vec<EVMAddress> {
+    let mut holders = vec![];
+    holders.extend(returned_address(
+        &endpoint.eth_call(contract, Bytes::from(OWNER_SELECTOR.to_vec())),
+    ));
+    for slot in ADMIN_SLOTS {
+        let slot = EVMU256::from_str_radix(slot, 16).unwrap();
+        let value = endpoint.get_contract_slot(contract, slot, false);
+        holders.extend(returned_address(&value.to_be_bytes::<32>()));
+    }
+
+    // members of DEFAULT_ADMIN_ROLE (0x00) of AccessControlEnumerable
+    let default_admin_role = [0u8; 32];
+    let count = endpoint.eth_call(
+        contract,
+        Bytes::from([ROLE_MEMBER_COUNT_SELECTOR.as_slice(), &default_admin_role].concat()),
+    );
+    if count.len() == 32 {
+        let count = EVMU256::from_be_slice(&count).min(EVMU256::from(ROLE_MEMBERS_MAX));
+        for idx in 0..count.as_limbs()[0] {
+            let data = [
+                ROLE_MEMBER_SELECTOR.as_slice(),
+                &default_admin_role,
+                &EVMU256::from(idx).to_be_bytes::<32>(),
+            ]
+            .concat();
+            holders.extend(returned_address(&endpoint.eth_call(contract, Bytes::from(data))));
+        }
+    }
+    holders.into_iter().unique().collect()
+}
+
+/// Find the role holders of the contracts, to be included in the senders
+pub fn impersonate_role_holders(endpoint: &mut OnChainConfig, contracts: &[EVMAddress]) -> Vec<EVMAddress> {
+    let holders = contracts
+        .iter()
+        .flat_map(|contract| find_role_holders(endpoint, *contract))
+        .filter(|holder| !contracts.contains(holder))
+        .unique()
+        .collect_vec();
+    for holder in &holders {
+        info!("Impersonating privileged sender {}", checksum(holder));
+    }
+    unsafe {
+        PRIVILEGED_SENDERS.extend(holders.iter().cloned());
+    }
+    holders
+}
+
+/// Whether the checksummed address is an impersonated role holder
+pub fn is_privileged_sender(address: &str) -> bool {
+    unsafe { PRIVILEGED_SENDERS.iter().any(|sender| checksum(sender) == address) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_returned_address() {
+        let mut ret = vec![0u8; 32];
+        assert_eq!(returned_address(&ret), None);
+        ret[31] = 1;
+        assert_eq!(returned_address(&ret), Some(EVMAddress::from_slice(&ret[12..])));
+        ret[0] = 1;
+        assert_eq!(returned_address(&ret), None);
+        assert_eq!(returned_address(&[]), None);
+    }
+}
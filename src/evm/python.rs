@@ -0,0 +1,479 @@
+//! Python scripts given with `--python-script`, to prototype target-specific
+//! checks without rebuilding ityfuzz. Built with the `python` feature.
+//!
+//! The script registers its hooks with the decorators defined before it runs:
+//!
+//! ```python
+//! @register_oracle
+//! def on_tx_end(state, trace):
+//!     if state["balances"].get("0x...", 0) == 0:
+//!         return "the vault is drained"
+//!
+//! @register_calldata_processor
+//! def fix_deadline(calldata):
+//!     return calldata[:36] + (2**64).to_bytes(32, "big") + calldata[68:]
+//! ```
+//!
+//! - An oracle is called after each transaction with the post-state (`storage`
+//!   and `balances` of the addresses, as dicts keyed by `0x…` addresses, built
+//!   when accessed, or single values read with `sload(address, slot)` and
+//!   `balance(address)`) and the call tree of the transaction (a dict per call,
+//!   see [`frame_to_py`]), and returns a message when it finds a bug, None
+//!   otherwise. Each oracle reports a single bug.
+//! - A calldata processor is called with the calldata of each input once
+//!   mutated, and returns the calldata stored in the input instead, so that the
+//!   corpus and the reports keep the calldata executed. Calldata not decoding
+//!   with the ABI of the input is dropped.
+
+use std::{
+    any,
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    rc::Rc,
+    str::FromStr,
+};
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::{PyBytes, PyDict, PyList, PyLong},
+};
+use revm_interpreter::Interpreter;
+use revm_primitives::Bytecode;
+use tracing::warn;
+
+use crate::{
+    evm::{
+        host::FuzzHost,
+        input::{ConciseEVMInput, EVMInput},
+        middlewares::{
+            middleware::{Middleware, MiddlewareType},
+            trace::{CallFrame, CallTracer, TraceItem},
+        },
+        mutator::CalldataProcessor,
+        oracle::EVMBugResult,
+        oracles::PYTHON_BUG_IDX,
+        types::{EVMAddress, EVMFuzzState, EVMOracleCtx, EVMQueueExecutor, EVMU256},
+        vm::EVMState,
+    },
+    input::VMInputT,
+    oracle::{BugMetadata, Oracle, OracleCtx},
+    oracle_should_skip,
+    state::HasExecutionResult,
+};
+
+/// Defines the decorators, run before the script in the same globals
+const PRELUDE: &str = r#"
+_oracles = []
+_calldata_processors = []
+
+def register_oracle(f):
+    _oracles.append(f)
+    return f
+
+def register_calldata_processor(f):
+    _calldata_processors.append(f)
+    return f
+"#;
+
+/// Hooks registered by a script, along with their names
+#[derive(Debug)]
+pub struct PythonScript {
+    oracles: Vec<(String, PyObject)>,
+    calldata_processors: Vec<(String, PyObject)>,
+}
+
+fn registered(module: &PyModule, list: &str) -> PyResult<Vec<(String, PyObject)>> {
+    module
+        .getattr(list)?
+        .extract::<Vec<PyObject>>()?
+        .into_iter()
+        .map(|f| Ok((f.getattr(module.py(), "__name__")?.extract(module.py())?, f)))
+        .collect()
+}
+
+fn py_int(py: Python<'_>, v: EVMU256) -> PyResult<PyObject> {
+    Ok(py.get_type::<PyLong>().call1((format!("{:x}", v), 16))?.into())
+}
+
+fn py_address(address: &EVMAddress) -> String {
+    format!("{:?}", address)
+}
+
+fn py_word(slot: &PyAny) -> PyResult<EVMU256> {
+    Ok(EVMU256::from_be_slice(
+        &slot.call_method1("to_bytes", (32, "big"))?.extract::<Vec<u8>>()?,
+    ))
+}
+
+fn parse_address(address: &str) -> PyResult<EVMAddress> {
+    EVMAddress::from_str(address).map_err(|_| PyValueError::new_err(format!("invalid address {}", address)))
+}
+
+/// Post-state given to the oracles. `state["storage"]` and `state["balances"]`
+/// are only converted to dicts when accessed, `state.sload(address, slot)` and
+/// `state.balance(address)` read a single value.
+#[pyclass(unsendable)]
+struct PyEVMState {
+    state: EVMState,
+}
+
+#[pymethods]
+impl PyEVMState {
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        match key {
+            "storage" => {
+                let storage = PyDict::new(py);
+                for (address, slots) in self.state.state.iter() {
+                    let slots_py = PyDict::new(py);
+                    for (slot, value) in slots.iter() {
+                        slots_py.set_item(py_int(py, *slot)?, py_int(py, *value)?)?;
+                    }
+                    storage.set_item(py_address(address), slots_py)?;
+                }
+                Ok(storage.into())
+            }
+            "balances" => {
+                let balances = PyDict::new(py);
+                for (address, balance) in self.state.balance.iter() {
+                    balances.set_item(py_address(address), py_int(py, *balance)?)?;
+                }
+                Ok(balances.into())
+            }
+            _ => Err(PyKeyError::new_err(key.to_string())),
+        }
+    }
+
+    fn sload(&self, py: Python<'_>, address: &str, slot: &PyAny) -> PyResult<Option<PyObject>> {
+        self.state
+            .sload(parse_address(address)?, py_word(slot)?)
+            .map(|value| py_int(py, value))
+            .transpose()
+    }
+
+    fn balance(&self, py: Python<'_>, address: &str) -> PyResult<Option<PyObject>> {
+        self.state
+            .get_balance(&parse_address(address)?)
+            .map(|balance| py_int(py, *balance))
+            .transpose()
+    }
+}
+
+/// A call as a dict with `kind` (`Call`, `DelegateCall`, `Create`…),
+/// `caller`, `target` (None for a failed creation), `value`, `input`,
+/// `output`, `success` (None when the call did not return) and `items`, the
+/// nested calls (`type` `call`), logs (`type` `log`, with `address`, `topics`
+/// and `data`) and storage writes (`type` `sstore`, with `address`, `slot` and
+/// `value`) in execution order
+fn frame_to_py<'py>(py: Python<'py>, frame: &CallFrame) -> PyResult<&'py PyDict> {
+    let items = PyList::empty(py);
+    for item in &frame.items {
+        let item_py = match item {
+            TraceItem::Call(call) => {
+                let call_py = frame_to_py(py, call)?;
+                call_py.set_item("type", "call")?;
+                call_py
+            }
+            TraceItem::Log { address, topics, data } => {
+                let log = PyDict::new(py);
+                log.set_item("type", "log")?;
+                log.set_item("address", py_address(address))?;
+                let topics = topics
+                    .iter()
+                    .map(|topic| py_int(py, *topic))
+                    .collect::<PyResult<Vec<_>>>()?;
+                log.set_item("topics", topics)?;
+                log.set_item("data", PyBytes::new(py, data))?;
+                log
+            }
+            TraceItem::Sstore { address, slot, value } => {
+                let sstore = PyDict::new(py);
+                sstore.set_item("type", "sstore")?;
+                sstore.set_item("address", py_address(address))?;
+                sstore.set_item("slot", py_int(py, *slot)?)?;
+                sstore.set_item("value", py_int(py, *value)?)?;
+                sstore
+            }
+        };
+        items.append(item_py)?;
+    }
+    let dict = PyDict::new(py);
+    dict.set_item("kind", format!("{:?}", frame.kind))?;
+    dict.set_item("caller", py_address(&frame.caller))?;
+    dict.set_item("target", frame.target.as_ref().map(py_address))?;
+    dict.set_item("value", py_int(py, frame.value)?)?;
+    dict.set_item("input", PyBytes::new(py, &frame.input))?;
+    dict.set_item("output", PyBytes::new(py, &frame.output))?;
+    dict.set_item("success", frame.success)?;
+    dict.set_item("items", items)?;
+    Ok(dict)
+}
+
+impl PythonScript {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let code = fs::read_to_string(path).map_err(|e| format!("failed to read python script {}: {}", path, e))?;
+        Self::from_code(&code).map_err(|e| format!("failed to load python script {}: {}", path, e))
+    }
+
+    pub fn from_code(code: &str) -> Result<Self, String> {
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "ityfuzz_script")?;
+            py.run(PRELUDE, Some(module.dict()), None)?;
+            py.run(code, Some(module.dict()), None)?;
+            Ok(Self {
+                oracles: registered(module, "_oracles")?,
+                calldata_processors: registered(module, "_calldata_processors")?,
+            })
+        })
+        .map_err(|e: PyErr| e.to_string())
+    }
+
+    pub fn has_oracles(&self) -> bool {
+        !self.oracles.is_empty()
+    }
+
+    /// Processor of the calldata of the mutated inputs, None if the script
+    /// registers no calldata processor
+    pub fn calldata_processor(self: &Rc<Self>) -> Option<CalldataProcessor> {
+        if self.calldata_processors.is_empty() {
+            return None;
+        }
+        let script = self.clone();
+        Some(Rc::new(move |calldata: &[u8]| script.process_calldata(calldata)))
+    }
+
+    /// Calldata returned by the processors, each processing the calldata
+    /// returned by the previous one
+    pub fn process_calldata(&self, calldata: &[u8]) -> Vec<u8> {
+        let mut calldata = calldata.to_vec();
+        Python::with_gil(|py| {
+            for (name, processor) in &self.calldata_processors {
+                match processor
+                    .call1(py, (PyBytes::new(py, &calldata),))
+                    .and_then(|processed| processed.extract::<Vec<u8>>(py))
+                {
+                    Ok(processed) => calldata = processed,
+                    Err(e) => warn!("python calldata processor {} failed: {}", name, e),
+                }
+            }
+        });
+        calldata
+    }
+
+    /// Name and message of the oracles finding a bug
+    pub fn on_tx_end(&self, state: &EVMState, trace: Option<&CallFrame>) -> Vec<(String, String)> {
+        Python::with_gil(|py| {
+            let args = Py::new(py, PyEVMState { state: state.clone() }).and_then(|state| {
+                let trace = match trace {
+                    Some(trace) => frame_to_py(py, trace)?.to_object(py),
+                    None => py.None(),
+                };
+                Ok((state, trace))
+            });
+            let args = match args {
+                Ok(args) => args,
+                Err(e) => {
+                    warn!("failed to convert the execution for the python oracles: {}", e);
+                    return vec![];
+                }
+            };
+            self.oracles
+                .iter()
+                .filter_map(|(name, oracle)| {
+                    match oracle
+                        .call1(py, args.clone())
+                        .and_then(|message| message.extract::<Option<String>>(py))
+                    {
+                        Ok(message) => message.map(|message| (name.clone(), message)),
+                        Err(e) => {
+                            warn!("python oracle {} failed: {}", name, e);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+/// Records the call tree of each transaction for the oracles of the script
+#[derive(Debug)]
+pub struct PythonHooks {
+    script: Rc<PythonScript>,
+    tracer: CallTracer,
+}
+
+impl PythonHooks {
+    pub fn new(script: Rc<PythonScript>) -> Self {
+        Self {
+            script,
+            tracer: CallTracer::default(),
+        }
+    }
+
+    pub fn calldata_processor(&self) -> Option<CalldataProcessor> {
+        self.script.calldata_processor()
+    }
+}
+
+impl<SC> Middleware<SC> for PythonHooks
+where
+    SC: Scheduler<State = EVMFuzzState> + Clone,
+{
+    unsafe fn on_step(&mut self, interp: &mut Interpreter, host: &mut FuzzHost<SC>, state: &mut EVMFuzzState) {
+        if self.script.has_oracles() {
+            Middleware::<SC>::on_step(&mut self.tracer, interp, host, state);
+        }
+    }
+
+    unsafe fn before_execute(
+        &mut self,
+        _interp: Option<&mut Interpreter>,
+        _host: &mut FuzzHost<SC>,
+        _state: &mut EVMFuzzState,
+        is_step: bool,
+        _data: &mut Bytes,
+        _evm_state: &mut EVMState,
+    ) {
+        // a step resumes the transaction of the previous execution
+        if is_step {
+            return;
+        }
+        // drop the calls of the executions not checked by the oracles
+        self.tracer.finish_tx(false, &[]);
+    }
+
+    fn get_type(&self) -> MiddlewareType {
+        MiddlewareType::Python
+    }
+
+    fn as_any(&self) -> &dyn any::Any {
+        self
+    }
+}
+
+pub struct PythonOracle {
+    hooks: Rc<RefCell<PythonHooks>>,
+}
+
+impl PythonOracle {
+    pub fn new(hooks: Rc<RefCell<PythonHooks>>) -> Self {
+        Self { hooks }
+    }
+}
+
+impl
+    Oracle<
+        EVMState,
+        EVMAddress,
+        Bytecode,
+        Bytes,
+        EVMAddress,
+        EVMU256,
+        Vec<u8>,
+        EVMInput,
+        EVMFuzzState,
+        ConciseEVMInput,
+        EVMQueueExecutor,
+    > for PythonOracle
+{
+    fn transition(&self, _ctx: &mut EVMOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(
+        &self,
+        ctx: &mut OracleCtx<
+            EVMState,
+            EVMAddress,
+            Bytecode,
+            Bytes,
+            EVMAddress,
+            EVMU256,
+            Vec<u8>,
+            EVMInput,
+            EVMFuzzState,
+            ConciseEVMInput,
+            EVMQueueExecutor,
+        >,
+        _stage: u64,
+    ) -> Vec<u64> {
+        let bugs = {
+            let result = ctx.fuzz_state.get_execution_result();
+            let mut hooks = self.hooks.borrow_mut();
+            let trace = hooks.tracer.finish_tx(result.reverted, &result.output);
+            hooks.script.on_tx_end(&ctx.post_state, trace.as_ref())
+        };
+
+        let mut res = vec![];
+        for (name, message) in bugs {
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            let bug_idx = (hasher.finish() << 8) + PYTHON_BUG_IDX;
+            if res.contains(&bug_idx) || oracle_should_skip!(ctx, bug_idx) {
+                continue;
+            }
+            EVMBugResult::new(
+                format!("Python ({})", name),
+                bug_idx,
+                format!("{}\n", message),
+                ConciseEVMInput::from_input(ctx.input, ctx.fuzz_state.get_execution_result()),
+                None,
+                None,
+            )
+            .push_to_output();
+            res.push(bug_idx);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_script() {
+        let script = PythonScript::from_code(
+            r#"
+@register_oracle
+def drained(state, trace):
+    vault = "0x1111111111111111111111111111111111111111"
+    if state["balances"].get(vault) == 0 and state.balance(vault) == 0 and state.sload(vault, 1) is None:
+        return "drained by " + trace["caller"]
+
+@register_calldata_processor
+def append(calldata):
+    return calldata + b"\x01"
+"#,
+        )
+        .unwrap();
+        assert_eq!(script.process_calldata(&[0xaa]), vec![0xaa, 0x01]);
+
+        let mut state = EVMState::default();
+        let trace = CallFrame {
+            kind: crate::evm::middlewares::trace::CallKind::Call,
+            caller: EVMAddress::from_slice(&[0x22; 20]),
+            target: Some(EVMAddress::from_slice(&[0x11; 20])),
+            value: EVMU256::ZERO,
+            input: vec![],
+            output: vec![],
+            success: Some(true),
+            items: vec![],
+        };
+        assert!(script.on_tx_end(&state, Some(&trace)).is_empty());
+        state.set_balance(EVMAddress::from_slice(&[0x11; 20]), EVMU256::ZERO);
+        assert_eq!(
+            script.on_tx_end(&state, Some(&trace)),
+            vec![(
+                "drained".to_string(),
+                "drained by 0x2222222222222222222222222222222222222222".to_string()
+            )]
+        );
+        assert!(PythonScript::from_code("def broken(:").is_err());
+    }
+}
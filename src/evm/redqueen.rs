@@ -0,0 +1,177 @@
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use libafl::{
+    corpus::Corpus,
+    events::ProgressReporter,
+    prelude::{CorpusId, ObserversTuple, Stage},
+    state::{HasCorpus, UsesState},
+    Error,
+    Evaluator,
+};
+use tracing::debug;
+
+use crate::{
+    evm::{
+        input::EVMInput,
+        middlewares::{cmp_log::CmpLog, middleware::MiddlewareType},
+        types::{EVMFuzzExecutor, EVMFuzzState, EVMQueueExecutor, EVMU256},
+    },
+    generic_vm::{vm_executor::GenericVM, vm_state::VMStateT},
+    input::VMInputT,
+    r#const::REDQUEEN_MAX_CANDIDATES,
+};
+
+/// Input-to-state replacements (Redqueen): calldata words equal to an operand
+/// of a comparison executed are replaced by the other operand, and by its
+/// neighbors for `<` and `>` checks. This passes the checks against magic
+/// values and hashes that random mutations hardly hit.
+///
+/// `calldata` is the selector followed by the ABI encoded args, so values are
+/// in the 32-byte words after the selector.
+pub fn i2s_candidates(calldata: &[u8], comparisons: &[(EVMU256, EVMU256)]) -> Vec<Vec<u8>> {
+    let mut res: Vec<Vec<u8>> = vec![];
+    for (offset, word) in calldata.get(4..).unwrap_or_default().chunks_exact(32).enumerate() {
+        let word = EVMU256::from_be_slice(word);
+        let offset = 4 + offset * 32;
+        for (a, b) in comparisons {
+            let replacement = if word == *a {
+                *b
+            } else if word == *b {
+                *a
+            } else {
+                continue;
+            };
+            for value in [
+                replacement,
+                replacement.wrapping_add(EVMU256::from(1)),
+                replacement.wrapping_sub(EVMU256::from(1)),
+            ] {
+                let mut candidate = calldata.to_vec();
+                candidate[offset..offset + 32].copy_from_slice(&value.to_be_bytes::<32>());
+                if candidate != calldata && !res.contains(&candidate) {
+                    res.push(candidate);
+                }
+                if res.len() >= REDQUEEN_MAX_CANDIDATES {
+                    return res;
+                }
+            }
+        }
+    }
+    res
+}
+
+/// Runs each new testcase with comparisons logged by [`CmpLog`] and evaluates
+/// its input-to-state replacements (see [`i2s_candidates`])
+pub struct RedqueenStage<OT> {
+    pub enabled: bool,
+    pub last_corpus_idx: usize,
+    pub vm_executor: Rc<RefCell<EVMQueueExecutor>>,
+    pub phantom: std::marker::PhantomData<OT>,
+}
+
+impl<OT> UsesState for RedqueenStage<OT> {
+    type State = EVMFuzzState;
+}
+
+impl<OT> RedqueenStage<OT> {
+    pub fn new(enabled: bool, vm_executor: Rc<RefCell<EVMQueueExecutor>>) -> Self {
+        Self {
+            enabled,
+            last_corpus_idx: 0,
+            vm_executor,
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Comparisons executed by the testcase
+    fn log_comparisons(&self, testcase: &EVMInput, state: &mut EVMFuzzState) -> Vec<(EVMU256, EVMU256)> {
+        let cmp_log = Rc::new(RefCell::new(CmpLog::new()));
+        let mut vm = self.vm_executor.deref().borrow_mut();
+        vm.host.add_middlewares(cmp_log.clone());
+        vm.execute(testcase, state);
+        vm.host.remove_middlewares_by_ty(&MiddlewareType::CmpLog);
+        let comparisons = cmp_log.deref().borrow().comparisons.clone();
+        comparisons
+    }
+}
+
+impl<EM, Z, OT> Stage<EVMFuzzExecutor<OT>, EM, Z> for RedqueenStage<OT>
+where
+    Z: Evaluator<EVMFuzzExecutor<OT>, EM, State = Self::State>,
+    EM: ProgressReporter + UsesState<State = Self::State>,
+    OT: ObserversTuple<Self::State>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut EVMFuzzExecutor<OT>,
+        state: &mut Self::State,
+        manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let count = state.corpus().count();
+        if self.last_corpus_idx >= count {
+            return Ok(());
+        }
+        let idxs = (self.last_corpus_idx..count).collect::<Vec<_>>();
+        self.last_corpus_idx = count;
+
+        for idx in idxs {
+            let testcase = match state.corpus().get(idx.into()) {
+                Ok(testcase) => testcase.borrow().input().clone(),
+                Err(_) => continue,
+            };
+            let testcase = match testcase {
+                Some(testcase) => testcase,
+                None => continue,
+            };
+            let data_abi = match testcase.get_data_abi() {
+                Some(data_abi) if !testcase.is_step() && !testcase.get_state().has_post_execution() => data_abi,
+                _ => continue,
+            };
+
+            let comparisons = self.log_comparisons(&testcase, state);
+            let candidates = i2s_candidates(&data_abi.get_bytes(), &comparisons);
+            debug!(
+                "[redqueen] {} replacements for testcase #{} from {} comparisons",
+                candidates.len(),
+                idx,
+                comparisons.len()
+            );
+            for candidate in candidates {
+                let mut new_data_abi = data_abi.clone();
+                if !new_data_abi.set_bytes(candidate) {
+                    continue;
+                }
+                let mut new_testcase = testcase.clone();
+                new_testcase.data = Some(new_data_abi);
+                fuzzer.evaluate_input(state, executor, manager, new_testcase)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i2s_candidates() {
+        let word = |value: u64| EVMU256::from(value).to_be_bytes::<32>().to_vec();
+        let calldata = [vec![0xaa; 4], word(7), word(9)].concat();
+        let candidates = i2s_candidates(&calldata, &[(EVMU256::from(0x1337), EVMU256::from(9))]);
+        assert_eq!(
+            candidates,
+            vec![
+                [vec![0xaa; 4], word(7), word(0x1337)].concat(),
+                [vec![0xaa; 4], word(7), word(0x1338)].concat(),
+                [vec![0xaa; 4], word(7), word(0x1336)].concat(),
+            ]
+        );
+        assert!(i2s_candidates(&calldata, &[(EVMU256::from(1), EVMU256::from(2))]).is_empty());
+    }
+}
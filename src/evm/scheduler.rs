@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt::Debug, marker::PhantomData};
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, marker::PhantomData, rc::Rc};
 
 /// Corpus schedulers for ItyFuzz
 /// Used to determine which input / VMState to fuzz next
@@ -13,6 +13,7 @@ use libafl::{
 use libafl_bolts::impl_serdeany;
 use revm_primitives::HashSet;
 use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
 
 use super::{
     host::{BRANCH_STATUS, BRANCH_STATUS_IDX},
@@ -24,10 +25,12 @@ use crate::{
         blaz::builder::{ArtifactInfoMetadata, BuildJobResult},
         corpus_initializer::EVMInitializationArtifacts,
         input::EVMInput,
+        sig_to_score::{function_slug, load_sig_to_score, sig_to_score_reload_requested, SigToScore},
+        summary::FunctionStatsMetadata,
     },
     input::VMInputT,
     power_sched::{PowerMutationalStageWithId, TestcaseScoreWithId},
-    r#const::{MAX_POWER, MIN_POWER, POWER_MULTIPLIER},
+    r#const::{MAX_POWER, MIN_POWER, POWER_MULTIPLIER, RARE_SELECTOR_MAX_BOOST},
 };
 
 /// The status of the branch, whether it is covered on true, false or both
@@ -69,6 +72,15 @@ impl BranchCoveredStatus {
             Self::False
         }
     }
+
+    /// Join with the status of the same branch observed elsewhere
+    fn join(&self, other: &Self) -> Self {
+        if self == other {
+            self.clone()
+        } else {
+            Self::Both
+        }
+    }
 }
 
 /// The Metadata for uncovered branches
@@ -99,6 +111,61 @@ impl UncoveredBranchesMetadata {
             branch_status: HashMap::new(),
         }
     }
+
+    /// Status of all branches covered so far, to be shared with other fuzzer
+    /// instances
+    pub fn branch_status(&self) -> Vec<((EVMAddress, usize), BranchCoveredStatus)> {
+        self.branch_status.iter().map(|(k, v)| (*k, v.clone())).collect()
+    }
+
+    /// Forget a testcase removed from the corpus
+    pub fn remove_testcase(&mut self, idx: CorpusId) {
+        self.testcase_to_uncovered_branches.remove(&idx);
+        for testcases in self.branch_to_testcases.values_mut() {
+            testcases.remove(&idx);
+        }
+    }
+
+    /// Branches covered on both sides, which are not worth solving for
+    pub fn fully_covered(&self) -> HashSet<(EVMAddress, usize)> {
+        self.branch_status
+            .iter()
+            .filter(|(_, status)| **status == BranchCoveredStatus::Both)
+            .map(|(branch, _)| *branch)
+            .collect()
+    }
+
+    /// Merge the branch status of another fuzzer instance. Merging is
+    /// commutative and idempotent, so instances can exchange their status in
+    /// any order.
+    pub fn merge_branch_status(&mut self, other: &[((EVMAddress, usize), BranchCoveredStatus)]) {
+        for (branch, status) in other {
+            match self.branch_status.get_mut(branch) {
+                Some(v) => {
+                    let new_v = v.join(status);
+                    if new_v == *v {
+                        continue;
+                    }
+                    // the branch is fully covered now, the local testcases are no
+                    // longer uncovering it
+                    if let Some(testcases) = self.branch_to_testcases.remove(branch) {
+                        for tc_id in testcases {
+                            self.testcase_to_uncovered_branches
+                                .entry(tc_id)
+                                .and_modify(|e| *e = e.saturating_sub(1));
+                        }
+                    }
+                    *v = new_v;
+                }
+                None => {
+                    self.branch_status.insert(*branch, status.clone());
+                    if *status != BranchCoveredStatus::Both {
+                        self.branch_to_testcases.insert(*branch, HashSet::new());
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl_serdeany!(UncoveredBranchesMetadata);
@@ -112,51 +179,112 @@ impl_serdeany!(UncoveredBranchesMetadata);
 pub struct PowerABITestcaseMetadata {
     /// Number of lines in source code, initialized in on_add
     lines: usize,
+    /// Contract and selector of the function called by the testcase
+    function: Option<(EVMAddress, [u8; 4])>,
+    /// Multiplier of the power from the static hints on the function
+    score: f64,
 }
 
 impl PowerABITestcaseMetadata {
     /// Create new [`struct@SchedulerTestcaseMetadata`]
     #[must_use]
-    pub fn new(lines: usize) -> Self {
-        Self { lines }
+    pub fn new(lines: usize, function: Option<(EVMAddress, [u8; 4])>, score: f64) -> Self {
+        Self { lines, function, score }
     }
 }
 
+/// Contract and selector of the function called by the input, None for
+/// inputs without ABI, like borrow
+fn called_function(input: &EVMInput) -> Option<(EVMAddress, [u8; 4])> {
+    input.get_data_abi().map(|abi| (input.get_contract(), abi.function))
+}
+
+/// Multiplier of the power of a testcase calling a function executed `calls`
+/// times, while functions are executed `average_calls` times on average:
+/// functions rarely executed get more power, up to
+/// [`RARE_SELECTOR_MAX_BOOST`]
+fn rare_selector_boost(calls: usize, average_calls: f64) -> f64 {
+    (average_calls / (calls + 1) as f64).clamp(1.0, RARE_SELECTOR_MAX_BOOST)
+}
+
 impl_serdeany!(PowerABITestcaseMetadata);
 
 #[derive(Debug, Clone)]
 pub struct PowerABIScheduler<S> {
+    /// Static hints on the functions to fuzz more, e.g., the ones flagged by
+    /// Slither
+    hints: SigToScore,
+    /// `--sig-to-score` file, whose scores replace the hints
+    sig_to_score_file: Option<String>,
+    /// Scores of the functions, shared by the clones of the scheduler so that
+    /// a reload applies to all of them
+    sig_to_score: Rc<RefCell<SigToScore>>,
     phantom: PhantomData<S>,
 }
 
 impl<S> Default for PowerABIScheduler<S> {
     fn default() -> Self {
-        Self::new()
+        Self::new(SigToScore::default(), None)
     }
 }
 
 impl<S> PowerABIScheduler<S> {
-    pub fn new() -> Self {
-        Self { phantom: PhantomData }
+    pub fn new(hints: SigToScore, sig_to_score_file: Option<String>) -> Self {
+        Self {
+            sig_to_score: Rc::new(RefCell::new(hints.clone())),
+            hints,
+            sig_to_score_file,
+            phantom: PhantomData,
+        }
+    }
+
+    /// (Re)load the scores of the `--sig-to-score` file over the hints
+    pub fn load_sig_to_score(&self) -> Result<(), String> {
+        let Some(file) = &self.sig_to_score_file else {
+            return Ok(());
+        };
+        let mut scores = self.hints.clone();
+        scores.extend(load_sig_to_score(file)?);
+        *self.sig_to_score.borrow_mut() = scores;
+        Ok(())
+    }
+
+    /// Multiplier of the power of the testcases calling the function
+    fn score(&self, function: Option<(EVMAddress, [u8; 4])>) -> f64 {
+        let Some((contract, selector)) = function else {
+            return 1.0;
+        };
+        let slug = unsafe { FUNCTION_SIG.get(&selector) }.map(|signature| function_slug(signature));
+        self.sig_to_score
+            .borrow()
+            .score(contract, selector, slug.as_deref())
+            .unwrap_or(1.0)
+    }
+
+    fn testcase_metadata(&self, lines: usize, function: Option<(EVMAddress, [u8; 4])>) -> PowerABITestcaseMetadata {
+        PowerABITestcaseMetadata::new(lines, function, self.score(function))
     }
 
     fn add_abi_metadata(&mut self, testcase: &mut Testcase<EVMInput>, artifact: &BuildJobResult) -> Result<(), Error> {
         let input = testcase.input().clone().unwrap();
+        let function = called_function(&input);
         let tc_func = match input.get_data_abi() {
             Some(abi) => abi.function,
             None => {
-                testcase.add_metadata(PowerABITestcaseMetadata::new(1));
+                testcase.add_metadata(self.testcase_metadata(1, function));
                 return Ok(()); // Some EVMInput don't have abi, like borrow
             }
         };
-        let tc_func_name = unsafe {
-            FUNCTION_SIG.get(&tc_func).unwrap_or_else(|| {
-                panic!(
-                    "function signature {} @ {:?} not found in FUNCTION_SIG",
-                    hex::encode(tc_func),
-                    input.get_contract()
-                )
-            })
+        // the signatures of the functions decompiled without a match on
+        // 4byte.directory are unknown
+        let Some(tc_func_name) = (unsafe { FUNCTION_SIG.get(&tc_func) }) else {
+            debug!(
+                "function signature {} @ {:?} not found in FUNCTION_SIG",
+                hex::encode(tc_func),
+                input.get_contract()
+            );
+            testcase.add_metadata(self.testcase_metadata(1, function));
+            return Ok(());
         };
         let tc_func_slug = {
             let amount_args = tc_func_name.matches(',').count() + {
@@ -187,18 +315,45 @@ impl<S> PowerABIScheduler<S> {
                             break; // not true function implementation, break to
                                    // find in next contract
                         }
-                        testcase.add_metadata(PowerABITestcaseMetadata::new(num_lines));
+                        testcase.add_metadata(self.testcase_metadata(num_lines, function));
                         return Ok(());
                     }
                 }
             }
         }
         // NOTE: testcase function is [0,0,0,0] !fallback!
-        testcase.add_metadata(PowerABITestcaseMetadata::new(1));
+        testcase.add_metadata(self.testcase_metadata(1, function));
         Ok(())
     }
 }
 
+impl<S> PowerABIScheduler<S>
+where
+    S: State + HasCorpus<Input = EVMInput> + HasTestcase + HasMetadata,
+{
+    /// Rescore the corpus if the reload of the sig_to_score file was requested
+    fn poll_sig_to_score(&mut self, state: &mut S) {
+        if !sig_to_score_reload_requested() {
+            return;
+        }
+        match self.load_sig_to_score() {
+            Ok(()) => {
+                info!("Reloaded the sig_to_score file");
+                let mut id = state.corpus().first();
+                while let Some(tc_id) = id {
+                    if let Ok(mut testcase) = state.testcase_mut(tc_id) {
+                        if let Ok(meta) = testcase.metadata_mut::<PowerABITestcaseMetadata>() {
+                            meta.score = self.score(meta.function);
+                        }
+                    }
+                    id = state.corpus().next(tc_id);
+                }
+            }
+            Err(e) => warn!("Failed to reload the sig_to_score file: {}", e),
+        }
+    }
+}
+
 impl<S> UsesState for PowerABIScheduler<S>
 where
     S: State + UsesInput,
@@ -211,6 +366,8 @@ where
     S: State + HasCorpus<Input = EVMInput> + HasTestcase + HasMetadata,
 {
     fn on_add(&mut self, state: &mut Self::State, idx: CorpusId) -> Result<(), Error> {
+        self.poll_sig_to_score(state);
+
         // adding power scheduling information based on code size
         {
             let mut testcase = state.testcase_mut(idx).unwrap();
@@ -223,7 +380,7 @@ where
             let artifact = match meta.get(&input.contract) {
                 Some(artifact) => artifact,
                 None => {
-                    testcase.add_metadata(PowerABITestcaseMetadata::new(1));
+                    testcase.add_metadata(self.testcase_metadata(1, called_function(&input)));
                     return Ok(());
                 } // some contracts are not in ArtifactInfo, like borrow
             };
@@ -297,6 +454,8 @@ where
     }
 
     fn next(&mut self, state: &mut Self::State) -> Result<CorpusId, Error> {
+        // the corpus may not grow for long once saturated
+        self.poll_sig_to_score(state);
         if state.corpus().count() == 0 {
             Err(Error::empty("No entries in corpus".to_owned()))
         } else {
@@ -318,10 +477,17 @@ where
 {
     fn on_remove(
         &mut self,
-        _state: &mut Self::State,
-        _idx: CorpusId,
+        state: &mut Self::State,
+        idx: CorpusId,
         _testcase: &Option<Testcase<<Self::State as UsesInput>::Input>>,
     ) -> Result<(), Error> {
+        // the next testcase is scheduled from the start of the corpus
+        if *state.corpus().current() == Some(idx) {
+            *state.corpus_mut().current_mut() = None;
+        }
+        if let Some(meta) = state.metadata_map_mut().get_mut::<UncoveredBranchesMetadata>() {
+            meta.remove_testcase(idx);
+        }
         Ok(())
     }
 
@@ -364,7 +530,7 @@ where
         let artifact = match artifacts.build_artifacts.get(&input.contract) {
             Some(artifact) => artifact,
             None => {
-                testcase.add_metadata(PowerABITestcaseMetadata::new(1));
+                testcase.add_metadata(self.testcase_metadata(1, called_function(&input)));
                 return Ok(());
             } // build_artifacts may not contain contracts whose source code is not available
         };
@@ -385,9 +551,9 @@ where
     S: HasCorpus + HasMetadata,
 {
     fn compute(state: &S, entry: &mut Testcase<S::Input>, idx: CorpusId) -> Result<f64, Error> {
-        let _num_lines = match entry.metadata::<PowerABITestcaseMetadata>() {
-            Ok(meta) => meta.lines,
-            Err(_e) => 1, // FIXME: should not happen
+        let (_num_lines, function, score) = match entry.metadata::<PowerABITestcaseMetadata>() {
+            Ok(meta) => (meta.lines, meta.function, meta.score),
+            Err(_e) => (1, None, 1.0), // FIXME: should not happen
         };
         // TODO: more sophisticated power score
         let uncov_branch = {
@@ -395,7 +561,16 @@ where
             meta.testcase_to_uncovered_branches.get(&idx).unwrap_or(&0).to_owned() + 1
         };
 
-        let mut power = uncov_branch as f64 * POWER_MULTIPLIER;
+        // testcases calling rarely executed functions are more likely to reach
+        // new states
+        let boost = match (function, state.metadata_map().get::<FunctionStatsMetadata>()) {
+            (Some((contract, selector)), Some(stats)) => {
+                rare_selector_boost(stats.calls(contract, selector), stats.average_calls())
+            }
+            _ => 1.0,
+        };
+
+        let mut power = uncov_branch as f64 * POWER_MULTIPLIER * boost * score;
         // we score based on how a test case uncovered branches. 100 is cap, 1 is always
         // min
         if power >= MAX_POWER {
@@ -413,3 +588,116 @@ where
 /// The standard powerscheduling stage
 pub type PowerABIMutationalStage<E, EM, I, M, Z> =
     PowerMutationalStageWithId<E, CorpusPowerABITestcaseScore<<E as UsesState>::State>, EM, I, M, Z>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_branch_status() {
+        let addr = EVMAddress::zero();
+        let mut a = UncoveredBranchesMetadata::new();
+        a.branch_status.insert((addr, 1), BranchCoveredStatus::True);
+        a.branch_to_testcases
+            .insert((addr, 1), HashSet::from_iter([CorpusId::from(0usize)]));
+        a.testcase_to_uncovered_branches.insert(CorpusId::from(0usize), 1);
+        let mut b = UncoveredBranchesMetadata::new();
+        b.branch_status.insert((addr, 1), BranchCoveredStatus::False);
+        b.branch_status.insert((addr, 2), BranchCoveredStatus::True);
+
+        let (a_status, b_status) = (a.branch_status(), b.branch_status());
+        a.merge_branch_status(&b_status);
+        b.merge_branch_status(&a_status);
+        a.merge_branch_status(&b_status);
+        assert_eq!(a.branch_status, b.branch_status);
+        assert_eq!(a.branch_status[&(addr, 1)], BranchCoveredStatus::Both);
+        assert_eq!(a.testcase_to_uncovered_branches[&CorpusId::from(0usize)], 0);
+        assert!(!a.branch_to_testcases.contains_key(&(addr, 1)));
+        assert!(a.branch_to_testcases.contains_key(&(addr, 2)));
+        assert_eq!(a.fully_covered(), HashSet::from_iter([(addr, 1)]));
+    }
+
+    #[test]
+    fn test_sig_to_score_reload() {
+        use crate::{
+            evm::{
+                input::ConciseEVMInput,
+                sig_to_score::SIG_TO_SCORE_RELOAD_REQUESTED,
+                types::{EVMFuzzState, EVMStagedVMState},
+            },
+            state::FuzzState,
+        };
+
+        let file = std::env::temp_dir().join(format!("ityfuzz_sig_to_score_{}", std::process::id()));
+        std::fs::write(&file, "*,0x2e1a7d4d,3\n").unwrap();
+        let mut scheduler =
+            PowerABIScheduler::<EVMFuzzState>::new(SigToScore::default(), Some(file.to_string_lossy().to_string()));
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let (input, _) = ConciseEVMInput::default().to_input(EVMStagedVMState::default());
+        let mut testcase = Testcase::new(input);
+        let function = Some((EVMAddress::zero(), [0x2e, 0x1a, 0x7d, 0x4d]));
+        testcase.add_metadata(PowerABITestcaseMetadata::new(1, function, 1.0));
+        let idx = state.corpus_mut().add(testcase).unwrap();
+
+        // the reload is applied when scheduling, without new testcases
+        SIG_TO_SCORE_RELOAD_REQUESTED.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(scheduler.next(&mut state).unwrap(), idx);
+        let score = state
+            .testcase(idx)
+            .unwrap()
+            .metadata::<PowerABITestcaseMetadata>()
+            .unwrap()
+            .score;
+        assert_eq!(score, 3.0);
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_remove_testcase() {
+        use crate::{
+            evm::{
+                input::ConciseEVMInput,
+                types::{EVMFuzzState, EVMStagedVMState},
+            },
+            state::FuzzState,
+        };
+
+        let mut scheduler = PowerABIScheduler::<EVMFuzzState>::default();
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let ids = (0..2)
+            .map(|_| {
+                let (input, _) = ConciseEVMInput::default().to_input(EVMStagedVMState::default());
+                let mut testcase = Testcase::new(input);
+                testcase.add_metadata(PowerABITestcaseMetadata::new(1, None, 1.0));
+                state.corpus_mut().add(testcase).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let addr = EVMAddress::zero();
+        let mut meta = UncoveredBranchesMetadata::new();
+        meta.branch_status.insert((addr, 1), BranchCoveredStatus::True);
+        meta.branch_to_testcases
+            .insert((addr, 1), HashSet::from_iter(ids.clone()));
+        meta.testcase_to_uncovered_branches.insert(ids[0], 1);
+        meta.testcase_to_uncovered_branches.insert(ids[1], 1);
+        state.metadata_map_mut().insert(meta);
+
+        // remove the testcase being scheduled
+        assert_eq!(scheduler.next(&mut state).unwrap(), ids[0]);
+        let testcase = state.corpus_mut().remove(ids[0]).unwrap();
+        scheduler.on_remove(&mut state, ids[0], &Some(testcase)).unwrap();
+
+        assert_eq!(scheduler.next(&mut state).unwrap(), ids[1]);
+        assert_eq!(scheduler.next(&mut state).unwrap(), ids[1]);
+        let meta = state.metadata_map().get::<UncoveredBranchesMetadata>().unwrap();
+        assert!(!meta.testcase_to_uncovered_branches.contains_key(&ids[0]));
+        assert_eq!(meta.branch_to_testcases[&(addr, 1)], HashSet::from_iter([ids[1]]));
+    }
+
+    #[test]
+    fn test_rare_selector_boost() {
+        assert_eq!(rare_selector_boost(0, 0.0), 1.0);
+        assert_eq!(rare_selector_boost(100, 50.0), 1.0);
+        assert_eq!(rare_selector_boost(9, 20.0), 2.0);
+        assert_eq!(rare_selector_boost(0, 1000.0), RARE_SELECTOR_MAX_BOOST);
+    }
+}
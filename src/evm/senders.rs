@@ -0,0 +1,176 @@
+//! Senders of the transactions and ranges of the ETH sent along, given with
+//! `--senders` and `--call-values`
+//!
+//! A sender is `<address>[:<balance>][:<role>]`, e.g.,
+//! `0x…:100ether:owner`. All senders send the fuzzed transactions, the owner
+//! also deploys the contracts. A call value range is
+//! `[<function>=]<min>..<max>`, e.g., `deposit=1ether..10ether`, the range
+//! without function applying to the other functions.
+
+use std::str::FromStr;
+
+use crate::evm::types::{EVMAddress, EVMU256};
+
+/// Ranges of the value of the calls, see [`clamp_call_value`]
+pub static mut CALL_VALUE_RANGES: Vec<CallValueRange> = Vec::new();
+
+/// Parse an amount of wei, or of ether with the `ether` suffix, e.g.,
+/// `1.5ether`
+pub fn parse_amount(s: &str) -> Result<EVMU256, String> {
+    let invalid = |_| format!("invalid amount {}", s);
+    let Some(ether) = s.strip_suffix("ether") else {
+        return EVMU256::from_str_radix(s, 10).map_err(invalid);
+    };
+    let (int, frac) = ether.split_once('.').unwrap_or((ether, ""));
+    if frac.len() > 18 {
+        return Err(format!("invalid amount {}", s));
+    }
+    let int = if int.is_empty() { "0" } else { int };
+    let wei = format!("{}{:0<18}", int, frac);
+    EVMU256::from_str_radix(&wei, 10).map_err(invalid)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SenderRole {
+    #[default]
+    Attacker,
+    Owner,
+}
+
+impl FromStr for SenderRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "attacker" => Ok(Self::Attacker),
+            "owner" => Ok(Self::Owner),
+            _ => Err(format!("unknown sender role {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sender {
+    pub address: EVMAddress,
+    /// Initial balance, the default one if `None`
+    pub balance: Option<EVMU256>,
+    pub role: SenderRole,
+}
+
+impl FromStr for Sender {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let address = parts.next().unwrap_or_default();
+        let address = EVMAddress::from_str(address.trim_start_matches("0x"))
+            .map_err(|e| format!("invalid sender address {}: {}", address, e))?;
+        let mut sender = Self {
+            address,
+            balance: None,
+            role: SenderRole::default(),
+        };
+        for part in parts {
+            match part.parse::<SenderRole>() {
+                Ok(role) => sender.role = role,
+                Err(_) => sender.balance = Some(parse_amount(part)?),
+            }
+        }
+        Ok(sender)
+    }
+}
+
+/// Parse the comma separated senders
+pub fn parse_senders(s: &str) -> Result<Vec<Sender>, String> {
+    s.split(',')
+        .map(|sender| sender.trim())
+        .filter(|sender| !sender.is_empty())
+        .map(Sender::from_str)
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallValueRange {
+    /// Name of the function the range applies to, any if `None`
+    pub function: Option<String>,
+    pub min: EVMU256,
+    pub max: EVMU256,
+}
+
+impl FromStr for CallValueRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (function, range) = match s.split_once('=') {
+            Some((function, range)) => (Some(function.to_string()), range),
+            None => (None, s),
+        };
+        let (min, max) = range
+            .split_once("..")
+            .ok_or(format!("invalid call value range {}", s))?;
+        let (min, max) = (parse_amount(min)?, parse_amount(max)?);
+        if min > max {
+            return Err(format!("invalid call value range {}", s));
+        }
+        Ok(Self { function, min, max })
+    }
+}
+
+/// Parse the comma separated call value ranges
+pub fn parse_call_value_ranges(s: &str) -> Result<Vec<CallValueRange>, String> {
+    s.split(',')
+        .map(|range| range.trim())
+        .filter(|range| !range.is_empty())
+        .map(CallValueRange::from_str)
+        .collect()
+}
+
+/// Map the value of a call to `function` into its range, unchanged if there
+/// is none
+pub fn clamp_call_value(function: &str, value: EVMU256) -> EVMU256 {
+    let ranges = unsafe { &CALL_VALUE_RANGES };
+    let range = ranges
+        .iter()
+        .find(|range| range.function.as_deref() == Some(function))
+        .or_else(|| ranges.iter().find(|range| range.function.is_none()));
+    match range {
+        Some(range) => match (range.max - range.min).checked_add(EVMU256::from(1)) {
+            Some(len) => range.min + value % len,
+            None => value,
+        },
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_senders() {
+        let senders = parse_senders(
+            "0x8EF508Aca04B32Ff3ba5003177cb18BfA6Cd79dd:1.5ether:owner, 35c9dfd76bf02107ff4f7128Bd69716612d31dDb",
+        )
+        .unwrap();
+        assert_eq!(senders.len(), 2);
+        assert_eq!(senders[0].balance, Some(EVMU256::from(1_500_000_000_000_000_000u128)));
+        assert_eq!(senders[0].role, SenderRole::Owner);
+        assert_eq!(senders[1].balance, None);
+        assert_eq!(senders[1].role, SenderRole::Attacker);
+        assert!(parse_senders("0x8EF508Aca04B32Ff3ba5003177cb18BfA6Cd79dd:manager").is_err());
+    }
+
+    #[test]
+    fn test_call_value_ranges() {
+        let ranges = parse_call_value_ranges("0..100, deposit=1ether..2ether").unwrap();
+        assert_eq!(ranges[0].max, EVMU256::from(100));
+        assert_eq!(ranges[1].function.as_deref(), Some("deposit"));
+        assert!(parse_call_value_ranges("2..1").is_err());
+
+        unsafe { CALL_VALUE_RANGES = ranges };
+        assert_eq!(clamp_call_value("withdraw", EVMU256::from(205)), EVMU256::from(3));
+        let value = clamp_call_value("deposit", EVMU256::from(12345));
+        assert!(value >= EVMU256::from(10u128.pow(18)) && value <= EVMU256::from(2 * 10u128.pow(18)));
+        unsafe { CALL_VALUE_RANGES = vec![] };
+    }
+}
@@ -0,0 +1,197 @@
+//! Hand-written scores of the functions to fuzz more, given with
+//! `--sig-to-score` as a CSV file of `address,function,score` lines, e.g.,
+//!
+//! ```text
+//! # address,function,score
+//! 0x…,withdraw@1,4
+//! *,0x2e1a7d4d,2.5
+//! ```
+//!
+//! The address is `*` to match any contract, and the function is either a
+//! `name@argcount` slug or a 4-byte selector. The file is reloaded on SIGHUP.
+
+use std::{
+    collections::HashMap,
+    fs,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::{
+    libc::c_int,
+    sys::signal::{signal, SigHandler, Signal},
+};
+use tracing::warn;
+
+use crate::evm::{oracles::assertion::split_signatures, types::EVMAddress};
+
+/// Set on SIGHUP to reload the `--sig-to-score` file
+pub static SIG_TO_SCORE_RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_sig_to_score_reload(_: c_int) {
+    SIG_TO_SCORE_RELOAD_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Reload the `--sig-to-score` file when receiving SIGHUP
+pub fn handle_sig_to_score_reload_signal() {
+    if let Err(e) = unsafe { signal(Signal::SIGHUP, SigHandler::Handler(request_sig_to_score_reload)) } {
+        warn!("Failed to handle SIGHUP: {}", e);
+    }
+}
+
+/// Whether a reload was requested since the last call
+pub fn sig_to_score_reload_requested() -> bool {
+    SIG_TO_SCORE_RELOAD_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// `name@argcount` slug of a function signature, e.g., `withdraw@1` for
+/// `withdraw(uint256)`
+pub fn function_slug(signature: &str) -> String {
+    let (name, args) = signature.split_once('(').unwrap_or((signature, ""));
+    let args = args.strip_suffix(')').unwrap_or(args);
+    let argcount = if args.trim().is_empty() {
+        0
+    } else {
+        split_signatures(args).len()
+    };
+    format!("{}@{}", name, argcount)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FunctionKey {
+    Selector([u8; 4]),
+    /// `name@argcount`
+    Slug(String),
+}
+
+impl FromStr for FunctionKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(selector) = s.strip_prefix("0x") {
+            let selector = hex::decode(selector).map_err(|e| format!("invalid selector {}: {}", s, e))?;
+            return selector
+                .try_into()
+                .map(Self::Selector)
+                .map_err(|_| format!("invalid selector {}, expected 4 bytes", s));
+        }
+        match s.split_once('@') {
+            Some((name, argcount)) if !name.is_empty() && argcount.parse::<usize>().is_ok() => {
+                Ok(Self::Slug(s.to_string()))
+            }
+            _ => Err(format!("invalid function {}, expected name@argcount or a selector", s)),
+        }
+    }
+}
+
+/// Scores of the functions, a contract of None matching any contract
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SigToScore {
+    scores: HashMap<(Option<EVMAddress>, FunctionKey), f64>,
+}
+
+impl SigToScore {
+    /// Scores of the selectors of any contract
+    pub fn from_selectors(scores: HashMap<[u8; 4], f64>) -> Self {
+        Self {
+            scores: scores
+                .into_iter()
+                .map(|(selector, score)| ((None, FunctionKey::Selector(selector)), score))
+                .collect(),
+        }
+    }
+
+    /// Add the scores of `other`, replacing the scores of the same functions
+    pub fn extend(&mut self, other: SigToScore) {
+        self.scores.extend(other.scores);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Score of the function of the contract, the scores given for the
+    /// contract taking precedence over the ones given for any contract, and
+    /// the selectors over the slugs
+    pub fn score(&self, contract: EVMAddress, selector: [u8; 4], slug: Option<&str>) -> Option<f64> {
+        let keys = [
+            Some(FunctionKey::Selector(selector)),
+            slug.map(|s| FunctionKey::Slug(s.to_string())),
+        ];
+        [Some(contract), None].into_iter().find_map(|address| {
+            keys.iter()
+                .flatten()
+                .find_map(|key| self.scores.get(&(address, key.clone())).cloned())
+        })
+    }
+}
+
+/// Parse the `address,function,score` lines, ignoring empty lines, comments
+/// (`#`) and the header
+pub fn parse_sig_to_score(s: &str) -> Result<SigToScore, String> {
+    let mut res = SigToScore::default();
+    for (idx, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("address,") {
+            continue;
+        }
+        let err = |msg: String| format!("line {}: {}", idx + 1, msg);
+        let fields = line.split(',').map(|field| field.trim()).collect::<Vec<_>>();
+        let [address, function, score] = fields[..] else {
+            return Err(err(format!("expected address,function,score, got {:?}", line)));
+        };
+        let address = match address {
+            "*" => None,
+            _ => Some(
+                EVMAddress::from_str(address.trim_start_matches("0x"))
+                    .map_err(|e| err(format!("invalid address {}: {}", address, e)))?,
+            ),
+        };
+        let function = function.parse::<FunctionKey>().map_err(err)?;
+        let score = score
+            .parse::<f64>()
+            .ok()
+            .filter(|score| score.is_finite() && *score > 0.0)
+            .ok_or_else(|| err(format!("invalid score {}, expected a positive number", score)))?;
+        res.scores.insert((address, function), score);
+    }
+    Ok(res)
+}
+
+pub fn load_sig_to_score(path: &str) -> Result<SigToScore, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    parse_sig_to_score(&content).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sig_to_score() {
+        let contract = EVMAddress::from_slice(&[0x11; 20]);
+        let scores = parse_sig_to_score(
+            "address,function,score\n\
+             # withdraw(uint256)\n\
+             *,0x2e1a7d4d,2\n\
+             0x1111111111111111111111111111111111111111,withdraw@1,4\n\
+             *,swap@4,1.5\n",
+        )
+        .unwrap();
+        let withdraw = [0x2e, 0x1a, 0x7d, 0x4d];
+        assert_eq!(scores.score(contract, withdraw, Some("withdraw@1")), Some(4.0));
+        assert_eq!(scores.score(EVMAddress::zero(), withdraw, None), Some(2.0));
+        assert_eq!(scores.score(contract, [0; 4], Some("swap@4")), Some(1.5));
+        assert_eq!(scores.score(contract, [0; 4], Some("swap@3")), None);
+
+        assert_eq!(function_slug("swap(uint256,uint256,address,bytes)"), "swap@4");
+        assert_eq!(function_slug("f((uint256,address),uint8)"), "f@2");
+        assert_eq!(function_slug("deposit()"), "deposit@0");
+
+        assert!(parse_sig_to_score("*,withdraw,1").unwrap_err().starts_with("line 1:"));
+        assert!(parse_sig_to_score("\n*,0x2e1a7d,1").unwrap_err().starts_with("line 2:"));
+        assert!(parse_sig_to_score("0x11,withdraw@1,1").is_err());
+        assert!(parse_sig_to_score("*,withdraw@1,-1").is_err());
+        assert!(parse_sig_to_score("*,withdraw@1").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Static hints from Slither: the functions flagged by its detectors (e.g.,
+//! `arbitrary-send-eth`, `unchecked-transfer`) get a score, given to
+//! [`crate::evm::scheduler::PowerABIScheduler`], so that the testcases
+//! calling them are fuzzed more
+//!
+//! The hints are either read from the JSON output of Slither (`slither
+//! <target> --json <file>`) or obtained by running Slither on the target.
+
+use std::{collections::HashMap, fs, process::Command};
+
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{evm::onchain::keccak256, r#const::SLITHER_MAX_BOOST};
+
+/// Multiplier of the score of a function flagged by a detector of the
+/// impact, None for the detectors not pointing to bugs
+fn impact_multiplier(impact: &str) -> Option<f64> {
+    match impact {
+        "High" => Some(2.0),
+        "Medium" => Some(1.5),
+        "Low" => Some(1.2),
+        _ => None,
+    }
+}
+
+/// Signatures of the functions in the elements of a detector hit, i.e., the
+/// functions flagged and the functions of the flagged statements
+fn flagged_signatures(elements: &[Value]) -> Vec<String> {
+    let function_signature = |element: &Value| {
+        (element["type"] == "function")
+            .then(|| element["type_specific_fields"]["signature"].as_str())
+            .flatten()
+            .map(String::from)
+    };
+    elements
+        .iter()
+        .filter_map(|element| {
+            function_signature(element).or_else(|| function_signature(&element["type_specific_fields"]["parent"]))
+        })
+        .collect()
+}
+
+/// Scores of the selectors of the functions flagged in the JSON output of
+/// Slither, at least 1 and at most [`SLITHER_MAX_BOOST`]
+pub fn parse_slither_json(json: &Value) -> HashMap<[u8; 4], f64> {
+    let mut res: HashMap<[u8; 4], f64> = HashMap::new();
+    let detectors = json["results"]["detectors"].as_array().cloned().unwrap_or_default();
+    for hit in detectors {
+        let Some(multiplier) = hit["impact"].as_str().and_then(impact_multiplier) else {
+            continue;
+        };
+        let elements = hit["elements"].as_array().cloned().unwrap_or_default();
+        let mut signatures = flagged_signatures(&elements);
+        signatures.sort();
+        signatures.dedup();
+        for signature in signatures {
+            let hash = keccak256(signature.as_bytes()).to_be_bytes::<32>();
+            let score = res.entry([hash[0], hash[1], hash[2], hash[3]]).or_insert(1.0);
+            *score = (*score * multiplier).min(SLITHER_MAX_BOOST);
+        }
+    }
+    res
+}
+
+/// Scores of the functions flagged by Slither, `target` being either its
+/// JSON output or what to run it on (e.g., `.` for a Foundry or Hardhat
+/// project)
+pub fn slither_hints(target: &str) -> Result<HashMap<[u8; 4], f64>, String> {
+    let output = if target.ends_with(".json") {
+        fs::read_to_string(target).map_err(|e| format!("failed to read {}: {}", target, e))?
+    } else {
+        info!("Running slither on {}", target);
+        // slither exits with an error when the detectors find something
+        let output = Command::new("slither")
+            .args([target, "--json", "-"])
+            .output()
+            .map_err(|e| format!("failed to run slither: {}", e))?;
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+    let json: Value = serde_json::from_str(&output).map_err(|e| format!("invalid slither output: {}", e))?;
+    if json["success"] == false {
+        warn!("slither failed: {}", json["error"]);
+    }
+    let hints = parse_slither_json(&json);
+    info!("Slither flagged {} functions", hints.len());
+    Ok(hints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slither_json() {
+        let json = serde_json::json!({
+            "success": true,
+            "results": {"detectors": [
+                {
+                    "check": "arbitrary-send-eth",
+                    "impact": "High",
+                    "elements": [
+                        {"type": "function", "name": "withdraw",
+                         "type_specific_fields": {"signature": "withdraw(uint256)"}},
+                        {"type": "node", "name": "to.transfer(amount)",
+                         "type_specific_fields": {"parent": {"type": "function", "name": "withdraw",
+                            "type_specific_fields": {"signature": "withdraw(uint256)"}}}}
+                    ]
+                },
+                {
+                    "check": "unchecked-transfer",
+                    "impact": "High",
+                    "elements": [{"type": "function", "name": "withdraw",
+                        "type_specific_fields": {"signature": "withdraw(uint256)"}}]
+                },
+                {
+                    "check": "naming-convention",
+                    "impact": "Informational",
+                    "elements": [{"type": "function", "name": "foo",
+                        "type_specific_fields": {"signature": "foo()"}}]
+                }
+            ]}
+        });
+        let hints = parse_slither_json(&json);
+        // withdraw(uint256)
+        assert_eq!(hints, HashMap::from([([0x2e, 0x1a, 0x7d, 0x4d], SLITHER_MAX_BOOST)]));
+    }
+}
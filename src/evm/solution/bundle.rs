@@ -0,0 +1,229 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use super::SellType;
+use crate::{
+    evm::types::{EVMAddress, EVMU256},
+    input::SolutionTx,
+};
+
+/// Init code of the batch executor, deploying [`BATCH_EXECUTOR_RUNTIME`]
+const BATCH_EXECUTOR_INIT: [u8; 11] = [
+    0x60, 0x40, // PUSH1 runtime size
+    0x80, // DUP1
+    0x60, 0x0b, // PUSH1 runtime offset
+    0x60, 0x00, // PUSH1 0
+    0x39, // CODECOPY
+    0x60, 0x00, // PUSH1 0
+    0xf3, // RETURN
+];
+
+/// Runtime code of the batch executor, which executes the calls packed in
+/// its calldata as `to (20 bytes) | value (32 bytes) | size (32 bytes) |
+/// data`, and reverts with the revert data of the first failing call
+const BATCH_EXECUTOR_RUNTIME: [u8; 0x40] = [
+    0x60, 0x00, // PUSH1 0 (offset of the next call)
+    0x5b, // JUMPDEST loop
+    0x36, 0x81, 0x10, // CALLDATASIZE DUP2 LT
+    0x60, 0x0a, 0x57, // PUSH1 body JUMPI
+    0x00, // STOP
+    0x5b, // JUMPDEST body
+    0x80, 0x60, 0x34, 0x01, 0x35, // size = CALLDATALOAD(offset + 52)
+    0x80, 0x82, 0x60, 0x54, 0x01, 0x60, 0x00, 0x37, // CALLDATACOPY(0, offset + 84, size)
+    0x60, 0x00, 0x60, 0x00, 0x82, 0x60, 0x00, // retSize retOffset argsSize argsOffset
+    0x85, 0x60, 0x14, 0x01, 0x35, // value = CALLDATALOAD(offset + 20)
+    0x86, 0x35, 0x60, 0x60, 0x1c, // to = CALLDATALOAD(offset) >> 96
+    0x5a, 0xf1, // GAS CALL
+    0x60, 0x38, 0x57, // PUSH1 next JUMPI
+    0x3d, 0x60, 0x00, 0x60, 0x00, 0x3e, // RETURNDATACOPY(0, 0, RETURNDATASIZE)
+    0x3d, 0x60, 0x00, 0xfd, // REVERT(0, RETURNDATASIZE)
+    0x5b, // JUMPDEST next
+    0x60, 0x54, 0x01, 0x01, // offset += 84 + size
+    0x60, 0x02, 0x56, // PUSH1 loop JUMP
+];
+
+/// Selector of `swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,
+/// address[],address,uint256)` of Uniswap V2 routers
+const SWAP_EXACT_ETH_FOR_TOKENS: [u8; 4] = [0xb6, 0xf9, 0xde, 0x95];
+/// Selector of `deposit()` of WETH
+const DEPOSIT: [u8; 4] = [0xd0, 0xe3, 0x0d, 0xb0];
+
+/// A transaction of the exploit, to be signed by `from`
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct RawTx {
+    pub from: String,
+    pub to: String,
+    pub data: String,
+    pub value: String,
+    pub block_number: String,
+    pub timestamp: String,
+}
+
+/// The transactions executed atomically by a batch executor deployed by the
+/// attacker, as needed by flashloans
+#[derive(Debug, Serialize, Default)]
+pub struct Batch {
+    /// Init code of the batch executor
+    pub init_code: String,
+    /// Calldata of the call to the batch executor
+    pub data: String,
+    /// Value of the call to the batch executor
+    pub value: String,
+}
+
+/// The exploit as raw transactions, to be simulated in Tenderly or anvil
+#[derive(Debug, Serialize, Default)]
+pub struct Bundle {
+    pub chain: String,
+    pub block_number: String,
+    pub transactions: Vec<RawTx>,
+    /// Steps of the exploit that can not be sent as raw transactions, e.g.,
+    /// selling a share of a balance only known during the execution
+    pub unsupported: Vec<String>,
+    pub batch: Batch,
+}
+
+fn encode_word(word: &[u8]) -> Vec<u8> {
+    [vec![0; 32 - word.len()], word.to_vec()].concat()
+}
+
+/// Calldata of buying the last token of `path` with ETH through a Uniswap V2
+/// router, the tokens being sent to `to`
+pub fn encode_buy(path: &[EVMAddress], to: EVMAddress) -> Vec<u8> {
+    let mut res = SWAP_EXACT_ETH_FOR_TOKENS.to_vec();
+    res.extend(encode_word(&[0])); // amountOutMin
+    res.extend(encode_word(&[0x80])); // offset of path
+    res.extend(encode_word(&to.0));
+    res.extend(EVMU256::MAX.to_be_bytes::<32>()); // deadline
+    res.extend(encode_word(&EVMU256::from(path.len()).to_be_bytes::<32>()));
+    for token in path {
+        res.extend(encode_word(&token.0));
+    }
+    res
+}
+
+/// Calldata of the batch executor executing the transactions
+pub fn encode_batch(txs: &[RawTx]) -> Vec<u8> {
+    let mut res = vec![];
+    for tx in txs {
+        let data = hex::decode(tx.data.trim_start_matches("0x")).unwrap_or_default();
+        res.extend(EVMAddress::from_str(&tx.to).unwrap_or_default().0);
+        res.extend(parse_value(&tx.value).to_be_bytes::<32>());
+        res.extend(EVMU256::from(data.len()).to_be_bytes::<32>());
+        res.extend(data);
+    }
+    res
+}
+
+fn parse_value(value: &str) -> EVMU256 {
+    EVMU256::from_str_radix(value.trim_start_matches("0x"), 16).unwrap_or_default()
+}
+
+impl Bundle {
+    pub fn new<T: SolutionTx>(chain: String, block_number: String, inputs: &[T]) -> Self {
+        let mut bundle = Self {
+            chain,
+            block_number,
+            ..Default::default()
+        };
+        for input in inputs {
+            let raw = |to: String, data: Vec<u8>| RawTx {
+                from: input.caller(),
+                to,
+                data: format!("0x{}", hex::encode(data)),
+                value: input.raw_value(),
+                block_number: input.block_number(),
+                timestamp: input.timestamp(),
+            };
+            let swap_data = input.swap_data();
+            if input.is_borrow() {
+                if let Some(deposit) = swap_data.get("deposit") {
+                    bundle.transactions.push(raw(deposit.target.clone(), DEPOSIT.to_vec()));
+                } else if let Some(buy) = swap_data.get("buy") {
+                    let path = buy
+                        .path
+                        .iter()
+                        .map(|token| EVMAddress::from_str(token).unwrap_or_default())
+                        .collect::<Vec<_>>();
+                    let to = EVMAddress::from_str(&input.caller()).unwrap_or_default();
+                    bundle.transactions.push(raw(buy.target.clone(), encode_buy(&path, to)));
+                } else {
+                    bundle
+                        .unsupported
+                        .push(format!("borrow {} without a swap path", input.contract()));
+                }
+            } else if input.fn_selector() == "0x00000000" {
+                bundle
+                    .unsupported
+                    .push(format!("return to the pending call of {}", input.contract()));
+            } else {
+                let data = hex::decode(input.calldata()).unwrap_or_default();
+                bundle.transactions.push(raw(input.contract(), data));
+            }
+            if SellType::new(input.liq_percent(), &swap_data) != SellType::None {
+                bundle.unsupported.push(format!(
+                    "liquidate {}% of the balance of {} of {}",
+                    input.liq_percent() as usize * 10,
+                    input.contract(),
+                    input.caller()
+                ));
+            }
+        }
+
+        let value = bundle
+            .transactions
+            .iter()
+            .fold(EVMU256::ZERO, |acc, tx| acc.saturating_add(parse_value(&tx.value)));
+        bundle.batch = Batch {
+            init_code: format!(
+                "0x{}{}",
+                hex::encode(BATCH_EXECUTOR_INIT),
+                hex::encode(BATCH_EXECUTOR_RUNTIME)
+            ),
+            data: format!("0x{}", hex::encode(encode_batch(&bundle.transactions))),
+            value: format!("0x{:x}", value),
+        };
+        bundle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_executor() {
+        assert_eq!(BATCH_EXECUTOR_INIT[1] as usize, BATCH_EXECUTOR_RUNTIME.len());
+        assert_eq!(BATCH_EXECUTOR_INIT[4] as usize, BATCH_EXECUTOR_INIT.len());
+        // jump destinations
+        for dest in [0x02, 0x0a, 0x38] {
+            assert_eq!(BATCH_EXECUTOR_RUNTIME[dest], 0x5b);
+        }
+
+        let tx = RawTx {
+            from: String::new(),
+            to: format!("{:?}", EVMAddress::from_slice(&[0xaa; 20])),
+            data: "0x1234".to_string(),
+            value: "0x10".to_string(),
+            block_number: String::new(),
+            timestamp: String::new(),
+        };
+        let batch = encode_batch(&[tx]);
+        assert_eq!(batch.len(), 84 + 2);
+        assert_eq!(&batch[..20], &[0xaa; 20]);
+        assert_eq!(batch[51], 0x10);
+        assert_eq!(batch[83], 2);
+        assert_eq!(&batch[84..], &[0x12, 0x34]);
+    }
+
+    #[test]
+    fn test_encode_buy() {
+        let path = [EVMAddress::from_slice(&[1; 20]), EVMAddress::from_slice(&[2; 20])];
+        let data = encode_buy(&path, EVMAddress::from_slice(&[3; 20]));
+        assert_eq!(data.len(), 4 + 32 * 7);
+        assert_eq!(&data[..4], &SWAP_EXACT_ETH_FOR_TOKENS);
+        assert_eq!(data[4 + 32 * 4 + 31], 2);
+        assert_eq!(&data[4 + 32 * 6 + 12..], &[2; 20]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Flashloans wrapping the generated exploits, so that the ETH the fuzzer
+//! borrows is borrowed (as WETH) from a lender of the forked chain instead of
+//! being dealt, and the test fails when the loan cannot be repaid.
+
+use std::str::FromStr;
+
+use serde::Serialize;
+use tracing::warn;
+
+use super::{BuyType, Tx};
+use crate::evm::{
+    onchain::chains::get_custom_chain,
+    types::{checksum, EVMAddress, EVMU256},
+};
+
+/// Balancer V2 vault, at the same address on the chains it is deployed on
+const BALANCER_VAULT: &str = "0xBA12222222228d8Ba445958a75a0704d566BF2C8";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlashloanProvider {
+    AaveV3,
+    Balancer,
+    DyDx,
+    /// Flash swap of a Uniswap V2 pair of the wrapped native token
+    Pair,
+}
+
+impl FromStr for FlashloanProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aave_v3" => Ok(Self::AaveV3),
+            "balancer" => Ok(Self::Balancer),
+            "dydx" => Ok(Self::DyDx),
+            "pair" => Ok(Self::Pair),
+            _ => Err(format!("unknown flashloan provider {}", s)),
+        }
+    }
+}
+
+impl From<FlashloanProvider> for String {
+    fn from(input: FlashloanProvider) -> Self {
+        match input {
+            FlashloanProvider::AaveV3 => "aave_v3".to_string(),
+            FlashloanProvider::Balancer => "balancer".to_string(),
+            FlashloanProvider::DyDx => "dydx".to_string(),
+            FlashloanProvider::Pair => "pair".to_string(),
+        }
+    }
+}
+
+impl FlashloanProvider {
+    /// Without fee first
+    const ALL: [Self; 4] = [Self::Balancer, Self::DyDx, Self::AaveV3, Self::Pair];
+
+    /// Contract lending the wrapped native token of the chain, None if the
+    /// provider is not deployed on it
+    pub fn lender(&self, chain: &str, weth: EVMAddress) -> Option<String> {
+        let lender = match (self, chain) {
+            (Self::AaveV3, "eth") => "0x87870Bca3F3fD6335C3F4ce8392D69350B4fA4E2",
+            (Self::AaveV3, "bsc") => "0x6807dc923806fE8Fd134338EABCA509979a7e0cB",
+            (Self::AaveV3, "polygon" | "optimism" | "arbitrum" | "avalanche") => {
+                "0x794a61358D6845594F94dc1DB02A252b5b4814aD"
+            }
+            (Self::AaveV3, "base") => "0xA238Dd80C259a72e81d7e4664a9801593F98d1c5",
+            (Self::Balancer, "eth" | "polygon" | "optimism" | "arbitrum" | "avalanche" | "gnosis" | "base") => {
+                BALANCER_VAULT
+            }
+            (Self::DyDx, "eth") => "0x1E0447b19BB6EcFdAe1e4AE1694b0C3659614e4e",
+            // WETH / USDC of Uniswap V2 and WBNB / BUSD of PancakeSwap V2
+            (Self::Pair, "eth") => "0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc",
+            (Self::Pair, "bsc") => "0x58F876857a02D6762E0101bb5C46A8c1ED44Dc16",
+            (Self::Pair, name) => return custom_pair(name, weth),
+            _ => return None,
+        };
+        Some(lender.to_string())
+    }
+
+    /// Fee charged by the lender on `principal`, in wei
+    pub fn fee(&self, principal: EVMU256) -> EVMU256 {
+        match self {
+            // 0.05%, rounded up
+            Self::AaveV3 => (principal * EVMU256::from(5) + EVMU256::from(9999)) / EVMU256::from(10000),
+            Self::Balancer => EVMU256::ZERO,
+            Self::DyDx => EVMU256::from(2),
+            // the swap fee on the amount out, rounded up as in `_flashSwap`
+            Self::Pair => principal * EVMU256::from(1000) / EVMU256::from(997) + EVMU256::from(1) - principal,
+        }
+    }
+}
+
+/// Pair of the wrapped native token and a pegged token on a DEX of the chain
+/// registry whose init code hash is known
+fn custom_pair(chain: &str, weth: EVMAddress) -> Option<String> {
+    let chain = get_custom_chain(chain)?;
+    let mut pegged_tokens: Vec<&String> = chain.pegged_tokens.values().collect();
+    pegged_tokens.sort();
+    let token = EVMAddress::from_str(pegged_tokens.first()?).ok()?;
+    chain
+        .dexes
+        .iter()
+        .find_map(|dex| dex.pair_address(weth, token))
+        .map(|pair| checksum(&pair))
+}
+
+/// Parse the providers separated by comma, in order of preference, `auto`
+/// being all of them
+pub fn parse_flashloan_providers(input: &str) -> Result<Vec<FlashloanProvider>, String> {
+    let mut providers = vec![];
+    for provider in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if provider == "auto" {
+            providers.extend(FlashloanProvider::ALL);
+        } else {
+            providers.push(FlashloanProvider::from_str(provider)?);
+        }
+    }
+    Ok(providers)
+}
+
+/// Flashloan of the test, the fee being paid with the amount the lender
+/// charges in the callback
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct Flashloan {
+    provider: String,
+    lender: String,
+    weth: String,
+    /// ETH borrowed by the txs, in wei
+    principal: String,
+}
+
+impl Flashloan {
+    /// Flashloan from the first provider deployed on the chain whose fee is
+    /// covered by the ETH `profit` of the exploit (in wei, any fee when
+    /// unknown), None when nothing is borrowed
+    pub fn new(
+        providers: &[FlashloanProvider],
+        chain: &str,
+        weth: &str,
+        trace: &[Tx],
+        profit: Option<EVMU256>,
+    ) -> Option<Self> {
+        let principal = principal(trace);
+        let weth = EVMAddress::from_str(weth).ok()?;
+        if providers.is_empty() || principal.is_zero() {
+            return None;
+        }
+
+        let repaid = |provider: &FlashloanProvider| profit.map_or(true, |profit| provider.fee(principal) <= profit);
+        let flashloan = providers
+            .iter()
+            .filter(|provider| repaid(provider))
+            .find_map(|provider| {
+                provider.lender(chain, weth).map(|lender| Self {
+                    provider: String::from(*provider),
+                    lender,
+                    weth: checksum(&weth),
+                    principal: principal.to_string(),
+                })
+            });
+        if flashloan.is_none() {
+            warn!(
+                "no flashloan provider on {} whose fee the exploit repays, the borrowed ETH is dealt",
+                chain
+            );
+        }
+        flashloan
+    }
+}
+
+/// ETH borrowed by the tx to deposit or buy tokens, in wei
+pub fn borrowed(tx: &Tx) -> Option<EVMU256> {
+    if tx.buy_type == BuyType::None {
+        return None;
+    }
+    EVMU256::from_str_radix(tx.raw_value.trim_start_matches("0x"), 16).ok()
+}
+
+fn principal(trace: &[Tx]) -> EVMU256 {
+    trace
+        .iter()
+        .filter_map(borrowed)
+        .fold(EVMU256::ZERO, |acc, value| acc.saturating_add(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flashloan() {
+        assert_eq!(
+            parse_flashloan_providers("pair, aave_v3").unwrap(),
+            vec![FlashloanProvider::Pair, FlashloanProvider::AaveV3]
+        );
+        assert_eq!(parse_flashloan_providers("auto").unwrap().len(), 4);
+        assert!(parse_flashloan_providers("").unwrap().is_empty());
+        assert!(parse_flashloan_providers("maker").is_err());
+
+        let weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+        let trace: Vec<Tx> = [
+            (BuyType::Buy, "0xde0b6b3a7640000"),
+            (BuyType::None, "0x10"),
+            (BuyType::Deposit, "0x2"),
+        ]
+        .into_iter()
+        .map(|(buy_type, raw_value)| Tx {
+            buy_type,
+            raw_value: raw_value.to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+        // dYdX is only deployed on Ethereum
+        let providers = [FlashloanProvider::DyDx, FlashloanProvider::Pair];
+        let flashloan = Flashloan::new(&providers, "bsc", weth, &trace, None).unwrap();
+        assert_eq!(flashloan.provider, "pair");
+        assert_eq!(flashloan.principal, "1000000000000000002");
+        assert!(Flashloan::new(&providers, "fantom", weth, &trace, None).is_none());
+        assert!(Flashloan::new(&providers, "bsc", weth, &trace[1..2], None).is_none());
+
+        // the providers whose fee exceeds the profit of the exploit are skipped
+        let providers = [
+            FlashloanProvider::Pair,
+            FlashloanProvider::AaveV3,
+            FlashloanProvider::DyDx,
+        ];
+        let profit = |wei: u64| Some(EVMU256::from(wei));
+        let provider = |profit| Flashloan::new(&providers, "eth", weth, &trace, profit).map(|f| f.provider);
+        assert_eq!(provider(profit(10_000_000_000_000_000)).unwrap(), "pair");
+        assert_eq!(provider(profit(1_000_000_000_000_000)).unwrap(), "aave_v3");
+        assert_eq!(provider(profit(2)).unwrap(), "dydx");
+        assert!(provider(profit(1)).is_none());
+    }
+
+    #[test]
+    fn test_fee() {
+        let principal = EVMU256::from(1_000_000_000_000_000_000_u128);
+        assert_eq!(
+            FlashloanProvider::AaveV3.fee(principal),
+            EVMU256::from(500_000_000_000_000_u128)
+        );
+        assert_eq!(FlashloanProvider::AaveV3.fee(EVMU256::from(1)), EVMU256::from(1));
+        assert_eq!(FlashloanProvider::Balancer.fee(principal), EVMU256::ZERO);
+        assert_eq!(FlashloanProvider::DyDx.fee(principal), EVMU256::from(2));
+        // 1e18 * 1000 / 997 + 1
+        assert_eq!(
+            FlashloanProvider::Pair.fee(principal),
+            EVMU256::from(3_009_027_081_243_732_u128)
+        );
+    }
+}
@@ -1,4 +1,6 @@
 mod abi;
+mod bundle;
+pub mod flashloan;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -11,15 +13,22 @@ use std::{
 use abi::StructDef;
 use handlebars::{handlebars_helper, Handlebars};
 use serde::Serialize;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
-use self::abi::{Abi, DecodedArg};
-use super::{types::EVMU256, utils, OnChainConfig};
-use crate::{generic_vm::vm_state::SwapInfo, input::SolutionTx};
+use self::{
+    abi::{Abi, DecodedArg},
+    bundle::Bundle,
+    flashloan::{Flashloan, FlashloanProvider},
+};
+use super::{onchain::ChainConfig, types::EVMU256, utils, OnChainConfig};
+use crate::{generic_vm::vm_state::SwapInfo, input::SolutionTx, r#const::BLOCK_GAS_LIMIT};
 
 /// Template
 const TEMPLATE: &str = include_str!("foundry_test.hbs");
 
+/// Name of the test contracts of the Foundry project
+const EXPLOIT_CONTRACT_NAME: &str = "Exploit";
+
 /// Cli args.
 static CLI_ARGS: OnceLock<CliArgs> = OnceLock::new();
 
@@ -28,18 +37,32 @@ handlebars_helper!(is_deposit: |ty: String| ty == "deposit");
 handlebars_helper!(is_buy: |ty: String| ty == "buy");
 handlebars_helper!(is_withdraw: |ty: String| ty == "withdraw");
 handlebars_helper!(is_sell: |ty: String| ty == "sell");
+handlebars_helper!(is_aave_v3: |provider: String| provider == "aave_v3");
+handlebars_helper!(is_balancer: |provider: String| provider == "balancer");
+handlebars_helper!(is_dydx: |provider: String| provider == "dydx");
+handlebars_helper!(is_pair: |provider: String| provider == "pair");
 
 /// Initialize CLI_ARGS.
-pub fn init_cli_args(target: String, work_dir: String, onchain: &Option<OnChainConfig>) {
-    let (chain, block_number) = match onchain {
+pub fn init_cli_args(
+    target: String,
+    work_dir: String,
+    onchain: &Option<OnChainConfig>,
+    flashloan_providers: Vec<FlashloanProvider>,
+) {
+    let (chain, block_number, weth) = match onchain {
         Some(oc) => {
             let block_number = oc.block_number.clone();
             let number = EVMU256::from_str_radix(block_number.trim_start_matches("0x"), 16)
                 .unwrap()
                 .to_string();
-            (oc.chain_name.clone(), number)
+            // only looked up for the flashloans, it is unknown on some chains
+            let weth = match flashloan_providers.is_empty() {
+                true => String::new(),
+                false => oc.get_weth(),
+            };
+            (oc.chain_name.clone(), number, weth)
         }
-        None => (String::from(""), String::from("")),
+        None => (String::from(""), String::from(""), String::from("")),
     };
 
     let cli_args = CliArgs {
@@ -48,13 +71,35 @@ pub fn init_cli_args(target: String, work_dir: String, onchain: &Option<OnChainC
         target,
         block_number,
         output_dir: format!("{}/vulnerabilities", work_dir),
+        project_dir: format!("{}/foundry", work_dir),
+        weth,
+        flashloan_providers,
     };
 
     let _ = CLI_ARGS.set(cli_args);
 }
 
-/// Generate a foundry test file.
-pub fn generate_test<T: SolutionTx>(solution: String, inputs: Vec<T>) {
+/// Handlebars with the test template and its helpers registered
+fn handlebars() -> Result<Handlebars<'static>, String> {
+    let mut handlebars = Handlebars::new();
+    handlebars
+        .register_template_string("foundry_test", TEMPLATE)
+        .map_err(|e| e.to_string())?;
+
+    handlebars.register_helper("is_deposit", Box::new(is_deposit));
+    handlebars.register_helper("is_buy", Box::new(is_buy));
+    handlebars.register_helper("is_withdraw", Box::new(is_withdraw));
+    handlebars.register_helper("is_sell", Box::new(is_sell));
+    handlebars.register_helper("is_aave_v3", Box::new(is_aave_v3));
+    handlebars.register_helper("is_balancer", Box::new(is_balancer));
+    handlebars.register_helper("is_dydx", Box::new(is_dydx));
+    handlebars.register_helper("is_pair", Box::new(is_pair));
+    Ok(handlebars)
+}
+
+/// Generate a foundry test file, flashloaning the borrowed ETH from a lender
+/// whose fee is covered by the ETH `profit` of the exploit, in wei.
+pub fn generate_test<T: SolutionTx>(solution: String, inputs: Vec<T>, profit: Option<EVMU256>) {
     let solution = utils::remove_color(&solution);
 
     let trace: Vec<Tx> = inputs.iter().map(Tx::from).collect();
@@ -62,7 +107,7 @@ pub fn generate_test<T: SolutionTx>(solution: String, inputs: Vec<T>) {
         error!("generate_test error: no trace found.");
         return;
     }
-    let args = TemplateArgs::new(solution, trace);
+    let args = TemplateArgs::new(solution, trace, profit);
     if args.is_err() {
         debug!("skip generating test: not evm solution.");
         return;
@@ -75,16 +120,13 @@ pub fn generate_test<T: SolutionTx>(solution: String, inputs: Vec<T>) {
         );
         return;
     }
-    let mut handlebars = Handlebars::new();
-    if handlebars.register_template_string("foundry_test", TEMPLATE).is_err() {
-        error!("generate_test error: failed to register template file.");
-        return;
-    }
-
-    handlebars.register_helper("is_deposit", Box::new(is_deposit));
-    handlebars.register_helper("is_buy", Box::new(is_buy));
-    handlebars.register_helper("is_withdraw", Box::new(is_withdraw));
-    handlebars.register_helper("is_sell", Box::new(is_sell));
+    let handlebars = match handlebars() {
+        Ok(handlebars) => handlebars,
+        Err(_) => {
+            error!("generate_test error: failed to register template file.");
+            return;
+        }
+    };
 
     let path = format!("{}/{}.t.sol", args.output_dir, args.contract_name);
     let output = File::create(path);
@@ -95,9 +137,73 @@ pub fn generate_test<T: SolutionTx>(solution: String, inputs: Vec<T>) {
 
     if let Err(e) = handlebars.render_to_write("foundry_test", &args, &mut output.unwrap()) {
         error!("generate_test error: failed to render template: {:?}", e);
+        return;
+    }
+
+    let bundle = Bundle::new(args.chain.clone(), args.block_number.clone(), &inputs);
+    let path = format!("{}/{}.bundle.json", args.output_dir, args.contract_name);
+    if fs::write(&path, serde_json::to_string_pretty(&bundle).unwrap()).is_err() {
+        error!("generate_test error: failed to write {:?}.", path);
+    }
+
+    write_foundry_project(&handlebars, args);
+}
+
+/// Write the test to the Foundry project of the work dir as
+/// `test/Exploit.t.sol` (`test/Exploit2.t.sol`, ... for the next findings),
+/// so that the findings can be replayed with `forge test`
+fn write_foundry_project(handlebars: &Handlebars, mut args: TemplateArgs) {
+    let cli_args = CLI_ARGS.get().unwrap();
+    let test_dir = format!("{}/test", cli_args.project_dir);
+    if fs::create_dir_all(&test_dir).is_err() {
+        error!("generate_test error: failed to create project dir {:?}.", test_dir);
+        return;
+    }
+    let config_path = format!("{}/foundry.toml", cli_args.project_dir);
+    if !Path::new(&config_path).exists() && fs::write(&config_path, foundry_config(cli_args)).is_err() {
+        error!("generate_test error: failed to write {:?}.", config_path);
+        return;
+    }
+
+    args.contract_name = (1..)
+        .map(|idx| match idx {
+            1 => EXPLOIT_CONTRACT_NAME.to_string(),
+            _ => format!("{}{}", EXPLOIT_CONTRACT_NAME, idx),
+        })
+        .find(|name| !Path::new(&format!("{}/{}.t.sol", test_dir, name)).exists())
+        .unwrap();
+    let output = match File::create(format!("{}/{}.t.sol", test_dir, args.contract_name)) {
+        Ok(output) => output,
+        Err(_) => {
+            error!("generate_test error: failed to create output file.");
+            return;
+        }
+    };
+    if let Err(e) = handlebars.render_to_write("foundry_test", &args, output) {
+        error!("generate_test error: failed to render template: {:?}", e);
     }
 }
 
+/// `foundry.toml` of the Foundry project, with the RPC endpoint of the forked
+/// chain read from `<CHAIN>_RPC_URL`
+fn foundry_config(cli_args: &CliArgs) -> String {
+    let mut config = String::from(
+        "# forge install foundry-rs/forge-std --no-git && forge test -vvv\n\
+         [profile.default]\n\
+         src = \"src\"\n\
+         test = \"test\"\n\
+         libs = [\"lib\"]\n",
+    );
+    if cli_args.is_onchain {
+        config.push_str(&format!(
+            "\n[rpc_endpoints]\n{} = \"${{{}_RPC_URL}}\"\n",
+            cli_args.chain,
+            cli_args.chain.to_uppercase()
+        ));
+    }
+    config
+}
+
 #[derive(Debug, Clone)]
 struct CliArgs {
     is_onchain: bool,
@@ -105,6 +211,12 @@ struct CliArgs {
     target: String,
     block_number: String,
     output_dir: String,
+    /// Foundry project the tests are also written to
+    project_dir: String,
+    /// Wrapped native token of the chain, borrowed by the flashloans
+    weth: String,
+    /// Providers the borrowed ETH is flashloaned from, in order of preference
+    flashloan_providers: Vec<FlashloanProvider>,
 }
 
 #[derive(Debug, Serialize, Default)]
@@ -117,6 +229,8 @@ pub struct Tx {
     caller: String,
     contract: String,
     value: String,
+    // Value in wei as a hex string
+    raw_value: String,
     fn_signature: String,
     fn_selector: String,
     fn_args: String,
@@ -125,6 +239,15 @@ pub struct Tx {
     balance_idx: u32,
     // map<type, swap_info>
     swap_data: HashMap<String, SwapInfo>,
+    gas_used: u64,
+    // Whether the tx starts a new block
+    new_block: bool,
+    // Block environment the tx was executed in
+    block_number: String,
+    timestamp: String,
+    // Block number / timestamp to move to before the tx, if they changed
+    roll: String,
+    warp: String,
 }
 
 impl<T: SolutionTx> From<&T> for Tx {
@@ -145,12 +268,16 @@ impl<T: SolutionTx> From<&T> for Tx {
             caller: input.caller(),
             contract: input.contract(),
             value: input.value(),
+            raw_value: input.raw_value(),
             fn_signature: input.fn_signature(),
             fn_selector: input.fn_selector(),
             fn_args: input.fn_args(),
             calldata: input.calldata(),
             liq_percent,
             swap_data,
+            gas_used: input.gas_used(),
+            block_number: input.block_number(),
+            timestamp: input.timestamp(),
             ..Default::default()
         }
     }
@@ -196,11 +323,13 @@ pub struct TemplateArgs {
     solution: String,
     trace: Vec<Tx>,
     stepping_with_return: bool,
+    // The borrowed ETH is dealt without it
+    flashloan: Option<Flashloan>,
     output_dir: String,
 }
 
 impl TemplateArgs {
-    pub fn new(solution: String, trace: Vec<Tx>) -> Result<Self, String> {
+    pub fn new(solution: String, trace: Vec<Tx>, profit: Option<EVMU256>) -> Result<Self, String> {
         let cli_args = CLI_ARGS.get();
         if cli_args.is_none() {
             return Err(String::from("CLI_ARGS is not initialized."));
@@ -212,6 +341,23 @@ impl TemplateArgs {
         let mut trace: Vec<Tx> = trace.into_iter().filter(|tx| tx.fn_selector != "0x00000000").collect();
 
         setup_trace(&mut trace);
+        let flashloan = Flashloan::new(
+            &cli_args.flashloan_providers,
+            &cli_args.chain,
+            &cli_args.weth,
+            &trace,
+            profit,
+        );
+        if flashloan.is_some() {
+            // exact amounts, adding up to the principal
+            for tx in trace.iter_mut() {
+                if let Some(value) = flashloan::borrowed(tx) {
+                    tx.value = value.to_string();
+                }
+            }
+        }
+        split_blocks(&mut trace);
+        set_block_env(&mut trace);
         let router = get_router(&trace);
         let contract_name = make_contract_name(cli_args);
         let include_interface = trace
@@ -239,6 +385,7 @@ impl TemplateArgs {
             solution,
             trace,
             stepping_with_return,
+            flashloan,
             output_dir: cli_args.output_dir.clone(),
         })
     }
@@ -322,6 +469,44 @@ fn setup_trace(trace: &mut [Tx]) {
     }
 }
 
+/// Packs the txs into blocks under the block gas limit, a tx which does not
+/// fit in the current block starts a new one.
+fn split_blocks(trace: &mut [Tx]) {
+    let mut block_gas = 0;
+    for (idx, tx) in trace.iter_mut().enumerate() {
+        if tx.gas_used > BLOCK_GAS_LIMIT {
+            warn!(
+                "tx {} uses {} gas, exceeding the block gas limit {}",
+                idx, tx.gas_used, BLOCK_GAS_LIMIT
+            );
+        }
+        if block_gas > 0 && block_gas + tx.gas_used > BLOCK_GAS_LIMIT {
+            tx.new_block = true;
+            block_gas = 0;
+        }
+        block_gas += tx.gas_used;
+    }
+}
+
+/// Moves to the block number and timestamp of each tx when they differ from
+/// the ones of the previous tx, the block env of the first tx being the one of
+/// the fork (or zero offchain)
+fn set_block_env(trace: &mut [Tx]) {
+    let (mut block_number, mut timestamp) = (String::from("0"), String::from("0"));
+    for tx in trace.iter_mut() {
+        if !tx.block_number.is_empty() && tx.block_number != block_number {
+            tx.roll = tx.block_number.clone();
+            block_number = tx.block_number.clone();
+            // the roll already starts a new block
+            tx.new_block = false;
+        }
+        if !tx.timestamp.is_empty() && tx.timestamp != timestamp {
+            tx.warp = tx.timestamp.clone();
+            timestamp = tx.timestamp.clone();
+        }
+    }
+}
+
 fn make_erc20_calls(tx: &Tx) -> Option<String> {
     if tx.buy_type != BuyType::None {
         return None;
@@ -479,6 +664,7 @@ mod tests {
         liq_percent: u8,
         swap_data: HashMap<String, SwapInfo>,
         calldata: String,
+        gas_used: u64,
     }
 
     impl MockInput {
@@ -494,6 +680,7 @@ mod tests {
                 liq_percent: 0,
                 swap_data: HashMap::new(),
                 calldata: String::from(calldata),
+                gas_used: 0,
             }
         }
     }
@@ -529,19 +716,100 @@ mod tests {
         fn calldata(&self) -> String {
             self.calldata.clone()
         }
+        fn gas_used(&self) -> u64 {
+            self.gas_used
+        }
     }
 
     #[test]
     fn test_template_is_valid() {
-        let mut handlebars = Handlebars::new();
-        assert!(handlebars.register_template_string("foundry_test", TEMPLATE).is_ok());
+        assert!(handlebars().is_ok());
+    }
+
+    #[test]
+    fn test_render_flashloan() {
+        let weth = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+        let handlebars = handlebars().unwrap();
+        let cases = [
+            (FlashloanProvider::AaveV3, ["flashLoanSimple(", "executeOperation("]),
+            (FlashloanProvider::Balancer, ["IBalancerVault(", "receiveFlashLoan("]),
+            (FlashloanProvider::DyDx, ["operate(", "callFunction("]),
+            (FlashloanProvider::Pair, ["uniswapV2Call(", "_flashSwap("]),
+        ];
+        for (provider, expected) in cases {
+            let mut input = MockInput::new("deposit()", "0xd0e30db0", "1");
+            input.is_borrow = true;
+            input.swap_data.insert("deposit".to_string(), SwapInfo::default());
+            let mut tx = Tx::from(&input);
+            tx.raw_value = "0xde0b6b3a7640000".to_string();
+            let trace = vec![tx];
+
+            let args = TemplateArgs {
+                flashloan: Flashloan::new(&[provider], "eth", weth, &trace, None),
+                trace,
+                ..Default::default()
+            };
+            assert!(args.flashloan.is_some());
+            let test = handlebars.render("foundry_test", &args).unwrap();
+            for expected in expected {
+                assert!(test.contains(expected), "{:?} test lacks {}", provider, expected);
+            }
+            // the exploit runs in the callback and the test fails without repayment
+            assert!(test.contains("_exploitWithFlashloan("));
+            assert!(test.contains("flashloan not repaid"));
+            assert!(test.contains("1000000000000000000"));
+        }
+    }
+
+    #[test]
+    fn test_split_blocks() {
+        let mut trace: Vec<Tx> = [20_000_000, 5_000_000, 6_000_000, 40_000_000, 1_000]
+            .iter()
+            .map(|&gas_used| {
+                let mut input = MockInput::new("", "", "");
+                input.gas_used = gas_used;
+                Tx::from(&input)
+            })
+            .collect();
+        split_blocks(&mut trace);
+        let new_blocks: Vec<bool> = trace.iter().map(|tx| tx.new_block).collect();
+        assert_eq!(new_blocks, vec![false, false, true, true, true]);
+    }
+
+    #[test]
+    fn test_set_block_env() {
+        let mut trace: Vec<Tx> = [("0", "0"), ("5", "60"), ("5", "60"), ("5", "120")]
+            .iter()
+            .map(|(block_number, timestamp)| {
+                let mut tx = Tx::from(&MockInput::new("", "", ""));
+                tx.block_number = block_number.to_string();
+                tx.timestamp = timestamp.to_string();
+                tx
+            })
+            .collect();
+        set_block_env(&mut trace);
+        let env: Vec<(&str, &str)> = trace.iter().map(|tx| (tx.roll.as_str(), tx.warp.as_str())).collect();
+        assert_eq!(env, vec![("", ""), ("5", "60"), ("", ""), ("", "120")]);
+    }
+
+    #[test]
+    fn test_foundry_config() {
+        init_cli_args(
+            "0xca143ce32fe78f1f7019d7d551a6402fc5350c73".to_string(),
+            "/tmp".to_string(),
+            &None,
+            vec![],
+        );
+        let config = foundry_config(CLI_ARGS.get().unwrap());
+        assert!(config.contains("[profile.default]"));
+        assert!(!config.contains("[rpc_endpoints]"));
     }
 
     #[test]
     fn test_generate_test() {
         let target = "0xca143ce32fe78f1f7019d7d551a6402fc5350c73".to_string();
         let work_dir = "/tmp".to_string();
-        init_cli_args(target, work_dir, &None);
+        init_cli_args(target, work_dir, &None, vec![]);
 
         let input1 = MockInput::new(
             "approve(address,uint256)",
@@ -555,6 +823,6 @@ mod tests {
         );
         let inputs = vec![input1, input2];
         let solution = String::from("solution");
-        generate_test(solution, inputs);
+        generate_test(solution, inputs, None);
     }
 }
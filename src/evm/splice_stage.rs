@@ -0,0 +1,286 @@
+//! Crossover of the transaction sequences of the infant state corpus
+//!
+//! A suffix of the sequence leading to a VM state is replayed on a prefix of
+//! the sequence leading to another one, the prefix ending with a VM state
+//! related to the one the suffix starts from, i.e., reached by calling the
+//! same contract. The suffix keeps following the sender of the prefix if it
+//! followed the sender of its own, and its transactions go through the
+//! feedbacks and oracles, so that multi-step exploit preambles propagate
+//! between testcases.
+
+use std::marker::PhantomData;
+
+use libafl::{
+    corpus::Corpus,
+    events::ProgressReporter,
+    prelude::{CorpusId, HasRand, ObserversTuple, Stage},
+    state::UsesState,
+    Error,
+    Evaluator,
+};
+use libafl_bolts::prelude::Rand;
+
+use crate::{
+    evm::{
+        host::CALL_UNTIL,
+        input::ConciseEVMInput,
+        types::{EVMAddress, EVMFuzzExecutor, EVMFuzzState, EVMInfantStateState, EVMStagedVMState},
+        vm::EVMStateT,
+    },
+    generic_vm::vm_state::VMStateT,
+    r#const::{MUTATOR_SAMPLE_MAX, SPLICE_CHOICE},
+    scheduler::SortedDroppingScheduler,
+    state::{HasExecutionResult, HasInfantStateState, HasItyState},
+};
+
+/// Indexes and VM states of the sequence leading to the VM state at `idx` of
+/// the infant state corpus, oldest first
+fn lineage(state: &mut EVMFuzzState, idx: usize) -> Vec<(usize, EVMStagedVMState)> {
+    let mut lineage = vec![];
+    let mut next = Some(idx);
+    while let Some(idx) = next {
+        let Ok(testcase) = state.get_infant_state_state().corpus().get(idx.into()) else {
+            break;
+        };
+        let Some(vm_state) = testcase.borrow().input().clone() else {
+            break;
+        };
+        next = vm_state.trace.from_idx;
+        lineage.push((idx, vm_state));
+    }
+    lineage.reverse();
+    lineage
+}
+
+/// Positions of `lineage` the sequence can be cut at, i.e., the VM states
+/// reached by calling `contract` that are not waiting for a control leak to
+/// resume
+fn cut_points(lineage: &[(usize, EVMStagedVMState)], contract: Option<EVMAddress>) -> Vec<usize> {
+    lineage
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, vm_state))| {
+            !vm_state.state.has_post_execution() &&
+                vm_state
+                    .state
+                    .get_last_function()
+                    .is_some_and(|(addr, _)| contract.map_or(true, |contract| addr == contract))
+        })
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+/// Transactions of `lineage` after the VM state at position `cut`
+fn suffix(lineage: &[(usize, EVMStagedVMState)], cut: usize) -> Vec<ConciseEVMInput> {
+    lineage[cut + 1..]
+        .iter()
+        .flat_map(|(_, vm_state)| vm_state.trace.transactions.clone())
+        .collect()
+}
+
+/// Make the transactions sent by `from` sent by `to`
+fn follow_sender(txns: &mut [ConciseEVMInput], from: EVMAddress, to: EVMAddress) {
+    txns.iter_mut()
+        .filter(|txn| txn.caller == from)
+        .for_each(|txn| txn.caller = to);
+}
+
+/// Splices two sequences of the infant state corpus in a fuzzing iteration
+/// out of [`MUTATOR_SAMPLE_MAX`] / [`SPLICE_CHOICE`]
+pub struct SpliceStage<OT> {
+    /// Adds the VM states in the middle of the spliced sequences to the infant
+    /// state corpus, so that the traces of the later ones are complete
+    infant_scheduler: SortedDroppingScheduler<EVMInfantStateState>,
+    pub phantom: PhantomData<OT>,
+}
+
+impl<OT> UsesState for SpliceStage<OT> {
+    type State = EVMFuzzState;
+}
+
+impl<OT> Default for SpliceStage<OT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<OT> SpliceStage<OT> {
+    pub fn new() -> Self {
+        Self {
+            infant_scheduler: SortedDroppingScheduler::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Pick the suffix to replay and the index of the VM state to replay it
+    /// on, if the two sequences sampled are related
+    fn pick(&self, state: &mut EVMFuzzState) -> Option<(usize, Vec<ConciseEVMInput>)> {
+        let infant_corpus = state.get_infant_state_state().corpus();
+        let mut ids = vec![];
+        let mut id = infant_corpus.first();
+        while let Some(tc_id) = id {
+            ids.push(usize::from(tc_id));
+            id = infant_corpus.next(tc_id);
+        }
+        if ids.len() < 2 {
+            return None;
+        }
+        let a = ids[state.rand_mut().below(ids.len() as u64) as usize];
+        let b = ids[state.rand_mut().below(ids.len() as u64) as usize];
+        if a == b {
+            return None;
+        }
+
+        let seq_a = lineage(state, a);
+        let cuts = cut_points(&seq_a, None);
+        let cut = *cuts.get(state.rand_mut().below(cuts.len().max(1) as u64) as usize)?;
+        let (cut_idx, cut_state) = &seq_a[cut];
+        let (contract, _) = cut_state.state.get_last_function()?;
+
+        let seq_b = lineage(state, b);
+        let prefixes = cut_points(&seq_b, Some(contract))
+            .into_iter()
+            .filter(|pos| seq_b[*pos].0 != *cut_idx)
+            .collect::<Vec<_>>();
+        let prefix = *prefixes.get(state.rand_mut().below(prefixes.len().max(1) as u64) as usize)?;
+        let (prefix_idx, prefix_state) = &seq_b[prefix];
+
+        let mut txns = suffix(&seq_a, cut);
+        if txns.is_empty() {
+            return None;
+        }
+        if let (Some(from), Some(to)) = (cut_state.state.get_last_caller(), prefix_state.state.get_last_caller()) {
+            follow_sender(&mut txns, from, to);
+        }
+        Some((*prefix_idx, txns))
+    }
+}
+
+impl<EM, Z, OT> Stage<EVMFuzzExecutor<OT>, EM, Z> for SpliceStage<OT>
+where
+    Z: Evaluator<EVMFuzzExecutor<OT>, EM, State = Self::State>,
+    EM: ProgressReporter + UsesState<State = Self::State>,
+    OT: ObserversTuple<Self::State>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut EVMFuzzExecutor<OT>,
+        state: &mut Self::State,
+        manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        if state.rand_mut().below(MUTATOR_SAMPLE_MAX) >= SPLICE_CHOICE {
+            return Ok(());
+        }
+        let Some((mut state_idx, txns)) = self.pick(state) else {
+            return Ok(());
+        };
+
+        let last = txns.len() - 1;
+        for (i, txn) in txns.into_iter().enumerate() {
+            // the VM state may have been pruned meanwhile
+            let Ok(testcase) = state.get_infant_state_state().corpus().get(state_idx.into()) else {
+                break;
+            };
+            let vm_state = testcase.borrow().input().clone().unwrap();
+            let (mut inp, call_until) = txn.to_input(vm_state);
+            inp.sstate_idx = state_idx;
+            unsafe {
+                CALL_UNTIL = call_until;
+            }
+            let infant_states = state.get_infant_state_state().corpus().count();
+            fuzzer.evaluate_input(state, executor, manager, inp)?;
+            if i == last || state.get_execution_result().reverted {
+                break;
+            }
+
+            // the next transaction runs on the VM state just reached, which the
+            // fuzzer added to the infant state corpus if it was interesting
+            state_idx = if state.get_infant_state_state().corpus().count() > infant_states {
+                state.get_infant_state_state().corpus().last().unwrap().into()
+            } else {
+                let new_state = state.get_execution_result().new_state.clone();
+                state.add_infant_state(&new_state, &mut self.infant_scheduler, state_idx)
+            };
+        }
+        unsafe {
+            CALL_UNTIL = u32::MAX;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::vm::EVMState;
+
+    fn vm_state(from_idx: Option<usize>, contract: u64, caller: u64, txns: &[u64]) -> EVMStagedVMState {
+        let mut state = EVMState::new();
+        state.last_function = Some((EVMAddress::from_low_u64_be(contract), [0; 4]));
+        state.last_caller = Some(EVMAddress::from_low_u64_be(caller));
+        let mut vm_state = EVMStagedVMState::new_with_state(state);
+        vm_state.trace.from_idx = from_idx;
+        for caller in txns {
+            vm_state.trace.add_input(ConciseEVMInput {
+                caller: EVMAddress::from_low_u64_be(*caller),
+                contract: EVMAddress::from_low_u64_be(contract),
+                ..Default::default()
+            });
+        }
+        vm_state
+    }
+
+    #[test]
+    fn test_splice() {
+        let seq = vec![
+            (0, vm_state(None, 1, 1, &[])),
+            (1, vm_state(Some(0), 2, 1, &[1])),
+            (2, vm_state(Some(1), 1, 2, &[2, 1])),
+            (3, vm_state(Some(2), 3, 1, &[1])),
+        ];
+        assert_eq!(cut_points(&seq, None), vec![0, 1, 2, 3]);
+        assert_eq!(cut_points(&seq, Some(EVMAddress::from_low_u64_be(1))), vec![0, 2]);
+
+        let mut txns = suffix(&seq, 1);
+        assert_eq!(txns.len(), 3);
+        // the transactions of the sender of the cut VM state follow the sender of
+        // the prefix
+        follow_sender(
+            &mut txns,
+            EVMAddress::from_low_u64_be(1),
+            EVMAddress::from_low_u64_be(5),
+        );
+        assert_eq!(
+            txns.iter().map(|txn| txn.caller.to_low_u64_be()).collect::<Vec<_>>(),
+            vec![2, 5, 5]
+        );
+        assert!(suffix(&seq, 3).is_empty());
+    }
+
+    #[test]
+    fn test_lineage() {
+        let mut state: EVMFuzzState = crate::state::FuzzState::new(0);
+        let mut scheduler = SortedDroppingScheduler::new();
+        let root = state.add_infant_state(&vm_state(None, 1, 1, &[]), &mut scheduler, 0);
+        let child = state.add_infant_state(&vm_state(Some(root), 2, 1, &[1]), &mut scheduler, root);
+        let leaf = state.add_infant_state(&vm_state(Some(child), 1, 2, &[2]), &mut scheduler, child);
+        assert_eq!(
+            lineage(&mut state, leaf)
+                .iter()
+                .map(|(idx, _)| *idx)
+                .collect::<Vec<_>>(),
+            vec![root, child, leaf]
+        );
+
+        // the caller and function of the VM states survive a save of the campaign
+        let saved = serde_cbor::to_vec(&lineage(&mut state, leaf)[2].1.state).unwrap();
+        let restored: EVMState = serde_cbor::from_slice(&saved).unwrap();
+        assert_eq!(restored.get_last_caller(), Some(EVMAddress::from_low_u64_be(2)));
+        assert_eq!(
+            restored.get_last_function(),
+            Some((EVMAddress::from_low_u64_be(1), [0; 4]))
+        );
+    }
+}
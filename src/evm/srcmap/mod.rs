@@ -153,6 +153,18 @@ impl SourceMapProvider {
         }
     }
 
+    /// File name and line (1-based) of the source code of the pc, if the pc
+    /// matches a single statement
+    pub fn get_source_line(&self, address: &EVMAddress, pc: usize) -> Option<(String, usize)> {
+        let item = self.source_maps.get(address)?.get_source_map_item_by_pc(pc)?;
+        if !item.pc_has_match {
+            return None;
+        }
+        let (file, content) = self.source_code.get(address)?.get(item.raw_info.file_idx?)?;
+        let line = content.get(..item.raw_info.offset)?.matches('\n').count() + 1;
+        Some((file.clone(), line))
+    }
+
     fn uncompress_srcmap_single(
         &self,
         map: String,
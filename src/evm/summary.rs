@@ -0,0 +1,284 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap},
+    ops::Deref,
+    rc::Rc,
+};
+
+use itertools::Itertools;
+use libafl::state::HasMetadata;
+use libafl_bolts::impl_serdeany;
+use revm_primitives::Bytecode;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    evm::{
+        bytecode_analyzer::{find_caller_guards, CallerGuard},
+        contract_utils::ABIConfig,
+        middlewares::coverage::Coverage,
+        oracles::erc20::IERC20OracleFlashloan,
+        types::{convert_u256_to_h160, EVMAddress, EVMFuzzState},
+        vm::EVMStorage,
+    },
+    r#const::{SUMMARY_FILE, SUMMARY_MAX_ITEMS, SUMMARY_STUCK_MIN_CALLS},
+    state::HasCaller,
+};
+
+/// Number of calls and of successful calls of each function of the fuzzed
+/// contracts
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FunctionStatsMetadata {
+    pub stats: HashMap<(EVMAddress, [u8; 4]), (usize, usize)>,
+    /// Number of calls of all functions
+    pub total_calls: usize,
+}
+
+impl_serdeany!(FunctionStatsMetadata);
+
+impl FunctionStatsMetadata {
+    pub fn record(&mut self, contract: EVMAddress, function: [u8; 4], reverted: bool) {
+        let (calls, successes) = self.stats.entry((contract, function)).or_default();
+        *calls += 1;
+        self.total_calls += 1;
+        if !reverted {
+            *successes += 1;
+        }
+    }
+
+    /// Number of calls of the function
+    pub fn calls(&self, contract: EVMAddress, function: [u8; 4]) -> usize {
+        self.stats.get(&(contract, function)).map_or(0, |(calls, _)| *calls)
+    }
+
+    /// Average number of calls of the functions called so far
+    pub fn average_calls(&self) -> f64 {
+        if self.stats.is_empty() {
+            return 0.0;
+        }
+        self.total_calls as f64 / self.stats.len() as f64
+    }
+
+    /// Functions called at least `min_calls` times that always reverted, the
+    /// most called first
+    pub fn stuck(&self, min_calls: usize) -> Vec<((EVMAddress, [u8; 4]), usize)> {
+        self.stats
+            .iter()
+            .filter(|(_, (calls, successes))| *calls >= min_calls && *successes == 0)
+            .map(|(function, (calls, _))| (*function, *calls))
+            .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)))
+            .collect()
+    }
+}
+
+/// Actionable checklist for users treating the fuzzer as a black box:
+/// accounts to impersonate to unlock branches, functions always reverting,
+/// tokens without a price and detectors worth enabling. It is refreshed with
+/// the coverage, written to `<work_dir>/summary.txt` and printed on exit.
+pub struct CampaignSummary {
+    pub address_to_name: HashMap<EVMAddress, String>,
+    pub address_to_abi: HashMap<EVMAddress, Vec<ABIConfig>>,
+    /// Branches comparing the caller to an account, by contract
+    pub caller_guards: HashMap<EVMAddress, HashMap<usize, CallerGuard>>,
+    /// Storage before fuzzing, holding the owners of the contracts
+    pub initial_storage: im::HashMap<EVMAddress, EVMStorage>,
+    /// Provides the known tokens
+    pub erc20_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
+    /// Detectors not enabled that apply to the fuzzed contracts
+    pub detector_hints: Vec<String>,
+    pub work_dir: String,
+}
+
+impl CampaignSummary {
+    pub fn new(
+        address_to_name: HashMap<EVMAddress, String>,
+        address_to_abi: HashMap<EVMAddress, Vec<ABIConfig>>,
+        address_to_bytecode: &HashMap<EVMAddress, Bytecode>,
+        initial_storage: im::HashMap<EVMAddress, EVMStorage>,
+        erc20_oracle: Rc<RefCell<IERC20OracleFlashloan>>,
+        work_dir: String,
+    ) -> Self {
+        let caller_guards = address_to_bytecode
+            .iter()
+            .map(|(address, bytecode)| (*address, find_caller_guards(bytecode)))
+            .filter(|(_, guards)| !guards.is_empty())
+            .collect();
+        Self {
+            address_to_name,
+            address_to_abi,
+            caller_guards,
+            initial_storage,
+            erc20_oracle,
+            detector_hints: vec![],
+            work_dir,
+        }
+    }
+
+    /// Suggest enabling `detector`, which applies to `count` contracts
+    pub fn add_detector_hint(&mut self, detector: &str, count: usize, reason: &str) {
+        if count > 0 {
+            self.detector_hints.push(format!(
+                "enable the {} detector (add `{}` to -d): {} contract(s) {}",
+                detector, detector, count, reason
+            ));
+        }
+    }
+
+    fn name(&self, address: &EVMAddress) -> String {
+        self.address_to_name
+            .get(address)
+            .cloned()
+            .unwrap_or(format!("{:?}", address))
+    }
+
+    /// Accounts compared to the caller in branches not fully covered, which
+    /// are not callers yet, the ones unlocking the most branches first
+    fn impersonations(&self, coverage: &Coverage, state: &EVMFuzzState) -> Vec<String> {
+        let mut unlocked: HashMap<EVMAddress, (usize, BTreeSet<String>)> = HashMap::new();
+        for (address, guards) in &self.caller_guards {
+            let covered = coverage.jumpi_coverage.get(address);
+            for (pc, guard) in guards {
+                let uncovered = [true, false]
+                    .iter()
+                    .filter(|side| !covered.is_some_and(|covered| covered.contains(&(*pc, **side))))
+                    .count();
+                if uncovered == 0 {
+                    continue;
+                }
+                let account = match guard {
+                    CallerGuard::Address(account) => convert_u256_to_h160(*account),
+                    CallerGuard::Storage(slot) => {
+                        match self.initial_storage.get(address).and_then(|storage| storage.get(slot)) {
+                            Some(value) => convert_u256_to_h160(*value),
+                            None => continue,
+                        }
+                    }
+                };
+                if account == EVMAddress::default() || state.has_caller(&account) {
+                    continue;
+                }
+                let (branches, contracts) = unlocked.entry(account).or_default();
+                *branches += uncovered;
+                contracts.insert(self.name(address));
+            }
+        }
+        unlocked
+            .into_iter()
+            .sorted_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)))
+            .map(|(account, (branches, contracts))| {
+                format!(
+                    "impersonate {:?} (add it to `sender` of --echidna-config) to unlock {} branches of {}",
+                    account,
+                    branches,
+                    contracts.iter().join(", ")
+                )
+            })
+            .collect()
+    }
+
+    fn stuck_functions(&self, state: &EVMFuzzState) -> Vec<String> {
+        let stats = match state.metadata_map().get::<FunctionStatsMetadata>() {
+            Some(stats) => stats,
+            None => return vec![],
+        };
+        stats
+            .stuck(SUMMARY_STUCK_MIN_CALLS)
+            .into_iter()
+            .map(|((address, function), calls)| {
+                let function_name = self
+                    .address_to_abi
+                    .get(&address)
+                    .and_then(|abis| abis.iter().find(|abi| abi.function == function))
+                    .map(|abi| format!("{}{}", abi.function_name, abi.abi))
+                    .unwrap_or(format!("0x{}", hex::encode(function)));
+                format!(
+                    "{}.{} reverted in all {} calls: check its preconditions (roles, pauses, deadlines, setup calls)",
+                    self.name(&address),
+                    function_name,
+                    calls
+                )
+            })
+            .collect()
+    }
+
+    fn unpriced_tokens(&self) -> Vec<String> {
+        self.erc20_oracle
+            .deref()
+            .borrow()
+            .known_tokens
+            .iter()
+            .filter(|(_, token_ctx)| token_ctx.swaps.is_empty())
+            .map(|(token, _)| *token)
+            .sorted()
+            .map(|token| {
+                format!(
+                    "provide a price for token {} (a pair with WETH, or constantPairs() in the setUp contract): gains in it are not counted",
+                    self.name(&token)
+                )
+            })
+            .collect()
+    }
+
+    /// The checklist, with at most [`SUMMARY_MAX_ITEMS`] suggestions of each
+    /// kind
+    pub fn checklist(&self, coverage: &Coverage, state: &EVMFuzzState) -> Vec<String> {
+        [
+            self.impersonations(coverage, state),
+            self.stuck_functions(state),
+            self.unpriced_tokens(),
+            self.detector_hints.clone(),
+        ]
+        .into_iter()
+        .flat_map(truncate)
+        .collect()
+    }
+
+    /// Write the checklist to the work dir
+    pub fn dump(&self, coverage: &Coverage, state: &EVMFuzzState) {
+        let items = self.checklist(coverage, state);
+        let text: String = if items.is_empty() {
+            "Nothing to suggest\n".to_string()
+        } else {
+            items.iter().map(|item| format!("- [ ] {}\n", item)).collect()
+        };
+        if let Err(e) = std::fs::write(format!("{}/{}", self.work_dir, SUMMARY_FILE), text) {
+            warn!("Failed to write the campaign summary: {}", e);
+        }
+    }
+}
+
+/// At most [`SUMMARY_MAX_ITEMS`] items, followed by the number of the others
+fn truncate(mut items: Vec<String>) -> Vec<String> {
+    if items.len() > SUMMARY_MAX_ITEMS {
+        let more = items.len() - SUMMARY_MAX_ITEMS;
+        items.truncate(SUMMARY_MAX_ITEMS);
+        items.push(format!("... and {} more", more));
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_stats() {
+        let (a, b) = (EVMAddress::from_slice(&[1; 20]), EVMAddress::from_slice(&[2; 20]));
+        let mut stats = FunctionStatsMetadata::default();
+        for _ in 0..10 {
+            stats.record(a, [1; 4], true);
+            stats.record(b, [2; 4], true);
+        }
+        stats.record(a, [1; 4], true);
+        stats.record(b, [2; 4], false);
+        assert_eq!(stats.stuck(10), vec![((a, [1; 4]), 11)]);
+        assert!(stats.stuck(12).is_empty());
+        assert_eq!(stats.calls(a, [1; 4]), 11);
+        assert_eq!(stats.average_calls(), 11.0);
+
+        let items = (0..SUMMARY_MAX_ITEMS + 2).map(|i| i.to_string()).collect_vec();
+        let truncated = truncate(items);
+        assert_eq!(truncated.len(), SUMMARY_MAX_ITEMS + 1);
+        assert_eq!(truncated.last().unwrap(), "... and 2 more");
+    }
+}
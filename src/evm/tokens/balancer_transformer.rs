@@ -0,0 +1,362 @@
+use std::fmt::Debug;
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::PairContext;
+use crate::{
+    evm::{
+        tokens::{
+            v2_transformer::{balance_of_bytes, UniswapPairContext},
+            v3_transformer::approve_bytes,
+        },
+        types::{EVMAddress, EVMFuzzState, EVMU256},
+        vm::{EVMExecutor, MEM_LIMIT},
+    },
+    generic_vm::vm_state::VMStateT,
+    get_code_tokens,
+    input::ConciseSerde,
+    is_call_success,
+};
+
+/// Balancer weights and fees are expressed with 18 decimals
+const ONE: f64 = 1e18;
+
+pub const BALANCER_TOKEN_HOLDER: [u8; 20] = [0xa3; 20];
+
+/// A Balancer V2 weighted pool used as one hop of a swap path.
+///
+/// Balancer pools do not hold tokens themselves, all of them live in the
+/// Vault (`inner.uniswap_info.router`), which is also where swaps are made
+/// through `swap(SingleSwap,FundManagement,uint256,uint256)`.
+/// The weighted math is used to quote the swap beforehand, so that hops
+/// without usable liquidity are skipped without executing them.
+#[derive(Debug)]
+pub struct BalancerPoolContext {
+    pub inner: UniswapPairContext,
+}
+
+pub fn get_pool_id_bytes() -> Bytes {
+    Bytes::from(vec![0x38, 0xff, 0xf2, 0xd0]) // getPoolId()
+}
+
+pub fn get_normalized_weights_bytes() -> Bytes {
+    Bytes::from(vec![0xf8, 0x9f, 0x27, 0xed]) // getNormalizedWeights()
+}
+
+pub fn get_swap_fee_bytes() -> Bytes {
+    Bytes::from(vec![0x55, 0xc6, 0x76, 0x28]) // getSwapFeePercentage()
+}
+
+pub fn get_pool_tokens_bytes(pool_id: &[u8; 32]) -> Bytes {
+    let mut ret = Vec::new();
+    ret.extend_from_slice(&[0xf9, 0x4d, 0x46, 0x68]); // getPoolTokens(bytes32)
+    ret.extend_from_slice(pool_id);
+    Bytes::from(ret)
+}
+
+fn address_word(addr: &EVMAddress) -> [u8; 32] {
+    let mut ret = [0u8; 32];
+    ret[12..].copy_from_slice(&addr.0);
+    ret
+}
+
+/// Calldata of a GIVEN_IN single swap, paid by `sender` and sent to
+/// `recipient`, without any limit.
+pub fn swap_bytes(
+    pool_id: &[u8; 32],
+    asset_in: &EVMAddress,
+    asset_out: &EVMAddress,
+    amount: EVMU256,
+    sender: &EVMAddress,
+    recipient: &EVMAddress,
+) -> Bytes {
+    let mut ret = Vec::new();
+    ret.extend_from_slice(&[0x52, 0xbb, 0xbe, 0x29]); // swap
+    ret.extend_from_slice(&EVMU256::from(0xe0).to_be_bytes::<32>()); // offset of singleSwap
+
+    // funds
+    ret.extend_from_slice(&address_word(sender)); // sender
+    ret.extend_from_slice(&[0x00; 32]); // fromInternalBalance
+    ret.extend_from_slice(&address_word(recipient)); // recipient
+    ret.extend_from_slice(&[0x00; 32]); // toInternalBalance
+    ret.extend_from_slice(&[0x00; 32]); // limit
+    ret.extend_from_slice(&[0xff; 32]); // deadline
+
+    // singleSwap
+    ret.extend_from_slice(pool_id); // poolId
+    ret.extend_from_slice(&[0x00; 32]); // kind = GIVEN_IN
+    ret.extend_from_slice(&address_word(asset_in)); // assetIn
+    ret.extend_from_slice(&address_word(asset_out)); // assetOut
+    ret.extend_from_slice(&amount.to_be_bytes::<32>()); // amount
+    ret.extend_from_slice(&EVMU256::from(0xc0).to_be_bytes::<32>()); // offset of userData
+    ret.extend_from_slice(&[0x00; 32]); // userData length
+    Bytes::from(ret)
+}
+
+fn read_word(data: &[u8], offset: usize) -> Option<EVMU256> {
+    EVMU256::try_from_be_slice(data.get(offset..offset + 32)?)
+}
+
+/// Decodes the dynamic array whose offset is stored in the `idx`-th head word
+fn read_array(data: &[u8], idx: usize) -> Option<Vec<EVMU256>> {
+    let offset: usize = read_word(data, idx * 32)?.try_into().ok()?;
+    let len: usize = read_word(data, offset)?.try_into().ok()?;
+    (0..len).map(|i| read_word(data, offset + 32 * (i + 1))).collect()
+}
+
+/// Decodes the `(address[] tokens, uint256[] balances, uint256)` returned
+/// by `getPoolTokens`
+pub fn decode_pool_tokens(data: &[u8]) -> Option<(Vec<EVMAddress>, Vec<EVMU256>)> {
+    let tokens = read_array(data, 0)?
+        .iter()
+        .map(|t| EVMAddress::from_slice(&t.to_be_bytes::<32>()[12..]))
+        .collect::<Vec<_>>();
+    let balances = read_array(data, 1)?;
+    if tokens.len() != balances.len() {
+        return None;
+    }
+    Some((tokens, balances))
+}
+
+/// Decodes the `uint256[]` returned by `getNormalizedWeights`
+pub fn decode_weights(data: &[u8]) -> Option<Vec<EVMU256>> {
+    read_array(data, 0)
+}
+
+fn u256_to_f64(v: EVMU256) -> f64 {
+    v.as_limbs()
+        .iter()
+        .enumerate()
+        .map(|(idx, limb)| *limb as f64 * 2f64.powi(64 * idx as i32))
+        .sum()
+}
+
+/// Quotes the amount of token out received for `amount_in` of token in,
+/// after fees, following the weighted invariant:
+/// `out = b_out * (1 - (b_in / (b_in + in * (1 - fee))) ^ (w_in / w_out))`
+///
+/// Only used to filter out hops without liquidity, so the precision of f64
+/// is enough.
+pub fn get_amount_out(
+    balance_in: EVMU256,
+    weight_in: EVMU256,
+    balance_out: EVMU256,
+    weight_out: EVMU256,
+    amount_in: EVMU256,
+    fee: EVMU256,
+) -> EVMU256 {
+    let balance_in = u256_to_f64(balance_in);
+    let balance_out = u256_to_f64(balance_out);
+    let weight_in = u256_to_f64(weight_in);
+    let weight_out = u256_to_f64(weight_out);
+    if balance_in == 0.0 || balance_out == 0.0 || weight_out == 0.0 {
+        return EVMU256::ZERO;
+    }
+
+    let amount_in = u256_to_f64(amount_in) * (1.0 - u256_to_f64(fee) / ONE);
+    let base = balance_in / (balance_in + amount_in);
+    let out = balance_out * (1.0 - base.powf(weight_in / weight_out));
+    if !out.is_finite() || out < 1.0 {
+        return EVMU256::ZERO;
+    }
+    EVMU256::from(out.min(u128::MAX as f64) as u128)
+}
+
+impl BalancerPoolContext {
+    pub fn initial_transfer<VS, CI, SC>(
+        &self,
+        src: &EVMAddress,
+        next: &EVMAddress,
+        amount: EVMU256,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+    ) -> Option<()>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        self.inner.initial_transfer(src, next, amount, state, vm)
+    }
+}
+
+impl PairContext for BalancerPoolContext {
+    fn transform<VS, CI, SC>(
+        &self,
+        _src: &EVMAddress,
+        next: &EVMAddress,
+        amount: EVMU256,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+        reverse: bool,
+    ) -> Option<(EVMAddress, EVMU256)>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        let src = EVMAddress::from_slice(&BALANCER_TOKEN_HOLDER);
+        let pool = self.inner.pair_address;
+        let vault = self.inner.uniswap_info.router?;
+        let (in_token_address, out_token_address) = if reverse {
+            (self.inner.next_hop, self.inner.in_token_address)
+        } else {
+            (self.inner.in_token_address, self.inner.next_hop)
+        };
+
+        let in_token_code = get_code_tokens!(in_token_address, vm, state)?;
+        let out_token_code = get_code_tokens!(out_token_address, vm, state)?;
+        let pool_code = get_code_tokens!(pool, vm, state)?;
+        let vault_code = get_code_tokens!(vault, vm, state)?;
+
+        macro_rules! call_contract {
+            ($addr: expr, $code: expr, $caller: expr, $input: expr) => {{
+                let call = Contract::new_with_context_analyzed(
+                    $input,
+                    $code,
+                    &CallContext {
+                        address: $addr,
+                        caller: $caller,
+                        code_address: $addr,
+                        apparent_value: EVMU256::ZERO,
+                        scheme: CallScheme::Call,
+                    },
+                );
+                let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+                let ir = vm.host.run_inspect(&mut interp, state);
+                if !is_call_success!(ir) {
+                    return None;
+                }
+                interp.return_value().to_vec()
+            }};
+        }
+
+        macro_rules! read_u256 {
+            ($addr: expr, $code: expr, $input: expr) => {{
+                let ret = call_contract!($addr, $code, EVMAddress::default(), $input);
+                read_word(ret.as_slice(), 0)?
+            }};
+        }
+
+        // 1. quote the swap with the weighted math
+        let pool_id = read_u256!(pool, pool_code.clone(), get_pool_id_bytes()).to_be_bytes::<32>();
+        let pool_tokens = call_contract!(
+            vault,
+            vault_code.clone(),
+            EVMAddress::default(),
+            get_pool_tokens_bytes(&pool_id)
+        );
+        let (tokens, balances) = decode_pool_tokens(&pool_tokens)?;
+        let weights = decode_weights(&call_contract!(
+            pool,
+            pool_code.clone(),
+            EVMAddress::default(),
+            get_normalized_weights_bytes()
+        ))?;
+        let fee = read_u256!(pool, pool_code.clone(), get_swap_fee_bytes());
+
+        let idx_in = tokens.iter().position(|t| *t == in_token_address)?;
+        let idx_out = tokens.iter().position(|t| *t == out_token_address)?;
+        let quoted = get_amount_out(
+            balances[idx_in],
+            *weights.get(idx_in)?,
+            balances[idx_out],
+            *weights.get(idx_out)?,
+            amount,
+            fee,
+        );
+        if quoted == EVMU256::ZERO {
+            return None;
+        }
+
+        // 2. approve the vault and swap, the vault sends the output to the next hop
+        call_contract!(in_token_address, in_token_code.clone(), src, approve_bytes(&vault));
+        let orig_balance = read_u256!(out_token_address, out_token_code.clone(), balance_of_bytes(next));
+        call_contract!(
+            vault,
+            vault_code.clone(),
+            src,
+            swap_bytes(&pool_id, &in_token_address, &out_token_address, amount, &src, next)
+        );
+        let new_balance = read_u256!(out_token_address, out_token_code.clone(), balance_of_bytes(next));
+        let amount_out = new_balance.checked_sub(orig_balance)?;
+
+        vm.host
+            .evmstate
+            .flashloan_data
+            .oracle_recheck_balance
+            .insert(in_token_address);
+        vm.host
+            .evmstate
+            .flashloan_data
+            .oracle_recheck_balance
+            .insert(out_token_address);
+        Some((*next, amount_out))
+    }
+
+    fn name(&self) -> String {
+        "balancer".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e18(v: u64) -> EVMU256 {
+        EVMU256::from(v) * EVMU256::from(10).pow(EVMU256::from(18))
+    }
+
+    #[test]
+    fn test_get_amount_out_equal_weights() {
+        // 50/50 pool without fee behaves like x * y = k
+        let out = get_amount_out(
+            e18(1000),
+            e18(1) / EVMU256::from(2),
+            e18(1000),
+            e18(1) / EVMU256::from(2),
+            e18(10),
+            EVMU256::ZERO,
+        );
+        let expected = u256_to_f64(e18(1000)) * 10.0 / 1010.0;
+        assert!((u256_to_f64(out) - expected).abs() / expected < 1e-9);
+    }
+
+    #[test]
+    fn test_get_amount_out_fee_and_empty() {
+        let w = e18(1) / EVMU256::from(2);
+        let no_fee = get_amount_out(e18(1000), w, e18(1000), w, e18(10), EVMU256::ZERO);
+        // 1%
+        let with_fee = get_amount_out(e18(1000), w, e18(1000), w, e18(10), e18(1) / EVMU256::from(100));
+        assert!(with_fee < no_fee);
+        assert_eq!(
+            get_amount_out(EVMU256::ZERO, w, e18(1000), w, e18(10), EVMU256::ZERO),
+            EVMU256::ZERO
+        );
+    }
+
+    #[test]
+    fn test_decode_pool_tokens() {
+        let mut data = vec![];
+        data.extend_from_slice(&EVMU256::from(0x60).to_be_bytes::<32>());
+        data.extend_from_slice(&EVMU256::from(0xc0).to_be_bytes::<32>());
+        data.extend_from_slice(&EVMU256::from(1234).to_be_bytes::<32>());
+        data.extend_from_slice(&EVMU256::from(2).to_be_bytes::<32>());
+        data.extend_from_slice(&address_word(&EVMAddress::from_slice(&[0x11; 20])));
+        data.extend_from_slice(&address_word(&EVMAddress::from_slice(&[0x22; 20])));
+        data.extend_from_slice(&EVMU256::from(2).to_be_bytes::<32>());
+        data.extend_from_slice(&EVMU256::from(100).to_be_bytes::<32>());
+        data.extend_from_slice(&EVMU256::from(200).to_be_bytes::<32>());
+
+        let (tokens, balances) = decode_pool_tokens(&data).unwrap();
+        assert_eq!(
+            tokens,
+            vec![EVMAddress::from_slice(&[0x11; 20]), EVMAddress::from_slice(&[0x22; 20])]
+        );
+        assert_eq!(balances, vec![EVMU256::from(100), EVMU256::from(200)]);
+        assert!(decode_pool_tokens(&data[..0x80]).is_none());
+    }
+}
@@ -0,0 +1,88 @@
+//! Code of the contracts the swap paths go through, analyzed once and shared
+//! by code hash: the pairs of a factory or the clones of a token hold a single
+//! copy. The code of an address missing from the cache is fetched from the
+//! chain given with [`set_code_endpoint`].
+
+use std::{cell::RefCell, collections::HashMap, sync::Arc};
+
+use revm_primitives::Bytecode;
+use tracing::warn;
+
+use crate::evm::{
+    onchain::{keccak256, ChainConfig},
+    types::{EVMAddress, EVMU256},
+};
+
+#[derive(Default)]
+pub struct CodeCache {
+    /// Code by its hash
+    codes: HashMap<EVMU256, Arc<Bytecode>>,
+    address_to_hash: HashMap<EVMAddress, EVMU256>,
+    /// Chain the code of the addresses missing from the cache is fetched from
+    endpoint: Option<Box<dyn ChainConfig>>,
+}
+
+impl CodeCache {
+    /// Cache the code of the address, returns the code shared with the other
+    /// addresses having the same code
+    pub fn insert(&mut self, address: EVMAddress, code: Bytecode) -> Arc<Bytecode> {
+        let hash = keccak256(code.bytes());
+        self.address_to_hash.insert(address, hash);
+        self.codes.entry(hash).or_insert_with(|| Arc::new(code)).clone()
+    }
+
+    /// Code of the address, fetched from the chain if missing from the cache.
+    /// None if the address has no code
+    pub fn get(&mut self, address: EVMAddress) -> Option<Arc<Bytecode>> {
+        if let Some(hash) = self.address_to_hash.get(&address) {
+            return self.codes.get(hash).cloned();
+        }
+        let code = self.endpoint.as_mut()?.get_contract_code_analyzed(address, false);
+        if code.is_empty() {
+            warn!("no code for {:?}", address);
+            return None;
+        }
+        Some(self.insert(address, code))
+    }
+}
+
+// thread local as the chain configs are not `Send`, the swaps are executed by
+// the fuzzing thread only
+thread_local! {
+    static CODE_CACHE: RefCell<CodeCache> = RefCell::new(CodeCache::default());
+}
+
+/// Set the chain the code of the addresses missing from the cache is fetched
+/// from
+pub fn set_code_endpoint(endpoint: Box<dyn ChainConfig>) {
+    CODE_CACHE.with(|cache| cache.borrow_mut().endpoint = Some(endpoint));
+}
+
+pub fn register_code(address: EVMAddress, code: Bytecode) -> Arc<Bytecode> {
+    CODE_CACHE.with(|cache| cache.borrow_mut().insert(address, code))
+}
+
+pub fn get_code(address: EVMAddress) -> Option<Arc<Bytecode>> {
+    CODE_CACHE.with(|cache| cache.borrow_mut().get(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn test_code_cache() {
+        let mut cache = CodeCache::default();
+        let code = Bytecode::new_raw(Bytes::from(vec![0x60, 0x00, 0x60, 0x00, 0xf3]));
+        let a = EVMAddress::from_slice(&[0x11; 20]);
+        let b = EVMAddress::from_slice(&[0x22; 20]);
+        let shared = cache.insert(a, code.clone());
+        assert!(Arc::ptr_eq(&shared, &cache.insert(b, code)));
+        assert!(Arc::ptr_eq(&shared, &cache.get(b).unwrap()));
+        assert_eq!(cache.codes.len(), 1);
+        // not cached and no chain to fetch it from
+        assert!(cache.get(EVMAddress::zero()).is_none());
+    }
+}
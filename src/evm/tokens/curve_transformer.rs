@@ -0,0 +1,434 @@
+use std::fmt::Debug;
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::PairContext;
+use crate::{
+    evm::{
+        tokens::{
+            v2_transformer::{balance_of_bytes, transfer_bytes, UniswapPairContext},
+            v3_transformer::approve_bytes,
+        },
+        types::{EVMAddress, EVMFuzzState, EVMU256},
+        vm::{EVMExecutor, MEM_LIMIT},
+    },
+    generic_vm::vm_state::VMStateT,
+    get_code_tokens,
+    input::ConciseSerde,
+    is_call_success,
+};
+
+/// Curve fees are expressed with 10 decimals
+const FEE_DENOMINATOR: u64 = 10_000_000_000;
+/// Maximum number of Newton iterations, same as the Vyper implementation
+const MAX_ITERATIONS: usize = 255;
+/// Decimals the balances are scaled to before applying the invariant
+const PRECISION_DECIMALS: u8 = 18;
+
+pub const CURVE_TOKEN_HOLDER: [u8; 20] = [0xa2; 20];
+
+/// A StableSwap (Curve 3pool-like) pool used as one hop of a swap path.
+///
+/// The pool is driven through its own `exchange(int128,int128,uint256,uint256)`
+/// entrypoint so that its internal balance accounting stays consistent.
+/// The StableSwap invariant is used to quote the swap beforehand, so that
+/// hops without usable liquidity are skipped without executing them.
+#[derive(Debug)]
+pub struct CurvePoolContext {
+    /// index of `inner.in_token_address` in the pool's coins
+    pub coin_in: u8,
+    /// index of `inner.next_hop` in the pool's coins
+    pub coin_out: u8,
+    pub n_coins: u8,
+    /// Multiplier of the balance of each coin scaling it to 18 decimals, the
+    /// `PRECISION_MUL` of the pool
+    pub precision_mul: Vec<EVMU256>,
+    pub inner: UniswapPairContext,
+}
+
+/// `PRECISION_MUL` of a coin with `decimals`
+pub fn precision_mul(decimals: u8) -> EVMU256 {
+    EVMU256::from(10).pow(EVMU256::from(PRECISION_DECIMALS.saturating_sub(decimals)))
+}
+
+pub fn balances_bytes(idx: u8) -> Bytes {
+    let mut ret = Vec::new();
+    ret.extend_from_slice(&[0x49, 0x03, 0xb0, 0xd1]); // balances(uint256)
+    ret.extend_from_slice(&EVMU256::from(idx).to_be_bytes::<32>());
+    Bytes::from(ret)
+}
+
+pub fn amp_bytes() -> Bytes {
+    Bytes::from(vec![0xf4, 0x46, 0xc1, 0xd0]) // A()
+}
+
+pub fn fee_bytes() -> Bytes {
+    Bytes::from(vec![0xdd, 0xca, 0x3f, 0x43]) // fee()
+}
+
+pub fn exchange_bytes(i: u8, j: u8, dx: EVMU256) -> Bytes {
+    let mut ret = Vec::new();
+    ret.extend_from_slice(&[0x3d, 0xf0, 0x21, 0x24]); // exchange(int128,int128,uint256,uint256)
+    ret.extend_from_slice(&EVMU256::from(i).to_be_bytes::<32>()); // i
+    ret.extend_from_slice(&EVMU256::from(j).to_be_bytes::<32>()); // j
+    ret.extend_from_slice(&dx.to_be_bytes::<32>()); // dx
+    ret.extend_from_slice(&[0x00; 32]); // min_dy
+    Bytes::from(ret)
+}
+
+fn abs_diff(a: EVMU256, b: EVMU256) -> EVMU256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+/// Computes the StableSwap invariant D for the given balances, scaled to 18
+/// decimals.
+pub fn get_d(xp: &[EVMU256], amp: EVMU256) -> Option<EVMU256> {
+    let n = EVMU256::from(xp.len());
+    let s = xp.iter().try_fold(EVMU256::ZERO, |acc, x| acc.checked_add(*x))?;
+    if s == EVMU256::ZERO {
+        return Some(EVMU256::ZERO);
+    }
+
+    let ann = amp.checked_mul(n)?;
+    let mut d = s;
+    for _ in 0..MAX_ITERATIONS {
+        let mut d_p = d;
+        for x in xp {
+            d_p = d_p.checked_mul(d)?.checked_div(x.checked_mul(n)?)?;
+        }
+        let d_prev = d;
+        let numerator = ann.checked_mul(s)?.checked_add(d_p.checked_mul(n)?)?.checked_mul(d)?;
+        let denominator = ann
+            .checked_sub(EVMU256::from(1))?
+            .checked_mul(d)?
+            .checked_add((n + EVMU256::from(1)).checked_mul(d_p)?)?;
+        d = numerator.checked_div(denominator)?;
+        if abs_diff(d, d_prev) <= EVMU256::from(1) {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Computes the new balance of coin `j` when the balance of coin `i` is set to
+/// `x` while keeping the invariant unchanged, all scaled to 18 decimals.
+pub fn get_y(i: usize, j: usize, x: EVMU256, xp: &[EVMU256], amp: EVMU256) -> Option<EVMU256> {
+    if i == j || i >= xp.len() || j >= xp.len() {
+        return None;
+    }
+    let n = EVMU256::from(xp.len());
+    let d = get_d(xp, amp)?;
+    let ann = amp.checked_mul(n)?;
+
+    let mut c = d;
+    let mut s = EVMU256::ZERO;
+    for (k, balance) in xp.iter().enumerate() {
+        let x_k = if k == i {
+            x
+        } else if k != j {
+            *balance
+        } else {
+            continue;
+        };
+        s = s.checked_add(x_k)?;
+        c = c.checked_mul(d)?.checked_div(x_k.checked_mul(n)?)?;
+    }
+    c = c.checked_mul(d)?.checked_div(ann.checked_mul(n)?)?;
+    let b = s.checked_add(d.checked_div(ann)?)?;
+
+    let mut y = d;
+    for _ in 0..MAX_ITERATIONS {
+        let y_prev = y;
+        let numerator = y.checked_mul(y)?.checked_add(c)?;
+        let denominator = y.checked_mul(EVMU256::from(2))?.checked_add(b)?.checked_sub(d)?;
+        y = numerator.checked_div(denominator)?;
+        if abs_diff(y, y_prev) <= EVMU256::from(1) {
+            return Some(y);
+        }
+    }
+    None
+}
+
+/// Quotes the amount of coin `j` received for `dx` of coin `i`, after fees,
+/// the pool holding `balances` of coins whose `PRECISION_MUL` is
+/// `precision_mul`. Amounts are in the decimals of their coin.
+pub fn get_dy(
+    i: usize,
+    j: usize,
+    dx: EVMU256,
+    balances: &[EVMU256],
+    precision_mul: &[EVMU256],
+    amp: EVMU256,
+    fee: EVMU256,
+) -> EVMU256 {
+    let quote = || {
+        if precision_mul.len() != balances.len() {
+            return None;
+        }
+        let xp = balances
+            .iter()
+            .zip(precision_mul)
+            .map(|(balance, mul)| balance.checked_mul(*mul))
+            .collect::<Option<Vec<_>>>()?;
+        let x = xp.get(i)?.checked_add(dx.checked_mul(precision_mul[i])?)?;
+        let y = get_y(i, j, x, &xp, amp)?;
+        let dy = xp[j].saturating_sub(y).saturating_sub(EVMU256::from(1)) / precision_mul[j];
+        let dy_fee = dy.checked_mul(fee)? / EVMU256::from(FEE_DENOMINATOR);
+        Some(dy - dy_fee)
+    };
+    quote().unwrap_or(EVMU256::ZERO)
+}
+
+impl CurvePoolContext {
+    pub fn initial_transfer<VS, CI, SC>(
+        &self,
+        src: &EVMAddress,
+        next: &EVMAddress,
+        amount: EVMU256,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+    ) -> Option<()>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        self.inner.initial_transfer(src, next, amount, state, vm)
+    }
+}
+
+impl PairContext for CurvePoolContext {
+    fn transform<VS, CI, SC>(
+        &self,
+        _src: &EVMAddress,
+        next: &EVMAddress,
+        amount: EVMU256,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+        reverse: bool,
+    ) -> Option<(EVMAddress, EVMU256)>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        let src = EVMAddress::from_slice(&CURVE_TOKEN_HOLDER);
+        let pool = self.inner.pair_address;
+        let (in_token_address, out_token_address, coin_in, coin_out) = if reverse {
+            (
+                self.inner.next_hop,
+                self.inner.in_token_address,
+                self.coin_out,
+                self.coin_in,
+            )
+        } else {
+            (
+                self.inner.in_token_address,
+                self.inner.next_hop,
+                self.coin_in,
+                self.coin_out,
+            )
+        };
+
+        let in_token_code = get_code_tokens!(in_token_address, vm, state)?;
+        let out_token_code = get_code_tokens!(out_token_address, vm, state)?;
+        let pool_code = get_code_tokens!(pool, vm, state)?;
+
+        macro_rules! call_contract {
+            ($addr: expr, $code: expr, $caller: expr, $input: expr) => {{
+                let call = Contract::new_with_context_analyzed(
+                    $input,
+                    $code,
+                    &CallContext {
+                        address: $addr,
+                        caller: $caller,
+                        code_address: $addr,
+                        apparent_value: EVMU256::ZERO,
+                        scheme: CallScheme::Call,
+                    },
+                );
+                let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+                let ir = vm.host.run_inspect(&mut interp, state);
+                if !is_call_success!(ir) {
+                    return None;
+                }
+                interp.return_value().to_vec()
+            }};
+        }
+
+        macro_rules! read_u256 {
+            ($addr: expr, $code: expr, $input: expr) => {{
+                let ret = call_contract!($addr, $code, EVMAddress::default(), $input);
+                match EVMU256::try_from_be_slice(ret.as_slice()) {
+                    Some(num) => num,
+                    None => return None,
+                }
+            }};
+        }
+
+        // 1. quote the swap with the StableSwap invariant
+        let balances = (0..self.n_coins)
+            .map(|idx| Some(read_u256!(pool, pool_code.clone(), balances_bytes(idx))))
+            .collect::<Option<Vec<_>>>()?;
+        let amp = read_u256!(pool, pool_code.clone(), amp_bytes());
+        let fee = read_u256!(pool, pool_code.clone(), fee_bytes());
+        let quoted = get_dy(
+            coin_in as usize,
+            coin_out as usize,
+            amount,
+            &balances,
+            &self.precision_mul,
+            amp,
+            fee,
+        );
+        if quoted == EVMU256::ZERO {
+            return None;
+        }
+
+        // 2. approve the pool and exchange
+        call_contract!(in_token_address, in_token_code.clone(), src, approve_bytes(&pool));
+        let orig_balance = read_u256!(out_token_address, out_token_code.clone(), balance_of_bytes(&src));
+        call_contract!(pool, pool_code.clone(), src, exchange_bytes(coin_in, coin_out, amount));
+        let new_balance = read_u256!(out_token_address, out_token_code.clone(), balance_of_bytes(&src));
+        let amount_out = new_balance.checked_sub(orig_balance)?;
+
+        // 3. pools send the output to msg.sender, forward it to the next hop
+        if *next != src {
+            call_contract!(
+                out_token_address,
+                out_token_code.clone(),
+                src,
+                transfer_bytes(next, amount_out)
+            );
+        }
+
+        vm.host
+            .evmstate
+            .flashloan_data
+            .oracle_recheck_balance
+            .insert(in_token_address);
+        vm.host
+            .evmstate
+            .flashloan_data
+            .oracle_recheck_balance
+            .insert(out_token_address);
+        Some((*next, amount_out))
+    }
+
+    fn name(&self) -> String {
+        "curve".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e18(v: u64) -> EVMU256 {
+        EVMU256::from(v) * EVMU256::from(10).pow(EVMU256::from(18))
+    }
+
+    #[test]
+    fn test_get_d_balanced() {
+        // for a balanced pool D is the sum of the balances
+        let xp = vec![e18(1_000_000), e18(1_000_000), e18(1_000_000)];
+        let d = get_d(&xp, EVMU256::from(2000)).unwrap();
+        assert!(abs_diff(d, e18(3_000_000)) <= EVMU256::from(3));
+    }
+
+    #[test]
+    fn test_get_dy_close_to_peg() {
+        let xp = vec![e18(1_000_000), e18(1_000_000), e18(1_000_000)];
+        let dy = get_dy(
+            0,
+            1,
+            e18(1000),
+            &xp,
+            &[EVMU256::from(1); 3],
+            EVMU256::from(2000),
+            EVMU256::ZERO,
+        );
+        // a small swap in a deep stable pool is almost 1:1
+        assert!(dy < e18(1000));
+        assert!(dy > e18(999));
+    }
+
+    #[test]
+    fn test_get_dy_fee() {
+        let xp = vec![e18(1_000_000), e18(1_000_000)];
+        let precision_mul = [EVMU256::from(1); 2];
+        let no_fee = get_dy(0, 1, e18(1000), &xp, &precision_mul, EVMU256::from(100), EVMU256::ZERO);
+        // 0.04%
+        let with_fee = get_dy(
+            0,
+            1,
+            e18(1000),
+            &xp,
+            &precision_mul,
+            EVMU256::from(100),
+            EVMU256::from(4_000_000),
+        );
+        assert!(with_fee < no_fee);
+    }
+
+    #[test]
+    fn test_get_dy_empty_pool() {
+        let xp = vec![EVMU256::ZERO, EVMU256::ZERO];
+        assert_eq!(
+            get_dy(
+                0,
+                1,
+                e18(1),
+                &xp,
+                &[EVMU256::from(1); 2],
+                EVMU256::from(100),
+                EVMU256::ZERO
+            ),
+            EVMU256::ZERO
+        );
+    }
+
+    #[test]
+    fn test_get_dy_mixed_decimals() {
+        // USDC (6 decimals) / DAI (18 decimals) pool holding 1M of each
+        let usdc = |v: u64| EVMU256::from(v) * EVMU256::from(1_000_000);
+        let balances = vec![usdc(1_000_000), e18(1_000_000)];
+        let precision_mul = vec![precision_mul(6), precision_mul(18)];
+        assert_eq!(
+            precision_mul,
+            vec![EVMU256::from(1_000_000_000_000u64), EVMU256::from(1)]
+        );
+
+        // 1000 USDC buy almost 1000 DAI
+        let dai = get_dy(
+            0,
+            1,
+            usdc(1000),
+            &balances,
+            &precision_mul,
+            EVMU256::from(2000),
+            EVMU256::ZERO,
+        );
+        assert!(dai < e18(1000));
+        assert!(dai > e18(999));
+        // and the other way around
+        let usdc_out = get_dy(
+            1,
+            0,
+            e18(1000),
+            &balances,
+            &precision_mul,
+            EVMU256::from(2000),
+            EVMU256::ZERO,
+        );
+        assert!(usdc_out < usdc(1000));
+        assert!(usdc_out > usdc(999));
+    }
+}
@@ -12,6 +12,7 @@ use alloy_primitives::hex;
 use libafl::schedulers::Scheduler;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+use self::swap_path::{decode_swap_path, is_swap_selector};
 use super::{
     types::{checksum, EVMFuzzState},
     vm::EVMExecutor,
@@ -19,8 +20,12 @@ use super::{
 use crate::{
     evm::{
         abi::{AArray, BoxedABI},
-        onchain::endpoints::Chain,
-        tokens::v3_transformer::V3_TOKEN_HOLDER,
+        onchain::{chains::get_custom_dex, endpoints::Chain},
+        tokens::{
+            balancer_transformer::BALANCER_TOKEN_HOLDER,
+            curve_transformer::CURVE_TOKEN_HOLDER,
+            v3_transformer::V3_TOKEN_HOLDER,
+        },
         types::{EVMAddress, EVMU256},
     },
     generic_vm::{
@@ -31,7 +36,13 @@ use crate::{
     state::HasCaller,
 };
 
+pub mod balancer_transformer;
+pub mod code_cache;
 pub mod constant_pair;
+pub mod curve_transformer;
+pub mod nft;
+pub mod numeraire;
+pub mod swap_path;
 pub mod uniswap;
 pub mod v2_transformer;
 pub mod v3_transformer;
@@ -57,21 +68,16 @@ pub enum UniswapProvider {
 }
 
 #[macro_export]
+/// Code of the address deployed in the host, taken from the code cache (and
+/// deployed) if missing. None if the address has no code
 macro_rules! get_code_tokens {
     ($addr: expr, $vm: expr, $state: expr) => {
         match $vm.host.code.get(&$addr) {
-            Some(code) => code.clone(),
-            None => {
-                let code = CODE_REGISTRY
-                    .lock()
-                    .unwrap()
-                    .get(&$addr)
-                    .cloned()
-                    .expect(format!("Internal Error: token {:?} code not found in registry.", $addr).as_str());
-                // println!("inserting: {:?}", $addr);
-                $vm.host.set_code($addr, code.clone(), $state);
-                $vm.host.code.get(&$addr).unwrap().clone()
-            }
+            Some(code) => Some(code.clone()),
+            None => $crate::evm::tokens::code_cache::get_code($addr).map(|code| {
+                $vm.host.set_code($addr, (*code).clone(), $state);
+                $vm.host.code[&$addr].clone()
+            }),
         }
     };
 }
@@ -122,6 +128,8 @@ enum PairContextTy {
     Uniswap(Rc<RefCell<v2_transformer::UniswapPairContext>>),
     UniswapV3(Rc<RefCell<v3_transformer::UniswapV3PairContext>>),
     Weth(Rc<RefCell<weth_transformer::WethContext>>),
+    Curve(Rc<RefCell<curve_transformer::CurvePoolContext>>),
+    Balancer(Rc<RefCell<balancer_transformer::BalancerPoolContext>>),
 }
 
 impl Debug for PairContextTy {
@@ -130,6 +138,8 @@ impl Debug for PairContextTy {
             PairContextTy::Uniswap(ctx) => write!(f, "Uniswap({:?})", ctx.borrow()),
             PairContextTy::Weth(ctx) => write!(f, "Weth({:?})", ctx.borrow()),
             PairContextTy::UniswapV3(ctx) => write!(f, "UniswapV3({:?})", ctx.borrow()),
+            PairContextTy::Curve(ctx) => write!(f, "Curve({:?})", ctx.borrow()),
+            PairContextTy::Balancer(ctx) => write!(f, "Balancer({:?})", ctx.borrow()),
         }
     }
 }
@@ -149,6 +159,8 @@ pub struct TokenContext {
 static mut WETH_MAX: EVMU256 = EVMU256::ZERO;
 
 impl TokenContext {
+    /// Buy the token with `amount_in` ETH for `to` along one of the swap
+    /// paths. The state is only updated if every hop of the path succeeds
     pub fn buy<VS, CI, SC>(
         &self,
         amount_in: EVMU256,
@@ -157,6 +169,52 @@ impl TokenContext {
         vm: &mut EVMExecutor<VS, CI, SC>,
         seed: &[u8],
     ) -> Option<()>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        let snapshot = vm.host.snapshot();
+        let res = self.buy_path(amount_in, to, state, vm, seed);
+        if res.is_none() {
+            vm.host.revert_to(snapshot);
+        }
+        vm.host.discard_snapshot(snapshot);
+        res
+    }
+
+    /// Sell `amount_in` of the token of `src` for ETH along one of the swap
+    /// paths. The state is only updated if every hop of the path succeeds
+    pub fn sell<VS, CI, SC>(
+        &self,
+        amount_in: EVMU256,
+        src: EVMAddress,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+        seed: &[u8],
+    ) -> Option<()>
+    where
+        VS: VMStateT + Default + 'static,
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        let snapshot = vm.host.snapshot();
+        let res = self.sell_path(amount_in, src, state, vm, seed);
+        if res.is_none() {
+            vm.host.revert_to(snapshot);
+        }
+        vm.host.discard_snapshot(snapshot);
+        res
+    }
+
+    fn buy_path<VS, CI, SC>(
+        &self,
+        amount_in: EVMU256,
+        to: EVMAddress,
+        state: &mut EVMFuzzState,
+        vm: &mut EVMExecutor<VS, CI, SC>,
+        seed: &[u8],
+    ) -> Option<()>
     where
         VS: VMStateT + Default + 'static,
         CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
@@ -165,7 +223,9 @@ impl TokenContext {
         if self.is_weth {
             let ctx = &self.swaps[0].route[0];
             if let PairContextTy::Weth(ctx) = ctx {
-                ctx.deref().borrow_mut().transform(&to, &to, amount_in, state, vm, true);
+                ctx.deref()
+                    .borrow_mut()
+                    .transform(&to, &to, amount_in, state, vm, true)?;
             } else {
                 panic!("Invalid weth context");
             }
@@ -186,6 +246,8 @@ impl TokenContext {
                     match &path_ctx.route[path_len - nth - 2] {
                         PairContextTy::Uniswap(ctx) => ctx.borrow().pair_address,
                         PairContextTy::UniswapV3(_) => EVMAddress::from_slice(&V3_TOKEN_HOLDER),
+                        PairContextTy::Curve(_) => EVMAddress::from_slice(&CURVE_TOKEN_HOLDER),
+                        PairContextTy::Balancer(_) => EVMAddress::from_slice(&BALANCER_TOKEN_HOLDER),
                         PairContextTy::Weth(_ctx) => panic!("Invalid weth context"),
                     }
                 };
@@ -255,6 +317,70 @@ impl TokenContext {
                             return None;
                         }
                     }
+                    PairContextTy::Curve(ctx) => {
+                        #[cfg(test)]
+                        {
+                            println!("======== Curve ========");
+                            println!("pool = {:?}", ctx.borrow().inner.pair_address);
+                            println!(
+                                "{:?} => {:?} ({}/{:?})",
+                                current_sender, next, current_amount_in, current_amount_in
+                            );
+                        }
+                        if let Some((receiver, amount)) = ctx.deref().borrow_mut().transform(
+                            &current_sender.unwrap(),
+                            &next,
+                            current_amount_in,
+                            state,
+                            vm,
+                            true,
+                        ) {
+                            #[cfg(test)]
+                            {
+                                println!("Hop out = {}/{:?}", amount, amount);
+                            }
+                            current_amount_in = amount;
+                            current_sender = Some(receiver);
+                        } else {
+                            #[cfg(test)]
+                            {
+                                println!("!!! Curve Failed !!!");
+                            }
+                            return None;
+                        }
+                    }
+                    PairContextTy::Balancer(ctx) => {
+                        #[cfg(test)]
+                        {
+                            println!("======== Balancer ========");
+                            println!("pool = {:?}", ctx.borrow().inner.pair_address);
+                            println!(
+                                "{:?} => {:?} ({}/{:?})",
+                                current_sender, next, current_amount_in, current_amount_in
+                            );
+                        }
+                        if let Some((receiver, amount)) = ctx.deref().borrow_mut().transform(
+                            &current_sender.unwrap(),
+                            &next,
+                            current_amount_in,
+                            state,
+                            vm,
+                            true,
+                        ) {
+                            #[cfg(test)]
+                            {
+                                println!("Hop out = {}/{:?}", amount, amount);
+                            }
+                            current_amount_in = amount;
+                            current_sender = Some(receiver);
+                        } else {
+                            #[cfg(test)]
+                            {
+                                println!("!!! Balancer Failed !!!");
+                            }
+                            return None;
+                        }
+                    }
                     PairContextTy::Weth(ctx) => {
                         #[cfg(test)]
                         {
@@ -267,8 +393,7 @@ impl TokenContext {
                         assert!(current_sender.is_none());
                         ctx.deref()
                             .borrow_mut()
-                            .transform(&to, &next, amount_in, state, vm, true)
-                            .expect("Weth failed");
+                            .transform(&to, &next, amount_in, state, vm, true)?;
                         current_sender = Some(to);
                     }
                 }
@@ -278,7 +403,7 @@ impl TokenContext {
     }
 
     // swapExactTokensForETHSupportingFeeOnTransferTokens
-    pub fn sell<VS, CI, SC>(
+    fn sell_path<VS, CI, SC>(
         &self,
         amount_in: EVMU256,
         src: EVMAddress,
@@ -295,8 +420,7 @@ impl TokenContext {
             if let PairContextTy::Weth(ctx) = &self.swaps[0].route[0] {
                 ctx.deref()
                     .borrow_mut()
-                    .transform(&src, &EVMAddress::zero(), amount_in, state, vm, false)
-                    .map(|_| ());
+                    .transform(&src, &EVMAddress::zero(), amount_in, state, vm, false)?;
             } else {
                 panic!("Invalid weth context");
             }
@@ -318,6 +442,8 @@ impl TokenContext {
                         PairContextTy::Uniswap(ctx) => ctx.borrow().pair_address,
                         PairContextTy::Weth(_ctx) => state.get_rand_caller(),
                         PairContextTy::UniswapV3(_) => EVMAddress::from_slice(&V3_TOKEN_HOLDER),
+                        PairContextTy::Curve(_) => EVMAddress::from_slice(&CURVE_TOKEN_HOLDER),
+                        PairContextTy::Balancer(_) => EVMAddress::from_slice(&BALANCER_TOKEN_HOLDER),
                     }
                 };
                 match pair {
@@ -411,6 +537,94 @@ impl TokenContext {
                             return None;
                         }
                     }
+                    PairContextTy::Curve(ctx) => {
+                        #[cfg(test)]
+                        {
+                            println!("======== Curve ========");
+                            println!("pool = {:?}", ctx.borrow().inner.pair_address);
+                            println!(
+                                "{:?} => {:?} ({}/{:?})",
+                                current_sender, next, current_amount_in, current_amount_in
+                            );
+                        }
+
+                        if is_first {
+                            ctx.deref().borrow_mut().initial_transfer(
+                                &current_sender,
+                                &EVMAddress::from_slice(&CURVE_TOKEN_HOLDER),
+                                current_amount_in,
+                                state,
+                                vm,
+                            );
+                            is_first = false;
+                        }
+
+                        if let Some((receiver, amount)) = ctx.deref().borrow_mut().transform(
+                            &current_sender,
+                            &next,
+                            current_amount_in,
+                            state,
+                            vm,
+                            false,
+                        ) {
+                            #[cfg(test)]
+                            {
+                                println!("Hop out = {}/{:?}", amount, amount);
+                            }
+                            current_amount_in = amount;
+                            current_sender = receiver;
+                        } else {
+                            #[cfg(test)]
+                            {
+                                println!("!!! Curve Failed !!!");
+                            }
+                            return None;
+                        }
+                    }
+                    PairContextTy::Balancer(ctx) => {
+                        #[cfg(test)]
+                        {
+                            println!("======== Balancer ========");
+                            println!("pool = {:?}", ctx.borrow().inner.pair_address);
+                            println!(
+                                "{:?} => {:?} ({}/{:?})",
+                                current_sender, next, current_amount_in, current_amount_in
+                            );
+                        }
+
+                        if is_first {
+                            ctx.deref().borrow_mut().initial_transfer(
+                                &current_sender,
+                                &EVMAddress::from_slice(&BALANCER_TOKEN_HOLDER),
+                                current_amount_in,
+                                state,
+                                vm,
+                            );
+                            is_first = false;
+                        }
+
+                        if let Some((receiver, amount)) = ctx.deref().borrow_mut().transform(
+                            &current_sender,
+                            &next,
+                            current_amount_in,
+                            state,
+                            vm,
+                            false,
+                        ) {
+                            #[cfg(test)]
+                            {
+                                println!("Hop out = {}/{:?}", amount, amount);
+                            }
+                            current_amount_in = amount;
+                            current_sender = receiver;
+                        } else {
+                            #[cfg(test)]
+                            {
+                                println!("!!! Balancer Failed !!!");
+                            }
+                            return None;
+                        }
+                    }
                     PairContextTy::Weth(ctx) => {
                         #[cfg(test)]
                         {
@@ -421,10 +635,14 @@ impl TokenContext {
                                 current_sender, next, current_amount_in, current_amount_in
                             );
                         }
-                        ctx.deref()
-                            .borrow_mut()
-                            .transform(&current_sender, &next, current_amount_in, state, vm, false)
-                            .expect("Weth failed");
+                        ctx.deref().borrow_mut().transform(
+                            &current_sender,
+                            &next,
+                            current_amount_in,
+                            state,
+                            vm,
+                            false,
+                        )?;
                     }
                 }
             }
@@ -504,7 +722,25 @@ pub fn get_uniswap_info(src_exact: &str) -> UniswapInfo {
             pool_fee: 100,
             router: None,
         },
-        _ => panic!("Uniswap provider {:?} not supported", src_exact),
+        // fee of StableSwap pools is read from the pool itself
+        "curve_eth" | "curve_arb" | "curve_polygon" | "curve_op" | "curve_avax" | "curve_base" => UniswapInfo {
+            pool_fee: 0,
+            router: None,
+        },
+        // all Balancer V2 pools are traded through the Vault
+        "balancer_eth" | "balancer_arb" | "balancer_polygon" | "balancer_op" | "balancer_avax" | "balancer_base" => {
+            UniswapInfo {
+                pool_fee: 0,
+                router: Some(EVMAddress::from_str("0xBA12222222228d8Ba445958a75a0704d566BF2C8").unwrap()),
+            }
+        }
+        _ => match get_custom_dex(src_exact) {
+            Some(dex) => UniswapInfo {
+                pool_fee: dex.fee as usize,
+                router: dex.router.as_ref().map(|router| EVMAddress::from_str(router).unwrap()),
+            },
+            None => panic!("Uniswap provider {:?} not supported", src_exact),
+        },
     }
 }
 
@@ -518,8 +754,8 @@ impl SwapData {
         Default::default()
     }
 
-    pub fn push(&mut self, addr: &EVMAddress, abi: &mut BoxedABI) {
-        if let Some(new) = SwapInfo::try_new(addr, abi) {
+    pub fn push(&mut self, addr: &EVMAddress, abi: &mut BoxedABI, value: EVMU256) {
+        if let Some(new) = SwapInfo::try_new(addr, abi, value) {
             // swap_infos with same type will be merged
             if let hash_map::Entry::Vacant(e) = self.inner.entry(new.ty) {
                 e.insert(new);
@@ -566,7 +802,8 @@ pub struct SwapInfo {
 }
 
 impl SwapInfo {
-    pub fn try_new(target: &EVMAddress, abi: &mut BoxedABI) -> Option<Self> {
+    /// Swap of the call to `target`, `value` being the ETH sent along
+    pub fn try_new(target: &EVMAddress, abi: &mut BoxedABI, value: EVMU256) -> Option<Self> {
         let get_path = |abi: &mut BoxedABI, idx: usize| -> Option<Vec<String>> {
             if let Some(args) = abi.b.as_any().downcast_mut::<AArray>() {
                 let path = args.data[idx]
@@ -589,7 +826,18 @@ impl SwapInfo {
             SWAP_SELL => (SwapType::Sell, get_path(abi, 2)),
             SWAP_DEPOSIT => (SwapType::Deposit, Some(vec![])),
             SWAP_WITHDRAW => (SwapType::Withdraw, Some(vec![])),
-            _ => return None,
+            // swaps of the V3, universal and aggregation routers, buying when
+            // paying with ETH
+            _ if !is_swap_selector(&abi.function) => return None,
+            _ => {
+                let path = decode_swap_path(&abi.get_bytes())?;
+                let ty = if value > EVMU256::ZERO {
+                    SwapType::Buy
+                } else {
+                    SwapType::Sell
+                };
+                (ty, Some(path.iter().map(checksum).collect()))
+            }
         };
 
         if let Some(path) = path {
@@ -629,7 +877,9 @@ impl From<SwapInfo> for vm_state::SwapInfo {
 mod tests {
     use std::{cell::RefCell, rc::Rc};
 
+    use bytes::Bytes;
     use libafl::{schedulers::StdScheduler, state::HasMetadata};
+    use revm_primitives::Bytecode;
 
     use super::*;
     use crate::{
@@ -645,8 +895,8 @@ mod tests {
                 OnChain,
             },
             oracles::v2_pair::reserve_parser,
-            tokens::uniswap::{fetch_uniswap_path, CODE_REGISTRY},
-            types::{generate_random_address, EVMAddress, EVMFuzzState, EVMU256},
+            tokens::{code_cache::register_code, uniswap::fetch_uniswap_path},
+            types::{generate_random_address, EVMAddress, EVMFuzzState, EVMU256, EVMU512},
             vm::{EVMExecutor, EVMState},
         },
         state::{FuzzState, HasCaller},
@@ -677,10 +927,7 @@ mod tests {
 
         fuzz_host.evmstate = vm_state;
 
-        CODE_REGISTRY
-            .lock()
-            .unwrap()
-            .insert(token, onchain.get_contract_code_analyzed(token, false));
+        register_code(token, onchain.get_contract_code_analyzed(token, false));
 
         let mut chain: Box<dyn ChainConfig> = Box::new(onchain);
         let token_ctx = fetch_uniswap_path(&mut chain, token);
@@ -824,4 +1071,55 @@ mod tests {
         trade("sell", token, amount, 0, 19226633, &EVMAddress::from_str("0xD92Ec2123473e0E4099733b1e03405384Aa1024D").unwrap());
     }
     */
+
+    #[test]
+    fn test_failed_hop_rollback() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> = EVMExecutor::new(
+            FuzzHost::new(StdScheduler::new(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+        // the deposit writes slot 0 of WETH
+        let weth_address = generate_random_address(&mut state);
+        evm_executor.host.set_code(
+            weth_address,
+            Bytecode::new_raw(Bytes::from(hex::decode("600160005500").unwrap())),
+            &mut state,
+        );
+        let weth = PairContextTy::Weth(wrap!(weth_transformer::WethContext { weth_address }));
+        let to = generate_random_address(&mut state);
+        let amount = EVMU256::from(1000);
+
+        // the pair of the second hop has no code, so the hop fails after the
+        // deposit succeeded
+        let pair = PairContextTy::Uniswap(wrap!(v2_transformer::UniswapPairContext {
+            pair_address: generate_random_address(&mut state),
+            in_token_address: generate_random_address(&mut state),
+            next_hop: weth_address,
+            ..Default::default()
+        }));
+        let token_ctx = TokenContext {
+            swaps: vec![PathContext {
+                route: vec![pair, weth.clone()],
+            }],
+            is_weth: false,
+            weth_address,
+        };
+        assert!(token_ctx.buy(amount, to, &mut state, &mut evm_executor, &[0]).is_none());
+        let evmstate = &evm_executor.host.evmstate;
+        assert_eq!(evmstate.sload(weth_address, EVMU256::ZERO), None);
+        assert_eq!(evmstate.flashloan_data.owed, EVMU512::ZERO);
+        assert!(!evmstate.balance.contains_key(&weth_address));
+
+        // the deposit alone is committed
+        let token_ctx = TokenContext {
+            swaps: vec![PathContext { route: vec![weth] }],
+            is_weth: true,
+            weth_address,
+        };
+        assert!(token_ctx.buy(amount, to, &mut state, &mut evm_executor, &[0]).is_some());
+        let evmstate = &evm_executor.host.evmstate;
+        assert_eq!(evmstate.sload(weth_address, EVMU256::ZERO), Some(EVMU256::from(1)));
+        assert!(evmstate.flashloan_data.owed > EVMU512::ZERO);
+    }
 }
@@ -0,0 +1,208 @@
+//! ERC-721 and ERC-1155 transfers, valued at the floor price of their
+//! collection so that NFTs received by the attacker count as profit
+//!
+//! The transfers are decoded from the `Transfer`, `TransferSingle` and
+//! `TransferBatch` events, and only credited once the transaction succeeded
+//! and `ownerOf` / `balanceOf` confirm the attacker holds the tokens, as the
+//! frame emitting an event can still revert. Floor prices are given in wei of
+//! the native token with `--nft-floor-prices <collection>=<price>,...`;
+//! transfers of other collections are ignored.
+
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::evm::{
+    senders::parse_amount,
+    types::{EVMAddress, EVMU256},
+};
+
+/// `Transfer(address,address,uint256)`, ERC-721 when `tokenId` is indexed
+const TRANSFER_TOPIC: &str = "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+/// `TransferSingle(address,address,address,uint256,uint256)`
+const TRANSFER_SINGLE_TOPIC: &str = "c3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62";
+/// `TransferBatch(address,address,address,uint256[],uint256[])`
+const TRANSFER_BATCH_TOPIC: &str = "4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb";
+
+/// `ownerOf(uint256)`
+const OWNER_OF: [u8; 4] = [0x63, 0x52, 0x21, 0x1e];
+/// `balanceOf(address,uint256)`
+const BALANCE_OF: [u8; 4] = [0x00, 0xfd, 0xd5, 0x8e];
+
+/// Transfer of `amount` tokens `id` of a collection
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NFTTransfer {
+    pub collection: EVMAddress,
+    pub from: EVMAddress,
+    pub to: EVMAddress,
+    pub id: EVMU256,
+    pub amount: EVMU256,
+    /// ERC-1155 transfer, ERC-721 otherwise
+    pub erc1155: bool,
+}
+
+impl NFTTransfer {
+    /// Calldata of the query of the amount of the token held by `holder`
+    pub fn holding_call(&self, holder: EVMAddress) -> Vec<u8> {
+        let id = self.id.to_be_bytes::<32>();
+        if self.erc1155 {
+            [BALANCE_OF.as_slice(), &[0; 12], holder.as_bytes(), &id].concat()
+        } else {
+            [OWNER_OF.as_slice(), &id].concat()
+        }
+    }
+
+    /// Amount of the token held by `holder` according to the output of
+    /// [`NFTTransfer::holding_call`]
+    pub fn held(&self, holder: EVMAddress, output: &[u8]) -> EVMU256 {
+        let Some(word) = word(output, 0) else {
+            return EVMU256::ZERO;
+        };
+        match self.erc1155 {
+            true => word,
+            false if topic_address(&word) == holder => EVMU256::from(1),
+            false => EVMU256::ZERO,
+        }
+    }
+}
+
+fn topic_address(topic: &EVMU256) -> EVMAddress {
+    EVMAddress::from_slice(&topic.to_be_bytes::<32>()[12..])
+}
+
+fn word(data: &[u8], idx: usize) -> Option<EVMU256> {
+    data.get(idx * 32..idx * 32 + 32).map(EVMU256::from_be_slice)
+}
+
+/// The `uint256[]` at the `idx`-th head word of `data`
+fn word_array(data: &[u8], idx: usize) -> Option<Vec<EVMU256>> {
+    let offset: usize = word(data, idx)?.try_into().ok()?;
+    if offset % 32 != 0 {
+        return None;
+    }
+    let len: usize = word(data, offset / 32)?.try_into().ok()?;
+    (0..len).map(|i| word(data, offset / 32 + 1 + i)).collect()
+}
+
+/// Decode the NFT transfers of an event emitted by `collection`, empty if it
+/// is not one
+pub fn decode_transfer(collection: EVMAddress, topics: &[EVMU256], data: &[u8]) -> Vec<NFTTransfer> {
+    let Some(topic0) = topics.first().map(|topic| format!("{:064x}", topic)) else {
+        return vec![];
+    };
+    let transfer = |(from, to): (usize, usize), id: EVMU256, amount: EVMU256, erc1155: bool| NFTTransfer {
+        collection,
+        from: topic_address(&topics[from]),
+        to: topic_address(&topics[to]),
+        id,
+        amount,
+        erc1155,
+    };
+    match (topic0.as_str(), topics.len()) {
+        (TRANSFER_TOPIC, 4) => vec![transfer((1, 2), topics[3], EVMU256::from(1), false)],
+        (TRANSFER_SINGLE_TOPIC, 4) => match (word(data, 0), word(data, 1)) {
+            (Some(id), Some(amount)) => vec![transfer((2, 3), id, amount, true)],
+            _ => vec![],
+        },
+        (TRANSFER_BATCH_TOPIC, 4) => match (word_array(data, 0), word_array(data, 1)) {
+            (Some(ids), Some(amounts)) if ids.len() == amounts.len() => ids
+                .into_iter()
+                .zip(amounts)
+                .map(|(id, amount)| transfer((2, 3), id, amount, true))
+                .collect(),
+            _ => vec![],
+        },
+        _ => vec![],
+    }
+}
+
+/// Parse the comma separated `<collection>=<price>` floor prices
+pub fn parse_floor_prices(s: &str) -> Result<HashMap<EVMAddress, EVMU256>, String> {
+    s.split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (collection, price) = entry.split_once('=').ok_or(format!("invalid floor price {}", entry))?;
+            let collection = EVMAddress::from_str(collection.trim_start_matches("0x"))
+                .map_err(|e| format!("invalid collection {}: {}", collection, e))?;
+            Ok((collection, parse_amount(price)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address_topic(byte: u8) -> EVMU256 {
+        EVMU256::from_be_slice(&[byte; 20])
+    }
+
+    #[test]
+    fn test_decode_transfer() {
+        let collection = EVMAddress::from_slice(&[9; 20]);
+        let transfer = EVMU256::from_str_radix(TRANSFER_TOPIC, 16).unwrap();
+        let erc721 = [transfer, address_topic(1), address_topic(2), EVMU256::from(42)];
+        assert_eq!(
+            decode_transfer(collection, &erc721, &[]),
+            vec![NFTTransfer {
+                collection,
+                from: EVMAddress::from_slice(&[1; 20]),
+                to: EVMAddress::from_slice(&[2; 20]),
+                id: EVMU256::from(42),
+                amount: EVMU256::from(1),
+                erc1155: false,
+            }]
+        );
+        // ERC-20 transfers do not index the amount
+        assert!(decode_transfer(collection, &erc721[..3], &[]).is_empty());
+
+        let batch = EVMU256::from_str_radix(TRANSFER_BATCH_TOPIC, 16).unwrap();
+        let topics = [batch, address_topic(3), address_topic(1), address_topic(2)];
+        // ids at 0x40: [7, 8], values at 0xa0: [2, 3]
+        let data = [0x40u64, 0xa0, 2, 7, 8, 2, 2, 3]
+            .iter()
+            .flat_map(|w| EVMU256::from(*w).to_be_bytes::<32>())
+            .collect::<Vec<_>>();
+        let transfers = decode_transfer(collection, &topics, &data);
+        assert_eq!(
+            transfers.iter().map(|t| (t.id, t.amount)).collect::<Vec<_>>(),
+            vec![
+                (EVMU256::from(7), EVMU256::from(2)),
+                (EVMU256::from(8), EVMU256::from(3))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_held() {
+        let holder = EVMAddress::from_slice(&[2; 20]);
+        let transfer = EVMU256::from_str_radix(TRANSFER_TOPIC, 16).unwrap();
+        let topics = [transfer, address_topic(1), address_topic(2), EVMU256::from(42)];
+        let erc721 = decode_transfer(EVMAddress::zero(), &topics, &[]).remove(0);
+        assert_eq!(
+            erc721.holding_call(holder),
+            [OWNER_OF.to_vec(), EVMU256::from(42).to_be_bytes::<32>().to_vec()].concat()
+        );
+        assert_eq!(
+            erc721.held(holder, &address_topic(2).to_be_bytes::<32>()),
+            EVMU256::from(1)
+        );
+        assert_eq!(
+            erc721.held(holder, &address_topic(1).to_be_bytes::<32>()),
+            EVMU256::ZERO
+        );
+        // ownerOf reverted
+        assert_eq!(erc721.held(holder, &[]), EVMU256::ZERO);
+    }
+
+    #[test]
+    fn test_parse_floor_prices() {
+        let prices = parse_floor_prices("0x0101010101010101010101010101010101010101=1.5ether").unwrap();
+        assert_eq!(
+            prices[&EVMAddress::from_slice(&[1; 20])],
+            EVMU256::from(1_500_000_000_000_000_000u128)
+        );
+        assert!(parse_floor_prices("0x01=1").is_err());
+    }
+}
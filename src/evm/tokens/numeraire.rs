@@ -0,0 +1,195 @@
+use std::{fmt::Debug, str::FromStr};
+
+use bytes::Bytes;
+use libafl::schedulers::Scheduler;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    evm::{
+        onchain::ChainConfig,
+        oracles::u512_div_float,
+        tokens::{uniswap::fetch_uniswap_path, v2_transformer::balance_of_bytes},
+        types::{EVMAddress, EVMFuzzState, EVMU256, EVMU512},
+        vm::{EVMExecutor, EVMState},
+    },
+    generic_vm::vm_executor::GenericVM,
+    input::ConciseSerde,
+};
+
+/// Selector of `decimals()`
+const DECIMALS: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+/// Account receiving the numeraire tokens bought when quoting
+const QUOTE_RECEIVER: &str = "0x00000000000000000000000000000000000ca5e5";
+
+/// Unit in which profits, capitals and thresholds are expressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Numeraire {
+    /// The native token of the chain, in which the fuzzer accounts
+    #[default]
+    Native,
+    WETH,
+    WBNB,
+    USDC,
+    USDT,
+}
+
+impl FromStr for Numeraire {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "native" | "eth" => Ok(Self::Native),
+            "weth" => Ok(Self::WETH),
+            "wbnb" => Ok(Self::WBNB),
+            "usdc" => Ok(Self::USDC),
+            "usdt" => Ok(Self::USDT),
+            _ => Err(format!("unknown numeraire: {}", s)),
+        }
+    }
+}
+
+impl Numeraire {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Native => "ETH",
+            Self::WETH => "WETH",
+            Self::WBNB => "WBNB",
+            Self::USDC => "USDC",
+            Self::USDT => "USDT",
+        }
+    }
+}
+
+/// The numeraire and the price of the native token in it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumerairePrice {
+    pub numeraire: Numeraire,
+    /// Amount of numeraire for one native token
+    pub per_native: f64,
+}
+
+impl Default for NumerairePrice {
+    fn default() -> Self {
+        Self {
+            numeraire: Numeraire::Native,
+            per_native: 1.0,
+        }
+    }
+}
+
+impl NumerairePrice {
+    /// Convert an amount of numeraire into native tokens
+    pub fn to_native(&self, amount: f64) -> f64 {
+        amount / self.per_native
+    }
+
+    /// Format an amount of native tokens in the numeraire, `amount / scale`
+    /// being the amount in thousandths of native tokens (see
+    /// [`u512_div_float`])
+    pub fn format(&self, amount: EVMU512, scale: EVMU512) -> String {
+        let native = u512_div_float(amount, scale, 3);
+        if self.numeraire == Numeraire::Native {
+            return format!("{} ETH", native);
+        }
+        format!(
+            "{:.3} {} ({} ETH)",
+            native.parse::<f64>().unwrap_or_default() * self.per_native,
+            self.numeraire.symbol(),
+            native
+        )
+    }
+
+    /// Price the native token in `numeraire` by buying the numeraire token
+    /// with one native token through the token routing layer on
+    /// `initial_state`. None if the numeraire is not known on the chain or
+    /// can not be bought.
+    pub fn quote<CI, SC>(
+        numeraire: Numeraire,
+        chain: &mut Box<dyn ChainConfig>,
+        vm: &mut EVMExecutor<EVMState, CI, SC>,
+        state: &mut EVMFuzzState,
+        initial_state: &EVMState,
+    ) -> Option<Self>
+    where
+        CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde + 'static,
+        SC: Scheduler<State = EVMFuzzState> + Clone + 'static,
+    {
+        if numeraire == Numeraire::Native {
+            return Some(Self::default());
+        }
+        let token = chain.get_pegged_token().get(numeraire.symbol()).cloned()?;
+        if token.to_lowercase() == chain.get_weth().to_lowercase() {
+            return Some(Self {
+                numeraire,
+                per_native: 1.0,
+            });
+        }
+        let token = EVMAddress::from_str(&token).ok()?;
+        let token_ctx = fetch_uniswap_path(chain, token);
+        if token_ctx.swaps.is_empty() {
+            warn!("no swap path to {}", numeraire.symbol());
+            return None;
+        }
+
+        let receiver = EVMAddress::from_str(QUOTE_RECEIVER).unwrap();
+        let one_native = EVMU256::from(1_000_000_000_000_000_000_u128);
+        vm.host.evmstate = initial_state.clone();
+        token_ctx.buy(one_native, receiver, state, vm, &[0])?;
+        let bought = vm.host.evmstate.clone();
+        let out = vm.fast_static_call(
+            &[
+                (token, balance_of_bytes(&receiver)),
+                (token, Bytes::from(DECIMALS.to_vec())),
+            ],
+            &bought,
+            state,
+        );
+        vm.host.evmstate = initial_state.clone();
+
+        let (balance, decimals) = match (parse_u256(&out[0]), parse_u256(&out[1])) {
+            (Some(balance), Some(decimals)) if balance > EVMU256::ZERO && decimals <= EVMU256::from(36) => {
+                (balance, decimals.as_limbs()[0] as u32)
+            }
+            _ => return None,
+        };
+        let per_native = u512_div_float(
+            EVMU512::from(balance) * EVMU512::from(1_000_000),
+            EVMU512::from(10).pow(EVMU512::from(decimals)),
+            6,
+        )
+        .parse::<f64>()
+        .ok()?;
+        info!("1 ETH = {} {}", per_native, numeraire.symbol());
+        Some(Self { numeraire, per_native })
+    }
+}
+
+fn parse_u256(out: &[u8]) -> Option<EVMU256> {
+    if out.len() != 32 {
+        return None;
+    }
+    EVMU256::try_from_be_slice(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numeraire_price() {
+        assert_eq!(Numeraire::from_str("USDC"), Ok(Numeraire::USDC));
+        assert!(Numeraire::from_str("DOGE").is_err());
+
+        let price = NumerairePrice {
+            numeraire: Numeraire::USDC,
+            per_native: 2000.0,
+        };
+        assert_eq!(price.to_native(1000.0), 0.5);
+        // 1.5 ETH scaled by 1e24
+        let amount = EVMU512::from(1_500_000_000_000_000_000_000_000_u128);
+        let scale = EVMU512::from(1_000_000_000_000_000_000_000_u128);
+        assert_eq!(price.format(amount, scale), "3000.000 USDC (1.500 ETH)");
+        assert_eq!(NumerairePrice::default().format(amount, scale), "1.500 ETH");
+    }
+}
@@ -0,0 +1,270 @@
+//! Token paths of the swaps through the V3 routers (Uniswap and PancakeSwap),
+//! the universal routers and the 1inch aggregation routers, decoded from the
+//! calldata since their ABI is not necessarily known.
+
+use alloy_dyn_abi::{DynSolType, DynSolValue};
+
+use crate::evm::types::EVMAddress;
+
+/// Length of a token followed by a fee in the path of the V3 swaps
+const V3_HOP_LEN: usize = 23;
+
+// Commands of the universal router
+const V3_SWAP_EXACT_IN: u8 = 0x00;
+const V3_SWAP_EXACT_OUT: u8 = 0x01;
+const V2_SWAP_EXACT_IN: u8 = 0x08;
+const V2_SWAP_EXACT_OUT: u8 = 0x09;
+const COMMAND_TYPE_MASK: u8 = 0x3f;
+
+/// Selectors of the swaps decoded by [`decode_swap_path`]
+const SWAP_SELECTORS: [[u8; 4]; 13] = [
+    [0xc0, 0x4b, 0x8d, 0x59],
+    [0xb8, 0x58, 0x18, 0x3f],
+    [0xf2, 0x8c, 0x04, 0x98],
+    [0x09, 0xb8, 0x13, 0x46],
+    [0x41, 0x4b, 0xf3, 0x89],
+    [0xdb, 0x3e, 0x21, 0x98],
+    [0x04, 0xe4, 0x5a, 0xaf],
+    [0x50, 0x23, 0xb4, 0xdf],
+    [0x35, 0x93, 0x56, 0x4c],
+    [0x24, 0x85, 0x6b, 0xc3],
+    [0x7c, 0x02, 0x52, 0x00],
+    [0x12, 0xaa, 0x3c, 0xaf],
+    [0x07, 0xed, 0x23, 0x79],
+];
+
+/// Whether the calls with `selector` may be swaps whose path can be decoded,
+/// checked before encoding the calldata
+pub fn is_swap_selector(selector: &[u8; 4]) -> bool {
+    SWAP_SELECTORS.contains(selector)
+}
+
+/// Tokens swapped by the call, from the input token to the output one. None if
+/// the call is not a known swap
+pub fn decode_swap_path(calldata: &[u8]) -> Option<Vec<EVMAddress>> {
+    if calldata.len() < 4 {
+        return None;
+    }
+    let (selector, args) = calldata.split_at(4);
+    match selector {
+        // exactInput of SwapRouter and SwapRouter02
+        [0xc0, 0x4b, 0x8d, 0x59] => v3_path(
+            tuple_field(&decode("((bytes,address,uint256,uint256,uint256))", args)?, 0)?,
+            false,
+        ),
+        [0xb8, 0x58, 0x18, 0x3f] => v3_path(
+            tuple_field(&decode("((bytes,address,uint256,uint256))", args)?, 0)?,
+            false,
+        ),
+        // exactOutput, whose path is reversed
+        [0xf2, 0x8c, 0x04, 0x98] => v3_path(
+            tuple_field(&decode("((bytes,address,uint256,uint256,uint256))", args)?, 0)?,
+            true,
+        ),
+        [0x09, 0xb8, 0x13, 0x46] => v3_path(
+            tuple_field(&decode("((bytes,address,uint256,uint256))", args)?, 0)?,
+            true,
+        ),
+        // exactInputSingle and exactOutputSingle
+        [0x41, 0x4b, 0xf3, 0x89] | [0xdb, 0x3e, 0x21, 0x98] => single_path(&decode(
+            "((address,address,uint24,address,uint256,uint256,uint256,uint160))",
+            args,
+        )?),
+        [0x04, 0xe4, 0x5a, 0xaf] | [0x50, 0x23, 0xb4, 0xdf] => single_path(&decode(
+            "((address,address,uint24,address,uint256,uint256,uint160))",
+            args,
+        )?),
+        // execute of the universal router, with and without deadline
+        [0x35, 0x93, 0x56, 0x4c] => universal_router_path(&decode("(bytes,bytes[],uint256)", args)?),
+        [0x24, 0x85, 0x6b, 0xc3] => universal_router_path(&decode("(bytes,bytes[])", args)?),
+        // swap of the 1inch aggregation routers V4, V5 and V6
+        [0x7c, 0x02, 0x52, 0x00] => one_inch_path(&decode(
+            "(address,(address,address,address,address,uint256,uint256,uint256,bytes),bytes)",
+            args,
+        )?),
+        [0x12, 0xaa, 0x3c, 0xaf] => one_inch_path(&decode(
+            "(address,(address,address,address,address,uint256,uint256,uint256),bytes,bytes)",
+            args,
+        )?),
+        [0x07, 0xed, 0x23, 0x79] => one_inch_path(&decode(
+            "(address,(address,address,address,address,uint256,uint256,uint256),bytes)",
+            args,
+        )?),
+        _ => None,
+    }
+}
+
+fn decode(params: &str, args: &[u8]) -> Option<Vec<DynSolValue>> {
+    match DynSolType::parse(params).ok()?.abi_decode_params(args).ok()? {
+        DynSolValue::Tuple(values) => Some(values),
+        _ => None,
+    }
+}
+
+/// `idx`-th field of the struct given as the only argument
+fn tuple_field(values: &[DynSolValue], idx: usize) -> Option<&DynSolValue> {
+    values.first()?.as_tuple()?.get(idx)
+}
+
+fn address(value: &DynSolValue) -> Option<EVMAddress> {
+    Some(EVMAddress::from_slice(value.as_address()?.as_slice()))
+}
+
+/// Tokens of a path encoded as `token (fee token)*`
+fn v3_path(path: &DynSolValue, reversed: bool) -> Option<Vec<EVMAddress>> {
+    let path = path.as_bytes()?;
+    if path.len() < 20 || (path.len() - 20) % V3_HOP_LEN != 0 {
+        return None;
+    }
+    let mut tokens = (0..=(path.len() - 20) / V3_HOP_LEN)
+        .map(|hop| EVMAddress::from_slice(&path[hop * V3_HOP_LEN..hop * V3_HOP_LEN + 20]))
+        .collect::<Vec<_>>();
+    if reversed {
+        tokens.reverse();
+    }
+    Some(tokens)
+}
+
+fn single_path(values: &[DynSolValue]) -> Option<Vec<EVMAddress>> {
+    Some(vec![
+        address(tuple_field(values, 0)?)?,
+        address(tuple_field(values, 1)?)?,
+    ])
+}
+
+/// Paths of the swap commands joined, e.g., V2 then V3 hops
+fn universal_router_path(values: &[DynSolValue]) -> Option<Vec<EVMAddress>> {
+    let commands = values.first()?.as_bytes()?;
+    let inputs = values.get(1)?.as_array()?;
+    let mut path: Vec<EVMAddress> = vec![];
+    for (command, input) in commands.iter().zip(inputs) {
+        let input = input.as_bytes()?;
+        let hops = match command & COMMAND_TYPE_MASK {
+            V3_SWAP_EXACT_IN => v3_path(decode("(address,uint256,uint256,bytes,bool)", input)?.get(3)?, false)?,
+            V3_SWAP_EXACT_OUT => v3_path(decode("(address,uint256,uint256,bytes,bool)", input)?.get(3)?, true)?,
+            V2_SWAP_EXACT_IN | V2_SWAP_EXACT_OUT => decode("(address,uint256,uint256,address[],bool)", input)?
+                .get(3)?
+                .as_array()?
+                .iter()
+                .map(address)
+                .collect::<Option<Vec<_>>>()?,
+            _ => continue,
+        };
+        // the output token of a swap is the input one of the next
+        let skip = usize::from(!path.is_empty() && path.last() == hops.first());
+        path.extend(hops.into_iter().skip(skip));
+    }
+    if path.is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+fn one_inch_path(values: &[DynSolValue]) -> Option<Vec<EVMAddress>> {
+    let desc = values.get(1)?.as_tuple()?;
+    Some(vec![address(desc.first()?)?, address(desc.get(1)?)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{Address, U256};
+
+    use super::*;
+
+    fn token(byte: u8) -> EVMAddress {
+        EVMAddress::from_slice(&[byte; 20])
+    }
+
+    fn address_value(byte: u8) -> DynSolValue {
+        DynSolValue::Address(Address::from_slice(&[byte; 20]))
+    }
+
+    fn uint(v: u64) -> DynSolValue {
+        DynSolValue::Uint(U256::from(v), 256)
+    }
+
+    /// `a (fee b)*`
+    fn encoded_v3_path(tokens: &[u8]) -> Vec<u8> {
+        let mut path = vec![tokens[0]; 20];
+        for t in &tokens[1..] {
+            path.extend([0x00, 0x01, 0xf4]);
+            path.extend([*t; 20]);
+        }
+        path
+    }
+
+    #[test]
+    fn test_is_swap_selector() {
+        assert!(is_swap_selector(&[0xc0, 0x4b, 0x8d, 0x59]));
+        assert!(is_swap_selector(&[0x07, 0xed, 0x23, 0x79]));
+        // transfer(address,uint256)
+        assert!(!is_swap_selector(&[0xa9, 0x05, 0x9c, 0xbb]));
+    }
+
+    #[test]
+    fn test_decode_swap_path() {
+        // exactInput of SwapRouter02
+        let params = DynSolValue::Tuple(vec![
+            DynSolValue::Bytes(encoded_v3_path(&[1, 2, 3])),
+            address_value(9),
+            uint(100),
+            uint(0),
+        ]);
+        let calldata = [
+            vec![0xb8, 0x58, 0x18, 0x3f],
+            DynSolValue::Tuple(vec![params]).abi_encode_params(),
+        ]
+        .concat();
+        assert_eq!(decode_swap_path(&calldata), Some(vec![token(1), token(2), token(3)]));
+
+        // execute of the universal router, V2 swap then V3 exact out swap
+        let v2_input = DynSolValue::Tuple(vec![
+            address_value(9),
+            uint(100),
+            uint(0),
+            DynSolValue::Array(vec![address_value(1), address_value(2)]),
+            DynSolValue::Bool(true),
+        ]);
+        let v3_input = DynSolValue::Tuple(vec![
+            address_value(9),
+            uint(100),
+            uint(0),
+            DynSolValue::Bytes(encoded_v3_path(&[3, 2])),
+            DynSolValue::Bool(false),
+        ]);
+        let args = DynSolValue::Tuple(vec![
+            // WRAP_ETH, V2_SWAP_EXACT_IN, V3_SWAP_EXACT_OUT
+            DynSolValue::Bytes(vec![0x0b, V2_SWAP_EXACT_IN, V3_SWAP_EXACT_OUT]),
+            DynSolValue::Array(vec![
+                DynSolValue::Bytes(vec![]),
+                DynSolValue::Bytes(v2_input.abi_encode_params()),
+                DynSolValue::Bytes(v3_input.abi_encode_params()),
+            ]),
+        ]);
+        let calldata = [vec![0x24, 0x85, 0x6b, 0xc3], args.abi_encode_params()].concat();
+        assert_eq!(decode_swap_path(&calldata), Some(vec![token(1), token(2), token(3)]));
+
+        // swap of the 1inch aggregation router V5
+        let desc = DynSolValue::Tuple(vec![
+            address_value(1),
+            address_value(4),
+            address_value(9),
+            address_value(9),
+            uint(100),
+            uint(0),
+            uint(0),
+        ]);
+        let args = DynSolValue::Tuple(vec![
+            address_value(8),
+            desc,
+            DynSolValue::Bytes(vec![]),
+            DynSolValue::Bytes(vec![]),
+        ]);
+        let calldata = [vec![0x12, 0xaa, 0x3c, 0xaf], args.abi_encode_params()].concat();
+        assert_eq!(decode_swap_path(&calldata), Some(vec![token(1), token(4)]));
+
+        assert_eq!(decode_swap_path(&[0xb8, 0x58, 0x18, 0x3f, 0x00]), None);
+        assert_eq!(decode_swap_path(&[0xde, 0xad]), None);
+    }
+}
@@ -3,12 +3,10 @@ use std::{
     collections::{HashMap, HashSet},
     rc::Rc,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::Arc,
 };
 
 use itertools::Itertools;
-use lazy_static::lazy_static;
-use revm_primitives::Bytecode;
 use tracing::{info, warn};
 
 use super::{
@@ -20,7 +18,12 @@ use super::{
 };
 use crate::evm::{
     onchain::{endpoints::PairData, ChainConfig},
-    tokens::v3_transformer::UniswapV3PairContext,
+    tokens::{
+        balancer_transformer::BalancerPoolContext,
+        code_cache::register_code,
+        curve_transformer::{precision_mul, CurvePoolContext},
+        v3_transformer::UniswapV3PairContext,
+    },
     types::{EVMAddress, EVMU256},
 };
 
@@ -35,10 +38,8 @@ pub struct BasicInfo {
 }
 
 const MAX_HOPS: u32 = 2; // Assuming the value of MAX_HOPS
-
-lazy_static! {
-    pub static ref CODE_REGISTRY: Mutex<HashMap<EVMAddress, Bytecode>> = Mutex::new(HashMap::new());
-}
+const MAX_DISCOVERY_HOPS: usize = 3;
+const MAX_DISCOVERED_ROUTES: usize = 5;
 
 pub fn fetch_uniswap_path(chain: &mut Box<dyn ChainConfig>, token_address: EVMAddress) -> TokenContext {
     let token = format!("{:?}", token_address);
@@ -52,21 +53,22 @@ pub fn fetch_uniswap_path(chain: &mut Box<dyn ChainConfig>, token_address: EVMAd
     let weth = EVMAddress::from_str(&basic_info.weth).unwrap();
     let is_weth = basic_info.is_weth;
 
-    let routes: Vec<Vec<PairData>> = info.routes;
+    let mut routes: Vec<Vec<PairData>> = info.routes;
+    if routes.is_empty() && !is_weth {
+        routes = discover_routes(chain, &token);
+    }
 
     macro_rules! register_code {
         ($addr: expr) => {
-            CODE_REGISTRY
-                .lock()
-                .unwrap()
-                .insert($addr, chain.get_contract_code_analyzed($addr, false));
+            register_code($addr, chain.get_contract_code_analyzed($addr, false));
         };
     }
 
     let paths_parsed = routes
         .iter()
-        .map(|pairs| {
+        .filter_map(|pairs| {
             let mut path_parsed: PathContext = Default::default();
+            let mut is_valid = true;
 
             macro_rules! _gen_v2_pair_context {
                 ($pair: expr) => {{
@@ -101,12 +103,57 @@ pub fn fetch_uniswap_path(chain: &mut Box<dyn ChainConfig>, token_address: EVMAd
                 }};
             }
 
+            macro_rules! gen_curve_pair_context {
+                ($pair: expr) => {{
+                    let pool_address = EVMAddress::from_str($pair.pair.as_str()).expect("failed to parse pair");
+                    let coins = chain.get_curve_coins(pool_address);
+                    let inner = _gen_v2_pair_context!($pair);
+                    let coin_in = coins.iter().position(|c| *c == inner.in_token_address);
+                    let coin_out = coins.iter().position(|c| *c == inner.next_hop);
+                    if let (Some(coin_in), Some(coin_out)) = (coin_in, coin_out) {
+                        register_code!(pool_address);
+                        register_code!(inner.in_token_address);
+                        let precision_mul = coins
+                            .iter()
+                            .map(|coin| precision_mul(chain.get_token_decimals(*coin)))
+                            .collect();
+                        let curve = Rc::new(RefCell::new(CurvePoolContext {
+                            coin_in: coin_in as u8,
+                            coin_out: coin_out as u8,
+                            n_coins: coins.len() as u8,
+                            precision_mul,
+                            inner,
+                        }));
+                        path_parsed.route.push(super::PairContextTy::Curve(curve));
+                    } else {
+                        warn!("failed to resolve coins of curve pool {:?}", pool_address);
+                        is_valid = false;
+                    }
+                }};
+            }
+
+            macro_rules! gen_balancer_pair_context {
+                ($pair: expr) => {{
+                    let inner = _gen_v2_pair_context!($pair);
+                    register_code!(inner.pair_address);
+                    register_code!(inner.in_token_address);
+                    // the vault holding the tokens of all pools
+                    register_code!(inner.uniswap_info.router.unwrap());
+                    let balancer = Rc::new(RefCell::new(BalancerPoolContext { inner }));
+                    path_parsed.route.push(super::PairContextTy::Balancer(balancer));
+                }};
+            }
+
             pairs.iter().for_each(|pair| match pair.src.as_str() {
                 "lp" => {
                     if pair.interface == "uniswapv2" {
                         gen_v2_pair_context!(pair);
                     } else if pair.interface == "uniswapv3" {
                         gen_v3_pair_context!(pair);
+                    } else if pair.interface == "curve" {
+                        gen_curve_pair_context!(pair);
+                    } else if pair.interface == "balancer" {
+                        gen_balancer_pair_context!(pair);
                     } else {
                         unimplemented!("unknown interface");
                     }
@@ -116,6 +163,10 @@ pub fn fetch_uniswap_path(chain: &mut Box<dyn ChainConfig>, token_address: EVMAd
                         gen_v2_pair_context!(pair);
                     } else if pair.interface == "uniswapv3" {
                         gen_v3_pair_context!(pair);
+                    } else if pair.interface == "curve" {
+                        gen_curve_pair_context!(pair);
+                    } else if pair.interface == "balancer" {
+                        gen_balancer_pair_context!(pair);
                     } else {
                         unimplemented!("unknown interface");
                     }
@@ -143,7 +194,11 @@ pub fn fetch_uniswap_path(chain: &mut Box<dyn ChainConfig>, token_address: EVMAd
                 }
             }
 
-            path_parsed
+            if is_valid {
+                Some(path_parsed)
+            } else {
+                None
+            }
         })
         .collect();
 
@@ -250,7 +305,7 @@ fn add_reserve_info(chain: &mut Box<dyn ChainConfig>, pair_data: &mut PairData)
             pair_data.initial_reserves_1 = EVMU256::try_from_be_slice(&hex::decode(r1).unwrap()).unwrap();
         }
     }
-    if pair_data.interface == "uniswapv3" {
+    if pair_data.interface == "uniswapv3" || pair_data.interface == "curve" {
         let t0 = EVMAddress::from_str(&pair_data.token0).unwrap();
         let t1 = EVMAddress::from_str(&pair_data.token1).unwrap();
         let lp = EVMAddress::from_str(&pair_data.pair).unwrap();
@@ -259,6 +314,16 @@ fn add_reserve_info(chain: &mut Box<dyn ChainConfig>, pair_data: &mut PairData)
         pair_data.initial_reserves_0 = r0;
         pair_data.initial_reserves_1 = r1;
     }
+    if pair_data.interface == "balancer" {
+        // tokens of Balancer pools are held by the vault, shared by all pools,
+        // so this is only an upper bound of the liquidity
+        let t0 = EVMAddress::from_str(&pair_data.token0).unwrap();
+        let t1 = EVMAddress::from_str(&pair_data.token1).unwrap();
+        if let Some(vault) = get_uniswap_info(&pair_data.src_exact).router {
+            pair_data.initial_reserves_0 = chain.get_token_balance(t0, vault);
+            pair_data.initial_reserves_1 = chain.get_token_balance(t1, vault);
+        }
+    }
 }
 
 fn get_liquidity_cmp(pair_data: &PairData) -> EVMU256 {
@@ -360,6 +425,95 @@ fn find_path_subgraph(chain: &mut Box<dyn ChainConfig>, token: &str) -> Info {
     with_info(routes, token, &weth)
 }
 
+/// Discovers routes from `token` to weth by querying the V2 factories for
+/// pairs through the pegged tokens, e.g. token -> WBNB -> BUSD -> target.
+/// Routes with the best bottleneck liquidity come first.
+fn discover_routes(chain: &mut Box<dyn ChainConfig>, token: &str) -> Vec<Vec<PairData>> {
+    let token = token.to_lowercase();
+    let weth = chain.get_weth().to_lowercase();
+    let connectors: Vec<String> = chain
+        .get_pegged_token()
+        .values()
+        .map(|v| v.to_lowercase())
+        .filter(|v| *v != token)
+        .sorted()
+        .collect();
+
+    let mut cache: HashMap<(String, String), Option<PairData>> = HashMap::new();
+    let mut routes: Vec<Vec<PairData>> = vec![];
+    let mut visited: HashSet<String> = HashSet::from([token.clone()]);
+    discover_dfs(
+        chain,
+        &token,
+        &weth,
+        &connectors,
+        &mut vec![],
+        &mut visited,
+        &mut cache,
+        &mut routes,
+    );
+
+    let bottleneck = |route: &Vec<PairData>| {
+        route
+            .iter()
+            .filter(|p| p.src == "lp")
+            .map(get_liquidity_cmp)
+            .min()
+            .unwrap_or(EVMU256::ZERO)
+    };
+    routes.sort_by(|a, b| bottleneck(b).cmp(&bottleneck(a)));
+    routes.truncate(MAX_DISCOVERED_ROUTES);
+    info!("discovered {} routes for {token}", routes.len());
+    routes
+}
+
+#[allow(clippy::too_many_arguments)]
+fn discover_dfs(
+    chain: &mut Box<dyn ChainConfig>,
+    token: &str,
+    weth: &str,
+    connectors: &[String],
+    path: &mut Vec<PairData>,
+    visited: &mut HashSet<String>,
+    cache: &mut HashMap<(String, String), Option<PairData>>,
+    routes: &mut Vec<Vec<PairData>>,
+) {
+    if path.len() >= MAX_DISCOVERY_HOPS {
+        return;
+    }
+    for connector in connectors {
+        if visited.contains(connector) {
+            continue;
+        }
+        let pair = cache
+            .entry((token.to_string(), connector.clone()))
+            .or_insert_with(|| {
+                let mut pairs = chain.get_factory_pairs(token, connector);
+                for pair in &mut pairs {
+                    add_reserve_info(chain, pair);
+                }
+                pairs.into_iter().max_by_key(get_liquidity_cmp)
+            })
+            .clone();
+        let pair = match pair {
+            Some(pair) if get_liquidity_cmp(&pair) > EVMU256::ZERO => pair,
+            _ => continue,
+        };
+
+        path.push(pair);
+        if connector == weth {
+            let mut route = path.clone();
+            route.push(get_pegged_next_hop(chain, weth));
+            routes.push(route);
+        } else {
+            visited.insert(connector.clone());
+            discover_dfs(chain, connector, weth, connectors, path, visited, cache, routes);
+            visited.remove(connector);
+        }
+        path.pop();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -409,6 +563,16 @@ mod tests {
         assert!(!v.weth_address.is_zero());
     }
 
+    #[test]
+    fn test_discover_routes() {
+        let mut config: Box<dyn ChainConfig> = Box::new(OnChainConfig::new(BSC, 22055611));
+        let v = discover_routes(&mut config, "0x0e09fabb73bd3ade0a17ecc321fd13a19e81ce82");
+        assert!(!v.is_empty());
+        for route in v {
+            assert_eq!(route.last().unwrap().src, "pegged_weth");
+        }
+    }
+
     #[test]
     fn test_fetch_uniswap_path_wbnb() {
         let mut config: Box<dyn ChainConfig> = Box::new(OnChainConfig::new(BSC, 22055611));
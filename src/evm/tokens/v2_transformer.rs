@@ -2,19 +2,20 @@ use std::{collections::HashMap, fmt::Debug, sync::Arc};
 
 use bytes::Bytes;
 use libafl::schedulers::Scheduler;
-use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
+use revm_interpreter::{CallContext, CallScheme, Contract};
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{uniswap::CODE_REGISTRY, PairContext, UniswapInfo};
+use super::{PairContext, UniswapInfo};
 use crate::{
     evm::{
         types::{EVMAddress, EVMFuzzState, EVMU256},
-        vm::{EVMExecutor, MEM_LIMIT},
+        vm::EVMExecutor,
     },
     generic_vm::vm_state::VMStateT,
     get_code_tokens,
     input::ConciseSerde,
     is_call_success,
+    mutation_utils::add_interesting_values,
 };
 #[derive(Clone, Debug, Default)]
 pub struct UniswapPairContext {
@@ -90,7 +91,7 @@ impl UniswapPairContext {
     {
         let call = Contract::new_with_context_analyzed(
             transfer_bytes(next, amount),
-            get_code_tokens!(self.in_token_address, vm, state),
+            get_code_tokens!(self.in_token_address, vm, state)?,
             &CallContext {
                 address: self.in_token_address,
                 caller: *src,
@@ -100,11 +101,11 @@ impl UniswapPairContext {
             },
         );
 
-        let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+        let mut interp = vm.interpreter_pool.acquire(call, 1e10 as u64, false);
         let ir = vm.host.run_inspect(&mut interp, state);
+        vm.interpreter_pool.release(interp);
         if !is_call_success!(ir) {
             // println!("transfer failed1");
-            // println!("return value: {:?}", interp.return_value());
             None
         } else {
             // println!("transfer success");
@@ -134,8 +135,8 @@ impl PairContext for UniswapPairContext {
             (self.in_token_address, self.next_hop, self.side)
         };
 
-        let in_token_code = get_code_tokens!(in_token_address, vm, state);
-        let out_token_code = get_code_tokens!(out_token_address, vm, state);
+        let in_token_code = get_code_tokens!(in_token_address, vm, state)?;
+        let out_token_code = get_code_tokens!(out_token_address, vm, state)?;
 
         // get balance of pair's token
         macro_rules! balanceof_token {
@@ -156,19 +157,19 @@ impl PairContext for UniswapPairContext {
                         scheme: CallScheme::Call,
                     },
                 );
-                let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+                let mut interp = vm.interpreter_pool.acquire(call, 1e10 as u64, false);
                 let ir = vm.host.run_inspect(&mut interp, state);
+                let balance = EVMU256::try_from_be_slice(interp.return_value().as_ref());
+                vm.interpreter_pool.release(interp);
                 if !is_call_success!(ir) {
                     return None;
                 }
-                let in_balance =
-                    if let Some(num) = EVMU256::try_from_be_slice(interp.return_value().to_vec().as_slice()) {
-                        num
-                    } else {
-                        // println!("balance of failed");
-                        // println!("return value: {:?}", interp.return_value());
-                        return None;
-                    };
+                let in_balance = if let Some(num) = balance {
+                    num
+                } else {
+                    // println!("balance of failed");
+                    return None;
+                };
 
                 // println!("balance of {:?}@{:?}: {:?}", $who, addr, in_balance);
                 in_balance
@@ -198,14 +199,14 @@ impl PairContext for UniswapPairContext {
                 // println!("transfer {:?}@{:?} for {:?} => {:?}", $amt, addr, $who, $dst);
                 // println!("pre_vm_state: {:?}", vm.host.evmstate.state);
 
-                let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+                let mut interp = vm.interpreter_pool.acquire(call, 1e10 as u64, false);
 
                 let ir = vm.host.run_inspect(&mut interp, state);
+                vm.interpreter_pool.release(interp);
                 // println!("bytes: {:?}", transfer_bytes($dst, $amt));
                 // println!("from: {:?} => {:?}, {:?}", $who, $dst, addr);
                 if !is_call_success!(ir) {
                     // println!("transfer failed2");
-                    // println!("return value: {:?}", interp.return_value());
                     return None;
                 }
                 // println!("transfer success");
@@ -237,6 +238,7 @@ impl PairContext for UniswapPairContext {
         };
         let reserve_in = if side == 0 { reserve.0 } else { reserve.1 };
         let reserve_out = if side == 0 { reserve.1 } else { reserve.0 };
+        add_interesting_values(state, [reserve_in, reserve_out]);
 
         // 2. get balance of pair's token
         let new_balance = balanceof_token!(true, &self.pair_address);
@@ -5,7 +5,7 @@ use libafl::schedulers::Scheduler;
 use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{uniswap::CODE_REGISTRY, PairContext, UniswapInfo};
+use super::{PairContext, UniswapInfo};
 use crate::{
     evm::{
         tokens::v2_transformer::{balance_of_bytes, UniswapPairContext},
@@ -149,8 +149,8 @@ impl PairContext for UniswapV3PairContext {
             (self.inner.in_token_address, self.inner.next_hop, self.inner.side)
         };
 
-        let in_token_code = get_code_tokens!(in_token_address, vm, state);
-        let out_token_code = get_code_tokens!(out_token_address, vm, state);
+        let in_token_code = get_code_tokens!(in_token_address, vm, state)?;
+        let out_token_code = get_code_tokens!(out_token_address, vm, state)?;
         macro_rules! balanceof_token {
             ($dir: expr, $who: expr) => {{
                 let addr = if $dir { in_token_address } else { out_token_address };
@@ -240,7 +240,7 @@ impl PairContext for UniswapV3PairContext {
 
         // 2. use router to do the swap
         // println!("looking for router code: {:?}", router);
-        let router_code = get_code_tokens!(router, vm, state);
+        let router_code = get_code_tokens!(router, vm, state)?;
         let by = exact_in_single_swap(in_token_address, out_token_address, self.fee, *next, _amount);
         // println!("bytes: {:?}", hex::encode(by.clone()));
 
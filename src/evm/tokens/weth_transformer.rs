@@ -5,8 +5,9 @@ use bytes::Bytes;
 use libafl::schedulers::Scheduler;
 use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
 use serde::{de::DeserializeOwned, Serialize};
+use tracing::debug;
 
-use super::{uniswap::CODE_REGISTRY, PairContext};
+use super::PairContext;
 use crate::{
     evm::{
         types::{EVMAddress, EVMFuzzState, EVMU256, EVMU512},
@@ -71,7 +72,7 @@ impl PairContext for WethContext {
         vm.host.evmstate.balance.insert(self.weth_address, EVMU256::MAX);
 
         let addr = self.weth_address;
-        let code = get_code_tokens!(addr, vm, state);
+        let code = get_code_tokens!(addr, vm, state)?;
         let call = Contract::new_with_context_analyzed(
             if reverse {
                 // buy
@@ -92,13 +93,14 @@ impl PairContext for WethContext {
         let mut interp = Interpreter::new_with_memory_limit(call.clone(), 1e10 as u64, false, MEM_LIMIT);
         let ir = vm.host.run_inspect(&mut interp, state);
         if !is_call_success!(ir) {
-            println!(
-                "call: {:?} => {:?} {:?}",
+            debug!(
+                "weth call failed: {:?} => {:?} {:?}: {:?} {:?}",
                 call.caller,
                 call.address,
-                hex::encode(call.input)
+                hex::encode(call.input),
+                ir,
+                interp.return_value()
             );
-            panic!("Weth call failed: {:?} {:?}", ir, interp.return_value());
             return None;
         }
 
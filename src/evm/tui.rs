@@ -0,0 +1,180 @@
+//! Live dashboard of the campaign, shown with `--tui` in place of the logs
+//!
+//! The dashboard is redrawn every time the monitor reports the stats of the
+//! fuzzer, i.e., every `REPORTING_INTERVAL` milliseconds.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, Stdout},
+    rc::Rc,
+    sync::Arc,
+};
+
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Terminal,
+};
+use serde_json::Value;
+
+use crate::{
+    evm::{middlewares::coverage::Coverage, onchain::provider::RpcBudget},
+    logger,
+    r#const::TUI_RECENT_OBJECTIVES,
+};
+
+/// Stats reported by the monitor, e.g., `[Testcase #0] run time: 0h-0m-5s,
+/// clients: 1, corpus: 10, objectives: 0, executions: 5000, exec/sec: 1000`
+pub fn parse_monitor_stats(msg: &str) -> HashMap<String, String> {
+    let stats = msg.split_once("] ").map_or(msg, |(_, stats)| stats);
+    stats
+        .split(", ")
+        .filter_map(|stat| stat.split_once(": "))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+pub struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    work_dir: String,
+    rpc_budget: Option<Arc<RpcBudget>>,
+    coverage: Option<Rc<RefCell<Coverage>>>,
+    oracles: Vec<String>,
+}
+
+impl Dashboard {
+    /// Take the terminal, the logs are written to `work_dir/fuzzer.log` from
+    /// now on
+    pub fn new(work_dir: String, rpc_budget: Option<Arc<RpcBudget>>) -> io::Result<Self> {
+        fs::create_dir_all(&work_dir)?;
+        logger::redirect_to_file(&format!("{}/fuzzer.log", work_dir))?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        terminal.clear()?;
+        Ok(Self {
+            terminal,
+            work_dir,
+            rpc_budget,
+            coverage: None,
+            oracles: vec![],
+        })
+    }
+
+    pub fn set_coverage(&mut self, coverage: Rc<RefCell<Coverage>>) {
+        self.coverage = Some(coverage);
+    }
+
+    pub fn set_oracles(&mut self, oracles: Vec<String>) {
+        self.oracles = oracles;
+    }
+
+    /// Types and descriptions of the last objectives found
+    fn recent_objectives(&self) -> Vec<String> {
+        let vulns = fs::read_to_string(format!("{}/vuln_info.jsonl", self.work_dir)).unwrap_or_default();
+        let objectives = vulns
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .map(|v| {
+                format!(
+                    "[{}] {}",
+                    v["bug_type"].as_str().unwrap_or_default(),
+                    v["bug_info"].as_str().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>();
+        objectives[objectives.len().saturating_sub(TUI_RECENT_OBJECTIVES)..].to_vec()
+    }
+
+    /// Redraw the dashboard with the stats reported by the monitor
+    pub fn update(&mut self, msg: &str) {
+        let stats = parse_monitor_stats(msg);
+        let stat = |key: &str| stats.get(key).cloned().unwrap_or("-".to_string());
+        let header = format!(
+            "run time: {} | executions: {} | exec/sec: {} | corpus: {} | objectives: {}",
+            stat("run time"),
+            stat("executions"),
+            stat("exec/sec"),
+            stat("corpus"),
+            stat("objectives")
+        );
+        let coverage = match &self.coverage {
+            Some(coverage) => match coverage.try_borrow() {
+                Ok(coverage) => coverage
+                    .branch_coverage()
+                    .into_iter()
+                    .map(|(name, covered, total)| {
+                        format!(
+                            "{}: {}/{} ({:.2}%)",
+                            name,
+                            covered,
+                            total,
+                            (covered * 100) as f64 / total as f64
+                        )
+                    })
+                    .collect(),
+                Err(_) => vec![],
+            },
+            None => vec![],
+        };
+        let rpc = match &self.rpc_budget {
+            Some(budget) => budget.to_string(),
+            None => "offchain".to_string(),
+        };
+        let objectives = self.recent_objectives();
+        let oracles = self.oracles.clone();
+
+        let res = self.terminal.draw(|f| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(3),
+                    Constraint::Min(5),
+                    Constraint::Length(TUI_RECENT_OBJECTIVES as u16 + 2),
+                    Constraint::Length(3),
+                ])
+                .split(f.size());
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .split(rows[1]);
+            let block = |title: &'static str| Block::default().borders(Borders::ALL).title(title);
+            let list = |items: &[String]| {
+                items
+                    .iter()
+                    .map(|item| ListItem::new(item.as_str()))
+                    .collect::<Vec<_>>()
+            };
+
+            f.render_widget(Paragraph::new(header.as_str()).block(block("ItyFuzz")), rows[0]);
+            f.render_widget(List::new(list(&coverage)).block(block("Branch Coverage")), columns[0]);
+            f.render_widget(List::new(list(&oracles)).block(block("Oracles")), columns[1]);
+            f.render_widget(List::new(list(&objectives)).block(block("Recent Objectives")), rows[2]);
+            f.render_widget(Paragraph::new(rpc.as_str()).block(block("RPC")), rows[3]);
+        });
+        // leave the cursor visible below the dashboard in case the fuzzer exits
+        if res.is_ok() &&
+            let Ok(size) = self.terminal.size()
+        {
+            let _ = self.terminal.set_cursor(0, size.height.saturating_sub(1));
+            let _ = self.terminal.show_cursor();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_monitor_stats() {
+        let stats = parse_monitor_stats(
+            "[Testcase #0] run time: 0h-0m-5s, clients: 1, corpus: 10, objectives: 0, executions: 5000, exec/sec: 1.000k",
+        );
+        assert_eq!(stats["run time"], "0h-0m-5s");
+        assert_eq!(stats["corpus"], "10");
+        assert_eq!(stats["exec/sec"], "1.000k");
+        assert_eq!(stats.len(), 6);
+    }
+}
@@ -15,7 +15,7 @@ use std::{
 use bytes::Bytes;
 /// EVM executor implementation
 use itertools::Itertools;
-use libafl::schedulers::Scheduler;
+use libafl::{schedulers::Scheduler, state::HasMetadata};
 use revm_interpreter::{
     BytecodeLocked,
     CallContext,
@@ -32,25 +32,37 @@ use revm_primitives::Bytecode;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{debug, error};
 
-use super::{input::EVMInput, middlewares::reentrancy::ReentrancyData, types::EVMFuzzState};
-use crate::{evm::tokens::SwapData, generic_vm::vm_state};
+use super::{
+    input::EVMInput,
+    mempool::VictimOutcome,
+    middlewares::{call_taint::TaintedCall, price_source::PriceRead, reentrancy::ReentrancyData},
+    types::EVMFuzzState,
+};
+use crate::{
+    evm::tokens::{nft::NFTTransfer, SwapData},
+    generic_vm::vm_state,
+};
 #[allow(unused_imports)]
 use crate::{
     evm::{
         bytecode_analyzer,
-        host::{FuzzHost, CMP_MAP, COVERAGE_NOT_CHANGED, JMP_MAP, READ_MAP, STATE_CHANGE, WRITE_MAP},
+        host::{EmittedLog, FuzzHost, CMP_MAP, COVERAGE_NOT_CHANGED, JMP_MAP, READ_MAP, STATE_CHANGE, WRITE_MAP},
         input::{ConciseEVMInput, EVMInputT, EVMInputTy},
-        middlewares::middleware::Middleware,
+        middlewares::{middleware::Middleware, trace::CallTracer},
         onchain::flashloan::FlashloanData,
+        summary::FunctionStatsMetadata,
         types::{float_scale_to_u512, EVMAddress, EVMU256, EVMU512},
         vm::Constraint::{NoLiquidation, Value},
     },
     generic_vm::{
+        trace::ExecutionTrace,
         vm_executor::{ExecutionResult, GenericVM, MAP_SIZE},
         vm_state::VMStateT,
     },
     input::{ConciseSerde, VMInputT},
     invoke_middlewares,
+    mutation_utils::add_interesting_values,
+    r#const::{BORROW_GAS_ESTIMATE, INTERPRETER_POOL_SIZE, TX_BASE_GAS},
     state::{HasCaller, HasCurrentInputIdx, HasItyState},
     state_input::StagedVMState,
 };
@@ -226,11 +238,23 @@ impl Hash for PostExecutionCtx {
     }
 }
 
+/// Block environment of a transaction
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub timestamp: EVMU256,
+    pub number: EVMU256,
+    pub basefee: EVMU256,
+}
+
+/// Storage of a contract, a persistent map so that the clones of a state
+/// share the slots until they are written (copy-on-write)
+pub type EVMStorage = im::HashMap<EVMU256, EVMU256>;
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct EVMState {
     /// State of the EVM, which is mapping of EVMU256 slot to EVMU256 value for
-    /// each contract
-    pub state: HashMap<EVMAddress, HashMap<EVMU256, EVMU256>>,
+    /// each contract. Cloning it is cheap, the storage being persistent
+    pub state: im::HashMap<EVMAddress, EVMStorage>,
 
     /// Balance of addresses
     pub balance: HashMap<EVMAddress, EVMU256>,
@@ -264,12 +288,53 @@ pub struct EVMState {
     pub integer_overflow: HashSet<(EVMAddress, usize, &'static str)>,
     #[serde(skip)]
     pub reentrancy_metadata: ReentrancyData,
+    /// Calls whose target or function selector is controlled by the fuzzer
+    /// input
+    #[serde(skip)]
+    pub tainted_calls: HashSet<TaintedCall>,
+    /// Storage slots holding values derived from the fuzzer input, kept
+    /// along the sequence so that the calls they control in the later
+    /// transactions are tracked
+    #[serde(default)]
+    pub tainted_slots: HashSet<(EVMAddress, EVMU256)>,
+    /// Functions (contract and selector) called by contracts during the
+    /// transaction that led to this state
+    #[serde(skip)]
+    pub call_path: HashSet<(EVMAddress, [u8; 4])>,
+    /// Calls to price sources made during the transaction that led to this
+    /// state
+    #[serde(skip)]
+    pub price_reads: HashSet<PriceRead>,
     #[serde(skip)]
     pub swap_data: SwapData,
+    /// Estimated gas used by the transaction that led to this state
+    #[serde(skip)]
+    pub gas_used: u64,
+    /// Contract and function called by the transaction that led to this state
+    #[serde(default)]
+    pub last_function: Option<(EVMAddress, [u8; 4])>,
+    /// Sender of the transaction that led to this state
+    #[serde(default)]
+    pub last_caller: Option<EVMAddress>,
+    /// Events emitted during the transaction that led to this state, only
+    /// recorded for the oracles inspecting them
+    #[serde(skip)]
+    pub logs: Vec<EmittedLog>,
+    /// Block environment of the transaction that led to this state, the next
+    /// transactions advance from it
+    #[serde(default)]
+    pub last_block: Option<BlockInfo>,
+    /// Outcome of the pending transaction of the victim, if the sequence
+    /// leading to this state included it
+    #[serde(default)]
+    pub victim: Option<VictimOutcome>,
 }
 
 pub trait EVMStateT {
     fn get_constraints(&self) -> Vec<Constraint>;
+    fn get_last_function(&self) -> Option<(EVMAddress, [u8; 4])>;
+    fn get_last_caller(&self) -> Option<EVMAddress>;
+    fn get_last_block(&self) -> Option<BlockInfo>;
 }
 
 impl EVMStateT for EVMState {
@@ -279,6 +344,18 @@ impl EVMStateT for EVMState {
             None => vec![],
         }
     }
+
+    fn get_last_function(&self) -> Option<(EVMAddress, [u8; 4])> {
+        self.last_function
+    }
+
+    fn get_last_caller(&self) -> Option<EVMAddress> {
+        self.last_caller
+    }
+
+    fn get_last_block(&self) -> Option<BlockInfo> {
+        self.last_block
+    }
 }
 
 impl VMStateT for EVMState {
@@ -363,18 +440,18 @@ impl EVMState {
     }
 
     /// Get all storage slots of a specific contract
-    pub fn get(&self, address: &EVMAddress) -> Option<&HashMap<EVMU256, EVMU256>> {
+    pub fn get(&self, address: &EVMAddress) -> Option<&EVMStorage> {
         self.state.get(address)
     }
 
     /// Get all storage slots of a specific contract (mutable)
-    pub fn get_mut(&mut self, address: &EVMAddress) -> Option<&mut HashMap<EVMU256, EVMU256>> {
+    pub fn get_mut(&mut self, address: &EVMAddress) -> Option<&mut EVMStorage> {
         self.state.get_mut(address)
     }
 
     /// Insert all storage slots of a specific contract
-    pub fn insert(&mut self, address: EVMAddress, storage: HashMap<EVMU256, EVMU256>) {
-        self.state.insert(address, storage);
+    pub fn insert(&mut self, address: EVMAddress, storage: impl Into<EVMStorage>) {
+        self.state.insert(address, storage.into());
     }
 
     /// Get balance of a specific address
@@ -419,9 +496,58 @@ where
     pub deployer: EVMAddress,
     /// Known arbitrary (caller,pc)
     pub _known_arbitrary: HashSet<(EVMAddress, usize)>,
+    /// Init code (without constructor args) of the contracts whose
+    /// constructor args are fuzzed
+    pub constructors: HashMap<EVMAddress, Bytes>,
+    /// Interpreters of the internal calls, e.g., of the swaps
+    pub interpreter_pool: InterpreterPool,
+    /// Records the call trees when tracing, see [`GenericVM::set_tracing`]
+    tracer: Option<Rc<RefCell<CallTracer>>>,
+    /// Traces of the transactions executed since they were last taken
+    traces: Vec<ExecutionTrace<EVMAddress>>,
     phandom: PhantomData<(EVMInput, VS, CI)>,
 }
 
+/// Stacks and memories of the interpreters of finished internal calls, reused
+/// by the next ones instead of allocating them again
+#[derive(Debug, Clone, Default)]
+pub struct InterpreterPool {
+    buffers: Vec<(Stack, Memory)>,
+}
+
+impl InterpreterPool {
+    /// Interpreter of the contract, as [`Interpreter::new_with_memory_limit`]
+    /// with the buffers of a released interpreter if any
+    pub fn acquire(&mut self, contract: Contract, gas_limit: u64, is_static: bool) -> Interpreter {
+        let Some((mut stack, mut memory)) = self.buffers.pop() else {
+            return Interpreter::new_with_memory_limit(contract, gas_limit, is_static, MEM_LIMIT);
+        };
+        stack.data.clear();
+        memory.data.clear();
+        // the fields are set as the constructor does, `test_interpreter_pool`
+        // catches the divergences on revm upgrades
+        Interpreter {
+            instruction_pointer: contract.bytecode.as_ptr(),
+            instruction_result: InstructionResult::Continue,
+            gas: Gas::new(gas_limit),
+            memory,
+            stack,
+            return_data_buffer: Bytes::new(),
+            return_range: 0..0,
+            is_static,
+            contract,
+            memory_limit: MEM_LIMIT,
+        }
+    }
+
+    /// Keep the buffers of the interpreter once its output is read
+    pub fn release(&mut self, interp: Interpreter) {
+        if self.buffers.len() < INTERPRETER_POOL_SIZE {
+            self.buffers.push((interp.stack, interp.memory));
+        }
+    }
+}
+
 pub fn is_reverted_or_control_leak(ret: &InstructionResult) -> bool {
     !matches!(
         *ret,
@@ -429,6 +555,19 @@ pub fn is_reverted_or_control_leak(ret: &InstructionResult) -> bool {
     )
 }
 
+/// Whether the frame reverted, discarding its changes including its logs, as
+/// opposed to succeeding or leaking the control
+pub fn is_frame_reverted(ret: &InstructionResult) -> bool {
+    is_reverted_or_control_leak(ret) &&
+        !matches!(
+            *ret,
+            InstructionResult::Continue |
+                ControlLeak |
+                InstructionResult::ArbitraryExternalCallAddressBounded(..) |
+                InstructionResult::AddressUnboundedStaticCall
+        )
+}
+
 /// Execution result that may have control leaked
 /// Contains raw information of revm output and execution
 #[derive(Clone, Debug)]
@@ -454,6 +593,7 @@ macro_rules! init_host {
         $host.call_count = 0;
         $host.jumpi_trace = 37;
         $host.current_typed_bug = vec![];
+        $host.current_logs = vec![];
         $host.randomness = vec![9];
         $host.transient_storage = HashMap::new();
         // Uncomment the next line if middleware is needed.
@@ -516,12 +656,41 @@ where
         (interp.return_value(), ret)
     }
 
+    /// Gas used by a call of `caller` to `address` on `vm_state`, None if the
+    /// call reverts
+    pub fn call_gas(
+        &mut self,
+        caller: EVMAddress,
+        address: EVMAddress,
+        data: Bytes,
+        value: EVMU256,
+        vm_state: &EVMState,
+        state: &mut EVMFuzzState,
+    ) -> Option<u64> {
+        self.host.evmstate = vm_state.clone();
+        init_host!(self.host);
+        self.host.gas_estimate = TX_BASE_GAS;
+        let ctx = CallContext {
+            address,
+            caller,
+            code_address: address,
+            apparent_value: value,
+            scheme: CallScheme::Call,
+        };
+        let (_, success) = execute_call_single!(ctx, self.host, state, &address, data);
+        (success && self.host.check_assert_result().is_none()).then_some(self.host.gas_estimate)
+    }
+
     /// Create a new EVM executor given a host and deployer address
     pub fn new(fuzz_host: FuzzHost<SC>, deployer: EVMAddress) -> Self {
         Self {
             host: fuzz_host,
             deployer,
             _known_arbitrary: Default::default(),
+            constructors: Default::default(),
+            interpreter_pool: Default::default(),
+            tracer: None,
+            traces: vec![],
             phandom: PhantomData,
         }
     }
@@ -554,7 +723,9 @@ where
             self.host.coverage_changed = false;
             self.host.bug_hit = false;
             self.host.current_typed_bug = vec![];
+            self.host.current_logs = vec![];
             self.host.jumpi_trace = 37;
+            self.host.gas_estimate = TX_BASE_GAS;
             self.host.current_self_destructs = vec![];
             self.host.current_arbitrary_calls = vec![];
             self.host.transient_storage = HashMap::new();
@@ -632,6 +803,8 @@ where
             r = self.host.run_inspect(&mut interp, state);
         }
 
+        self.host.evmstate.gas_used = self.host.gas_estimate;
+
         // Build the result
         let mut result = IntermediateExecutionResult {
             output: interp.return_value(),
@@ -661,6 +834,93 @@ where
         result
     }
 
+    /// Execute the constructor of the contract again with the constructor
+    /// args of the input, on an empty storage of the contract
+    ///
+    /// The runtime code is shared by all VM states, so constructor args ending
+    /// up in immutables (i.e., changing the runtime code) are reverted.
+    fn execute_deploy(
+        &mut self,
+        input: &EVMInput,
+        state: &mut EVMFuzzState,
+    ) -> ExecutionResult<EVMAddress, EVMAddress, VS, Vec<u8>, CI> {
+        let contract = input.get_contract();
+        let mut vm_state = unsafe { input.get_state().as_any().downcast_ref_unchecked::<EVMState>().clone() };
+        let reverted = ExecutionResult {
+            output: vec![],
+            reverted: true,
+            new_state: StagedVMState::new_uninitialized(),
+            additional_info: None,
+        };
+        let init_code = match self.constructors.get(&contract) {
+            Some(code) => code.clone(),
+            None => return reverted,
+        };
+        let args = input.get_data_abi().map(|abi| abi.get_bytes_vec()).unwrap_or_default();
+
+        vm_state.state.insert(contract, EVMStorage::new());
+        let mut data = Bytes::new();
+        unsafe {
+            invoke_middlewares!(
+                &mut self.host,
+                None,
+                state,
+                before_execute,
+                false,
+                &mut data,
+                &mut vm_state
+            );
+            STATE_CHANGE = false;
+        }
+        self.host.coverage_changed = false;
+        self.host.bug_hit = false;
+        self.host.current_typed_bug = vec![];
+        self.host.current_logs = vec![];
+        self.host.current_self_destructs = vec![];
+        self.host.current_arbitrary_calls = vec![];
+        self.host.transient_storage = HashMap::new();
+        self.host.gas_estimate = TX_BASE_GAS;
+        self.host.evmstate = vm_state;
+        self.host.env = input.get_vm_env().clone();
+        self.host.env.tx.caller = self.deployer;
+        self.host.access_pattern = input.get_access_pattern().clone();
+        self.host.call_count = 0;
+        self.host.randomness = input.get_randomness();
+
+        let call = Contract::new(
+            Bytes::new(),
+            Bytecode::new_raw(Bytes::from([init_code.to_vec(), args].concat())),
+            contract,
+            contract,
+            self.deployer,
+            EVMU256::ZERO,
+        );
+        let mut interp = Interpreter::new_with_memory_limit(call, 1e10 as u64, false, MEM_LIMIT);
+        let r = self.host.run_inspect(&mut interp, state);
+        let same_code = self
+            .host
+            .code
+            .get(&contract)
+            .is_some_and(|code| code.bytecode()[..code.len()] == interp.return_value()[..]);
+        if r != InstructionResult::Return || !same_code {
+            return reverted;
+        }
+
+        self.host.evmstate.gas_used = self.host.gas_estimate;
+        unsafe {
+            ExecutionResult {
+                output: vec![],
+                reverted: false,
+                new_state: StagedVMState::new_with_state(
+                    VMStateT::as_any(&self.host.evmstate.clone())
+                        .downcast_ref_unchecked::<VS>()
+                        .clone(),
+                ),
+                additional_info: None,
+            }
+        }
+    }
+
     /// Execute a transaction, wrapper of [`EVMExecutor::execute_from_pc`]
     fn execute_abi(
         &mut self,
@@ -670,6 +930,27 @@ where
         // Get necessary info from input
         let mut vm_state = unsafe { input.get_state().as_any().downcast_ref_unchecked::<EVMState>().clone() };
 
+        // the pending transaction can only be included once
+        let is_victim = input.get_input_type() == EVMInputTy::Victim;
+        if is_victim && vm_state.victim.is_some() {
+            return ExecutionResult {
+                output: vec![],
+                reverted: true,
+                new_state: StagedVMState::new_uninitialized(),
+                additional_info: None,
+            };
+        }
+        let flashloan_before = (vm_state.flashloan_data.earned, vm_state.flashloan_data.owed);
+
+        // the sequence goes on in a later block, with the debt not repaid yet
+        if !input.is_step() &&
+            vm_state
+                .last_block
+                .is_some_and(|last| input.get_vm_env().block.number > last.number)
+        {
+            vm_state.flashloan_data.carry_debt();
+        }
+
         // check balance
         #[cfg(feature = "real_balance")]
         {
@@ -826,6 +1107,32 @@ where
                 .chain(self.host.current_arbitrary_calls.iter().cloned()),
         );
 
+        if !input.is_step() {
+            r.new_state.last_function = input.get_data_abi().map(|abi| (input.get_contract(), abi.function));
+            r.new_state.last_caller = Some(input.get_caller());
+            // swaps done by the transaction, for the generated exploits, not
+            // those inherited from the previous ones of the sequence
+            r.new_state.swap_data = SwapData::new();
+            if let Some(mut abi) = input.get_data_abi() {
+                r.new_state.swap_data.push(
+                    &input.get_contract(),
+                    &mut abi,
+                    input.get_txn_value().unwrap_or_default(),
+                );
+            }
+        }
+        r.new_state.last_block = Some(BlockInfo {
+            timestamp: self.host.env.block.timestamp,
+            number: self.host.env.block.number,
+            basefee: self.host.env.block.basefee,
+        });
+
+        r.new_state.logs = if is_frame_reverted(&r.ret) {
+            vec![]
+        } else {
+            self.host.current_logs.clone()
+        };
+
         r.new_state.integer_overflow = HashSet::from_iter(
             vm_state
                 .integer_overflow
@@ -834,18 +1141,51 @@ where
                 .chain(self.host.current_integer_overflow.iter().cloned()),
         );
 
+        // a transaction that cannot fit in any block is infeasible
+        let exceeds_block_gas = r.new_state.gas_used > self.host.chain_profile.tx_gas_limit;
+        if exceeds_block_gas {
+            debug!("transaction exceeds block gas limit: {}", r.new_state.gas_used);
+        }
+
+        let reverted = exceeds_block_gas ||
+            !matches!(
+                r.ret,
+                InstructionResult::Return |
+                    InstructionResult::Stop |
+                    InstructionResult::ControlLeak |
+                    InstructionResult::SelfDestruct |
+                    InstructionResult::AddressUnboundedStaticCall |
+                    InstructionResult::ArbitraryExternalCallAddressBounded(_, _, _)
+            );
+        if !input.is_step() {
+            if let (Some(abi), Some(stats)) = (
+                input.get_data_abi(),
+                state.metadata_map_mut().get_mut::<FunctionStatsMetadata>(),
+            ) {
+                stats.record(input.get_contract(), abi.function, reverted);
+            }
+        }
+        // the NFTs transferred are only valued once the transaction is over, a
+        // control leak resumes it later
+        if r.ret != ControlLeak {
+            let pending = std::mem::take(&mut r.new_state.flashloan_data.pending_nfts);
+            if !reverted && !pending.is_empty() {
+                self.settle_nft_transfers(pending, &mut r.new_state, state);
+            }
+        }
+        if is_victim {
+            r.new_state.victim = Some(VictimOutcome {
+                reverted,
+                output: r.output.to_vec(),
+                earned_before: flashloan_before.0,
+                owed_before: flashloan_before.1,
+            });
+        }
+
         unsafe {
             ExecutionResult {
                 output: r.output.to_vec(),
-                reverted: !matches!(
-                    r.ret,
-                    InstructionResult::Return |
-                        InstructionResult::Stop |
-                        InstructionResult::ControlLeak |
-                        InstructionResult::SelfDestruct |
-                        InstructionResult::AddressUnboundedStaticCall |
-                        InstructionResult::ArbitraryExternalCallAddressBounded(_, _, _)
-                ),
+                reverted,
                 new_state: StagedVMState::new_with_state(
                     VMStateT::as_any(&r.new_state).downcast_ref_unchecked::<VS>().clone(),
                 ),
@@ -858,6 +1198,85 @@ where
         }
     }
 
+    /// Runtime code returned by the init code `code` (with the constructor
+    /// args) deployed at `address` on `vm_state`, the state being left as it
+    /// was, i.e., nothing is deployed
+    pub fn runtime_code(&mut self, code: Bytecode, address: EVMAddress, vm_state: &EVMState) -> Option<Bytecode> {
+        let deployer = Contract::new(Bytes::new(), code, address, address, self.deployer, EVMU256::from(0));
+        let previous = std::mem::replace(&mut self.host.evmstate, vm_state.clone());
+        unsafe {
+            IN_DEPLOY = true;
+        }
+        let mut interp = Interpreter::new_with_memory_limit(deployer, 1e10 as u64, false, MEM_LIMIT);
+        let mut dummy_state = EVMFuzzState::default();
+        let r = self.host.run_inspect(&mut interp, &mut dummy_state);
+        unsafe {
+            IN_DEPLOY = false;
+        }
+        self.host.evmstate = previous;
+        if r != InstructionResult::Return {
+            error!("deploy failed: {:?}", r);
+            return None;
+        }
+        Some(Bytecode::new_raw(interp.return_value()))
+    }
+
+    /// Credit the NFTs received by the attacker at the floor price of their
+    /// collection if `ownerOf` / `balanceOf` confirm it holds them, and debit
+    /// those it sent away
+    fn settle_nft_transfers(&mut self, pending: Vec<NFTTransfer>, new_state: &mut EVMState, state: &mut EVMFuzzState) {
+        let Some(flashloan) = self.host.flashloan_middleware.clone() else {
+            return;
+        };
+        let previous = std::mem::replace(&mut self.host.evmstate, new_state.clone());
+        unsafe {
+            IS_FAST_CALL_STATIC = true;
+        }
+        for transfer in pending {
+            if !self.host.code.contains_key(&transfer.collection) {
+                continue;
+            }
+            let (to_attacker, from_attacker) = (state.has_caller(&transfer.to), state.has_caller(&transfer.from));
+            let mut held = |holder: EVMAddress| {
+                let ctx = CallContext {
+                    address: transfer.collection,
+                    caller: Default::default(),
+                    code_address: transfer.collection,
+                    apparent_value: Default::default(),
+                    scheme: CallScheme::StaticCall,
+                };
+                let data = Bytes::from(transfer.holding_call(holder));
+                let (output, success) = execute_call_single!(ctx, self.host, state, &transfer.collection, data);
+                if success {
+                    transfer.held(holder, &output)
+                } else {
+                    EVMU256::ZERO
+                }
+            };
+            let received = if to_attacker {
+                held(transfer.to).min(transfer.amount)
+            } else {
+                EVMU256::ZERO
+            };
+            let sent = if !from_attacker {
+                EVMU256::ZERO
+            } else if transfer.erc1155 {
+                // the balance left after an ERC-1155 transfer does not tell
+                // whether it happened, so it is always debited
+                transfer.amount
+            } else {
+                transfer.amount.saturating_sub(held(transfer.from))
+            };
+            let flashloan = flashloan.deref().borrow();
+            new_state.flashloan_data.earned += flashloan.nft_value(&transfer.collection, received);
+            new_state.flashloan_data.owed += flashloan.nft_value(&transfer.collection, sent);
+        }
+        unsafe {
+            IS_FAST_CALL_STATIC = false;
+        }
+        self.host.evmstate = previous;
+    }
+
     pub fn reexecute_with_middleware(
         &mut self,
         input: &EVMInput,
@@ -990,7 +1409,7 @@ where
     ) -> ExecutionResult<EVMAddress, EVMAddress, VS, Vec<u8>, CI> {
         use super::host::clear_branch_status;
         clear_branch_status();
-        match input.get_input_type() {
+        let result = match input.get_input_type() {
             // buy (borrow because we have infinite ETH) tokens with ETH using uniswap
             EVMInputTy::Borrow => {
                 let token = input.get_contract();
@@ -1015,18 +1434,23 @@ where
                     self,
                     input.get_randomness().as_slice(),
                 ) {
-                    Some(()) => unsafe {
-                        ExecutionResult {
-                            output: vec![],
-                            reverted: false,
-                            new_state: StagedVMState::new_with_state(
-                                VMStateT::as_any(&self.host.evmstate.clone())
-                                    .downcast_ref_unchecked::<VS>()
-                                    .clone(),
-                            ),
-                            additional_info: None,
+                    Some(()) => {
+                        // the swaps are executed as internal calls, so the gas used
+                        // by them does not represent the borrow transaction
+                        self.host.evmstate.gas_used = BORROW_GAS_ESTIMATE;
+                        unsafe {
+                            ExecutionResult {
+                                output: vec![],
+                                reverted: false,
+                                new_state: StagedVMState::new_with_state(
+                                    VMStateT::as_any(&self.host.evmstate.clone())
+                                        .downcast_ref_unchecked::<VS>()
+                                        .clone(),
+                                ),
+                                additional_info: None,
+                            }
                         }
-                    },
+                    }
                     None => {
                         ExecutionResult {
                             // we don't have enough liquidity to buy the token
@@ -1046,8 +1470,16 @@ where
                 unreachable!("liquidate should be handled by middleware");
             }
             EVMInputTy::ABI => self.execute_abi(input, state),
-            EVMInputTy::ArbitraryCallBoundedAddr => self.execute_abi(input, state),
+            EVMInputTy::ArbitraryCallBoundedAddr | EVMInputTy::Victim => self.execute_abi(input, state),
+            EVMInputTy::Deploy => self.execute_deploy(input, state),
+        };
+        add_interesting_values(state, self.host.interesting_values.drain(..));
+        if let Some(tracer) = &self.tracer {
+            if let Some(call_tree) = tracer.borrow_mut().finish_tx(result.reverted, &result.output) {
+                self.traces.push(call_tree.to_execution_trace());
+            }
         }
+        result
     }
 
     /// Execute a static call
@@ -1066,6 +1498,7 @@ where
             self.host.call_count = 0;
             self.host.jumpi_trace = 37;
             self.host.current_typed_bug = vec![];
+            self.host.current_logs = vec![];
             self.host.randomness = vec![9];
         }
 
@@ -1158,24 +1591,52 @@ where
     fn as_any(&mut self) -> &mut dyn Any {
         self
     }
+
+    fn set_tracing(&mut self, enabled: bool) {
+        if enabled == self.tracer.is_some() {
+            return;
+        }
+        if enabled {
+            let tracer = Rc::new(RefCell::new(CallTracer::default()));
+            self.host.add_middlewares(tracer.clone());
+            self.tracer = Some(tracer);
+        } else if let Some(tracer) = self.tracer.take() {
+            // only ours, the call tracer of `--trace` may be attached too
+            let ours = Rc::as_ptr(&tracer) as *const ();
+            self.host
+                .middlewares
+                .write()
+                .unwrap()
+                .retain(|middleware| Rc::as_ptr(middleware) as *const () != ours);
+        }
+    }
+
+    fn take_traces(&mut self) -> Vec<ExecutionTrace<EVMAddress>> {
+        std::mem::take(&mut self.traces)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{cell::RefCell, collections::HashMap, path::Path, rc::Rc};
+    use std::{cell::RefCell, collections::HashMap, path::Path, rc::Rc, time::Instant};
 
     use bytes::Bytes;
     use libafl::prelude::StdScheduler;
     use libafl_bolts::tuples::tuple_list;
+    use revm_interpreter::{CallContext, CallScheme, Contract, Interpreter};
     use revm_primitives::Bytecode;
     use tracing::debug;
 
+    use super::{InterpreterPool, MEM_LIMIT};
     use crate::{
         evm::{
-            host::{FuzzHost, JMP_MAP},
+            abi::get_abi_type_boxed,
+            code_analysis::analyze,
+            host::{FuzzHost, JMP_MAP, RECORD_LOGS},
             input::{ConciseEVMInput, EVMInput, EVMInputTy},
+            middlewares::{call_taint::CallTaintTracer, middleware::MiddlewareType},
             mutator::AccessPattern,
-            types::{generate_random_address, EVMFuzzState, EVMU256},
+            types::{generate_random_address, EVMAddress, EVMFuzzState, EVMU256},
             vm::{EVMExecutor, EVMState},
         },
         generic_vm::vm_executor::{GenericVM, MAP_SIZE},
@@ -1230,6 +1691,7 @@ mod tests {
             env: Default::default(),
             access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
             liquidation_percent: 0,
+            liquidation_path: 0,
             direct_data: Bytes::from(
                 [
                     function_hash.clone(),
@@ -1268,6 +1730,7 @@ mod tests {
             env: Default::default(),
             access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
             liquidation_percent: 0,
+            liquidation_path: 0,
             direct_data: Bytes::from(
                 [
                     function_hash.clone(),
@@ -1296,4 +1759,341 @@ mod tests {
         assert!(cov_changed);
         assert!(execution_result_5.reverted);
     }
+
+    #[test]
+    fn test_execute_deploy() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> = EVMExecutor::new(
+            FuzzHost::new(StdScheduler::new(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+
+        // stores the constructor arg into slot 0 and returns STOP as runtime code
+        let init_code = hex::decode("6020601260003960005160005560016020f3").unwrap();
+        let address = generate_random_address(&mut state);
+        evm_executor
+            .deploy(
+                Bytecode::new_raw(Bytes::from([init_code.clone(), vec![0; 32]].concat())),
+                None,
+                address,
+                &mut FuzzState::new(0),
+            )
+            .unwrap();
+        evm_executor.constructors.insert(address, Bytes::from(init_code));
+
+        let mut abi = get_abi_type_boxed("(uint256)");
+        abi.b.set_bytes([vec![0; 31], vec![7]].concat());
+        let input = EVMInput {
+            caller: evm_executor.deployer,
+            contract: address,
+            data: Some(abi),
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            direct_data: Bytes::new(),
+            input_type: EVMInputTy::Deploy,
+            randomness: vec![],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        };
+
+        let result = evm_executor.execute(&input, &mut state);
+        assert!(!result.reverted);
+        assert_eq!(result.new_state.state.state[&address][&EVMU256::ZERO], EVMU256::from(7));
+    }
+
+    #[test]
+    fn test_reverted_logs() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> = EVMExecutor::new(
+            FuzzHost::new(StdScheduler::new(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+        unsafe {
+            RECORD_LOGS = true;
+        }
+
+        // LOG0 of no data then REVERT
+        let callee = generate_random_address(&mut state);
+        let callee_code = hex::decode("60006000a060006000fd").unwrap();
+        evm_executor
+            .host
+            .set_code(callee, Bytecode::new_raw(Bytes::from(callee_code)), &mut state);
+        // CALL callee, then LOG0 of one byte
+        let caller = generate_random_address(&mut state);
+        let caller_code = [
+            hex::decode("60006000600060006000").unwrap(),
+            [vec![0x73], callee.0.to_vec()].concat(),
+            hex::decode("5af15060016000a000").unwrap(),
+        ]
+        .concat();
+        evm_executor
+            .host
+            .set_code(caller, Bytecode::new_raw(Bytes::from(caller_code)), &mut state);
+
+        let input = EVMInput {
+            caller: evm_executor.deployer,
+            contract: caller,
+            data: Some(get_abi_type_boxed("()")),
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            direct_data: Bytes::new(),
+            input_type: EVMInputTy::ABI,
+            randomness: vec![],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        };
+        let result = evm_executor.execute(&input, &mut state);
+        assert!(!result.reverted);
+        // the log of the reverted call is dropped
+        assert_eq!(result.new_state.state.logs.len(), 1);
+        assert_eq!(result.new_state.state.logs[0].address, caller);
+
+        let mut input = input;
+        input.contract = callee;
+        let result = evm_executor.execute(&input, &mut state);
+        assert!(result.reverted);
+        assert!(result.new_state.state.logs.is_empty());
+    }
+
+    #[test]
+    fn test_storage_taint() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> = EVMExecutor::new(
+            FuzzHost::new(StdScheduler::new(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+        evm_executor
+            .host
+            .add_middlewares(Rc::new(RefCell::new(CallTaintTracer::new())));
+
+        // with an argument, SSTORE it at slot 0, otherwise CALL the address
+        // at slot 0
+        let contract = generate_random_address(&mut state);
+        let code = hex::decode("36600410601757600060006000600060006000545af1005b60043560005500").unwrap();
+        evm_executor
+            .host
+            .set_code(contract, Bytecode::new_raw(Bytes::from(code)), &mut state);
+
+        let target = generate_random_address(&mut state);
+        let input = EVMInput {
+            caller: evm_executor.deployer,
+            contract,
+            data: None,
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            direct_data: Bytes::from([vec![0; 16], target.0.to_vec()].concat()),
+            input_type: EVMInputTy::ABI,
+            randomness: vec![],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        };
+        let result = evm_executor.execute(&input, &mut state);
+        assert!(!result.reverted);
+        assert!(result
+            .new_state
+            .state
+            .tainted_slots
+            .contains(&(contract, EVMU256::ZERO)));
+        assert!(result.new_state.state.tainted_calls.is_empty());
+
+        // the next transaction calls the address the previous one stored
+        let mut input = input;
+        input.sstate = result.new_state.clone();
+        input.direct_data = Bytes::new();
+        let result = evm_executor.execute(&input, &mut state);
+        assert!(!result.reverted);
+        let calls = result.new_state.state.tainted_calls.iter().collect::<Vec<_>>();
+        assert_eq!(calls.len(), 1);
+        assert_eq!((calls[0].target, calls[0].tainted_target), (target, true));
+    }
+
+    #[test]
+    fn test_tracing() {
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut evm_executor: EVMExecutor<EVMState, ConciseEVMInput, StdScheduler<EVMFuzzState>> = EVMExecutor::new(
+            FuzzHost::new(StdScheduler::new(), "work_dir".to_string()),
+            generate_random_address(&mut state),
+        );
+        // STOP
+        let callee = generate_random_address(&mut state);
+        evm_executor
+            .host
+            .set_code(callee, Bytecode::new_raw(Bytes::from(vec![0x00])), &mut state);
+        // CALL callee, then STOP
+        let caller = generate_random_address(&mut state);
+        let caller_code = [
+            hex::decode("60006000600060006000").unwrap(),
+            [vec![0x73], callee.0.to_vec()].concat(),
+            hex::decode("5af15000").unwrap(),
+        ]
+        .concat();
+        evm_executor
+            .host
+            .set_code(caller, Bytecode::new_raw(Bytes::from(caller_code)), &mut state);
+
+        let input = EVMInput {
+            caller: evm_executor.deployer,
+            contract: caller,
+            data: Some(get_abi_type_boxed("()")),
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            direct_data: Bytes::new(),
+            input_type: EVMInputTy::ABI,
+            randomness: vec![],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        };
+        evm_executor.execute(&input, &mut state);
+        assert!(evm_executor.take_traces().is_empty());
+
+        evm_executor.set_tracing(true);
+        evm_executor.execute(&input, &mut state);
+        evm_executor.set_tracing(false);
+        evm_executor.execute(&input, &mut state);
+        let traces = evm_executor.take_traces();
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].calls().count(), 2);
+        assert_eq!(traces[0].success(), Some(true));
+        // the tracer is detached
+        assert!(evm_executor
+            .host
+            .middlewares
+            .read()
+            .unwrap()
+            .iter()
+            .all(|middleware| middleware.borrow().get_type() != MiddlewareType::CallTracer));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let path = Path::new("work_dir");
+        if !path.exists() {
+            std::fs::create_dir(path).unwrap();
+        }
+        let mut host: FuzzHost<StdScheduler<EVMFuzzState>> = FuzzHost::new(StdScheduler::new(), "work_dir".to_string());
+        let address = EVMAddress::from_slice(&[0x10; 20]);
+        host.evmstate.sstore(address, EVMU256::ZERO, EVMU256::from(1));
+        let snapshot = host.snapshot();
+        host.evmstate.sstore(address, EVMU256::ZERO, EVMU256::from(2));
+        let later = host.snapshot();
+
+        assert!(host.revert_to(snapshot));
+        assert_eq!(host.evmstate.sload(address, EVMU256::ZERO), Some(EVMU256::from(1)));
+        // the snapshots taken after the restored one are discarded
+        assert!(!host.revert_to(later));
+    }
+
+    /// Host and contract returning 42
+    fn pool_call() -> (FuzzHost<StdScheduler<EVMFuzzState>>, Contract) {
+        let path = Path::new("work_dir");
+        if !path.exists() {
+            std::fs::create_dir(path).unwrap();
+        }
+        let host = FuzzHost::new(StdScheduler::new(), "work_dir".to_string());
+        // mstore(0, 42) return(0, 32)
+        let code = Bytecode::new_raw(Bytes::from(hex::decode("602a60005260206000f3").unwrap()));
+        let contract = Contract::new_with_context_analyzed(
+            Bytes::new(),
+            analyze(code),
+            &CallContext {
+                address: EVMAddress::zero(),
+                caller: EVMAddress::zero(),
+                code_address: EVMAddress::zero(),
+                apparent_value: EVMU256::ZERO,
+                scheme: CallScheme::Call,
+            },
+        );
+        (host, contract)
+    }
+
+    #[test]
+    fn test_interpreter_pool() {
+        let (mut host, contract) = pool_call();
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut pool = InterpreterPool::default();
+        for _ in 0..2 {
+            let mut interp = pool.acquire(contract.clone(), 1e10 as u64, false);
+            assert!(interp.memory.data.is_empty() && interp.stack.data.is_empty());
+            host.run_inspect(&mut interp, &mut state);
+            assert_eq!(
+                EVMU256::try_from_be_slice(&interp.return_value()),
+                Some(EVMU256::from(42))
+            );
+            pool.release(interp);
+        }
+        assert_eq!(pool.buffers.len(), 1);
+
+        // a reused interpreter starts as a new one
+        let interp = pool.acquire(contract.clone(), 1e10 as u64, true);
+        let new = Interpreter::new_with_memory_limit(contract, 1e10 as u64, true, MEM_LIMIT);
+        assert_eq!(interp.instruction_pointer, new.instruction_pointer);
+        assert_eq!(interp.instruction_result, new.instruction_result);
+        assert_eq!(
+            (interp.gas.limit(), interp.gas.remaining(), interp.gas.refunded()),
+            (new.gas.limit(), new.gas.remaining(), new.gas.refunded())
+        );
+        assert_eq!(interp.memory.data, new.memory.data);
+        assert_eq!(interp.stack.data, new.stack.data);
+        assert_eq!(interp.return_data_buffer, new.return_data_buffer);
+        assert_eq!(interp.return_range, new.return_range);
+        assert_eq!(interp.is_static, new.is_static);
+        assert_eq!(interp.contract.address, new.contract.address);
+        assert_eq!(interp.contract.input, new.contract.input);
+        assert_eq!(interp.memory_limit, new.memory_limit);
+    }
+
+    /// Executions per second of internal calls with allocated and pooled
+    /// interpreters, run with
+    /// `cargo test --release bench_interpreter_pool -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_interpreter_pool() {
+        let (mut host, contract) = pool_call();
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let mut pool = InterpreterPool::default();
+        let execs = 100_000;
+        for pooled in [false, true] {
+            let start = Instant::now();
+            for _ in 0..execs {
+                let mut interp = if pooled {
+                    pool.acquire(contract.clone(), 1e10 as u64, false)
+                } else {
+                    Interpreter::new_with_memory_limit(contract.clone(), 1e10 as u64, false, MEM_LIMIT)
+                };
+                host.run_inspect(&mut interp, &mut state);
+                if pooled {
+                    pool.release(interp);
+                }
+            }
+            println!(
+                "pooled: {}, exec/sec: {:.0}",
+                pooled,
+                execs as f64 / start.elapsed().as_secs_f64()
+            );
+        }
+    }
 }
@@ -2,6 +2,7 @@
 use std::cell::RefCell;
 use std::{
     fmt::{Debug, Formatter},
+    fs,
     marker::PhantomData,
     ops::Deref,
     rc::Rc,
@@ -14,10 +15,15 @@ use libafl::{
     state::{State, UsesState},
     Error,
 };
-use serde::{de::DeserializeOwned, Serialize};
+use libafl_bolts::impl_serdeany;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
-    generic_vm::{vm_executor::GenericVM, vm_state::VMStateT},
+    generic_vm::{
+        vm_executor::{ExecutionResult, GenericVM},
+        vm_state::VMStateT,
+    },
     input::{ConciseSerde, VMInputT},
     state::HasExecutionResult,
 };
@@ -40,6 +46,9 @@ where
     pub vm: Rc<RefCell<dyn GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI>>>,
     /// Observers (e.g., coverage)
     observers: OT,
+    /// Directory of the inputs quarantined by the determinism check, which
+    /// is enabled when set
+    quarantine_dir: Option<String>,
     phantom: PhantomData<(I, S, Addr, Out)>,
 }
 
@@ -110,9 +119,47 @@ where
         Self {
             vm: vm_executor,
             observers,
+            quarantine_dir: None,
             phantom: PhantomData,
         }
     }
+
+    /// Execute each input twice from the same state and quarantine the
+    /// inputs whose executions differ into `quarantine_dir`
+    pub fn enable_determinism_check(&mut self, quarantine_dir: &str) {
+        self.quarantine_dir = Some(quarantine_dir.to_string());
+    }
+}
+
+/// Inputs whose executions were not deterministic
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct QuarantineMetadata {
+    pub count: usize,
+}
+
+impl_serdeany!(QuarantineMetadata);
+
+/// Whether two executions of the same input from the same state agree
+fn same_execution<Loc, Addr, VS, Out, CI>(
+    a: &ExecutionResult<Loc, Addr, VS, Out, CI>,
+    b: &ExecutionResult<Loc, Addr, VS, Out, CI>,
+) -> bool
+where
+    VS: Default + VMStateT,
+    Addr: Serialize + DeserializeOwned + Debug,
+    Loc: Serialize + DeserializeOwned + Debug,
+    Out: Default + Into<Vec<u8>> + Clone,
+    CI: Serialize + DeserializeOwned + Debug + Clone + ConciseSerde,
+{
+    a.reverted == b.reverted &&
+        Into::<Vec<u8>>::into(a.output.clone()) == Into::<Vec<u8>>::into(b.output.clone()) &&
+        a.new_state.state.get_hash() == b.new_state.state.get_hash()
+}
+
+/// Write the quarantined input to `quarantine_dir` in the replayable format
+fn quarantine<CI: ConciseSerde>(quarantine_dir: &str, count: usize, input: &CI) {
+    let _ = fs::create_dir_all(quarantine_dir);
+    let _ = fs::write(format!("{}/{}", quarantine_dir, count), input.serialize_concise());
 }
 
 impl<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, OT, EM, Z, CI> Executor<EM, Z>
@@ -137,7 +184,27 @@ where
         _mgr: &mut EM,
         input: &Self::Input,
     ) -> Result<ExitKind, Error> {
-        let res = self.vm.deref().borrow_mut().execute(input, state);
+        let mut res = self.vm.deref().borrow_mut().execute(input, state);
+        if let Some(quarantine_dir) = &self.quarantine_dir {
+            // reset the coverage of the first run so that it is not counted twice
+            self.observers.pre_exec_all(state, input)?;
+            let rerun = self.vm.deref().borrow_mut().execute(input, state);
+            if !same_execution(&res, &rerun) {
+                if !state.has_metadata::<QuarantineMetadata>() {
+                    state.metadata_map_mut().insert(QuarantineMetadata::default());
+                }
+                let meta = state.metadata_map_mut().get_mut::<QuarantineMetadata>().unwrap();
+                meta.count += 1;
+                let concise = input.get_concise(&res);
+                warn!(
+                    "nondeterministic execution of input, quarantined:\n{}",
+                    concise.serialize_string()
+                );
+                quarantine(quarantine_dir, meta.count, &concise);
+                // reverted results are never added to the infant state corpus
+                res.reverted = true;
+            }
+        }
         // the execution result is added to the fuzzer state
         // later the feedback/objective can run oracle on this result
         state.set_execution_result(res);
@@ -167,3 +234,46 @@ where
         &mut self.observers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::{
+        input::ConciseEVMInput,
+        types::{EVMAddress, EVMU256},
+        vm::EVMState,
+    };
+
+    type EVMExecutionResult = ExecutionResult<EVMAddress, EVMAddress, EVMState, Vec<u8>, ConciseEVMInput>;
+
+    #[test]
+    fn test_same_execution() {
+        let a = EVMExecutionResult::empty_result();
+        let mut b = EVMExecutionResult::empty_result();
+        assert!(same_execution(&a, &b));
+
+        b.output = vec![1];
+        assert!(!same_execution(&a, &b));
+
+        b.output = vec![];
+        b.new_state
+            .state
+            .sstore(EVMAddress::zero(), EVMU256::from(1), EVMU256::from(1));
+        assert!(!same_execution(&a, &b));
+    }
+
+    #[test]
+    fn test_quarantine() {
+        let dir = std::env::temp_dir().join(format!("ityfuzz_quarantine_{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let input = ConciseEVMInput {
+            caller: EVMAddress::from_low_u64_be(1),
+            ..Default::default()
+        };
+        quarantine(dir, 1, &input);
+
+        let replayed = ConciseEVMInput::deserialize_concise(&fs::read(format!("{}/1", dir)).unwrap());
+        assert_eq!(replayed.caller, input.caller);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
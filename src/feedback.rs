@@ -120,7 +120,8 @@ where
     /// specified in the bug_idx.
     pub fn reproduces(&mut self, state: &mut S, input: &S::Input, bug_idx: &[u64]) -> bool {
         let initial_oracle_output = unsafe { ORACLE_OUTPUT.clone() };
-        if state.get_execution_result().reverted {
+        let reverted = state.get_execution_result().reverted;
+        if reverted && !self.oracle.iter().any(|oracle| oracle.deref().borrow().on_revert()) {
             return false;
         }
         // set up oracle context
@@ -139,10 +140,12 @@ where
             };
         }
 
-        // execute producers
-        self.producers.iter().for_each(|producer| {
-            producer.deref().borrow_mut().produce(&mut oracle_ctx);
-        });
+        // execute producers, only needed by the oracles of successful executions
+        if !reverted {
+            self.producers.iter().for_each(|producer| {
+                producer.deref().borrow_mut().produce(&mut oracle_ctx);
+            });
+        }
 
         let mut bug_to_hit = bug_idx.to_owned();
         let has_post_exec = oracle_ctx
@@ -154,6 +157,9 @@ where
 
         // execute oracles and update stages if needed
         for idx in 0..self.oracle.len() {
+            if reverted && !self.oracle[idx].deref().borrow().on_revert() {
+                continue;
+            }
             let original_stage = if idx >= input.get_staged_state().stage.len() {
                 0
             } else {
@@ -200,7 +206,8 @@ where
 
     /// Called after every execution.
     /// It executes the producers and then oracles after each successful
-    /// execution. Returns true if any of the oracle returns true.
+    /// execution, and the oracles called on revert after each reverted one.
+    /// Returns true if any of the oracle returns true.
     fn is_interesting<EMI, OT>(
         &mut self,
         state: &mut S,
@@ -213,7 +220,8 @@ where
         EMI: EventFirer<State = S>,
         OT: ObserversTuple<S>,
     {
-        if state.get_execution_result().reverted {
+        let reverted = state.get_execution_result().reverted;
+        if reverted && !self.oracle.iter().any(|oracle| oracle.deref().borrow().on_revert()) {
             return Ok(false);
         }
         {
@@ -242,10 +250,12 @@ where
             };
         }
 
-        // execute producers
-        self.producers.iter().for_each(|producer| {
-            producer.deref().borrow_mut().produce(&mut oracle_ctx);
-        });
+        // execute producers, only needed by the oracles of successful executions
+        if !reverted {
+            self.producers.iter().for_each(|producer| {
+                producer.deref().borrow_mut().produce(&mut oracle_ctx);
+            });
+        }
 
         let mut is_any_bug_hit = false;
         let has_post_exec = oracle_ctx
@@ -257,6 +267,9 @@ where
 
         // execute oracles and update stages if needed
         for idx in 0..self.oracle.len() {
+            if reverted && !self.oracle[idx].deref().borrow().on_revert() {
+                continue;
+            }
             let original_stage = if idx >= input.get_staged_state().stage.len() {
                 0
             } else {
@@ -39,21 +39,33 @@ use libafl::{
 };
 use libafl_bolts::current_time;
 use serde::{de::DeserializeOwned, Serialize};
-use tracing::info;
+use serde_json::json;
+use tracing::{debug, error, info};
 
 use crate::{
-    evm::{host::JMP_MAP, solution, utils::prettify_concise_inputs},
+    checkpoint::{add_checkpoint, CheckpointMetadata},
+    evm::{host::JMP_MAP, privileged::is_privileged_sender, solution, types::EVMU256, utils::prettify_concise_inputs},
     feedback::CmpMetadata,
     generic_vm::{vm_executor::MAP_SIZE, vm_state::VMStateT},
     input::{ConciseSerde, SolutionTx, VMInputT},
+    logger,
+    metrics,
     minimizer::SequentialMinimizer,
     oracle::BugMetadata,
-    r#const::INFANT_STATE_INITIAL_VOTES,
+    persistence::{load_campaign, save_campaign},
+    r#const::{INFANT_STATE_INITIAL_VOTES, SUMMARY_FILE},
     scheduler::HasReportCorpus,
+    sequence_length::SequenceLengthMetadata,
     state::{HasCurrentInputIdx, HasExecutionResult, HasInfantStateState, HasItyState, InfantStateState},
+    stop_conditions::STOP_CONDITIONS,
+    telemetry,
 };
 
 pub static mut RUN_FOREVER: bool = false;
+/// Stop fuzzing after this number of executions, unlimited if None
+pub static mut MAX_EXECUTIONS: Option<usize> = None;
+/// Save the campaign every this number of seconds, never if None
+pub static mut SAVE_INTERVAL: Option<u64> = None;
 pub static mut ORACLE_OUTPUT: Vec<serde_json::Value> = vec![];
 
 /// A fuzzer that implements ItyFuzz logic using LibAFL's [`Fuzzer`] trait
@@ -194,6 +206,29 @@ where
         }
         None
     }
+
+    /// Save the campaign to the work dir, see [`crate::persistence`]
+    pub fn save_campaign(&self, state: &S) {
+        match save_campaign(&self.work_dir, state, &self.minimizer_map) {
+            Ok(()) => debug!("Saved campaign to {}", self.work_dir),
+            Err(e) => error!("Failed to save campaign: {}", e),
+        }
+    }
+
+    /// Flush the campaign to the work dir, print the summary and exit
+    fn stop(&self, state: &S) -> ! {
+        self.save_campaign(state);
+        print_summary(&self.work_dir);
+        exit(0);
+    }
+
+    /// Replace the state with the campaign last saved to the work dir
+    pub fn resume(&mut self, state: &mut S) -> Result<(), Error> {
+        let (saved_state, minimizer_map) = load_campaign(&self.work_dir)?;
+        *state = saved_state;
+        self.minimizer_map = minimizer_map;
+        Ok(())
+    }
 }
 
 impl<VS, Loc, Addr, Out, CS, IS, F, IF, IFR, I, OF, S, OT, CI, SM> UsesState
@@ -278,13 +313,42 @@ where
                 .parse::<u64>()
                 .unwrap(),
         );
+        let save_interval = unsafe { SAVE_INTERVAL }.map(Duration::from_secs);
+        let mut last_save = current_time();
+        let start = current_time();
         loop {
             self.fuzz_one(stages, executor, state, manager)?;
             manager.maybe_report_progress(state, reporting_interval)?;
+            if let Some(interval) = save_interval &&
+                current_time() - last_save >= interval
+            {
+                self.save_campaign(state);
+                last_save = current_time();
+            }
+            if let Some(max_execs) = unsafe { MAX_EXECUTIONS } &&
+                *state.executions() >= max_execs
+            {
+                info!("Reached the maximum number of executions ({}), stopping", max_execs);
+                self.stop(state);
+            }
+            if let Some(reason) =
+                unsafe { STOP_CONDITIONS.reached(current_time() - start, &metrics::metrics().coverage()) }
+            {
+                info!("{}, stopping", reason);
+                self.stop(state);
+            }
         }
     }
 }
 
+/// Print the next steps suggested by the last campaign summary written to the
+/// work dir, if any
+fn print_summary(work_dir: &str) {
+    if let Ok(summary) = std::fs::read_to_string(format!("{}/{}", work_dir, SUMMARY_FILE)) {
+        info!("============= Next Steps =============\n{}", summary.trim_end());
+    }
+}
+
 #[cfg(feature = "print_txn_corpus")]
 pub static mut DUMP_FILE_COUNT: usize = 0;
 
@@ -363,7 +427,9 @@ impl<VS, Loc, Addr, Out, E, EM, I, S, CS, IS, F, IF, IFR, OF, OT, CI, SM> Evalua
     for ItyFuzzer<VS, Loc, Addr, Out, CS, IS, F, IF, IFR, I, OF, S, OT, CI, SM>
 where
     CS: Scheduler<State = S> + RemovableScheduler,
-    IS: Scheduler<State = InfantStateState<Loc, Addr, VS, CI>> + HasReportCorpus<InfantStateState<Loc, Addr, VS, CI>>,
+    IS: Scheduler<State = InfantStateState<Loc, Addr, VS, CI>>
+        + RemovableScheduler
+        + HasReportCorpus<InfantStateState<Loc, Addr, VS, CI>>,
     F: Feedback<S>,
     IF: Feedback<S>,
     IFR: Feedback<S>,
@@ -428,12 +494,14 @@ where
             .objective
             .is_interesting(state, manager, &input, observers, &exitkind)?;
 
+        // the new VM state is the result of the `depth`-th transaction of the sequence
+        state.get_execution_result_mut().new_state.trace.derived_time = input.get_staged_state().trace.derived_time + 1;
+        let depth = state.get_execution_result().new_state.trace.derived_time as usize;
+
         // add the trace of the new state
         #[cfg(any(feature = "print_infant_corpus", feature = "print_txn_corpus"))]
         {
             state.get_execution_result_mut().new_state.trace.from_idx = Some(input.get_state_idx());
-            state.get_execution_result_mut().new_state.trace.derived_time =
-                input.get_staged_state().trace.derived_time + 1;
             state
                 .get_execution_result_mut()
                 .new_state
@@ -441,9 +509,32 @@ where
                 .add_input(concise_input);
         }
 
+        if !state.has_metadata::<SequenceLengthMetadata>() {
+            state.add_metadata(SequenceLengthMetadata::default());
+        }
+        let seq_len_allowed = state.metadata::<SequenceLengthMetadata>().unwrap().allows(depth);
+
+        // keep the new VM state as a checkpoint every K transactions of a sequence so
+        // that mutation can resume from it instead of re-executing the prefix
+        let is_checkpoint = !is_infant_interesting &&
+            !reverted &&
+            seq_len_allowed &&
+            state
+                .metadata_map_mut()
+                .get_mut::<CheckpointMetadata>()
+                .is_some_and(|meta| meta.should_checkpoint(depth));
+
         // add the new VM state to infant state corpus if it is interesting
         let mut state_idx = input.get_state_idx();
-        if is_infant_interesting && !reverted {
+        if is_checkpoint {
+            state_idx = add_checkpoint(
+                state,
+                &mut self.infant_scheduler,
+                &state.get_execution_result().new_state.clone(),
+                input.get_state_idx(),
+            );
+        }
+        if is_infant_interesting && !reverted && seq_len_allowed {
             state_idx = state.add_infant_state(
                 &state.get_execution_result().new_state.clone(),
                 &mut self.infant_scheduler,
@@ -463,7 +554,8 @@ where
         }
 
         let mut res = ExecuteInputResult::None;
-        if is_solution && !reverted {
+        // reverted executions are only solutions for the oracles called on revert
+        if is_solution {
             res = ExecuteInputResult::Solution;
         } else {
             let is_corpus = self
@@ -482,6 +574,15 @@ where
             }
         }
 
+        state
+            .metadata_map_mut()
+            .get_mut::<SequenceLengthMetadata>()
+            .unwrap()
+            .record(
+                depth,
+                (is_infant_interesting && !reverted) || res != ExecuteInputResult::None,
+            );
+
         let mut corpus_idx = CorpusId::from(0usize);
         if res == ExecuteInputResult::Corpus || res == ExecuteInputResult::Solution {
             // Add the input to the main corpus
@@ -492,6 +593,16 @@ where
                 .report_corpus(state.get_infant_state_state(), state_idx);
             self.scheduler.on_add(state, corpus_idx)?;
             self.on_add_corpus(&input, unsafe { &JMP_MAP }, corpus_idx.into());
+            if res == ExecuteInputResult::Corpus {
+                telemetry::emit(
+                    "new_corpus",
+                    json!({
+                        "corpus_idx": usize::from(corpus_idx),
+                        "corpus_size": state.corpus().count(),
+                        "executions": *state.executions(),
+                    }),
+                );
+            }
         }
 
         let final_res = match res {
@@ -565,8 +676,18 @@ where
                     .map(|ci| String::from_utf8(ci.serialize_concise()).expect("utf-8 failed"))
                     .join("\n");
 
-                println!("\n\n\n😊😊 Found vulnerabilities! \n\n");
-                let cur_report =
+                // tag the bugs only reachable by an impersonated role holder
+                if minimized.iter().any(|tx| is_privileged_sender(&tx.caller())) {
+                    for bug in unsafe { ORACLE_OUTPUT.iter_mut() } {
+                        bug["requires_privileged_sender"] = json!(true);
+                        if let Some(info) = bug["bug_info"].as_str() {
+                            bug["bug_info"] = json!(format!("{} (requires privileged sender)", info));
+                        }
+                    }
+                }
+
+                logger::print("\n\n\n😊😊 Found vulnerabilities! \n\n");
+                let mut cur_report =
                     format!(
                     "================ Description ================\n{}\n================ Trace ================\n{}\n",
                     unsafe { ORACLE_OUTPUT.iter().map(|v| {
@@ -574,9 +695,23 @@ where
                      }).join("\n") },
                     txn_text
                 );
-                println!("{}", cur_report);
+                let storage_diff = unsafe { ORACLE_OUTPUT.iter().find_map(|v| v["storage_diff"].as_array()) };
+                if let Some(diff) = storage_diff {
+                    cur_report.push_str(&format!(
+                        "================ Storage Diff ================\n{}\n",
+                        diff.iter().filter_map(|line| line.as_str()).join("\n")
+                    ));
+                }
+                let call_tree = unsafe { ORACLE_OUTPUT.iter().find_map(|v| v["call_tree"].as_str()) };
+                if let Some(call_tree) = call_tree {
+                    cur_report.push_str(&format!("================ Call Tree ================\n{}\n", call_tree));
+                }
+                logger::print(&cur_report);
 
-                solution::generate_test(cur_report.clone(), minimized);
+                // the ETH the exploit earns on the replay, out of which a flashloan is repaid
+                let profit = unsafe { ORACLE_OUTPUT.iter().find_map(|v| v["profit"].as_str()) }
+                    .and_then(|profit| EVMU256::from_str_radix(profit, 10).ok());
+                solution::generate_test(cur_report.clone(), minimized, profit);
 
                 let vuln_file = format!("{}/vuln_info.jsonl", self.work_dir.as_str());
                 let mut f = OpenOptions::new()
@@ -593,6 +728,16 @@ where
                 })
                 .expect("Unable to write data");
                 f.write_all(b"\n").expect("Unable to write data");
+                for bug in unsafe { ORACLE_OUTPUT.iter() } {
+                    metrics::metrics().record_objective(bug["bug_type"].as_str().unwrap_or_default());
+                }
+                telemetry::emit(
+                    "objective_found",
+                    json!({
+                        "bugs": unsafe { ORACLE_OUTPUT.clone() },
+                        "executions": *state.executions(),
+                    }),
+                );
 
                 #[cfg(feature = "print_txn_corpus")]
                 {
@@ -620,8 +765,18 @@ where
                     // dump_file!(state, vulns_dir, false);
                 }
 
-                if !unsafe { RUN_FOREVER } {
-                    exit(0);
+                let objectives = state
+                    .metadata_map()
+                    .get::<BugMetadata>()
+                    .unwrap()
+                    .corpus_idx_to_bug
+                    .len();
+                if unsafe { STOP_CONDITIONS.objectives_reached(objectives) } {
+                    info!("Found {} objectives, stopping", objectives);
+                    self.stop(state);
+                }
+                if !unsafe { RUN_FOREVER || STOP_CONDITIONS.objectives.is_some() } {
+                    self.stop(state);
                 }
 
                 return Ok((res, None));
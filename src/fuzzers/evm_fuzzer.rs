@@ -1,22 +1,36 @@
-use std::{cell::RefCell, collections::HashMap, fs::File, io::Read, ops::Deref, path::Path, process::exit, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::Read,
+    ops::Deref,
+    path::Path,
+    process::exit,
+    rc::Rc,
+    time::Duration,
+};
 
 use bytes::Bytes;
 use glob::glob;
 use itertools::Itertools;
 use libafl::{
     feedbacks::Feedback,
-    prelude::{HasMetadata, MaxMapFeedback, SimpleEventManager, SimpleMonitor, StdMapObserver},
+    prelude::{HasExecutions, HasMetadata, MaxMapFeedback, SimpleEventManager, SimpleMonitor, StdMapObserver},
     Evaluator,
     Fuzzer,
 };
 use libafl_bolts::tuples::tuple_list;
 use revm_primitives::Bytecode;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
+#[cfg(feature = "python")]
+use crate::evm::python::{PythonHooks, PythonOracle, PythonScript};
 use crate::{
+    checkpoint::CheckpointMetadata,
     evm::{
         abi::{ABIAddressToInstanceMap, BoxedABI},
         blaz::builder::ArtifactInfoMetadata,
+        cmin::{branch_footprint, select_testcases, write_minimized_corpus},
         concolic::{
             concolic_host::CONCOLIC_TIMEOUT,
             concolic_stage::{ConcolicFeedbackWrapper, ConcolicStage},
@@ -24,8 +38,9 @@ use crate::{
         config::Config,
         contract_utils::FIX_DEPLOYER,
         corpus_initializer::EVMCorpusInitializer,
+        corpus_sync::{self, CorpusSyncStage},
         cov_stage::CoverageStage,
-        feedbacks::Sha3WrappedFeedback,
+        feedbacks::{CustomMapsFeedback, Sha3WrappedFeedback},
         host::{
             FuzzHost,
             ACTIVE_MATCH_EXT_CALL,
@@ -34,40 +49,87 @@ use crate::{
             JMP_MAP,
             PANIC_ON_BUG,
             READ_MAP,
+            RECORD_LOGS,
             WRITE_MAP,
             WRITE_RELATIONSHIPS,
         },
-        input::{ConciseEVMInput, EVMInput},
+        input::{ConciseEVMInput, EVMInput, MAX_BLOCK_DELAY, MAX_TIME_DELAY},
+        mempool::init_victim_tx,
         middlewares::{
+            breakpoint::Debugger,
+            call_path::CallPathTracer,
             call_printer::CallPrinter,
+            call_taint::CallTaintTracer,
+            chainlink::{ChainlinkMock, CHAINLINK_FEEDS},
             cheatcode::Cheatcode,
-            coverage::{Coverage, EVAL_COVERAGE},
+            coverage::{handle_coverage_report_signal, Coverage, EVAL_COVERAGE},
             middleware::Middleware,
+            price_source::{reads_prices, PriceSourceTracer},
             reentrancy::ReentrancyTracer,
-            sha3_bypass::{Sha3Bypass, Sha3TaintAnalysis},
+            sha3_bypass::{Sha3Bypass, Sha3TaintAnalysis, Sha3TaintedJumpiMetadata},
+            trace::CallTracer,
         },
         minimizer::EVMMinimizer,
         mutator::FuzzMutator,
-        onchain::{flashloan::Flashloan, offchain::OffChainConfig, ChainConfig, OnChain, WHITELIST_ADDR},
+        onchain::{
+            endpoints::Chain,
+            flashloan::{Flashloan, MAX_CAPITAL},
+            offchain::OffChainConfig,
+            upgrades::UpgradeWatchStage,
+            ChainConfig,
+            OnChain,
+            BLACKLIST_ADDR,
+            WHITELIST_ADDR,
+        },
         oracles::{
             arb_call::ArbitraryCallOracle,
+            assertion::AssertionOracle,
+            contract_size::{ContractSize, ContractSizeOracle},
+            differential::DifferentialOracle,
             echidna::EchidnaOracle,
+            erc20::FundLossOracle,
+            erc4626::ERC4626Oracle,
+            event::EventOracle,
+            gas_griefing::GasGriefingOracle,
             invariant::InvariantOracle,
+            post_state::{PostStateOracle, PostStateOracles},
+            price_manipulation::PriceManipulationOracle,
             reentrancy::ReentrancyOracle,
+            sandwich::SandwichOracle,
             selfdestruct::SelfdestructOracle,
+            supply::{SupplyOracle, SupplyToken},
+            tainted_call::TaintedCallOracle,
+            takeover::TakeoverOracle,
             typed_bug::TypedBugOracle,
         },
         presets::ExploitTemplate,
+        privileged::impersonate_role_holders,
+        redqueen::RedqueenStage,
         scheduler::{PowerABIMutationalStage, PowerABIScheduler, UncoveredBranchesMetadata},
+        senders::{SenderRole, CALL_VALUE_RANGES},
+        sig_to_score::{handle_sig_to_score_reload_signal, SigToScore},
+        splice_stage::SpliceStage,
+        summary::{CampaignSummary, FunctionStatsMetadata},
+        tokens::{
+            code_cache::set_code_endpoint,
+            numeraire::{Numeraire, NumerairePrice},
+        },
+        tui::{parse_monitor_stats, Dashboard},
         types::{fixed_address, EVMAddress, EVMFuzzMutator, EVMFuzzState, EVMQueueExecutor, EVMU256},
         vm::{EVMExecutor, EVMState},
     },
     executor::FuzzExecutor,
     feedback::{CmpFeedback, DataflowFeedback, OracleFeedback},
-    fuzzer::{ItyFuzzer, REPLAY, RUN_FOREVER},
+    fuzzer::{ItyFuzzer, MAX_EXECUTIONS, REPLAY, RUN_FOREVER, SAVE_INTERVAL},
+    generic_vm::vm_executor::GenericVM,
+    input::ConciseSerde,
+    metrics,
     oracle::BugMetadata,
+    r#const::DEFAULT_NATIVE_PRICE_USD,
     scheduler::SortedDroppingScheduler,
     state::{FuzzState, HasCaller, HasExecutionResult, HasPresets},
+    stop_conditions::STOP_CONDITIONS,
+    telemetry,
 };
 
 #[allow(clippy::type_complexity)]
@@ -92,10 +154,48 @@ pub fn evm_fuzzer(
     // create work dir if not exists
     let _path = Path::new(config.work_dir.as_str());
 
-    let monitor = SimpleMonitor::new(|s| info!("{}", s));
+    if let Some(target) = &config.telemetry {
+        if let Err(e) = telemetry::init(target) {
+            error!("Failed to open telemetry {}: {}", target, e);
+        }
+    }
+    if let Some(addr) = &config.metrics_addr {
+        if let Err(e) = metrics::serve(addr) {
+            error!("Failed to serve metrics on {}: {}", addr, e);
+        }
+    }
+
+    let rpc_budget = config.onchain.as_ref().map(|onchain| onchain.rpc_budget.clone());
+    let dashboard = config.tui.then(|| {
+        Rc::new(RefCell::new(
+            Dashboard::new(config.work_dir.clone(), rpc_budget.clone()).expect("Failed to start the dashboard"),
+        ))
+    });
+    let monitor_dashboard = dashboard.clone();
+    let monitor = SimpleMonitor::new(move |s| {
+        let stats = parse_monitor_stats(&s);
+        let stat = |key: &str| stats.get(key).and_then(|v| v.parse().ok()).unwrap_or_default();
+        metrics::metrics().record_stats(stat("executions"), stat("corpus"), stat("objectives"));
+        if let Some(budget) = &rpc_budget {
+            metrics::metrics().record_rpc(budget.requests(), budget.credits());
+        }
+        telemetry::emit("stats", telemetry::stats_fields(&stats));
+        match (&monitor_dashboard, &rpc_budget) {
+            (Some(dashboard), _) => dashboard.borrow_mut().update(&s),
+            (None, Some(budget)) => info!("{}, {}", s, budget),
+            (None, None) => info!("{}", s),
+        }
+    });
     let mut mgr = SimpleEventManager::new(monitor);
     let infant_scheduler = SortedDroppingScheduler::new();
-    let scheduler = PowerABIScheduler::new();
+    let scheduler = PowerABIScheduler::new(
+        SigToScore::from_selectors(config.sig_to_score.clone()),
+        config.sig_to_score_file.clone(),
+    );
+    if config.sig_to_score_file.is_some() {
+        scheduler.load_sig_to_score().expect("Invalid sig_to_score file");
+        handle_sig_to_score_reload_signal();
+    }
 
     let jmps = unsafe { &mut JMP_MAP };
     let cmps = unsafe { &mut CMP_MAP };
@@ -103,9 +203,20 @@ pub fn evm_fuzzer(
     let writes = unsafe { &mut WRITE_MAP };
     let jmp_observer = unsafe { StdMapObserver::new("jmp", jmps) };
 
-    let deployer = fixed_address(FIX_DEPLOYER);
+    let deployer = config
+        .senders
+        .iter()
+        .find(|sender| sender.role == SenderRole::Owner)
+        .map_or(fixed_address(FIX_DEPLOYER), |owner| owner.address);
     let mut fuzz_host = FuzzHost::new(scheduler.clone(), config.work_dir.clone());
     fuzz_host.set_spec_id(config.spec_id);
+    if let Some(chain) = config
+        .onchain
+        .as_ref()
+        .and_then(|onchain| Chain::from_chain_id(onchain.chain_id))
+    {
+        fuzz_host.set_chain_profile(chain.get_profile());
+    }
 
     // **Note**: cheatcode should be the first middleware because it consumes the
     // step if it is a call to cheatcode_address, and this step should not be
@@ -167,6 +278,9 @@ pub fn evm_fuzzer(
 
     unsafe {
         PANIC_ON_BUG = config.panic_on_bug;
+        MAX_EXECUTIONS = config.max_execs;
+        STOP_CONDITIONS = config.stop_conditions.clone();
+        SAVE_INTERVAL = (config.save_interval > 0).then_some(config.save_interval);
     }
 
     if !config.only_fuzz.is_empty() {
@@ -175,24 +289,41 @@ pub fn evm_fuzzer(
         }
     }
 
+    // harness contracts only hold invariants, so none of their functions is
+    // fuzzed
+    let harness_addrs = config
+        .contract_loader
+        .contracts
+        .iter()
+        .filter(|contract| InvariantOracle::is_harness(&contract.name, &config.invariant_harness))
+        .map(|contract| contract.deployed_address)
+        .collect::<HashSet<_>>();
+
+    let chain_cfg = || -> Option<Box<dyn ChainConfig>> {
+        if let Some(onchain) = config.onchain.clone() {
+            Some(Box::new(onchain) as Box<dyn ChainConfig>)
+        } else if let Some(ref setup_data) = config.contract_loader.setup_data {
+            if setup_data.v2_pairs.is_empty() {
+                None
+            } else {
+                Some(Box::new(OffChainConfig::new(setup_data).unwrap()) as Box<dyn ChainConfig>)
+            }
+        } else {
+            None
+        }
+    };
+
     if config.flashloan {
         // we should use real balance of tokens in the contract instead of providing
         // flashloan to contract as well for on chain env
-        {
-            let chain_cfg: Option<Box<dyn ChainConfig>> = if let Some(onchain) = config.onchain.clone() {
-                Some(Box::new(onchain) as Box<dyn ChainConfig>)
-            } else if let Some(ref setup_data) = config.contract_loader.setup_data {
-                if setup_data.v2_pairs.is_empty() {
-                    None
-                } else {
-                    Some(Box::new(OffChainConfig::new(setup_data).unwrap()) as Box<dyn ChainConfig>)
-                }
-            } else {
-                None
-            };
-
-            fuzz_host.add_flashloan_middleware(Flashloan::new(true, chain_cfg, config.flashloan_oracle));
+        let mut flashloan = Flashloan::new(true, chain_cfg(), config.flashloan_oracle.clone());
+        // the code of the contracts of the swap paths missing from the code
+        // cache is fetched from the chain
+        if let Some(chain) = chain_cfg() {
+            set_code_endpoint(chain);
         }
+        flashloan.set_nft_floor_prices(config.nft_floor_prices.clone());
+        fuzz_host.add_flashloan_middleware(flashloan);
     }
     let sha3_taint = Rc::new(RefCell::new(Sha3TaintAnalysis::new()));
 
@@ -206,8 +337,93 @@ pub fn evm_fuzzer(
         fuzz_host.add_middlewares(Rc::new(RefCell::new(ReentrancyTracer::new())));
     }
 
+    if config.supply_oracle || config.contract_size_oracle {
+        fuzz_host.add_middlewares(Rc::new(RefCell::new(CallPathTracer::new())));
+    }
+
+    if config.price_manipulation_oracle {
+        fuzz_host.add_middlewares(Rc::new(RefCell::new(PriceSourceTracer::new())));
+    }
+
+    if !config.chainlink_feeds.is_empty() {
+        let mut chainlink = ChainlinkMock::new(config.chainlink_feeds.clone());
+        unsafe {
+            CHAINLINK_FEEDS = config.chainlink_feeds.len();
+        }
+        if let Some(mut onchain) = config.onchain.clone() {
+            chainlink.fetch_history(&mut onchain);
+        }
+        fuzz_host.add_middlewares(Rc::new(RefCell::new(chainlink)));
+    }
+
+    // the breakpoints would be hit on every execution reaching them when
+    // fuzzing
+    if !config.breakpoints.is_empty() {
+        if config.replay_file.is_some() {
+            fuzz_host.add_middlewares(Rc::new(RefCell::new(Debugger::new(config.breakpoints.clone()))));
+        } else {
+            warn!("--break-at is ignored when fuzzing, use it with `ityfuzz replay`");
+        }
+    }
+
+    if config.tainted_call_oracle {
+        debug!("tainted call oracle enabled");
+        fuzz_host.add_middlewares(Rc::new(RefCell::new(CallTaintTracer::new())));
+    }
+
+    #[cfg(feature = "python")]
+    let python_hooks = (!config.python_script.is_empty()).then(|| {
+        let script = PythonScript::load(&config.python_script).expect("Invalid python script");
+        let hooks = Rc::new(RefCell::new(PythonHooks::new(Rc::new(script))));
+        fuzz_host.add_middlewares(hooks.clone());
+        hooks
+    });
+    #[cfg(not(feature = "python"))]
+    if !config.python_script.is_empty() {
+        panic!("--python-script needs ityfuzz built with the python feature");
+    }
+
+    for custom in &config.custom_feedbacks {
+        if let Some(middleware) = &custom.middleware {
+            fuzz_host.add_middlewares(middleware.clone());
+        }
+    }
+
     let mut evm_executor: EVMQueueExecutor = EVMExecutor::new(fuzz_host, deployer);
 
+    // price the native token in the numeraire, so that the thresholds and
+    // reports are converted to it, and in USD for the minimum profit
+    let initial_state = match config.contract_loader.setup_data {
+        Some(ref setup_data) => setup_data.evmstate.clone(),
+        None => evm_executor.host.evmstate.clone(),
+    };
+    let mut quote = |numeraire| {
+        chain_cfg().and_then(|mut chain| {
+            NumerairePrice::quote(numeraire, &mut chain, &mut evm_executor, state, &initial_state)
+        })
+    };
+    if config.numeraire != Numeraire::Native {
+        match quote(config.numeraire) {
+            Some(price) => config.flashloan_oracle.deref().borrow_mut().thresholds.numeraire = price,
+            None => warn!(
+                "failed to price ETH in {}, thresholds and reports stay in ETH",
+                config.numeraire.symbol()
+            ),
+        }
+    }
+    if config.onchain.is_some() {
+        match quote(Numeraire::USDC) {
+            Some(price) => config.flashloan_oracle.deref().borrow_mut().thresholds.native_usd = price.per_native,
+            None => warn!(
+                "failed to price ETH in USDC, the minimum profit assumes {} USD per ETH",
+                DEFAULT_NATIVE_PRICE_USD
+            ),
+        }
+    }
+    unsafe {
+        MAX_CAPITAL = config.flashloan_oracle.deref().borrow().thresholds.max_capital_wei();
+    }
+
     if config.replay_file.is_some() {
         // add coverage middleware for replay
         unsafe {
@@ -220,6 +436,13 @@ pub fn evm_fuzzer(
     if !state.has_metadata::<ArtifactInfoMetadata>() {
         state.add_metadata(ArtifactInfoMetadata::new());
     }
+
+    if !harness_addrs.is_empty() {
+        unsafe {
+            BLACKLIST_ADDR.get_or_insert_with(HashSet::new).extend(harness_addrs);
+        }
+    }
+
     let mut corpus_initializer = EVMCorpusInitializer::new(
         &mut evm_executor,
         scheduler.clone(),
@@ -227,6 +450,41 @@ pub fn evm_fuzzer(
         state,
         config.work_dir.clone(),
     );
+    corpus_initializer.set_fuzz_constructor_args(config.fuzz_constructor_args);
+    corpus_initializer.set_harness_setup(config.harness_setup);
+    corpus_initializer.set_infer_interface(config.infer_interface);
+    if let Some(victim_tx) = &config.victim_tx {
+        init_victim_tx(victim_tx.clone());
+    }
+    corpus_initializer.set_victim_tx(config.victim_tx.clone());
+
+    if let Some(echidna_config) = &config.echidna_config {
+        corpus_initializer.set_callers(echidna_config.senders().expect("invalid echidna senders"));
+        unsafe {
+            MAX_TIME_DELAY = echidna_config.max_time_delay.map(EVMU256::from);
+            MAX_BLOCK_DELAY = echidna_config.max_block_delay.map(EVMU256::from);
+        }
+    }
+    if !config.senders.is_empty() {
+        corpus_initializer.set_callers(config.senders.iter().map(|sender| sender.address).collect());
+        for sender in &config.senders {
+            if let Some(balance) = sender.balance {
+                corpus_initializer.set_caller_balance(sender.address, balance);
+            }
+        }
+    }
+    unsafe {
+        CALL_VALUE_RANGES = config.call_value_ranges.clone();
+    }
+    if let (true, Some(mut onchain)) = (config.impersonate_owners, config.onchain.clone()) {
+        let targets = config
+            .contract_loader
+            .contracts
+            .iter()
+            .map(|contract| contract.deployed_address)
+            .collect_vec();
+        corpus_initializer.set_privileged_callers(impersonate_role_holders(&mut onchain, &targets));
+    }
 
     let mut artifacts = corpus_initializer.initialize(&mut config.contract_loader.clone());
 
@@ -285,6 +543,10 @@ pub fn evm_fuzzer(
     )));
 
     evm_executor.host.add_middlewares(cov_middleware.clone());
+    if let Some(dashboard) = &dashboard {
+        dashboard.borrow_mut().set_coverage(cov_middleware.clone());
+    }
+    handle_coverage_report_signal();
 
     state.add_metadata(instance_map);
 
@@ -324,23 +586,117 @@ pub fn evm_fuzzer(
         evm_executor_ref.clone(),
         config.concolic_num_threads,
     );
+    let redqueen_stage = RedqueenStage::new(config.redqueen, evm_executor_ref.clone());
     let mutator: EVMFuzzMutator = FuzzMutator::new(infant_scheduler.clone());
+    #[cfg(feature = "python")]
+    let mutator = {
+        let mut mutator = mutator;
+        if let Some(processor) = python_hooks
+            .as_ref()
+            .and_then(|hooks| hooks.borrow().calldata_processor())
+        {
+            mutator.set_calldata_processor(processor);
+        }
+        mutator
+    };
 
     state.metadata_map_mut().insert(UncoveredBranchesMetadata::new());
+    state.metadata_map_mut().insert(FunctionStatsMetadata::default());
     let std_stage = PowerABIMutationalStage::new(mutator);
 
     let call_printer_mid = Rc::new(RefCell::new(CallPrinter::new(artifacts.address_to_name.clone())));
 
+    let mut summary = CampaignSummary::new(
+        artifacts.address_to_name.clone(),
+        artifacts.address_to_abi.clone(),
+        &artifacts.address_to_bytecode,
+        artifacts.initial_state.state.state.clone(),
+        config.flashloan_oracle.clone(),
+        config.work_dir.clone(),
+    );
+    // detectors not enabled that apply to some of the fuzzed contracts
+    let count_contracts = |applies: &dyn Fn(&[(&str, &str)]) -> bool| {
+        artifacts
+            .address_to_abi
+            .values()
+            .filter(|abis| {
+                applies(
+                    &abis
+                        .iter()
+                        .map(|abi| (abi.function_name.as_str(), abi.abi.as_str()))
+                        .collect_vec(),
+                )
+            })
+            .count()
+    };
+    if !config.supply_oracle {
+        let count = count_contracts(&|functions| SupplyOracle::has_supply(functions.iter().cloned()));
+        summary.add_detector_hint("supply", count, "have a totalSupply()");
+    }
+    if !config.erc4626_oracle {
+        let count = count_contracts(&|functions| ERC4626Oracle::is_vault(functions.iter().cloned()));
+        summary.add_detector_hint("erc4626", count, "look like ERC4626 vaults");
+    }
+    if !config.price_manipulation_oracle {
+        let count = artifacts
+            .address_to_bytecode
+            .values()
+            .filter(|bytecode| reads_prices(bytecode))
+            .count();
+        summary.add_detector_hint("price_manipulation", count, "read price sources");
+    }
+    if !config.echidna_oracle {
+        let count = count_contracts(&|functions| functions.iter().any(|(name, _)| name.starts_with("echidna_")));
+        summary.add_detector_hint("echidna", count, "have echidna_ properties");
+    }
+    if !config.invariant_oracle {
+        let count = count_contracts(&|functions| functions.iter().any(|(name, _)| name.starts_with("invariant_")));
+        summary.add_detector_hint("invariant", count, "have invariant_ functions");
+    }
+
     let coverage_obs_stage = CoverageStage::new(
         evm_executor_ref.clone(),
         cov_middleware.clone(),
         call_printer_mid.clone(),
         config.work_dir.clone(),
+        summary,
     );
 
-    let mut stages = tuple_list!(std_stage, concolic_stage, coverage_obs_stage);
+    let corpus_sync_stage = CorpusSyncStage::new(
+        config
+            .corpus_sync
+            .as_ref()
+            .map(|location| corpus_sync::open_store(location, &config.work_dir)),
+        config.corpus_sync_node.clone(),
+        Duration::from_secs(config.corpus_sync_interval),
+        &config.work_dir,
+        artifacts.initial_state.clone(),
+    );
+
+    let upgrade_watch_stage = UpgradeWatchStage::new(
+        config.onchain.clone(),
+        config.builder.clone(),
+        Duration::from_secs(config.watch_upgrades),
+        &artifacts.address_to_bytecode,
+        evm_executor_ref.clone(),
+    );
+
+    let splice_stage = SpliceStage::new();
+
+    let mut stages = tuple_list!(
+        std_stage,
+        splice_stage,
+        concolic_stage,
+        redqueen_stage,
+        coverage_obs_stage,
+        corpus_sync_stage,
+        upgrade_watch_stage
+    );
 
     let mut executor = FuzzExecutor::new(evm_executor_ref.clone(), tuple_list!(jmp_observer));
+    if config.determinism_check {
+        executor.enable_determinism_check(&format!("{}/quarantine", config.work_dir));
+    }
 
     #[cfg(feature = "deployer_is_attacker")]
     state.add_caller(&deployer);
@@ -431,6 +787,9 @@ pub fn evm_fuzzer(
     }
 
     state.add_metadata(BugMetadata::new());
+    if let Some(interval) = config.checkpoint_interval {
+        state.add_metadata(CheckpointMetadata::new(interval));
+    }
 
     if config.selfdestruct_oracle {
         oracles.push(Rc::new(RefCell::new(SelfdestructOracle::new(
@@ -438,16 +797,254 @@ pub fn evm_fuzzer(
         ))));
     }
 
+    if config.takeover_oracle {
+        let owned = artifacts
+            .address_to_abi
+            .iter()
+            .filter(|(_, abis)| {
+                TakeoverOracle::has_owner(abis.iter().map(|abi| (abi.function_name.as_str(), abi.abi.as_str())))
+            })
+            .map(|(address, _)| *address)
+            .collect();
+        oracles.push(Rc::new(RefCell::new(TakeoverOracle::new(
+            artifacts.address_to_name.clone(),
+            owned,
+        ))));
+    }
+
+    if config.tainted_call_oracle {
+        oracles.push(Rc::new(RefCell::new(TaintedCallOracle::new(
+            artifacts.address_to_name.clone(),
+            config.flashloan_oracle.clone(),
+        ))));
+    }
+
+    if config.erc4626_oracle {
+        let vaults = artifacts
+            .address_to_abi
+            .iter()
+            .filter(|(_, abis)| {
+                ERC4626Oracle::is_vault(abis.iter().map(|abi| (abi.function_name.as_str(), abi.abi.as_str())))
+            })
+            .map(|(address, _)| {
+                let name = artifacts
+                    .address_to_name
+                    .get(address)
+                    .cloned()
+                    .unwrap_or(format!("{:?}", address));
+                (*address, name)
+            })
+            .collect::<HashMap<_, _>>();
+        oracles.push(Rc::new(RefCell::new(ERC4626Oracle::new(vaults))));
+    }
+
+    if config.supply_oracle {
+        let tokens = artifacts
+            .address_to_abi
+            .iter()
+            .filter(|(_, abis)| {
+                SupplyOracle::has_supply(abis.iter().map(|abi| (abi.function_name.as_str(), abi.abi.as_str())))
+            })
+            .map(|(address, abis)| {
+                let name = artifacts
+                    .address_to_name
+                    .get(address)
+                    .cloned()
+                    .unwrap_or(format!("{:?}", address));
+                let whitelist = abis
+                    .iter()
+                    .filter(|abi| SupplyOracle::is_whitelisted(&abi.function_name, &config.supply_whitelist))
+                    .map(|abi| abi.function)
+                    .collect();
+                (*address, SupplyToken { name, whitelist })
+            })
+            .collect::<HashMap<_, _>>();
+        oracles.push(Rc::new(RefCell::new(SupplyOracle::new(tokens))));
+    }
+
+    if config.price_manipulation_oracle {
+        let consumers = artifacts
+            .address_to_bytecode
+            .iter()
+            .filter(|(_, bytecode)| reads_prices(bytecode))
+            .map(|(address, _)| *address)
+            .collect();
+        oracles.push(Rc::new(RefCell::new(PriceManipulationOracle::new(
+            artifacts.address_to_name.clone(),
+            consumers,
+            artifacts.initial_state.state.clone(),
+            config.flashloan_oracle.clone(),
+        ))));
+    }
+
+    // oracles only reading the post-state, evaluated concurrently
+    let mut post_state_oracles: Vec<Box<dyn PostStateOracle>> = vec![];
+    if config.erc20_oracle {
+        post_state_oracles.push(Box::new(FundLossOracle::new(
+            config.flashloan_oracle.deref().borrow().thresholds,
+        )));
+    }
+
     if config.reentrancy_oracle {
-        oracles.push(Rc::new(RefCell::new(ReentrancyOracle::new(
+        post_state_oracles.push(Box::new(ReentrancyOracle::new(artifacts.address_to_name.clone())));
+    }
+
+    if config.contract_size_oracle {
+        let oversized = config
+            .contract_loader
+            .contracts
+            .iter()
+            .filter(|contract| !contract.is_code_deployed)
+            .filter_map(|contract| {
+                let runtime_size = artifacts.address_to_bytecode.get(&contract.deployed_address)?.len();
+                let violations = ContractSize::of_contract(contract, runtime_size).violations();
+                (!violations.is_empty()).then_some((contract.deployed_address, violations))
+            })
+            .collect();
+        post_state_oracles.push(Box::new(ContractSizeOracle::new(
+            artifacts.address_to_name.clone(),
+            oversized,
+        )));
+    }
+
+    if !config.event_properties.is_empty() {
+        unsafe {
+            RECORD_LOGS = true;
+        }
+        post_state_oracles.push(Box::new(EventOracle::new(
+            artifacts.address_to_name.clone(),
+            config.event_properties.clone(),
+        )));
+    }
+    let plugin_names = config
+        .oracle_plugins
+        .iter()
+        .map(|plugin| plugin.name().to_string())
+        .collect::<Vec<_>>();
+    if !config.oracle_plugins.is_empty() {
+        unsafe {
+            RECORD_LOGS = true;
+        }
+    }
+    for plugin in config.oracle_plugins {
+        post_state_oracles.push(Box::new(plugin));
+    }
+    if !post_state_oracles.is_empty() {
+        oracles.push(Rc::new(RefCell::new(PostStateOracles::new(post_state_oracles))));
+    }
+
+    if config.gas_griefing_oracle {
+        oracles.push(Rc::new(RefCell::new(GasGriefingOracle::new(
+            artifacts.address_to_name.clone(),
+        ))));
+    }
+
+    #[cfg(feature = "python")]
+    if let Some(hooks) = &python_hooks {
+        oracles.push(Rc::new(RefCell::new(PythonOracle::new(hooks.clone()))));
+    }
+
+    if let Some(victim_tx) = &config.victim_tx {
+        // outcome of the victim transaction on the forked state, without
+        // front-run
+        let mut input = victim_tx.to_input(&artifacts.initial_env);
+        input.sstate = artifacts.initial_state.clone();
+        let baseline = evm_executor_ref
+            .deref()
+            .borrow_mut()
+            .execute(&input, state)
+            .new_state
+            .state
+            .victim;
+        oracles.push(Rc::new(RefCell::new(SandwichOracle::new(
+            baseline.unwrap_or_default(),
+            config.flashloan_oracle.deref().borrow().thresholds,
+        ))));
+    }
+
+    if !config.upgrades.is_empty() {
+        unsafe {
+            RECORD_LOGS = true;
+        }
+        oracles.push(Rc::new(RefCell::new(DifferentialOracle::new(
+            "the upgrade".to_string(),
+            config.upgrades.clone(),
+            artifacts.address_to_name.clone(),
+        ))));
+    }
+
+    for variant in &config.compiler_variants {
+        unsafe {
+            RECORD_LOGS = true;
+        }
+        // runtime code of the variant, with the immutables set by the constructor
+        let codes = variant
+            .init_codes
+            .iter()
+            .filter_map(|(addr, init_code)| {
+                evm_executor_ref
+                    .deref()
+                    .borrow_mut()
+                    .runtime_code(
+                        Bytecode::new_raw(init_code.clone()),
+                        *addr,
+                        &artifacts.initial_state.state,
+                    )
+                    .map(|code| (*addr, code))
+            })
+            .collect();
+        oracles.push(Rc::new(RefCell::new(DifferentialOracle::new(
+            format!("the build with `{}`", variant.flags),
+            codes,
             artifacts.address_to_name.clone(),
         ))));
     }
 
+    if config.assertion_oracle {
+        oracles.push(Rc::new(RefCell::new(AssertionOracle::new(
+            artifacts.address_to_name.clone(),
+            config.assertion_errors.clone(),
+        ))));
+    }
+
     if let Some(m) = onchain_middleware.clone() {
         m.borrow_mut().add_abi(artifacts.address_to_abi.clone());
     }
 
+    if let Some(dashboard) = &dashboard {
+        let active_oracles = [
+            (config.echidna_oracle, "echidna"),
+            (config.invariant_oracle, "invariant"),
+            (config.arbitrary_external_call, "arbitrary call"),
+            (config.typed_bug, "typed bug"),
+            (config.selfdestruct_oracle, "selfdestruct"),
+            (config.takeover_oracle, "takeover"),
+            (config.tainted_call_oracle, "tainted call"),
+            (config.reentrancy_oracle, "reentrancy"),
+            (config.erc4626_oracle, "erc4626"),
+            (config.supply_oracle, "supply"),
+            (config.price_manipulation_oracle, "price manipulation"),
+            (config.contract_size_oracle, "contract size"),
+            (config.assertion_oracle, "assertion"),
+            (config.gas_griefing_oracle, "gas griefing"),
+            (config.victim_tx.is_some(), "sandwich"),
+            (
+                !config.upgrades.is_empty() || !config.compiler_variants.is_empty(),
+                "differential",
+            ),
+            (!config.event_properties.is_empty(), "event"),
+            (!config.python_script.is_empty(), "python"),
+        ];
+        dashboard.borrow_mut().set_oracles(
+            active_oracles
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, name)| name.to_string())
+                .chain(plugin_names)
+                .collect(),
+        );
+    }
+
     let mut producers = config.producers;
 
     let objective: OracleFeedback<
@@ -464,9 +1061,14 @@ pub fn evm_fuzzer(
         ConciseEVMInput,
         EVMQueueExecutor,
     > = OracleFeedback::new(&mut oracles, &mut producers, evm_executor_ref.clone());
+    let custom_maps = config
+        .custom_feedbacks
+        .iter()
+        .map(|custom| custom.map.clone())
+        .collect();
     let wrapped_feedback = ConcolicFeedbackWrapper::new(Sha3WrappedFeedback::new(
-        feedback,
-        sha3_taint,
+        CustomMapsFeedback::new(feedback, custom_maps),
+        sha3_taint.clone(),
         evm_executor_ref.clone(),
         config.sha3_bypass,
     ));
@@ -478,12 +1080,17 @@ pub fn evm_fuzzer(
         infant_feedback,
         infant_result_feedback,
         objective,
-        EVMMinimizer::new(evm_executor_ref.clone()),
+        EVMMinimizer::new(
+            evm_executor_ref.clone(),
+            config.flashloan_oracle.deref().borrow().thresholds.numeraire,
+            artifacts.address_to_name.clone(),
+        ),
         config.work_dir,
     );
 
     let initial_vm_state = artifacts.initial_state.clone();
     let mut testcases = vec![];
+    let mut testcase_files = vec![];
     let to_load_glob: String;
 
     if let Some(files) = config.replay_file.clone() {
@@ -510,6 +1117,7 @@ pub fn evm_fuzzer(
                 deserialized_transactions.push(deserialized_tx.unwrap());
             }
             testcases.push(deserialized_transactions);
+            testcase_files.push(file.unwrap());
         }
     }
 
@@ -531,19 +1139,31 @@ pub fn evm_fuzzer(
 
     match config.replay_file {
         None => {
-            // load initial corpus
-            for testcase in testcases {
-                let mut vm_state = initial_vm_state.clone();
-                for txn in testcase {
-                    load_code!(txn);
-                    let (inp, call_until) = txn.to_input(vm_state.clone());
-                    unsafe {
-                        CALL_UNTIL = call_until;
+            if config.resume {
+                // the saved state already holds the corpus
+                if let Err(e) = fuzzer.resume(state) {
+                    error!("Failed to resume the campaign: {}", e);
+                    exit(1);
+                }
+                if let Some(meta) = state.metadata_map().get::<Sha3TaintedJumpiMetadata>() {
+                    sha3_taint.borrow_mut().tainted_jumpi = meta.jumpis.clone();
+                }
+                info!("Resumed the campaign after {} executions", state.executions());
+            } else {
+                // load initial corpus
+                for testcase in testcases {
+                    let mut vm_state = initial_vm_state.clone();
+                    for txn in testcase {
+                        load_code!(txn);
+                        let (inp, call_until) = txn.to_input(vm_state.clone());
+                        unsafe {
+                            CALL_UNTIL = call_until;
+                        }
+                        fuzzer
+                            .evaluate_input_events(state, &mut executor, &mut mgr, inp, false)
+                            .unwrap();
+                        vm_state = state.get_execution_result().new_state.clone();
                     }
-                    fuzzer
-                        .evaluate_input_events(state, &mut executor, &mut mgr, inp, false)
-                        .unwrap();
-                    vm_state = state.get_execution_result().new_state.clone();
                 }
             }
             let res = fuzzer.fuzz_loop(&mut stages, &mut executor, state, &mut mgr);
@@ -559,12 +1179,40 @@ pub fn evm_fuzzer(
             exit(1);
         }
         Some(_) => {
+            if let Some(output_dir) = config.cmin_output {
+                // replay each testcase to collect the branches it covers
+                let mut footprints = vec![];
+                for testcase in testcases {
+                    let txns = testcase.len();
+                    let mut footprint = HashSet::new();
+                    let mut vm_state = initial_vm_state.clone();
+                    for txn in testcase {
+                        load_code!(txn);
+                        let (inp, call_until) = txn.to_input(vm_state.clone());
+                        unsafe {
+                            CALL_UNTIL = call_until;
+                        }
+                        let res = evm_executor_ref.deref().borrow_mut().execute(&inp, state);
+                        footprint.extend(branch_footprint());
+                        vm_state = res.new_state;
+                    }
+                    footprints.push((footprint, txns));
+                }
+
+                let selected = select_testcases(&footprints);
+                if let Err(e) = write_minimized_corpus(&testcase_files, &selected, &output_dir) {
+                    error!("Failed to write the minimized corpus: {}", e);
+                    exit(1);
+                }
+                return;
+            }
+
             unsafe {
                 EVAL_COVERAGE = true;
             }
 
-            let printer = Rc::new(RefCell::new(CallPrinter::new(artifacts.address_to_name.clone())));
-            evm_executor_ref.borrow_mut().host.add_middlewares(printer.clone());
+            let tracer = Rc::new(RefCell::new(CallTracer::new(artifacts.address_to_name.clone())));
+            evm_executor_ref.borrow_mut().host.add_middlewares(tracer.clone());
 
             for testcase in testcases {
                 let mut vm_state = initial_vm_state.clone();
@@ -574,8 +1222,8 @@ pub fn evm_fuzzer(
                     idx += 1;
                     // let splitter = txn.split(" ").collect::<Vec<&str>>();
                     info!("============ Execution {} ===============", idx);
+                    println!("{}", txn.serialize_string());
                     let (inp, call_until) = txn.to_input(vm_state.clone());
-                    printer.borrow_mut().cleanup();
 
                     unsafe {
                         CALL_UNTIL = call_until;
@@ -587,7 +1235,11 @@ pub fn evm_fuzzer(
 
                     info!("============ Execution result {} =============", idx);
                     info!("reverted: {:?}", state.get_execution_result().clone().reverted);
-                    info!("call trace:\n{}", printer.deref().borrow().get_trace());
+                    let result = state.get_execution_result();
+                    if let Some(call_tree) = tracer.borrow_mut().finish_tx(result.reverted, &result.output) {
+                        // printed rather than logged, for the trace to be complete at any log level
+                        println!("call trace:\n{}", tracer.deref().borrow().render(&call_tree));
+                    }
                     info!("output: {:?}", hex::encode(state.get_execution_result().clone().output));
 
                     // debug!(
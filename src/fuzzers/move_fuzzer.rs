@@ -7,8 +7,12 @@ use libafl::{
     Fuzzer,
 };
 use libafl_bolts::tuples::tuple_list;
+#[cfg(feature = "sui_support")]
+use move_core_types::parser::parse_type_tag;
 use tracing::info;
 
+#[cfg(feature = "sui_support")]
+use crate::r#const::MOVE_FLASHLOAN_AMOUNT;
 #[cfg(feature = "sui_support")]
 use crate::r#move::corpus_initializer::MoveCorpusInitializer;
 #[cfg(feature = "sui_support")]
@@ -20,10 +24,14 @@ use crate::r#move::movevm::MoveVM;
 #[cfg(feature = "sui_support")]
 use crate::r#move::mutator::MoveFuzzMutator;
 #[cfg(feature = "sui_support")]
+use crate::r#move::oracles::profit::ProfitOracle;
+#[cfg(feature = "sui_support")]
 use crate::r#move::oracles::typed_bug::TypedBugOracle;
 #[cfg(feature = "sui_support")]
 use crate::r#move::scheduler::{MoveTestcaseScheduler, MoveVMStateScheduler};
 #[cfg(feature = "sui_support")]
+use crate::r#move::tokens::MoveTokenContext;
+#[cfg(feature = "sui_support")]
 use crate::r#move::types::MoveFuzzState;
 #[cfg(feature = "sui_support")]
 use crate::scheduler::SortedDroppingScheduler;
@@ -40,6 +48,10 @@ pub struct MoveFuzzConfig {
     pub target: String,
     pub work_dir: String,
     pub seed: u64,
+    /// Whether the fuzzer borrows the coins, enabling the profit oracle
+    pub flashloan: bool,
+    /// Coin the profit is measured in, e.g., `0x2::sui::SUI`
+    pub numeraire: String,
 }
 
 pub static mut MOVE_ENABLED: bool = cfg!(feature = "move_support");
@@ -59,8 +71,12 @@ pub fn move_fuzzer(config: &MoveFuzzConfig) {
     };
 
     {
-        MoveCorpusInitializer::new(&mut state, &mut vm, scheduler.clone(), infant_scheduler.clone())
-            .setup(vec![config.target.clone()]);
+        let mut initializer =
+            MoveCorpusInitializer::new(&mut state, &mut vm, scheduler.clone(), infant_scheduler.clone());
+        initializer.setup(vec![config.target.clone()]);
+        if config.flashloan {
+            initializer.add_flashloan_state(MOVE_FLASHLOAN_AMOUNT);
+        }
     }
 
     let vm_ref = Rc::new(RefCell::new(vm));
@@ -81,6 +97,12 @@ pub fn move_fuzzer(config: &MoveFuzzConfig) {
 
     let mut oracles: Vec<Rc<RefCell<dyn Oracle<_, _, _, _, _, _, _, _, _, _, _>>>> =
         vec![Rc::new(RefCell::new(TypedBugOracle::new()))];
+    if config.flashloan {
+        let numeraire = parse_type_tag(&config.numeraire).expect("Invalid numeraire");
+        oracles.push(Rc::new(RefCell::new(ProfitOracle::new(MoveTokenContext::new(
+            numeraire,
+        )))));
+    }
     let mut producers = vec![];
 
     let objective = OracleFeedback::new(&mut oracles, &mut producers, vm_ref.clone());
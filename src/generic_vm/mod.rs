@@ -1,2 +1,3 @@
+pub mod trace;
 pub mod vm_executor;
 pub mod vm_state;
@@ -0,0 +1,194 @@
+//! Traces of the executions in a format shared by the VMs, so that the
+//! oracles and reporters reading them are written once. The VM specific data
+//! (calldata, storage keys, values…) is rendered as strings: hex for the EVM,
+//! the debug format of the values for Move.
+
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceEvent<Addr> {
+    /// Call (or contract creation), `depth` being 0 for the transaction
+    Call {
+        depth: usize,
+        caller: Addr,
+        /// None for a contract creation that failed
+        target: Option<Addr>,
+        function: String,
+        input: String,
+    },
+    /// Return of the last call at the depth, `success` being None when the
+    /// call did not return, e.g., the execution stopped on a control leak
+    Return {
+        depth: usize,
+        success: Option<bool>,
+        output: String,
+    },
+    /// Write of the storage slot (EVM) or the resource (Move)
+    StateWrite { address: Addr, key: String, value: String },
+    /// Emitted log (EVM) or event (Move)
+    Event {
+        address: Option<Addr>,
+        topics: Vec<String>,
+        data: String,
+    },
+}
+
+/// Events of a transaction, in execution order
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionTrace<Addr> {
+    pub events: Vec<TraceEvent<Addr>>,
+}
+
+impl<Addr> Default for ExecutionTrace<Addr> {
+    fn default() -> Self {
+        Self { events: vec![] }
+    }
+}
+
+impl<Addr> ExecutionTrace<Addr> {
+    pub fn calls(&self) -> impl Iterator<Item = &TraceEvent<Addr>> {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::Call { .. }))
+    }
+
+    pub fn state_writes(&self) -> impl Iterator<Item = &TraceEvent<Addr>> {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::StateWrite { .. }))
+    }
+
+    pub fn emitted_events(&self) -> impl Iterator<Item = &TraceEvent<Addr>> {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::Event { .. }))
+    }
+
+    /// Whether the transaction succeeded, None if it did not return
+    pub fn success(&self) -> Option<bool> {
+        self.events.iter().rev().find_map(|event| match event {
+            TraceEvent::Return { depth: 0, success, .. } => *success,
+            _ => None,
+        })
+    }
+}
+
+impl<Addr: Debug> ExecutionTrace<Addr> {
+    /// One line per event, indented by the depth of the call it is in
+    pub fn render(&self) -> String {
+        self.render_with(|address| format!("{:?}", address))
+    }
+
+    /// [`ExecutionTrace::render`] with the addresses rendered by `name`, e.g.,
+    /// the names of the contracts
+    pub fn render_with(&self, name: impl Fn(&Addr) -> String) -> String {
+        let optional = |address: &Option<Addr>| address.as_ref().map_or("?".to_string(), &name);
+        // depth of the call being executed
+        let mut current = 0;
+        let mut lines = vec![];
+        for event in &self.events {
+            let (indent, line) = match event {
+                TraceEvent::Call {
+                    depth,
+                    caller,
+                    target,
+                    function,
+                    input,
+                } => {
+                    current = *depth;
+                    (
+                        *depth,
+                        format!("{} -> {}::{}({})", name(caller), optional(target), function, input),
+                    )
+                }
+                TraceEvent::Return { depth, success, output } => {
+                    current = depth.saturating_sub(1);
+                    (*depth, format!("<- success: {:?}, output: {}", success, output))
+                }
+                TraceEvent::StateWrite { address, key, value } => {
+                    (current + 1, format!("[write] {} {} = {}", name(address), key, value))
+                }
+                TraceEvent::Event { address, topics, data } => (
+                    current + 1,
+                    format!(
+                        "[event] {} topics: [{}] data: {}",
+                        optional(address),
+                        topics.join(", "),
+                        data
+                    ),
+                ),
+            };
+            lines.push(format!("{}{}", "  ".repeat(indent), line));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execution_trace() {
+        let trace = ExecutionTrace {
+            events: vec![
+                TraceEvent::Call {
+                    depth: 0,
+                    caller: 1,
+                    target: Some(2),
+                    function: "deposit".to_string(),
+                    input: "0x01".to_string(),
+                },
+                TraceEvent::Call {
+                    depth: 1,
+                    caller: 2,
+                    target: Some(3),
+                    function: "transfer".to_string(),
+                    input: "0x02".to_string(),
+                },
+                TraceEvent::StateWrite {
+                    address: 3,
+                    key: "0x0".to_string(),
+                    value: "0x1".to_string(),
+                },
+                TraceEvent::Return {
+                    depth: 1,
+                    success: Some(true),
+                    output: "0x".to_string(),
+                },
+                TraceEvent::Event {
+                    address: Some(2),
+                    topics: vec!["0xdd".to_string()],
+                    data: "0x".to_string(),
+                },
+                TraceEvent::Return {
+                    depth: 0,
+                    success: Some(false),
+                    output: "0x".to_string(),
+                },
+            ],
+        };
+        assert_eq!(trace.calls().count(), 2);
+        assert_eq!(trace.state_writes().count(), 1);
+        assert_eq!(trace.emitted_events().count(), 1);
+        assert_eq!(trace.success(), Some(false));
+        assert_eq!(
+            trace.render(),
+            [
+                "1 -> 2::deposit(0x01)",
+                "  2 -> 3::transfer(0x02)",
+                "    [write] 3 0x0 = 0x1",
+                "  <- success: Some(true), output: 0x",
+                "  [event] 2 topics: [0xdd] data: 0x",
+                "<- success: Some(false), output: 0x",
+            ]
+            .join("\n")
+        );
+        let name = |address: &i32| ["a", "b", "c", "d"][*address as usize].to_string();
+        assert!(trace
+            .render_with(name)
+            .starts_with("b -> c::deposit(0x01)\n  c -> d::transfer(0x02)"));
+    }
+}
@@ -2,7 +2,11 @@ use std::fmt::Debug;
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{generic_vm::vm_state::VMStateT, input::ConciseSerde, state_input::StagedVMState};
+use crate::{
+    generic_vm::{trace::ExecutionTrace, vm_state::VMStateT},
+    input::ConciseSerde,
+    state_input::StagedVMState,
+};
 
 pub const MAP_SIZE: usize = 4096;
 
@@ -80,4 +84,16 @@ pub trait GenericVM<VS, Code, By, Loc, Addr, SlotTy, Out, I, S, CI> {
     fn state_changed(&self) -> bool;
 
     fn as_any(&mut self) -> &mut dyn std::any::Any;
+
+    // optional methods
+
+    /// Whether the traces of the next executions are recorded, see
+    /// [`GenericVM::take_traces`]
+    fn set_tracing(&mut self, _enabled: bool) {}
+
+    /// Traces of the transactions executed since the last call, in execution
+    /// order. Empty if the tracing is disabled or not supported by the VM
+    fn take_traces(&mut self) -> Vec<ExecutionTrace<Addr>> {
+        vec![]
+    }
 }
@@ -138,6 +138,10 @@ pub trait SolutionTx {
     fn value(&self) -> String {
         String::from("")
     }
+    /// Value in wei as a hex string, for raw transactions
+    fn raw_value(&self) -> String {
+        String::from("0x0")
+    }
     fn is_borrow(&self) -> bool {
         false
     }
@@ -150,4 +154,13 @@ pub trait SolutionTx {
     fn calldata(&self) -> String {
         String::from("")
     }
+    fn gas_used(&self) -> u64 {
+        0
+    }
+    fn block_number(&self) -> String {
+        String::from("")
+    }
+    fn timestamp(&self) -> String {
+        String::from("")
+    }
 }
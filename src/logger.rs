@@ -1,7 +1,48 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::OnceLock,
+};
+
 use anyhow::Result;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
+/// File the logs are written to instead of stdout, e.g., while the dashboard
+/// takes the terminal
+static LOG_FILE: OnceLock<File> = OnceLock::new();
+
+/// Write the logs to `path` instead of stdout from now on
+pub fn redirect_to_file(path: &str) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let _ = LOG_FILE.set(file);
+    Ok(())
+}
+
+/// Print `msg` to stdout, or to the log file while the logs are redirected,
+/// so that the reports do not garble the dashboard
+pub fn print(msg: &str) {
+    let _ = writeln!(LogWriter, "{}", msg);
+}
+
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match LOG_FILE.get() {
+            Some(mut file) => file.write(buf),
+            None => io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match LOG_FILE.get() {
+            Some(mut file) => file.flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
+
 pub fn init() {
     let level = if cfg!(debug_assertions) {
         Level::DEBUG
@@ -19,7 +60,8 @@ fn init_logger(level: Level, with_target: bool) -> Result<()> {
     let subscriber_builder = FmtSubscriber::builder()
         .compact()
         .with_target(with_target)
-        .without_time();
+        .without_time()
+        .with_writer(|| LogWriter);
     let subscriber = subscriber_builder.with_max_level(level).finish();
     tracing::subscriber::set_global_default(subscriber)?;
     Ok(())
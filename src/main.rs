@@ -5,7 +5,10 @@
 
 extern crate core;
 
+pub mod bench;
 pub mod cache;
+pub mod checkpoint;
+pub mod config_file;
 pub mod r#const;
 pub mod evm;
 pub mod executor;
@@ -16,20 +19,30 @@ pub mod generic_vm;
 pub mod indexed_corpus;
 pub mod input;
 pub mod logger;
+pub mod metrics;
 pub mod minimizer;
 pub mod mutation_utils;
 pub mod oracle;
+pub mod persistence;
+pub mod pipeline;
 pub mod power_sched;
+pub mod report;
 pub mod scheduler;
+pub mod sequence_length;
 pub mod state;
 pub mod state_input;
+pub mod stop_conditions;
+pub mod telemetry;
 pub mod tracer;
 
 #[cfg(feature = "sui_support")]
 pub mod r#move;
 
+use bench::{bench_main, BenchArgs};
 use clap::{Parser, Subcommand};
-use evm::{evm_main, EvmArgs};
+use evm::{cmin_main, evm_main, replay_main, CminArgs, EvmArgs, ReplayArgs};
+use report::{report_main, ReportArgs};
+use tracing::error;
 
 #[cfg(feature = "sui_support")]
 use crate::r#move::{move_main, MoveArgs};
@@ -45,16 +58,32 @@ pub fn init_sentry() {
 }
 
 #[derive(Parser)]
-#[command(author, version=env!("GIT_VERSION_INFO"), about)]
+#[command(author, version=env!("GIT_VERSION_INFO"), about, args_conflicts_with_subcommands = true, arg_required_else_help = true)]
 struct Cli {
+    /// Load the fuzzer and its options, or a pipeline of campaigns, from a
+    /// TOML / YAML file
+    #[arg(long)]
+    config: Option<String>,
+
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 enum Commands {
+    /// Fuzz EVM smart contracts
     Evm(EvmArgs),
+    /// Replay transactions found by a previous EVM campaign
+    Replay(ReplayArgs),
+    /// Minimize the corpus of a previous EVM campaign, preserving its branch
+    /// coverage
+    Cmin(CminArgs),
+    /// Summarize the bugs found by a previous campaign
+    Report(ReportArgs),
+    /// Benchmark the time to find the bugs of the bundled vulnerable
+    /// contracts
+    Bench(BenchArgs),
     #[cfg(feature = "sui_support")]
     Move(MoveArgs),
 }
@@ -63,14 +92,46 @@ fn main() {
     init_sentry();
     logger::init();
 
-    let args = Cli::parse();
+    let mut args = Cli::parse();
+    if let Some(path) = args.config {
+        let config = config_file::read_config(&path).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        if pipeline::is_pipeline(&config) {
+            let stages = pipeline::parse_stages(&config).unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(1);
+            });
+            std::process::exit(if pipeline::run_pipeline(&stages) { 0 } else { 1 });
+        }
+        let config_args = config_file::config_to_args(&config).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        args = Cli::parse_from(config_args);
+    }
+
     match args.command {
-        Commands::Evm(args) => {
+        Some(Commands::Evm(args)) => {
             evm_main(args);
         }
+        Some(Commands::Replay(args)) => {
+            replay_main(args);
+        }
+        Some(Commands::Cmin(args)) => {
+            cmin_main(args);
+        }
+        Some(Commands::Report(args)) => {
+            report_main(args);
+        }
+        Some(Commands::Bench(args)) => {
+            std::process::exit(if bench_main(args) { 0 } else { 1 });
+        }
         #[cfg(feature = "sui_support")]
-        Commands::Move(args) => {
+        Some(Commands::Move(args)) => {
             move_main(args);
         }
+        None => unreachable!("a subcommand is required without --config"),
     }
 }
@@ -0,0 +1,255 @@
+//! Prometheus metrics of the campaign, served over HTTP on `/metrics` when
+//! enabled with `--metrics-addr`
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+use tracing::{info, warn};
+
+/// Instruction and branch coverage of a contract
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContractCoverage {
+    pub name: String,
+    pub instructions: usize,
+    pub total_instructions: usize,
+    pub branches: usize,
+    pub total_branches: usize,
+}
+
+pub struct Metrics {
+    executions: AtomicU64,
+    /// f64 bits
+    execs_per_sec: AtomicU64,
+    corpus_size: AtomicU64,
+    objectives: AtomicU64,
+    rpc_requests: AtomicU64,
+    rpc_credits: AtomicU64,
+    coverage: Mutex<Vec<ContractCoverage>>,
+    /// bug type -> number of times the oracles found it
+    oracle_triggers: Mutex<BTreeMap<String, u64>>,
+    /// Time and executions of the last stats, for the execution speed
+    last_stats: Mutex<Option<(Instant, u64)>>,
+}
+
+static METRICS: Metrics = Metrics::new();
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn metric_header(out: &mut String, name: &str, ty: &str, help: &str) {
+    out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, ty));
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            executions: AtomicU64::new(0),
+            execs_per_sec: AtomicU64::new(0),
+            corpus_size: AtomicU64::new(0),
+            objectives: AtomicU64::new(0),
+            rpc_requests: AtomicU64::new(0),
+            rpc_credits: AtomicU64::new(0),
+            coverage: Mutex::new(vec![]),
+            oracle_triggers: Mutex::new(BTreeMap::new()),
+            last_stats: Mutex::new(None),
+        }
+    }
+
+    pub fn record_stats(&self, executions: u64, corpus_size: u64, objectives: u64) {
+        let now = Instant::now();
+        let mut last_stats = self.last_stats.lock().unwrap();
+        if let Some((time, last_executions)) = *last_stats {
+            let secs = now.duration_since(time).as_secs_f64();
+            if secs > 0.0 {
+                let speed = executions.saturating_sub(last_executions) as f64 / secs;
+                self.execs_per_sec.store(speed.to_bits(), Ordering::Relaxed);
+            }
+        }
+        *last_stats = Some((now, executions));
+        self.executions.store(executions, Ordering::Relaxed);
+        self.corpus_size.store(corpus_size, Ordering::Relaxed);
+        self.objectives.store(objectives, Ordering::Relaxed);
+    }
+
+    pub fn record_rpc(&self, requests: u64, credits: u64) {
+        self.rpc_requests.store(requests, Ordering::Relaxed);
+        self.rpc_credits.store(credits, Ordering::Relaxed);
+    }
+
+    pub fn record_coverage(&self, mut coverage: Vec<ContractCoverage>) {
+        coverage.sort_by(|a, b| a.name.cmp(&b.name));
+        *self.coverage.lock().unwrap() = coverage;
+    }
+
+    /// Coverage of the contracts last recorded
+    pub fn coverage(&self) -> Vec<ContractCoverage> {
+        self.coverage.lock().unwrap().clone()
+    }
+
+    pub fn record_objective(&self, bug_type: &str) {
+        *self
+            .oracle_triggers
+            .lock()
+            .unwrap()
+            .entry(bug_type.to_string())
+            .or_default() += 1;
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let scalars = [
+            (
+                "ityfuzz_executions_total",
+                "counter",
+                "Number of executions",
+                self.executions.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "ityfuzz_execs_per_second",
+                "gauge",
+                "Executions per second since the last stats",
+                f64::from_bits(self.execs_per_sec.load(Ordering::Relaxed)),
+            ),
+            (
+                "ityfuzz_corpus_size",
+                "gauge",
+                "Number of testcases in the corpus",
+                self.corpus_size.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "ityfuzz_objectives_total",
+                "counter",
+                "Number of objectives found",
+                self.objectives.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "ityfuzz_rpc_requests_total",
+                "counter",
+                "Number of RPC requests sent",
+                self.rpc_requests.load(Ordering::Relaxed) as f64,
+            ),
+            (
+                "ityfuzz_rpc_credits_total",
+                "counter",
+                "Number of RPC credits consumed",
+                self.rpc_credits.load(Ordering::Relaxed) as f64,
+            ),
+        ];
+        for (name, ty, help, value) in scalars {
+            metric_header(&mut out, name, ty, help);
+            out.push_str(&format!("{} {}\n", name, value));
+        }
+
+        let coverage = self.coverage.lock().unwrap();
+        let per_contract: [(&str, &str, fn(&ContractCoverage) -> usize); 4] = [
+            ("ityfuzz_covered_instructions", "Number of covered instructions", |c| {
+                c.instructions
+            }),
+            ("ityfuzz_instructions", "Number of instructions", |c| {
+                c.total_instructions
+            }),
+            ("ityfuzz_covered_branches", "Number of covered branches", |c| c.branches),
+            ("ityfuzz_branches", "Number of branches", |c| c.total_branches),
+        ];
+        for (name, help, value) in per_contract {
+            metric_header(&mut out, name, "gauge", help);
+            for contract in coverage.iter() {
+                out.push_str(&format!(
+                    "{}{{contract=\"{}\"}} {}\n",
+                    name,
+                    escape(&contract.name),
+                    value(contract)
+                ));
+            }
+        }
+
+        let name = "ityfuzz_oracle_triggers_total";
+        metric_header(&mut out, name, "counter", "Number of objectives found by type");
+        for (bug_type, count) in self.oracle_triggers.lock().unwrap().iter() {
+            out.push_str(&format!("{}{{bug_type=\"{}\"}} {}\n", name, escape(bug_type), count));
+        }
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Metrics of the campaign
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+fn handle(mut stream: TcpStream) -> io::Result<()> {
+    let mut buf = [0; 1024];
+    let len = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let response = if path == "/metrics" {
+        let body = METRICS.render();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: \
+             close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes())
+}
+
+/// Serve the metrics on `http://<addr>/metrics` from a background thread
+pub fn serve(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    info!("Serving metrics on http://{}/metrics", listener.local_addr()?);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Err(e) = stream.and_then(handle) {
+                warn!("Failed to serve metrics: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let metrics = Metrics::new();
+        metrics.record_stats(1000, 10, 1);
+        metrics.record_coverage(vec![ContractCoverage {
+            name: "Token".to_string(),
+            instructions: 5,
+            total_instructions: 10,
+            branches: 2,
+            total_branches: 4,
+        }]);
+        metrics.record_objective("Reentrancy");
+        metrics.record_objective("Reentrancy");
+
+        let out = metrics.render();
+        assert!(out.contains("# TYPE ityfuzz_executions_total counter\nityfuzz_executions_total 1000\n"));
+        assert!(out.contains("ityfuzz_corpus_size 10\n"));
+        assert!(out.contains("ityfuzz_covered_branches{contract=\"Token\"} 2\n"));
+        assert!(out.contains("ityfuzz_oracle_triggers_total{bug_type=\"Reentrancy\"} 2\n"));
+    }
+}
@@ -29,8 +29,9 @@ use crate::{
         movevm,
         movevm::TypeTagInfoMeta,
         scheduler::MoveSchedulerMeta,
+        tokens::{coin_types, mint_coin},
         types::{MoveFuzzState, MoveInfantStateState, MoveStagedVMState},
-        vm_state::MoveVMState,
+        vm_state::{Gate, GatedValue, MoveVMState},
     },
     state::HasCaller,
     state_input::StagedVMState,
@@ -152,6 +153,51 @@ where
             .expect("failed to call infant scheduler on_add");
     }
 
+    /// Add an infant state holding `amount` of each coin the fuzzed functions
+    /// take or return, borrowed with a flashloan
+    pub fn add_flashloan_state(&mut self, amount: u64) {
+        let tags = self
+            .state
+            .metadata_map()
+            .get::<TypeTagInfoMeta>()
+            .expect("type tag info not found")
+            .clone();
+        let mut vm_state = MoveVMState::new();
+        for (ty, kind, coin) in coin_types(&tags) {
+            let abilities = self.executor.loader.abilities(&ty).expect("unknown coin type");
+            self.state
+                .metadata_map_mut()
+                .get_mut::<StructAbilities>()
+                .expect("StructAbilities not found")
+                .set_ability(ty.clone(), abilities);
+            let coin_value = mint_coin(kind, amount, self.state.get_rand_address());
+            vm_state.values.entry(ty).or_default().push((
+                GatedValue {
+                    v: coin_value,
+                    gate: Gate::Own,
+                },
+                1,
+            ));
+            *vm_state.borrowed.entry(coin).or_default() += amount as u128;
+        }
+        if vm_state.borrowed.is_empty() {
+            return;
+        }
+        info!("flashloan of {} coins", vm_state.borrowed.len());
+
+        let mut tc = Testcase::new(StagedVMState::new_with_state(vm_state));
+        tc.set_exec_time(Duration::from_secs(0));
+        let idx = self
+            .state
+            .infant_states_state
+            .corpus_mut()
+            .add(tc)
+            .expect("failed to add");
+        self.infant_scheduler
+            .on_add(&mut self.state.infant_states_state, idx)
+            .expect("failed to call infant scheduler on_add");
+    }
+
     pub fn initialize_glob(&mut self, dirs: Vec<String>) {
         let mut modules = vec![];
         let mut modules_dependencies = vec![];
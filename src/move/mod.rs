@@ -5,6 +5,7 @@ pub mod movevm;
 pub mod mutator;
 pub mod oracles;
 pub mod scheduler;
+pub mod tokens;
 pub mod types;
 pub mod vm_state;
 
@@ -25,6 +26,15 @@ pub struct MoveArgs {
     /// Seed for the RNG
     #[arg(short, long, default_value = "0")]
     seed: u64,
+
+    /// Borrow the coins taken by the fuzzed functions, enabling the profit
+    /// oracle
+    #[arg(long, default_value = "false")]
+    flashloan: bool,
+
+    /// Coin the profit is measured in
+    #[arg(long, default_value = "0x2::sui::SUI")]
+    numeraire: String,
 }
 
 pub fn move_main(args: MoveArgs) {
@@ -32,5 +42,7 @@ pub fn move_main(args: MoveArgs) {
         target: args.target,
         work_dir: "./work_dir".to_string(),
         seed: args.seed,
+        flashloan: args.flashloan,
+        numeraire: args.numeraire,
     });
 }
@@ -42,6 +42,7 @@ use tracing::debug;
 use super::types::MoveFuzzState;
 use crate::{
     generic_vm::{
+        trace::{ExecutionTrace, TraceEvent},
         vm_executor::{ExecutionResult, GenericVM, MAP_SIZE},
         vm_state::VMStateT,
     },
@@ -67,6 +68,10 @@ pub struct MoveVM<I, S> {
     pub loader: Loader,
     pub protocol_config: ProtocolConfig,
     pub native_context: NativeContextExtensions<'static>,
+    /// Whether the traces are recorded, see [`GenericVM::set_tracing`]. The
+    /// args and outputs of the nested calls are not recorded
+    tracing: bool,
+    traces: Vec<ExecutionTrace<AccountAddress>>,
     _phantom: std::marker::PhantomData<(I, S)>,
 }
 
@@ -92,6 +97,10 @@ impl TypeTagInfoMeta {
         }
     }
     pub fn register_type_tag(&mut self, ty: Type, loader: &Loader) {
+        // the values of the state are keyed by the type referenced
+        if let Type::Reference(inner) | Type::MutableReference(inner) = &ty {
+            self.register_type_tag((**inner).clone(), loader);
+        }
         let tag = self.find_type(&ty, loader);
         if let TypeTag::Struct(struct_tag) = tag {
             if is_tx_context(&struct_tag) {
@@ -150,6 +159,8 @@ impl<I, S> MoveVM<I, S> {
             loader: Loader::new(Self::get_natives(), Default::default()),
             protocol_config: Self::get_protocol_config(),
             native_context: Self::get_extension(),
+            tracing: false,
+            traces: vec![],
             _phantom: Default::default(),
         }
     }
@@ -185,6 +196,15 @@ impl<I, S> MoveVM<I, S> {
         state.total_events_size = 0;
     }
 
+    /// Module defining `func`, None if it is not deployed (e.g., the natives
+    /// of the framework)
+    fn module_of(&self, func: &Arc<Function>) -> Option<&ModuleId> {
+        self.functions
+            .iter()
+            .find(|(_, functions)| functions.values().any(|function| Arc::ptr_eq(function, func)))
+            .map(|(module, _)| module)
+    }
+
     pub fn call_native(
         func: Arc<Function>,
         ty_args: Vec<Type>,
@@ -487,7 +507,7 @@ where
                 .or_default()
                 .insert(f.name.to_owned(), f.clone());
             let meta = state.metadata_map_mut().get_mut::<TypeTagInfoMeta>().unwrap();
-            for ty in &f.parameter_types {
+            for ty in f.parameter_types.iter().chain(f.return_types().iter()) {
                 meta.register_type_tag(ty.clone(), &self.loader);
             }
         }
@@ -617,6 +637,49 @@ where
         let mut native_called = false;
         let mut gas_meter = UnmeteredGasMeter {};
 
+        let mut trace = ExecutionTrace::default();
+        if self.tracing {
+            trace.events.push(TraceEvent::Call {
+                depth: 0,
+                caller: input.get_caller(),
+                target: Some(*input.module_id().address()),
+                function: format!("{}::{}", input.module_id().name(), input.function_name()),
+                input: format!("{:?}", input.args()),
+            });
+        }
+        // nested calls, at the depth of the frame called
+        macro_rules! trace_call {
+            ($func: expr, $depth: expr) => {
+                if self.tracing {
+                    let caller = self
+                        .module_of(&current_frame.function)
+                        .map_or(input.get_caller(), |module| *module.address());
+                    let module = self.module_of(&$func);
+                    trace.events.push(TraceEvent::Call {
+                        depth: $depth,
+                        caller,
+                        target: module.map(|module| *module.address()),
+                        function: match module {
+                            Some(module) => format!("{}::{}", module.name(), $func.name),
+                            None => $func.name.to_string(),
+                        },
+                        input: String::new(),
+                    });
+                }
+            };
+        }
+        macro_rules! trace_return {
+            ($depth: expr, $success: expr) => {
+                if self.tracing {
+                    trace.events.push(TraceEvent::Return {
+                        depth: $depth,
+                        success: Some($success),
+                        output: String::new(),
+                    });
+                }
+            };
+        }
+
         // debug!("running {:?} with args {:?}", initial_function.name.as_str(),
         // input.args());
 
@@ -640,6 +703,7 @@ where
             match ret.unwrap() {
                 ExitCode::Return => match call_stack.pop() {
                     Some(frame) => {
+                        trace_return!(call_stack.len() + 1, true);
                         current_frame = frame;
                         current_frame.pc += 1;
                     }
@@ -651,10 +715,11 @@ where
                     // todo: handle native here
                     let func = resolver.function_from_handle(fh_idx);
 
+                    trace_call!(func, call_stack.len() + 1);
                     if func.is_native() {
                         // debug!("calling native function: {:?}", func.name.as_str());
                         native_called = true;
-                        if !Self::call_native(
+                        let ok = Self::call_native(
                             func,
                             vec![],
                             &mut interp,
@@ -662,7 +727,9 @@ where
                             &resolver,
                             &mut gas_meter,
                             &mut self.native_context,
-                        ) {
+                        );
+                        trace_return!(call_stack.len() + 1, ok);
+                        if !ok {
                             reverted = true;
                             break;
                         } else {
@@ -696,9 +763,10 @@ where
                     let func = resolver.function_from_instantiation(fh_idx);
 
                     // todo: handle native here
+                    trace_call!(func, call_stack.len() + 1);
                     if func.is_native() {
                         native_called = true;
-                        if !Self::call_native(
+                        let ok = Self::call_native(
                             func,
                             ty_args,
                             &mut interp,
@@ -706,7 +774,9 @@ where
                             &resolver,
                             &mut gas_meter,
                             &mut self.native_context,
-                        ) {
+                        );
+                        trace_return!(call_stack.len() + 1, ok);
+                        if !ok {
                             reverted = true;
                             break;
                         } else {
@@ -741,7 +811,14 @@ where
         // debug!("{:?}", interp.operand_stack.value);
 
         macro_rules! add_value {
-            ($v: expr, $t: expr, $gate: expr) => {{
+            ($v: expr, $t: expr, $gate: expr, $owner: expr) => {{
+                if self.tracing {
+                    trace.events.push(TraceEvent::StateWrite {
+                        address: $owner,
+                        key: format!("{:?}", $t),
+                        value: format!("{:?}", $v),
+                    });
+                }
                 let res = vm_state.add_new_value(
                     GatedValue {
                         v: $v.clone(),
@@ -765,14 +842,14 @@ where
             .iter()
             .zip(initial_function.return_types().iter())
         {
-            add_value!(v, t, Gate::Own);
+            add_value!(v, t, Gate::Own, input.get_caller());
             // debug!("adding as own: {:?}", v);
             out.vars.push((t.clone(), v.clone()));
             // debug!("val: {:?} {:?}", v, resolver.loader.type_to_type_tag(t));
         }
 
         if native_called {
-            for (_uid, (owner, ty, value)) in &self.native_context.get::<ObjectRuntime>().state.transfers {
+            for (uid, (owner, ty, value)) in &self.native_context.get::<ObjectRuntime>().state.transfers {
                 let gate = match owner {
                     Owner::AddressOwner(addr) => {
                         if state.has_caller(&MoveAddress::new(addr.to_vec().try_into().unwrap())) {
@@ -790,11 +867,24 @@ where
 
                 // debug!("adding as {:?}: {:?}", gate, value);
 
-                add_value!(value, ty, gate);
+                // the shared and immutable objects are keyed by their id
+                let owner = match owner {
+                    Owner::AddressOwner(addr) => AccountAddress::from_bytes(addr.to_vec()),
+                    _ => AccountAddress::from_bytes(uid.to_vec()),
+                }
+                .unwrap();
+                add_value!(value, ty, gate, owner);
                 // debug!("transfer: {:?}", t);
             }
 
             for (_t, st, v) in &self.native_context.get::<ObjectRuntime>().state.events {
+                if self.tracing {
+                    trace.events.push(TraceEvent::Event {
+                        address: Some(st.address),
+                        topics: vec![st.to_string()],
+                        data: format!("{:?}", v),
+                    });
+                }
                 // debug!("st.name.as_str(): {:?}, v: {:?}", st.name.as_str(), v);
                 if st.name.as_str() == "AAAA__fuzzland_move_bug" {
                     if let Value(ValueImpl::Container(Container::Struct(data))) = v {
@@ -813,6 +903,15 @@ where
             self.clear_context();
         }
 
+        if self.tracing {
+            trace.events.push(TraceEvent::Return {
+                depth: 0,
+                success: Some(!reverted),
+                output: format!("{:?}", out.vars.iter().map(|(_, v)| v).collect::<Vec<_>>()),
+            });
+            self.traces.push(trace);
+        }
+
         ExecutionResult {
             new_state: StagedVMState::new_with_state(vm_state),
             output: out,
@@ -840,6 +939,14 @@ where
     fn state_changed(&self) -> bool {
         unsafe { MOVE_STATE_CHANGED }
     }
+
+    fn set_tracing(&mut self, enabled: bool) {
+        self.tracing = enabled;
+    }
+
+    fn take_traces(&mut self) -> Vec<ExecutionTrace<AccountAddress>> {
+        std::mem::take(&mut self.traces)
+    }
 }
 
 pub struct DummyChildObjectResolver;
@@ -919,6 +1026,17 @@ mod tests {
         args: Vec<CloneableValue>,
         func: &str,
     ) -> ExecutionResult<ModuleId, AccountAddress, MoveVMState, MoveOutput, ConciseMoveInput> {
+        _run_traced(bytecode, args, func).0
+    }
+
+    fn _run_traced(
+        bytecode: &str,
+        args: Vec<CloneableValue>,
+        func: &str,
+    ) -> (
+        ExecutionResult<ModuleId, AccountAddress, MoveVMState, MoveOutput, ConciseMoveInput>,
+        Vec<ExecutionTrace<AccountAddress>>,
+    ) {
         let module_bytecode = hex::decode(bytecode).unwrap();
         let module = CompiledModule::deserialize_no_check_bounds(&module_bytecode).unwrap();
         let _module_idx = module.self_id();
@@ -967,7 +1085,9 @@ mod tests {
             _deps: Default::default(),
             _resolved: true,
         };
-        mv.execute(&input.clone(), &mut FuzzState::new(0))
+        mv.set_tracing(true);
+        let res = mv.execute(&input.clone(), &mut FuzzState::new(0));
+        (res, mv.take_traces())
     }
 
     #[test]
@@ -982,6 +1102,35 @@ mod tests {
         _run(module_hex, vec![CloneableValue::from(Value::u64(20))], "test1");
     }
 
+    #[test]
+    fn test_tracing() {
+        // module 0x3::TestMod {
+        //         public fun test1(data: u64) : u64 {
+        //         data * 2
+        //     }
+        // }
+        let module_hex = "a11ceb0b0500000006010002030205050703070a0e0818200c38130000000100000001030007546573744d6f6405746573743100000000000000000000000000000000000000000000000000000000000000030001000001040b00060200000000000000180200";
+        let (res, traces) = _run_traced(module_hex, vec![CloneableValue::from(Value::u64(20))], "test1");
+        assert!(!res.reverted);
+        assert_eq!(traces.len(), 1);
+        let trace = &traces[0];
+        assert_eq!(trace.calls().count(), 1);
+        assert_eq!(trace.success(), Some(true));
+        match &trace.events[0] {
+            TraceEvent::Call {
+                depth,
+                target,
+                function,
+                ..
+            } => {
+                assert_eq!(*depth, 0);
+                assert_eq!(*target, Some(AccountAddress::from_hex_literal("0x3").unwrap()));
+                assert_eq!(function, "TestMod::test1");
+            }
+            _ => unreachable!(),
+        }
+    }
+
     #[test]
     fn test_dropping() {
         // module 0x3::TestMod {
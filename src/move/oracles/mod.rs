@@ -1,3 +1,5 @@
+pub mod profit;
 pub mod typed_bug;
 
 pub static TYPED_BUG_BUG_IDX: u64 = 4;
+pub static PROFIT_BUG_IDX: u64 = 5;
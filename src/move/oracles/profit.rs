@@ -0,0 +1,71 @@
+use libafl::state::HasMetadata;
+use move_binary_format::CompiledModule;
+use move_core_types::language_storage::ModuleId;
+use serde_json::json;
+
+use crate::{
+    fuzzer::ORACLE_OUTPUT,
+    oracle::Oracle,
+    r#move::{
+        input::{ConciseMoveInput, MoveFunctionInput},
+        movevm::{MoveVM, TypeTagInfoMeta},
+        oracles::PROFIT_BUG_IDX,
+        tokens::{find_pools, holdings, MoveTokenContext},
+        types::{MoveAddress, MoveFuzzState, MoveOracleCtx, MoveOutput, MoveSlotTy},
+        vm_state::MoveVMState,
+    },
+};
+
+/// Reports when the coins held by the fuzzer are worth more than the ones it
+/// borrowed, priced through the pools of the post-state
+pub struct ProfitOracle {
+    pub context: MoveTokenContext,
+}
+
+impl ProfitOracle {
+    pub fn new(context: MoveTokenContext) -> Self {
+        Self { context }
+    }
+}
+
+impl
+    Oracle<
+        MoveVMState,
+        MoveAddress,
+        CompiledModule,
+        MoveFunctionInput,
+        ModuleId,
+        MoveSlotTy,
+        MoveOutput,
+        MoveFunctionInput,
+        MoveFuzzState,
+        ConciseMoveInput,
+        MoveVM<MoveFunctionInput, MoveFuzzState>,
+    > for ProfitOracle
+{
+    fn transition(&self, _ctx: &mut MoveOracleCtx<'_>, _stage: u64) -> u64 {
+        0
+    }
+
+    fn oracle(&self, ctx: &mut MoveOracleCtx<'_>, _stage: u64) -> Vec<u64> {
+        let Some(tags) = ctx.fuzz_state.metadata_map().get::<TypeTagInfoMeta>() else {
+            return vec![];
+        };
+        let pools = find_pools(&ctx.post_state, tags);
+        let holdings = holdings(&ctx.post_state, tags);
+        match self.context.profit(&pools, &holdings, &ctx.post_state.borrowed) {
+            Some(profit) if profit > 0 => {
+                let msg = json!({
+                    "bug_type": "Profit".to_string(),
+                    "bug_info": format!("earned {} of {}", profit, self.context.numeraire),
+                    "bug_idx": PROFIT_BUG_IDX,
+                });
+                unsafe {
+                    ORACLE_OUTPUT.push(msg);
+                }
+                vec![PROFIT_BUG_IDX]
+            }
+            _ => vec![],
+        }
+    }
+}
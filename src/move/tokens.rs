@@ -0,0 +1,375 @@
+//! Coins and their prices for the Move VM, the counterpart of `evm/tokens`.
+//!
+//! The coins are `0x2::coin::Coin<T>` and `0x2::balance::Balance<T>` on Sui
+//! and `0x1::coin::Coin<T>` on Aptos. They are priced in a numeraire coin by
+//! selling them through the constant product pools of the DEX modules found in
+//! the state: structs with (at least) two type parameters, the coins of the
+//! pool, whose first two balance fields (structs holding a single `u64`) are
+//! the reserves of these coins, e.g., `Pool<X, Y> { id, reserve_x: Balance<X>,
+//! reserve_y: Balance<Y>, .. }`.
+//!
+//! With flashloans, the fuzzer holds a borrowed coin of each coin type the
+//! fuzzed functions take, and the profit is the value of the coins held
+//! minus the borrowed ones.
+
+use std::collections::{BTreeMap, HashSet};
+
+use move_core_types::{
+    account_address::AccountAddress,
+    language_storage::{StructTag, TypeTag},
+};
+use move_vm_types::{
+    loaded_data::runtime_types::Type,
+    values::{Container, Struct, Value, ValueImpl},
+};
+
+use crate::r#move::{
+    movevm::TypeTagInfoMeta,
+    vm_state::{Gate, MoveVMState},
+};
+
+/// Fee of the pools, in thousandths of the amount in
+const POOL_FEE: u128 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoinKind {
+    /// `0x2::coin::Coin<T> { id: UID, balance: Balance<T> }`
+    SuiCoin,
+    /// `0x2::balance::Balance<T> { value: u64 }`
+    SuiBalance,
+    /// `0x1::coin::Coin<T> { value: u64 }`
+    AptosCoin,
+}
+
+/// Kind and coin type of a coin struct
+pub fn coin_of(tag: &StructTag) -> Option<(CoinKind, &TypeTag)> {
+    let kind = match (
+        tag.address.short_str_lossless().as_str(),
+        tag.module.as_str(),
+        tag.name.as_str(),
+    ) {
+        ("2", "coin", "Coin") => CoinKind::SuiCoin,
+        ("2", "balance", "Balance") => CoinKind::SuiBalance,
+        ("1", "coin", "Coin") => CoinKind::AptosCoin,
+        _ => return None,
+    };
+    match tag.type_params.as_slice() {
+        [coin] => Some((kind, coin)),
+        _ => None,
+    }
+}
+
+/// Fields of a struct value
+fn fields(v: &ValueImpl) -> Option<Vec<ValueImpl>> {
+    match v {
+        ValueImpl::Container(Container::Struct(fields)) => Some((**fields).borrow().clone()),
+        _ => None,
+    }
+}
+
+/// Amount of a coin or a balance, held by its last field (or the last field of
+/// its last field)
+pub fn coin_amount(v: &ValueImpl) -> Option<u64> {
+    match v {
+        ValueImpl::U64(amount) => Some(*amount),
+        _ => coin_amount(fields(v)?.last()?),
+    }
+}
+
+/// A coin of the kind, `id` being the object id of a Sui coin
+pub fn mint_coin(kind: CoinKind, amount: u64, id: AccountAddress) -> Value {
+    let balance = Value::struct_(Struct::pack(vec![Value::u64(amount)]));
+    match kind {
+        CoinKind::SuiCoin => {
+            let uid = Value::struct_(Struct::pack(vec![Value::struct_(Struct::pack(vec![Value::address(
+                id,
+            )]))]));
+            Value::struct_(Struct::pack(vec![uid, balance]))
+        }
+        CoinKind::SuiBalance | CoinKind::AptosCoin => balance,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovePool {
+    pub coin_x: TypeTag,
+    pub coin_y: TypeTag,
+    pub reserve_x: u128,
+    pub reserve_y: u128,
+}
+
+impl MovePool {
+    /// Reserves of the coin and of the other coin of the pool, None if the
+    /// coin is not in the pool
+    fn reserves(&self, coin: &TypeTag) -> Option<(u128, u128, &TypeTag)> {
+        if *coin == self.coin_x {
+            Some((self.reserve_x, self.reserve_y, &self.coin_y))
+        } else if *coin == self.coin_y {
+            Some((self.reserve_y, self.reserve_x, &self.coin_x))
+        } else {
+            None
+        }
+    }
+
+    /// Coin received and its amount when swapping `amount_in` of `coin_in`
+    pub fn amount_out(&self, coin_in: &TypeTag, amount_in: u128) -> Option<(&TypeTag, u128)> {
+        let (reserve_in, reserve_out, coin_out) = self.reserves(coin_in)?;
+        let amount_in = amount_in.checked_mul(1000 - POOL_FEE)?;
+        let out = amount_in
+            .checked_mul(reserve_out)?
+            .checked_div(reserve_in.checked_mul(1000)?.checked_add(amount_in)?)?;
+        Some((coin_out, out))
+    }
+
+    /// Coin paid and its amount when swapping for `amount_out` of `coin_out`,
+    /// None if the pool does not hold enough of `coin_out`
+    pub fn amount_in(&self, coin_out: &TypeTag, amount_out: u128) -> Option<(&TypeTag, u128)> {
+        let (reserve_out, reserve_in, coin_in) = self.reserves(coin_out)?;
+        if amount_out >= reserve_out {
+            return None;
+        }
+        let amount_in = reserve_in
+            .checked_mul(amount_out)?
+            .checked_mul(1000)?
+            .checked_div((reserve_out - amount_out).checked_mul(1000 - POOL_FEE)?)?;
+        Some((coin_in, amount_in + 1))
+    }
+}
+
+/// Constant product pools held in the state
+pub fn find_pools(state: &MoveVMState, tags: &TypeTagInfoMeta) -> Vec<MovePool> {
+    let values = state
+        .values
+        .iter()
+        .flat_map(|(ty, values)| values.iter().map(move |(v, _)| (ty, &v.v)))
+        .chain(
+            state
+                .resources
+                .values()
+                .flat_map(|resources| resources.iter().map(|(ty, v)| (ty, v))),
+        );
+    let mut pools = vec![];
+    for (ty, value) in values {
+        let Some(tag) = tags.get_type_tag(ty) else {
+            continue;
+        };
+        if tag.type_params.len() < 2 || coin_of(tag).is_some() {
+            continue;
+        }
+        let Some(fields) = fields(&value.0) else {
+            continue;
+        };
+        let balances = fields
+            .iter()
+            .filter_map(|field| balance_amount(field).map(|amount| amount as u128))
+            .take(2)
+            .collect::<Vec<_>>();
+        if let [reserve_x, reserve_y] = balances[..] {
+            pools.push(MovePool {
+                coin_x: tag.type_params[0].clone(),
+                coin_y: tag.type_params[1].clone(),
+                reserve_x,
+                reserve_y,
+            });
+        }
+    }
+    pools
+}
+
+/// Amount of a balance field, a struct holding a single `u64`
+fn balance_amount(v: &ValueImpl) -> Option<u64> {
+    match fields(v)?.as_slice() {
+        [ValueImpl::U64(amount)] => Some(*amount),
+        _ => None,
+    }
+}
+
+/// Amounts of the coins owned by the fuzzer, by coin type
+pub fn holdings(state: &MoveVMState, tags: &TypeTagInfoMeta) -> BTreeMap<TypeTag, u128> {
+    let mut holdings = BTreeMap::new();
+    for (ty, values) in &state.values {
+        let Some((_, coin)) = tags.get_type_tag(ty).and_then(coin_of) else {
+            continue;
+        };
+        for (value, count) in values {
+            if value.gate != Gate::Own {
+                continue;
+            }
+            if let Some(amount) = coin_amount(&value.v.0) {
+                *holdings.entry(coin.clone()).or_default() += amount as u128 * *count as u128;
+            }
+        }
+    }
+    holdings
+}
+
+/// Coin structs of the types taken or returned by the functions, along with
+/// their kind and coin type
+pub fn coin_types(tags: &TypeTagInfoMeta) -> Vec<(Type, CoinKind, TypeTag)> {
+    tags.type_to_type_tag
+        .iter()
+        .filter(|(ty, _)| !matches!(ty, Type::Reference(_) | Type::MutableReference(_)))
+        .filter_map(|(ty, tag)| {
+            let (kind, coin) = coin_of(tag)?;
+            Some((ty.clone(), kind, coin.clone()))
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct MoveTokenContext {
+    /// Coin the profit is measured in
+    pub numeraire: TypeTag,
+}
+
+impl MoveTokenContext {
+    pub fn new(numeraire: TypeTag) -> Self {
+        Self { numeraire }
+    }
+
+    /// Numeraire received selling `amount` of `coin` through the best path
+    /// of at most two pools
+    pub fn sell_value(&self, pools: &[MovePool], coin: &TypeTag, amount: u128) -> Option<u128> {
+        if *coin == self.numeraire {
+            return Some(amount);
+        }
+        let mut best = None;
+        for (idx, pool) in pools.iter().enumerate() {
+            let Some((mid, out)) = pool.amount_out(coin, amount) else {
+                continue;
+            };
+            let value = if *mid == self.numeraire {
+                Some(out)
+            } else {
+                pools
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != idx)
+                    .filter_map(|(_, next)| match next.amount_out(mid, out) {
+                        Some((coin_out, out)) if *coin_out == self.numeraire => Some(out),
+                        _ => None,
+                    })
+                    .max()
+            };
+            best = best.max(value);
+        }
+        best
+    }
+
+    /// Numeraire paid buying `amount` of `coin` through the cheapest path of
+    /// at most two pools, None if the pools do not hold enough of the coin
+    pub fn buy_cost(&self, pools: &[MovePool], coin: &TypeTag, amount: u128) -> Option<u128> {
+        if *coin == self.numeraire {
+            return Some(amount);
+        }
+        let mut best: Option<u128> = None;
+        for (idx, pool) in pools.iter().enumerate() {
+            let Some((mid, paid)) = pool.amount_in(coin, amount) else {
+                continue;
+            };
+            let cost = if *mid == self.numeraire {
+                Some(paid)
+            } else {
+                pools
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != idx)
+                    .filter_map(|(_, prev)| match prev.amount_in(mid, paid) {
+                        Some((coin_in, paid)) if *coin_in == self.numeraire => Some(paid),
+                        _ => None,
+                    })
+                    .min()
+            };
+            if let Some(cost) = cost {
+                best = Some(best.map_or(cost, |best| best.min(cost)));
+            }
+        }
+        best
+    }
+
+    /// Numeraire earned by the fuzzer: the coins held beyond the borrowed
+    /// ones sold, minus the cost of buying back the borrowed coins spent.
+    /// None if a coin spent cannot be bought back
+    pub fn profit(
+        &self,
+        pools: &[MovePool],
+        holdings: &BTreeMap<TypeTag, u128>,
+        borrowed: &BTreeMap<TypeTag, u128>,
+    ) -> Option<i128> {
+        let coins = holdings.keys().chain(borrowed.keys()).collect::<HashSet<_>>();
+        let mut profit: i128 = 0;
+        for coin in coins {
+            let held = holdings.get(coin).copied().unwrap_or_default();
+            let owed = borrowed.get(coin).copied().unwrap_or_default();
+            if held > owed {
+                profit += self.sell_value(pools, coin, held - owed).unwrap_or_default() as i128;
+            } else if owed > held {
+                profit -= self.buy_cost(pools, coin, owed - held)? as i128;
+            }
+        }
+        Some(profit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use move_core_types::identifier::Identifier;
+
+    use super::*;
+
+    fn coin(name: &str) -> TypeTag {
+        TypeTag::Struct(Box::new(StructTag {
+            address: AccountAddress::from_hex_literal("0x42").unwrap(),
+            module: Identifier::new(name).unwrap(),
+            name: Identifier::new(name.to_uppercase()).unwrap(),
+            type_params: vec![],
+        }))
+    }
+
+    #[test]
+    fn test_move_token_context() {
+        let (sui, usdc, eth) = (coin("sui"), coin("usdc"), coin("eth"));
+        let pools = vec![
+            MovePool {
+                coin_x: usdc.clone(),
+                coin_y: sui.clone(),
+                reserve_x: 1_000_000,
+                reserve_y: 2_000_000,
+            },
+            MovePool {
+                coin_x: eth.clone(),
+                coin_y: usdc.clone(),
+                reserve_x: 1_000,
+                reserve_y: 1_000_000,
+            },
+        ];
+        let context = MoveTokenContext::new(sui.clone());
+        assert_eq!(context.sell_value(&pools, &sui, 5), Some(5));
+        // 1000 usdc for about 2000 sui, minus the fee and the slippage
+        let direct = context.sell_value(&pools, &usdc, 1000).unwrap();
+        assert!(direct > 1980 && direct < 2000);
+        // eth through usdc
+        let two_hops = context.sell_value(&pools, &eth, 1).unwrap();
+        assert!(two_hops > 1950 && two_hops < 2000);
+        assert!(context.buy_cost(&pools, &usdc, 1000).unwrap() > 2000);
+        assert!(context.buy_cost(&pools, &eth, 1_000).is_none());
+
+        let borrowed = BTreeMap::from([(usdc.clone(), 10_000)]);
+        // spent nothing
+        assert_eq!(context.profit(&pools, &borrowed, &borrowed), Some(0));
+        // gained eth
+        let holdings = BTreeMap::from([(usdc.clone(), 10_000), (eth.clone(), 1)]);
+        assert_eq!(context.profit(&pools, &holdings, &borrowed), Some(two_hops as i128));
+        // spent usdc
+        let holdings = BTreeMap::from([(usdc.clone(), 9_000)]);
+        assert!(context.profit(&pools, &holdings, &borrowed).unwrap() < -2000);
+    }
+
+    #[test]
+    fn test_coin_amount() {
+        let id = AccountAddress::random();
+        for kind in [CoinKind::SuiCoin, CoinKind::SuiBalance, CoinKind::AptosCoin] {
+            assert_eq!(coin_amount(&mint_coin(kind, 42, id).0), Some(42));
+        }
+    }
+}
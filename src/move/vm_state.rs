@@ -1,6 +1,6 @@
 use std::{
     any::Any,
-    collections::{hash_map::DefaultHasher, HashMap},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
     hash::{Hash, Hasher},
 };
 
@@ -12,7 +12,7 @@ use move_core_types::{
     effects::Op,
     gas_algebra::NumBytes,
     identifier::IdentStr,
-    language_storage::ModuleId,
+    language_storage::{ModuleId, TypeTag},
     value::MoveTypeLayout,
 };
 use move_vm_runtime::loader::Resolver;
@@ -84,6 +84,9 @@ pub struct MoveVMState {
     pub typed_bug: Vec<String>,
 
     pub ref_in_use: Vec<(Type, GatedValue)>,
+
+    /// Amounts of the coins borrowed by the fuzzer, by coin type
+    pub borrowed: BTreeMap<TypeTag, u128>,
 }
 
 impl MoveVMStateT for MoveVMState {
@@ -115,6 +118,7 @@ impl MoveVMState {
             values: HashMap::new(),
             typed_bug: vec![],
             ref_in_use: vec![],
+            borrowed: BTreeMap::new(),
         }
     }
 
@@ -372,6 +376,7 @@ impl Clone for MoveVMState {
             values: self.values.clone(),
             typed_bug: self.typed_bug.clone(),
             ref_in_use: self.ref_in_use.clone(),
+            borrowed: self.borrowed.clone(),
         }
     }
 }
@@ -558,6 +563,7 @@ impl VMStateT for MoveVMState {
                 amt.hash(&mut hasher);
             });
         });
+        self.borrowed.hash(&mut hasher);
 
         hasher.finish()
     }
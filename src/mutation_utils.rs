@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 /// Mutation utilities for the EVM
 use libafl::inputs::{HasBytesVec, Input};
@@ -35,7 +35,10 @@ use libafl::{
 use libafl_bolts::{impl_serdeany, prelude::Rand, tuples::tuple_list, Named};
 use serde::{Deserialize, Serialize};
 
-use crate::{evm::types::EVMU256, r#const::MAX_STACK_POW};
+use crate::{
+    evm::{dictionary::sample_dictionary_value, types::EVMU256, vm::EVMStorage},
+    r#const::{INTERESTING_VALUES_MAX, MAX_STACK_POW},
+};
 
 /// Constants in the contracts
 ///
@@ -60,6 +63,56 @@ impl ConstantPoolMetadata {
 
 impl_serdeany!(ConstantPoolMetadata);
 
+/// Interesting values observed during the campaign, e.g., storage values,
+/// reserves and comparison operands
+///
+/// Unlike [`ConstantPoolMetadata`], the pool keeps evolving: once full, the
+/// oldest values are replaced by the newly observed ones.
+///
+/// This is metadata attached to the global fuzz state
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub struct InterestingValuesMetadata {
+    /// Values in the pool
+    pub values: Vec<EVMU256>,
+    /// Set of values in the pool, for deduplication
+    known: HashSet<EVMU256>,
+    /// Index of the value to be replaced when the pool is full
+    next_evict: usize,
+}
+
+impl InterestingValuesMetadata {
+    /// Create a new [`InterestingValuesMetadata`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a value to the pool, trivial values are ignored
+    pub fn add_value(&mut self, value: EVMU256) {
+        if value <= EVMU256::from(1) || value == EVMU256::MAX || self.known.contains(&value) {
+            return;
+        }
+        self.known.insert(value);
+        if self.values.len() < INTERESTING_VALUES_MAX {
+            self.values.push(value);
+        } else {
+            let evicted = std::mem::replace(&mut self.values[self.next_evict], value);
+            self.known.remove(&evicted);
+            self.next_evict = (self.next_evict + 1) % INTERESTING_VALUES_MAX;
+        }
+    }
+}
+
+impl_serdeany!(InterestingValuesMetadata);
+
+/// Add the values to the [`InterestingValuesMetadata`] of the state
+pub fn add_interesting_values<S: HasMetadata>(state: &mut S, values: impl IntoIterator<Item = EVMU256>) {
+    if !state.has_metadata::<InterestingValuesMetadata>() {
+        state.metadata_map_mut().insert(InterestingValuesMetadata::new());
+    }
+    let meta = state.metadata_map_mut().get_mut::<InterestingValuesMetadata>().unwrap();
+    values.into_iter().for_each(|value| meta.add_value(value));
+}
+
 /// Metadata for Mutations
 ///
 /// This is metadata attached to the global fuzz state
@@ -372,7 +425,7 @@ where
 /// setting the bytes to the values in the VM state allow us to increase test
 /// coverage.
 pub struct VMStateHintedMutator<'a> {
-    pub vm_slots: &'a HashMap<EVMU256, EVMU256>,
+    pub vm_slots: &'a EVMStorage,
 }
 
 impl Named for VMStateHintedMutator<'_> {
@@ -382,13 +435,13 @@ impl Named for VMStateHintedMutator<'_> {
 }
 
 impl<'a> VMStateHintedMutator<'a> {
-    pub fn new(vm_slots: &'a HashMap<EVMU256, EVMU256>) -> Self {
+    pub fn new(vm_slots: &'a EVMStorage) -> Self {
         Self { vm_slots }
     }
 }
 
 /// Mutate the input to a value in the VM state
-pub fn mutate_with_vm_slot<S: State + HasRand>(vm_slots: &HashMap<EVMU256, EVMU256>, state: &mut S) -> EVMU256 {
+pub fn mutate_with_vm_slot<S: State + HasRand>(vm_slots: &EVMStorage, state: &mut S) -> EVMU256 {
     // sample a key from the vm_state.state
     let idx = state.rand_mut().below(vm_slots.len() as u64) as usize;
     let key = vm_slots.keys().nth(idx).unwrap();
@@ -437,10 +490,145 @@ where
     }
 }
 
+/// [`InterestingValueMutator`] is a mutator that mutates the input to a value
+/// in the [`InterestingValuesMetadata`], or right next to it
+///
+/// Values compared against or read during the campaign are likely to be
+/// thresholds, so hitting them or their neighbors helps pass the checks.
+#[derive(Default)]
+pub struct InterestingValueMutator;
+
+impl Named for InterestingValueMutator {
+    fn name(&self) -> &str {
+        "InterestingValueMutator"
+    }
+}
+
+impl InterestingValueMutator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Sample a value from the [`InterestingValuesMetadata`] of the state, which
+/// is off by one with a small probability
+pub fn sample_interesting_value<S: HasRand + HasMetadata>(state: &mut S) -> Option<EVMU256> {
+    let len = match state.metadata_map().get::<InterestingValuesMetadata>() {
+        Some(meta) if !meta.values.is_empty() => meta.values.len(),
+        _ => return None,
+    };
+    let idx = state.rand_mut().below(len as u64) as usize;
+    let value = state.metadata_map().get::<InterestingValuesMetadata>().unwrap().values[idx];
+    Some(match state.rand_mut().below(4) {
+        0 => value.wrapping_add(EVMU256::from(1)),
+        1 => value.wrapping_sub(EVMU256::from(1)),
+        _ => value,
+    })
+}
+
+impl<I, S> Mutator<I, S> for InterestingValueMutator
+where
+    S: State + HasRand + HasMetadata,
+    I: Input + HasBytesVec,
+{
+    /// Mutate the input to an interesting value
+    /// This always entirely overwrites the input (unless it skips mutation)
+    fn mutate(&mut self, state: &mut S, input: &mut I, _stage_idx: i32) -> Result<MutationResult, Error> {
+        // if full_overwrite_performed is true, we skip mutation
+        if let Some(metadata) = state.metadata_map().get::<MutatorMetadata>() {
+            if metadata.full_overwrite_performed {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let input_len = input.bytes().len();
+        if input_len == 0 || input_len > 32 {
+            return Ok(MutationResult::Skipped);
+        }
+        let new_val = match sample_interesting_value(state) {
+            Some(v) => v,
+            None => return Ok(MutationResult::Skipped),
+        };
+
+        let data: [u8; 32] = new_val.to_be_bytes();
+        input.bytes_mut().copy_from_slice(&data[(32 - input_len)..]);
+
+        // prevent fully overwriting the input on this mutation cycle again
+        if let Some(metadata) = state.metadata_map_mut().get_mut::<MutatorMetadata>() {
+            metadata.set_full_overwrite_performed(true);
+        } else {
+            let mut metadata = MutatorMetadata::new();
+            metadata.set_full_overwrite_performed(true);
+            state.metadata_map_mut().insert(metadata);
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+/// [`DictionaryMutator`] is a mutator that mutates the input to a value in the
+/// dictionary of the contract targeted by the input, see
+/// [`crate::evm::dictionary::ContractDictionary`]
+///
+/// Unlike [`ConstantHintedMutator`], which samples the constants of all the
+/// contracts, the values are the addresses, selectors and thresholds the
+/// targeted contract refers to.
+#[derive(Default)]
+pub struct DictionaryMutator;
+
+impl Named for DictionaryMutator {
+    fn name(&self) -> &str {
+        "DictionaryMutator"
+    }
+}
+
+impl DictionaryMutator {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<I, S> Mutator<I, S> for DictionaryMutator
+where
+    S: State + HasRand + HasMetadata,
+    I: Input + HasBytesVec,
+{
+    /// Mutate the input to a value in the dictionary
+    /// This always entirely overwrites the input (unless it skips mutation)
+    fn mutate(&mut self, state: &mut S, input: &mut I, _stage_idx: i32) -> Result<MutationResult, Error> {
+        // if full_overwrite_performed is true, we skip mutation
+        if let Some(metadata) = state.metadata_map().get::<MutatorMetadata>() {
+            if metadata.full_overwrite_performed {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let input_len = input.bytes().len();
+        if input_len == 0 || input_len > 32 {
+            return Ok(MutationResult::Skipped);
+        }
+        let new_val = match sample_dictionary_value(state, input_len) {
+            Some(v) => v,
+            None => return Ok(MutationResult::Skipped),
+        };
+        input.bytes_mut().copy_from_slice(&new_val);
+
+        // prevent fully overwriting the input on this mutation cycle again
+        if let Some(metadata) = state.metadata_map_mut().get_mut::<MutatorMetadata>() {
+            metadata.set_full_overwrite_performed(true);
+        } else {
+            let mut metadata = MutatorMetadata::new();
+            metadata.set_full_overwrite_performed(true);
+            state.metadata_map_mut().insert(metadata);
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
 /// Mutator that mutates the `CONSTANT SIZE` input bytes (e.g., uint256) in
 /// various ways provided by [`libafl::mutators`]. It also uses the
-/// [`ConstantHintedMutator`] and [`VMStateHintedMutator`]
-pub fn byte_mutator<I, S>(state: &mut S, input: &mut I, vm_slots: Option<HashMap<EVMU256, EVMU256>>) -> MutationResult
+/// [`ConstantHintedMutator`], [`InterestingValueMutator`],
+/// [`DictionaryMutator`] and [`VMStateHintedMutator`]
+pub fn byte_mutator<I, S>(state: &mut S, input: &mut I, vm_slots: Option<EVMStorage>) -> MutationResult
 where
     S: State + HasRand + HasMetadata,
     I: HasBytesVec + Input,
@@ -451,6 +639,8 @@ where
         WordInterestingMutator::new(),
         DwordInterestingMutator::new(),
         ConstantHintedMutator::new(),
+        InterestingValueMutator::new(),
+        DictionaryMutator::new(),
         GaussianNoiseMutator::new(),
         IncDecValue::new(),
     );
@@ -482,12 +672,9 @@ where
 
 /// Mutator that mutates the `VARIABLE SIZE` input bytes (e.g., string) in
 /// various ways provided by [`libafl::mutators`]. It also uses the
-/// [`ConstantHintedMutator`] and [`VMStateHintedMutator`]
-pub fn byte_mutator_with_expansion<I, S>(
-    state: &mut S,
-    input: &mut I,
-    vm_slots: Option<HashMap<EVMU256, EVMU256>>,
-) -> MutationResult
+/// [`ConstantHintedMutator`], [`InterestingValueMutator`],
+/// [`DictionaryMutator`] and [`VMStateHintedMutator`]
+pub fn byte_mutator_with_expansion<I, S>(state: &mut S, input: &mut I, vm_slots: Option<EVMStorage>) -> MutationResult
 where
     S: State + HasRand + HasMaxSize + HasMetadata,
     I: HasBytesVec + Input,
@@ -501,6 +688,8 @@ where
         BytesInsertMutator::new(),
         BytesRandInsertMutator::new(),
         ConstantHintedMutator::new(),
+        InterestingValueMutator::new(),
+        DictionaryMutator::new(),
         GaussianNoiseMutator::new(),
         IncDecValue::new(),
     );
@@ -528,3 +717,27 @@ where
         .set_full_overwrite_performed(false);
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interesting_values() {
+        let mut meta = InterestingValuesMetadata::new();
+        meta.add_value(EVMU256::from(1));
+        meta.add_value(EVMU256::MAX);
+        assert!(meta.values.is_empty());
+
+        for value in 2..INTERESTING_VALUES_MAX + 2 {
+            meta.add_value(EVMU256::from(value));
+        }
+        meta.add_value(EVMU256::from(2));
+        assert_eq!(meta.values.len(), INTERESTING_VALUES_MAX);
+        // the oldest value is evicted once full
+        meta.add_value(EVMU256::from(INTERESTING_VALUES_MAX + 2));
+        assert_eq!(meta.values.len(), INTERESTING_VALUES_MAX);
+        assert_eq!(meta.values[0], EVMU256::from(INTERESTING_VALUES_MAX + 2));
+        assert!(!meta.known.contains(&EVMU256::from(2)));
+    }
+}
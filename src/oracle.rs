@@ -75,6 +75,14 @@ where
         }
     }
 
+    /// Conduct a batch of static calls on the state before the execution
+    pub(crate) fn call_pre_batch(&mut self, data: &[(Addr, By)]) -> Vec<Out> {
+        self.executor
+            .deref()
+            .borrow_mut()
+            .fast_static_call(data, self.pre_state, self.fuzz_state)
+    }
+
     /// Conduct a batch of static calls on the state after the execution
     pub(crate) fn call_post_batch(&mut self, data: &[(Addr, By)]) -> Vec<Out> {
         self.executor
@@ -127,6 +135,12 @@ where
     /// Oracle function, called everytime after non-reverted execution
     /// Returns Some(bug_idx) if the oracle is violated
     fn oracle(&self, ctx: &mut OracleCtx<VS, Addr, Code, By, Loc, SlotTy, Out, I, S, CI, E>, stage: u64) -> Vec<u64>;
+
+    /// Whether the oracle is also called after reverted executions, e.g., to
+    /// inspect the revert data
+    fn on_revert(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
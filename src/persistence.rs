@@ -0,0 +1,197 @@
+//! Persistence of fuzzing campaigns
+//!
+//! The fuzz state, which holds the input corpus, the infant state corpus,
+//! the metadata (e.g., uncovered branches, coverage maps) and the random
+//! number generator, is saved to the work dir periodically along with the
+//! corpus minimizer map of the fuzzer and the [`CampaignGlobals`]. A stopped
+//! campaign is continued from the last save with `--resume`. The bugs already
+//! reported by the oracles are part of the metadata
+//! ([`crate::oracle::BugMetadata`]), and the middlewares learning across
+//! executions keep what they learnt in the metadata too (e.g., the tainted
+//! JUMPIs of the SHA3 bypass).
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use libafl::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[cfg(feature = "print_txn_corpus")]
+use crate::fuzzer::DUMP_FILE_COUNT;
+use crate::{fuzzer::ORACLE_OUTPUT, r#const::CAMPAIGN_FILE};
+
+/// Map from hash of the coverage of a testcase to the (testcase idx, fav
+/// factor), see [`crate::fuzzer::ItyFuzzer`]
+pub type MinimizerMap = HashMap<u64, (usize, f64)>;
+
+/// Globals of the fuzzer not held by the fuzz state
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CampaignGlobals {
+    /// Outputs of the oracles not reported yet
+    pub oracle_output: Vec<serde_json::Value>,
+    /// Number of corpus files dumped, so that they are not overwritten
+    pub dump_file_count: usize,
+}
+
+impl CampaignGlobals {
+    pub fn capture() -> Self {
+        Self {
+            oracle_output: unsafe { ORACLE_OUTPUT.clone() },
+            #[cfg(feature = "print_txn_corpus")]
+            dump_file_count: unsafe { DUMP_FILE_COUNT },
+            #[cfg(not(feature = "print_txn_corpus"))]
+            dump_file_count: 0,
+        }
+    }
+
+    pub fn restore(self) {
+        unsafe {
+            ORACLE_OUTPUT = self.oracle_output;
+            #[cfg(feature = "print_txn_corpus")]
+            {
+                DUMP_FILE_COUNT = self.dump_file_count;
+            }
+        }
+    }
+}
+
+/// Save the campaign to the work dir, replacing the previous save only once
+/// this one is complete
+pub fn save_campaign<S: Serialize>(work_dir: &str, state: &S, minimizer_map: &MinimizerMap) -> Result<(), Error> {
+    write_campaign(work_dir, state, minimizer_map, &CampaignGlobals::capture())
+}
+
+/// Load the campaign last saved to the work dir, restoring the globals
+pub fn load_campaign<S: DeserializeOwned>(work_dir: &str) -> Result<(S, MinimizerMap), Error> {
+    let (state, minimizer_map, globals) = read_campaign(work_dir)?;
+    globals.restore();
+    Ok((state, minimizer_map))
+}
+
+fn write_campaign<S: Serialize>(
+    work_dir: &str,
+    state: &S,
+    minimizer_map: &MinimizerMap,
+    globals: &CampaignGlobals,
+) -> Result<(), Error> {
+    fs::create_dir_all(work_dir)?;
+    let path = Path::new(work_dir).join(CAMPAIGN_FILE);
+    let tmp_path = path.with_extension("tmp");
+    let writer = BufWriter::new(File::create(&tmp_path)?);
+    serde_cbor::to_writer(writer, &(state, minimizer_map, globals))
+        .map_err(|e| Error::serialize(format!("failed to save campaign: {}", e)))?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+fn read_campaign<S: DeserializeOwned>(work_dir: &str) -> Result<(S, MinimizerMap, CampaignGlobals), Error> {
+    let path = Path::new(work_dir).join(CAMPAIGN_FILE);
+    let reader = BufReader::new(File::open(&path)?);
+    serde_cbor::from_reader(reader).map_err(|e| Error::serialize(format!("failed to load campaign: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load_campaign() {
+        let work_dir = std::env::temp_dir().join(format!("ityfuzz_campaign_{}", std::process::id()));
+        let work_dir = work_dir.to_str().unwrap();
+        let state: HashMap<[u8; 4], Vec<u64>> = HashMap::from([([1, 2, 3, 4], vec![5, 6])]);
+        let minimizer_map = MinimizerMap::from([(7, (8, 0.5))]);
+
+        write_campaign(work_dir, &state, &minimizer_map, &CampaignGlobals::default()).unwrap();
+        let (loaded, loaded_map, _): (HashMap<[u8; 4], Vec<u64>>, _, _) = read_campaign(work_dir).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(loaded_map, minimizer_map);
+        fs::remove_dir_all(work_dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_resume_fuzz_state() {
+        use std::{cell::RefCell, rc::Rc};
+
+        use libafl::{
+            corpus::{Corpus, Testcase},
+            prelude::{HasCorpus, HasMetadata},
+            state::HasExecutions,
+        };
+        use serde_json::json;
+
+        use crate::{
+            evm::{
+                input::{EVMInput, EVMInputTy},
+                middlewares::sha3_bypass::Sha3TaintedJumpiMetadata,
+                mutator::AccessPattern,
+                types::{EVMAddress, EVMFuzzState},
+            },
+            oracle::BugMetadata,
+            state::FuzzState,
+            state_input::StagedVMState,
+        };
+
+        let work_dir = std::env::temp_dir().join(format!("ityfuzz_resume_{}", std::process::id()));
+        let work_dir = work_dir.to_str().unwrap();
+        let mut state: EVMFuzzState = FuzzState::new(0);
+        let contract = EVMAddress::from_slice(&[1; 20]);
+        let input = EVMInput {
+            caller: EVMAddress::from_slice(&[2; 20]),
+            contract,
+            data: None,
+            sstate: StagedVMState::new_uninitialized(),
+            sstate_idx: 0,
+            txn_value: None,
+            step: false,
+            env: Default::default(),
+            access_pattern: Rc::new(RefCell::new(AccessPattern::new())),
+            liquidation_percent: 0,
+            liquidation_path: 0,
+            input_type: EVMInputTy::ArbitraryCallBoundedAddr,
+            direct_data: vec![0xde, 0xad].into(),
+            randomness: vec![0],
+            repeat: 1,
+            swap_data: HashMap::new(),
+        };
+        state.corpus_mut().add(Testcase::new(input)).unwrap();
+        *state.executions_mut() = 42;
+        state.add_metadata(BugMetadata {
+            known_bugs: [7].into(),
+            ..Default::default()
+        });
+        state.add_metadata(Sha3TaintedJumpiMetadata {
+            jumpis: [(contract, 10)].into(),
+        });
+        let globals = CampaignGlobals {
+            oracle_output: vec![json!({"bug_idx": 7})],
+            dump_file_count: 3,
+        };
+        write_campaign(work_dir, &state, &MinimizerMap::new(), &globals).unwrap();
+
+        let (resumed, _, resumed_globals): (EVMFuzzState, _, _) = read_campaign(work_dir).unwrap();
+        assert_eq!(resumed_globals, globals);
+        assert_eq!(*resumed.executions(), 42);
+        assert_eq!(resumed.corpus().count(), 1);
+        let testcase = resumed.corpus().get(resumed.corpus().first().unwrap()).unwrap();
+        let input = testcase.borrow().input().clone().unwrap();
+        assert_eq!(input.contract, contract);
+        assert_eq!(input.direct_data.to_vec(), vec![0xde, 0xad]);
+        assert!(resumed
+            .metadata_map()
+            .get::<BugMetadata>()
+            .unwrap()
+            .known_bugs
+            .contains(&7));
+        assert!(resumed
+            .metadata_map()
+            .get::<Sha3TaintedJumpiMetadata>()
+            .unwrap()
+            .jumpis
+            .contains(&(contract, 10)));
+        fs::remove_dir_all(work_dir).unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+//! Pipelines of campaigns run in sequence
+//!
+//! A config file with a `stages` list runs each stage as a separate campaign,
+//! in order, e.g., a coverage campaign whose corpus seeds a focused campaign,
+//! whose bugs are then replayed for validation:
+//!
+//! ```toml
+//! work_dir = "audit"
+//!
+//! [[stages]]
+//! name = "scan"
+//! target = "./build/*"
+//! timeout = 3600
+//!
+//! [[stages]]
+//! name = "focus"
+//! target = "./build/*"
+//! only_fuzz = "0x..."
+//! load_corpus = "${scan}/corpus"
+//! run_forever = true
+//! timeout = 7200
+//!
+//! [[stages]]
+//! name = "validate"
+//! fuzzer = "replay"
+//! target = "./build/*"
+//! files = "${focus}/vulnerabilities/*_replayable"
+//! ```
+//!
+//! Each stage is a config as accepted by `--config` (see
+//! [`crate::config_file`]), plus:
+//! - `name`: referenced by the later stages as `${name}`, which expands to the
+//!   work dir of that stage
+//! - `timeout`: seconds after which the stage is stopped and the pipeline moves
+//!   on (no timeout by default)
+//! - `continue_on_error`: keep going when the stage fails (false by default)
+//!
+//! A stage without `work_dir` uses `<work_dir of the pipeline>/<name>`.
+
+use std::{
+    collections::HashMap,
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use tracing::{error, info, warn};
+
+use crate::config_file::config_to_args;
+
+/// Key holding the stages of a pipeline
+const STAGES_KEY: &str = "stages";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    pub work_dir: String,
+    /// Command line arguments, including the binary name and the subcommand
+    pub args: Vec<String>,
+    pub timeout: Option<Duration>,
+    pub continue_on_error: bool,
+}
+
+/// Whether the parsed config file defines a pipeline
+pub fn is_pipeline(config: &Value) -> bool {
+    config.get(STAGES_KEY).is_some()
+}
+
+/// Parses the stages of the pipeline, expanding the references to the work
+/// dirs of the previous stages
+pub fn parse_stages(config: &Value) -> Result<Vec<Stage>, String> {
+    let base_work_dir = config.get("work_dir").and_then(|v| v.as_str()).unwrap_or("work_dir");
+    let stages = config
+        .get(STAGES_KEY)
+        .and_then(|v| v.as_array())
+        .ok_or("`stages` must be a list of stages")?;
+
+    let mut work_dirs: HashMap<String, String> = HashMap::new();
+    let mut result = vec![];
+    for (idx, stage) in stages.iter().enumerate() {
+        let mut table = stage
+            .as_object()
+            .ok_or(format!("stage {} must be a table of options", idx))?
+            .clone();
+        let name = match table.remove("name") {
+            Some(Value::String(name)) => name,
+            Some(v) => return Err(format!("stage {}: `name` must be a string, got {}", idx, v)),
+            None => format!("stage{}", idx),
+        };
+        if work_dirs.contains_key(&name) {
+            return Err(format!("duplicate stage name `{}`", name));
+        }
+        let timeout = match table.remove("timeout") {
+            Some(v) => Some(Duration::from_secs(
+                v.as_u64()
+                    .ok_or(format!("stage {}: `timeout` must be a number of seconds", name))?,
+            )),
+            None => None,
+        };
+        let continue_on_error = match table.remove("continue_on_error") {
+            Some(v) => v
+                .as_bool()
+                .ok_or(format!("stage {}: `continue_on_error` must be a boolean", name))?,
+            None => false,
+        };
+
+        let work_dir = match table.get("work_dir") {
+            Some(Value::String(dir)) => dir.clone(),
+            _ => format!("{}/{}", base_work_dir, name),
+        };
+        table.insert("work_dir".to_string(), Value::String(work_dir.clone()));
+        for value in table.values_mut() {
+            expand_refs(value, &work_dirs);
+        }
+
+        let args = config_to_args(&Value::Object(table)).map_err(|e| format!("stage {}: {}", name, e))?;
+        work_dirs.insert(name.clone(), work_dir.clone());
+        result.push(Stage {
+            name,
+            work_dir,
+            args,
+            timeout,
+            continue_on_error,
+        });
+    }
+    Ok(result)
+}
+
+/// Replaces `${name}` in the strings of `value` with the work dir of stage
+/// `name`
+fn expand_refs(value: &mut Value, work_dirs: &HashMap<String, String>) {
+    match value {
+        Value::String(s) => {
+            for (name, dir) in work_dirs {
+                *s = s.replace(&format!("${{{}}}", name), dir);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| expand_refs(item, work_dirs)),
+        _ => {}
+    }
+}
+
+/// Runs the stages in sequence, each in its own process. Returns whether all
+/// stages succeeded.
+pub fn run_pipeline(stages: &[Stage]) -> bool {
+    let exe = std::env::current_exe().expect("failed to locate the ityfuzz binary");
+    let mut all_succeeded = true;
+    for stage in stages {
+        info!("pipeline stage `{}`: {}", stage.name, stage.args[1..].join(" "));
+        let succeeded = match run_stage(Command::new(&exe).args(&stage.args[1..]), stage.timeout) {
+            Ok(succeeded) => succeeded,
+            Err(e) => {
+                error!("pipeline stage `{}` could not run: {}", stage.name, e);
+                false
+            }
+        };
+        if !succeeded {
+            all_succeeded = false;
+            if !stage.continue_on_error {
+                error!("pipeline stage `{}` failed, stopping", stage.name);
+                return false;
+            }
+            warn!("pipeline stage `{}` failed, continuing", stage.name);
+        }
+    }
+    all_succeeded
+}
+
+/// Runs the stage, stopping it after `timeout`. A stage stopped by its timeout
+/// succeeded, as campaigns run until stopped.
+fn run_stage(cmd: &mut Command, timeout: Option<Duration>) -> Result<bool, String> {
+    let mut child = cmd.spawn().map_err(|e| e.to_string())?;
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            return Ok(status.success());
+        }
+        if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(true);
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stages() {
+        let config: Value = toml::from_str(
+            r#"
+            work_dir = "audit"
+
+            [[stages]]
+            name = "scan"
+            target = "./build/*"
+            timeout = 60
+
+            [[stages]]
+            name = "validate"
+            fuzzer = "replay"
+            files = "${scan}/vulnerabilities/*_replayable"
+            continue_on_error = true
+            "#,
+        )
+        .unwrap();
+        assert!(is_pipeline(&config));
+        let stages = parse_stages(&config).unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].work_dir, "audit/scan");
+        assert_eq!(stages[0].timeout, Some(Duration::from_secs(60)));
+        assert!(!stages[0].args.join(" ").contains("--timeout"));
+        assert_eq!(stages[1].args[1], "replay");
+        assert!(stages[1]
+            .args
+            .join(" ")
+            .contains("--files audit/scan/vulnerabilities/*_replayable"));
+        assert!(stages[1].continue_on_error);
+    }
+
+    #[test]
+    fn test_invalid_stages() {
+        let config: Value = toml::from_str("[[stages]]\nname = \"a\"\n[[stages]]\nname = \"a\"\n").unwrap();
+        assert!(parse_stages(&config).is_err());
+        let config: Value = toml::from_str("stages = 1").unwrap();
+        assert!(parse_stages(&config).is_err());
+    }
+
+    #[test]
+    fn test_run_stage_timeout() {
+        assert_eq!(run_stage(&mut Command::new("true"), None), Ok(true));
+        assert_eq!(run_stage(&mut Command::new("false"), None), Ok(false));
+        let mut sleep = Command::new("sleep");
+        sleep.arg("10");
+        assert_eq!(run_stage(&mut sleep, Some(Duration::from_millis(100))), Ok(true));
+    }
+}
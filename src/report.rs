@@ -0,0 +1,115 @@
+//! Summarize the results of a campaign from its work dir, without rerunning
+//! the fuzzer
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use clap::Parser;
+use serde_json::Value;
+use tracing::error;
+
+/// CLI for reporting the bugs found by a campaign
+#[derive(Parser, Debug)]
+pub struct ReportArgs {
+    /// Path of work dir of the campaign
+    #[arg(long, short, default_value = "work_dir")]
+    work_dir: String,
+
+    /// Print the bugs as JSON lines instead of a summary
+    #[arg(long, default_value = "false")]
+    json: bool,
+}
+
+/// Bugs found by a campaign
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Bug type -> bug infos
+    pub bugs: BTreeMap<String, Vec<String>>,
+    /// Replayable files of the bugs
+    pub replayable: Vec<String>,
+}
+
+impl Report {
+    /// Parses the content of `vuln_info.jsonl`
+    pub fn from_vuln_info(content: &str) -> Self {
+        let mut report = Self::default();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let v: Value = match serde_json::from_str(line) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let bug_type = v["bug_type"].as_str().unwrap_or("Unknown").to_string();
+            let bug_info = v["bug_info"].as_str().unwrap_or("").to_string();
+            report.bugs.entry(bug_type).or_default().push(bug_info);
+        }
+        report
+    }
+
+    pub fn total(&self) -> usize {
+        self.bugs.values().map(|v| v.len()).sum()
+    }
+}
+
+pub fn report_main(args: ReportArgs) {
+    let vuln_file = format!("{}/vuln_info.jsonl", args.work_dir);
+    if !Path::new(&vuln_file).exists() {
+        println!("No bugs found in {}", args.work_dir);
+        return;
+    }
+    let content = match fs::read_to_string(&vuln_file) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("failed to read {}: {}", vuln_file, e);
+            std::process::exit(1);
+        }
+    };
+
+    if args.json {
+        content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .for_each(|l| println!("{}", l));
+        return;
+    }
+
+    let mut report = Report::from_vuln_info(&content);
+    if let Ok(entries) = fs::read_dir(format!("{}/vulnerabilities", args.work_dir)) {
+        report.replayable = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().display().to_string())
+            .filter(|p| p.ends_with("_replayable"))
+            .collect();
+        report.replayable.sort();
+    }
+
+    println!("Found {} bug(s) in {}", report.total(), args.work_dir);
+    for (bug_type, infos) in &report.bugs {
+        println!("[{}] x{}", bug_type, infos.len());
+        for info in infos {
+            println!("    {}", info);
+        }
+    }
+    if !report.replayable.is_empty() {
+        println!("Replayable files (ityfuzz replay --files <file> ...):");
+        for file in &report.replayable {
+            println!("    {}", file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_from_vuln_info() {
+        let content = r#"{"bug_type":"Erc20","bug_info":"drained","bug_idx":1}
+{"bug_type":"Erc20","bug_info":"drained again","bug_idx":2}
+
+{"bug_type":"Reentrancy","bug_info":"reentered","bug_idx":3}
+not json"#;
+        let report = Report::from_vuln_info(content);
+        assert_eq!(report.total(), 3);
+        assert_eq!(report.bugs["Erc20"].len(), 2);
+        assert_eq!(report.bugs["Reentrancy"], vec!["reentered".to_string()]);
+    }
+}
@@ -236,10 +236,11 @@ where
             if corpus_size > DROP_THRESHOLD {
                 // get top 100 entries sorted by votes (descending)
                 let mut sorted: Vec<_> = data.votes_and_visits.iter().collect();
-                sorted.sort_by(|(_idx_1, (votes1, visits1)), (_idx_2, (votes2, visits2))| {
+                // ties are broken by index, the order of the map being random
+                sorted.sort_by(|(idx_1, (votes1, visits1)), (idx_2, (votes2, visits2))| {
                     let score_1 = (*votes1 as f64) / (*visits1 as f64);
                     let score_2 = (*votes2 as f64) / (*visits2 as f64);
-                    score_1.partial_cmp(&score_2).unwrap()
+                    score_1.partial_cmp(&score_2).unwrap().then(idx_1.cmp(idx_2))
                 });
 
                 for i in sorted.iter().take(PRUNE_AMT) {
@@ -391,7 +392,7 @@ where
             data.sorted_votes.sort_by(|x, y| {
                 let (votes_x, _) = data.votes_and_visits.get(x).unwrap();
                 let (votes_y, _) = data.votes_and_visits.get(y).unwrap();
-                votes_y.cmp(votes_x)
+                votes_y.cmp(votes_x).then(x.cmp(y))
             });
         }
     }
@@ -0,0 +1,142 @@
+//! Adaptive control of the maximum transaction sequence length
+//!
+//! Deep protocols need long transaction sequences to reach interesting
+//! states, while simple targets waste executions on them. Instead of a fixed
+//! cap, we track the marginal gain (new infant states or corpus entries) of
+//! executions at each sequence depth and move the cap accordingly.
+
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::r#const::{
+    SEQ_LEN_ADAPT_INTERVAL,
+    SEQ_LEN_GROW_RATE,
+    SEQ_LEN_INITIAL,
+    SEQ_LEN_MAX,
+    SEQ_LEN_MIN,
+    SEQ_LEN_SHRINK_RATE,
+};
+
+/// Metadata stored in the fuzz state tracking the gains per sequence depth
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SequenceLengthMetadata {
+    /// Maximum depth of a VMState that can be added to the infant state corpus
+    pub max_len: usize,
+    /// (executions, gains) of executions at each depth, decayed on each
+    /// adaptation
+    pub stats: Vec<(usize, usize)>,
+    /// Executions since last adaptation
+    execs_since_adapt: usize,
+}
+
+impl_serdeany!(SequenceLengthMetadata);
+
+impl Default for SequenceLengthMetadata {
+    fn default() -> Self {
+        Self::new(SEQ_LEN_INITIAL)
+    }
+}
+
+impl SequenceLengthMetadata {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len: max_len.clamp(SEQ_LEN_MIN, SEQ_LEN_MAX),
+            stats: vec![],
+            execs_since_adapt: 0,
+        }
+    }
+
+    /// Whether a VMState resulting from an execution at `depth` can be
+    /// added to the infant state corpus
+    pub fn allows(&self, depth: usize) -> bool {
+        depth <= self.max_len
+    }
+
+    /// Record the outcome of an execution at `depth`, i.e., the execution of
+    /// the `depth`-th transaction of a sequence
+    pub fn record(&mut self, depth: usize, gain: bool) {
+        if self.stats.len() <= depth {
+            self.stats.resize(depth + 1, (0, 0));
+        }
+        self.stats[depth].0 += 1;
+        if gain {
+            self.stats[depth].1 += 1;
+        }
+
+        self.execs_since_adapt += 1;
+        if self.execs_since_adapt >= SEQ_LEN_ADAPT_INTERVAL {
+            self.adapt();
+        }
+    }
+
+    fn gain_rate(&self, depth: usize) -> Option<f64> {
+        match self.stats.get(depth) {
+            Some((execs, gains)) if *execs > 0 => Some(*gains as f64 / *execs as f64),
+            _ => None,
+        }
+    }
+
+    /// Grow the cap if executions past it still yield gains, shrink it if
+    /// the deepest allowed depths no longer do
+    fn adapt(&mut self) {
+        self.execs_since_adapt = 0;
+
+        // executions on top of the deepest states reveal whether longer
+        // sequences would still pay off
+        let frontier = self.gain_rate(self.max_len + 1);
+        let deepest = self.gain_rate(self.max_len);
+
+        if frontier.is_some_and(|rate| rate >= SEQ_LEN_GROW_RATE) && self.max_len < SEQ_LEN_MAX {
+            self.max_len += 1;
+            debug!("sequence length cap grown to {}", self.max_len);
+        } else if frontier.unwrap_or(0.0) < SEQ_LEN_SHRINK_RATE &&
+            deepest.is_some_and(|rate| rate < SEQ_LEN_SHRINK_RATE) &&
+            self.max_len > SEQ_LEN_MIN
+        {
+            self.max_len -= 1;
+            debug!("sequence length cap shrunk to {}", self.max_len);
+        }
+
+        // decay so that the rates reflect recent campaign progress
+        for (execs, gains) in self.stats.iter_mut() {
+            *execs /= 2;
+            *gains /= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grow_on_frontier_gain() {
+        let mut meta = SequenceLengthMetadata::new(3);
+        for i in 0..SEQ_LEN_ADAPT_INTERVAL {
+            meta.record(4, i % 10 == 0);
+        }
+        assert_eq!(meta.max_len, 4);
+        assert!(meta.allows(4));
+        assert!(!meta.allows(5));
+    }
+
+    #[test]
+    fn test_shrink_without_gain() {
+        let mut meta = SequenceLengthMetadata::new(4);
+        for i in 0..SEQ_LEN_ADAPT_INTERVAL {
+            meta.record(3 + i % 3, false);
+        }
+        assert_eq!(meta.max_len, 3);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut meta = SequenceLengthMetadata::new(SEQ_LEN_MIN);
+        for _ in 0..SEQ_LEN_ADAPT_INTERVAL * 4 {
+            meta.record(SEQ_LEN_MIN, false);
+        }
+        assert_eq!(meta.max_len, SEQ_LEN_MIN);
+        assert_eq!(SequenceLengthMetadata::new(usize::MAX).max_len, SEQ_LEN_MAX);
+    }
+}
@@ -266,8 +266,12 @@ where
             seed = current_nanos();
         }
         debug!("Seed: {}", seed);
+        let mut rand_generator = RomuDuoJrRand::with_seed(seed);
+        // the infant state scheduler draws from its own generator, derived from the
+        // seed for the campaign to be deterministic
+        let infant_seed = rand_generator.next();
         Self {
-            infant_states_state: InfantStateState::new(),
+            infant_states_state: InfantStateState::with_seed(infant_seed),
             #[cfg(not(feature = "evaluation"))]
             txn_corpus: InMemoryCorpus::new(),
             #[cfg(feature = "evaluation")]
@@ -280,7 +284,7 @@ where
             execution_result: ExecutionResult::empty_result(),
             callers_pool: Vec::new(),
             addresses_pool: Vec::new(),
-            rand_generator: RomuDuoJrRand::with_seed(seed),
+            rand_generator,
             max_size: MAX_INPUT_SIZE,
             hash_to_address: Default::default(),
             last_report_time: None,
@@ -452,6 +456,15 @@ where
             rand_generator: Default::default(),
         }
     }
+
+    /// Create a new [`InfantStateState`] whose random generator is seeded with
+    /// `seed`
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rand_generator: StdRand::with_seed(seed),
+            ..Self::new()
+        }
+    }
 }
 
 impl<VI, VS, Loc, Addr, Out, CI> HasHashToAddress for FuzzState<VI, VS, Loc, Addr, Out, CI>
@@ -0,0 +1,132 @@
+//! Conditions to stop a campaign other than finding the first objective or
+//! reaching the maximum executions: a coverage target every contract has to
+//! reach (`--stop-on-coverage 90%`), a number of objectives
+//! (`--stop-after-objectives 3`) and a timeout (`--timeout 2h`)
+
+use std::time::Duration;
+
+use crate::metrics::ContractCoverage;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StopConditions {
+    /// Instruction coverage (in percent) every contract has to reach
+    pub coverage: Option<f64>,
+    /// Number of objectives to find
+    pub objectives: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+pub static mut STOP_CONDITIONS: StopConditions = StopConditions {
+    coverage: None,
+    objectives: None,
+    timeout: None,
+};
+
+impl StopConditions {
+    /// Reason to stop the campaign running for `elapsed` with the coverage of
+    /// the contracts, None if no condition holds
+    pub fn reached(&self, elapsed: Duration, coverage: &[ContractCoverage]) -> Option<String> {
+        if let Some(timeout) = self.timeout &&
+            elapsed >= timeout
+        {
+            return Some(format!("Reached the timeout ({}s)", timeout.as_secs()));
+        }
+        let target = self.coverage?;
+        let covered = !coverage.is_empty() &&
+            coverage.iter().all(|contract| {
+                contract.total_instructions == 0 ||
+                    contract.instructions as f64 * 100.0 >= target * contract.total_instructions as f64
+            });
+        covered.then(|| format!("All contracts reached {}% instruction coverage", target))
+    }
+
+    /// Whether enough objectives are found to stop the campaign
+    pub fn objectives_reached(&self, objectives: usize) -> bool {
+        self.objectives.map_or(false, |target| objectives >= target)
+    }
+}
+
+/// Parse a coverage target, e.g., `90%` or `90`
+pub fn parse_coverage_target(s: &str) -> Result<Option<f64>, String> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    let target = s
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .map_err(|_| format!("invalid coverage target {}", s))?;
+    if !(0.0..=100.0).contains(&target) {
+        return Err(format!("invalid coverage target {}, expected a percentage", s));
+    }
+    Ok(Some(target))
+}
+
+/// Parse a duration, e.g., `2h`, `1h30m`, `45s` or `3600` (seconds)
+pub fn parse_timeout(s: &str) -> Result<Option<Duration>, String> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Some(Duration::from_secs(secs)));
+    }
+    let (mut secs, mut number) = (0u64, String::new());
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(format!("invalid timeout {}, unknown unit {}", s, c)),
+        };
+        let value = number
+            .parse::<u64>()
+            .map_err(|_| format!("invalid timeout {}, expected e.g. 2h or 1h30m", s))?;
+        secs += value * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(format!("invalid timeout {}, missing the unit of {}", s, number));
+    }
+    Ok(Some(Duration::from_secs(secs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_conditions() {
+        assert_eq!(parse_coverage_target("90%"), Ok(Some(90.0)));
+        assert_eq!(parse_coverage_target(""), Ok(None));
+        assert!(parse_coverage_target("120%").is_err());
+        assert_eq!(parse_timeout("1h30m"), Ok(Some(Duration::from_secs(5400))));
+        assert_eq!(parse_timeout("600"), Ok(Some(Duration::from_secs(600))));
+        assert!(parse_timeout("2x").is_err());
+        assert!(parse_timeout("1h30").is_err());
+
+        let conditions = StopConditions {
+            coverage: Some(90.0),
+            objectives: Some(2),
+            timeout: Some(Duration::from_secs(60)),
+        };
+        let contract = |instructions| ContractCoverage {
+            name: "C".to_string(),
+            instructions,
+            total_instructions: 100,
+            ..Default::default()
+        };
+        assert!(conditions.reached(Duration::from_secs(60), &[]).is_some());
+        assert_eq!(conditions.reached(Duration::ZERO, &[]), None);
+        assert_eq!(conditions.reached(Duration::ZERO, &[contract(95), contract(80)]), None);
+        assert!(conditions
+            .reached(Duration::ZERO, &[contract(95), contract(90)])
+            .is_some());
+        assert!(!conditions.objectives_reached(1));
+        assert!(conditions.objectives_reached(2));
+    }
+}
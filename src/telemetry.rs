@@ -0,0 +1,156 @@
+//! Stream of the events of the campaign as newline-delimited JSON, for CI
+//! systems and external dashboards
+//!
+//! Each line is an object with the `event` name (`new_corpus`,
+//! `new_coverage`, `objective_found` or `stats`), the unix `time` in
+//! milliseconds and the fields of the event.
+
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{self, Write},
+    os::unix::net::UnixStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+        OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Map, Value};
+use tracing::warn;
+
+static TELEMETRY: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Set when the sink fails, e.g., the reader of the socket went away, the
+/// events are not emitted anymore
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Send the events to `target`: a unix socket `unix:<path>` or a file,
+/// appended to
+pub fn init(target: &str) -> io::Result<()> {
+    let sink: Box<dyn Write + Send> = match target.strip_prefix("unix:") {
+        Some(path) => Box::new(UnixStream::connect(path)?),
+        None => Box::new(OpenOptions::new().create(true).append(true).open(target)?),
+    };
+    let _ = TELEMETRY.set(Mutex::new(sink));
+    Ok(())
+}
+
+/// JSON line of the event, `data` being an object holding its fields
+pub fn event_line(event: &str, time: u128, data: Value) -> String {
+    let mut line = Map::new();
+    line.insert("event".to_string(), Value::from(event));
+    line.insert("time".to_string(), Value::from(time as u64));
+    match data {
+        Value::Object(fields) => line.extend(fields),
+        Value::Null => {}
+        data => {
+            line.insert("data".to_string(), data);
+        }
+    }
+    format!("{}\n", Value::Object(line))
+}
+
+/// Fields of the `stats` event out of the stats reported by the monitor, as
+/// numbers: the `clients`, `corpus`, `objectives` and `executions` counters,
+/// `exec_per_sec` and the `run_time` in seconds
+pub fn stats_fields(stats: &HashMap<String, String>) -> Value {
+    let counter = |key: &str| stats.get(key).and_then(|v| v.parse::<u64>().ok());
+    // e.g., 1h-2m-3s
+    let run_time = stats.get("run time").and_then(|v| {
+        v.split('-').try_fold(0, |secs, part| {
+            let (value, unit) = part.split_at(part.len().checked_sub(1)?);
+            let value = value.parse::<u64>().ok()?;
+            match unit {
+                "h" => Some(secs + value * 3600),
+                "m" => Some(secs + value * 60),
+                "s" => Some(secs + value),
+                _ => None,
+            }
+        })
+    });
+    json!({
+        "clients": counter("clients"),
+        "corpus": counter("corpus"),
+        "objectives": counter("objectives"),
+        "executions": counter("executions"),
+        "exec_per_sec": stats.get("exec/sec").and_then(|v| parse_pretty(v)),
+        "run_time": run_time,
+    })
+}
+
+/// Number pretty printed by the monitor, e.g., `1.500k`
+fn parse_pretty(value: &str) -> Option<f64> {
+    let (value, scale) = match value.char_indices().last()? {
+        (idx, 'k') => (&value[..idx], 1e3),
+        (idx, 'M') => (&value[..idx], 1e6),
+        (idx, 'G') => (&value[..idx], 1e9),
+        _ => (value, 1.0),
+    };
+    value.parse::<f64>().ok().map(|value| value * scale)
+}
+
+/// Emit the event, does nothing unless the telemetry is enabled
+pub fn emit(event: &str, data: Value) {
+    let Some(sink) = TELEMETRY.get() else {
+        return;
+    };
+    if DISABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    let line = event_line(event, time, data);
+    let mut sink = sink.lock().unwrap();
+    if let Err(e) = sink.write_all(line.as_bytes()).and_then(|_| sink.flush()) {
+        warn!("Failed to emit {} event, disabling the telemetry: {}", event, e);
+        DISABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_line() {
+        let line = event_line("new_corpus", 1000, json!({"corpus_size": 3}));
+        assert!(line.ends_with('\n'));
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap(),
+            json!({"event": "new_corpus", "time": 1000, "corpus_size": 3})
+        );
+        let line = event_line("stats", 1000, json!([1]));
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap(),
+            json!({"event": "stats", "time": 1000, "data": [1]})
+        );
+    }
+
+    #[test]
+    fn test_stats_fields() {
+        let stats = HashMap::from(
+            [
+                ("run time", "1h-2m-3s"),
+                ("clients", "1"),
+                ("corpus", "10"),
+                ("objectives", "0"),
+                ("executions", "5000"),
+                ("exec/sec", "1.5k"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        assert_eq!(
+            stats_fields(&stats),
+            json!({
+                "clients": 1,
+                "corpus": 10,
+                "objectives": 0,
+                "executions": 5000,
+                "exec_per_sec": 1500.0,
+                "run_time": 3723,
+            })
+        );
+    }
+}
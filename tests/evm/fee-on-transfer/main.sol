@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+import "../../../solidity_utils/lib.sol";
+import "../../../solidity_utils/mock_tokens.sol";
+
+// Vault crediting the amount transferred instead of the amount received
+contract main {
+    FeeOnTransferToken token;
+    address constant escrow = address(0xdead);
+    uint256 credited;
+
+    constructor() {
+        token = new FeeOnTransferToken(1e24, 100);
+    }
+
+    function deposit(uint256 amount) public {
+        require(amount <= token.balanceOf(address(this)));
+        token.transfer(escrow, amount);
+        credited += amount;
+    }
+
+    function check() public {
+        if (token.balanceOf(escrow) < credited) {
+            bug();
+        }
+    }
+}
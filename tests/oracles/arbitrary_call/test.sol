@@ -0,0 +1,11 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    // forwards any call on behalf of the contract
+    function execute(address target, bytes calldata data) external returns (bytes memory) {
+        (bool success, bytes memory result) = target.call(data);
+        require(success, "call failed");
+        return result;
+    }
+}
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    bool private paused = true;
+    uint256 private counter;
+
+    function unpause(uint256 key) external {
+        if (key == 42) {
+            paused = false;
+        }
+    }
+
+    function increment() external {
+        require(!paused, "paused");
+        counter += 1;
+    }
+
+    function echidna_counter_bounded() public view returns (bool) {
+        return counter < 3;
+    }
+}
@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+interface Vm {
+    function deal(address who, uint256 amount) external;
+}
+
+contract Bank {
+    mapping(address => uint256) public deposits;
+
+    function deposit() external payable {
+        deposits[msg.sender] += msg.value;
+    }
+
+    // the deposit is not cleared, it can be withdrawn again and again
+    function withdraw() external {
+        uint256 amount = deposits[msg.sender];
+        require(amount > 0, "no deposit");
+        payable(msg.sender).transfer(amount);
+    }
+}
+
+contract Setup {
+    Vm constant vm = Vm(address(uint160(uint256(keccak256("hevm cheat code")))));
+    Bank public bank;
+
+    function setUp() public {
+        bank = new Bank();
+        vm.deal(address(bank), 100 ether);
+    }
+}
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract Token {
+    mapping(address => uint256) public balanceOf;
+
+    constructor() {
+        balanceOf[msg.sender] = 1000 ether;
+    }
+
+    function transfer(address to, uint256 amount) external returns (bool) {
+        require(balanceOf[msg.sender] >= amount, "insufficient balance");
+        balanceOf[msg.sender] -= amount;
+        balanceOf[to] += amount;
+        return true;
+    }
+}
+
+contract main {
+    Token public immutable token;
+    uint256 public totalSupply;
+    mapping(address => uint256) public balanceOf;
+
+    constructor() {
+        token = new Token();
+        totalSupply = 1000 ether;
+        balanceOf[address(0xdead)] = 1000 ether;
+    }
+
+    function asset() external view returns (address) {
+        return address(token);
+    }
+
+    function totalAssets() public view returns (uint256) {
+        return token.balanceOf(address(this));
+    }
+
+    function convertToAssets(uint256 shares) public view returns (uint256) {
+        return (shares * totalAssets()) / totalSupply;
+    }
+
+    // the rewards are paid out of the assets of the depositors, without
+    // burning any share
+    function claimRewards() external {
+        token.transfer(msg.sender, (totalAssets() * 3) / 4);
+    }
+}
@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    uint256[] public stakers;
+    uint256 public checksum;
+
+    // anyone can grow the array
+    function push() external {
+        stakers.push(1);
+    }
+
+    // nested loops over the array, its gas grows quadratically with the number
+    // of pushes
+    function distribute() external {
+        uint256 sum;
+        for (uint256 i = 0; i < stakers.length; i++) {
+            for (uint256 j = 0; j < stakers.length; j++) {
+                sum += stakers[i] * stakers[j];
+            }
+        }
+        checksum = sum;
+    }
+}
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    uint256 public deposits;
+    uint256 public withdrawals;
+
+    function deposit(uint256 amount) external {
+        require(amount < 1e18, "too much");
+        deposits += amount;
+    }
+
+    // withdrawals are not checked against deposits
+    function withdraw(uint256 amount) external {
+        require(amount < 1e18, "too much");
+        withdrawals += amount;
+    }
+
+    function invariant_solvent() public view returns (bool) {
+        return withdrawals <= deposits;
+    }
+}
@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    mapping(address => uint256) public balances;
+
+    // the balance of the sender underflows when sending more than it has
+    function transfer(address to, uint256 amount) external {
+        unchecked {
+            balances[msg.sender] -= amount;
+            balances[to] += amount;
+        }
+    }
+}
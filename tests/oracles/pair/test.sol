@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+// reserves stored in slot 8 like UniswapV2Pair
+contract main {
+    uint256[8] private slots;
+    uint112 private reserve0;
+    uint112 private reserve1;
+    uint32 private blockTimestampLast;
+
+    constructor() {
+        reserve0 = 2 ** 111;
+        reserve1 = 2 ** 111;
+    }
+
+    function getReserves() external view returns (uint112, uint112, uint32) {
+        return (reserve0, reserve1, blockTimestampLast);
+    }
+
+    // the constant product is not checked, the reserves can be taken out
+    // for free
+    function swap(uint256 amount0Out, uint256 amount1Out, address, bytes calldata) external {
+        reserve0 -= uint112(amount0Out);
+        reserve1 -= uint112(amount1Out);
+        blockTimestampLast = uint32(block.timestamp);
+    }
+
+    function skim(address) external {}
+
+    function sync() external {}
+}
@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract Token {
+    mapping(address => uint256) public balanceOf;
+
+    constructor() {
+        balanceOf[msg.sender] = 1e24;
+    }
+
+    function faucet() external {
+        balanceOf[msg.sender] += 1e21;
+    }
+
+    function transfer(address to, uint256 amount) external returns (bool) {
+        balanceOf[msg.sender] -= amount;
+        balanceOf[to] += amount;
+        return true;
+    }
+}
+
+contract main {
+    Token public token;
+    address public pool = address(0x1234);
+    mapping(address => bool) public claimed;
+
+    constructor() {
+        token = new Token();
+        token.transfer(pool, 1e21);
+    }
+
+    // the reward is priced by the spot balance of the pool, which anyone can
+    // inflate by transferring tokens to it
+    function claim() external {
+        require(!claimed[msg.sender], "claimed");
+        claimed[msg.sender] = true;
+        uint256 price = token.balanceOf(pool);
+        token.transfer(msg.sender, price / 1000);
+    }
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    mapping(address => uint256) public balances;
+
+    function deposit() external payable {
+        balances[msg.sender] += msg.value;
+    }
+
+    function withdraw() external {
+        uint256 amount = balances[msg.sender];
+        require(amount > 0, "no balance");
+        // the balance is cleared after the external call
+        (bool success, ) = msg.sender.call{value: amount}("");
+        require(success, "transfer failed");
+        balances[msg.sender] = 0;
+    }
+}
@@ -0,0 +1,17 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    uint256 private unlocked;
+
+    function unlock(uint256 key) external {
+        if (key == 0x1337) {
+            unlocked = 1;
+        }
+    }
+
+    function destruct() external {
+        require(unlocked == 1, "locked");
+        selfdestruct(payable(msg.sender));
+    }
+}
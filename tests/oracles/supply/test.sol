@@ -0,0 +1,24 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    uint256 public totalSupply;
+    mapping(address => uint256) public balanceOf;
+
+    function mint(uint256 amount) external {
+        totalSupply += amount;
+        balanceOf[msg.sender] += amount;
+    }
+
+    function burn(uint256 amount) external {
+        balanceOf[msg.sender] -= amount;
+        totalSupply -= amount;
+    }
+
+    // rewards are minted without going through mint()
+    function claimRewards(uint256 amount) external {
+        require(amount > 0 && amount < 1000, "invalid amount");
+        totalSupply += amount;
+        balanceOf[msg.sender] += amount;
+    }
+}
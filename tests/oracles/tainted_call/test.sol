@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    address public implementation;
+
+    function setImplementation(address impl) external {
+        implementation = impl;
+    }
+
+    // delegatecall to an implementation anyone can set
+    function upgradeAndCall(bytes calldata data) external {
+        (bool success, ) = implementation.delegatecall(data);
+        require(success, "delegatecall failed");
+    }
+}
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+contract main {
+    address public owner;
+    bool private initialized;
+
+    constructor() {
+        owner = address(0xdead);
+    }
+
+    // missing access control: anyone can re-initialize the owner
+    function initialize(address newOwner) external {
+        require(!initialized, "initialized");
+        owner = newOwner;
+    }
+
+    function lock() external {
+        require(msg.sender == owner, "not owner");
+        initialized = true;
+    }
+}
@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.15;
+
+import "../../../solidity_utils/lib.sol";
+
+contract main {
+    uint256 private step;
+
+    function a(uint256 x) external {
+        if (x == 0xdeadbeef) {
+            step = 1;
+        }
+    }
+
+    function b(uint256 y) external {
+        if (step == 1 && y > 1000 && y < 1010) {
+            typed_bug("0x1");
+        }
+    }
+}